@@ -72,4 +72,4 @@ pub use crate::bytes::{Bytes, BytesMut, BytesVec};
 pub use crate::string::ByteString;
 
 #[doc(hidden)]
-pub use crate::pool::{Pool, PoolId, PoolRef};
+pub use crate::pool::{CacheStats, Pool, PoolId, PoolRef};
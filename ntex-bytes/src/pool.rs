@@ -2,7 +2,8 @@
 use std::sync::atomic::Ordering::{Relaxed, Release};
 use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::task::{Context, Poll, Waker};
-use std::{cell::Cell, cell::RefCell, fmt, future::Future, mem, pin::Pin, ptr, rc::Rc};
+use std::{cell::Cell, cell::RefCell, cmp, fmt, future::Future, mem, pin::Pin};
+use std::{ptr, rc::Rc};
 
 use futures_core::task::__internal::AtomicWaker;
 
@@ -25,6 +26,23 @@ pub struct BufParams {
     pub low: u32,
 }
 
+/// Io buffers cache statistics
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of read buffers taken from cache
+    pub read_hits: usize,
+    /// Number of newly allocated read buffers
+    pub read_misses: usize,
+    /// Number of write buffers taken from cache
+    pub write_hits: usize,
+    /// Number of newly allocated write buffers
+    pub write_misses: usize,
+    /// Number of cached read buffers
+    pub read_cached: usize,
+    /// Number of cached write buffers
+    pub write_cached: usize,
+}
+
 bitflags::bitflags! {
     struct Flags: u8 {
         const SPAWNED    = 0b0000_0001;
@@ -50,9 +68,10 @@ struct MemoryPool {
 
     // io read/write cache and params
     read_wm: Cell<BufParams>,
-    read_cache: RefCell<Vec<BytesVec>>,
+    read_cache: RefCell<BufCache>,
     write_wm: Cell<BufParams>,
-    write_cache: RefCell<Vec<BytesVec>>,
+    write_cache: RefCell<BufCache>,
+    cache_size: Cell<usize>,
 
     spawn: RefCell<Option<Rc<dyn Fn(Pin<Box<dyn Future<Output = ()>>>)>>>,
 }
@@ -111,6 +130,13 @@ impl PoolId {
         self
     }
 
+    #[inline]
+    /// Set max number of cached io buffers
+    pub fn set_cache_size(self, size: usize) -> Self {
+        self.pool_ref().set_cache_size(size);
+        self
+    }
+
     /// Set future spawn fn
     pub fn set_spawn_fn<T>(self, f: T) -> Self
     where
@@ -140,6 +166,13 @@ impl PoolId {
             }
         });
     }
+
+    /// Release idle cached io buffers of all pools of current thread.
+    ///
+    /// Returns number of released buffers.
+    pub fn shrink_cache_all() -> usize {
+        POOLS.with(|pools| pools.iter().map(|pool| PoolRef(pool).shrink_cache()).sum())
+    }
 }
 
 thread_local! {
@@ -260,6 +293,11 @@ impl PoolRef {
     pub fn set_read_params(self, h: u32, l: u32) -> Self {
         assert!(l < h);
         self.0.read_wm.set(BufParams { high: h, low: l });
+        self.0.read_cache.borrow_mut().retain(
+            h as usize,
+            l as usize,
+            self.0.cache_size.get(),
+        );
         self
     }
 
@@ -280,6 +318,11 @@ impl PoolRef {
     pub fn set_write_params(self, h: u32, l: u32) -> Self {
         assert!(l < h);
         self.0.write_wm.set(BufParams { high: h, low: l });
+        self.0.write_cache.borrow_mut().retain(
+            h as usize,
+            l as usize,
+            self.0.cache_size.get(),
+        );
         self
     }
 
@@ -296,15 +339,14 @@ impl PoolRef {
     #[doc(hidden)]
     #[inline]
     /// Release read buffer, buf must be allocated from this pool
-    pub fn release_read_buf(self, mut buf: BytesVec) {
+    pub fn release_read_buf(self, buf: BytesVec) {
         let cap = buf.capacity();
         let (hw, lw) = self.0.read_wm.get().unpack();
         if cap > lw && cap <= hw {
-            let v = &mut self.0.read_cache.borrow_mut();
-            if v.len() < CACHE_SIZE {
-                buf.clear();
-                v.push(buf);
-            }
+            self.0
+                .read_cache
+                .borrow_mut()
+                .push(buf, hw, self.0.cache_size.get());
         }
     }
 
@@ -321,18 +363,53 @@ impl PoolRef {
     #[doc(hidden)]
     #[inline]
     /// Release write buffer, buf must be allocated from this pool
-    pub fn release_write_buf(self, mut buf: BytesVec) {
+    pub fn release_write_buf(self, buf: BytesVec) {
         let cap = buf.capacity();
         let (hw, lw) = self.0.write_wm.get().unpack();
         if cap > lw && cap <= hw {
-            let v = &mut self.0.write_cache.borrow_mut();
-            if v.len() < CACHE_SIZE {
-                buf.clear();
-                v.push(buf);
-            }
+            self.0
+                .write_cache
+                .borrow_mut()
+                .push(buf, hw, self.0.cache_size.get());
+        }
+    }
+
+    #[inline]
+    /// Set max number of cached io buffers.
+    ///
+    /// Read and write buffers are cached separately. By default
+    /// cache size is 16 buffers.
+    pub fn set_cache_size(self, size: usize) -> Self {
+        self.0.cache_size.set(size);
+        self.0.read_cache.borrow_mut().truncate(size);
+        self.0.write_cache.borrow_mut().truncate(size);
+        self
+    }
+
+    #[inline]
+    /// Get io buffers cache statistics.
+    pub fn cache_stats(self) -> CacheStats {
+        let read = self.0.read_cache.borrow();
+        let write = self.0.write_cache.borrow();
+        CacheStats {
+            read_hits: read.hits,
+            read_misses: read.misses,
+            write_hits: write.hits,
+            write_misses: write.misses,
+            read_cached: read.len(),
+            write_cached: write.len(),
         }
     }
 
+    /// Release cached io buffers that were not used since previous call.
+    ///
+    /// Should be called periodically, cache shrinks after load spikes.
+    /// Server workers call `PoolId::shrink_cache_all()` periodically.
+    /// Returns number of released buffers.
+    pub fn shrink_cache(self) -> usize {
+        self.0.read_cache.borrow_mut().shrink() + self.0.write_cache.borrow_mut().shrink()
+    }
+
     #[inline]
     pub(crate) fn acquire(self, size: usize) {
         let prev = self.0.size.fetch_add(size, Relaxed);
@@ -419,17 +496,120 @@ impl MemoryPool {
                 high: 4 * 1024,
                 low: 1024,
             }),
-            read_cache: RefCell::new(Vec::with_capacity(CACHE_SIZE)),
+            read_cache: RefCell::new(BufCache::new()),
             write_wm: Cell::new(BufParams {
                 high: 4 * 1024,
                 low: 1024,
             }),
-            write_cache: RefCell::new(Vec::with_capacity(CACHE_SIZE)),
+            write_cache: RefCell::new(BufCache::new()),
+            cache_size: Cell::new(CACHE_SIZE),
             spawn: RefCell::new(None),
         }))
     }
 }
 
+/// Number of io buffers cache size classes
+const CACHE_CLASSES: usize = 4;
+
+/// Cache of io buffers
+///
+/// Buffers are grouped by capacity. Class `n` keeps buffers with capacity
+/// in `(hw >> (n + 1), hw >> n]`, last class keeps all smaller buffers.
+/// Buffers with larger capacity are reused first.
+struct BufCache {
+    classes: [CacheClass; CACHE_CLASSES],
+    hits: usize,
+    misses: usize,
+}
+
+#[derive(Default)]
+struct CacheClass {
+    bufs: Vec<BytesVec>,
+    // min number of cached buffers since last shrink
+    idle: usize,
+}
+
+impl BufCache {
+    fn new() -> Self {
+        BufCache {
+            classes: Default::default(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Number of cached buffers
+    fn len(&self) -> usize {
+        self.classes.iter().map(|c| c.bufs.len()).sum()
+    }
+
+    fn pop(&mut self) -> Option<BytesVec> {
+        for class in self.classes.iter_mut() {
+            if let Some(buf) = class.bufs.pop() {
+                self.hits += 1;
+                class.idle = cmp::min(class.idle, class.bufs.len());
+                return Some(buf);
+            }
+        }
+        self.misses += 1;
+        for class in self.classes.iter_mut() {
+            class.idle = 0;
+        }
+        None
+    }
+
+    fn push(&mut self, mut buf: BytesVec, hw: usize, max: usize) {
+        if self.len() < max {
+            let cap = buf.capacity();
+            let mut idx = 0;
+            while idx < CACHE_CLASSES - 1 && cap <= hw >> (idx + 1) {
+                idx += 1;
+            }
+            buf.clear();
+            self.classes[idx].bufs.push(buf);
+        }
+    }
+
+    /// Re-classify buffers, remove buffers with capacity out of watermarks
+    fn retain(&mut self, hw: usize, lw: usize, max: usize) {
+        let mut bufs = Vec::new();
+        for class in self.classes.iter_mut() {
+            bufs.append(&mut class.bufs);
+            class.idle = 0;
+        }
+        for buf in bufs {
+            if buf.capacity() > lw && buf.capacity() <= hw {
+                self.push(buf, hw, max);
+            }
+        }
+    }
+
+    /// Remove buffers of smaller classes first
+    fn truncate(&mut self, size: usize) {
+        let mut len = self.len();
+        for class in self.classes.iter_mut().rev() {
+            if len <= size {
+                break;
+            }
+            let keep = class.bufs.len().saturating_sub(len - size);
+            len -= class.bufs.len() - keep;
+            class.bufs.truncate(keep);
+            class.idle = cmp::min(class.idle, keep);
+        }
+    }
+
+    /// Release buffers that were not used since last shrink
+    fn shrink(&mut self) -> usize {
+        let mut released = 0;
+        for class in self.classes.iter_mut() {
+            released += class.idle;
+            class.bufs.drain(..class.idle);
+            class.idle = class.bufs.len();
+        }
+        released
+    }
+}
+
 impl BufParams {
     #[inline]
     pub fn unpack(self) -> (usize, usize) {
@@ -1,6 +1,8 @@
 use std::{borrow::Borrow, borrow::BorrowMut, task::Poll};
 
-use ntex_bytes::{Buf, BufMut, Bytes, BytesMut, BytesVec, Pool, PoolId, PoolRef};
+use ntex_bytes::{
+    Buf, BufMut, Bytes, BytesMut, BytesVec, CacheStats, Pool, PoolId, PoolRef,
+};
 
 const LONG: &[u8] = b"mary had a little lamb, little lamb, little lamb";
 const SHORT: &[u8] = b"hello world";
@@ -1030,6 +1032,71 @@ fn pool() {
     assert_eq!(p3.allocated(), 2080 + shared_vec());
 }
 
+#[test]
+fn pool_cache() {
+    let p = PoolId::P4.pool_ref();
+    assert_eq!(p.cache_stats(), CacheStats::default());
+
+    let buf1 = p.get_read_buf();
+    let buf2 = p.get_read_buf();
+    let wbuf = p.get_write_buf();
+    p.release_read_buf(buf1);
+    p.release_read_buf(buf2);
+    p.release_write_buf(wbuf);
+
+    let stats = p.cache_stats();
+    assert_eq!(stats.read_misses, 2);
+    assert_eq!(stats.write_misses, 1);
+    assert_eq!(stats.read_cached, 2);
+    assert_eq!(stats.write_cached, 1);
+
+    let buf = p.get_read_buf();
+    assert_eq!(p.cache_stats().read_hits, 1);
+    p.release_read_buf(buf);
+
+    // first call marks cached buffers as idle
+    assert_eq!(p.shrink_cache(), 0);
+    let buf = p.get_read_buf();
+    assert_eq!(p.shrink_cache(), 2);
+    assert_eq!(p.cache_stats().read_cached, 0);
+    assert_eq!(p.cache_stats().write_cached, 0);
+
+    // cache size
+    p.set_cache_size(0);
+    p.release_read_buf(buf);
+    assert_eq!(p.cache_stats().read_cached, 0);
+}
+
+#[test]
+fn pool_cache_classes() {
+    let p = PoolId::P5.set_read_params(4096, 512).pool_ref();
+
+    // larger buffers are reused first
+    let small = p.vec_with_capacity(1024);
+    let small_cap = small.capacity();
+    let large = p.get_read_buf();
+    p.release_read_buf(large);
+    p.release_read_buf(small);
+    assert_eq!(p.cache_stats().read_cached, 2);
+    assert!(p.get_read_buf().capacity() > small_cap);
+    let buf = p.get_read_buf();
+    assert_eq!(buf.capacity(), small_cap);
+
+    // smaller buffers are removed first
+    p.release_read_buf(buf);
+    p.release_read_buf(p.vec_with_capacity(4096));
+    p.set_cache_size(1);
+    assert!(p.get_read_buf().capacity() > small_cap);
+    assert_eq!(p.cache_stats().read_cached, 0);
+
+    // idle buffers of all pools
+    p.set_cache_size(16);
+    p.release_read_buf(p.vec_with_capacity(4096));
+    assert_eq!(PoolId::shrink_cache_all(), 0);
+    assert_eq!(PoolId::shrink_cache_all(), 1);
+    assert_eq!(p.cache_stats().read_cached, 0);
+}
+
 #[ntex::test]
 async fn pool_usage() {
     use ntex::{time, util};
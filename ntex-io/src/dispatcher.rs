@@ -4,7 +4,7 @@ use std::{cell::Cell, future, pin::Pin, rc::Rc, task::Context, task::Poll, time}
 use ntex_bytes::Pool;
 use ntex_codec::{Decoder, Encoder};
 use ntex_service::{IntoService, Service};
-use ntex_util::time::Seconds;
+use ntex_util::time::{sleep, Millis, Seconds, Sleep};
 use ntex_util::{future::Either, ready, spawn};
 
 use crate::{DispatchItem, IoBoxed, IoRef, IoStatusUpdate, RecvError};
@@ -46,9 +46,17 @@ where
     error: Cell<Option<S::Error>>,
     flags: Cell<Flags>,
     shared: Rc<DispatcherShared<S, U>>,
+    heartbeat: Option<Heartbeat<U>>,
     pool: Pool,
 }
 
+/// Heartbeat ping
+struct Heartbeat<U: Encoder> {
+    interval: Millis,
+    sleep: Sleep,
+    ping: Box<dyn Fn() -> Response<U>>,
+}
+
 struct DispatcherShared<S, U>
 where
     S: Service<DispatchItem<U>, Response = Option<Response<U>>>,
@@ -117,6 +125,7 @@ where
                     error: Cell::new(None),
                     inflight: Cell::new(0),
                 }),
+                heartbeat: None,
                 io,
                 ka_timeout,
             },
@@ -150,6 +159,54 @@ where
         self.inner.io.set_disconnect_timeout(val.into());
         self
     }
+
+    /// Set write buffer high and low watermarks.
+    ///
+    /// If size of write buffer exceeds `high` watermark, dispatcher stops
+    /// reading incoming frames and sends `WBackPressureEnabled` to the service.
+    /// Processing resumes when size of write buffer drops below `low` watermark.
+    ///
+    /// By default both watermarks are set to double of memory pool's write high watermark.
+    pub fn write_watermarks(self, high: usize, low: usize) -> Self {
+        self.inner.io.set_write_watermarks(high, low);
+        self
+    }
+
+    /// Set max size of write buffer.
+    ///
+    /// If size of write buffer exceeds this value, connection get dropped
+    /// and service receives `Disconnect` item.
+    ///
+    /// By default size of write buffer is not limited.
+    pub fn max_write_buffer(self, size: usize) -> Self {
+        self.inner.io.set_max_write_buffer(size);
+        self
+    }
+
+    /// Send heartbeat pings.
+    ///
+    /// Dispatcher writes item produced by `ping` to io stream every `interval`.
+    /// Pong responses are regular frames and get passed to the service,
+    /// each incoming frame refreshes keep-alive timer, so dead peers get
+    /// disconnected with `KeepAliveTimeout` item.
+    ///
+    /// By default heartbeat is disabled.
+    pub fn heartbeat<F>(mut self, interval: Seconds, ping: F) -> Self
+    where
+        F: Fn() -> Response<U> + 'static,
+    {
+        self.inner.heartbeat = if interval.is_zero() {
+            None
+        } else {
+            let interval = interval.into();
+            Some(Heartbeat {
+                interval,
+                sleep: sleep(interval),
+                ping: Box::new(ping),
+            })
+        };
+        self
+    }
 }
 
 impl<S, U> DispatcherShared<S, U>
@@ -203,6 +260,9 @@ where
             return Poll::Pending;
         }
 
+        // send heartbeat ping
+        slf.poll_heartbeat(cx, ioref);
+
         loop {
             match slf.st.get() {
                 DispatcherState::Processing => {
@@ -422,6 +482,23 @@ where
         }
     }
 
+    fn poll_heartbeat(&self, cx: &mut Context<'_>, io: &IoRef) {
+        if let Some(ref hb) = self.heartbeat {
+            match self.st.get() {
+                DispatcherState::Processing | DispatcherState::Backpressure => {
+                    while hb.sleep.poll_elapsed(cx).is_ready() {
+                        log::trace!("send heartbeat ping");
+                        hb.sleep.reset(hb.interval);
+                        if let Err(err) = io.encode((hb.ping)(), &self.shared.codec) {
+                            self.shared.error.set(Some(DispatcherError::Encoder(err)));
+                        }
+                    }
+                }
+                DispatcherState::Stop | DispatcherState::Shutdown => (),
+            }
+        }
+    }
+
     fn insert_flags(&self, f: Flags) {
         let mut flags = self.flags.get();
         flags.insert(f);
@@ -508,6 +585,7 @@ mod tests {
                         st: Cell::new(DispatcherState::Processing),
                         pool: state.memory_pool().pool(),
                         io: state.into(),
+                        heartbeat: None,
                         shared,
                         ka_timeout,
                     },
@@ -752,6 +830,105 @@ mod tests {
         assert_eq!(&data.lock().unwrap().borrow()[..], &[0, 1, 2]);
     }
 
+    #[ntex::test]
+    async fn test_write_watermarks() {
+        let (client, server) = IoTest::create();
+        // do not allow to write to socket
+        client.remote_buffer_cap(0);
+
+        let data = Arc::new(Mutex::new(RefCell::new(Vec::new())));
+        let data2 = data.clone();
+
+        let (disp, state) = Dispatcher::debug(
+            server,
+            BytesCodec,
+            ntex_service::fn_service(move |msg: DispatchItem<BytesCodec>| {
+                let data = data2.clone();
+                async move {
+                    match msg {
+                        DispatchItem::Item(_) => {
+                            data.lock().unwrap().borrow_mut().push(0);
+                            return Ok::<_, ()>(Some(Bytes::from(vec![b'x'; 40_960])));
+                        }
+                        DispatchItem::WBackPressureEnabled => {
+                            data.lock().unwrap().borrow_mut().push(1);
+                        }
+                        DispatchItem::WBackPressureDisabled => {
+                            data.lock().unwrap().borrow_mut().push(2);
+                        }
+                        _ => (),
+                    }
+                    Ok(None)
+                }
+            }),
+        );
+        let disp = disp.write_watermarks(40_960, 8192);
+        spawn(async move {
+            let _ = disp.await;
+        });
+
+        client.write("GET /test HTTP/1\r\n\r\n");
+        sleep(Millis(25)).await;
+        assert_eq!(state.io().with_write_buf(|buf| buf.len()).unwrap(), 40_960);
+        assert_eq!(&data.lock().unwrap().borrow()[..], &[0, 1]);
+
+        // write buffer is above low watermark
+        client.remote_buffer_cap(20_480);
+        sleep(Millis(50)).await;
+        assert_eq!(state.io().with_write_buf(|buf| buf.len()).unwrap(), 20_480);
+        assert_eq!(&data.lock().unwrap().borrow()[..], &[0, 1]);
+
+        // back-pressure disabled
+        client.remote_buffer_cap(16_384);
+        sleep(Millis(50)).await;
+        assert_eq!(state.io().with_write_buf(|buf| buf.len()).unwrap(), 4096);
+        assert_eq!(&data.lock().unwrap().borrow()[..], &[0, 1, 2]);
+    }
+
+    #[ntex::test]
+    async fn test_max_write_buffer() {
+        let (client, server) = IoTest::create();
+        // do not allow to write to socket
+        client.remote_buffer_cap(0);
+
+        let data = Arc::new(Mutex::new(RefCell::new(Vec::new())));
+        let data2 = data.clone();
+
+        let (disp, state) = Dispatcher::debug(
+            server,
+            BytesCodec,
+            ntex_service::fn_service(move |msg: DispatchItem<BytesCodec>| {
+                let data = data2.clone();
+                async move {
+                    match msg {
+                        DispatchItem::Item(_) => {
+                            data.lock().unwrap().borrow_mut().push(0);
+                            return Ok::<_, ()>(Some(Bytes::from(vec![b'x'; 40_960])));
+                        }
+                        DispatchItem::Disconnect(err) => {
+                            assert!(err.is_some());
+                            data.lock().unwrap().borrow_mut().push(1);
+                        }
+                        _ => (),
+                    }
+                    Ok(None)
+                }
+            }),
+        );
+        let disp = disp
+            .write_watermarks(65_536, 65_536)
+            .max_write_buffer(30_720);
+        spawn(async move {
+            let _ = disp.await;
+        });
+
+        assert!(!state.flags().contains(Flags::IO_STOPPED));
+        client.write("GET /test HTTP/1\r\n\r\n");
+        sleep(Millis(25)).await;
+        assert!(state.flags().contains(Flags::IO_STOPPED));
+        assert_eq!(&data.lock().unwrap().borrow()[..], &[0, 1]);
+    }
+
     #[ntex::test]
     async fn test_keepalive() {
         let (client, server) = IoTest::create();
@@ -800,6 +977,53 @@ mod tests {
         assert_eq!(&data.lock().unwrap().borrow()[..], &[0, 1]);
     }
 
+    #[ntex::test]
+    async fn test_heartbeat() {
+        let (client, server) = IoTest::create();
+        client.remote_buffer_cap(1024);
+
+        let data = Arc::new(Mutex::new(RefCell::new(Vec::new())));
+        let data2 = data.clone();
+
+        let (disp, state) = Dispatcher::debug(
+            server,
+            BytesCodec,
+            ntex_service::fn_service(move |msg: DispatchItem<BytesCodec>| {
+                let data = data2.clone();
+                async move {
+                    match msg {
+                        DispatchItem::Item(_) => data.lock().unwrap().borrow_mut().push(0),
+                        DispatchItem::KeepAliveTimeout => {
+                            data.lock().unwrap().borrow_mut().push(1)
+                        }
+                        _ => (),
+                    }
+                    Ok::<_, ()>(None)
+                }
+            }),
+        );
+        spawn(async move {
+            let _ = disp
+                .keepalive_timeout(Seconds(2))
+                .heartbeat(Seconds(1), || Bytes::from_static(b"PING"))
+                .await;
+        });
+
+        let buf = client.read().await.unwrap();
+        assert_eq!(buf, Bytes::from_static(b"PING"));
+
+        // pong
+        client.write("PONG");
+        let buf = client.read().await.unwrap();
+        assert_eq!(buf, Bytes::from_static(b"PING"));
+        assert_eq!(&data.lock().unwrap().borrow()[..], &[0]);
+
+        // peer does not respond
+        sleep(Millis(2500)).await;
+        assert!(state.flags().contains(Flags::IO_STOPPING));
+        assert_eq!(&data.lock().unwrap().borrow()[..], &[0, 1]);
+    }
+
     #[ntex::test]
     async fn test_unhandled_data() {
         let handled = Arc::new(AtomicBool::new(false));
@@ -96,11 +96,27 @@ impl Filter for Base {
         if buf.is_empty() {
             pool.release_write_buf(buf);
         } else {
-            if buf.len() >= pool.write_params_high() {
-                self.0 .0.insert_flags(Flags::WR_BACKPRESSURE);
+            let st = &self.0 .0;
+            let len = buf.len();
+            let high = st
+                .write_watermarks
+                .get()
+                .map(|(high, _)| high)
+                .unwrap_or_else(|| pool.write_params_high());
+            if len >= high {
+                st.insert_flags(Flags::WR_BACKPRESSURE);
+            }
+            st.write_buf.set(Some(buf));
+            st.write_task.wake();
+
+            // write buffer is too large, drop connection
+            if st.write_max.get().map(|max| len > max).unwrap_or(false) {
+                log::trace!("write buffer size exceeds limit, force close io stream");
+                st.io_stopped(Some(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Write buffer size exceeds limit",
+                )));
             }
-            self.0 .0.write_buf.set(Some(buf));
-            self.0 .0.write_task.wake();
         }
         Ok(())
     }
@@ -1,7 +1,8 @@
 use std::cell::Cell;
 use std::task::{Context, Poll};
 use std::{
-    fmt, future::Future, hash, io, marker, mem, ops::Deref, pin::Pin, ptr, rc::Rc, time,
+    cmp, fmt, future::Future, hash, io, marker, mem, ops::Deref, pin::Pin, ptr, rc::Rc,
+    time,
 };
 
 use ntex_bytes::{BytesVec, PoolId, PoolRef};
@@ -58,6 +59,8 @@ pub(crate) struct IoState {
     pub(super) flags: Cell<Flags>,
     pub(super) pool: Cell<PoolRef>,
     pub(super) disconnect_timeout: Cell<Millis>,
+    pub(super) write_watermarks: Cell<Option<(usize, usize)>>,
+    pub(super) write_max: Cell<Option<usize>>,
     pub(super) error: Cell<Option<io::Error>>,
     pub(super) read_task: LocalWaker,
     pub(super) write_task: LocalWaker,
@@ -86,6 +89,18 @@ impl IoState {
         self.flags.set(flags);
     }
 
+    #[inline]
+    /// Write buffer high and low watermarks
+    ///
+    /// By default both watermarks are set to double of memory pool's
+    /// write high watermark.
+    pub(super) fn write_watermarks(&self) -> (usize, usize) {
+        self.write_watermarks.get().unwrap_or_else(|| {
+            let hw = self.pool.get().write_params_high() << 1;
+            (hw, hw)
+        })
+    }
+
     #[inline]
     pub(super) fn notify_keepalive(&self) {
         log::trace!("keep-alive timeout, notify dispatcher");
@@ -248,6 +263,8 @@ impl Io {
             flags: Cell::new(Flags::empty()),
             error: Cell::new(None),
             disconnect_timeout: Cell::new(Millis::ONE_SEC),
+            write_watermarks: Cell::new(None),
+            write_max: Cell::new(None),
             dispatch_task: LocalWaker::new(),
             read_task: LocalWaker::new(),
             write_task: LocalWaker::new(),
@@ -297,6 +314,26 @@ impl<F> Io<F> {
         self.0 .0.disconnect_timeout.set(timeout);
     }
 
+    #[inline]
+    /// Set write buffer high and low watermarks
+    ///
+    /// Write back-pressure get enabled if size of write buffer exceeds
+    /// `high` watermark and get disabled when size drops below `low` watermark.
+    pub fn set_write_watermarks(&self, high: usize, low: usize) {
+        self.0
+             .0
+            .write_watermarks
+            .set(Some((high, cmp::min(high, low))));
+    }
+
+    #[inline]
+    /// Set max size of write buffer
+    ///
+    /// Io stream get force closed if size of write buffer exceeds this value.
+    pub fn set_max_write_buffer(&self, size: usize) {
+        self.0 .0.write_max.set(Some(size));
+    }
+
     #[inline]
     /// Clone current io object.
     ///
@@ -312,6 +349,8 @@ impl<F> Io<F> {
             ),
             error: Cell::new(None),
             disconnect_timeout: Cell::new(Millis::ONE_SEC),
+            write_watermarks: Cell::new(None),
+            write_max: Cell::new(None),
             dispatch_task: LocalWaker::new(),
             read_task: LocalWaker::new(),
             write_task: LocalWaker::new(),
@@ -616,11 +655,14 @@ impl<F> Io<F> {
                 .with_write_buf(|buf| buf.as_ref().map(|b| b.len()).unwrap_or(0));
 
             if len > 0 {
+                let (high, low) = self.0 .0.write_watermarks();
                 if full {
                     self.0 .0.insert_flags(Flags::WR_WAIT);
                     self.0 .0.dispatch_task.register(cx.waker());
                     return Poll::Pending;
-                } else if len >= self.0.memory_pool().write_params_high() << 1 {
+                } else if len >= high
+                    || (flags.contains(Flags::WR_BACKPRESSURE) && len >= low)
+                {
                     self.0 .0.insert_flags(Flags::WR_BACKPRESSURE);
                     self.0 .0.dispatch_task.register(cx.waker());
                     return Poll::Pending;
@@ -12,6 +12,7 @@ mod filter;
 mod framed;
 mod io;
 mod ioref;
+mod proxy;
 mod seal;
 mod tasks;
 mod timer;
@@ -25,9 +26,11 @@ pub use self::dispatcher::Dispatcher;
 pub use self::filter::Base;
 pub use self::framed::Framed;
 pub use self::io::{Io, IoRef, OnDisconnect};
+pub use self::proxy::{ProxyError, ProxyFilter, ProxyProtocol, ProxyProtocolService};
 pub use self::seal::{IoBoxed, Sealed};
 pub use self::tasks::{ReadContext, WriteContext};
-pub use self::utils::{filter, seal};
+pub use self::testing::duplex;
+pub use self::utils::{filter, seal, select, SelectError, Selected};
 
 /// Status for read task
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
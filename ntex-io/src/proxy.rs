@@ -0,0 +1,492 @@
+//! HAProxy PROXY protocol support
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::{any, error, fmt, future::Future, io, marker::PhantomData, pin::Pin};
+use std::{task::Context, task::Poll};
+
+use ntex_bytes::{Buf, BytesVec};
+use ntex_service::{Service, ServiceFactory};
+use ntex_util::{future::Ready, time::timeout_checked, time::Millis};
+
+use crate::types::{PeerAddr, ProxyInfo};
+use crate::{Filter, Io, IoRef, ReadStatus, WriteStatus};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_SIZE: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_SIZE: usize = 16;
+
+/// PROXY protocol error
+#[derive(Debug)]
+pub enum ProxyError {
+    /// Malformed PROXY protocol header
+    Invalid,
+    /// Header is not received within handshake timeout
+    Timeout,
+    /// Peer is disconnected before header get received
+    PeerGone(Option<io::Error>),
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::Invalid => write!(f, "Malformed PROXY protocol header"),
+            ProxyError::Timeout => write!(f, "PROXY protocol header timeout"),
+            ProxyError::PeerGone(Some(err)) => write!(f, "Peer is disconnected: {}", err),
+            ProxyError::PeerGone(None) => write!(f, "Peer is disconnected"),
+        }
+    }
+}
+
+impl error::Error for ProxyError {}
+
+/// PROXY protocol acceptor
+///
+/// Acceptor reads PROXY protocol header (versions 1 and 2) from accepted
+/// io stream. Original client address is available via `PeerAddr` and
+/// `ProxyInfo` queries, so `peer_addr()` of http requests returns address
+/// of the client instead of the address of the proxy. Data received after
+/// header is kept in read buffer.
+///
+/// Acceptor must be used only for listeners that receive connections
+/// from trusted proxies, i.e. it could be configured for specific listener
+/// in server's `ServiceConfig` and chained with tls acceptor or http service.
+pub struct ProxyProtocol<F> {
+    timeout: Millis,
+    _t: PhantomData<F>,
+}
+
+impl<F> ProxyProtocol<F> {
+    /// Create PROXY protocol acceptor
+    pub fn new() -> Self {
+        ProxyProtocol {
+            timeout: Millis(5_000),
+            _t: PhantomData,
+        }
+    }
+
+    /// Set header receive timeout.
+    ///
+    /// Default is set to 5 seconds. Zero value disables timeout.
+    pub fn timeout<U: Into<Millis>>(mut self, timeout: U) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+}
+
+impl<F> Default for ProxyProtocol<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> Clone for ProxyProtocol<F> {
+    fn clone(&self) -> Self {
+        Self {
+            timeout: self.timeout,
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<F: Filter, C> ServiceFactory<Io<F>, C> for ProxyProtocol<F> {
+    type Response = Io<ProxyFilter<F>>;
+    type Error = ProxyError;
+    type Service = ProxyProtocolService<F>;
+    type InitError = ();
+    type Future = Ready<Self::Service, Self::InitError>;
+
+    #[inline]
+    fn new_service(&self, _: C) -> Self::Future {
+        Ready::Ok(ProxyProtocolService {
+            timeout: self.timeout,
+            _t: PhantomData,
+        })
+    }
+}
+
+/// PROXY protocol acceptor service
+pub struct ProxyProtocolService<F> {
+    timeout: Millis,
+    _t: PhantomData<F>,
+}
+
+impl<F: Filter> Service<Io<F>> for ProxyProtocolService<F> {
+    type Response = Io<ProxyFilter<F>>;
+    type Error = ProxyError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&self, io: Io<F>) -> Self::Future {
+        let timeout = self.timeout;
+
+        Box::pin(async move {
+            let fut = async {
+                loop {
+                    let res = io.with_read_buf(|buf| {
+                        parse(buf).map(|res| {
+                            res.map(|(size, info)| {
+                                buf.advance(size);
+                                info
+                            })
+                        })
+                    })?;
+                    if let Some(info) = res {
+                        return Ok(info);
+                    }
+
+                    match io.read_ready().await {
+                        Ok(Some(())) => continue,
+                        Ok(None) => return Err(ProxyError::PeerGone(None)),
+                        Err(err) => return Err(ProxyError::PeerGone(Some(err))),
+                    }
+                }
+            };
+            let res = timeout_checked(timeout, fut).await;
+
+            match res {
+                Ok(Ok(info)) => io.map_filter(|inner| Ok(ProxyFilter { inner, info })),
+                Ok(Err(err)) => Err(err),
+                Err(_) => {
+                    log::trace!("PROXY protocol header timeout");
+                    Err(ProxyError::Timeout)
+                }
+            }
+        })
+    }
+}
+
+/// Io filter for streams with received PROXY protocol header
+///
+/// Filter overrides `PeerAddr` query with original client address.
+/// For `LOCAL` connections (i.e. proxy health checks) addresses are
+/// not available and queries are handled by inner filter.
+pub struct ProxyFilter<F> {
+    inner: F,
+    info: Option<ProxyInfo>,
+}
+
+impl<F> ProxyFilter<F> {
+    /// Addresses received with PROXY protocol header
+    pub fn info(&self) -> Option<&ProxyInfo> {
+        self.info.as_ref()
+    }
+}
+
+impl<F: Filter> Filter for ProxyFilter<F> {
+    #[inline]
+    fn query(&self, id: any::TypeId) -> Option<Box<dyn any::Any>> {
+        if let Some(ref info) = self.info {
+            if id == any::TypeId::of::<PeerAddr>() {
+                return Some(Box::new(PeerAddr(info.source)));
+            } else if id == any::TypeId::of::<ProxyInfo>() {
+                return Some(Box::new(*info));
+            }
+        }
+        self.inner.query(id)
+    }
+
+    #[inline]
+    fn get_read_buf(&self) -> Option<BytesVec> {
+        self.inner.get_read_buf()
+    }
+
+    #[inline]
+    fn release_read_buf(&self, buf: BytesVec) {
+        self.inner.release_read_buf(buf)
+    }
+
+    #[inline]
+    fn process_read_buf(&self, io: &IoRef, n: usize) -> io::Result<(usize, usize)> {
+        self.inner.process_read_buf(io, n)
+    }
+
+    #[inline]
+    fn get_write_buf(&self) -> Option<BytesVec> {
+        self.inner.get_write_buf()
+    }
+
+    #[inline]
+    fn release_write_buf(&self, buf: BytesVec) -> io::Result<()> {
+        self.inner.release_write_buf(buf)
+    }
+
+    #[inline]
+    fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<ReadStatus> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    #[inline]
+    fn poll_write_ready(&self, cx: &mut Context<'_>) -> Poll<WriteStatus> {
+        self.inner.poll_write_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self) -> Poll<io::Result<()>> {
+        self.inner.poll_shutdown()
+    }
+}
+
+/// Parse PROXY protocol header
+///
+/// Returns header size and addresses, `None` if more data is required.
+fn parse(buf: &[u8]) -> Result<Option<(usize, Option<ProxyInfo>)>, ProxyError> {
+    let n = std::cmp::min(buf.len(), V2_SIGNATURE.len());
+    if buf[..n] == V2_SIGNATURE[..n] {
+        return if n < V2_SIGNATURE.len() {
+            Ok(None)
+        } else {
+            parse_v2(buf)
+        };
+    }
+
+    let n = std::cmp::min(buf.len(), V1_PREFIX.len());
+    if buf[..n] == V1_PREFIX[..n] {
+        if n < V1_PREFIX.len() {
+            Ok(None)
+        } else {
+            parse_v1(buf)
+        }
+    } else {
+        Err(ProxyError::Invalid)
+    }
+}
+
+/// Parse text header, i.e. `PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n`
+fn parse_v1(buf: &[u8]) -> Result<Option<(usize, Option<ProxyInfo>)>, ProxyError> {
+    let end = match buf.iter().take(V1_MAX_SIZE).position(|b| *b == b'\n') {
+        Some(pos) => pos,
+        None if buf.len() >= V1_MAX_SIZE => return Err(ProxyError::Invalid),
+        None => return Ok(None),
+    };
+    if buf[end - 1] != b'\r' {
+        return Err(ProxyError::Invalid);
+    }
+
+    let line = std::str::from_utf8(&buf[V1_PREFIX.len()..end - 1])
+        .map_err(|_| ProxyError::Invalid)?;
+    let mut parts = line.split(' ');
+    let info = match parts.next() {
+        Some("UNKNOWN") => None,
+        Some(proto) if proto == "TCP4" || proto == "TCP6" => {
+            let src: IpAddr = field(&mut parts)?;
+            let dst: IpAddr = field(&mut parts)?;
+            let src_port: u16 = field(&mut parts)?;
+            let dst_port: u16 = field(&mut parts)?;
+            if parts.next().is_some()
+                || src.is_ipv4() != dst.is_ipv4()
+                || src.is_ipv4() != (proto == "TCP4")
+            {
+                return Err(ProxyError::Invalid);
+            }
+            Some(ProxyInfo {
+                source: SocketAddr::new(src, src_port),
+                destination: SocketAddr::new(dst, dst_port),
+            })
+        }
+        _ => return Err(ProxyError::Invalid),
+    };
+    Ok(Some((end + 1, info)))
+}
+
+fn field<'a, T, I>(parts: &mut I) -> Result<T, ProxyError>
+where
+    T: std::str::FromStr,
+    I: Iterator<Item = &'a str>,
+{
+    parts
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or(ProxyError::Invalid)
+}
+
+/// Parse binary header
+fn parse_v2(buf: &[u8]) -> Result<Option<(usize, Option<ProxyInfo>)>, ProxyError> {
+    if buf.len() < V2_HEADER_SIZE {
+        return Ok(None);
+    }
+    if buf[12] >> 4 != 2 {
+        return Err(ProxyError::Invalid);
+    }
+    let size = V2_HEADER_SIZE + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if buf.len() < size {
+        return Ok(None);
+    }
+
+    let addrs = &buf[V2_HEADER_SIZE..size];
+    let port = |pos: usize| u16::from_be_bytes([addrs[pos], addrs[pos + 1]]);
+    let info = match buf[12] & 0x0f {
+        // LOCAL command
+        0x00 => None,
+        // PROXY command
+        0x01 => match buf[13] >> 4 {
+            // AF_INET
+            0x1 if addrs.len() >= 12 => {
+                let src: [u8; 4] = addrs[0..4].try_into().unwrap();
+                let dst: [u8; 4] = addrs[4..8].try_into().unwrap();
+                Some(ProxyInfo {
+                    source: SocketAddr::new(Ipv4Addr::from(src).into(), port(8)),
+                    destination: SocketAddr::new(Ipv4Addr::from(dst).into(), port(10)),
+                })
+            }
+            // AF_INET6
+            0x2 if addrs.len() >= 36 => {
+                let src: [u8; 16] = addrs[0..16].try_into().unwrap();
+                let dst: [u8; 16] = addrs[16..32].try_into().unwrap();
+                Some(ProxyInfo {
+                    source: SocketAddr::new(Ipv6Addr::from(src).into(), port(32)),
+                    destination: SocketAddr::new(Ipv6Addr::from(dst).into(), port(34)),
+                })
+            }
+            // AF_UNSPEC and AF_UNIX
+            0x0 | 0x3 => None,
+            _ => return Err(ProxyError::Invalid),
+        },
+        _ => return Err(ProxyError::Invalid),
+    };
+    Ok(Some((size, info)))
+}
+
+#[cfg(test)]
+mod tests {
+    use ntex_bytes::Bytes;
+    use ntex_codec::BytesCodec;
+    use ntex_util::time::{sleep, Millis};
+
+    use super::*;
+    use crate::testing::IoTest;
+
+    #[test]
+    fn test_parse_v1() {
+        assert!(parse(b"").unwrap().is_none());
+        assert!(parse(b"PRO").unwrap().is_none());
+        assert!(parse(b"PROXY TCP4 192.168.0.1").unwrap().is_none());
+        assert!(parse(b"GET / HTTP/1.1\r\n").is_err());
+
+        let (size, info) = parse(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET")
+            .unwrap()
+            .unwrap();
+        assert_eq!(size, 47);
+        let info = info.unwrap();
+        assert_eq!(info.source, "192.168.0.1:56324".parse().unwrap());
+        assert_eq!(info.destination, "192.168.0.11:443".parse().unwrap());
+
+        let (_, info) = parse(b"PROXY TCP6 ::1 ::2 1000 443\r\n").unwrap().unwrap();
+        assert_eq!(info.unwrap().source, "[::1]:1000".parse().unwrap());
+
+        let (size, info) = parse(b"PROXY UNKNOWN\r\n").unwrap().unwrap();
+        assert_eq!(size, 15);
+        assert!(info.is_none());
+
+        assert!(parse(b"PROXY TCP4 ::1 ::2 1000 443\r\n").is_err());
+        assert!(parse(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324\r\n").is_err());
+        assert!(parse(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\n").is_err());
+        assert!(parse(b"PROXY UDP4 192.168.0.1 192.168.0.11 56324 443\r\n").is_err());
+        assert!(parse(&[b'P'; 200][..]).is_err());
+        assert!(parse(&[&b"PROXY "[..], &[b' '; 200][..]].concat()).is_err());
+    }
+
+    fn v2_header(cmd: u8, family: u8, addrs: &[u8]) -> Vec<u8> {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.push(0x20 | cmd);
+        buf.push(family);
+        buf.extend_from_slice(&(addrs.len() as u16).to_be_bytes());
+        buf.extend_from_slice(addrs);
+        buf
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let buf = v2_header(
+            0x01,
+            0x11,
+            &[192, 168, 0, 1, 192, 168, 0, 11, 0xdc, 0x04, 0x01, 0xbb],
+        );
+        assert!(parse(&buf[..10]).unwrap().is_none());
+        assert!(parse(&buf[..20]).unwrap().is_none());
+
+        let (size, info) = parse(&buf).unwrap().unwrap();
+        assert_eq!(size, 28);
+        let info = info.unwrap();
+        assert_eq!(info.source, "192.168.0.1:56324".parse().unwrap());
+        assert_eq!(info.destination, "192.168.0.11:443".parse().unwrap());
+
+        let mut addrs = [0; 36];
+        addrs[15] = 1;
+        addrs[31] = 2;
+        addrs[32..34].copy_from_slice(&1000u16.to_be_bytes());
+        addrs[34..36].copy_from_slice(&443u16.to_be_bytes());
+        let (_, info) = parse(&v2_header(0x01, 0x21, &addrs)).unwrap().unwrap();
+        let info = info.unwrap();
+        assert_eq!(info.source, "[::1]:1000".parse().unwrap());
+        assert_eq!(info.destination, "[::2]:443".parse().unwrap());
+
+        // tlvs are skipped
+        let (size, info) = parse(&v2_header(0x00, 0x00, &[0x01, 0x00, 0x01, b'h']))
+            .unwrap()
+            .unwrap();
+        assert_eq!(size, 20);
+        assert!(info.is_none());
+
+        assert!(parse(&v2_header(0x02, 0x11, &[0; 12])).is_err());
+        assert!(parse(&v2_header(0x01, 0x11, &[0; 4])).is_err());
+        let mut buf = v2_header(0x01, 0x11, &[0; 12]);
+        buf[12] = 0x11;
+        assert!(parse(&buf).is_err());
+    }
+
+    #[ntex::test]
+    async fn test_proxy_protocol() {
+        let factory = ProxyProtocol::new().timeout(Millis(100));
+        let srv = factory.new_service(()).await.unwrap();
+
+        let (client, server) = IoTest::create();
+        client.write("PROXY TCP4 192.168.0.1 192.168.0.11 ");
+        ntex_util::spawn(async move {
+            sleep(Millis(25)).await;
+            client.write("56324 443\r\nGET");
+            sleep(Millis(250)).await;
+            drop(client);
+        });
+        let io = srv.call(Io::new(server)).await.unwrap();
+        assert_eq!(
+            io.query::<PeerAddr>().get(),
+            Some(PeerAddr("192.168.0.1:56324".parse().unwrap()))
+        );
+        assert_eq!(
+            io.query::<ProxyInfo>().get().unwrap().destination,
+            "192.168.0.11:443".parse().unwrap()
+        );
+        assert!(io.filter().info().is_some());
+        assert_eq!(
+            io.recv(&BytesCodec).await.unwrap().unwrap(),
+            Bytes::from_static(b"GET")
+        );
+
+        // local connection
+        let (client, server) = IoTest::create();
+        client.write(v2_header(0x00, 0x00, &[]));
+        let io = srv.call(Io::new(server)).await.unwrap();
+        assert!(io.query::<ProxyInfo>().get().is_none());
+
+        let (client, server) = IoTest::create();
+        client.write("GET / HTTP/1.1\r\n");
+        let res = srv.call(Io::new(server)).await;
+        assert!(matches!(res, Err(ProxyError::Invalid)));
+        assert!(format!("{}", res.err().unwrap()).contains("Malformed"));
+
+        let (_client, server) = IoTest::create();
+        let res = srv.call(Io::new(server)).await;
+        assert!(matches!(res, Err(ProxyError::Timeout)));
+
+        let (client, server) = IoTest::create();
+        client.write("PROXY");
+        client.close().await;
+        let res = srv.call(Io::new(server)).await;
+        assert!(matches!(res, Err(ProxyError::PeerGone(None))));
+    }
+}
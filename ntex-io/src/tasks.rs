@@ -117,9 +117,9 @@ impl WriteContext {
                 self.0 .0.dispatch_task.wake();
             }
         } else {
-            // if write buffer is smaller than high watermark value, turn off back-pressure
+            // if write buffer is smaller than low watermark value, turn off back-pressure
             if flags.contains(Flags::WR_BACKPRESSURE)
-                && buf.len() < pool.write_params_high() << 1
+                && buf.len() < self.0 .0.write_watermarks().1
             {
                 flags.remove(Flags::WR_BACKPRESSURE);
                 self.0.set_flags(flags);
@@ -8,7 +8,20 @@ use ntex_bytes::{Buf, BufMut, Bytes, BytesVec};
 use ntex_util::future::poll_fn;
 use ntex_util::time::{sleep, Millis, Sleep};
 
-use crate::{types, Handle, IoStream, ReadContext, ReadStatus, WriteContext, WriteStatus};
+use crate::WriteStatus;
+use crate::{types, Handle, Io, IoStream, ReadContext, ReadStatus, WriteContext};
+
+/// Create memory-backed duplex stream
+///
+/// Returns client side stream and server side `Io` object. Client side
+/// stream writes data to and reads data from server `Io` object. `cap` is
+/// the number of bytes server `Io` object could write to the client side
+/// stream, it could be changed with `IoTest::remote_buffer_cap()` method.
+pub fn duplex(cap: usize) -> (IoTest, Io) {
+    let (client, server) = IoTest::create();
+    client.remote_buffer_cap(cap);
+    (client, Io::new(server))
+}
 
 #[derive(Default)]
 struct AtomicWaker(Arc<Mutex<RefCell<Option<Waker>>>>);
@@ -64,6 +77,7 @@ struct Channel {
     buf_cap: usize,
     flags: IoTestFlags,
     waker: AtomicWaker,
+    write_waker: AtomicWaker,
     read: IoTestState,
     write: IoTestState,
 }
@@ -119,10 +133,12 @@ impl IoTest {
         )
     }
 
+    /// Check if client side stream is dropped
     pub fn is_client_dropped(&self) -> bool {
         self.state.get().client_dropped
     }
 
+    /// Check if server side stream is dropped
     pub fn is_server_dropped(&self) -> bool {
         self.state.get().server_dropped
     }
@@ -153,7 +169,10 @@ impl IoTest {
     /// Set write error on remote side
     pub fn write_error(&self, err: io::Error) {
         self.local.lock().unwrap().borrow_mut().write = IoTestState::Err(err);
-        self.remote.lock().unwrap().borrow().waker.wake();
+        let guard = self.remote.lock().unwrap();
+        let remote = guard.borrow();
+        remote.waker.wake();
+        remote.write_waker.wake();
     }
 
     /// Access read buffer.
@@ -196,12 +215,15 @@ impl IoTest {
         write.waker.wake();
     }
 
-    /// Read any available data
+    /// Set number of bytes remote side could write to the stream
     pub fn remote_buffer_cap(&self, cap: usize) {
         // change cap
         self.local.lock().unwrap().borrow_mut().buf_cap = cap;
         // wake remote
-        self.remote.lock().unwrap().borrow().waker.wake();
+        let guard = self.remote.lock().unwrap();
+        let remote = guard.borrow();
+        remote.waker.wake();
+        remote.write_waker.wake();
     }
 
     /// Read any available data
@@ -290,7 +312,7 @@ impl IoTest {
                         .lock()
                         .unwrap()
                         .borrow_mut()
-                        .waker
+                        .write_waker
                         .0
                         .lock()
                         .unwrap()
@@ -305,7 +327,7 @@ impl IoTest {
                     .lock()
                     .unwrap()
                     .borrow_mut()
-                    .waker
+                    .write_waker
                     .0
                     .lock()
                     .unwrap()
@@ -467,7 +489,7 @@ impl Future for WriteTask {
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.as_mut().get_mut();
+        let this = self.as_mut().get_mut();
 
         match this.st {
             IoWriteState::Processing(ref mut delay) => {
@@ -666,4 +688,29 @@ mod tests {
         drop(server);
         assert!(server2.is_server_dropped());
     }
+
+    #[ntex::test]
+    async fn test_duplex() {
+        let (client, server) = duplex(4);
+        client.write("GET /test");
+        assert_eq!(server.read_ready().await.unwrap(), Some(()));
+        assert_eq!(
+            server.with_read_buf(|buf| buf.split()),
+            Bytes::from_static(b"GET /test")
+        );
+
+        // server side could write 4 bytes
+        server.write(b"response").unwrap();
+        sleep(Millis(25)).await;
+        assert_eq!(client.read_any(), Bytes::from_static(b"resp"));
+        client.remote_buffer_cap(1024);
+        assert_eq!(client.read().await.unwrap(), Bytes::from_static(b"onse"));
+
+        assert!(!client.is_server_dropped());
+        client.close().await;
+        assert_eq!(server.read_ready().await.unwrap(), None);
+        drop(server);
+        sleep(Millis(25)).await;
+        assert!(client.is_server_dropped());
+    }
 }
@@ -1,5 +1,8 @@
 //! Query related types
-use std::{any, fmt, marker::PhantomData, net::SocketAddr};
+use std::{any, fmt, marker::PhantomData, net::SocketAddr, time::Duration};
+
+#[cfg(unix)]
+use std::{io, os::unix::io::RawFd, rc::Rc, task::Context, task::Poll};
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct PeerAddr(pub SocketAddr);
@@ -22,6 +25,66 @@ impl fmt::Debug for PeerAddr {
     }
 }
 
+/// Original connection addresses received with PROXY protocol header
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProxyInfo {
+    /// Client address
+    pub source: SocketAddr,
+    /// Destination address
+    pub destination: SocketAddr,
+}
+
+/// Credentials of the peer process, unix domain sockets only.
+///
+/// Captured with `SO_PEERCRED` socket option.
+#[cfg(unix)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PeerCred {
+    /// User id of the peer process
+    pub uid: u32,
+    /// Group id of the peer process
+    pub gid: u32,
+    /// Process id of the peer process, if available
+    pub pid: Option<i32>,
+}
+
+/// Tcp connection statistics, queried with `TCP_INFO` socket option
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TcpInfo {
+    /// Smoothed round trip time estimate
+    pub rtt: Duration,
+    /// Round trip time variance
+    pub rtt_var: Duration,
+}
+
+#[cfg(unix)]
+/// Direct write access to underlying socket.
+///
+/// Io stream could provide it for zero-copy transmissions, i.e. `sendfile`.
+/// Write buffer must be flushed before socket is used directly.
+#[derive(Clone)]
+pub struct RawWrite(pub Rc<dyn RawWriteHandle>);
+
+#[cfg(unix)]
+pub trait RawWriteHandle {
+    /// Wait for socket write readiness and call `f` with raw socket descriptor.
+    ///
+    /// `f` must perform non-blocking write operation and return number of
+    /// written bytes or `WouldBlock` error.
+    fn poll_write_raw(
+        &self,
+        cx: &mut Context<'_>,
+        f: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+    ) -> Poll<io::Result<usize>>;
+}
+
+#[cfg(unix)]
+impl fmt::Debug for RawWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawWrite").finish()
+    }
+}
+
 pub struct QueryItem<T> {
     item: Option<Box<dyn any::Any>>,
     _t: PhantomData<T>,
@@ -1,7 +1,9 @@
-use std::{marker::PhantomData, task::Context, task::Poll};
+use std::{future::Future, io, marker::PhantomData, pin::Pin, rc::Rc};
+use std::{task::Context, task::Poll};
 
+use ntex_bytes::BytesVec;
 use ntex_service::{fn_factory_with_config, into_service, Service, ServiceFactory};
-use ntex_util::future::Ready;
+use ntex_util::future::{Either, Ready};
 
 use crate::{Filter, FilterFactory, Io, IoBoxed};
 
@@ -86,3 +88,201 @@ where
         req.add_filter(self.filter.clone())
     }
 }
+
+/// Protocol detection result
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Selected {
+    A,
+    B,
+}
+
+/// Select service error
+#[derive(Debug)]
+pub enum SelectError<A, B> {
+    A(A),
+    B(B),
+    /// Peer is disconnected before protocol get detected
+    PeerGone(Option<io::Error>),
+}
+
+/// Create protocol detection service factory
+///
+/// `detect` function peeks into read buffer and selects service for
+/// the io stream. If `detect` returns `None`, more data get read from
+/// the io stream. Read buffer is not consumed, selected service
+/// receives io stream with all buffered data.
+pub fn select<F, C, D, A, B>(detect: D, a: A, b: B) -> SelectServiceFactory<F, D, A, B>
+where
+    F: Filter,
+    D: Fn(&BytesVec) -> Option<Selected> + 'static,
+    A: ServiceFactory<Io<F>, C>,
+    B: ServiceFactory<Io<F>, C, InitError = A::InitError>,
+{
+    SelectServiceFactory {
+        a,
+        b,
+        detect: Rc::new(detect),
+        _t: PhantomData,
+    }
+}
+
+pub struct SelectServiceFactory<F, D, A, B> {
+    a: A,
+    b: B,
+    detect: Rc<D>,
+    _t: PhantomData<F>,
+}
+
+impl<F, C, D, A, B> ServiceFactory<Io<F>, C> for SelectServiceFactory<F, D, A, B>
+where
+    F: Filter,
+    C: Clone,
+    D: Fn(&BytesVec) -> Option<Selected> + 'static,
+    A: ServiceFactory<Io<F>, C>,
+    B: ServiceFactory<Io<F>, C, InitError = A::InitError>,
+    A::Service: 'static,
+    B::Service: 'static,
+    A::Future: 'static,
+    B::Future: 'static,
+{
+    type Response = Either<A::Response, B::Response>;
+    type Error = SelectError<A::Error, B::Error>;
+    type Service = SelectService<D, A::Service, B::Service>;
+    type InitError = A::InitError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Service, Self::InitError>>>>;
+
+    fn new_service(&self, cfg: C) -> Self::Future {
+        let fut_a = self.a.new_service(cfg.clone());
+        let fut_b = self.b.new_service(cfg);
+        let detect = self.detect.clone();
+
+        Box::pin(async move {
+            Ok(SelectService {
+                a: Rc::new(fut_a.await?),
+                b: Rc::new(fut_b.await?),
+                detect,
+            })
+        })
+    }
+}
+
+pub struct SelectService<D, A, B> {
+    a: Rc<A>,
+    b: Rc<B>,
+    detect: Rc<D>,
+}
+
+impl<F, D, A, B> Service<Io<F>> for SelectService<D, A, B>
+where
+    F: Filter,
+    D: Fn(&BytesVec) -> Option<Selected> + 'static,
+    A: Service<Io<F>> + 'static,
+    B: Service<Io<F>> + 'static,
+{
+    type Response = Either<A::Response, B::Response>;
+    type Error = SelectError<A::Error, B::Error>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let ready_a = self.a.poll_ready(cx).map_err(SelectError::A)?.is_ready();
+        let ready_b = self.b.poll_ready(cx).map_err(SelectError::B)?.is_ready();
+        if ready_a && ready_b {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        let ready_a = self.a.poll_shutdown(cx, is_error).is_ready();
+        let ready_b = self.b.poll_shutdown(cx, is_error).is_ready();
+        if ready_a && ready_b {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn call(&self, io: Io<F>) -> Self::Future {
+        let a = self.a.clone();
+        let b = self.b.clone();
+        let detect = self.detect.clone();
+
+        Box::pin(async move {
+            loop {
+                match io.with_read_buf(|buf| (*detect)(buf)) {
+                    Some(Selected::A) => {
+                        return a.call(io).await.map(Either::Left).map_err(SelectError::A)
+                    }
+                    Some(Selected::B) => {
+                        return b.call(io).await.map(Either::Right).map_err(SelectError::B)
+                    }
+                    None => match io.read_ready().await {
+                        Ok(Some(())) => continue,
+                        Ok(None) => return Err(SelectError::PeerGone(None)),
+                        Err(err) => return Err(SelectError::PeerGone(Some(err))),
+                    },
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ntex_bytes::Bytes;
+    use ntex_codec::BytesCodec;
+    use ntex_service::fn_service;
+    use ntex_util::time::{sleep, Millis};
+
+    use super::*;
+    use crate::testing::IoTest;
+
+    fn detect(buf: &BytesVec) -> Option<Selected> {
+        if buf.len() < 4 {
+            None
+        } else if &buf[..4] == b"NEW " {
+            Some(Selected::B)
+        } else {
+            Some(Selected::A)
+        }
+    }
+
+    #[ntex::test]
+    async fn test_select() {
+        let factory = select(
+            detect,
+            fn_service(|io: Io| async move {
+                Ok::<_, ()>(io.recv(&BytesCodec).await.unwrap().unwrap())
+            }),
+            fn_service(|io: Io| async move {
+                Ok::<_, ()>(io.recv(&BytesCodec).await.unwrap().unwrap().len())
+            }),
+        );
+        let srv = factory.new_service(()).await.unwrap();
+
+        let (client, server) = IoTest::create();
+        client.write("OLD DATA");
+        let res = srv.call(Io::new(server)).await;
+        assert_eq!(
+            res.ok().unwrap(),
+            Either::Left(Bytes::from_static(b"OLD DATA").into())
+        );
+
+        // not enough data for detection
+        let (client, server) = IoTest::create();
+        client.write("NE");
+        ntex_util::spawn(async move {
+            sleep(Millis(25)).await;
+            client.write("W DATA");
+        });
+        let res = srv.call(Io::new(server)).await;
+        assert_eq!(res.ok().unwrap(), Either::Right(8));
+
+        let (client, server) = IoTest::create();
+        client.write("NE");
+        client.close().await;
+        let res = srv.call(Io::new(server)).await;
+        assert!(matches!(res, Err(SelectError::PeerGone(None))));
+    }
+}
@@ -414,8 +414,17 @@ impl<'de> Deserializer<'de> for Value<'de> {
         Err(de::value::Error::custom("unsupported type: tuple struct"))
     }
 
+    // multi-segment value, i.e. tail match
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(ValueSeq {
+            segments: self.value.split('/'),
+        })
+    }
+
     unsupported_type!(deserialize_any, "any");
-    unsupported_type!(deserialize_seq, "seq");
     unsupported_type!(deserialize_map, "map");
     unsupported_type!(deserialize_identifier, "identifier");
 }
@@ -438,6 +447,26 @@ impl<'de, T: ResourcePath> de::SeqAccess<'de> for ParamsSeq<'de, T> {
     }
 }
 
+struct ValueSeq<'de> {
+    segments: std::str::Split<'de, char>,
+}
+
+impl<'de> de::SeqAccess<'de> for ValueSeq<'de> {
+    type Error = de::value::Error;
+
+    fn next_element_seed<U>(&mut self, seed: U) -> Result<Option<U::Value>, Self::Error>
+    where
+        U: de::DeserializeSeed<'de>,
+    {
+        for value in self.segments.by_ref() {
+            if !value.is_empty() {
+                return Ok(Some(seed.deserialize(Value { value })?));
+            }
+        }
+        Ok(None)
+    }
+}
+
 struct ValueEnum<'de> {
     value: &'de str,
 }
@@ -631,6 +660,37 @@ mod tests {
         assert!(i.is_err());
     }
 
+    #[test]
+    fn test_extract_tail() {
+        let mut path = Path::new("/files/10/images/logo.png");
+        path.segments = vec![
+            ("id", PathItem::Static("10")),
+            ("tail", PathItem::Static("images//logo.png")),
+        ];
+
+        #[derive(Deserialize)]
+        struct Files {
+            id: u32,
+            tail: Vec<String>,
+        }
+        let s: Files = de::Deserialize::deserialize(PathDeserializer::new(&path)).unwrap();
+        assert_eq!(s.id, 10);
+        assert_eq!(s.tail, vec!["images", "logo.png"]);
+
+        let s: (u32, Vec<&str>) =
+            de::Deserialize::deserialize(PathDeserializer::new(&path)).unwrap();
+        assert_eq!(s.0, 10);
+        assert_eq!(s.1, vec!["images", "logo.png"]);
+
+        let s: (u32, String) =
+            de::Deserialize::deserialize(PathDeserializer::new(&path)).unwrap();
+        assert_eq!(s.1, "images//logo.png");
+
+        let s: Result<(u32, Vec<u32>), _> =
+            de::Deserialize::deserialize(PathDeserializer::new(&path));
+        assert!(format!("{:?}", s).contains("can not parse"));
+    }
+
     #[test]
     fn test_extract_enum() {
         let mut path = Path::new("/val1/");
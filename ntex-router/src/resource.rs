@@ -13,6 +13,12 @@ pub(super) struct Segments {
 
 /// ResourceDef describes an entry in resources table
 ///
+/// Dynamic segment is defined as `{name}`, by default it matches
+/// one path segment. Dynamic segment could define custom regex
+/// constraint, `{id:\d+}`. Segment followed by `*` matches remainder
+/// of the path regardless of slashes, constraint is applied to the whole
+/// remainder, `{path:[a-z0-9/]+\.png}*`.
+///
 /// Resource definition can contain only 16 dynamic segments
 #[derive(Clone, Debug)]
 pub struct ResourceDef {
@@ -189,6 +195,14 @@ impl ResourceDef {
         &self.pattern
     }
 
+    /// Names of the dynamic segments of the path pattern
+    pub fn var_names(&self) -> impl Iterator<Item = &str> {
+        self.elements.iter().filter_map(|el| match el {
+            PathElement::Var(ref name) => Some(name.as_str()),
+            PathElement::Str(_) => None,
+        })
+    }
+
     /// Build resource path from elements. Returns `true` on success.
     pub fn resource_path<U, I>(&self, path: &mut String, elements: &mut U) -> bool
     where
@@ -285,22 +299,21 @@ impl ResourceDef {
 
             let (name, pat) = match param.find(':') {
                 Some(idx) => {
-                    if tail {
-                        panic!("Custom regex is not supported for remainder match");
-                    }
                     let (name, pattern) = param.split_at(idx);
                     (name, &pattern[1..])
                 }
                 None => (
                     param,
                     if tail {
-                        rem = &rem[1..];
                         DEFAULT_PATTERN_TAIL
                     } else {
                         DEFAULT_PATTERN
                     },
                 ),
             };
+            if tail {
+                rem = &rem[1..];
+            }
 
             re.push_str(&format!(r"(?P<{}>{})", &escape(name), pat));
 
@@ -998,6 +1011,18 @@ mod tests {
         assert_eq!(s, "/user/item/item2/");
     }
 
+    #[test]
+    fn test_var_names() {
+        let resource = ResourceDef::new("/user/{item1}/{item2:[0-9]+}/test");
+        assert_eq!(resource.var_names().collect::<Vec<_>>(), ["item1", "item2"]);
+
+        let resource = ResourceDef::new("/user/{tail}*");
+        assert_eq!(resource.var_names().collect::<Vec<_>>(), ["tail"]);
+
+        let resource = ResourceDef::new("/user/test");
+        assert_eq!(resource.var_names().count(), 0);
+    }
+
     #[test]
     fn test_non_rooted() {
         let tree = Tree::new(&ResourceDef::new("name"), 1);
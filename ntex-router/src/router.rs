@@ -1,22 +1,29 @@
-use super::tree::Tree;
+use super::tree::{MatchOptions, Tree};
 use super::{IntoPattern, Resource, ResourceDef, ResourcePath};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ResourceId(pub(crate) u16);
 
+impl ResourceId {
+    /// Id of the matched resource definition, see `ResourceDef::id()`
+    pub fn id(&self) -> u16 {
+        self.0
+    }
+}
+
 /// Resource router.
 #[derive(Clone)]
 pub struct Router<T, U = ()> {
     tree: Tree,
     resources: Vec<(ResourceDef, T, Option<U>)>,
-    insensitive: bool,
+    opts: MatchOptions,
 }
 
 impl<T, U> Router<T, U> {
     pub fn build() -> RouterBuilder<T, U> {
         RouterBuilder {
             resources: Vec::new(),
-            insensitive: false,
+            opts: MatchOptions::default(),
         }
     }
 
@@ -25,11 +32,10 @@ impl<T, U> Router<T, U> {
         R: Resource<P>,
         P: ResourcePath,
     {
-        if let Some(idx) = if self.insensitive {
-            self.tree.find_insensitive(resource)
-        } else {
-            self.tree.find(resource)
-        } {
+        if let Some(idx) = self
+            .tree
+            .find_checked_inner(resource, self.opts, &|_, _| true)
+        {
             let item = &self.resources[idx];
             Some((&item.1, ResourceId(item.0.id())))
         } else {
@@ -42,11 +48,10 @@ impl<T, U> Router<T, U> {
         R: Resource<P>,
         P: ResourcePath,
     {
-        if let Some(idx) = if self.insensitive {
-            self.tree.find_insensitive(resource)
-        } else {
-            self.tree.find(resource)
-        } {
+        if let Some(idx) = self
+            .tree
+            .find_checked_inner(resource, self.opts, &|_, _| true)
+        {
             let item = &mut self.resources[idx];
             Some((&mut item.1, ResourceId(item.0.id())))
         } else {
@@ -64,17 +69,13 @@ impl<T, U> Router<T, U> {
         R: Resource<P>,
         P: ResourcePath,
     {
-        if let Some(idx) = if self.insensitive {
-            self.tree.find_checked_insensitive(resource, &|idx, res| {
+        if let Some(idx) = self
+            .tree
+            .find_checked_inner(resource, self.opts, &|idx, res| {
                 let item = &self.resources[idx];
                 check(res, item.2.as_ref())
             })
-        } else {
-            self.tree.find_checked(resource, &|idx, res| {
-                let item = &self.resources[idx];
-                check(res, item.2.as_ref())
-            })
-        } {
+        {
             let item = &self.resources[idx];
             Some((&item.1, ResourceId(item.0.id())))
         } else {
@@ -92,17 +93,13 @@ impl<T, U> Router<T, U> {
         R: Resource<P>,
         P: ResourcePath,
     {
-        if let Some(idx) = if self.insensitive {
-            self.tree.find_checked_insensitive(resource, &|idx, res| {
+        if let Some(idx) = self
+            .tree
+            .find_checked_inner(resource, self.opts, &|idx, res| {
                 let item = &self.resources[idx];
                 check(res, item.2.as_ref())
             })
-        } else {
-            self.tree.find_checked(resource, &|idx, res| {
-                let item = &self.resources[idx];
-                check(res, item.2.as_ref())
-            })
-        } {
+        {
             let item = &mut self.resources[idx];
             Some((&mut item.1, ResourceId(item.0.id())))
         } else {
@@ -112,7 +109,7 @@ impl<T, U> Router<T, U> {
 }
 
 pub struct RouterBuilder<T, U = ()> {
-    insensitive: bool,
+    opts: MatchOptions,
     resources: Vec<(ResourceDef, T, Option<U>)>,
 }
 
@@ -122,7 +119,18 @@ impl<T, U> RouterBuilder<T, U> {
     ///
     /// By default router is case sensitive.
     pub fn case_insensitive(&mut self) {
-        self.insensitive = true;
+        self.opts.insensitive = true;
+    }
+
+    /// Ignore trailing slash during path matching.
+    ///
+    /// Resource matches path with or without trailing slash, i.e. `/path`
+    /// and `/path/` both match `/path` and `/path/` resources. If both
+    /// resources are registered, the first one wins.
+    ///
+    /// By default trailing slash is significant.
+    pub fn ignore_trailing_slash(&mut self) {
+        self.opts.trailing_slash = true;
     }
 
     /// Register resource for specified path.
@@ -166,13 +174,14 @@ impl<T, U> RouterBuilder<T, U> {
             for (idx, r) in self.resources[1..].iter().enumerate() {
                 tree.insert(&r.0, idx + 1)
             }
+            tree.build_index();
             tree
         };
 
         Router {
             tree,
             resources: self.resources,
-            insensitive: self.insensitive,
+            opts: self.opts,
         }
     }
 }
@@ -181,6 +190,8 @@ impl<T, U> RouterBuilder<T, U> {
 mod tests {
     use crate::path::Path;
     use crate::router::{ResourceId, Router};
+    use crate::tree::Tree;
+    use crate::ResourceDef;
 
     #[test]
     fn test_recognizer_1() {
@@ -282,6 +293,36 @@ mod tests {
         assert_eq!(*h, 11);
     }
 
+    #[test]
+    fn test_recognizer_constraints() {
+        let mut router = Router::<usize>::build();
+        router.path("/user/{id:\\d+}", 10);
+        router.path("/user/{slug}", 11);
+        router.path("/files/{path:[a-z/]+\\.png}*", 12);
+        router.path("/files/{path}*", 13);
+        let mut router = router.finish();
+
+        let mut path = Path::new("/user/123");
+        let (h, _) = router.recognize_mut(&mut path).unwrap();
+        assert_eq!(*h, 10);
+        assert_eq!(path.get("id").unwrap(), "123");
+
+        let mut path = Path::new("/user/bob");
+        let (h, _) = router.recognize_mut(&mut path).unwrap();
+        assert_eq!(*h, 11);
+        assert_eq!(path.get("slug").unwrap(), "bob");
+
+        let mut path = Path::new("/files/images/logo.png");
+        let (h, _) = router.recognize_mut(&mut path).unwrap();
+        assert_eq!(*h, 12);
+        assert_eq!(path.get("path").unwrap(), "images/logo.png");
+
+        let mut path = Path::new("/files/images/logo.jpg");
+        let (h, _) = router.recognize_mut(&mut path).unwrap();
+        assert_eq!(*h, 13);
+        assert_eq!(path.get("path").unwrap(), "images/logo.jpg");
+    }
+
     #[test]
     fn test_recognizer_3() {
         let mut router = Router::<usize>::build();
@@ -452,4 +493,87 @@ mod tests {
             11
         );
     }
+
+    #[test]
+    fn test_recognizer_trailing_slash() {
+        let mut router = Router::<usize>::build();
+        router.ignore_trailing_slash();
+        router.path("", 10).0.set_id(0);
+        router.path("/name", 11).0.set_id(1);
+        router.path("/name/{val}/", 12).0.set_id(2);
+        router.path("/v/{tail}*", 13).0.set_id(3);
+        router.prefix("/prefix/", 14).0.set_id(4);
+        let mut router = router.finish();
+
+        for (path, val) in &[
+            ("", 10),
+            ("/", 10),
+            ("/name", 11),
+            ("/name/", 11),
+            ("/name/value", 12),
+            ("/name/value/", 12),
+            ("/v/a/b/", 13),
+            ("/prefix/", 14),
+        ] {
+            let mut p = Path::new(*path);
+            assert_eq!(router.recognize_mut(&mut p).unwrap().0, val, "{}", path);
+        }
+
+        let mut p = Path::new("/name/value");
+        router.recognize_mut(&mut p).unwrap();
+        assert_eq!(p.get("val").unwrap(), "value");
+
+        assert!(router.recognize_mut(&mut Path::new("/name//")).is_none());
+        assert!(router.recognize_mut(&mut Path::new("/prefix")).is_none());
+
+        // trailing slash is significant by default
+        let mut router = Router::<usize>::build();
+        router.path("/name", 10);
+        let mut router = router.finish();
+        assert!(router.recognize_mut(&mut Path::new("/name/")).is_none());
+    }
+
+    #[test]
+    fn test_recognizer_index() {
+        let mut builder = Router::<usize>::build();
+        let mut paths = Vec::new();
+        for i in 0..20 {
+            paths.push(format!("/api{}/users", i));
+            paths.push(format!("/api{}/users/{{id}}", i));
+            paths.push(format!("/api{}/orders/{{id}}/items", i));
+        }
+        paths.push("/{section}/users".to_string());
+        paths.push("/api3/{tail}*".to_string());
+        paths.push("/Static/page".to_string());
+        for (idx, path) in paths.iter().enumerate() {
+            builder.path(path.as_str(), idx);
+        }
+        let router = builder.finish();
+
+        // tree without index
+        let mut tree = Tree::new(&ResourceDef::new(paths[0].as_str()), 0);
+        for (idx, path) in paths[1..].iter().enumerate() {
+            tree.insert(&ResourceDef::new(path.as_str()), idx + 1);
+        }
+
+        for (path, expected) in [
+            ("/api0/users", Some(0)),
+            ("/api7/users/10", Some(22)),
+            ("/api19/orders/10/items", Some(59)),
+            ("/api3/orders", Some(61)),
+            ("/other/users", Some(60)),
+            ("/api20/users", Some(60)),
+            ("/Static/page", Some(62)),
+            ("/static/page", None),
+            ("/unknown", None),
+        ] {
+            let mut p = Path::new(path);
+            assert_eq!(router.recognize(&mut p).map(|r| *r.0), expected, "{}", path);
+            assert_eq!(tree.find(&mut Path::new(path)), expected, "{}", path);
+        }
+
+        let mut p = Path::new("/api7/users/10");
+        router.recognize(&mut p).unwrap();
+        assert_eq!(p.get("id").unwrap(), "10");
+    }
 }
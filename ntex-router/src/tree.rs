@@ -1,14 +1,28 @@
-use std::borrow::Cow;
-use std::mem;
+use std::{borrow::Cow, collections::HashMap, mem};
 
 use super::path::PathItem;
 use super::resource::{ResourceDef, Segment};
 use super::{Resource, ResourcePath};
 
+/// Number of subtrees that requires static segments index
+const INDEX_THRESHOLD: usize = 8;
+
 #[derive(Debug, Clone, Default)]
 pub(super) struct Tree {
     key: Vec<Segment>,
     items: Vec<Item>,
+    index: Option<Box<Index>>,
+}
+
+/// Index of node's items by first static segment of subtree key.
+///
+/// Subtrees with static first segment could match only equal path segment,
+/// other items must be checked for every path. Positions are sorted, so
+/// items are visited in the same order as without index.
+#[derive(Debug, Clone, Default)]
+struct Index {
+    statics: HashMap<String, Vec<usize>>,
+    other: Vec<usize>,
 }
 
 #[derive(Clone, Debug)]
@@ -25,6 +39,15 @@ enum Value {
     PrefixSlash(usize),
 }
 
+/// Path matching options
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct MatchOptions {
+    /// Static segments are compared case insensitively
+    pub(super) insensitive: bool,
+    /// Trailing slash is not significant
+    pub(super) trailing_slash: bool,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum PathState {
     Empty,
@@ -86,10 +109,69 @@ impl Tree {
         } else {
             Vec::new()
         };
-        Tree { key, items }
+        Tree {
+            key,
+            items,
+            index: None,
+        }
+    }
+
+    /// Build static segments index for nodes with large number of subtrees.
+    pub(super) fn build_index(&mut self) {
+        let mut subtrees = 0;
+        for item in &mut self.items {
+            if let Item::Subtree(ref mut tree) = item {
+                tree.build_index();
+                subtrees += 1;
+            }
+        }
+
+        self.index = if subtrees >= INDEX_THRESHOLD {
+            let mut index = Index::default();
+            for (idx, item) in self.items.iter().enumerate() {
+                match item {
+                    Item::Subtree(Tree { ref key, .. }) if !key.is_empty() => {
+                        if let Segment::Static(ref seg) = key[0] {
+                            index
+                                .statics
+                                .entry(seg.to_ascii_lowercase())
+                                .or_default()
+                                .push(idx);
+                            continue;
+                        }
+                        index.other.push(idx);
+                    }
+                    _ => index.other.push(idx),
+                }
+            }
+            Some(Box::new(index))
+        } else {
+            None
+        };
+    }
+
+    /// Node's items that could match path, in insertion order.
+    fn items_for<T: ResourcePath>(&self, path: &str) -> Items<'_> {
+        if let Some(ref index) = self.index {
+            let idx = path.find('/').unwrap_or(path.len());
+            let segment = T::unquote(&path[..idx]);
+            let statics = if segment.bytes().any(|c| c.is_ascii_uppercase()) {
+                index.statics.get(&segment.to_ascii_lowercase())
+            } else {
+                index.statics.get(segment.as_ref())
+            };
+            Items::Indexed {
+                items: &self.items,
+                statics: statics.map(|v| v.as_slice()).unwrap_or(&[]),
+                other: &index.other,
+            }
+        } else {
+            Items::All(self.items.iter())
+        }
     }
 
     pub(super) fn insert(&mut self, resource: &ResourceDef, value: usize) {
+        self.index = None;
         for seg in &resource.tp {
             let value = if seg.slesh {
                 if resource.prefix {
@@ -115,6 +197,7 @@ impl Tree {
             let child = Tree {
                 key: self.key.split_off(p),
                 items: mem::take(&mut self.items),
+                index: None,
             };
             self.items.push(Item::Subtree(child));
         }
@@ -145,48 +228,29 @@ impl Tree {
         }
     }
 
+    #[cfg(test)]
     pub(crate) fn find<T, R>(&self, resource: &mut R) -> Option<usize>
     where
         T: ResourcePath,
         R: Resource<T>,
     {
-        self.find_checked_inner(resource, false, &|_, _| true)
-    }
-
-    pub(crate) fn find_insensitive<T, R>(&self, resource: &mut R) -> Option<usize>
-    where
-        T: ResourcePath,
-        R: Resource<T>,
-    {
-        self.find_checked_inner(resource, true, &|_, _| true)
+        self.find_checked_inner(resource, MatchOptions::default(), &|_, _| true)
     }
 
+    #[cfg(test)]
     pub(crate) fn find_checked<T, R, F>(&self, resource: &mut R, check: &F) -> Option<usize>
     where
         T: ResourcePath,
         R: Resource<T>,
         F: Fn(usize, &R) -> bool,
     {
-        self.find_checked_inner(resource, false, check)
-    }
-
-    pub(crate) fn find_checked_insensitive<T, R, F>(
-        &self,
-        resource: &mut R,
-        check: &F,
-    ) -> Option<usize>
-    where
-        T: ResourcePath,
-        R: Resource<T>,
-        F: Fn(usize, &R) -> bool,
-    {
-        self.find_checked_inner(resource, true, check)
+        self.find_checked_inner(resource, MatchOptions::default(), check)
     }
 
     pub(crate) fn find_checked_inner<T, R, F>(
         &self,
         resource: &mut R,
-        insensitive: bool,
+        opts: MatchOptions,
         check: &F,
     ) -> Option<usize>
     where
@@ -201,13 +265,14 @@ impl Tree {
 
         if self.key.is_empty() {
             if path == "/" {
-                for val in &self.items {
+                for val in self.items_for::<T>("") {
                     match val {
                         Item::Value(val) => {
                             let v = match val {
                                 Value::Slash(v)
                                 | Value::Prefix(v)
                                 | Value::PrefixSlash(v) => *v,
+                                Value::Val(v) if opts.trailing_slash => *v,
                                 _ => continue,
                             };
                             if check(v, resource) {
@@ -222,7 +287,7 @@ impl Tree {
                                 check,
                                 1,
                                 &mut segments,
-                                insensitive,
+                                opts,
                                 base_skip - 1,
                             );
                             if let Some((val, skip)) = result {
@@ -240,6 +305,7 @@ impl Tree {
                         Item::Value(val) => {
                             let v = match val {
                                 Value::Val(v) | Value::Prefix(v) => *v,
+                                Value::Slash(v) if opts.trailing_slash => *v,
                                 _ => continue,
                             };
                             if check(v, resource) {
@@ -258,7 +324,7 @@ impl Tree {
                     path
                 };
 
-                for val in &self.items {
+                for val in self.items_for::<T>(subtree_path) {
                     match val {
                         Item::Value(val) => {
                             let v = match val {
@@ -277,7 +343,7 @@ impl Tree {
                                 check,
                                 1,
                                 &mut segments,
-                                insensitive,
+                                opts,
                                 base_skip,
                             );
                             if let Some((val, skip)) = result {
@@ -303,7 +369,7 @@ impl Tree {
                 check,
                 1,
                 &mut segments,
-                insensitive,
+                opts,
                 base_skip,
             );
 
@@ -325,7 +391,7 @@ impl Tree {
         check: &F,
         skip: usize,
         segments: &mut Vec<(&'static str, PathItem)>,
-        insensitive: bool,
+        opts: MatchOptions,
         base_skip: isize,
     ) -> Option<(usize, usize)>
     where
@@ -334,15 +400,7 @@ impl Tree {
         F: Fn(usize, &R) -> bool,
     {
         let len = segments.len();
-        let res = self.find_inner2(
-            path,
-            resource,
-            check,
-            skip,
-            segments,
-            insensitive,
-            base_skip,
-        );
+        let res = self.find_inner2(path, resource, check, skip, segments, opts, base_skip);
         if res.is_none() {
             segments.truncate(len);
         }
@@ -357,7 +415,7 @@ impl Tree {
         check: &F,
         mut skip: usize,
         segments: &mut Vec<(&'static str, PathItem)>,
-        insensitive: bool,
+        opts: MatchOptions,
         base_skip: isize,
     ) -> Option<(usize, usize)>
     where
@@ -403,7 +461,7 @@ impl Tree {
             // check segment match
             let is_match = match key[0] {
                 Segment::Static(ref pattern) => {
-                    if insensitive {
+                    if opts.insensitive {
                         pattern.eq_ignore_ascii_case(segment.as_ref())
                     } else {
                         pattern == segment.as_ref()
@@ -474,6 +532,7 @@ impl Tree {
                                 if let Item::Value(ref val) = val {
                                     let v = match val {
                                         Value::Val(v) | Value::Prefix(v) => *v,
+                                        Value::Slash(v) if opts.trailing_slash => *v,
                                         Value::Slash(_) | Value::PrefixSlash(_) => continue,
                                     };
                                     if check(v, resource) {
@@ -496,12 +555,15 @@ impl Tree {
                         PathState::Tail
                     };
 
-                    for val in &self.items {
+                    for val in self.items_for::<T>(subtree_path) {
                         match val {
                             Item::Value(val) => {
                                 let v = match val {
                                     Value::Val(v) => {
-                                        if p == PathState::Empty {
+                                        if p == PathState::Empty
+                                            || (p == PathState::Slash
+                                                && opts.trailing_slash)
+                                        {
                                             *v
                                         } else {
                                             continue;
@@ -540,7 +602,7 @@ impl Tree {
                                     check,
                                     skip,
                                     segments,
-                                    insensitive,
+                                    opts,
                                     base_skip,
                                 );
                                 if result.is_some() {
@@ -560,6 +622,48 @@ impl Tree {
     }
 }
 
+enum Items<'a> {
+    All(std::slice::Iter<'a, Item>),
+    Indexed {
+        items: &'a [Item],
+        statics: &'a [usize],
+        other: &'a [usize],
+    },
+}
+
+impl<'a> Iterator for Items<'a> {
+    type Item = &'a Item;
+
+    fn next(&mut self) -> Option<&'a Item> {
+        match self {
+            Items::All(iter) => iter.next(),
+            Items::Indexed {
+                items,
+                statics,
+                other,
+            } => {
+                // merge sorted positions
+                let idx = match (statics.first().copied(), other.first().copied()) {
+                    (Some(s), Some(o)) if s < o => {
+                        *statics = &statics[1..];
+                        s
+                    }
+                    (_, Some(o)) => {
+                        *other = &other[1..];
+                        o
+                    }
+                    (Some(s), None) => {
+                        *statics = &statics[1..];
+                        s
+                    }
+                    (None, None) => return None,
+                };
+                Some(&items[idx])
+            }
+        }
+    }
+}
+
 fn common_prefix(k1: &[Segment], k2: &[Segment]) -> usize {
     k1.iter()
         .zip(k2.iter())
@@ -7,6 +7,8 @@ use async_channel::{unbounded, Receiver, Sender};
 use async_oneshot as oneshot;
 use futures_core::stream::Stream;
 
+use crate::metrics::TaskMetrics;
+use crate::supervisor::{self, RestartPolicy};
 use crate::system::System;
 
 thread_local!(
@@ -78,7 +80,17 @@ impl Arbiter {
     /// Returns address of newly created arbiter.
     pub fn new() -> Arbiter {
         let id = COUNT.fetch_add(1, Ordering::Relaxed);
-        let name = format!("ntex-rt:worker:{}", id);
+        Arbiter::start(id, format!("ntex-rt:worker:{}", id))
+    }
+
+    /// Spawn new thread with specified name and run event loop in spawned thread.
+    /// Returns address of newly created arbiter.
+    pub fn with_name(name: String) -> Arbiter {
+        let id = COUNT.fetch_add(1, Ordering::Relaxed);
+        Arbiter::start(id, name)
+    }
+
+    fn start(id: usize, name: String) -> Arbiter {
         let sys = System::current();
         let (arb_tx, arb_rx) = unbounded();
         let arb_tx2 = arb_tx.clone();
@@ -91,11 +103,12 @@ impl Arbiter {
                 let (stop, stop_rx) = oneshot::oneshot();
                 STORAGE.with(|cell| cell.borrow_mut().clear());
 
+                let rt = sys.runtime().clone();
                 System::set_current(sys);
 
-                crate::block_on(async move {
+                rt.block_on(async move {
                     // start arbiter controller
-                    crate::spawn(ArbiterController {
+                    System::current().runtime().spawn(ArbiterController {
                         stop: Some(stop),
                         rx: arb_rx,
                     });
@@ -135,6 +148,25 @@ impl Arbiter {
             .try_send(ArbiterCommand::Execute(Box::new(future)));
     }
 
+    /// Send a task factory to the Arbiter's thread, and spawn supervised task.
+    ///
+    /// If the task panics, factory is used for creating new task according to
+    /// restart policy. Lifecycle events are available via `System::subscribe()`.
+    /// Returns id of supervised task.
+    pub fn spawn_supervised<F, R>(&self, factory: F, policy: RestartPolicy) -> usize
+    where
+        F: Fn() -> R + Send + 'static,
+        R: Future<Output = ()> + 'static,
+    {
+        let id = supervisor::next_id();
+        self.exec_fn(move || {
+            System::current()
+                .runtime()
+                .spawn(supervisor::supervise(id, factory, policy));
+        });
+        id
+    }
+
     /// Send a function to the Arbiter's thread. This function will be executed asynchronously.
     /// A future is created, and when resolved will contain the result of the function sent
     /// to the Arbiters thread.
@@ -167,6 +199,13 @@ impl Arbiter {
             })));
     }
 
+    /// Get task metrics of the arbiter.
+    pub fn task_metrics(
+        &self,
+    ) -> impl Future<Output = Result<TaskMetrics, oneshot::Closed>> {
+        self.exec(TaskMetrics::current)
+    }
+
     /// Set item to current arbiter's storage
     pub fn set_item<T: 'static>(item: T) {
         STORAGE
@@ -262,7 +301,7 @@ impl Future for ArbiterController {
                         return Poll::Ready(());
                     }
                     ArbiterCommand::Execute(fut) => {
-                        crate::spawn(fut);
+                        System::current().runtime().spawn(fut);
                     }
                     ArbiterCommand::ExecuteFn(f) => {
                         f.call_box();
@@ -359,4 +398,78 @@ mod tests {
         assert!(Arbiter::contains_item::<&'static str>());
         assert!(format!("{:?}", Arbiter::current()).contains("Arbiter"));
     }
+
+    #[test]
+    fn test_arbiter_name() {
+        let s = System::new("test");
+        let arb = Arbiter::with_name("test-worker".to_string());
+        let name = s
+            .block_on(arb.exec(|| thread::current().name().map(|n| n.to_string())))
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("test-worker"));
+        arb.stop();
+    }
+
+    #[test]
+    fn test_spawn_supervised() {
+        use crate::SupervisorEvent::*;
+        use std::sync::{atomic::AtomicUsize, Arc};
+        use std::time::Duration;
+
+        let s = System::new("test");
+        let events = System::current().subscribe();
+        let arb = Arbiter::new();
+
+        let cnt = Arc::new(AtomicUsize::new(0));
+        let cnt2 = cnt.clone();
+        let policy = RestartPolicy::new()
+            .backoff(Duration::from_millis(1), Duration::from_millis(5));
+
+        let (id1, id2, received) = s.block_on(async move {
+            let mut received = Vec::new();
+            let id1 = arb.spawn_supervised(
+                move || {
+                    let num = cnt2.fetch_add(1, Ordering::Relaxed);
+                    async move {
+                        if num < 2 {
+                            panic!("task failed");
+                        }
+                    }
+                },
+                policy,
+            );
+            for _ in 0..6 {
+                received.push(events.recv().await.unwrap());
+            }
+
+            // max restarts
+            let id2 = arb.spawn_supervised(
+                || async { panic!("task failed") },
+                policy.max_restarts(1),
+            );
+            for _ in 0..5 {
+                received.push(events.recv().await.unwrap());
+            }
+            arb.stop();
+            (id1, id2, received)
+        });
+
+        assert_eq!(
+            received,
+            vec![
+                Started(id1),
+                Panicked(id1),
+                Restarted(id1, 1),
+                Panicked(id1),
+                Restarted(id1, 2),
+                Completed(id1),
+                Started(id2),
+                Panicked(id2),
+                Restarted(id2, 1),
+                Panicked(id2),
+                Failed(id2),
+            ]
+        );
+        assert_eq!(cnt.load(Ordering::Relaxed), 3);
+    }
 }
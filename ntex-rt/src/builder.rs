@@ -1,9 +1,11 @@
-use std::{cell::RefCell, future::Future, io, rc::Rc};
+use std::{cell::RefCell, future::Future, io, rc::Rc, sync::Arc, time::Duration};
 
 use async_channel::unbounded;
 use async_oneshot as oneshot;
 
 use crate::arbiter::{Arbiter, ArbiterController, SystemArbiter};
+use crate::metrics::{SlowPoll, SlowPollCallback, SlowPollConfig};
+use crate::runtime::{Runtime, RuntimeRef};
 use crate::System;
 
 /// Builder struct for a ntex runtime.
@@ -16,6 +18,11 @@ pub struct Builder {
     name: String,
     /// Whether the Arbiter will stop the whole System on uncaught panic. Defaults to false.
     stop_on_panic: bool,
+    /// Async runtime. Defaults to runtime selected by cargo features.
+    runtime: RuntimeRef,
+    /// Slow poll threshold. Defaults to None.
+    slow_poll: Option<Duration>,
+    slow_poll_callback: Option<SlowPollCallback>,
 }
 
 impl Builder {
@@ -23,6 +30,9 @@ impl Builder {
         Builder {
             name: "ntex".into(),
             stop_on_panic: false,
+            runtime: RuntimeRef::default(),
+            slow_poll: None,
+            slow_poll_callback: None,
         }
     }
 
@@ -41,6 +51,35 @@ impl Builder {
         self
     }
 
+    /// Sets async runtime for system and arbiter threads.
+    ///
+    /// By default runtime is selected by cargo features.
+    pub fn runtime<R: Runtime>(mut self, rt: R) -> Self {
+        self.runtime = RuntimeRef::new(rt);
+        self
+    }
+
+    /// Sets slow poll threshold for spawned tasks.
+    ///
+    /// Task polls exceeding threshold get logged and counted
+    /// in `TaskMetrics::slow_polls`. Measuring poll duration requires
+    /// reading system clock for every poll, so detector is disabled by default.
+    pub fn slow_poll_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_poll = Some(threshold);
+        self
+    }
+
+    /// Sets callback for slow polls.
+    ///
+    /// Callback is called on the thread where slow poll happened.
+    pub fn on_slow_poll<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&SlowPoll) + Send + Sync + 'static,
+    {
+        self.slow_poll_callback = Some(Arc::new(f));
+        self
+    }
+
     /// Create new System.
     ///
     /// This method panics if it can not create tokio runtime
@@ -50,7 +89,13 @@ impl Builder {
         let stop_on_panic = self.stop_on_panic;
 
         let (arb, arb_controller) = Arbiter::new_system();
-        let system = System::construct(sys_sender, arb, stop_on_panic);
+        let callback = self.slow_poll_callback;
+        let slow_poll = self.slow_poll.map(|threshold| SlowPollConfig {
+            threshold,
+            callback,
+        });
+        let system =
+            System::construct(sys_sender, arb, stop_on_panic, self.runtime, slow_poll);
 
         // system arbiter
         let arb = SystemArbiter::new(stop_tx, sys_receiver);
@@ -97,11 +142,11 @@ impl SystemRunner {
             stop,
             arb,
             arb_controller,
-            ..
+            system,
         } = self;
 
         // run loop
-        match block_on(stop, arb, arb_controller, f).take()? {
+        match block_on(system.runtime(), stop, arb, arb_controller, f).take()? {
             Ok(code) => {
                 if code != 0 {
                     Err(io::Error::new(
@@ -126,11 +171,12 @@ impl SystemRunner {
         let SystemRunner {
             arb,
             arb_controller,
+            system,
             ..
         } = self;
 
         // run loop
-        match block_on(fut, arb, arb_controller, || Ok(())).take() {
+        match block_on(system.runtime(), fut, arb, arb_controller, || Ok(())).take() {
             Ok(result) => result,
             Err(_) => unreachable!(),
         }
@@ -147,6 +193,7 @@ impl<T> BlockResult<T> {
 
 #[inline]
 fn block_on<F, R, F1>(
+    rt: &RuntimeRef,
     fut: F,
     arb: SystemArbiter,
     arb_controller: ArbiterController,
@@ -159,16 +206,17 @@ where
 {
     let result = Rc::new(RefCell::new(None));
     let result_inner = result.clone();
-    crate::block_on(Box::pin(async move {
-        crate::spawn(arb);
-        crate::spawn(arb_controller);
+    let rt2 = rt.clone();
+    rt.block_on(async move {
+        rt2.spawn(arb);
+        rt2.spawn(arb_controller);
         if let Err(e) = f() {
             *result_inner.borrow_mut() = Some(Err(e));
         } else {
             let r = fut.await;
             *result_inner.borrow_mut() = Some(Ok(r));
         }
-    }));
+    });
     BlockResult(result)
 }
 
@@ -212,4 +260,73 @@ mod tests {
         let id2 = rx.recv().unwrap();
         assert_eq!(id, id2);
     }
+
+    #[test]
+    fn test_custom_runtime() {
+        use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc};
+
+        use crate::{DefaultRuntime, LocalBoxFuture, Runtime};
+
+        #[derive(Clone, Default)]
+        struct Counting(Arc<AtomicUsize>, Arc<AtomicUsize>);
+
+        impl Runtime for Counting {
+            fn block_on(&self, fut: LocalBoxFuture) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                DefaultRuntime.block_on(fut)
+            }
+
+            fn spawn(&self, fut: LocalBoxFuture) {
+                self.1.fetch_add(1, Ordering::Relaxed);
+                DefaultRuntime.spawn(fut)
+            }
+        }
+
+        let rt = Counting::default();
+        let sys = System::build().runtime(rt.clone()).finish();
+        let arb = crate::Arbiter::new();
+        let res = sys.block_on(arb.exec(|| 10)).unwrap();
+        assert_eq!(res, 10);
+        arb.stop();
+
+        // system and arbiter threads
+        assert_eq!(rt.0.load(Ordering::Relaxed), 2);
+        assert!(rt.1.load(Ordering::Relaxed) >= 3);
+    }
+
+    #[test]
+    fn test_runtime_sleep() {
+        use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc};
+        use std::time::{Duration, Instant};
+
+        use crate::{DefaultRuntime, LocalBoxFuture, Runtime};
+
+        #[derive(Clone, Default)]
+        struct Counting(Arc<AtomicUsize>);
+
+        impl Runtime for Counting {
+            fn block_on(&self, fut: LocalBoxFuture) {
+                DefaultRuntime.block_on(fut)
+            }
+
+            fn spawn(&self, fut: LocalBoxFuture) {
+                DefaultRuntime.spawn(fut)
+            }
+
+            fn sleep(&self, dur: Duration) -> LocalBoxFuture {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                DefaultRuntime.sleep(dur)
+            }
+        }
+
+        let rt = Counting::default();
+        let sys = System::build().runtime(rt.clone()).finish();
+        let elapsed = sys.block_on(async {
+            let start = Instant::now();
+            crate::sleep(Duration::from_millis(10)).await;
+            start.elapsed()
+        });
+        assert!(elapsed >= Duration::from_millis(10));
+        assert_eq!(rt.0.load(Ordering::Relaxed), 1);
+    }
 }
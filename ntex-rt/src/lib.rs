@@ -1,12 +1,29 @@
 //! A runtime implementation that runs everything on the current thread.
 mod arbiter;
 mod builder;
+mod metrics;
+mod runtime;
+mod shutdown;
+mod supervisor;
 mod system;
 
 pub use self::arbiter::Arbiter;
 pub use self::builder::{Builder, SystemRunner};
+pub use self::metrics::{SlowPoll, TaskMetrics};
+pub use self::runtime::{sleep, DefaultRuntime, LocalBoxFuture, Runtime};
+pub use self::shutdown::{ShutdownHandle, ShutdownPhase};
+pub use self::supervisor::{RestartPolicy, SupervisorEvent, SupervisorEvents};
 pub use self::system::System;
 
+#[cfg(feature = "tokio")]
+pub use self::runtime::TokioRuntime;
+
+#[cfg(all(feature = "glommio", target_os = "linux"))]
+pub use self::runtime::GlommioRuntime;
+
+#[cfg(feature = "async-std")]
+pub use self::runtime::AsyncStdRuntime;
+
 #[allow(dead_code)]
 #[cfg(all(feature = "glommio", target_os = "linux"))]
 mod glommio {
@@ -42,10 +59,10 @@ mod glommio {
     {
         JoinHandle {
             fut: Either::Left(
-                Task::local(async move {
+                Task::local(crate::metrics::Instrumented::new(async move {
                     let _ = Task::<()>::later().await;
                     f.await
-                })
+                }))
                 .detach(),
             ),
         }
@@ -165,7 +182,7 @@ mod tokio {
     where
         F: Future + 'static,
     {
-        tok_io::task::spawn_local(f)
+        tok_io::task::spawn_local(crate::metrics::Instrumented::new(f))
     }
 
     /// Executes a future on the current thread. This does not create a new Arbiter
@@ -210,7 +227,7 @@ mod asyncstd {
         F: Future + 'static,
     {
         JoinHandle {
-            fut: async_std::task::spawn_local(f),
+            fut: async_std::task::spawn_local(crate::metrics::Instrumented::new(f)),
         }
     }
 
@@ -0,0 +1,186 @@
+//! Local task metrics
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{cell::Cell, cell::RefCell, fmt, future::Future, pin::Pin, sync::Arc};
+
+thread_local! {
+    static METRICS: Metrics = Metrics::default();
+}
+
+#[derive(Default)]
+struct Metrics {
+    alive: Cell<usize>,
+    spawned: Cell<u64>,
+    polls: Cell<u64>,
+    slow_polls: Cell<u64>,
+    slow_poll: RefCell<Option<SlowPollConfig>>,
+}
+
+/// Snapshot of current thread task metrics.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct TaskMetrics {
+    /// Number of alive spawned tasks
+    pub alive: usize,
+    /// Total number of spawned tasks
+    pub spawned: u64,
+    /// Total number of task polls
+    pub polls: u64,
+    /// Number of polls exceeded slow poll threshold
+    pub slow_polls: u64,
+}
+
+impl TaskMetrics {
+    /// Get task metrics of the current thread.
+    pub fn current() -> TaskMetrics {
+        METRICS.with(|m| TaskMetrics {
+            alive: m.alive.get(),
+            spawned: m.spawned.get(),
+            polls: m.polls.get(),
+            slow_polls: m.slow_polls.get(),
+        })
+    }
+}
+
+/// Slow poll information.
+#[derive(Debug, Copy, Clone)]
+pub struct SlowPoll {
+    /// Task id, unique within arbiter
+    pub task: u64,
+    /// Poll duration
+    pub duration: Duration,
+}
+
+pub(crate) type SlowPollCallback = Arc<dyn Fn(&SlowPoll) + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct SlowPollConfig {
+    pub(crate) threshold: Duration,
+    pub(crate) callback: Option<SlowPollCallback>,
+}
+
+impl fmt::Debug for SlowPollConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlowPollConfig")
+            .field("threshold", &self.threshold)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+/// Configure slow poll detector for current thread.
+pub(crate) fn configure(cfg: Option<SlowPollConfig>) {
+    METRICS.with(|m| *m.slow_poll.borrow_mut() = cfg);
+}
+
+pin_project_lite::pin_project! {
+    /// Spawned task wrapper, tracks task polls.
+    pub(crate) struct Instrumented<F> {
+        #[pin]
+        fut: F,
+        id: u64,
+        _alive: Alive,
+    }
+}
+
+struct Alive;
+
+impl Drop for Alive {
+    fn drop(&mut self) {
+        let _ = METRICS.try_with(|m| m.alive.set(m.alive.get().saturating_sub(1)));
+    }
+}
+
+impl<F> Instrumented<F> {
+    pub(crate) fn new(fut: F) -> Self {
+        let id = METRICS.with(|m| {
+            let id = m.spawned.get();
+            m.spawned.set(id + 1);
+            m.alive.set(m.alive.get() + 1);
+            id
+        });
+        Instrumented {
+            fut,
+            id,
+            _alive: Alive,
+        }
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let threshold = METRICS.with(|m| {
+            m.polls.set(m.polls.get() + 1);
+            m.slow_poll.borrow().as_ref().map(|cfg| cfg.threshold)
+        });
+
+        if let Some(threshold) = threshold {
+            let start = Instant::now();
+            let result = this.fut.poll(cx);
+            let duration = start.elapsed();
+            if duration >= threshold {
+                let info = SlowPoll {
+                    task: *this.id,
+                    duration,
+                };
+                log::warn!(
+                    "Slow poll detected in {:?}: task {} took {:?}",
+                    std::thread::current().name().unwrap_or("unnamed"),
+                    info.task,
+                    info.duration
+                );
+                let callback = METRICS.with(|m| {
+                    m.slow_polls.set(m.slow_polls.get() + 1);
+                    m.slow_poll
+                        .borrow()
+                        .as_ref()
+                        .and_then(|cfg| cfg.callback.clone())
+                });
+                if let Some(callback) = callback {
+                    (*callback)(&info);
+                }
+            }
+            result
+        } else {
+            this.fut.poll(cx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::System;
+
+    #[test]
+    fn test_task_metrics() {
+        let cnt = Arc::new(AtomicUsize::new(0));
+        let cnt2 = cnt.clone();
+        let sys = System::build()
+            .slow_poll_threshold(Duration::from_millis(10))
+            .on_slow_poll(move |info| {
+                assert!(info.duration >= Duration::from_millis(10));
+                cnt2.fetch_add(1, Ordering::Relaxed);
+            })
+            .finish();
+
+        let (before, after) = sys.block_on(async {
+            let before = TaskMetrics::current();
+            let _ = crate::spawn(async {
+                std::thread::sleep(Duration::from_millis(20));
+            })
+            .await;
+            (before, TaskMetrics::current())
+        });
+
+        assert_eq!(after.spawned, before.spawned + 1);
+        assert_eq!(after.alive, before.alive);
+        assert!(after.polls > before.polls);
+        assert_eq!(after.slow_polls, before.slow_polls + 1);
+        assert_eq!(cnt.load(Ordering::Relaxed), 1);
+    }
+}
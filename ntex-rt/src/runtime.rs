@@ -0,0 +1,253 @@
+//! Async runtime backends
+use std::{fmt, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use crate::system::System;
+
+/// Boxed local future executed by the runtime.
+pub type LocalBoxFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Single-threaded executor backend.
+///
+/// Runtime is responsible for constructing executor for system and
+/// arbiter threads. Each thread gets its own executor instance.
+///
+/// Runtime also provides timer that drives `ntex` timer wheel. Networking
+/// primitives (`tcp_connect`, `from_tcp_stream`, etc) and signal handling are
+/// not part of this trait, they are provided by backend crates (`ntex-tokio`,
+/// `ntex-glommio`, `ntex-async-std`) and get selected by cargo features, so
+/// runtime must be compatible with enabled runtime feature.
+pub trait Runtime: Send + Sync + 'static {
+    /// Runs the provided future, blocking the current thread until the future
+    /// completes.
+    fn block_on(&self, fut: LocalBoxFuture);
+
+    /// Spawn a future on the current thread.
+    ///
+    /// This method is called only from within `block_on()` context.
+    fn spawn(&self, fut: LocalBoxFuture);
+
+    /// Create future that completes after `dur` elapses.
+    ///
+    /// This method is called only from within `block_on()` context. Default
+    /// implementation uses runtime agnostic `futures-timer` thread.
+    fn sleep(&self, dur: Duration) -> LocalBoxFuture {
+        Box::pin(futures_timer::Delay::new(dur))
+    }
+}
+
+/// Create future that completes after `dur` elapses.
+///
+/// Uses timer of the current system's runtime. This is low level primitive
+/// for timer drivers, use `ntex::time` for application timers.
+pub fn sleep(dur: Duration) -> LocalBoxFuture {
+    match System::try_current() {
+        Some(sys) => sys.runtime().sleep(dur),
+        None => Box::pin(futures_timer::Delay::new(dur)),
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RuntimeRef(Arc<dyn Runtime>);
+
+impl RuntimeRef {
+    pub(crate) fn new<R: Runtime>(rt: R) -> Self {
+        RuntimeRef(Arc::new(rt))
+    }
+
+    pub(crate) fn block_on<F>(&self, fut: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.0.block_on(Box::pin(fut))
+    }
+
+    pub(crate) fn spawn<F>(&self, fut: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.0.spawn(Box::pin(fut))
+    }
+
+    pub(crate) fn sleep(&self, dur: Duration) -> LocalBoxFuture {
+        self.0.sleep(dur)
+    }
+}
+
+impl Default for RuntimeRef {
+    fn default() -> Self {
+        RuntimeRef(Arc::new(DefaultRuntime))
+    }
+}
+
+impl fmt::Debug for RuntimeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Runtime").finish()
+    }
+}
+
+/// Default runtime.
+///
+/// Uses runtime selected by cargo features. `tokio` has higher priority,
+/// then `async-std` and `glommio`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DefaultRuntime;
+
+impl Runtime for DefaultRuntime {
+    fn block_on(&self, fut: LocalBoxFuture) {
+        crate::block_on(fut)
+    }
+
+    fn spawn(&self, fut: LocalBoxFuture) {
+        drop(crate::spawn(fut));
+    }
+
+    #[cfg(feature = "tokio")]
+    fn sleep(&self, dur: Duration) -> LocalBoxFuture {
+        Box::pin(tok_io::time::sleep(dur))
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio::TokioRuntime;
+
+#[cfg(feature = "tokio")]
+mod tokio {
+    use std::time::Duration;
+
+    use super::{LocalBoxFuture, Runtime};
+    use crate::metrics::Instrumented;
+
+    /// Tokio current-thread runtime.
+    #[derive(Debug, Copy, Clone, Default)]
+    pub struct TokioRuntime {
+        event_interval: Option<u32>,
+    }
+
+    impl TokioRuntime {
+        /// Create tokio runtime with default settings.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Number of scheduler ticks after which the scheduler will poll for
+        /// external events (timers, I/O, and so on).
+        pub fn event_interval(mut self, val: u32) -> Self {
+            self.event_interval = Some(val);
+            self
+        }
+    }
+
+    impl Runtime for TokioRuntime {
+        fn block_on(&self, fut: LocalBoxFuture) {
+            let mut builder = tok_io::runtime::Builder::new_current_thread();
+            builder.enable_all();
+            if let Some(val) = self.event_interval {
+                builder.event_interval(val);
+            }
+            let rt = builder.build().unwrap();
+            tok_io::task::LocalSet::new().block_on(&rt, fut);
+        }
+
+        fn spawn(&self, fut: LocalBoxFuture) {
+            tok_io::task::spawn_local(Instrumented::new(fut));
+        }
+
+        fn sleep(&self, dur: Duration) -> LocalBoxFuture {
+            Box::pin(tok_io::time::sleep(dur))
+        }
+    }
+}
+
+#[cfg(all(feature = "glommio", target_os = "linux"))]
+pub use self::glommio::GlommioRuntime;
+
+#[cfg(all(feature = "glommio", target_os = "linux"))]
+mod glommio {
+    use std::time::Duration;
+
+    use glomm_io::{LocalExecutorBuilder, Task};
+
+    use super::{LocalBoxFuture, Runtime};
+    use crate::metrics::Instrumented;
+
+    /// Glommio io-uring based runtime.
+    ///
+    /// Requires linux kernel with io_uring support.
+    #[derive(Debug, Copy, Clone, Default)]
+    pub struct GlommioRuntime {
+        io_memory: Option<usize>,
+        preempt_timer: Option<Duration>,
+    }
+
+    impl GlommioRuntime {
+        /// Create glommio runtime with default settings.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Amount of memory to reserve for io_uring registered buffers.
+        pub fn io_memory(mut self, size: usize) -> Self {
+            self.io_memory = Some(size);
+            self
+        }
+
+        /// Time slice after which task queues yield to io processing.
+        pub fn preempt_timer(mut self, dur: Duration) -> Self {
+            self.preempt_timer = Some(dur);
+            self
+        }
+    }
+
+    impl Runtime for GlommioRuntime {
+        fn block_on(&self, fut: LocalBoxFuture) {
+            let mut builder = LocalExecutorBuilder::default();
+            if let Some(size) = self.io_memory {
+                builder = builder.io_memory(size);
+            }
+            if let Some(dur) = self.preempt_timer {
+                builder = builder.preempt_timer(dur);
+            }
+            let ex = builder
+                .make()
+                .unwrap_or_else(|e| panic!("Cannot create glommio executor: {}", e));
+            ex.run(fut)
+        }
+
+        fn spawn(&self, fut: LocalBoxFuture) {
+            Task::local(Instrumented::new(fut)).detach();
+        }
+
+        fn sleep(&self, dur: Duration) -> LocalBoxFuture {
+            Box::pin(glomm_io::timer::sleep(dur))
+        }
+    }
+}
+
+#[cfg(feature = "async-std")]
+pub use self::asyncstd::AsyncStdRuntime;
+
+#[cfg(feature = "async-std")]
+mod asyncstd {
+    use std::time::Duration;
+
+    use super::{LocalBoxFuture, Runtime};
+    use crate::metrics::Instrumented;
+
+    /// Async-std runtime.
+    #[derive(Debug, Copy, Clone, Default)]
+    pub struct AsyncStdRuntime;
+
+    impl Runtime for AsyncStdRuntime {
+        fn block_on(&self, fut: LocalBoxFuture) {
+            async_std::task::block_on(fut);
+        }
+
+        fn spawn(&self, fut: LocalBoxFuture) {
+            async_std::task::spawn_local(Instrumented::new(fut));
+        }
+
+        fn sleep(&self, dur: Duration) -> LocalBoxFuture {
+            Box::pin(async_std::task::sleep(dur))
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! System shutdown orchestration
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::{fmt, future::Future, mem, pin::Pin, time::Duration};
+
+use async_oneshot as oneshot;
+
+use crate::{arbiter::Arbiter, supervisor::delay, system::System};
+
+type Hook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+/// Shutdown phase.
+///
+/// Phases run in order, hooks of the next phase start after
+/// all hooks of the previous phase complete.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShutdownPhase {
+    /// Stop accepting new work
+    StopAccepting,
+    /// Wait for in-flight work to complete
+    Drain,
+    /// Release resources
+    Finalize,
+}
+
+const PHASES: [ShutdownPhase; 3] = [
+    ShutdownPhase::StopAccepting,
+    ShutdownPhase::Drain,
+    ShutdownPhase::Finalize,
+];
+
+/// System shutdown handle.
+///
+/// Components register async shutdown hooks, hooks are executed
+/// on the arbiter where they get registered. `ShutdownHandle::shutdown()`
+/// runs all hooks phase by phase and then stops the system.
+///
+/// Default shutdown timeout is 30 seconds.
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<Inner>);
+
+struct Inner {
+    started: AtomicBool,
+    timeout: Mutex<Duration>,
+    hooks: Mutex<Vec<(ShutdownPhase, Arbiter, Hook)>>,
+}
+
+impl fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownHandle")
+            .field("started", &self.0.started.load(Ordering::Relaxed))
+            .field("timeout", &*self.0.timeout.lock().unwrap())
+            .finish()
+    }
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> Self {
+        ShutdownHandle(Arc::new(Inner {
+            started: AtomicBool::new(false),
+            timeout: Mutex::new(Duration::from_secs(30)),
+            hooks: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// Set overall shutdown deadline.
+    ///
+    /// Hooks that do not complete within deadline are abandoned.
+    pub fn set_timeout(&self, timeout: Duration) {
+        *self.0.timeout.lock().unwrap() = timeout;
+    }
+
+    /// Check if shutdown is started
+    pub fn is_shutting_down(&self) -> bool {
+        self.0.started.load(Ordering::Acquire)
+    }
+
+    /// Register async shutdown hook.
+    ///
+    /// Hook is executed on the current thread's arbiter.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if arbiter is not running.
+    pub fn on_shutdown<F, R>(&self, phase: ShutdownPhase, f: F)
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Future<Output = ()> + 'static,
+    {
+        let hook: Hook = Box::new(move || Box::pin(f()));
+        self.0
+            .hooks
+            .lock()
+            .unwrap()
+            .push((phase, Arbiter::current(), hook));
+    }
+
+    /// Run shutdown hooks and stop the system.
+    ///
+    /// Returns `false` if deadline is reached before all hooks complete,
+    /// or if shutdown is already in progress.
+    pub async fn shutdown(&self) -> bool {
+        if self.0.started.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        let timeout = *self.0.timeout.lock().unwrap();
+
+        let completed = Deadline {
+            fut: Box::pin(self.clone().run_hooks()),
+            deadline: Box::pin(delay(timeout)),
+        }
+        .await;
+        if !completed {
+            log::warn!("Shutdown timeout is reached, stopping system");
+        }
+        System::current().stop();
+        completed
+    }
+
+    async fn run_hooks(self) {
+        for phase in PHASES.iter() {
+            log::trace!("Running {:?} shutdown phase", phase);
+
+            // hooks could be registered by other hooks
+            loop {
+                let hooks: Vec<_> = {
+                    let mut all = self.0.hooks.lock().unwrap();
+                    let (hooks, rest) = mem::take(&mut *all)
+                        .into_iter()
+                        .partition(|(p, _, _)| p == phase);
+                    *all = rest;
+                    hooks
+                };
+                if hooks.is_empty() {
+                    break;
+                }
+
+                let waiters: Vec<_> = hooks
+                    .into_iter()
+                    .map(|(_, arb, hook)| {
+                        let (mut tx, rx) = oneshot::oneshot();
+                        arb.exec_fn(move || {
+                            System::current().runtime().spawn(async move {
+                                hook().await;
+                                let _ = tx.send(());
+                            });
+                        });
+                        rx
+                    })
+                    .collect();
+                for rx in waiters {
+                    let _ = rx.await;
+                }
+            }
+        }
+    }
+}
+
+struct Deadline {
+    fut: Pin<Box<dyn Future<Output = ()>>>,
+    deadline: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl Future for Deadline {
+    type Output = bool;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.fut.as_mut().poll(cx).is_ready() {
+            Poll::Ready(true)
+        } else if self.deadline.as_mut().poll(cx).is_ready() {
+            Poll::Ready(false)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_shutdown() {
+        let s = System::new("test");
+        let handle = System::current().shutdown_handle();
+        let arb = Arbiter::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        for (phase, name) in [
+            (ShutdownPhase::Finalize, "finalize"),
+            (ShutdownPhase::StopAccepting, "stop"),
+        ] {
+            let log = log.clone();
+            handle.on_shutdown(phase, move || async move {
+                log.lock().unwrap().push(name);
+            });
+        }
+
+        // register hook on other arbiter
+        let (tx, rx) = mpsc::channel();
+        let log2 = log.clone();
+        arb.exec_fn(move || {
+            System::current().shutdown_handle().on_shutdown(
+                ShutdownPhase::Drain,
+                move || async move {
+                    delay(Duration::from_millis(25)).await;
+                    log2.lock().unwrap().push("drain");
+                },
+            );
+            let _ = tx.send(());
+        });
+        rx.recv().unwrap();
+
+        let h = handle.clone();
+        assert!(!handle.is_shutting_down());
+        assert!(s.block_on(async move { h.shutdown().await }));
+        assert!(handle.is_shutting_down());
+        assert_eq!(*log.lock().unwrap(), vec!["stop", "drain", "finalize"]);
+        arb.stop();
+    }
+
+    #[test]
+    fn test_shutdown_timeout() {
+        let s = System::new("test");
+        let handle = System::current().shutdown_handle();
+        handle.set_timeout(Duration::from_millis(50));
+        handle.on_shutdown(ShutdownPhase::Drain, || delay(Duration::from_secs(10)));
+        assert!(format!("{:?}", handle).contains("ShutdownHandle"));
+
+        let h = handle.clone();
+        let (res1, res2) = s.block_on(async move {
+            let res1 = h.shutdown().await;
+            (res1, h.shutdown().await)
+        });
+        assert!(!res1);
+        assert!(!res2);
+    }
+}
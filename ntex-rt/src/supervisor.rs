@@ -0,0 +1,209 @@
+//! Supervised arbiter tasks
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::{cmp, future::Future, pin::Pin, thread, time::Duration};
+
+use async_channel::Receiver;
+use async_oneshot as oneshot;
+use futures_core::stream::Stream;
+
+use crate::system::System;
+
+static WORKER_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Restart policy for supervised tasks.
+///
+/// Panicked task is re-created after backoff delay. Delay starts with
+/// initial value and doubles after each restart, up to the max delay.
+///
+/// By default, task is restarted up to 10 times, backoff starts with 100 millis
+/// and is limited to 30 seconds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RestartPolicy {
+    max_restarts: Option<usize>,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            max_restarts: Some(10),
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Create default restart policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Never restart panicked task
+    pub fn never() -> Self {
+        Self::default().max_restarts(0)
+    }
+
+    /// Set max number of restarts.
+    pub fn max_restarts(mut self, num: usize) -> Self {
+        self.max_restarts = Some(num);
+        self
+    }
+
+    /// Restart panicked task without limits.
+    pub fn unlimited(mut self) -> Self {
+        self.max_restarts = None;
+        self
+    }
+
+    /// Set initial and max backoff delay.
+    ///
+    /// Max delay is set to initial delay if it is smaller.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = cmp::max(initial, max);
+        self
+    }
+
+    /// Backoff delay before next restart
+    fn delay(&self, restarts: usize) -> Duration {
+        let factor = 1u32.checked_shl(restarts as u32).unwrap_or(u32::MAX);
+        cmp::min(
+            self.backoff.checked_mul(factor).unwrap_or(self.max_backoff),
+            self.max_backoff,
+        )
+    }
+}
+
+/// Lifecycle event of supervised task
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SupervisorEvent {
+    /// Task is started
+    Started(usize),
+    /// Task panicked
+    Panicked(usize),
+    /// Task is restarted, number of restarts
+    Restarted(usize, usize),
+    /// Task is completed
+    Completed(usize),
+    /// Task exceeded max number of restarts
+    Failed(usize),
+}
+
+/// Stream of lifecycle events of supervised tasks
+#[derive(Debug)]
+pub struct SupervisorEvents(pub(crate) Receiver<SupervisorEvent>);
+
+impl SupervisorEvents {
+    /// Receive next event
+    pub async fn recv(&self) -> Option<SupervisorEvent> {
+        self.0.recv().await.ok()
+    }
+}
+
+impl Stream for SupervisorEvents {
+    type Item = SupervisorEvent;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+pub(crate) fn next_id() -> usize {
+    WORKER_COUNT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Run tasks created by factory until task completes or
+/// max number of restarts is reached
+pub(crate) async fn supervise<F, R>(id: usize, factory: F, policy: RestartPolicy)
+where
+    F: Fn() -> R,
+    R: Future<Output = ()>,
+{
+    let mut restarts = 0;
+    System::current().notify(SupervisorEvent::Started(id));
+
+    loop {
+        let result = match catch_unwind(AssertUnwindSafe(&factory)) {
+            Ok(fut) => CatchUnwind { fut }.await,
+            Err(_) => Err(()),
+        };
+
+        if result.is_ok() {
+            System::current().notify(SupervisorEvent::Completed(id));
+            return;
+        }
+        log::error!("Supervised task {} panicked", id);
+        System::current().notify(SupervisorEvent::Panicked(id));
+
+        if matches!(policy.max_restarts, Some(max) if restarts >= max) {
+            log::error!("Supervised task {} exceeded max number of restarts", id);
+            System::current().notify(SupervisorEvent::Failed(id));
+            return;
+        }
+        delay(policy.delay(restarts)).await;
+
+        restarts += 1;
+        System::current().notify(SupervisorEvent::Restarted(id, restarts));
+    }
+}
+
+/// Runtime independent delay
+pub(crate) async fn delay(dur: Duration) {
+    if dur != Duration::ZERO {
+        let (mut tx, rx) = oneshot::oneshot();
+        thread::spawn(move || {
+            thread::sleep(dur);
+            let _ = tx.send(());
+        });
+        let _ = rx.await;
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct CatchUnwind<F> {
+        #[pin]
+        fut: F,
+    }
+}
+
+impl<F: Future<Output = ()>> Future for CatchUnwind<F> {
+    type Output = Result<(), ()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.project().fut;
+        match catch_unwind(AssertUnwindSafe(|| fut.poll(cx))) {
+            Ok(Poll::Ready(())) => Poll::Ready(Ok(())),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(_) => Poll::Ready(Err(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RestartPolicy::new()
+            .backoff(Duration::from_millis(10), Duration::from_millis(50));
+        assert_eq!(policy.delay(0), Duration::from_millis(10));
+        assert_eq!(policy.delay(1), Duration::from_millis(20));
+        assert_eq!(policy.delay(2), Duration::from_millis(40));
+        assert_eq!(policy.delay(3), Duration::from_millis(50));
+        assert_eq!(policy.delay(100), Duration::from_millis(50));
+
+        let policy = RestartPolicy::new()
+            .backoff(Duration::from_millis(10), Duration::from_millis(5));
+        assert_eq!(policy.delay(1), Duration::from_millis(10));
+        assert_eq!(RestartPolicy::never().max_restarts, Some(0));
+        assert_eq!(RestartPolicy::new().unlimited().max_restarts, None);
+    }
+}
@@ -1,8 +1,13 @@
-use async_channel::Sender;
-use std::{cell::RefCell, io, sync::atomic::AtomicUsize, sync::atomic::Ordering};
+use async_channel::{unbounded, Sender};
+use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc, Mutex};
+use std::{cell::RefCell, io};
 
 use super::arbiter::{Arbiter, SystemCommand};
 use super::builder::{Builder, SystemRunner};
+use super::metrics::{self, SlowPollConfig};
+use super::runtime::RuntimeRef;
+use super::shutdown::ShutdownHandle;
+use super::supervisor::{SupervisorEvent, SupervisorEvents};
 
 static SYSTEM_COUNT: AtomicUsize = AtomicUsize::new(0);
 
@@ -13,6 +18,10 @@ pub struct System {
     sys: Sender<SystemCommand>,
     arbiter: Arbiter,
     stop_on_panic: bool,
+    subscribers: Arc<Mutex<Vec<Sender<SupervisorEvent>>>>,
+    shutdown: ShutdownHandle,
+    runtime: RuntimeRef,
+    slow_poll: Option<SlowPollConfig>,
 }
 
 thread_local!(
@@ -25,11 +34,17 @@ impl System {
         sys: Sender<SystemCommand>,
         arbiter: Arbiter,
         stop_on_panic: bool,
+        runtime: RuntimeRef,
+        slow_poll: Option<SlowPollConfig>,
     ) -> Self {
         let sys = System {
             sys,
             arbiter,
             stop_on_panic,
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            shutdown: ShutdownHandle::new(),
+            runtime,
+            slow_poll,
             id: SYSTEM_COUNT.fetch_add(1, Ordering::SeqCst),
         };
         System::set_current(sys.clone());
@@ -60,9 +75,15 @@ impl System {
         })
     }
 
+    /// Get current running system, if any.
+    pub fn try_current() -> Option<System> {
+        CURRENT.with(|cell| cell.borrow().clone())
+    }
+
     /// Set current running system.
     #[doc(hidden)]
     pub fn set_current(sys: System) {
+        metrics::configure(sys.slow_poll.clone());
         CURRENT.with(|s| {
             *s.borrow_mut() = Some(sys);
         })
@@ -93,6 +114,33 @@ impl System {
         self.stop_on_panic
     }
 
+    pub(super) fn runtime(&self) -> &RuntimeRef {
+        &self.runtime
+    }
+
+    /// Get system shutdown handle.
+    ///
+    /// Shutdown handle coordinates graceful shutdown of system components.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Subscribe to lifecycle events of supervised tasks.
+    ///
+    /// Each subscriber receives all events emitted after subscription.
+    pub fn subscribe(&self) -> SupervisorEvents {
+        let (tx, rx) = unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        SupervisorEvents(rx)
+    }
+
+    pub(super) fn notify(&self, event: SupervisorEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.try_send(event).is_ok());
+    }
+
     /// System arbiter
     pub fn arbiter(&self) -> &Arbiter {
         &self.arbiter
@@ -11,7 +11,7 @@ pub(super) struct Counter(Rc<CounterInner>);
 
 struct CounterInner {
     count: Cell<usize>,
-    capacity: usize,
+    capacity: Cell<usize>,
     task: LocalWaker,
 }
 
@@ -19,7 +19,7 @@ impl Counter {
     /// Create `Counter` instance and set max value.
     pub fn new(capacity: usize) -> Self {
         Counter(Rc::new(CounterInner {
-            capacity,
+            capacity: Cell::new(capacity),
             count: Cell::new(0),
             task: LocalWaker::new(),
         }))
@@ -35,6 +35,24 @@ impl Counter {
     pub fn available(&self, cx: &mut task::Context<'_>) -> bool {
         self.0.available(cx)
     }
+
+    /// Get total number of acquired counts
+    pub fn total(&self) -> usize {
+        self.0.count.get()
+    }
+
+    /// Get max value
+    pub fn capacity(&self) -> usize {
+        self.0.capacity.get()
+    }
+
+    /// Change max value, notify task if counter is not at capacity anymore.
+    pub fn set_capacity(&self, capacity: usize) {
+        let old = self.0.capacity.replace(capacity);
+        if capacity > old && self.0.count.get() >= old {
+            self.0.task.wake();
+        }
+    }
 }
 
 pub(super) struct CounterGuard(Rc<CounterInner>);
@@ -60,13 +78,13 @@ impl CounterInner {
     fn dec(&self) {
         let num = self.count.get();
         self.count.set(num - 1);
-        if num == self.capacity {
+        if num == self.capacity.get() {
             self.task.wake();
         }
     }
 
     fn available(&self, cx: &mut task::Context<'_>) -> bool {
-        if self.count.get() < self.capacity {
+        if self.count.get() < self.capacity.get() {
             true
         } else {
             self.task.register(cx.waker());
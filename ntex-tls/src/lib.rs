@@ -16,9 +16,23 @@ mod counter;
 /// All listeners will stop accepting connections when this limit is
 /// reached. It can be used to limit the global SSL CPU usage.
 ///
+/// New limit is applied to the current thread immediately, other threads
+/// use it if they did not start accepting ssl connections yet.
+///
 /// By default max connections is set to a 256.
 pub fn max_concurrent_ssl_accept(num: usize) {
     MAX_SSL_ACCEPT.store(num, Ordering::Relaxed);
+    MAX_SSL_ACCEPT_COUNTER.with(|conns| conns.set_capacity(num));
+}
+
+/// Number of in-flight ssl handshakes for current thread.
+pub fn ssl_accept_handshakes() -> usize {
+    MAX_SSL_ACCEPT_COUNTER.with(|conns| conns.total())
+}
+
+/// Maximum concurrent ssl handshakes for current thread.
+pub fn max_ssl_accept_handshakes() -> usize {
+    MAX_SSL_ACCEPT_COUNTER.with(|conns| conns.capacity())
 }
 
 static MAX_SSL_ACCEPT: AtomicUsize = AtomicUsize::new(256);
@@ -9,7 +9,7 @@ use tls_openssl::ssl::SslAcceptor;
 use crate::counter::{Counter, CounterGuard};
 use crate::MAX_SSL_ACCEPT_COUNTER;
 
-use super::{SslAcceptor as IoSslAcceptor, SslFilter};
+use super::{AcceptorHandle, SslAcceptor as IoSslAcceptor, SslFilter};
 
 /// Support `TLS` server connections via openssl package
 ///
@@ -35,6 +35,14 @@ impl<F> Acceptor<F> {
         self.acceptor.timeout(timeout);
         self
     }
+
+    /// Get handle for certificates reloading.
+    ///
+    /// Handle could be used from any thread, reloaded acceptor
+    /// is used by all workers for new connections.
+    pub fn handle(&self) -> AcceptorHandle {
+        self.acceptor.handle()
+    }
 }
 
 impl<F> From<SslAcceptor> for Acceptor<F> {
@@ -43,6 +51,15 @@ impl<F> From<SslAcceptor> for Acceptor<F> {
     }
 }
 
+impl<F> From<AcceptorHandle> for Acceptor<F> {
+    fn from(handle: AcceptorHandle) -> Self {
+        Acceptor {
+            acceptor: handle.into(),
+            _t: PhantomData,
+        }
+    }
+}
+
 impl<F> Clone for Acceptor<F> {
     fn clone(&self) -> Self {
         Self {
@@ -0,0 +1,132 @@
+use std::{fmt, sync::Arc};
+
+use tls_openssl::error::ErrorStack;
+use tls_openssl::ssl::{SslContextBuilder, SslVerifyMode};
+use tls_openssl::x509::{X509VerifyResult, X509};
+
+use crate::types::PeerCertificates;
+
+type RevocationCheck = Arc<dyn Fn(&PeerCertificates) -> bool + Send + Sync>;
+
+/// Client certificates verification (mutual tls)
+///
+/// Verified client certificates are available via `PeerCertificates`
+/// io query, http services could use `PeerInfo::peer_certificates()`.
+///
+/// ```rust,ignore
+/// let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+/// ClientAuth::new()
+///     .ca_pem(&std::fs::read("ca.pem")?)?
+///     .configure(&mut builder)?;
+/// ```
+pub struct ClientAuth {
+    ca: Vec<X509>,
+    required: bool,
+    depth: Option<u32>,
+    check: Option<RevocationCheck>,
+}
+
+impl Default for ClientAuth {
+    fn default() -> Self {
+        ClientAuth {
+            ca: Vec::new(),
+            required: true,
+            depth: None,
+            check: None,
+        }
+    }
+}
+
+impl ClientAuth {
+    /// Create client auth configuration, client certificate is required.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add trusted CA certificate
+    pub fn ca_certificate(mut self, cert: X509) -> Self {
+        self.ca.push(cert);
+        self
+    }
+
+    /// Add trusted CA certificates from PEM bundle
+    pub fn ca_pem(mut self, pem: &[u8]) -> Result<Self, ErrorStack> {
+        self.ca.extend(X509::stack_from_pem(pem)?);
+        Ok(self)
+    }
+
+    /// Do not fail handshake if client does not send certificate.
+    ///
+    /// Certificates are still verified if client sends them.
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    /// Set max depth of certificates chain
+    pub fn verify_depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Set certificates revocation check.
+    ///
+    /// Check is called after successful chain verification, i.e. it could be
+    /// used for CRL or OCSP lookups. Handshake fails if check returns `false`.
+    pub fn revocation_check<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&PeerCertificates) -> bool + Send + Sync + 'static,
+    {
+        self.check = Some(Arc::new(f));
+        self
+    }
+
+    /// Apply configuration to ssl context builder
+    pub fn configure(self, builder: &mut SslContextBuilder) -> Result<(), ErrorStack> {
+        for cert in self.ca {
+            builder.add_client_ca(&cert)?;
+            builder.cert_store_mut().add_cert(cert)?;
+        }
+        if let Some(depth) = self.depth {
+            builder.set_verify_depth(depth);
+        }
+
+        let mode = if self.required {
+            SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT
+        } else {
+            SslVerifyMode::PEER
+        };
+        if let Some(check) = self.check {
+            builder.set_verify_callback(mode, move |preverified, ctx| {
+                // chain is verified from root to peer certificate
+                if !preverified || ctx.error_depth() != 0 {
+                    return preverified;
+                }
+                let certs = ctx
+                    .chain()
+                    .map(|chain| chain.iter().filter_map(|c| c.to_der().ok()).collect())
+                    .unwrap_or_default();
+                if check(&PeerCertificates(certs)) {
+                    true
+                } else {
+                    ctx.set_error(X509VerifyResult::APPLICATION_VERIFICATION);
+                    false
+                }
+            });
+        } else {
+            builder.set_verify(mode);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ClientAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientAuth")
+            .field("ca", &self.ca.len())
+            .field("required", &self.required)
+            .field("depth", &self.depth)
+            .field("revocation_check", &self.check.is_some())
+            .finish()
+    }
+}
@@ -1,18 +1,22 @@
 #![allow(clippy::type_complexity)]
 //! An implementation of SSL streams for ntex backed by OpenSSL
 use std::cell::{Cell, RefCell};
-use std::{
-    any, cmp, error::Error, future::Future, io, pin::Pin, task::Context, task::Poll,
-};
+use std::sync::{Arc, RwLock};
+use std::{any, cmp, error::Error, fmt, future::Future, io, pin::Pin};
+use std::{task::Context, task::Poll};
 
 use ntex_bytes::{BufMut, BytesVec, PoolRef};
 use ntex_io::{Base, Filter, FilterFactory, Io, IoRef, ReadStatus, WriteStatus};
 use ntex_util::{future::poll_fn, ready, time, time::Millis};
 use tls_openssl::ssl::{self, SslStream};
-use tls_openssl::x509::X509;
+use tls_openssl::x509::{X509VerifyResult, X509};
 
 mod accept;
+mod auth;
+mod session;
 pub use self::accept::{Acceptor, AcceptorService};
+pub use self::auth::ClientAuth;
+pub use self::session::Resumption;
 
 use super::types;
 
@@ -91,6 +95,14 @@ impl<F: Filter> Filter for SslFilter<F> {
                 types::HttpProtocol::Http1
             };
             Some(Box::new(proto))
+        } else if id == any::TypeId::of::<types::AlpnProtocol>() {
+            self.inner
+                .borrow()
+                .ssl()
+                .selected_alpn_protocol()
+                .map(|proto| {
+                    Box::new(types::AlpnProtocol(proto.to_vec())) as Box<dyn any::Any>
+                })
         } else if id == any::TypeId::of::<PeerCert>() {
             if let Some(cert) = self.inner.borrow().ssl().peer_certificate() {
                 Some(Box::new(PeerCert(cert)))
@@ -105,6 +117,33 @@ impl<F: Filter> Filter for SslFilter<F> {
             } else {
                 None
             }
+        } else if id == any::TypeId::of::<types::PeerCertificates>() {
+            let inner = self.inner.borrow();
+            let ssl = inner.ssl();
+            if ssl.verify_result() != X509VerifyResult::OK {
+                return None;
+            }
+            ssl.peer_certificate().and_then(|cert| {
+                let mut certs = vec![cert.to_der().ok()?];
+                // server side chain does not contain peer certificate
+                if let Some(chain) = ssl.peer_cert_chain() {
+                    for cert in chain {
+                        let der = cert.to_der().ok()?;
+                        if der != certs[0] {
+                            certs.push(der);
+                        }
+                    }
+                }
+                Some(Box::new(types::PeerCertificates(certs)) as Box<dyn any::Any>)
+            })
+        } else if id == any::TypeId::of::<types::TlsInfo>() {
+            let inner = self.inner.borrow();
+            inner.ssl().current_cipher().map(|cipher| {
+                Box::new(types::TlsInfo {
+                    version: inner.ssl().version_str().to_string(),
+                    cipher: cipher.name().to_string(),
+                }) as Box<dyn any::Any>
+            })
         } else {
             self.inner.borrow().get_ref().inner.query(id)
         }
@@ -238,7 +277,7 @@ impl<F: Filter> Filter for SslFilter<F> {
 }
 
 pub struct SslAcceptor {
-    acceptor: ssl::SslAcceptor,
+    acceptor: Arc<RwLock<ssl::SslAcceptor>>,
     timeout: Millis,
 }
 
@@ -246,7 +285,7 @@ impl SslAcceptor {
     /// Create openssl acceptor filter factory
     pub fn new(acceptor: ssl::SslAcceptor) -> Self {
         SslAcceptor {
-            acceptor,
+            acceptor: Arc::new(RwLock::new(acceptor)),
             timeout: Millis(5_000),
         }
     }
@@ -258,6 +297,20 @@ impl SslAcceptor {
         self.timeout = timeout.into();
         self
     }
+
+    /// Get handle for acceptor reloading.
+    pub fn handle(&self) -> AcceptorHandle {
+        AcceptorHandle(self.acceptor.clone())
+    }
+}
+
+impl From<AcceptorHandle> for SslAcceptor {
+    fn from(handle: AcceptorHandle) -> Self {
+        SslAcceptor {
+            acceptor: handle.0,
+            timeout: Millis(5_000),
+        }
+    }
 }
 
 impl Clone for SslAcceptor {
@@ -269,6 +322,55 @@ impl Clone for SslAcceptor {
     }
 }
 
+/// Acceptor reload handle
+///
+/// Handle is shared between all acceptors created from it. Server factory
+/// is called for each worker, so handle should be created outside of
+/// the factory and converted into acceptor in each worker.
+/// New acceptor is used for new connections, existing connections
+/// keep using previous certificates.
+#[derive(Clone)]
+pub struct AcceptorHandle(Arc<RwLock<ssl::SslAcceptor>>);
+
+impl AcceptorHandle {
+    /// Create reload handle for acceptor.
+    ///
+    /// Acceptors created from the same handle share acceptor.
+    pub fn new(acceptor: ssl::SslAcceptor) -> Self {
+        AcceptorHandle(Arc::new(RwLock::new(acceptor)))
+    }
+
+    /// Replace acceptor, i.e. after certificates renewal.
+    pub fn reload(&self, acceptor: ssl::SslAcceptor) {
+        *self.0.write().unwrap() = acceptor;
+    }
+}
+
+impl fmt::Debug for AcceptorHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptorHandle").finish()
+    }
+}
+
+/// Select certificates by server name (SNI).
+///
+/// `resolver` is called during handshake with server name requested by client,
+/// returned context is used for the connection. If resolver returns `None`
+/// or client does not send server name, acceptor's default context is used.
+pub fn set_sni_resolver<F>(builder: &mut ssl::SslContextBuilder, resolver: F)
+where
+    F: Fn(&str) -> Option<ssl::SslContext> + Send + Sync + 'static,
+{
+    builder.set_servername_callback(move |ssl, _| {
+        let ctx = ssl.servername(ssl::NameType::HOST_NAME).and_then(&resolver);
+        if let Some(ctx) = ctx {
+            ssl.set_ssl_context(&ctx)
+                .map_err(|_| ssl::SniError::ALERT_FATAL)?;
+        }
+        Ok(())
+    });
+}
+
 impl<F: Filter> FilterFactory<F> for SslAcceptor {
     type Filter = SslFilter<F>;
 
@@ -277,7 +379,7 @@ impl<F: Filter> FilterFactory<F> for SslAcceptor {
 
     fn create(self, st: Io<F>) -> Self::Future {
         let timeout = self.timeout;
-        let ctx_result = ssl::Ssl::new(self.acceptor.context());
+        let ctx_result = ssl::Ssl::new(self.acceptor.read().unwrap().context());
 
         Box::pin(async move {
             time::timeout(timeout, async {
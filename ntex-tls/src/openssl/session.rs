@@ -0,0 +1,84 @@
+use tls_openssl::error::ErrorStack;
+use tls_openssl::ssl::{SslContextBuilder, SslOptions, SslSessionCacheMode};
+
+/// Tls session resumption settings
+///
+/// Resumed sessions skip certificates exchange and key agreement,
+/// which is the most expensive part of handshake.
+///
+/// Session ticket keys are generated by openssl for each ssl context.
+/// Keys could be rotated by reloading acceptor with new context
+/// via `AcceptorHandle::reload()`, tickets issued with previous keys
+/// fall back to full handshake.
+///
+/// ```rust,ignore
+/// let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+/// Resumption::new()
+///     .cache_size(10_000)
+///     .tickets(false)
+///     .configure(&mut builder)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Resumption {
+    cache_size: usize,
+    tickets: bool,
+    id_context: Vec<u8>,
+}
+
+impl Default for Resumption {
+    fn default() -> Self {
+        Resumption {
+            cache_size: 20_480,
+            tickets: true,
+            id_context: b"ntex".to_vec(),
+        }
+    }
+}
+
+impl Resumption {
+    /// Create resumption settings, session cache and tickets are enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set max number of sessions in server side session cache.
+    ///
+    /// Zero disables session cache. Default is set to 20480.
+    pub fn cache_size(mut self, size: usize) -> Self {
+        self.cache_size = size;
+        self
+    }
+
+    /// Enable or disable stateless session tickets.
+    ///
+    /// Tickets are enabled by default.
+    pub fn tickets(mut self, enabled: bool) -> Self {
+        self.tickets = enabled;
+        self
+    }
+
+    /// Set session id context.
+    ///
+    /// Sessions are resumed only for acceptors with the same context,
+    /// context is required if client certificates verification is enabled.
+    pub fn id_context(mut self, ctx: &[u8]) -> Self {
+        self.id_context = ctx.to_vec();
+        self
+    }
+
+    /// Apply settings to ssl context builder
+    pub fn configure(self, builder: &mut SslContextBuilder) -> Result<(), ErrorStack> {
+        if self.cache_size == 0 {
+            builder.set_session_cache_mode(SslSessionCacheMode::OFF);
+        } else {
+            builder.set_session_cache_mode(SslSessionCacheMode::SERVER);
+            builder.set_session_cache_size(self.cache_size as i32);
+        }
+        if self.tickets {
+            builder.clear_options(SslOptions::NO_TICKET);
+        } else {
+            builder.set_options(SslOptions::NO_TICKET);
+        }
+        builder.set_session_id_context(&self.id_context)
+    }
+}
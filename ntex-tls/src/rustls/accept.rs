@@ -7,12 +7,14 @@ use ntex_io::{Filter, FilterFactory, Io};
 use ntex_service::{Service, ServiceFactory};
 use ntex_util::{future::Ready, time::Millis};
 
-use super::{TlsAcceptor, TlsFilter};
+use super::{AcceptorHandle, TlsAcceptor, TlsFilter};
 use crate::{counter::Counter, counter::CounterGuard, MAX_SSL_ACCEPT_COUNTER};
 
-/// Support `SSL` connections via rustls package
+/// Support `TLS` server connections via rustls package
 ///
-/// `rust-tls` feature enables `RustlsAcceptor` type
+/// `rustls` feature enables `Acceptor` type. Number of concurrent
+/// handshakes is limited by [`max_concurrent_ssl_accept`](../fn.max_concurrent_ssl_accept.html)
+/// the same way as for openssl acceptor.
 pub struct Acceptor<F> {
     inner: TlsAcceptor,
     _t: PhantomData<F>,
@@ -34,6 +36,14 @@ impl<F> Acceptor<F> {
         self.inner.timeout(timeout.into());
         self
     }
+
+    /// Get handle for config reloading.
+    ///
+    /// Handle could be used from any thread, reloaded config
+    /// is used by all workers for new connections.
+    pub fn handle(&self) -> AcceptorHandle {
+        self.inner.handle()
+    }
 }
 
 impl<F> From<ServerConfig> for Acceptor<F> {
@@ -42,6 +52,15 @@ impl<F> From<ServerConfig> for Acceptor<F> {
     }
 }
 
+impl<F> From<AcceptorHandle> for Acceptor<F> {
+    fn from(handle: AcceptorHandle) -> Self {
+        Acceptor {
+            inner: handle.into(),
+            _t: PhantomData,
+        }
+    }
+}
+
 impl<F> Clone for Acceptor<F> {
     fn clone(&self) -> Self {
         Self {
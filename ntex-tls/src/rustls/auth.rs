@@ -0,0 +1,126 @@
+use std::{fmt, iter, sync::Arc, time::SystemTime};
+
+use tls_rust::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient,
+    ClientCertVerified, ClientCertVerifier,
+};
+use tls_rust::{Certificate, DistinguishedNames, Error, RootCertStore};
+
+use crate::types::PeerCertificates;
+
+type RevocationCheck = Arc<dyn Fn(&PeerCertificates) -> bool + Send + Sync>;
+
+/// Client certificates verification (mutual tls)
+///
+/// Verified client certificates are available via `PeerCertificates`
+/// io query, http services could use `PeerInfo::peer_certificates()`.
+///
+/// ```rust,ignore
+/// let config = ServerConfig::builder()
+///     .with_safe_defaults()
+///     .with_client_cert_verifier(ClientAuth::new(roots).verifier())
+///     .with_single_cert(certs, key)?;
+/// ```
+pub struct ClientAuth {
+    roots: RootCertStore,
+    required: bool,
+    check: Option<RevocationCheck>,
+}
+
+impl ClientAuth {
+    /// Create client auth configuration with trusted CA certificates,
+    /// client certificate is required.
+    pub fn new(roots: RootCertStore) -> Self {
+        ClientAuth {
+            roots,
+            required: true,
+            check: None,
+        }
+    }
+
+    /// Do not fail handshake if client does not send certificate.
+    ///
+    /// Certificates are still verified if client sends them.
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    /// Set certificates revocation check.
+    ///
+    /// Check is called after successful chain verification, i.e. it could be
+    /// used for CRL or OCSP lookups. Handshake fails if check returns `false`.
+    pub fn revocation_check<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&PeerCertificates) -> bool + Send + Sync + 'static,
+    {
+        self.check = Some(Arc::new(f));
+        self
+    }
+
+    /// Create client certificates verifier for server config
+    pub fn verifier(self) -> Arc<dyn ClientCertVerifier> {
+        let inner = if self.required {
+            AllowAnyAuthenticatedClient::new(self.roots)
+        } else {
+            AllowAnyAnonymousOrAuthenticatedClient::new(self.roots)
+        };
+        if let Some(check) = self.check {
+            Arc::new(CheckedVerifier { inner, check })
+        } else {
+            inner
+        }
+    }
+}
+
+impl fmt::Debug for ClientAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientAuth")
+            .field("roots", &self.roots.len())
+            .field("required", &self.required)
+            .field("revocation_check", &self.check.is_some())
+            .finish()
+    }
+}
+
+struct CheckedVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    check: RevocationCheck,
+}
+
+impl ClientCertVerifier for CheckedVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> Option<bool> {
+        self.inner.client_auth_mandatory()
+    }
+
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        self.inner.client_auth_root_subjects()
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: SystemTime,
+    ) -> Result<ClientCertVerified, Error> {
+        let verified = self
+            .inner
+            .verify_client_cert(end_entity, intermediates, now)?;
+
+        let certs = iter::once(end_entity)
+            .chain(intermediates)
+            .map(|cert| cert.0.clone())
+            .collect();
+        if (self.check)(&PeerCertificates(certs)) {
+            Ok(verified)
+        } else {
+            Err(Error::InvalidCertificateData(
+                "Certificate is revoked".to_string(),
+            ))
+        }
+    }
+}
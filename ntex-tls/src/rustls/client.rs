@@ -10,7 +10,7 @@ use tls_rust::{ClientConfig, ClientConnection, ServerName};
 use crate::rustls::{IoInner, TlsFilter, Wrapper};
 use crate::types;
 
-use super::{PeerCert, PeerCertChain};
+use super::{tls_info, PeerCert, PeerCertChain};
 
 /// An implementation of SSL streams
 pub struct TlsClientFilter<F> {
@@ -36,6 +36,10 @@ impl<F: Filter> Filter for TlsClientFilter<F> {
                 types::HttpProtocol::Http1
             };
             Some(Box::new(proto))
+        } else if id == any::TypeId::of::<types::AlpnProtocol>() {
+            self.session.borrow().alpn_protocol().map(|proto| {
+                Box::new(types::AlpnProtocol(proto.to_vec())) as Box<dyn any::Any>
+            })
         } else if id == any::TypeId::of::<PeerCert>() {
             if let Some(cert_chain) = self.session.borrow().peer_certificates() {
                 if let Some(cert) = cert_chain.first() {
@@ -52,6 +56,8 @@ impl<F: Filter> Filter for TlsClientFilter<F> {
             } else {
                 None
             }
+        } else if id == any::TypeId::of::<types::TlsInfo>() {
+            tls_info(&self.session.borrow()).map(|info| Box::new(info) as Box<dyn any::Any>)
         } else {
             self.inner.filter.query(id)
         }
@@ -1,17 +1,25 @@
 #![allow(clippy::type_complexity)]
 //! An implementation of SSL streams for ntex backed by OpenSSL
-use std::{any, cmp, future::Future, io, pin::Pin, task::Context, task::Poll};
-use std::{cell::Cell, sync::Arc};
+use std::{any, cmp, fmt, future::Future, io, pin::Pin, task::Context, task::Poll};
+use std::{cell::Cell, sync::Arc, sync::RwLock};
 
 use ntex_bytes::{BytesVec, PoolRef};
 use ntex_io::{Base, Filter, FilterFactory, Io, IoRef, ReadStatus, WriteStatus};
 use ntex_util::time::Millis;
-use tls_rust::{Certificate, ClientConfig, ServerConfig, ServerName};
+use tls_rust::server::{ClientHello, ResolvesServerCert};
+use tls_rust::sign::CertifiedKey;
+use tls_rust::{Certificate, ClientConfig, CommonState, ServerConfig, ServerName};
+
+use crate::types;
 
 mod accept;
+mod auth;
 mod client;
 mod server;
+mod session;
 pub use accept::{Acceptor, AcceptorService};
+pub use auth::ClientAuth;
+pub use session::Resumption;
 
 use self::client::TlsClientFilter;
 use self::server::TlsServerFilter;
@@ -24,6 +32,16 @@ pub struct PeerCert(pub Certificate);
 #[derive(Debug)]
 pub struct PeerCertChain(pub Vec<Certificate>);
 
+/// Negotiated protocol version and cipher suite
+fn tls_info(session: &CommonState) -> Option<types::TlsInfo> {
+    let version = session.protocol_version()?;
+    let suite = session.negotiated_cipher_suite()?;
+    Some(types::TlsInfo {
+        version: format!("{:?}", version).replace('_', "."),
+        cipher: format!("{:?}", suite.suite()),
+    })
+}
+
 /// An implementation of SSL streams
 pub struct TlsFilter<F = Base> {
     inner: InnerTlsFilter<F>,
@@ -134,7 +152,7 @@ impl<F: Filter> Filter for TlsFilter<F> {
 }
 
 pub struct TlsAcceptor {
-    cfg: Arc<ServerConfig>,
+    cfg: Arc<RwLock<Arc<ServerConfig>>>,
     timeout: Millis,
 }
 
@@ -142,7 +160,7 @@ impl TlsAcceptor {
     /// Create openssl acceptor filter factory
     pub fn new(cfg: Arc<ServerConfig>) -> Self {
         TlsAcceptor {
-            cfg,
+            cfg: Arc::new(RwLock::new(cfg)),
             timeout: Millis(5_000),
         }
     }
@@ -154,6 +172,76 @@ impl TlsAcceptor {
         self.timeout = timeout.into();
         self
     }
+
+    /// Get handle for config reloading.
+    pub fn handle(&self) -> AcceptorHandle {
+        AcceptorHandle(self.cfg.clone())
+    }
+}
+
+/// Acceptor reload handle
+///
+/// Handle is shared between all acceptors created from it. Server factory
+/// is called for each worker, so handle should be created outside of
+/// the factory and converted into acceptor in each worker.
+/// New config is used for new connections, existing connections
+/// keep using previous certificates.
+#[derive(Clone)]
+pub struct AcceptorHandle(Arc<RwLock<Arc<ServerConfig>>>);
+
+impl AcceptorHandle {
+    /// Create reload handle for server config.
+    ///
+    /// Acceptors created from the same handle share server config.
+    pub fn new(cfg: Arc<ServerConfig>) -> Self {
+        AcceptorHandle(Arc::new(RwLock::new(cfg)))
+    }
+
+    /// Replace server config, i.e. after certificates renewal.
+    pub fn reload(&self, cfg: Arc<ServerConfig>) {
+        *self.0.write().unwrap() = cfg;
+    }
+}
+
+impl fmt::Debug for AcceptorHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptorHandle").finish()
+    }
+}
+
+/// Select certificates by server name (SNI).
+///
+/// Resolver is called during handshake with server name requested by client,
+/// server name is `None` if client does not support SNI. Handshake fails
+/// if resolver returns `None`.
+///
+/// ```rust,ignore
+/// let config = ServerConfig::builder()
+///     .with_safe_defaults()
+///     .with_no_client_auth()
+///     .with_cert_resolver(Arc::new(SniResolver::new(move |name| {
+///         certs.get(name.unwrap_or("default")).cloned()
+///     })));
+/// ```
+pub struct SniResolver<F>(F);
+
+impl<F> SniResolver<F>
+where
+    F: Fn(Option<&str>) -> Option<Arc<CertifiedKey>> + Send + Sync + 'static,
+{
+    /// Create resolver from function
+    pub fn new(resolver: F) -> Self {
+        SniResolver(resolver)
+    }
+}
+
+impl<F> ResolvesServerCert for SniResolver<F>
+where
+    F: Fn(Option<&str>) -> Option<Arc<CertifiedKey>> + Send + Sync + 'static,
+{
+    fn resolve(&self, hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        (self.0)(hello.server_name())
+    }
 }
 
 impl From<ServerConfig> for TlsAcceptor {
@@ -162,6 +250,15 @@ impl From<ServerConfig> for TlsAcceptor {
     }
 }
 
+impl From<AcceptorHandle> for TlsAcceptor {
+    fn from(handle: AcceptorHandle) -> Self {
+        TlsAcceptor {
+            cfg: handle.0,
+            timeout: Millis(5_000),
+        }
+    }
+}
+
 impl Clone for TlsAcceptor {
     fn clone(&self) -> Self {
         Self {
@@ -178,7 +275,7 @@ impl<F: Filter> FilterFactory<F> for TlsAcceptor {
     type Future = Pin<Box<dyn Future<Output = Result<Io<Self::Filter>, io::Error>>>>;
 
     fn create(self, st: Io<F>) -> Self::Future {
-        let cfg = self.cfg.clone();
+        let cfg = self.cfg.read().unwrap().clone();
         let timeout = self.timeout;
 
         Box::pin(async move { TlsServerFilter::create(st, cfg, timeout).await })
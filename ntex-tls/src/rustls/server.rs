@@ -10,12 +10,13 @@ use tls_rust::{ServerConfig, ServerConnection};
 use crate::rustls::{IoInner, TlsFilter, Wrapper};
 use crate::types;
 
-use super::{PeerCert, PeerCertChain};
+use super::{tls_info, PeerCert, PeerCertChain};
 
 /// An implementation of SSL streams
 pub struct TlsServerFilter<F> {
     inner: IoInner<F>,
     session: RefCell<ServerConnection>,
+    early_data: Cell<usize>,
 }
 
 impl<F: Filter> Filter for TlsServerFilter<F> {
@@ -36,6 +37,10 @@ impl<F: Filter> Filter for TlsServerFilter<F> {
                 types::HttpProtocol::Http1
             };
             Some(Box::new(proto))
+        } else if id == any::TypeId::of::<types::AlpnProtocol>() {
+            self.session.borrow().alpn_protocol().map(|proto| {
+                Box::new(types::AlpnProtocol(proto.to_vec())) as Box<dyn any::Any>
+            })
         } else if id == any::TypeId::of::<PeerCert>() {
             if let Some(cert_chain) = self.session.borrow().peer_certificates() {
                 if let Some(cert) = cert_chain.first() {
@@ -52,6 +57,19 @@ impl<F: Filter> Filter for TlsServerFilter<F> {
             } else {
                 None
             }
+        } else if id == any::TypeId::of::<types::PeerCertificates>() {
+            self.session.borrow().peer_certificates().map(|certs| {
+                Box::new(types::PeerCertificates(
+                    certs.iter().map(|cert| cert.0.clone()).collect(),
+                )) as Box<dyn any::Any>
+            })
+        } else if id == any::TypeId::of::<types::EarlyData>() {
+            match self.early_data.get() {
+                0 => None,
+                n => Some(Box::new(types::EarlyData(n))),
+            }
+        } else if id == any::TypeId::of::<types::TlsInfo>() {
+            tls_info(&self.session.borrow()).map(|info| Box::new(info) as Box<dyn any::Any>)
         } else {
             self.inner.filter.query(id)
         }
@@ -125,6 +143,7 @@ impl<F: Filter> Filter for TlsServerFilter<F> {
             let state = session
                 .process_new_packets()
                 .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            new_bytes += self.read_early_data(&mut session, &mut dst)?;
 
             let new_b = state.plaintext_bytes_to_read();
             if new_b > 0 {
@@ -169,6 +188,28 @@ impl<F: Filter> Filter for TlsServerFilter<F> {
 }
 
 impl<F: Filter> TlsServerFilter<F> {
+    /// Move early data (0-RTT) received during handshake to read buffer
+    fn read_early_data(
+        &self,
+        session: &mut ServerConnection,
+        dst: &mut BytesVec,
+    ) -> io::Result<usize> {
+        let mut total = 0;
+        if let Some(mut early) = session.early_data() {
+            let mut chunk = [0u8; 4096];
+            loop {
+                let n = early.read(&mut chunk)?;
+                if n == 0 {
+                    break;
+                }
+                dst.extend_from_slice(&chunk[..n]);
+                total += n;
+            }
+            self.early_data.set(self.early_data.get() + total);
+        }
+        Ok(total)
+    }
+
     pub(crate) async fn create(
         io: Io<F>,
         cfg: Arc<ServerConfig>,
@@ -192,15 +233,29 @@ impl<F: Filter> TlsServerFilter<F> {
                 Ok::<_, io::Error>(TlsFilter::new_server(TlsServerFilter {
                     inner,
                     session: RefCell::new(session),
+                    early_data: Cell::new(0),
                 }))
             })?;
 
             let filter = io.filter();
             loop {
                 let (result, wants_read) = {
-                    let mut session = filter.server().session.borrow_mut();
-                    let mut wrp = Wrapper(&filter.server().inner);
-                    (session.complete_io(&mut wrp), session.wants_read())
+                    let server = filter.server();
+                    let mut session = server.session.borrow_mut();
+                    let mut wrp = Wrapper(&server.inner);
+                    let result = session.complete_io(&mut wrp);
+
+                    // keep early data received during handshake
+                    let mut dst = server
+                        .inner
+                        .read_buf
+                        .take()
+                        .unwrap_or_else(|| server.inner.pool.get_read_buf());
+                    let early = server.read_early_data(&mut session, &mut dst);
+                    server.inner.read_buf.set(Some(dst));
+                    early?;
+
+                    (result, session.wants_read())
                 };
                 match result {
                     Ok(_) => {
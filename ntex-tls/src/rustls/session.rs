@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use tls_rust::server::{NoServerSessionStorage, ServerSessionMemoryCache};
+use tls_rust::{Error, ServerConfig, Ticketer};
+
+/// Tls session resumption settings
+///
+/// Resumed sessions skip certificates exchange and key agreement,
+/// which is the most expensive part of handshake.
+///
+/// ```rust,ignore
+/// let mut config = ServerConfig::builder()
+///     .with_safe_defaults()
+///     .with_no_client_auth()
+///     .with_single_cert(certs, key)?;
+/// Resumption::new()
+///     .tickets()
+///     .early_data(16_384)
+///     .configure(&mut config)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Resumption {
+    cache_size: usize,
+    tickets: bool,
+    early_data: u32,
+}
+
+impl Default for Resumption {
+    fn default() -> Self {
+        Resumption {
+            cache_size: 256,
+            tickets: false,
+            early_data: 0,
+        }
+    }
+}
+
+impl Resumption {
+    /// Create resumption settings, only session cache is enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set max number of sessions in server side session cache.
+    ///
+    /// Zero disables session cache. Default is set to 256.
+    pub fn cache_size(mut self, size: usize) -> Self {
+        self.cache_size = size;
+        self
+    }
+
+    /// Enable stateless session tickets.
+    ///
+    /// Ticket keys are generated randomly and rotated every 6 hours,
+    /// tickets encrypted with previous key are accepted until next rotation.
+    pub fn tickets(mut self) -> Self {
+        self.tickets = true;
+        self
+    }
+
+    /// Accept early data (0-RTT) from resumed sessions.
+    ///
+    /// Early data could be replayed, http server answers non-idempotent
+    /// requests received in early data with `425 Too Early`, client
+    /// is expected to retry request after handshake completion.
+    /// Early data is disabled by default.
+    pub fn early_data(mut self, max_size: u32) -> Self {
+        self.early_data = max_size;
+        self
+    }
+
+    /// Apply settings to server config
+    pub fn configure(self, cfg: &mut ServerConfig) -> Result<(), Error> {
+        cfg.session_storage = if self.cache_size == 0 {
+            Arc::new(NoServerSessionStorage {})
+        } else {
+            ServerSessionMemoryCache::new(self.cache_size)
+        };
+        if self.tickets {
+            cfg.ticketer = Ticketer::new().map_err(|_| Error::FailedToGetRandomBytes)?;
+        }
+        cfg.max_early_data_size = self.early_data;
+        Ok(())
+    }
+}
@@ -4,3 +4,63 @@ pub enum HttpProtocol {
     Http2,
     Unknown,
 }
+
+/// Protocol version and cipher suite negotiated during tls handshake
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TlsInfo {
+    /// Protocol version, i.e. `TLSv1.3`
+    pub version: String,
+    /// Cipher suite name
+    pub cipher: String,
+}
+
+/// Application protocol negotiated during tls handshake via ALPN
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AlpnProtocol(pub Vec<u8>);
+
+impl AlpnProtocol {
+    /// Negotiated protocol name
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Http protocol version for negotiated protocol
+    pub fn http_protocol(&self) -> HttpProtocol {
+        match self.0.as_slice() {
+            b"h2" => HttpProtocol::Http2,
+            b"http/1.1" | b"http/1.0" => HttpProtocol::Http1,
+            _ => HttpProtocol::Unknown,
+        }
+    }
+}
+
+/// Peer certificates verified during tls handshake
+///
+/// Certificates are in DER format, peer certificate comes first
+/// and followed by intermediate certificates.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PeerCertificates(pub Vec<Vec<u8>>);
+
+impl PeerCertificates {
+    /// Peer certificate in DER format
+    pub fn peer_certificate(&self) -> Option<&[u8]> {
+        self.0.first().map(|cert| cert.as_slice())
+    }
+
+    /// Number of certificates in chain
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if chain is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Early data (0-RTT) received during tls handshake
+///
+/// Early data could be replayed by an attacker, connection
+/// reports number of bytes received before handshake completion.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EarlyData(pub usize);
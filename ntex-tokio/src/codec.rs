@@ -0,0 +1,129 @@
+//! Adapters between ntex codecs and tokio-util codecs
+//!
+//! Codecs use different buffer types, so adapters copy data
+//! between buffers.
+use std::{cell::RefCell, io};
+
+use bytes::Buf;
+use ntex_bytes::BytesMut;
+use ntex_codec::{Decoder, Encoder};
+use tokio_util::codec;
+
+/// Wrapper for ntex codec, implements tokio-util `Encoder` and `Decoder` traits
+#[derive(Debug, Clone, Default)]
+pub struct TokioCodec<C>(pub C);
+
+impl<C> codec::Decoder for TokioCodec<C>
+where
+    C: Decoder,
+    C::Error: From<io::Error>,
+{
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<C::Item>, C::Error> {
+        let mut buf = BytesMut::from(&src[..]);
+        let item = self.0.decode(&mut buf)?;
+        src.advance(src.len() - buf.len());
+        Ok(item)
+    }
+}
+
+impl<C> codec::Encoder<C::Item> for TokioCodec<C>
+where
+    C: Encoder,
+    C::Error: From<io::Error>,
+{
+    type Error = C::Error;
+
+    fn encode(&mut self, item: C::Item, dst: &mut bytes::BytesMut) -> Result<(), C::Error> {
+        let mut buf = BytesMut::new();
+        self.0.encode(item, &mut buf)?;
+        dst.extend_from_slice(&buf);
+        Ok(())
+    }
+}
+
+/// Wrapper for tokio-util codec, implements ntex `Encoder` and `Decoder` traits
+#[derive(Debug, Default)]
+pub struct NtexCodec<C>(RefCell<C>);
+
+impl<C> NtexCodec<C> {
+    /// Create new codec wrapper
+    pub fn new(codec: C) -> Self {
+        NtexCodec(RefCell::new(codec))
+    }
+
+    /// Get inner codec
+    pub fn into_inner(self) -> C {
+        self.0.into_inner()
+    }
+}
+
+impl<C> Decoder for NtexCodec<C>
+where
+    C: codec::Decoder,
+    C::Error: std::fmt::Debug,
+{
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn decode(&self, src: &mut BytesMut) -> Result<Option<C::Item>, C::Error> {
+        let mut buf = bytes::BytesMut::from(&src[..]);
+        let item = self.0.borrow_mut().decode(&mut buf)?;
+        let _ = src.split_to(src.len() - buf.len());
+        Ok(item)
+    }
+}
+
+impl<C> Encoder for NtexCodec<C>
+where
+    C: codec::Encoder<<C as codec::Decoder>::Item> + codec::Decoder,
+    <C as codec::Encoder<<C as codec::Decoder>::Item>>::Error: std::fmt::Debug,
+{
+    type Item = <C as codec::Decoder>::Item;
+    type Error = <C as codec::Encoder<<C as codec::Decoder>::Item>>::Error;
+
+    fn encode(&self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut buf = bytes::BytesMut::new();
+        self.0.borrow_mut().encode(item, &mut buf)?;
+        dst.extend_from_slice(&buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ntex_bytes::Bytes;
+    use ntex_codec::BytesCodec;
+    use tokio_util::codec::{Decoder as _, Encoder as _, LinesCodec};
+
+    use super::*;
+
+    #[test]
+    fn test_tokio_codec() {
+        let mut codec = TokioCodec(BytesCodec);
+        let mut buf = bytes::BytesMut::from(&b"data"[..]);
+        let item = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(item, BytesMut::from(&b"data"[..]));
+        assert!(buf.is_empty());
+
+        codec.encode(Bytes::from_static(b"item"), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"item");
+    }
+
+    #[test]
+    fn test_ntex_codec() {
+        let codec = NtexCodec::new(LinesCodec::new());
+        let mut buf = BytesMut::from(&b"line1\nline2"[..]);
+        let item = Decoder::decode(&codec, &mut buf).unwrap().unwrap();
+        assert_eq!(item, "line1");
+        assert_eq!(&buf[..], b"line2");
+        assert!(Decoder::decode(&codec, &mut buf).unwrap().is_none());
+
+        let mut buf = BytesMut::new();
+        Encoder::encode(&codec, "line3".to_string(), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"line3\n");
+        let _ = codec.into_inner();
+    }
+}
@@ -31,6 +31,43 @@ impl Handle for HandleWrapper {
         } else if id == any::TypeId::of::<SocketOptions>() {
             return Some(Box::new(SocketOptions(Rc::downgrade(&self.0))));
         }
+        #[cfg(unix)]
+        if id == any::TypeId::of::<types::RawWrite>() {
+            return Some(Box::new(types::RawWrite(Rc::new(RawWriter(
+                Rc::downgrade(&self.0),
+            )))));
+        }
+        #[cfg(target_os = "linux")]
+        if id == any::TypeId::of::<types::TcpInfo>() {
+            return tcp_info(&self.0.borrow())
+                .map(|info| Box::new(info) as Box<dyn any::Any>);
+        }
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+/// Query tcp connection statistics
+fn tcp_info(io: &TcpStream) -> Option<types::TcpInfo> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut info: libc::tcp_info = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            io.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut libc::tcp_info as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res == 0 {
+        Some(types::TcpInfo {
+            rtt: std::time::Duration::from_micros(info.tcpi_rtt as u64),
+            rtt_var: std::time::Duration::from_micros(info.tcpi_rttvar as u64),
+        })
+    } else {
         None
     }
 }
@@ -159,7 +196,7 @@ impl Future for WriteTask {
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.as_mut().get_mut();
+        let this = self.as_mut().get_mut();
 
         match this.st {
             IoWriteState::Processing(ref mut delay) => {
@@ -446,13 +483,32 @@ impl AsyncWrite for TokioIoBoxed {
     }
 }
 
+/// Wrapper for tokio `AsyncRead` and `AsyncWrite` streams
+///
+/// Allows to use any tokio stream as ntex `Io` object, i.e. `Io::new(AsyncIo(stream))`.
+pub struct AsyncIo<T>(pub T);
+
+impl<T> IoStream for AsyncIo<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    fn start(self, read: ReadContext, write: WriteContext) -> Option<Box<dyn Handle>> {
+        let io = Rc::new(RefCell::new(self.0));
+
+        tokio::task::spawn_local(stream::ReadTask::new(io.clone(), read));
+        tokio::task::spawn_local(stream::WriteTask::new(io, write));
+        None
+    }
+}
+
 /// Query TCP Io connections for a handle to set socket options
 pub struct SocketOptions(Weak<RefCell<TcpStream>>);
 
 impl SocketOptions {
     pub fn set_linger(&self, dur: Option<Millis>) -> io::Result<()> {
-        self.try_self()
-            .and_then(|s| s.borrow().set_linger(dur.map(|d| d.into())))
+        self.try_self().and_then(|s| {
+            socket2::SockRef::from(&*s.borrow()).set_linger(dur.map(|d| d.into()))
+        })
     }
 
     pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
@@ -466,10 +522,43 @@ impl SocketOptions {
     }
 }
 
+#[cfg(unix)]
+struct RawWriter(Weak<RefCell<TcpStream>>);
+
+#[cfg(unix)]
+impl types::RawWriteHandle for RawWriter {
+    fn poll_write_raw(
+        &self,
+        cx: &mut Context<'_>,
+        f: &mut dyn FnMut(std::os::unix::io::RawFd) -> io::Result<usize>,
+    ) -> Poll<io::Result<usize>> {
+        use std::os::unix::io::AsRawFd;
+
+        let io = if let Some(io) = self.0.upgrade() {
+            io
+        } else {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "socket is gone",
+            )));
+        };
+        let io = io.borrow();
+
+        loop {
+            ready!(io.poll_write_ready(cx))?;
+            match io.try_io(tokio::io::Interest::WRITABLE, || f(io.as_raw_fd())) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                res => return Poll::Ready(res),
+            }
+        }
+    }
+}
+
 #[cfg(unix)]
 mod unixstream {
     use tokio::net::UnixStream;
 
+    use super::stream::{ReadTask, WriteTask};
     use super::*;
 
     impl IoStream for crate::UnixStream {
@@ -477,25 +566,47 @@ mod unixstream {
             let io = Rc::new(RefCell::new(self.0));
 
             tokio::task::spawn_local(ReadTask::new(io.clone(), read));
-            tokio::task::spawn_local(WriteTask::new(io, write));
+            tokio::task::spawn_local(WriteTask::new(io.clone(), write));
+            Some(Box::new(HandleWrapper(io)))
+        }
+    }
+
+    struct HandleWrapper(Rc<RefCell<UnixStream>>);
+
+    impl Handle for HandleWrapper {
+        fn query(&self, id: any::TypeId) -> Option<Box<dyn any::Any>> {
+            if id == any::TypeId::of::<types::PeerCred>() {
+                if let Ok(cred) = self.0.borrow().peer_cred() {
+                    return Some(Box::new(types::PeerCred {
+                        uid: cred.uid(),
+                        gid: cred.gid(),
+                        pid: cred.pid(),
+                    }));
+                }
+            }
             None
         }
     }
+}
+
+mod stream {
+    //! Io tasks for generic tokio streams
+    use super::*;
 
     /// Read io task
-    struct ReadTask {
-        io: Rc<RefCell<UnixStream>>,
+    pub(super) struct ReadTask<T> {
+        io: Rc<RefCell<T>>,
         state: ReadContext,
     }
 
-    impl ReadTask {
+    impl<T> ReadTask<T> {
         /// Create new read io task
-        fn new(io: Rc<RefCell<UnixStream>>, state: ReadContext) -> Self {
+        pub(super) fn new(io: Rc<RefCell<T>>, state: ReadContext) -> Self {
             Self { io, state }
         }
     }
 
-    impl Future for ReadTask {
+    impl<T: AsyncRead + AsyncWrite + Unpin> Future for ReadTask<T> {
         type Output = ();
 
         fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
@@ -572,15 +683,15 @@ mod unixstream {
     }
 
     /// Write io task
-    struct WriteTask {
+    pub(super) struct WriteTask<T> {
         st: IoWriteState,
-        io: Rc<RefCell<UnixStream>>,
+        io: Rc<RefCell<T>>,
         state: WriteContext,
     }
 
-    impl WriteTask {
+    impl<T> WriteTask<T> {
         /// Create new write io task
-        fn new(io: Rc<RefCell<UnixStream>>, state: WriteContext) -> Self {
+        pub(super) fn new(io: Rc<RefCell<T>>, state: WriteContext) -> Self {
             Self {
                 io,
                 state,
@@ -589,11 +700,11 @@ mod unixstream {
         }
     }
 
-    impl Future for WriteTask {
+    impl<T: AsyncRead + AsyncWrite + Unpin> Future for WriteTask<T> {
         type Output = ();
 
         fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-            let mut this = self.as_mut().get_mut();
+            let this = self.as_mut().get_mut();
 
             match this.st {
                 IoWriteState::Processing(ref mut delay) => {
@@ -3,10 +3,11 @@ use std::{io::Result, net, net::SocketAddr, path::Path};
 use ntex_bytes::PoolRef;
 use ntex_io::Io;
 
+pub mod codec;
 mod io;
 mod signals;
 
-pub use self::io::{SocketOptions, TokioIoBoxed};
+pub use self::io::{AsyncIo, SocketOptions, TokioIoBoxed};
 pub use self::signals::{signal, Signal};
 
 struct TcpStream(tokio::net::TcpStream);
@@ -0,0 +1,403 @@
+//! A multi-producer, multi-consumer broadcast queue.
+//!
+//! Each message is delivered to all subscribers, every subscriber has its
+//! own bounded buffer. Slow subscribers do not block senders, instead
+//! lagging subscriber loses oldest messages or gets disconnected,
+//! depending on channel's `LagPolicy`.
+//!
+//! ```rust,ignore
+//! let tx = broadcast::channel::<ByteString>(64, LagPolicy::DropOldest);
+//!
+//! // server-sent events
+//! let events = tx.subscribe().map(|msg| {
+//!     Ok::<_, std::io::Error>(Bytes::from(format!("data: {}\n\n", msg)))
+//! });
+//! HttpResponse::Ok()
+//!     .content_type("text/event-stream")
+//!     .streaming(events);
+//!
+//! tx.send("hello".into());
+//! ```
+use std::{collections::VecDeque, fmt, pin::Pin, task::Context, task::Poll};
+
+use futures_core::Stream;
+use slab::Slab;
+
+use super::cell::Cell;
+use crate::{future::poll_fn, task::LocalWaker};
+
+/// Policy for subscribers that do not keep up with senders
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Drop oldest buffered message to make room for the new one
+    DropOldest,
+    /// Disconnect subscriber, buffered messages are dropped
+    Disconnect,
+}
+
+/// Creates a broadcast channel.
+///
+/// `capacity` is the max number of buffered messages for each subscriber.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T: Clone>(capacity: usize, policy: LagPolicy) -> Sender<T> {
+    assert!(capacity > 0, "Capacity must be greater than zero");
+
+    Sender {
+        shared: Cell::new(Shared {
+            capacity,
+            policy,
+            senders: 1,
+            closed: false,
+            subscribers: Slab::new(),
+        }),
+    }
+}
+
+struct Shared<T> {
+    capacity: usize,
+    policy: LagPolicy,
+    senders: usize,
+    closed: bool,
+    subscribers: Slab<Cell<Queue<T>>>,
+}
+
+struct Queue<T> {
+    buffer: VecDeque<T>,
+    waker: LocalWaker,
+    lagged: u64,
+    disconnected: bool,
+}
+
+/// The transmission end of a broadcast channel.
+///
+/// This is created by the `channel` function.
+pub struct Sender<T> {
+    shared: Cell<Shared<T>>,
+}
+
+impl<T> Unpin for Sender<T> {}
+
+impl<T: Clone> Sender<T> {
+    /// Sends the message to all subscribers.
+    ///
+    /// Returns number of subscribers that received the message,
+    /// fails if channel is closed or there are no subscribers.
+    pub fn send(&self, item: T) -> Result<usize, SendError<T>> {
+        let shared = self.shared.get_mut();
+        if shared.closed || shared.subscribers.is_empty() {
+            return Err(SendError(item));
+        }
+
+        let mut disconnected = Vec::new();
+        let last = shared.subscribers.len() - 1;
+        let mut item = Some(item);
+
+        for (idx, (key, queue)) in shared.subscribers.iter().enumerate() {
+            let queue = queue.get_mut();
+            if queue.buffer.len() >= shared.capacity {
+                queue.lagged += 1;
+                match shared.policy {
+                    LagPolicy::DropOldest => {
+                        queue.buffer.pop_front();
+                    }
+                    LagPolicy::Disconnect => {
+                        queue.buffer.clear();
+                        queue.disconnected = true;
+                        queue.waker.wake();
+                        disconnected.push(key);
+                        continue;
+                    }
+                }
+            }
+            let msg = if idx == last {
+                item.take().unwrap()
+            } else {
+                item.as_ref().unwrap().clone()
+            };
+            queue.buffer.push_back(msg);
+            queue.waker.wake();
+        }
+
+        for key in disconnected.iter() {
+            shared.subscribers.remove(*key);
+        }
+        Ok(last + 1 - disconnected.len())
+    }
+}
+
+impl<T> Sender<T> {
+    /// Create new subscriber.
+    ///
+    /// Subscriber receives messages sent after subscription.
+    pub fn subscribe(&self) -> Subscriber<T> {
+        let queue = Cell::new(Queue {
+            buffer: VecDeque::new(),
+            waker: LocalWaker::new(),
+            lagged: 0,
+            disconnected: false,
+        });
+        let shared = self.shared.get_mut();
+        let key = shared.subscribers.insert(queue.clone());
+
+        Subscriber {
+            key,
+            queue,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Number of active subscribers
+    pub fn subscribers(&self) -> usize {
+        self.shared.get_ref().subscribers.len()
+    }
+
+    /// Closes the channel
+    ///
+    /// This prevents any further messages from being sent on the channel while
+    /// still enabling subscribers to drain messages that are buffered.
+    pub fn close(&self) {
+        let shared = self.shared.get_mut();
+        shared.closed = true;
+        for (_, queue) in shared.subscribers.iter() {
+            queue.get_ref().waker.wake();
+        }
+    }
+
+    /// Returns whether this channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.shared.get_ref().closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.get_mut().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let shared = self.shared.get_mut();
+        shared.senders -= 1;
+        let last = shared.senders == 0;
+        if last {
+            self.close();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("subscribers", &self.subscribers())
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// The receiving end of a broadcast channel which implements the `Stream` trait.
+///
+/// This is created by the `Sender::subscribe` method.
+pub struct Subscriber<T> {
+    key: usize,
+    queue: Cell<Queue<T>>,
+    shared: Cell<Shared<T>>,
+}
+
+impl<T> Subscriber<T> {
+    /// Receive next message, returns `None` if channel is closed
+    /// or subscriber is disconnected.
+    pub async fn recv(&self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Attempt to pull out the next message of this subscriber, registering
+    /// the current task for wakeup if the message is not yet available,
+    /// and returning None if the stream is exhausted.
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let queue = self.queue.get_mut();
+
+        if let Some(msg) = queue.buffer.pop_front() {
+            Poll::Ready(Some(msg))
+        } else if queue.disconnected || self.shared.get_ref().closed {
+            Poll::Ready(None)
+        } else {
+            queue.waker.register(cx.waker());
+            Poll::Pending
+        }
+    }
+
+    /// Number of messages lost because subscriber did not keep up
+    pub fn lagged(&self) -> u64 {
+        self.queue.get_ref().lagged
+    }
+
+    /// Returns whether subscriber was disconnected because
+    /// it did not keep up with senders.
+    pub fn is_disconnected(&self) -> bool {
+        self.queue.get_ref().disconnected
+    }
+
+    /// Create a sender for the same channel
+    ///
+    /// Channel stays closed if all senders were dropped.
+    pub fn sender(&self) -> Sender<T> {
+        self.shared.get_mut().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Unpin for Subscriber<T> {}
+
+impl<T> Stream for Subscriber<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Subscriber<T> {
+    fn drop(&mut self) {
+        // disconnected subscriber is removed by sender, key could be reused
+        if !self.queue.get_ref().disconnected {
+            self.shared.get_mut().subscribers.remove(self.key);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Subscriber<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let queue = self.queue.get_ref();
+        f.debug_struct("Subscriber")
+            .field("buffered", &queue.buffer.len())
+            .field("lagged", &queue.lagged)
+            .field("disconnected", &queue.disconnected)
+            .finish()
+    }
+}
+
+/// Error type for sending, used when the channel is closed
+/// or there are no subscribers
+pub struct SendError<T>(T);
+
+impl<T> std::error::Error for SendError<T> {}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("SendError").field(&"...").finish()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "send failed because there are no subscribers")
+    }
+}
+
+impl<T> SendError<T> {
+    /// Returns the message that was attempted to be sent but failed.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::{lazy, stream_recv};
+
+    #[ntex_macros::rt_test2]
+    async fn test_broadcast() {
+        let tx = channel(4, LagPolicy::DropOldest);
+        assert!(tx.send("test").is_err());
+
+        let mut rx1 = tx.subscribe();
+        let rx2 = tx.subscribe();
+        assert_eq!(tx.subscribers(), 2);
+        assert!(format!("{:?}", tx).contains("Sender"));
+        assert!(format!("{:?}", rx1).contains("Subscriber"));
+
+        assert_eq!(tx.send("test").unwrap(), 2);
+        let tx2 = tx.clone();
+        assert_eq!(tx2.send("test2").unwrap(), 2);
+        assert_eq!(stream_recv(&mut rx1).await, Some("test"));
+        assert_eq!(stream_recv(&mut rx1).await, Some("test2"));
+        assert_eq!(rx2.recv().await, Some("test"));
+        assert_eq!(rx2.recv().await, Some("test2"));
+        assert_eq!(
+            lazy(|cx| Pin::new(&mut rx1).poll_next(cx)).await,
+            Poll::Pending
+        );
+
+        drop(rx2);
+        assert_eq!(tx.subscribers(), 1);
+        assert_eq!(tx.send("test3").unwrap(), 1);
+
+        // channel is closed with last sender
+        drop(tx);
+        assert_eq!(tx2.send("test4").unwrap(), 1);
+        drop(tx2);
+        assert_eq!(rx1.recv().await, Some("test3"));
+        assert_eq!(rx1.recv().await, Some("test4"));
+        assert_eq!(rx1.recv().await, None);
+
+        let tx = rx1.sender();
+        assert!(tx.is_closed());
+        let tx = channel(1, LagPolicy::DropOldest);
+        let _rx = tx.subscribe();
+        tx.close();
+        assert!(tx.is_closed());
+        let err = tx.send("test").err().unwrap();
+        assert!(format!("{:?}", err).contains("SendError"));
+        assert!(format!("{}", err).contains("no subscribers"));
+        assert_eq!(err.into_inner(), "test");
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_drop_oldest() {
+        let tx = channel(2, LagPolicy::DropOldest);
+        let rx = tx.subscribe();
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.lagged(), 3);
+        assert!(!rx.is_disconnected());
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, Some(4));
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_disconnect() {
+        let tx = channel(2, LagPolicy::Disconnect);
+        let slow = tx.subscribe();
+        let fast = tx.subscribe();
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(fast.recv().await, Some(1));
+        assert_eq!(tx.send(3).unwrap(), 1);
+        assert_eq!(tx.subscribers(), 1);
+        assert!(slow.is_disconnected());
+        assert_eq!(slow.lagged(), 1);
+        assert_eq!(slow.recv().await, None);
+
+        // key of disconnected subscriber is reused
+        let rx = tx.subscribe();
+        drop(slow);
+        assert_eq!(tx.subscribers(), 2);
+        assert_eq!(fast.recv().await, Some(2));
+        assert_eq!(tx.send(4).unwrap(), 2);
+        assert_eq!(rx.recv().await, Some(4));
+        assert_eq!(fast.recv().await, Some(3));
+        assert_eq!(fast.recv().await, Some(4));
+    }
+}
@@ -1,6 +1,7 @@
 //! Communication primitives
 
 mod cell;
+pub mod broadcast;
 pub mod condition;
 pub mod mpsc;
 pub mod oneshot;
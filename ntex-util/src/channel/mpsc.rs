@@ -3,6 +3,7 @@ use std::{collections::VecDeque, fmt, pin::Pin, task::Context, task::Poll};
 
 use futures_core::Stream;
 use futures_sink::Sink;
+use slab::Slab;
 
 use super::cell::{Cell, WeakCell};
 use crate::{future::poll_fn, task::LocalWaker};
@@ -13,6 +14,8 @@ pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
         has_receiver: true,
         buffer: VecDeque::new(),
         blocked_recv: LocalWaker::new(),
+        capacity: usize::MAX,
+        blocked_send: Slab::new(),
     });
     let sender = Sender {
         shared: shared.clone(),
@@ -21,11 +24,50 @@ pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
     (sender, receiver)
 }
 
+/// Creates a bounded in-memory channel with buffered storage.
+///
+/// `BoundedSender` is ready to send while number of buffered messages
+/// is less than `capacity`. Senders created with `Receiver::sender()`
+/// do not respect channel's capacity.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn bounded<T>(capacity: usize) -> (BoundedSender<T>, Receiver<T>) {
+    assert!(capacity > 0, "Capacity must be greater than zero");
+
+    let mut blocked_send = Slab::new();
+    let key = blocked_send.insert(LocalWaker::new());
+    let shared = Cell::new(Shared {
+        capacity,
+        blocked_send,
+        has_receiver: true,
+        buffer: VecDeque::new(),
+        blocked_recv: LocalWaker::new(),
+    });
+    let sender = BoundedSender {
+        key,
+        shared: shared.clone(),
+    };
+    let receiver = Receiver { shared };
+    (sender, receiver)
+}
+
 #[derive(Debug)]
 struct Shared<T> {
     buffer: VecDeque<T>,
     blocked_recv: LocalWaker,
     has_receiver: bool,
+    capacity: usize,
+    blocked_send: Slab<LocalWaker>,
+}
+
+impl<T> Shared<T> {
+    fn wake_senders(&self) {
+        for (_, waker) in self.blocked_send.iter() {
+            waker.wake();
+        }
+    }
 }
 
 /// The transmission end of a channel.
@@ -136,6 +178,155 @@ impl<T> WeakSender<T> {
     }
 }
 
+/// The transmission end of a bounded channel.
+///
+/// This is created by the `bounded` function. Sender's readiness could
+/// be used for service's backpressure:
+///
+/// ```rust,ignore
+/// impl Service<Request> for Publisher {
+///     fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+///         self.tx.poll_ready(cx).map_err(|_| Error::Closed)
+///     }
+///
+///     fn call(&self, req: Request) -> Self::Future {
+///         Ready::from(self.tx.try_send(req).map_err(|_| Error::Overflow))
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BoundedSender<T> {
+    key: usize,
+    shared: Cell<Shared<T>>,
+}
+
+impl<T> Unpin for BoundedSender<T> {}
+
+impl<T> BoundedSender<T> {
+    /// Check if channel has capacity for the new message.
+    ///
+    /// Current task is woken up when receiver pulls message out of
+    /// the full channel. Returns error if receiver is gone.
+    pub fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), SendError<()>>> {
+        let shared = self.shared.get_mut();
+        if !shared.has_receiver {
+            Poll::Ready(Err(SendError(())))
+        } else if shared.buffer.len() < shared.capacity {
+            Poll::Ready(Ok(()))
+        } else {
+            shared.blocked_send[self.key].register(cx.waker());
+            Poll::Pending
+        }
+    }
+
+    /// Sends the message, waits for channel's capacity if channel is full.
+    pub async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        if poll_fn(|cx| self.poll_ready(cx)).await.is_err() {
+            Err(SendError(item))
+        } else {
+            self.push(item)
+        }
+    }
+
+    /// Attempts to send the message without waiting for channel's capacity.
+    pub fn try_send(&self, item: T) -> Result<(), TrySendError<T>> {
+        let shared = self.shared.get_ref();
+        if !shared.has_receiver {
+            Err(TrySendError::Closed(item))
+        } else if shared.buffer.len() >= shared.capacity {
+            Err(TrySendError::Full(item))
+        } else {
+            self.push(item).map_err(|e| TrySendError::Closed(e.0))
+        }
+    }
+
+    fn push(&self, item: T) -> Result<(), SendError<T>> {
+        let shared = self.shared.get_mut();
+        if !shared.has_receiver {
+            return Err(SendError(item)); // receiver was dropped
+        };
+        shared.buffer.push_back(item);
+        shared.blocked_recv.wake();
+        Ok(())
+    }
+
+    /// Closes the sender half
+    ///
+    /// This prevents any further messages from being sent on the channel while
+    /// still enabling the receiver to drain messages that are buffered.
+    pub fn close(&self) {
+        let shared = self.shared.get_mut();
+        shared.has_receiver = false;
+        shared.blocked_recv.wake();
+        shared.wake_senders();
+    }
+
+    /// Returns whether this channel is closed without needing a context.
+    pub fn is_closed(&self) -> bool {
+        self.shared.strong_count() == 1 || !self.shared.get_ref().has_receiver
+    }
+
+    /// Returns channel's capacity
+    pub fn capacity(&self) -> usize {
+        self.shared.get_ref().capacity
+    }
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        let key = self.shared.get_mut().blocked_send.insert(LocalWaker::new());
+        BoundedSender {
+            key,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Sink<T> for BoundedSender<T> {
+    type Error = SendError<T>;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        // if receiver is gone, `start_send` returns error with the item
+        BoundedSender::poll_ready(&self, cx).map(|_| Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), SendError<T>> {
+        self.push(item)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<(), SendError<T>>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        let count = self.shared.strong_count();
+        let shared = self.shared.get_mut();
+        shared.blocked_send.remove(self.key);
+
+        // check is last sender is about to drop
+        if shared.has_receiver && count == 2 {
+            // Wake up receiver as its stream has ended
+            shared.blocked_recv.wake();
+        }
+    }
+}
+
 /// The receiving end of a channel which implements the `Stream` trait.
 ///
 /// This is created by the `channel` function.
@@ -157,7 +348,9 @@ impl<T> Receiver<T> {
     /// This prevents any further messages from being sent on the channel
     /// while still enabling the receiver to drain messages that are buffered.
     pub fn close(&self) {
-        self.shared.get_mut().has_receiver = false;
+        let shared = self.shared.get_mut();
+        shared.has_receiver = false;
+        shared.wake_senders();
     }
 
     /// Returns whether this channel is closed without needing a context.
@@ -179,6 +372,10 @@ impl<T> Receiver<T> {
         let shared = self.shared.get_mut();
 
         if let Some(msg) = shared.buffer.pop_front() {
+            if shared.buffer.len() + 1 == shared.capacity {
+                // channel is not full anymore
+                shared.wake_senders();
+            }
             Poll::Ready(Some(msg))
         } else if shared.has_receiver {
             shared.blocked_recv.register(cx.waker());
@@ -210,6 +407,7 @@ impl<T> Drop for Receiver<T> {
         let shared = self.shared.get_mut();
         shared.buffer.clear();
         shared.has_receiver = false;
+        shared.wake_senders();
     }
 }
 
@@ -238,6 +436,45 @@ impl<T> SendError<T> {
     }
 }
 
+/// Error type for `BoundedSender::try_send`
+pub enum TrySendError<T> {
+    /// Channel is full
+    Full(T),
+    /// Receiving end of a channel is dropped
+    Closed(T),
+}
+
+impl<T> std::error::Error for TrySendError<T> {}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => fmt.debug_tuple("Full").field(&"...").finish(),
+            TrySendError::Closed(_) => fmt.debug_tuple("Closed").field(&"...").finish(),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(fmt, "send failed because channel is full"),
+            TrySendError::Closed(_) => {
+                write!(fmt, "send failed because receiver is gone")
+            }
+        }
+    }
+}
+
+impl<T> TrySendError<T> {
+    /// Returns the message that was attempted to be sent but failed.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(item) | TrySendError::Closed(item) => item,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -327,4 +564,57 @@ mod tests {
         let _tx = rx.sender();
         assert!(!rx.is_closed());
     }
+
+    #[ntex_macros::rt_test2]
+    async fn test_bounded() {
+        let (tx, mut rx) = bounded(2);
+        assert_eq!(tx.capacity(), 2);
+        tx.send("test1").await.unwrap();
+        tx.try_send("test2").unwrap();
+
+        let err = tx.try_send("test3").err().unwrap();
+        assert!(matches!(err, TrySendError::Full(_)));
+        assert!(format!("{:?}", err).contains("Full"));
+        assert!(format!("{}", err).contains("channel is full"));
+        assert_eq!(err.into_inner(), "test3");
+
+        let tx2 = tx.clone();
+        assert!(lazy(|cx| tx.poll_ready(cx)).await.is_pending());
+        assert!(lazy(|cx| tx2.poll_ready(cx)).await.is_pending());
+        assert_eq!(stream_recv(&mut rx).await, Some("test1"));
+        assert!(lazy(|cx| tx.poll_ready(cx)).await.is_ready());
+        tx2.send("test3").await.unwrap();
+        assert!(lazy(|cx| tx.poll_ready(cx)).await.is_pending());
+
+        assert_eq!(rx.recv().await, Some("test2"));
+        assert_eq!(rx.recv().await, Some("test3"));
+        drop(tx2);
+        drop(tx);
+        assert!(rx.is_closed());
+        assert_eq!(rx.recv().await, None);
+
+        let (tx, rx) = bounded::<&str>(1);
+        drop(rx);
+        assert!(tx.is_closed());
+        assert!(lazy(|cx| tx.poll_ready(cx)).await.is_ready());
+        assert!(tx.send("test").await.is_err());
+        let err = tx.try_send("test").err().unwrap();
+        assert!(matches!(err, TrySendError::Closed(_)));
+        assert!(format!("{}", err).contains("receiver is gone"));
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_bounded_sink() {
+        let (mut tx, mut rx) = bounded(1);
+        lazy(|cx| {
+            assert!(Pin::new(&mut tx).poll_ready(cx).is_ready());
+            assert!(Pin::new(&mut tx).start_send("test").is_ok());
+            assert!(Pin::new(&mut tx).poll_ready(cx).is_pending());
+            assert!(Pin::new(&mut tx).poll_flush(cx).is_ready());
+            assert!(Pin::new(&mut tx).poll_close(cx).is_ready());
+        })
+        .await;
+        assert_eq!(stream_recv(&mut rx).await.unwrap(), "test");
+        assert_eq!(stream_recv(&mut rx).await, None);
+    }
 }
@@ -5,7 +5,8 @@ use std::{collections::VecDeque, future::Future, marker::PhantomData, pin::Pin,
 
 use ntex_service::{IntoService, Service, Transform};
 
-use crate::{channel::oneshot, future::Either, task::LocalWaker};
+use crate::channel::{condition::Condition, condition::Waiter, oneshot};
+use crate::{future::poll_fn, future::Either, task::LocalWaker};
 
 /// Buffer - service factory for service that can buffer incoming request.
 ///
@@ -226,9 +227,239 @@ impl<R, S: Service<R, Error = E>, E> Future for BufferServiceResponse<R, S, E> {
     }
 }
 
+/// Buffer handle - cloneable service that passes requests to the service
+/// running on a separate local task.
+///
+/// Requests are passed through bounded queue, handle is ready if queue is
+/// not full. Inner service task stops when all handles are dropped.
+pub struct BufferHandle<R, S: Service<R>> {
+    shared: Rc<Shared<R, S>>,
+    waiter: Waiter,
+}
+
+type BufferItem<R, S> = (
+    R,
+    oneshot::Sender<Result<<S as Service<R>>::Future, <S as Service<R>>::Error>>,
+);
+
+struct Shared<R, S: Service<R>> {
+    size: usize,
+    closed: Cell<bool>,
+    waker: LocalWaker,
+    ready: Condition,
+    err: Rc<dyn Fn() -> S::Error>,
+    queue: RefCell<VecDeque<BufferItem<R, S>>>,
+}
+
+impl<R, E> Buffer<R, E> {
+    /// Spawn service on a separate local task and return handle to it.
+    pub fn spawn<S, U>(&self, service: U) -> BufferHandle<R, S>
+    where
+        S: Service<R, Error = E> + 'static,
+        U: IntoService<S, R>,
+        R: 'static,
+        E: 'static,
+    {
+        BufferHandle::spawn_service(self.buf_size, self.err.clone(), service.into_service())
+    }
+}
+
+impl<R, S> BufferHandle<R, S>
+where
+    S: Service<R> + 'static,
+    R: 'static,
+{
+    /// Spawn service on a separate local task.
+    ///
+    /// `err` is used for requests that cannot be processed because
+    /// service task is stopped.
+    pub fn new<U, F>(size: usize, err: F, service: U) -> Self
+    where
+        U: IntoService<S, R>,
+        F: Fn() -> S::Error + 'static,
+    {
+        Self::spawn_service(size, Rc::new(err), service.into_service())
+    }
+
+    fn spawn_service(size: usize, err: Rc<dyn Fn() -> S::Error>, service: S) -> Self {
+        let ready = Condition::new();
+        let shared = Rc::new(Shared {
+            size,
+            err,
+            closed: Cell::new(false),
+            waker: LocalWaker::default(),
+            queue: RefCell::new(VecDeque::with_capacity(size)),
+            ready: ready.clone(),
+        });
+        let mut worker = BufferWorker {
+            service,
+            shared: shared.clone(),
+            shutdown: false,
+        };
+        crate::spawn(async move { poll_fn(|cx| worker.poll(cx)).await });
+
+        BufferHandle {
+            waiter: ready.wait(),
+            shared,
+        }
+    }
+}
+
+impl<R, S: Service<R>> Clone for BufferHandle<R, S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            waiter: self.shared.ready.wait(),
+        }
+    }
+}
+
+impl<R, S: Service<R>> Drop for BufferHandle<R, S> {
+    fn drop(&mut self) {
+        // worker stops after last handle is dropped
+        self.shared.waker.wake();
+    }
+}
+
+impl<R, S: Service<R>> Service<R> for BufferHandle<R, S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BufferHandleResponse<R, S>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        loop {
+            if self.shared.closed.get() {
+                return Poll::Ready(Err((*self.shared.err)()));
+            } else if self.shared.queue.borrow().len() < self.shared.size {
+                return Poll::Ready(Ok(()));
+            } else if self.waiter.poll_ready(cx).is_pending() {
+                log::trace!("Buffer limit exceeded");
+                return Poll::Pending;
+            }
+        }
+    }
+
+    #[inline]
+    fn call(&self, req: R) -> Self::Future {
+        let (tx, rx) = oneshot::channel();
+        // if service task is stopped, sender is dropped and request fails
+        if !self.shared.closed.get() {
+            self.shared.queue.borrow_mut().push_back((req, tx));
+            self.shared.waker.wake();
+        }
+
+        BufferHandleResponse {
+            state: HandleState::Rx {
+                rx,
+                err: self.shared.err.clone(),
+            },
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[doc(hidden)]
+    pub struct BufferHandleResponse<R, S: Service<R>> {
+        #[pin]
+        state: HandleState<R, S>,
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = HandleStateProject]
+    enum HandleState<R, S: Service<R>> {
+        Rx { rx: oneshot::Receiver<Result<S::Future, S::Error>>, err: Rc<dyn Fn() -> S::Error> },
+        Srv { #[pin] fut: S::Future },
+    }
+}
+
+impl<R, S: Service<R>> Future for BufferHandleResponse<R, S> {
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+
+        loop {
+            match this.state.project() {
+                HandleStateProject::Rx { rx, err } => match Pin::new(rx).poll(cx) {
+                    Poll::Ready(Ok(Ok(fut))) => {
+                        this = self.as_mut().project();
+                        this.state.set(HandleState::Srv { fut });
+                    }
+                    Poll::Ready(Ok(Err(e))) => return Poll::Ready(Err(e)),
+                    Poll::Ready(Err(_)) => return Poll::Ready(Err((*err)())),
+                    Poll::Pending => return Poll::Pending,
+                },
+                HandleStateProject::Srv { fut } => return fut.poll(cx),
+            }
+        }
+    }
+}
+
+/// Service task, calls service for queued requests
+struct BufferWorker<R, S: Service<R>> {
+    service: S,
+    shared: Rc<Shared<R, S>>,
+    shutdown: bool,
+}
+
+impl<R, S: Service<R>> BufferWorker<R, S> {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let shared = self.shared.as_ref();
+        shared.waker.register(cx.waker());
+
+        if !self.shutdown {
+            loop {
+                {
+                    let mut queue = shared.queue.borrow_mut();
+
+                    // drop canceled requests
+                    while queue.front().map(|item| item.1.is_canceled()) == Some(true) {
+                        queue.pop_front();
+                        shared.ready.notify();
+                    }
+                    if queue.is_empty() {
+                        // all handles are dropped
+                        if Rc::strong_count(&self.shared) == 1 {
+                            break;
+                        }
+                        return Poll::Pending;
+                    }
+                }
+
+                match self.service.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => {
+                        let item = shared.queue.borrow_mut().pop_front();
+                        if let Some((req, tx)) = item {
+                            shared.ready.notify();
+                            let _ = tx.send(Ok(self.service.call(req)));
+                        }
+                    }
+                    Poll::Ready(Err(e)) => {
+                        log::trace!("Buffered service failed, stopping service task");
+                        let mut queue = shared.queue.borrow_mut();
+                        if let Some((_, tx)) = queue.pop_front() {
+                            let _ = tx.send(Err(e));
+                        }
+                        queue.clear();
+                        shared.closed.set(true);
+                        shared.ready.notify();
+                        break;
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            self.shutdown = true;
+        }
+
+        self.service.poll_shutdown(cx, shared.closed.get())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use ntex_service::{apply, fn_factory, Service, ServiceFactory};
+    use ntex_service::{apply, fn_factory, fn_service, Service, ServiceFactory};
     use std::task::{Context, Poll};
 
     use super::*;
@@ -350,4 +581,92 @@ mod tests {
         let _ = fut2.await;
         assert_eq!(inner.count.get(), 2);
     }
+
+    #[ntex_macros::rt_test2]
+    async fn test_handle() {
+        let inner = Rc::new(Inner {
+            ready: Cell::new(false),
+            waker: LocalWaker::default(),
+            count: Cell::new(0),
+        });
+
+        let srv = Buffer::new(|| ())
+            .buf_size(2)
+            .spawn(TestService(inner.clone()));
+        let srv2 = srv.clone();
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+
+        let fut1 = srv.call(());
+        assert_eq!(lazy(|cx| srv2.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        let fut2 = srv2.call(());
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Pending);
+        assert_eq!(lazy(|cx| srv2.poll_ready(cx)).await, Poll::Pending);
+        crate::time::sleep(crate::time::Millis(10)).await;
+        assert_eq!(inner.count.get(), 0);
+
+        inner.ready.set(true);
+        inner.waker.wake();
+        assert_eq!(fut1.await, Ok(()));
+        assert_eq!(inner.count.get(), 1);
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+
+        inner.ready.set(true);
+        inner.waker.wake();
+        assert_eq!(fut2.await, Ok(()));
+        assert_eq!(inner.count.get(), 2);
+
+        // concurrent producers
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        let srv = BufferHandle::new(
+            2,
+            || (),
+            fn_service(move |_| {
+                counter.set(counter.get() + 1);
+                Ready::<_, ()>::Ok(())
+            }),
+        );
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let srv = srv.clone();
+                crate::spawn(async move {
+                    crate::future::poll_fn(|cx| srv.poll_ready(cx)).await?;
+                    srv.call(()).await
+                })
+            })
+            .collect();
+        for hnd in handles {
+            assert_eq!(hnd.await.unwrap(), Ok(()));
+        }
+        assert_eq!(count.get(), 8);
+        assert!(lazy(|cx| srv.poll_shutdown(cx, false)).await.is_ready());
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_handle_error() {
+        struct Srv;
+
+        impl Service<()> for Srv {
+            type Response = ();
+            type Error = usize;
+            type Future = Ready<(), usize>;
+
+            fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), usize>> {
+                Poll::Ready(Err(1))
+            }
+
+            fn call(&self, _: ()) -> Self::Future {
+                Ready::Ok(())
+            }
+        }
+
+        let srv = BufferHandle::new(2, || 0, Srv);
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        let fut1 = srv.call(());
+        let fut2 = srv.call(());
+        assert_eq!(fut1.await, Err(1));
+        assert_eq!(fut2.await, Err(0));
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Err(0)));
+        assert_eq!(srv.call(()).await, Err(0));
+    }
 }
@@ -0,0 +1,293 @@
+//! Service that enforces absolute request deadlines.
+//!
+//! Deadline is attached to a request via extensions and propagated
+//! through the service pipeline. Inner services could query remaining
+//! time budget and pass it to outbound calls.
+use std::time::{Duration, Instant};
+use std::{future::Future, marker::PhantomData, pin::Pin, task::Context, task::Poll};
+
+use ntex_service::{Service, Transform};
+
+use super::{timeout::TimeoutError, Extensions};
+use crate::future::{Either, Ready};
+use crate::time::{now, sleep_until, Millis, Sleep};
+
+/// Absolute request deadline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Create deadline for specified instant.
+    pub fn new(instant: Instant) -> Self {
+        Deadline(instant)
+    }
+
+    /// Create deadline that elapses after `timeout`.
+    pub fn after<T: Into<Millis>>(timeout: T) -> Self {
+        Deadline(now() + Duration::from(timeout.into()))
+    }
+
+    /// Deadline instant.
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Remaining time budget, zero if deadline is elapsed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(now())
+    }
+
+    /// Returns `true` if deadline is elapsed.
+    pub fn is_elapsed(&self) -> bool {
+        now() >= self.0
+    }
+
+    /// Create sleep future that completes at deadline.
+    pub fn sleep(&self) -> Sleep {
+        sleep_until(self.0)
+    }
+}
+
+/// Requests that could carry deadline.
+pub trait HasDeadline {
+    /// Request deadline, if set.
+    fn deadline(&self) -> Option<Deadline>;
+
+    /// Set request deadline.
+    fn set_deadline(&mut self, deadline: Deadline);
+
+    /// Remaining time budget, if deadline is set.
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline().map(|d| d.remaining())
+    }
+}
+
+impl HasDeadline for Extensions {
+    fn deadline(&self) -> Option<Deadline> {
+        self.get::<Deadline>().copied()
+    }
+
+    fn set_deadline(&mut self, deadline: Deadline) {
+        self.insert(deadline)
+    }
+}
+
+/// Enforces request deadlines.
+///
+/// If request does not carry deadline, default timeout is used for
+/// creating new deadline. Default timeout is disabled if it is set to 0.
+/// Earlier deadline set by upstream is never extended.
+#[derive(Debug, Clone)]
+pub struct DeadlineTimeout {
+    default: Millis,
+}
+
+impl Default for DeadlineTimeout {
+    fn default() -> Self {
+        DeadlineTimeout {
+            default: Millis::ZERO,
+        }
+    }
+}
+
+impl DeadlineTimeout {
+    /// Create deadline transform without default timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set default timeout for requests without deadline.
+    pub fn default_timeout<T: Into<Millis>>(mut self, timeout: T) -> Self {
+        self.default = timeout.into();
+        self
+    }
+}
+
+impl<S> Transform<S> for DeadlineTimeout {
+    type Service = DeadlineTimeoutService<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        DeadlineTimeoutService {
+            service,
+            default: self.default,
+        }
+    }
+}
+
+/// Enforces request deadlines.
+#[derive(Debug, Clone)]
+pub struct DeadlineTimeoutService<S> {
+    service: S,
+    default: Millis,
+}
+
+impl<S> DeadlineTimeoutService<S> {
+    /// Create deadline service without default timeout.
+    pub fn new(service: S) -> Self {
+        DeadlineTimeoutService {
+            service,
+            default: Millis::ZERO,
+        }
+    }
+}
+
+impl<S, R> Service<R> for DeadlineTimeoutService<S>
+where
+    S: Service<R>,
+    R: HasDeadline,
+{
+    type Response = S::Response;
+    type Error = TimeoutError<S::Error>;
+    type Future =
+        Either<DeadlineServiceResponse<S, R>, Ready<S::Response, TimeoutError<S::Error>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(TimeoutError::Service)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, mut req: R) -> Self::Future {
+        let mut deadline = req.deadline();
+        if !self.default.is_zero() {
+            let default = Deadline::after(self.default);
+            if deadline.map(|d| default < d).unwrap_or(true) {
+                req.set_deadline(default);
+                deadline = Some(default);
+            }
+        }
+
+        if deadline.map(|d| d.is_elapsed()).unwrap_or(false) {
+            Either::Right(Ready::Err(TimeoutError::Timeout))
+        } else {
+            Either::Left(DeadlineServiceResponse {
+                sleep: deadline.map(|d| d.sleep()),
+                fut: self.service.call(req),
+                _t: PhantomData,
+            })
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// `DeadlineTimeoutService` response future
+    #[doc(hidden)]
+    pub struct DeadlineServiceResponse<S: Service<R>, R> {
+        #[pin]
+        fut: S::Future,
+        sleep: Option<Sleep>,
+        _t: PhantomData<R>
+    }
+}
+
+impl<S, R> Future for DeadlineServiceResponse<S, R>
+where
+    S: Service<R>,
+{
+    type Output = Result<S::Response, TimeoutError<S::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        match this.fut.poll(cx) {
+            Poll::Ready(Ok(v)) => return Poll::Ready(Ok(v)),
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(TimeoutError::Service(e))),
+            Poll::Pending => {}
+        }
+
+        match this.sleep {
+            Some(ref sleep) if sleep.poll_elapsed(cx).is_ready() => {
+                Poll::Ready(Err(TimeoutError::Timeout))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ntex_service::{apply, fn_factory, fn_service, ServiceFactory};
+
+    use super::*;
+    use crate::time::sleep;
+
+    struct Req(Extensions, Millis);
+
+    impl HasDeadline for Req {
+        fn deadline(&self) -> Option<Deadline> {
+            self.0.deadline()
+        }
+
+        fn set_deadline(&mut self, deadline: Deadline) {
+            self.0.set_deadline(deadline)
+        }
+    }
+
+    fn req(wait: u32, deadline: Option<Deadline>) -> Req {
+        let mut ext = Extensions::new();
+        if let Some(d) = deadline {
+            ext.set_deadline(d);
+        }
+        Req(ext, Millis(wait))
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_deadline() {
+        let srv = DeadlineTimeoutService::new(fn_service(|req: Req| async move {
+            let remaining = req.remaining();
+            sleep(req.1).await;
+            Ok::<_, ()>(remaining)
+        }));
+
+        // no deadline
+        assert_eq!(srv.call(req(10, None)).await, Ok(None));
+
+        let res = srv
+            .call(req(10, Some(Deadline::after(Millis(500)))))
+            .await
+            .unwrap();
+        assert!(res.unwrap() > Duration::from_millis(400));
+
+        let res = srv.call(req(500, Some(Deadline::after(Millis(50))))).await;
+        assert_eq!(res, Err(TimeoutError::Timeout));
+
+        // elapsed
+        let res = srv
+            .call(req(
+                0,
+                Some(Deadline::new(Instant::now() - Duration::from_secs(1))),
+            ))
+            .await;
+        assert_eq!(res, Err(TimeoutError::Timeout));
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_default_timeout() {
+        let factory = apply(
+            DeadlineTimeout::new().default_timeout(Millis(100)),
+            fn_factory(|| async {
+                Ok::<_, ()>(fn_service(|req: Req| async move {
+                    let deadline = req.deadline();
+                    sleep(req.1).await;
+                    Ok::<_, ()>(deadline)
+                }))
+            }),
+        );
+        let srv = factory.new_service(&()).await.unwrap();
+
+        assert!(srv.call(req(10, None)).await.unwrap().is_some());
+        assert_eq!(srv.call(req(500, None)).await, Err(TimeoutError::Timeout));
+
+        // earlier deadline is preserved
+        let d = Deadline::after(Millis(50));
+        assert_eq!(srv.call(req(10, Some(d))).await, Ok(Some(d)));
+        // later deadline is shortened
+        let d = Deadline::after(Millis::ONE_SEC);
+        let res = srv.call(req(10, Some(d))).await.unwrap().unwrap();
+        assert!(res < d);
+    }
+}
@@ -0,0 +1,361 @@
+//! Service that hedges slow requests.
+//!
+//! If the response does not complete within latency threshold, duplicate
+//! request is sent to the inner service. Response of the first completed
+//! call is returned, other calls are cancelled.
+use std::{cell::RefCell, cmp, collections::VecDeque, future::Future, pin::Pin, rc::Rc};
+use std::{task::Context, task::Poll, time::Instant};
+
+use ntex_service::{IntoService, Service, Transform};
+
+use crate::time::{now, sleep, Millis, Sleep};
+
+/// Min number of latency samples required for computing hedge delay
+const MIN_SAMPLES: usize = 10;
+
+/// Hedges slow requests.
+///
+/// Hedge delay is computed from rolling percentile of response latencies,
+/// until enough samples are collected, configured `delay` is used.
+/// `delay` is also a lower bound for computed hedge delay. Requests
+/// must be cloneable.
+///
+/// By default 95th percentile of last 100 responses is used.
+#[derive(Debug, Clone)]
+pub struct Hedge {
+    delay: Millis,
+    max_extra: usize,
+    percentile: u8,
+    window: usize,
+}
+
+impl Hedge {
+    /// Create hedge transform.
+    ///
+    /// `max_extra` is the max number of extra calls for each request.
+    pub fn new<T: Into<Millis>>(delay: T, max_extra: usize) -> Self {
+        Hedge {
+            delay: delay.into(),
+            max_extra,
+            percentile: 95,
+            window: 100,
+        }
+    }
+
+    /// Set latency percentile used as hedge delay.
+    ///
+    /// Value is capped at 100.
+    pub fn percentile(mut self, percentile: u8) -> Self {
+        self.percentile = cmp::min(percentile, 100);
+        self
+    }
+
+    /// Set number of latency samples used for percentile computation.
+    pub fn window(mut self, size: usize) -> Self {
+        self.window = cmp::max(size, 1);
+        self
+    }
+
+    fn latency(&self) -> Latency {
+        Latency {
+            delay: self.delay,
+            max_extra: self.max_extra,
+            percentile: self.percentile,
+            window: self.window,
+            samples: RefCell::new(VecDeque::with_capacity(self.window)),
+        }
+    }
+}
+
+impl<S> Transform<S> for Hedge {
+    type Service = HedgeService<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        HedgeService {
+            service: Rc::new(service),
+            latency: Rc::new(self.latency()),
+        }
+    }
+}
+
+/// Rolling window of response latencies
+struct Latency {
+    delay: Millis,
+    max_extra: usize,
+    percentile: u8,
+    window: usize,
+    samples: RefCell<VecDeque<u32>>,
+}
+
+impl Latency {
+    fn record(&self, start: Instant) {
+        let elapsed = now().saturating_duration_since(start).as_millis();
+        let mut samples = self.samples.borrow_mut();
+        if samples.len() == self.window {
+            samples.pop_front();
+        }
+        samples.push_back(cmp::min(elapsed, u32::MAX as u128) as u32);
+    }
+
+    /// Current hedge delay
+    fn delay(&self) -> Millis {
+        let samples = self.samples.borrow();
+        if samples.len() < cmp::min(MIN_SAMPLES, self.window) {
+            return self.delay;
+        }
+
+        let mut sorted: Vec<_> = samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let idx = (sorted.len() - 1) * self.percentile as usize / 100;
+        cmp::max(Millis(sorted[idx]), self.delay)
+    }
+}
+
+/// Hedges slow requests.
+pub struct HedgeService<S> {
+    service: Rc<S>,
+    latency: Rc<Latency>,
+}
+
+impl<S> HedgeService<S> {
+    pub fn new<U, R>(hedge: Hedge, service: U) -> Self
+    where
+        S: Service<R>,
+        U: IntoService<S, R>,
+    {
+        HedgeService {
+            service: Rc::new(service.into_service()),
+            latency: Rc::new(hedge.latency()),
+        }
+    }
+}
+
+impl<S> Clone for HedgeService<S> {
+    fn clone(&self) -> Self {
+        HedgeService {
+            service: self.service.clone(),
+            latency: self.latency.clone(),
+        }
+    }
+}
+
+impl<S, R> Service<R> for HedgeService<S>
+where
+    S: Service<R>,
+    R: Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = HedgeServiceResponse<S, R>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: R) -> Self::Future {
+        let (fut, req, delay) = if self.latency.max_extra == 0 {
+            (self.service.call(req), None, None)
+        } else {
+            let delay = self.latency.delay();
+            (
+                self.service.call(req.clone()),
+                Some(req),
+                Some((sleep(delay), delay)),
+            )
+        };
+
+        HedgeServiceResponse {
+            fut,
+            extra: Vec::new(),
+            req,
+            delay,
+            start: now(),
+            service: self.service.clone(),
+            latency: self.latency.clone(),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// `HedgeService` response future
+    #[doc(hidden)]
+    pub struct HedgeServiceResponse<S: Service<R>, R> {
+        #[pin]
+        fut: S::Future,
+        extra: Vec<Pin<Box<S::Future>>>,
+        req: Option<R>,
+        delay: Option<(Sleep, Millis)>,
+        start: Instant,
+        service: Rc<S>,
+        latency: Rc<Latency>,
+    }
+}
+
+impl<S, R> Future for HedgeServiceResponse<S, R>
+where
+    S: Service<R>,
+    R: Clone,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        loop {
+            if let Poll::Ready(res) = this.fut.as_mut().poll(cx) {
+                this.latency.record(*this.start);
+                return Poll::Ready(res);
+            }
+            for fut in this.extra.iter_mut() {
+                if let Poll::Ready(res) = fut.as_mut().poll(cx) {
+                    this.latency.record(*this.start);
+                    return Poll::Ready(res);
+                }
+            }
+
+            // send extra request
+            if let Some((ref sleep, delay)) = this.delay {
+                if sleep.poll_elapsed(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                match this.service.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => {
+                        log::trace!("Response is slow, sending hedged request");
+                        let req = this.req.as_ref().unwrap().clone();
+                        this.extra.push(Box::pin(this.service.call(req)));
+                        if this.extra.len() < this.latency.max_extra {
+                            sleep.reset(*delay);
+                        } else {
+                            *this.delay = None;
+                            *this.req = None;
+                        }
+                    }
+                    // inner service failed, wait for in-flight calls
+                    Poll::Ready(Err(_)) => {
+                        *this.delay = None;
+                        *this.req = None;
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            } else {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, task::Context, task::Poll};
+
+    use ntex_service::{apply, fn_factory, Service, ServiceFactory};
+
+    use super::*;
+    use crate::future::lazy;
+
+    /// Service responds with call number, calls below `fast` are slow
+    #[derive(Clone)]
+    struct Srv(Rc<Cell<usize>>, usize);
+
+    impl Service<()> for Srv {
+        type Response = usize;
+        type Error = ();
+        type Future = Pin<Box<dyn Future<Output = Result<usize, ()>>>>;
+
+        fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&self, _: ()) -> Self::Future {
+            let num = self.0.get() + 1;
+            self.0.set(num);
+            let fut = if num < self.1 {
+                sleep(Millis(500))
+            } else {
+                sleep(Millis(10))
+            };
+            Box::pin(async move {
+                fut.await;
+                Ok(num)
+            })
+        }
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_hedge() {
+        let cnt = Rc::new(Cell::new(0));
+        let srv = HedgeService::new(Hedge::new(Millis(50), 1), Srv(cnt.clone(), 2)).clone();
+        assert!(lazy(|cx| srv.poll_ready(cx)).await.is_ready());
+
+        let start = std::time::Instant::now();
+        assert_eq!(srv.call(()).await, Ok(2));
+        assert!(start.elapsed() < std::time::Duration::from_millis(300));
+        assert_eq!(cnt.get(), 2);
+
+        // fast response, no hedging
+        assert_eq!(srv.call(()).await, Ok(3));
+        assert_eq!(cnt.get(), 3);
+
+        assert!(lazy(|cx| srv.poll_shutdown(cx, false)).await.is_ready());
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_max_extra() {
+        // hedging is disabled
+        let cnt = Rc::new(Cell::new(0));
+        let srv = HedgeService::new(Hedge::new(Millis(20), 0), Srv(cnt.clone(), 2));
+        assert_eq!(srv.call(()).await, Ok(1));
+        assert_eq!(cnt.get(), 1);
+
+        let cnt = Rc::new(Cell::new(0));
+        let srv = HedgeService::new(Hedge::new(Millis(20), 2), Srv(cnt.clone(), 10));
+        assert_eq!(srv.call(()).await, Ok(1));
+        assert_eq!(cnt.get(), 3);
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_newservice() {
+        let cnt = Rc::new(Cell::new(0));
+        let factory = apply(
+            Hedge::new(Millis(50), 1).percentile(99).window(10),
+            fn_factory({
+                let cnt = cnt.clone();
+                move || {
+                    let cnt = cnt.clone();
+                    async move { Ok::<_, ()>(Srv(cnt, 2)) }
+                }
+            }),
+        );
+
+        let srv = factory.new_service(&()).await.unwrap();
+        assert_eq!(srv.call(()).await, Ok(2));
+        assert_eq!(cnt.get(), 2);
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_latency() {
+        let latency = Hedge::new(Millis(5), 1).window(20).percentile(90).latency();
+        assert_eq!(latency.delay(), Millis(5));
+
+        for i in 1..=20 {
+            latency.samples.borrow_mut().push_back(i);
+        }
+        assert_eq!(latency.delay(), Millis(18));
+
+        let start = now();
+        for _ in 0..20 {
+            latency.record(start);
+        }
+        assert_eq!(latency.samples.borrow().len(), 20);
+        assert_eq!(latency.delay(), Millis(5));
+
+        let latency = Hedge::new(Millis(5), 1).percentile(200).latency();
+        assert_eq!(latency.percentile, 100);
+    }
+}
@@ -8,7 +8,9 @@ use super::counter::{Counter, CounterGuard};
 /// InFlight - service factory for service that can limit number of in-flight
 /// async requests.
 ///
-/// Default number of in-flight requests is 15
+/// Default number of in-flight requests is 15. Use it with
+/// [`LoadShed`](super::loadshed::LoadShed) to reject requests above the limit
+/// instead of waiting for capacity.
 pub struct InFlight {
     max_inflight: usize,
 }
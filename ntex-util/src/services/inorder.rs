@@ -0,0 +1,324 @@
+//! Service that runs requests concurrently and returns responses in request order.
+use std::task::{Context, Poll};
+use std::{cell::Cell, cell::RefCell, cmp, collections::VecDeque, future::Future};
+use std::{marker::PhantomData, pin::Pin, rc::Rc};
+
+use ntex_service::{IntoService, Service, Transform};
+
+use crate::task::LocalWaker;
+
+/// InOrder - service factory for service that runs up to `max` requests
+/// concurrently, responses are returned in request order.
+///
+/// Response is returned only after responses for all previous requests
+/// are returned. Service is not ready if number of requests in reorder
+/// buffer reaches `max`.
+///
+/// Default number of concurrent requests is 16
+pub struct InOrder<R> {
+    max: usize,
+    _t: PhantomData<R>,
+}
+
+impl<R> InOrder<R> {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: cmp::max(max, 1),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<R> Default for InOrder<R> {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl<R> Clone for InOrder<R> {
+    fn clone(&self) -> Self {
+        Self::new(self.max)
+    }
+}
+
+impl<R, S> Transform<S> for InOrder<R>
+where
+    S: Service<R>,
+{
+    type Service = InOrderService<R, S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        InOrderService::create(self.max, service)
+    }
+}
+
+/// Service that runs requests concurrently and returns responses in request order.
+pub struct InOrderService<R, S: Service<R>> {
+    service: S,
+    inner: Rc<Inner<R, S>>,
+}
+
+/// Reorder buffer
+struct Inner<R, S: Service<R>> {
+    max: usize,
+    // id of the first entry in queue
+    head: Cell<usize>,
+    queue: RefCell<VecDeque<Entry<R, S>>>,
+    waker: LocalWaker,
+}
+
+struct Entry<R, S: Service<R>> {
+    state: EntryState<R, S>,
+    waker: LocalWaker,
+}
+
+enum EntryState<R, S: Service<R>> {
+    Call(Pin<Box<S::Future>>),
+    Done(Result<S::Response, S::Error>),
+    Canceled,
+}
+
+impl<R, S> InOrderService<R, S>
+where
+    S: Service<R>,
+{
+    pub fn new<U>(max: usize, service: U) -> Self
+    where
+        U: IntoService<S, R>,
+    {
+        Self::create(cmp::max(max, 1), service.into_service())
+    }
+
+    fn create(max: usize, service: S) -> Self {
+        Self {
+            service,
+            inner: Rc::new(Inner {
+                max,
+                head: Cell::new(0),
+                queue: RefCell::new(VecDeque::with_capacity(max)),
+                waker: LocalWaker::new(),
+            }),
+        }
+    }
+}
+
+impl<R, S> Service<R> for InOrderService<R, S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = InOrderServiceResponse<R, S>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.service.poll_ready(cx)?.is_pending() {
+            Poll::Pending
+        } else if self.inner.queue.borrow().len() >= self.inner.max {
+            log::trace!("InOrder reorder buffer is full");
+            self.inner.waker.register(cx.waker());
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    #[inline]
+    fn call(&self, req: R) -> Self::Future {
+        let fut = Box::pin(self.service.call(req));
+        let mut queue = self.inner.queue.borrow_mut();
+        let id = self.inner.head.get() + queue.len();
+        queue.push_back(Entry {
+            state: EntryState::Call(fut),
+            waker: LocalWaker::new(),
+        });
+
+        InOrderServiceResponse {
+            id,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<R, S: Service<R>> Inner<R, S> {
+    /// Poll in-flight calls
+    fn poll_calls(&self, cx: &mut Context<'_>) {
+        for entry in self.queue.borrow_mut().iter_mut() {
+            if let EntryState::Call(ref mut fut) = entry.state {
+                if let Poll::Ready(res) = fut.as_mut().poll(cx) {
+                    entry.state = EntryState::Done(res);
+                }
+            }
+        }
+    }
+
+    /// Remove canceled entries from the head of the queue
+    fn pop_canceled(&self, queue: &mut VecDeque<Entry<R, S>>) {
+        while let Some(EntryState::Canceled) = queue.front().map(|e| &e.state) {
+            queue.pop_front();
+            self.head.set(self.head.get() + 1);
+            self.waker.wake();
+        }
+    }
+}
+
+/// `InOrderService` response future
+pub struct InOrderServiceResponse<R, S: Service<R>> {
+    id: usize,
+    inner: Rc<Inner<R, S>>,
+}
+
+impl<R, S: Service<R>> Future for InOrderServiceResponse<R, S> {
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.inner.as_ref();
+        inner.poll_calls(cx);
+
+        let mut queue = inner.queue.borrow_mut();
+        if self.id == inner.head.get() {
+            if let Some(EntryState::Done(_)) = queue.front().map(|e| &e.state) {
+                let entry = queue.pop_front().unwrap();
+                inner.head.set(self.id + 1);
+                inner.pop_canceled(&mut queue);
+                inner.waker.wake();
+
+                // next response could be ready
+                if let Some(next) = queue.front() {
+                    next.waker.wake();
+                }
+                if let EntryState::Done(res) = entry.state {
+                    return Poll::Ready(res);
+                }
+            }
+        } else if let Some(EntryState::Done(_)) = queue.front().map(|e| &e.state) {
+            // head response is ready
+            queue.front().unwrap().waker.wake();
+        }
+
+        queue[self.id - inner.head.get()].waker.register(cx.waker());
+        Poll::Pending
+    }
+}
+
+impl<R, S: Service<R>> Drop for InOrderServiceResponse<R, S> {
+    fn drop(&mut self) {
+        let mut queue = self.inner.queue.borrow_mut();
+        let head = self.inner.head.get();
+        if self.id >= head {
+            if let Some(entry) = queue.get_mut(self.id - head) {
+                entry.state = EntryState::Canceled;
+            }
+            self.inner.pop_canceled(&mut queue);
+            if let Some(next) = queue.front() {
+                next.waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ntex_service::{apply, fn_factory, Service, ServiceFactory};
+    use std::{future::Future, pin::Pin, task::Context, task::Poll};
+
+    use super::*;
+    use crate::channel::oneshot;
+    use crate::future::lazy;
+
+    struct Srv;
+
+    impl Service<oneshot::Receiver<usize>> for Srv {
+        type Response = usize;
+        type Error = ();
+        type Future = Pin<Box<dyn Future<Output = Result<usize, ()>>>>;
+
+        fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&self, rx: oneshot::Receiver<usize>) -> Self::Future {
+            Box::pin(async move { rx.await.map_err(|_| ()) })
+        }
+    }
+
+    fn poll<F: Future + Unpin>(fut: &mut F) -> impl Future<Output = Poll<F::Output>> + '_ {
+        lazy(move |cx| Pin::new(fut).poll(cx))
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_inorder() {
+        let (tx1, rx1) = oneshot::channel();
+        let (tx2, rx2) = oneshot::channel();
+        let (tx3, rx3) = oneshot::channel();
+
+        let srv = InOrderService::new(3, Srv);
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        let mut fut1 = srv.call(rx1);
+        let mut fut2 = srv.call(rx2);
+        let mut fut3 = srv.call(rx3);
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Pending);
+
+        let _ = tx3.send(3);
+        assert_eq!(poll(&mut fut3).await, Poll::Pending);
+        let _ = tx2.send(2);
+        assert_eq!(poll(&mut fut2).await, Poll::Pending);
+        assert_eq!(poll(&mut fut3).await, Poll::Pending);
+
+        let _ = tx1.send(1);
+        assert_eq!(poll(&mut fut3).await, Poll::Pending);
+        assert_eq!(poll(&mut fut1).await, Poll::Ready(Ok(1)));
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        assert_eq!(fut2.await, Ok(2));
+        assert_eq!(fut3.await, Ok(3));
+        assert!(lazy(|cx| srv.poll_shutdown(cx, false)).await.is_ready());
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_inorder_cancel() {
+        let (_tx1, rx1) = oneshot::channel();
+        let (tx2, rx2) = oneshot::channel();
+        let (_tx3, rx3) = oneshot::channel();
+
+        let srv = InOrderService::new(2, Srv);
+        let mut fut1 = srv.call(rx1);
+        let fut2 = srv.call(rx2);
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Pending);
+
+        let _ = tx2.send(2);
+        assert_eq!(poll(&mut fut1).await, Poll::Pending);
+
+        // canceled head response
+        drop(fut1);
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        assert_eq!(fut2.await, Ok(2));
+
+        // canceled response in the middle of the queue
+        let (tx1, rx1) = oneshot::channel();
+        let fut1 = srv.call(rx1);
+        let fut3 = srv.call(rx3);
+        drop(fut3);
+        let _ = tx1.send(1);
+        assert_eq!(fut1.await, Ok(1));
+        assert_eq!(srv.inner.queue.borrow().len(), 0);
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_newtransform() {
+        let srv = apply(InOrder::new(1), fn_factory(|| async { Ok::<_, ()>(Srv) }));
+        let srv = srv.new_service(&()).await.unwrap();
+
+        let (tx1, rx1) = oneshot::channel();
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        let fut1 = srv.call(rx1);
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Pending);
+        let _ = tx1.send(1);
+        assert_eq!(fut1.await, Ok(1));
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+    }
+}
@@ -1,10 +1,10 @@
 use std::task::{Context, Poll};
 use std::{
-    cell::Cell, convert::Infallible, convert::TryInto, marker, time::Duration,
-    time::Instant,
+    cell::Cell, convert::Infallible, convert::TryInto, future::Future, marker, pin::Pin,
+    rc::Rc, time::Duration, time::Instant,
 };
 
-use ntex_service::{Service, ServiceFactory};
+use ntex_service::{IntoService, Service, ServiceFactory, Transform};
 
 use crate::future::Ready;
 use crate::time::{now, sleep, Millis, Sleep};
@@ -122,9 +122,203 @@ where
     }
 }
 
+/// IdleTimeout - service factory for service that fails if inner service
+/// is idle for a specified period of time.
+///
+/// Any successful call resets idle timer. If ping hook is set, it is called
+/// after idle period, service fails if there is no activity during
+/// grace period after ping.
+pub struct IdleTimeout<E> {
+    timeout: Millis,
+    grace: Millis,
+    err: Rc<dyn Fn() -> E>,
+    on_idle: Option<Rc<dyn Fn()>>,
+    on_ping: Option<Rc<dyn Fn()>>,
+}
+
+impl<E> IdleTimeout<E> {
+    /// Construct IdleTimeout service factory.
+    ///
+    /// timeout - idle timeout
+    /// err - error factory function
+    pub fn new<F>(timeout: Millis, err: F) -> Self
+    where
+        F: Fn() -> E + 'static,
+    {
+        IdleTimeout {
+            timeout,
+            grace: Millis::ZERO,
+            err: Rc::new(err),
+            on_idle: None,
+            on_ping: None,
+        }
+    }
+
+    /// Set callback that is called on idle timeout expiry.
+    pub fn on_idle<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + 'static,
+    {
+        self.on_idle = Some(Rc::new(f));
+        self
+    }
+
+    /// Set ping hook and grace period.
+    ///
+    /// Hook is called after idle period, for example to send protocol level ping.
+    /// Service fails if there is no activity during grace period.
+    pub fn ping<F>(mut self, grace: Millis, f: F) -> Self
+    where
+        F: Fn() + 'static,
+    {
+        self.grace = grace;
+        self.on_ping = Some(Rc::new(f));
+        self
+    }
+}
+
+impl<E> Clone for IdleTimeout<E> {
+    fn clone(&self) -> Self {
+        IdleTimeout {
+            timeout: self.timeout,
+            grace: self.grace,
+            err: self.err.clone(),
+            on_idle: self.on_idle.clone(),
+            on_ping: self.on_ping.clone(),
+        }
+    }
+}
+
+impl<S, E> Transform<S> for IdleTimeout<E> {
+    type Service = IdleTimeoutService<S, E>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        IdleTimeoutService::create(self.clone(), service)
+    }
+}
+
+/// Service that fails if inner service is idle for a specified period of time.
+pub struct IdleTimeoutService<S, E> {
+    service: S,
+    cfg: IdleTimeout<E>,
+    sleep: Sleep,
+    pinged: Cell<Option<Instant>>,
+    expired: Cell<bool>,
+    activity: Rc<Cell<Instant>>,
+}
+
+impl<S, E> IdleTimeoutService<S, E> {
+    pub fn new<U, R>(cfg: IdleTimeout<E>, service: U) -> Self
+    where
+        S: Service<R, Error = E>,
+        U: IntoService<S, R>,
+    {
+        Self::create(cfg, service.into_service())
+    }
+
+    fn create(cfg: IdleTimeout<E>, service: S) -> Self {
+        IdleTimeoutService {
+            service,
+            sleep: sleep(cfg.timeout),
+            pinged: Cell::new(None),
+            expired: Cell::new(false),
+            activity: Rc::new(Cell::new(now())),
+            cfg,
+        }
+    }
+
+    fn reset(&self, cx: &mut Context<'_>, millis: Millis) {
+        self.sleep.reset(millis);
+        let _ = self.sleep.poll_elapsed(cx);
+    }
+}
+
+impl<S, R, E> Service<R> for IdleTimeoutService<S, E>
+where
+    S: Service<R, Error = E>,
+{
+    type Response = S::Response;
+    type Error = E;
+    type Future = IdleTimeoutResponse<S, R>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.expired.get() {
+            return Poll::Ready(Err((*self.cfg.err)()));
+        }
+
+        if self.sleep.poll_elapsed(cx).is_ready() {
+            let now = now();
+            let activity = self.activity.get();
+            let expire = activity + Duration::from(self.cfg.timeout);
+
+            // ping is answered if there was activity after ping
+            if matches!(self.pinged.get(), Some(pinged) if activity > pinged) {
+                self.pinged.set(None);
+            }
+
+            if expire > now {
+                // there was activity, restart idle timer
+                let expire = expire - now;
+                self.reset(
+                    cx,
+                    Millis(expire.as_millis().try_into().unwrap_or(u32::MAX)),
+                );
+            } else if self.cfg.on_ping.is_some() && self.pinged.get().is_none() {
+                log::trace!("Service is idle, sending ping");
+                self.pinged.set(Some(now));
+                self.reset(cx, self.cfg.grace);
+                (*self.cfg.on_ping.as_ref().unwrap())();
+            } else {
+                log::trace!("Service is idle, idle timeout expired");
+                self.expired.set(true);
+                if let Some(ref f) = self.cfg.on_idle {
+                    (*f)();
+                }
+                return Poll::Ready(Err((*self.cfg.err)()));
+            }
+        }
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    #[inline]
+    fn call(&self, req: R) -> Self::Future {
+        IdleTimeoutResponse {
+            fut: self.service.call(req),
+            activity: self.activity.clone(),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[doc(hidden)]
+    pub struct IdleTimeoutResponse<S: Service<R>, R> {
+        #[pin]
+        fut: S::Future,
+        activity: Rc<Cell<Instant>>,
+    }
+}
+
+impl<S: Service<R>, R> Future for IdleTimeoutResponse<S, R> {
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = futures_core::ready!(this.fut.poll(cx));
+        if res.is_ok() {
+            this.activity.set(now());
+        }
+        Poll::Ready(res)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use ntex_service::{Service, ServiceFactory};
+    use ntex_service::{apply, fn_factory, fn_service, Service, ServiceFactory};
 
     use super::*;
     use crate::future::lazy;
@@ -148,4 +342,81 @@ mod tests {
             Poll::Ready(Err(TestErr))
         );
     }
+
+    #[ntex_macros::rt_test2]
+    async fn test_idle_timeout() {
+        let idle = Rc::new(Cell::new(0));
+        let idle2 = idle.clone();
+        let service = IdleTimeoutService::new(
+            IdleTimeout::new(Millis(100), || TestErr).on_idle(move || {
+                idle2.set(idle2.get() + 1);
+            }),
+            fn_service(|req: usize| async move {
+                if req == 0 {
+                    Err(TestErr)
+                } else {
+                    Ok(req)
+                }
+            }),
+        );
+
+        // successful calls reset idle timer
+        for _ in 0..4 {
+            sleep(Millis(50)).await;
+            assert_eq!(lazy(|cx| service.poll_ready(cx)).await, Poll::Ready(Ok(())));
+            assert_eq!(service.call(1).await, Ok(1));
+        }
+
+        // failed calls do not reset idle timer
+        sleep(Millis(60)).await;
+        assert_eq!(service.call(0).await, Err(TestErr));
+        sleep(Millis(60)).await;
+        assert_eq!(
+            lazy(|cx| service.poll_ready(cx)).await,
+            Poll::Ready(Err(TestErr))
+        );
+        assert_eq!(idle.get(), 1);
+        assert_eq!(
+            lazy(|cx| service.poll_ready(cx)).await,
+            Poll::Ready(Err(TestErr))
+        );
+        assert_eq!(idle.get(), 1);
+        assert!(lazy(|cx| service.poll_shutdown(cx, false)).await.is_ready());
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_idle_ping() {
+        let ping = Rc::new(Cell::new(0));
+        let ping2 = ping.clone();
+        let factory = apply(
+            IdleTimeout::new(Millis(100), || TestErr).ping(Millis(100), move || {
+                ping2.set(ping2.get() + 1);
+            }),
+            fn_factory(|| async {
+                Ok::<_, TestErr>(fn_service(
+                    |req: usize| async move { Ok::<_, TestErr>(req) },
+                ))
+            }),
+        );
+        let service = factory.clone().new_service(()).await.unwrap();
+
+        sleep(Millis(150)).await;
+        assert_eq!(lazy(|cx| service.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        assert_eq!(ping.get(), 1);
+
+        // activity during grace period
+        sleep(Millis(20)).await;
+        assert_eq!(service.call(1).await, Ok(1));
+        sleep(Millis(120)).await;
+        assert_eq!(lazy(|cx| service.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        assert_eq!(ping.get(), 2);
+
+        // no activity during grace period
+        sleep(Millis(150)).await;
+        assert_eq!(
+            lazy(|cx| service.poll_ready(cx)).await,
+            Poll::Ready(Err(TestErr))
+        );
+        assert_eq!(ping.get(), 2);
+    }
 }
@@ -0,0 +1,242 @@
+//! Service that rejects requests when inner service is not ready.
+//!
+//! Combined with [`InFlight`](super::inflight::InFlight) it limits number of
+//! concurrent requests and fails requests above the limit immediately,
+//! instead of waiting for capacity.
+use std::{
+    cell::Cell, fmt, future::Future, marker::PhantomData, pin::Pin, task::Context,
+    task::Poll,
+};
+
+use ntex_service::{IntoService, Service, Transform};
+
+use crate::future::{Either, Ready};
+
+/// LoadShed - service factory for service that rejects requests
+/// if inner service is not ready to accept them.
+///
+/// `LoadShedService` is always ready, if inner service is not ready
+/// request fails with `LoadShedError::Overloaded` error.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LoadShed;
+
+impl LoadShed {
+    pub fn new() -> Self {
+        LoadShed
+    }
+}
+
+impl<S> Transform<S> for LoadShed {
+    type Service = LoadShedService<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        LoadShedService {
+            service,
+            ready: Cell::new(false),
+        }
+    }
+}
+
+/// Load shedding error
+pub enum LoadShedError<E> {
+    /// Service error
+    Service(E),
+    /// Service is overloaded, request is rejected
+    Overloaded,
+}
+
+impl<E> From<E> for LoadShedError<E> {
+    fn from(err: E) -> Self {
+        LoadShedError::Service(err)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for LoadShedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadShedError::Service(e) => write!(f, "LoadShedError::Service({:?})", e),
+            LoadShedError::Overloaded => write!(f, "LoadShedError::Overloaded"),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for LoadShedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadShedError::Service(e) => e.fmt(f),
+            LoadShedError::Overloaded => write!(f, "Service is overloaded"),
+        }
+    }
+}
+
+impl<E: fmt::Display + fmt::Debug> std::error::Error for LoadShedError<E> {}
+
+impl<E: PartialEq> PartialEq for LoadShedError<E> {
+    fn eq(&self, other: &LoadShedError<E>) -> bool {
+        match (self, other) {
+            (LoadShedError::Service(e1), LoadShedError::Service(e2)) => e1 == e2,
+            (LoadShedError::Overloaded, LoadShedError::Overloaded) => true,
+            _ => false,
+        }
+    }
+}
+
+pub struct LoadShedService<S> {
+    service: S,
+    ready: Cell<bool>,
+}
+
+impl<S> LoadShedService<S> {
+    pub fn new<U, R>(service: U) -> Self
+    where
+        S: Service<R>,
+        U: IntoService<S, R>,
+    {
+        Self {
+            service: service.into_service(),
+            ready: Cell::new(false),
+        }
+    }
+}
+
+impl<S, R> Service<R> for LoadShedService<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = LoadShedError<S::Error>;
+    type Future =
+        Either<LoadShedServiceResponse<S, R>, Ready<S::Response, LoadShedError<S::Error>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // readiness of inner service is checked on each `poll_ready` call,
+        // `call` uses result of the last check
+        let ready = self
+            .service
+            .poll_ready(cx)
+            .map_err(LoadShedError::Service)?
+            .is_ready();
+        if !ready {
+            log::trace!("Service is not ready, shedding load");
+        }
+        self.ready.set(ready);
+        Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    #[inline]
+    fn call(&self, req: R) -> Self::Future {
+        if self.ready.replace(false) {
+            Either::Left(LoadShedServiceResponse {
+                fut: self.service.call(req),
+                _t: PhantomData,
+            })
+        } else {
+            Either::Right(Ready::Err(LoadShedError::Overloaded))
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[doc(hidden)]
+    pub struct LoadShedServiceResponse<S: Service<R>, R> {
+        #[pin]
+        fut: S::Future,
+        _t: PhantomData<R>
+    }
+}
+
+impl<S: Service<R>, R> Future for LoadShedServiceResponse<S, R> {
+    type Output = Result<S::Response, LoadShedError<S::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().fut.poll(cx).map_err(LoadShedError::Service)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ntex_service::{apply, fn_factory, Service, ServiceFactory};
+    use std::{task::Context, task::Poll, time::Duration};
+
+    use super::*;
+    use crate::future::lazy;
+    use crate::services::inflight::{InFlight, InFlightService};
+
+    #[derive(Clone)]
+    struct SleepService(Duration);
+
+    impl Service<()> for SleepService {
+        type Response = ();
+        type Error = ();
+        type Future = Pin<Box<dyn Future<Output = Result<(), ()>>>>;
+
+        fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&self, _: ()) -> Self::Future {
+            let fut = crate::time::sleep(self.0);
+            Box::pin(async move {
+                let _ = fut.await;
+                Ok::<_, ()>(())
+            })
+        }
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_loadshed() {
+        let wait_time = Duration::from_millis(50);
+
+        let srv = LoadShedService::new(InFlightService::new(1, SleepService(wait_time)));
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+
+        let res = srv.call(());
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        assert_eq!(srv.call(()).await, Err(LoadShedError::Overloaded));
+
+        // request without readiness check is rejected
+        assert_eq!(srv.call(()).await, Err(LoadShedError::Overloaded));
+
+        assert_eq!(res.await, Ok(()));
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        assert_eq!(srv.call(()).await, Ok(()));
+
+        assert!(lazy(|cx| srv.poll_shutdown(cx, false)).await.is_ready());
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_newtransform() {
+        let wait_time = Duration::from_millis(50);
+
+        let srv = apply(
+            LoadShed::new(),
+            apply(
+                InFlight::new(1),
+                fn_factory(|| async { Ok::<_, ()>(SleepService(wait_time)) }),
+            ),
+        );
+
+        let srv = srv.new_service(&()).await.unwrap();
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        let res = srv.call(());
+
+        assert_eq!(lazy(|cx| srv.poll_ready(cx)).await, Poll::Ready(Ok(())));
+        assert_eq!(srv.call(()).await, Err(LoadShedError::Overloaded));
+
+        assert_eq!(res.await, Ok(()));
+    }
+
+    #[test]
+    fn test_error() {
+        let err = LoadShedError::<std::io::Error>::Overloaded;
+        assert_eq!(format!("{}", err), "Service is overloaded");
+        assert_eq!(format!("{:?}", err), "LoadShedError::Overloaded");
+        assert_eq!(LoadShedError::from(1), LoadShedError::Service(1));
+    }
+}
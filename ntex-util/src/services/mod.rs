@@ -1,8 +1,13 @@
 pub mod buffer;
 pub mod counter;
+pub mod deadline;
 mod extensions;
+pub mod hedge;
 pub mod inflight;
+pub mod inorder;
 pub mod keepalive;
+pub mod loadshed;
+pub mod retry;
 pub mod timeout;
 pub mod variant;
 
@@ -0,0 +1,429 @@
+//! Service that retries failed requests.
+//!
+//! Request is cloned before each attempt, retry policy decides which
+//! errors are retryable and how long to wait before next attempt.
+use std::{cmp, fmt, future::Future, pin::Pin, rc::Rc};
+use std::{task::Context, task::Poll};
+
+use ntex_service::{IntoService, Service, Transform};
+
+use crate::time::{sleep, Millis, Sleep};
+
+/// Retry policy
+pub trait Policy<R, E> {
+    /// Check if request could be retried after an error.
+    ///
+    /// `attempt` is a number of failed attempts, starting from 1.
+    /// Returns delay before next attempt, `None` stops retries.
+    fn retry(&self, req: &R, err: &E, attempt: u32) -> Option<Millis>;
+
+    /// Clone request for next attempt.
+    ///
+    /// If request cannot be cloned, service error is returned as is.
+    fn clone_request(&self, req: &R) -> Option<R>;
+}
+
+type CloneFn<R> = Rc<dyn Fn(&R) -> Option<R>>;
+
+/// Retry policy with exponential backoff
+///
+/// Delay before next attempt is doubled after each failed attempt,
+/// starting from base delay and up to max delay. By default
+/// all errors are retryable, max number of retries is 3,
+/// base delay is 50 millis and max delay is 1 second.
+pub struct Backoff<R, E> {
+    max_retries: u32,
+    base: Millis,
+    max: Millis,
+    retryable: Rc<dyn Fn(&E) -> bool>,
+    clone: CloneFn<R>,
+}
+
+impl<R: Clone + 'static, E> Backoff<R, E> {
+    /// Create backoff policy for cloneable requests.
+    pub fn new() -> Self {
+        Self::with_factory(|req: &R| Some(req.clone()))
+    }
+}
+
+impl<R: Clone + 'static, E> Default for Backoff<R, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, E> Backoff<R, E> {
+    /// Create backoff policy, requests are cloned with provided factory.
+    ///
+    /// Factory returns `None` if request cannot be retried.
+    pub fn with_factory<F>(f: F) -> Self
+    where
+        F: Fn(&R) -> Option<R> + 'static,
+    {
+        Backoff {
+            max_retries: 3,
+            base: Millis(50),
+            max: Millis::ONE_SEC,
+            retryable: Rc::new(|_| true),
+            clone: Rc::new(f),
+        }
+    }
+
+    /// Set max number of retries.
+    pub fn max_retries(mut self, max: u32) -> Self {
+        self.max_retries = max;
+        self
+    }
+
+    /// Set base and max delay between attempts.
+    pub fn backoff<T: Into<Millis>, U: Into<Millis>>(mut self, base: T, max: U) -> Self {
+        self.base = base.into();
+        self.max = max.into();
+        self
+    }
+
+    /// Set function that checks if error is retryable.
+    pub fn retryable<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&E) -> bool + 'static,
+    {
+        self.retryable = Rc::new(f);
+        self
+    }
+
+    /// Delay before next attempt
+    fn delay(&self, attempt: u32) -> Millis {
+        let delay = (self.base.0 as u64) << cmp::min(attempt.saturating_sub(1), 31);
+        Millis(cmp::min(delay, self.max.0 as u64) as u32)
+    }
+}
+
+impl<R, E> Clone for Backoff<R, E> {
+    fn clone(&self) -> Self {
+        Backoff {
+            max_retries: self.max_retries,
+            base: self.base,
+            max: self.max,
+            retryable: self.retryable.clone(),
+            clone: self.clone.clone(),
+        }
+    }
+}
+
+impl<R, E> fmt::Debug for Backoff<R, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Backoff")
+            .field("max_retries", &self.max_retries)
+            .field("base", &self.base)
+            .field("max", &self.max)
+            .finish()
+    }
+}
+
+impl<R, E> Policy<R, E> for Backoff<R, E> {
+    fn retry(&self, _: &R, err: &E, attempt: u32) -> Option<Millis> {
+        if attempt <= self.max_retries && (*self.retryable)(err) {
+            Some(self.delay(attempt))
+        } else {
+            None
+        }
+    }
+
+    fn clone_request(&self, req: &R) -> Option<R> {
+        (*self.clone)(req)
+    }
+}
+
+/// Retries failed requests according to retry policy.
+pub struct Retry<P> {
+    policy: Rc<P>,
+}
+
+impl<P> Retry<P> {
+    pub fn new(policy: P) -> Self {
+        Retry {
+            policy: Rc::new(policy),
+        }
+    }
+}
+
+impl<P> Clone for Retry<P> {
+    fn clone(&self) -> Self {
+        Retry {
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+impl<S, P> Transform<S> for Retry<P> {
+    type Service = RetryService<S, P>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        RetryService {
+            service: Rc::new(service),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+/// Retries failed requests according to retry policy.
+pub struct RetryService<S, P> {
+    service: Rc<S>,
+    policy: Rc<P>,
+}
+
+impl<S, P> RetryService<S, P> {
+    pub fn new<U, R>(policy: P, service: U) -> Self
+    where
+        S: Service<R>,
+        U: IntoService<S, R>,
+    {
+        RetryService {
+            service: Rc::new(service.into_service()),
+            policy: Rc::new(policy),
+        }
+    }
+}
+
+impl<S, P> Clone for RetryService<S, P> {
+    fn clone(&self) -> Self {
+        RetryService {
+            service: self.service.clone(),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+impl<S, P, R> Service<R> for RetryService<S, P>
+where
+    S: Service<R>,
+    P: Policy<R, S::Error>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RetryServiceResponse<S, P, R>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: R) -> Self::Future {
+        RetryServiceResponse {
+            req: self.policy.clone_request(&req),
+            state: State::Call {
+                fut: self.service.call(req),
+            },
+            attempt: 0,
+            service: self.service.clone(),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// `RetryService` response future
+    #[doc(hidden)]
+    pub struct RetryServiceResponse<S: Service<R>, P, R> {
+        #[pin]
+        state: State<S::Future>,
+        req: Option<R>,
+        attempt: u32,
+        service: Rc<S>,
+        policy: Rc<P>,
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = StateProject]
+    enum State<F> {
+        Call { #[pin] fut: F },
+        Sleep { sleep: Sleep },
+        Ready,
+    }
+}
+
+impl<S, P, R> Future for RetryServiceResponse<S, P, R>
+where
+    S: Service<R>,
+    P: Policy<R, S::Error>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        loop {
+            match this.state.as_mut().project() {
+                StateProject::Call { fut } => match fut.poll(cx) {
+                    Poll::Ready(Ok(res)) => return Poll::Ready(Ok(res)),
+                    Poll::Ready(Err(err)) => {
+                        *this.attempt += 1;
+                        let delay = this
+                            .req
+                            .as_ref()
+                            .and_then(|req| this.policy.retry(req, &err, *this.attempt));
+                        match delay {
+                            Some(delay) if delay.is_zero() => this.state.set(State::Ready),
+                            Some(delay) => this.state.set(State::Sleep {
+                                sleep: sleep(delay),
+                            }),
+                            None => return Poll::Ready(Err(err)),
+                        }
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                StateProject::Sleep { sleep } => {
+                    if sleep.poll_elapsed(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    this.state.set(State::Ready);
+                }
+                StateProject::Ready => match this.service.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => {
+                        let req = this.req.take().unwrap();
+                        *this.req = this.policy.clone_request(&req);
+                        this.state.set(State::Call {
+                            fut: this.service.call(req),
+                        });
+                    }
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, future::Future, task::Context, task::Poll};
+
+    use ntex_service::{apply, fn_factory, Service, ServiceFactory};
+
+    use super::*;
+    use crate::future::{lazy, Ready};
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum SrvError {
+        Retryable,
+        Fatal,
+    }
+
+    /// Service fails until number of calls reaches `failures`
+    #[derive(Clone)]
+    struct Srv(Rc<Cell<usize>>, usize, SrvError);
+
+    impl Service<usize> for Srv {
+        type Response = usize;
+        type Error = SrvError;
+        type Future = Ready<usize, SrvError>;
+
+        fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&self, req: usize) -> Self::Future {
+            self.0.set(self.0.get() + 1);
+            if self.0.get() > self.1 {
+                Ready::Ok(req)
+            } else {
+                Ready::Err(self.2.clone())
+            }
+        }
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_retry() {
+        let cnt = Rc::new(Cell::new(0));
+        let srv = RetryService::new(
+            Backoff::new().backoff(Millis(1), Millis(10)),
+            Srv(cnt.clone(), 3, SrvError::Retryable),
+        )
+        .clone();
+        assert_eq!(srv.call(10).await, Ok(10));
+        assert_eq!(cnt.get(), 4);
+        assert!(lazy(|cx| srv.poll_ready(cx)).await.is_ready());
+        assert!(lazy(|cx| srv.poll_shutdown(cx, false)).await.is_ready());
+
+        // max retries
+        let cnt = Rc::new(Cell::new(0));
+        let srv = RetryService::new(
+            Backoff::new().max_retries(2).backoff(Millis(1), Millis(10)),
+            Srv(cnt.clone(), 3, SrvError::Retryable),
+        );
+        assert_eq!(srv.call(10).await, Err(SrvError::Retryable));
+        assert_eq!(cnt.get(), 3);
+
+        // non retryable error
+        let cnt = Rc::new(Cell::new(0));
+        let srv = RetryService::new(
+            Backoff::new()
+                .backoff(Millis::ZERO, Millis::ZERO)
+                .retryable(|err| *err == SrvError::Retryable),
+            Srv(cnt.clone(), 3, SrvError::Fatal),
+        );
+        assert_eq!(srv.call(10).await, Err(SrvError::Fatal));
+        assert_eq!(cnt.get(), 1);
+
+        // request cannot be cloned
+        let cnt = Rc::new(Cell::new(0));
+        let srv = RetryService::new(
+            Backoff::with_factory(|req: &usize| if *req > 5 { Some(*req) } else { None }),
+            Srv(cnt.clone(), 3, SrvError::Retryable),
+        );
+        assert_eq!(srv.call(1).await, Err(SrvError::Retryable));
+        assert_eq!(cnt.get(), 1);
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_retry_delay() {
+        let cnt = Rc::new(Cell::new(0));
+        let srv = RetryService::new(
+            Backoff::new().backoff(Millis(50), Millis(1000)),
+            Srv(cnt.clone(), 1, SrvError::Retryable),
+        );
+
+        let mut fut = srv.call(1);
+        assert!(lazy(|cx| Pin::new(&mut fut).poll(cx)).await.is_pending());
+        assert_eq!(cnt.get(), 1);
+        assert_eq!(fut.await, Ok(1));
+        assert_eq!(cnt.get(), 2);
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_retry_newservice() {
+        let cnt = Rc::new(Cell::new(0));
+        let cnt2 = cnt.clone();
+        let factory = apply(
+            Retry::new(Backoff::new().backoff(Millis(1), Millis(1))).clone(),
+            fn_factory(move || {
+                let srv = Srv(cnt2.clone(), 2, SrvError::Retryable);
+                async move { Ok::<_, ()>(srv) }
+            }),
+        );
+        let srv = factory.new_service(&()).await.unwrap();
+        assert_eq!(srv.call(1).await, Ok(1));
+        assert_eq!(cnt.get(), 3);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = Backoff::<(), ()>::new().backoff(Millis(100), Millis(1000));
+        assert_eq!(policy.retry(&(), &(), 1), Some(Millis(100)));
+        assert_eq!(policy.retry(&(), &(), 2), Some(Millis(200)));
+        assert_eq!(policy.retry(&(), &(), 3), Some(Millis(400)));
+        assert_eq!(policy.retry(&(), &(), 4), None);
+
+        let policy = policy.clone().max_retries(100);
+        assert_eq!(policy.retry(&(), &(), 5), Some(Millis(1000)));
+        assert_eq!(policy.retry(&(), &(), 100), Some(Millis(1000)));
+        assert_eq!(policy.clone_request(&()), Some(()));
+        assert!(format!("{:?}", policy).contains("Backoff"));
+    }
+}
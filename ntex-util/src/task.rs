@@ -1,6 +1,10 @@
 //! A synchronization primitive for task wakeup.
 use std::{cell::Cell, fmt, marker::PhantomData, rc, task::Waker};
 
+mod cpu;
+
+pub use self::cpu::{spawn_cpu, spawn_cpu_until, CpuJoinHandle, CpuTaskError};
+
 /// A synchronization primitive for task wakeup.
 ///
 /// Sometimes the task interested in a given event will change over time.
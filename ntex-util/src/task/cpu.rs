@@ -0,0 +1,227 @@
+//! Thread pool for cpu-bound tasks.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::{fmt, future::Future, panic, pin::Pin, thread, time::Instant};
+
+use once_cell::sync::Lazy;
+
+/// Env variable for cpu pool size.
+const ENV_CPU_POOL_VAR: &str = "NTEX_CPU_POOL";
+
+type Job = Box<dyn FnOnce() + Send>;
+
+static POOL: Lazy<CpuPool> = Lazy::new(|| {
+    let num = std::env::var(ENV_CPU_POOL_VAR)
+        .ok()
+        .and_then(|val| {
+            val.parse().ok().or_else(|| {
+                log::warn!("Can not parse {} value, using default", ENV_CPU_POOL_VAR);
+                None
+            })
+        })
+        .unwrap_or_else(num_cpus::get);
+    CpuPool::new(num)
+});
+
+struct CpuPool {
+    tx: Mutex<mpsc::Sender<Job>>,
+}
+
+impl CpuPool {
+    fn new(num: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+
+        for idx in 0..num {
+            let rx = rx.clone();
+            thread::Builder::new()
+                .name(format!("ntex-cpu:{}", idx))
+                .spawn(move || loop {
+                    let job = rx.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                })
+                .expect("Cannot spawn cpu pool thread");
+        }
+        CpuPool { tx: Mutex::new(tx) }
+    }
+
+    fn execute(&self, job: Job) {
+        let _ = self.tx.lock().unwrap().send(job);
+    }
+}
+
+/// Cpu-bound task error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CpuTaskError {
+    /// Task deadline elapsed before task got started
+    Expired,
+    /// Task panicked
+    Panicked,
+}
+
+impl fmt::Display for CpuTaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuTaskError::Expired => write!(f, "Task deadline elapsed"),
+            CpuTaskError::Panicked => write!(f, "Task panicked"),
+        }
+    }
+}
+
+impl std::error::Error for CpuTaskError {}
+
+struct Shared<T> {
+    canceled: AtomicBool,
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    result: Option<Result<T, CpuTaskError>>,
+    waker: Option<Waker>,
+}
+
+/// Run cpu-bound closure on the shared thread pool.
+///
+/// Thread pool is shared between all arbiters, pool size is
+/// number of cpus, it could be overridden with `NTEX_CPU_POOL` env variable.
+/// Unlike `spawn_blocking`, pool size is bounded, so pool must not be used
+/// for blocking io.
+///
+/// Dropping `CpuJoinHandle` cancels task if it is not started yet.
+pub fn spawn_cpu<F, T>(f: F) -> CpuJoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn(None, f)
+}
+
+/// Run cpu-bound closure on the shared thread pool with deadline.
+///
+/// If deadline elapses before task gets started, task is not executed
+/// and `CpuTaskError::Expired` is returned.
+pub fn spawn_cpu_until<F, T>(deadline: Instant, f: F) -> CpuJoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn(Some(deadline), f)
+}
+
+fn spawn<F, T>(deadline: Option<Instant>, f: F) -> CpuJoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        canceled: AtomicBool::new(false),
+        state: Mutex::new(State {
+            result: None,
+            waker: None,
+        }),
+    });
+    let inner = shared.clone();
+
+    POOL.execute(Box::new(move || {
+        if inner.canceled.load(Ordering::Acquire) {
+            return;
+        }
+        let result = if deadline.map(|d| Instant::now() >= d).unwrap_or(false) {
+            Err(CpuTaskError::Expired)
+        } else {
+            panic::catch_unwind(panic::AssertUnwindSafe(f))
+                .map_err(|_| CpuTaskError::Panicked)
+        };
+
+        let mut state = inner.state.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }));
+
+    CpuJoinHandle { shared }
+}
+
+/// Cpu-bound task completion future.
+///
+/// Resolves with result of closure execution.
+#[must_use = "futures do nothing unless polled"]
+pub struct CpuJoinHandle<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> CpuJoinHandle<T> {
+    /// Cancel task if it is not started yet.
+    pub fn cancel(&self) {
+        self.shared.canceled.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Drop for CpuJoinHandle<T> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl<T> fmt::Debug for CpuJoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CpuJoinHandle")
+            .field("canceled", &self.shared.canceled.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl<T> Future for CpuJoinHandle<T> {
+    type Output = Result<T, CpuTaskError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[ntex_macros::rt_test2]
+    async fn test_spawn_cpu() {
+        let name = spawn_cpu(|| thread::current().name().map(|s| s.to_string()))
+            .await
+            .unwrap();
+        assert!(name.unwrap().starts_with("ntex-cpu:"));
+
+        let res = spawn_cpu(|| panic!("test")).await;
+        assert_eq!(res, Err::<(), _>(CpuTaskError::Panicked));
+        assert_eq!(format!("{}", CpuTaskError::Panicked), "Task panicked");
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_spawn_cpu_until() {
+        let res = spawn_cpu_until(Instant::now() - Duration::from_millis(1), || 1).await;
+        assert_eq!(res, Err(CpuTaskError::Expired));
+
+        let res = spawn_cpu_until(Instant::now() + Duration::from_secs(10), || 1).await;
+        assert_eq!(res, Ok(1));
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_cancel() {
+        let hnd = spawn_cpu(|| ());
+        assert!(format!("{:?}", hnd).contains("canceled: false"));
+        hnd.cancel();
+        assert!(format!("{:?}", hnd).contains("canceled: true"));
+    }
+}
@@ -1,5 +1,8 @@
 //! Utilities for tracking time.
-use std::{future::Future, pin::Pin, task, task::Poll};
+use std::time::{Duration, Instant};
+use std::{cell::Cell, cell::RefCell, future::Future, pin::Pin, task, task::Poll};
+
+use futures_timer::Delay;
 
 mod types;
 mod wheel;
@@ -11,12 +14,34 @@ pub use self::wheel::{now, query_system_time, system_time, TimerHandle};
 ///
 /// No work is performed while awaiting on the sleep future to complete. `Sleep`
 /// operates at 16 millisecond granularity and should not be used for tasks that
-/// require high-resolution timers.
+/// require high-resolution timers, use [`sleep_precise`](sleep_precise) instead.
 #[inline]
 pub fn sleep<T: Into<Millis>>(dur: T) -> Sleep {
     Sleep::new(dur.into())
 }
 
+/// Waits until `deadline` is reached.
+///
+/// `Sleep` operates at 16 millisecond granularity, deadline is rounded
+/// up to the timer wheel resolution.
+#[inline]
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    let millis = deadline.saturating_duration_since(now()).as_millis();
+    Sleep::new(Millis(std::cmp::min(millis, u32::MAX as u128) as u32))
+}
+
+/// Waits until `duration` has elapsed, uses high-resolution timer.
+///
+/// High-resolution timers do not use timer wheel, each timer is registered
+/// with dedicated timer thread, so creating and resetting timer is more
+/// expensive and each expiration requires cross-thread wakeup. Use it only
+/// for short timeouts (sub 10ms) that require precision, i.e. for hedging or
+/// retry delays.
+#[inline]
+pub fn sleep_precise(dur: Duration) -> Sleep {
+    Sleep::precise(dur)
+}
+
 /// Creates new [`Interval`] that yields with interval of `period`.
 ///
 /// An interval will tick indefinitely. At any time, the [`Interval`] value can
@@ -40,6 +65,18 @@ where
     Timeout::new_with_delay(future, Sleep::new(dur.into()))
 }
 
+/// Require a `Future` to complete before the specified duration has elapsed,
+/// uses high-resolution timer.
+///
+/// See [`sleep_precise`](sleep_precise) for details.
+#[inline]
+pub fn timeout_precise<T>(dur: Duration, future: T) -> Timeout<T>
+where
+    T: Future,
+{
+    Timeout::new_with_delay(future, Sleep::precise(dur))
+}
+
 /// Require a `Future` to complete before the specified duration has elapsed.
 ///
 /// If the future completes before the duration has elapsed, then the completed
@@ -73,7 +110,16 @@ where
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 pub struct Sleep {
     // The link between the `Sleep` instance and the timer that drives it.
-    hnd: TimerHandle,
+    hnd: SleepTimer,
+}
+
+#[derive(Debug)]
+enum SleepTimer {
+    Wheel(TimerHandle),
+    Precise {
+        delay: RefCell<Delay>,
+        deadline: Cell<Instant>,
+    },
 }
 
 impl Sleep {
@@ -81,14 +127,35 @@ impl Sleep {
     #[inline]
     pub fn new(duration: Millis) -> Sleep {
         Sleep {
-            hnd: TimerHandle::new(duration.0 as u64),
+            hnd: SleepTimer::Wheel(TimerHandle::new(duration.0 as u64)),
         }
     }
 
+    /// Create new sleep future with high-resolution timer
+    ///
+    /// See [`sleep_precise`](sleep_precise) for details.
+    pub fn precise(duration: Duration) -> Sleep {
+        Sleep {
+            hnd: SleepTimer::Precise {
+                delay: RefCell::new(Delay::new(duration)),
+                deadline: Cell::new(Instant::now() + duration),
+            },
+        }
+    }
+
+    /// Returns `true` if `Sleep` uses high-resolution timer.
+    #[inline]
+    pub fn is_precise(&self) -> bool {
+        matches!(self.hnd, SleepTimer::Precise { .. })
+    }
+
     /// Returns `true` if `Sleep` has elapsed.
     #[inline]
     pub fn is_elapsed(&self) -> bool {
-        self.hnd.is_elapsed()
+        match self.hnd {
+            SleepTimer::Wheel(ref hnd) => hnd.is_elapsed(),
+            SleepTimer::Precise { ref deadline, .. } => Instant::now() >= deadline.get(),
+        }
     }
 
     /// Resets the `Sleep` instance to a new deadline.
@@ -99,12 +166,42 @@ impl Sleep {
     /// This function can be called both before and after the future has
     /// completed.
     pub fn reset<T: Into<Millis>>(&self, millis: T) {
-        self.hnd.reset(millis.into().0 as u64);
+        let millis = millis.into();
+        match self.hnd {
+            SleepTimer::Wheel(ref hnd) => hnd.reset(millis.0 as u64),
+            SleepTimer::Precise { .. } => self.reset_precise(millis.into()),
+        }
+    }
+
+    /// Resets the `Sleep` instance to a new deadline with sub-millisecond
+    /// precision.
+    ///
+    /// For timer wheel based `Sleep` duration is rounded up to millis.
+    pub fn reset_precise(&self, dur: Duration) {
+        match self.hnd {
+            SleepTimer::Wheel(ref hnd) => {
+                let millis =
+                    dur.as_millis() + u128::from(dur.subsec_nanos() % 1_000_000 != 0);
+                hnd.reset(millis as u64)
+            }
+            SleepTimer::Precise {
+                ref delay,
+                ref deadline,
+            } => {
+                delay.borrow_mut().reset(dur);
+                deadline.set(Instant::now() + dur);
+            }
+        }
     }
 
     #[inline]
     pub fn poll_elapsed(&self, cx: &mut task::Context<'_>) -> Poll<()> {
-        self.hnd.poll_elapsed(cx)
+        match self.hnd {
+            SleepTimer::Wheel(ref hnd) => hnd.poll_elapsed(cx),
+            SleepTimer::Precise { ref delay, .. } => {
+                Pin::new(&mut *delay.borrow_mut()).poll(cx)
+            }
+        }
     }
 }
 
@@ -112,7 +209,7 @@ impl Future for Sleep {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        self.hnd.poll_elapsed(cx)
+        self.poll_elapsed(cx)
     }
 }
 
@@ -202,14 +299,42 @@ where
     }
 }
 
+/// Defines the behavior of an [`Interval`] when it misses a tick.
+///
+/// Tick is missed if interval is not polled for longer than period,
+/// i.e. if the task is busy with other work.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// Missed ticks fire immediately, one after another, until
+    /// interval catches up. Schedule is not shifted.
+    Burst,
+    /// Next tick fires `period` after the missed tick got observed.
+    /// Schedule is shifted by the delay.
+    Delay,
+    /// Missed ticks are skipped, next tick fires at the next multiple
+    /// of `period` from the original schedule.
+    Skip,
+}
+
+impl Default for MissedTickBehavior {
+    fn default() -> Self {
+        MissedTickBehavior::Burst
+    }
+}
+
 /// Interval returned by [`interval`]
 ///
 /// This type allows you to wait on a sequence of instants with a certain
-/// duration between each instant.
+/// duration between each instant. Ticks are scheduled relative to the interval
+/// start, so interval does not drift if task handles ticks in time.
+/// Missed ticks are handled according to [`MissedTickBehavior`], default
+/// is `MissedTickBehavior::Burst`.
 #[derive(Debug)]
 pub struct Interval {
     hnd: TimerHandle,
     period: u32,
+    next: Cell<Instant>,
+    behavior: MissedTickBehavior,
 }
 
 impl Interval {
@@ -219,9 +344,22 @@ impl Interval {
         Interval {
             hnd: TimerHandle::new(period.0 as u64),
             period: period.0,
+            next: Cell::new(now() + Duration::from(period)),
+            behavior: MissedTickBehavior::default(),
         }
     }
 
+    /// Set missed tick behavior.
+    pub fn missed_tick_behavior(mut self, behavior: MissedTickBehavior) -> Self {
+        self.behavior = behavior;
+        self
+    }
+
+    /// Returns interval period.
+    pub fn period(&self) -> Millis {
+        Millis(self.period)
+    }
+
     #[inline]
     pub async fn tick(&self) {
         crate::future::poll_fn(|cx| self.poll_tick(cx)).await;
@@ -230,7 +368,25 @@ impl Interval {
     #[inline]
     pub fn poll_tick(&self, cx: &mut task::Context<'_>) -> Poll<()> {
         if self.hnd.poll_elapsed(cx).is_ready() {
-            self.hnd.reset(self.period as u64);
+            let period = Duration::from(Millis(self.period));
+            let now = now();
+            let mut next = self.next.get() + period;
+            if next <= now {
+                match self.behavior {
+                    MissedTickBehavior::Burst => (),
+                    MissedTickBehavior::Delay => next = now + period,
+                    MissedTickBehavior::Skip => {
+                        let missed = (now - next).as_millis() / period.as_millis().max(1);
+                        next += period * (missed as u32 + 1);
+                    }
+                }
+            }
+            self.next.set(next);
+            self.hnd.reset(
+                next.saturating_duration_since(now)
+                    .as_millis()
+                    .min(u32::MAX as u128) as u64,
+            );
             Poll::Ready(())
         } else {
             Poll::Pending
@@ -341,8 +497,10 @@ mod tests {
             let time = time::Instant::now();
             int.tick().await;
             let elapsed = time::Instant::now() - time;
+            // ticks are scheduled relative to interval start,
+            // timer wheel could fire previous tick a bit late
             assert!(
-                elapsed > time::Duration::from_millis(1000)
+                elapsed > time::Duration::from_millis(950)
                     && elapsed < time::Duration::from_millis(1300),
                 "elapsed: {:?}",
                 elapsed
@@ -350,6 +508,76 @@ mod tests {
         }
     }
 
+    #[ntex_macros::rt_test2]
+    async fn test_sleep_until() {
+        let time = time::Instant::now();
+        sleep_until(time + time::Duration::from_millis(50)).await;
+        assert!(time::Instant::now() - time >= time::Duration::from_millis(50));
+
+        // deadline in the past
+        let time = time::Instant::now();
+        sleep_until(time - time::Duration::from_millis(50)).await;
+        assert!(time::Instant::now() - time < time::Duration::from_millis(50));
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_sleep_precise() {
+        let dur = time::Duration::from_millis(3);
+        let time = time::Instant::now();
+        let s = sleep_precise(dur);
+        assert!(s.is_precise());
+        assert!(!s.is_elapsed());
+        s.await;
+        let elapsed = time::Instant::now() - time;
+        assert!(elapsed >= dur && elapsed < time::Duration::from_millis(15));
+
+        let s = sleep_precise(dur);
+        s.reset(Millis(10));
+        assert!(!s.is_elapsed());
+        let time = time::Instant::now();
+        s.await;
+        assert!(time::Instant::now() - time >= time::Duration::from_millis(9));
+
+        let result = timeout_precise(dur, sleep(Millis(100))).await;
+        assert!(result.is_err());
+        let result =
+            timeout_precise(time::Duration::from_millis(100), sleep_precise(dur)).await;
+        assert!(result.is_ok());
+    }
+
+    #[ntex_macros::rt_test2]
+    async fn test_interval_missed_ticks() {
+        // burst
+        let int = interval(Millis(50));
+        crate::time::sleep(Millis(170)).await;
+        let time = time::Instant::now();
+        int.tick().await;
+        int.tick().await;
+        assert!(time::Instant::now() - time < time::Duration::from_millis(40));
+
+        // delay
+        let int = interval(Millis(50)).missed_tick_behavior(MissedTickBehavior::Delay);
+        crate::time::sleep(Millis(170)).await;
+        int.tick().await;
+        let time = time::Instant::now();
+        int.tick().await;
+        assert!(time::Instant::now() - time >= time::Duration::from_millis(40));
+
+        // skip
+        let int = interval(Millis(100)).missed_tick_behavior(MissedTickBehavior::Skip);
+        assert_eq!(int.period(), Millis(100));
+        crate::time::sleep(Millis(250)).await;
+        int.tick().await;
+        let time = time::Instant::now();
+        int.tick().await;
+        let elapsed = time::Instant::now() - time;
+        assert!(
+            elapsed < time::Duration::from_millis(100),
+            "elapsed: {:?}",
+            elapsed
+        );
+    }
+
     #[ntex_macros::rt_test2]
     async fn test_timeout_checked() {
         let result = timeout_checked(Millis(200), sleep(Millis(100))).await;
@@ -6,7 +6,7 @@ use std::cell::RefCell;
 use std::time::{Duration, Instant, SystemTime};
 use std::{cmp::max, future::Future, mem, pin::Pin, rc::Rc, task, task::Poll};
 
-use futures_timer::Delay;
+use ntex_rt::LocalBoxFuture;
 use slab::Slab;
 
 use crate::task::LocalWaker;
@@ -149,14 +149,14 @@ struct Timer {
     next_expiry: u64,
     flags: Flags,
     driver: LocalWaker,
-    driver_sleep: Delay,
+    driver_sleep: Option<LocalBoxFuture>,
     buckets: Vec<Bucket>,
     /// Bit field tracking which bucket currently contain entries.
     occupied: [u64; WHEEL_SIZE],
     lowres_time: Option<Instant>,
     lowres_stime: Option<SystemTime>,
     lowres_driver: LocalWaker,
-    lowres_driver_sleep: Delay,
+    lowres_driver_sleep: Option<LocalBoxFuture>,
 }
 
 impl Timer {
@@ -169,12 +169,12 @@ impl Timer {
             next_expiry: u64::MAX,
             flags: Flags::empty(),
             driver: LocalWaker::new(),
-            driver_sleep: Delay::new(Duration::ZERO),
+            driver_sleep: None,
             occupied: [0; WHEEL_SIZE],
             lowres_time: None,
             lowres_stime: None,
             lowres_driver: LocalWaker::new(),
-            lowres_driver_sleep: Delay::new(Duration::ZERO),
+            lowres_driver_sleep: None,
         }
     }
 
@@ -554,12 +554,24 @@ impl TimerEntry {
     }
 }
 
+/// Poll runtime timer, completed timer gets dropped
+fn poll_sleep(sleep: &mut Option<LocalBoxFuture>, cx: &mut task::Context<'_>) -> Poll<()> {
+    if let Some(fut) = sleep {
+        if fut.as_mut().poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        *sleep = None;
+    }
+    Poll::Ready(())
+}
+
 struct TimerDriver(Rc<RefCell<Timer>>);
 
 impl TimerDriver {
     fn start(slf: &mut Timer, cell: &Rc<RefCell<Timer>>) {
         slf.flags.insert(Flags::DRIVER_STARTED);
-        slf.driver_sleep = Delay::new(Duration::from_millis(slf.next_expiry_ms()));
+        slf.driver_sleep =
+            Some(ntex_rt::sleep(Duration::from_millis(slf.next_expiry_ms())));
 
         crate::spawn(TimerDriver(cell.clone()));
     }
@@ -589,11 +601,11 @@ impl Future for TimerDriver {
                 } else {
                     Duration::from_millis(inner.next_expiry_ms())
                 };
-            inner.driver_sleep.reset(deadline);
+            inner.driver_sleep = Some(ntex_rt::sleep(deadline));
         }
 
         loop {
-            if Pin::new(&mut inner.driver_sleep).poll(cx).is_ready() {
+            if poll_sleep(&mut inner.driver_sleep, cx).is_ready() {
                 let now = Instant::now();
                 inner.elapsed = inner.next_expiry;
                 inner.elapsed_time = Some(now);
@@ -602,7 +614,7 @@ impl Future for TimerDriver {
                 if let Some(next_expiry) = inner.next_pending_bucket() {
                     inner.next_expiry = next_expiry;
                     let dur = Duration::from_millis(inner.next_expiry_ms());
-                    inner.driver_sleep.reset(dur);
+                    inner.driver_sleep = Some(ntex_rt::sleep(dur));
                     continue;
                 } else {
                     inner.next_expiry = u64::MAX;
@@ -619,7 +631,7 @@ struct LowresTimerDriver(Rc<RefCell<Timer>>);
 impl LowresTimerDriver {
     fn start(slf: &mut Timer, cell: &Rc<RefCell<Timer>>) {
         slf.flags.insert(Flags::LOWRES_DRIVER);
-        slf.lowres_driver_sleep = Delay::new(LOWRES_RESOLUTION);
+        slf.lowres_driver_sleep = Some(ntex_rt::sleep(LOWRES_RESOLUTION));
 
         crate::spawn(LowresTimerDriver(cell.clone()));
     }
@@ -642,7 +654,7 @@ impl Future for LowresTimerDriver {
 
         loop {
             if inner.flags.contains(Flags::LOWRES_TIMER) {
-                if Pin::new(&mut inner.lowres_driver_sleep).poll(cx).is_ready() {
+                if poll_sleep(&mut inner.lowres_driver_sleep, cx).is_ready() {
                     inner.lowres_time = None;
                     inner.lowres_stime = None;
                     inner.flags.remove(Flags::LOWRES_TIMER);
@@ -650,7 +662,7 @@ impl Future for LowresTimerDriver {
                 return Poll::Pending;
             } else {
                 inner.flags.insert(Flags::LOWRES_TIMER);
-                inner.lowres_driver_sleep.reset(LOWRES_RESOLUTION);
+                inner.lowres_driver_sleep = Some(ntex_rt::sleep(LOWRES_RESOLUTION));
             }
         }
     }
@@ -0,0 +1,71 @@
+#![feature(test)]
+#![deny(warnings, rust_2018_idioms)]
+
+extern crate test;
+
+use ntex::codec::Decoder;
+use ntex::http::{h1, DateService};
+use ntex::util::BytesMut;
+use test::Bencher;
+
+const REQ: &[u8] = b"GET /json HTTP/1.1\r\n\
+    Host: localhost:8080\r\n\
+    User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:95.0) Gecko/20100101 Firefox/95.0\r\n\
+    Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+    Accept-Language: en-US,en;q=0.5\r\n\
+    Accept-Encoding: gzip, deflate, br\r\n\
+    Connection: keep-alive\r\n\
+    Cache-Control: max-age=0\r\n\r\n";
+
+/// Number of pipelined requests per iteration
+const PIPELINE: usize = 16;
+
+fn pipelined() -> BytesMut {
+    let mut buf = BytesMut::with_capacity(REQ.len() * PIPELINE);
+    for _ in 0..PIPELINE {
+        buf.extend_from_slice(REQ);
+    }
+    buf
+}
+
+fn decode_pipelined(b: &mut Bencher, strict: bool) {
+    let codec = h1::Codec::new(DateService::default(), true).strict_parsing(strict);
+    let data = pipelined();
+
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        let mut buf = data.clone();
+        for _ in 0..PIPELINE {
+            test::black_box(codec.decode(&mut buf).unwrap().unwrap());
+        }
+        assert!(buf.is_empty());
+    })
+}
+
+#[bench]
+fn h1_decode_pipelined(b: &mut Bencher) {
+    decode_pipelined(b, false)
+}
+
+#[bench]
+fn h1_decode_pipelined_strict(b: &mut Bencher) {
+    decode_pipelined(b, true)
+}
+
+/// Request head is delivered in small chunks, decoder is called after each chunk
+#[bench]
+fn h1_decode_partial(b: &mut Bencher) {
+    let codec = h1::Codec::new(DateService::default(), true);
+
+    b.bytes = REQ.len() as u64;
+    b.iter(|| {
+        let mut buf = BytesMut::with_capacity(REQ.len());
+        for chunk in REQ.chunks(32) {
+            buf.extend_from_slice(chunk);
+            if let Some(item) = codec.decode(&mut buf).unwrap() {
+                test::black_box(item);
+            }
+        }
+        assert!(buf.is_empty());
+    })
+}
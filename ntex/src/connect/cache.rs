@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant};
+use std::{cell::Cell, cell::RefCell, fmt, io, net::SocketAddr, rc::Rc};
+
+use crate::time::{now, Seconds};
+use crate::util::HashMap;
+
+use super::ConnectError;
+
+/// In-process dns cache.
+///
+/// System resolver does not expose record's ttl, so cached records expire
+/// after configured `ttl` period. Failed lookups are cached for `negative_ttl`
+/// period. Cache could be shared between multiple resolvers.
+#[derive(Clone)]
+pub struct DnsCache(Rc<Inner>);
+
+struct Inner {
+    ttl: Cell<Seconds>,
+    negative_ttl: Cell<Seconds>,
+    max_entries: Cell<usize>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+    entries: RefCell<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    expires: Instant,
+    record: Record,
+}
+
+enum Record {
+    Addrs(Vec<SocketAddr>),
+    NoRecords,
+    Error(io::ErrorKind, String),
+}
+
+/// Dns cache statistics
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DnsCacheStats {
+    /// Number of lookups served from the cache
+    pub hits: u64,
+    /// Number of lookups passed to the system resolver
+    pub misses: u64,
+    /// Number of cached records
+    pub entries: usize,
+}
+
+impl DnsCache {
+    /// Create new dns cache.
+    ///
+    /// By default records are cached for 60 seconds, failed lookups
+    /// are cached for 5 seconds and cache size is limited to 1024 records.
+    pub fn new() -> Self {
+        DnsCache(Rc::new(Inner {
+            ttl: Cell::new(Seconds(60)),
+            negative_ttl: Cell::new(Seconds(5)),
+            max_entries: Cell::new(1024),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+            entries: RefCell::new(HashMap::default()),
+        }))
+    }
+
+    /// Set max time to live for resolved records.
+    ///
+    /// By default ttl is set to 60 seconds.
+    pub fn ttl(self, ttl: Seconds) -> Self {
+        self.0.ttl.set(ttl);
+        self
+    }
+
+    /// Set time to live for failed lookups.
+    ///
+    /// To disable negative caching set value to 0.
+    /// By default negative ttl is set to 5 seconds.
+    pub fn negative_ttl(self, ttl: Seconds) -> Self {
+        self.0.negative_ttl.set(ttl);
+        self
+    }
+
+    /// Set max number of cached records.
+    ///
+    /// By default cache size is limited to 1024 records.
+    pub fn max_entries(self, max: usize) -> Self {
+        self.0.max_entries.set(max);
+        self
+    }
+
+    /// Get cache statistics
+    pub fn stats(&self) -> DnsCacheStats {
+        DnsCacheStats {
+            hits: self.0.hits.get(),
+            misses: self.0.misses.get(),
+            entries: self.0.entries.borrow().len(),
+        }
+    }
+
+    /// Remove all cached records
+    pub fn clear(&self) {
+        self.0.entries.borrow_mut().clear();
+    }
+
+    pub(super) fn get(&self, host: &str) -> Option<Result<Vec<SocketAddr>, ConnectError>> {
+        let mut entries = self.0.entries.borrow_mut();
+
+        let expired = if let Some(entry) = entries.get(host) {
+            if entry.expires > now() {
+                self.0.hits.set(self.0.hits.get() + 1);
+                return Some(match entry.record {
+                    Record::Addrs(ref addrs) => Ok(addrs.clone()),
+                    Record::NoRecords => Err(ConnectError::NoRecords),
+                    Record::Error(kind, ref msg) => {
+                        Err(ConnectError::Resolver(io::Error::new(kind, msg.clone())))
+                    }
+                });
+            }
+            true
+        } else {
+            false
+        };
+        if expired {
+            entries.remove(host);
+        }
+        self.0.misses.set(self.0.misses.get() + 1);
+        None
+    }
+
+    pub(super) fn set(&self, host: String, result: &Result<Vec<SocketAddr>, ConnectError>) {
+        let (ttl, record) = match result {
+            Ok(addrs) => (self.0.ttl.get(), Record::Addrs(addrs.clone())),
+            Err(ConnectError::Resolver(e)) => (
+                self.0.negative_ttl.get(),
+                Record::Error(e.kind(), format!("{}", e)),
+            ),
+            Err(_) => (self.0.negative_ttl.get(), Record::NoRecords),
+        };
+        if ttl.is_zero() {
+            return;
+        }
+
+        let now = now();
+        let mut entries = self.0.entries.borrow_mut();
+        if entries.len() >= self.0.max_entries.get() {
+            entries.retain(|_, entry| entry.expires > now);
+            if entries.len() >= self.0.max_entries.get() {
+                return;
+            }
+        }
+        entries.insert(
+            host,
+            Entry {
+                record,
+                expires: now + Duration::from(ttl),
+            },
+        );
+    }
+}
+
+impl Default for DnsCache {
+    fn default() -> Self {
+        DnsCache::new()
+    }
+}
+
+impl fmt::Debug for DnsCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsCache")
+            .field("ttl", &self.0.ttl.get())
+            .field("negative_ttl", &self.0.negative_ttl.get())
+            .field("max_entries", &self.0.max_entries.get())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[crate::rt_test]
+    async fn test_dns_cache() {
+        let cache = DnsCache::default().ttl(Seconds(10)).max_entries(2);
+        assert!(format!("{:?}", cache).contains("DnsCache"));
+        assert!(cache.get("localhost:80").is_none());
+
+        let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        cache.set("localhost:80".to_string(), &Ok(vec![addr]));
+        assert_eq!(cache.get("localhost:80").unwrap().unwrap(), vec![addr]);
+
+        cache.set("unknown:80".to_string(), &Err(ConnectError::NoRecords));
+        assert!(matches!(
+            cache.get("unknown:80"),
+            Some(Err(ConnectError::NoRecords))
+        ));
+
+        // cache is full
+        cache.set("other:80".to_string(), &Ok(vec![addr]));
+        assert!(cache.get("other:80").is_none());
+
+        assert_eq!(
+            cache.stats(),
+            DnsCacheStats {
+                hits: 2,
+                misses: 2,
+                entries: 2
+            }
+        );
+        cache.clear();
+        assert_eq!(cache.stats().entries, 0);
+
+        // negative caching is disabled
+        let cache = cache.negative_ttl(Seconds::ZERO);
+        cache.set("unknown:80".to_string(), &Err(ConnectError::NoRecords));
+        assert!(cache.get("unknown:80").is_none());
+    }
+}
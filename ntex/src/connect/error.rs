@@ -1,10 +1,10 @@
-use std::io;
+use std::{error::Error, fmt, io, net::SocketAddr, time::Duration};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ConnectError {
     /// Failed to resolve the hostname
     #[error("Failed resolving hostname: {0}")]
-    Resolver(io::Error),
+    Resolver(#[source] io::Error),
 
     /// No dns records
     #[error("No dns records found for the input")]
@@ -18,23 +18,205 @@ pub enum ConnectError {
     #[error("Connector received `Connect` method with unresolved host")]
     Unresolved,
 
+    /// Connect timeout
+    #[error("Timeout out while establishing connection")]
+    Timeout,
+
+    /// Server certificates do not match pinned public keys
+    #[error("Server certificate does not match pinned public keys")]
+    PinMismatch,
+
     /// Connection io error
     #[error("{0}")]
     Io(#[from] io::Error),
 }
 
+impl ConnectError {
+    /// Connect diagnostics
+    ///
+    /// Diagnostics are attached to `Resolver` and `Io` errors returned
+    /// by connectors.
+    pub fn context(&self) -> Option<&ConnectContext> {
+        match self {
+            ConnectError::Resolver(err) | ConnectError::Io(err) => err
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<ConnectContext>()),
+            _ => None,
+        }
+    }
+
+    pub(super) fn with_context(
+        self,
+        host: &str,
+        stage: ConnectStage,
+        attempts: Vec<ConnectAttempt>,
+        elapsed: Duration,
+    ) -> ConnectError {
+        if self.context().is_some() {
+            return self;
+        }
+        let wrap = |error: io::Error| {
+            io::Error::new(
+                error.kind(),
+                ConnectContext {
+                    host: host.to_string(),
+                    stage,
+                    attempts,
+                    elapsed,
+                    error,
+                },
+            )
+        };
+
+        match self {
+            ConnectError::Resolver(err) => ConnectError::Resolver(wrap(err)),
+            ConnectError::Io(err) => ConnectError::Io(wrap(err)),
+            err => err,
+        }
+    }
+}
+
+/// Stage of connect process
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectStage {
+    /// Dns name resolution
+    Resolve,
+    /// Tcp connect to resolved addresses
+    Connect,
+    /// Tls handshake
+    Tls,
+}
+
+impl fmt::Display for ConnectStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectStage::Resolve => write!(f, "resolve"),
+            ConnectStage::Connect => write!(f, "connect"),
+            ConnectStage::Tls => write!(f, "tls handshake"),
+        }
+    }
+}
+
+/// Failed connection attempt to one of resolved addresses
+#[derive(Debug)]
+pub struct ConnectAttempt {
+    /// Remote address
+    pub addr: SocketAddr,
+    /// Connect error
+    pub error: io::Error,
+    /// Attempt duration
+    pub elapsed: Duration,
+}
+
+impl Clone for ConnectAttempt {
+    fn clone(&self) -> Self {
+        ConnectAttempt {
+            addr: self.addr,
+            error: io::Error::new(self.error.kind(), format!("{}", self.error)),
+            elapsed: self.elapsed,
+        }
+    }
+}
+
+/// Connect diagnostics
+///
+/// Contains target host, failed stage, failed attempts for each
+/// resolved address and time spent before failure. Underlying io error
+/// is available via `Error::source()`.
+#[derive(Debug)]
+pub struct ConnectContext {
+    host: String,
+    stage: ConnectStage,
+    attempts: Vec<ConnectAttempt>,
+    elapsed: Duration,
+    error: io::Error,
+}
+
+impl ConnectContext {
+    /// Target host
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Stage of connect process that failed
+    pub fn stage(&self) -> ConnectStage {
+        self.stage
+    }
+
+    /// Failed connection attempts
+    pub fn attempts(&self) -> &[ConnectAttempt] {
+        &self.attempts
+    }
+
+    /// Time spent before failure
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Underlying io error
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl Clone for ConnectContext {
+    fn clone(&self) -> Self {
+        ConnectContext {
+            host: self.host.clone(),
+            stage: self.stage,
+            attempts: self.attempts.clone(),
+            elapsed: self.elapsed,
+            error: io::Error::new(self.error.kind(), format!("{}", self.error)),
+        }
+    }
+}
+
+impl fmt::Display for ConnectContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to connect to {:?} at {} stage after {:?}: {}",
+            self.host, self.stage, self.elapsed, self.error
+        )?;
+        for (idx, attempt) in self.attempts.iter().enumerate() {
+            let sep = if idx == 0 { ", attempts: " } else { ", " };
+            write!(
+                f,
+                "{}{} - {} ({:?})",
+                sep, attempt.addr, attempt.error, attempt.elapsed
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ConnectContext {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Clone io error, keep connect diagnostics
+fn clone_io(err: &io::Error) -> io::Error {
+    match err
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<ConnectContext>())
+    {
+        Some(ctx) => io::Error::new(err.kind(), ctx.clone()),
+        None => io::Error::new(err.kind(), format!("{}", err)),
+    }
+}
+
 impl Clone for ConnectError {
     fn clone(&self) -> Self {
         match self {
-            ConnectError::Resolver(err) => {
-                ConnectError::Resolver(io::Error::new(err.kind(), format!("{}", err)))
-            }
+            ConnectError::Resolver(err) => ConnectError::Resolver(clone_io(err)),
             ConnectError::NoRecords => ConnectError::NoRecords,
             ConnectError::InvalidInput => ConnectError::InvalidInput,
             ConnectError::Unresolved => ConnectError::Unresolved,
-            ConnectError::Io(err) => {
-                ConnectError::Io(io::Error::new(err.kind(), format!("{}", err)))
-            }
+            ConnectError::Timeout => ConnectError::Timeout,
+            ConnectError::PinMismatch => ConnectError::PinMismatch,
+            ConnectError::Io(err) => ConnectError::Io(clone_io(err)),
         }
     }
 }
@@ -50,6 +232,52 @@ mod tests {
         let _ = ConnectError::NoRecords.clone();
         let _ = ConnectError::InvalidInput.clone();
         let _ = ConnectError::Unresolved.clone();
+        let _ = ConnectError::Timeout.clone();
+        let _ = ConnectError::PinMismatch.clone();
         let _ = ConnectError::Io(io::Error::new(io::ErrorKind::Other, "test")).clone();
     }
+
+    #[test]
+    fn connect_error_context() {
+        let attempt = ConnectAttempt {
+            addr: "127.0.0.1:8080".parse().unwrap(),
+            error: io::Error::new(io::ErrorKind::ConnectionRefused, "refused"),
+            elapsed: Duration::from_millis(1),
+        };
+        let err = ConnectError::Io(io::Error::new(io::ErrorKind::Other, "test"))
+            .with_context(
+                "localhost:8080",
+                ConnectStage::Connect,
+                vec![attempt],
+                Duration::from_millis(2),
+            )
+            .clone();
+        assert!(matches!(err, ConnectError::Io(_)));
+
+        let ctx = err.context().unwrap();
+        assert_eq!(ctx.host(), "localhost:8080");
+        assert_eq!(ctx.stage(), ConnectStage::Connect);
+        assert_eq!(ctx.attempts().len(), 1);
+        assert_eq!(ctx.elapsed(), Duration::from_millis(2));
+        assert_eq!(
+            err.to_string(),
+            "Failed to connect to \"localhost:8080\" at connect stage after 2ms: test, \
+             attempts: 127.0.0.1:8080 - refused (1ms)"
+        );
+
+        // error chain
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), err.to_string());
+        assert_eq!(source.source().unwrap().to_string(), "test");
+
+        // errors without io error do not carry diagnostics
+        let err = ConnectError::Timeout.with_context(
+            "localhost:8080",
+            ConnectStage::Connect,
+            Vec::new(),
+            Duration::from_millis(2),
+        );
+        assert!(matches!(err, ConnectError::Timeout));
+        assert!(err.context().is_none());
+    }
 }
@@ -1,10 +1,14 @@
 //! Tcp connector service
 use std::future::Future;
 
+mod cache;
 mod error;
 mod message;
+mod pool;
 mod resolve;
 mod service;
+#[cfg(feature = "dns-srv")]
+mod srv;
 mod uri;
 
 #[cfg(feature = "openssl")]
@@ -13,10 +17,17 @@ pub mod openssl;
 #[cfg(feature = "rustls")]
 pub mod rustls;
 
-pub use self::error::ConnectError;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+mod verify;
+
+pub use self::cache::{DnsCache, DnsCacheStats};
+pub use self::error::{ConnectAttempt, ConnectContext, ConnectError, ConnectStage};
 pub use self::message::{Address, Connect};
+pub use self::pool::{PooledConnector, PooledIo};
 pub use self::resolve::Resolver;
 pub use self::service::Connector;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+pub use self::verify::spki_sha256;
 
 use crate::io::Io;
 
@@ -26,5 +37,7 @@ where
     T: Address + 'static,
     Connect<T>: From<U>,
 {
-    service::ConnectServiceResponse::new(Box::pin(Resolver::new().lookup(message.into())))
+    let message: Connect<T> = message.into();
+    let host = message.host().to_string();
+    service::ConnectServiceResponse::new(host, Box::pin(Resolver::new().lookup(message)))
 }
@@ -1,19 +1,25 @@
-use std::{future::Future, io, pin::Pin, task::Context, task::Poll};
+use std::sync::{atomic::AtomicBool, atomic::Ordering, Arc};
+use std::{future::Future, io, pin::Pin, task::Context, task::Poll, time::Instant};
 
 pub use ntex_tls::openssl::SslFilter;
 pub use tls_openssl::ssl::{Error as SslError, HandshakeError, SslConnector, SslMethod};
 
 use ntex_tls::openssl::SslConnector as IoSslConnector;
+use tls_openssl::{ssl::SslVerifyMode, x509::X509VerifyResult};
 
 use crate::io::{Base, Io};
 use crate::service::{Service, ServiceFactory};
+use crate::tls::types::PeerCertificates;
 use crate::util::{PoolId, Ready};
 
+use super::error::ConnectStage;
+use super::verify::Verification;
 use super::{Address, Connect, ConnectError, Connector as BaseConnector};
 
 pub struct Connector<T> {
     connector: BaseConnector<T>,
     openssl: SslConnector,
+    verify: Verification,
 }
 
 impl<T> Connector<T> {
@@ -22,6 +28,7 @@ impl<T> Connector<T> {
         Connector {
             connector: BaseConnector::default(),
             openssl: connector,
+            verify: Verification::default(),
         }
     }
 
@@ -33,8 +40,43 @@ impl<T> Connector<T> {
         Self {
             connector: self.connector.memory_pool(id),
             openssl: self.openssl,
+            verify: self.verify,
         }
     }
+
+    /// Pin server public key.
+    ///
+    /// `hash` is SHA-256 hash of DER encoded `SubjectPublicKeyInfo`,
+    /// see [`spki_sha256`](../fn.spki_sha256.html). Connection is established
+    /// if any certificate in server chain matches any pinned key, otherwise
+    /// connect fails with `ConnectError::PinMismatch`. Pins are checked in
+    /// addition to chain verification.
+    pub fn pin_public_key(mut self, hash: [u8; 32]) -> Self {
+        self.verify.pin(hash);
+        self
+    }
+
+    /// Set custom server certificates verifier.
+    ///
+    /// Verifier replaces default chain and host name verification, it is
+    /// called with requested host and server certificates chain, peer
+    /// certificate comes first. Handshake fails if verifier returns `false`.
+    pub fn verifier<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &PeerCertificates) -> bool + Send + Sync + 'static,
+    {
+        self.verify.set_verifier(Arc::new(f));
+        self
+    }
+
+    /// Accept invalid server certificates.
+    ///
+    /// Disables chain and host name verification, use it for
+    /// test environments only. Pinned keys are still checked.
+    pub fn danger_accept_invalid_certs(mut self) -> Self {
+        self.verify.accept_invalid();
+        self
+    }
 }
 
 impl<T: Address + 'static> Connector<T> {
@@ -50,27 +92,104 @@ impl<T: Address + 'static> Connector<T> {
         let host = message.host().to_string();
         let conn = self.connector.call(message);
         let openssl = self.openssl.clone();
+        let verify = self.verify.clone();
 
         async move {
             let io = conn.await?;
-            trace!("SSL Handshake start for: {:?}", host);
-
-            match openssl.configure() {
-                Err(e) => Err(io::Error::new(io::ErrorKind::Other, e).into()),
-                Ok(config) => {
-                    let ssl = config
-                        .into_ssl(&host)
-                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-                    match io.add_filter(IoSslConnector::new(ssl)).await {
-                        Ok(io) => {
-                            trace!("SSL Handshake success: {:?}", host);
-                            Ok(io)
-                        }
-                        Err(e) => {
-                            trace!("SSL Handshake error: {:?}", e);
-                            Err(io::Error::new(io::ErrorKind::Other, format!("{}", e))
-                                .into())
-                        }
+            handshake(openssl, verify, io, host).await
+        }
+    }
+}
+
+impl<T> Connector<T> {
+    /// Perform ssl handshake over established connection
+    pub fn handshake(
+        &self,
+        io: Io,
+        host: &str,
+    ) -> impl Future<Output = Result<Io<SslFilter<Base>>, ConnectError>> {
+        handshake(
+            self.openssl.clone(),
+            self.verify.clone(),
+            io,
+            host.to_string(),
+        )
+    }
+}
+
+async fn handshake(
+    openssl: SslConnector,
+    verify: Verification,
+    io: Io,
+    host: String,
+) -> Result<Io<SslFilter<Base>>, ConnectError> {
+    let started = Instant::now();
+    do_handshake(openssl, verify, io, host.clone())
+        .await
+        .map_err(|err| {
+            err.with_context(&host, ConnectStage::Tls, Vec::new(), started.elapsed())
+        })
+}
+
+async fn do_handshake(
+    openssl: SslConnector,
+    verify: Verification,
+    io: Io,
+    host: String,
+) -> Result<Io<SslFilter<Base>>, ConnectError> {
+    trace!("SSL Handshake start for: {:?}", host);
+
+    match openssl.configure() {
+        Err(e) => Err(io::Error::new(io::ErrorKind::Other, e).into()),
+        Ok(mut config) => {
+            let pin_mismatch = Arc::new(AtomicBool::new(false));
+            if verify.is_custom() || verify.has_pins() {
+                if verify.is_custom() {
+                    config.set_verify_hostname(false);
+                }
+                let mismatch = pin_mismatch.clone();
+                let host = host.split(':').next().unwrap().to_owned();
+                config.set_verify_callback(SslVerifyMode::PEER, move |preverified, ctx| {
+                    // chain is verified from root to peer certificate
+                    if ctx.error_depth() != 0 {
+                        return preverified || verify.is_custom();
+                    }
+                    let certs = PeerCertificates(
+                        ctx.chain()
+                            .map(|chain| {
+                                chain.iter().filter_map(|c| c.to_der().ok()).collect()
+                            })
+                            .unwrap_or_default(),
+                    );
+                    let verified = if verify.is_custom() {
+                        verify.verify(&host, &certs)
+                    } else {
+                        preverified
+                    };
+                    if verified && !verify.check_pins(&certs) {
+                        mismatch.store(true, Ordering::Relaxed);
+                        ctx.set_error(X509VerifyResult::APPLICATION_VERIFICATION);
+                        false
+                    } else {
+                        verified
+                    }
+                });
+            }
+
+            let ssl = config
+                .into_ssl(&host)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            match io.add_filter(IoSslConnector::new(ssl)).await {
+                Ok(io) => {
+                    trace!("SSL Handshake success: {:?}", host);
+                    Ok(io)
+                }
+                Err(e) => {
+                    trace!("SSL Handshake error: {:?}", e);
+                    if pin_mismatch.load(Ordering::Relaxed) {
+                        Err(ConnectError::PinMismatch)
+                    } else {
+                        Err(io::Error::new(io::ErrorKind::Other, format!("{}", e)).into())
                     }
                 }
             }
@@ -83,6 +202,7 @@ impl<T> Clone for Connector<T> {
         Connector {
             connector: self.connector.clone(),
             openssl: self.openssl.clone(),
+            verify: self.verify.clone(),
         }
     }
 }
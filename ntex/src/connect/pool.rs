@@ -0,0 +1,506 @@
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{cell::RefCell, collections::VecDeque, fmt, future::Future, ops, pin::Pin};
+
+use crate::io::Io;
+use crate::rt::spawn;
+use crate::service::{Service, ServiceFactory};
+use crate::time::{interval, now, Millis, Seconds};
+use crate::util::{Either, HashMap, PoolId, Ready};
+
+use super::{Address, Connect, ConnectError, Connector};
+
+/// Period of expired idle connections cleanup
+const REAP_INTERVAL: Millis = Millis(1_000);
+
+#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+struct Key {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug)]
+struct IdleConnection {
+    io: Io,
+    used: Instant,
+    created: Instant,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Config {
+    max_idle_per_host: usize,
+    idle_timeout: Duration,
+    max_lifetime: Duration,
+}
+
+impl Config {
+    fn is_expired(&self, conn: &IdleConnection, now: Instant) -> bool {
+        (now - conn.used) > self.idle_timeout
+            || (now - conn.created) > self.max_lifetime
+            || conn.io.is_closed()
+    }
+}
+
+struct Inner {
+    config: Config,
+    reaper: bool,
+    available: HashMap<Key, VecDeque<IdleConnection>>,
+}
+
+impl Inner {
+    fn new(config: Config) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Inner {
+            config,
+            reaper: false,
+            available: HashMap::default(),
+        }))
+    }
+
+    fn acquire(&mut self, key: &Key) -> Option<(Io, Instant)> {
+        let now = now();
+        let config = self.config;
+
+        let connections = self.available.get_mut(key)?;
+        let mut result = None;
+        while let Some(conn) = connections.pop_back() {
+            // check if it still usable
+            if config.is_expired(&conn, now) {
+                close(conn.io);
+                continue;
+            }
+
+            // connection must not have any unread data
+            let is_valid = conn.io.with_read_buf(|buf| buf.is_empty());
+            if !is_valid {
+                close(conn.io);
+                continue;
+            }
+            result = Some((conn.io, conn.created));
+            break;
+        }
+        if connections.is_empty() {
+            self.available.remove(key);
+        }
+        result
+    }
+
+    /// Put connection to the pool, returns `true` if reaper must be started
+    fn release(&mut self, key: Key, io: Io, created: Instant) -> bool {
+        let now = now();
+        if io.is_closed()
+            || self.config.max_idle_per_host == 0
+            || (now - created) > self.config.max_lifetime
+        {
+            close(io);
+            return false;
+        }
+
+        let connections = self.available.entry(key).or_default();
+        connections.push_back(IdleConnection {
+            io,
+            created,
+            used: now,
+        });
+
+        // drop oldest idle connections
+        while connections.len() > self.config.max_idle_per_host {
+            if let Some(conn) = connections.pop_front() {
+                close(conn.io);
+            }
+        }
+
+        // start reaper if it is not running
+        !std::mem::replace(&mut self.reaper, true)
+    }
+
+    /// Close expired idle connections
+    fn reap(&mut self) {
+        let now = now();
+        let config = self.config;
+
+        self.available.retain(|_, connections| {
+            let mut idx = 0;
+            while idx < connections.len() {
+                if config.is_expired(&connections[idx], now) {
+                    if let Some(conn) = connections.remove(idx) {
+                        close(conn.io);
+                    }
+                } else {
+                    idx += 1;
+                }
+            }
+            !connections.is_empty()
+        });
+    }
+
+    fn idle(&self) -> usize {
+        self.available.values().map(|c| c.len()).sum()
+    }
+}
+
+fn close(io: Io) {
+    spawn(async move {
+        let _ = io.shutdown().await;
+    });
+}
+
+/// Periodically close expired idle connections.
+///
+/// Reaper stops when pool is empty or dropped.
+async fn reaper(pool: Weak<RefCell<Inner>>) {
+    let interval = interval(REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if let Some(inner) = pool.upgrade() {
+            let mut inner = inner.borrow_mut();
+            inner.reap();
+            if inner.available.is_empty() {
+                inner.reaper = false;
+                return;
+            }
+        } else {
+            return;
+        }
+    }
+}
+
+/// Tcp connector with idle connections pool.
+///
+/// Pool keeps idle connections keyed by host and port. Connection returned
+/// by the pooled connector must be explicitly released with
+/// [`PooledIo::release`](struct.PooledIo.html#method.release) method,
+/// otherwise it gets closed on drop. Expired idle connections are closed
+/// in background.
+///
+/// Clones of the connector share the pool. Configuration methods return
+/// connector with new empty pool, other clones are not affected.
+///
+/// ```rust,no_run
+/// use ntex::connect::PooledConnector;
+/// use ntex::time::Seconds;
+///
+/// #[ntex::main]
+/// async fn main() {
+///     let connector = PooledConnector::<String>::default()
+///         .max_idle_per_host(4)
+///         .idle_timeout(Seconds(30));
+///
+///     let io = connector.connect("www.rust-lang.org:80".to_string()).await.unwrap();
+///     // ... use connection, then return it to the pool
+///     io.release();
+/// }
+/// ```
+pub struct PooledConnector<T> {
+    connector: Connector<T>,
+    config: Config,
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl<T> PooledConnector<T> {
+    /// Construct new pooled connector.
+    ///
+    /// By default pool keeps up to 8 idle connections per host, idle timeout
+    /// is set to 15 seconds and max connection lifetime is 75 seconds.
+    pub fn new(connector: Connector<T>) -> Self {
+        let config = Config {
+            max_idle_per_host: 8,
+            idle_timeout: Duration::from_secs(15),
+            max_lifetime: Duration::from_secs(75),
+        };
+        PooledConnector {
+            connector,
+            config,
+            inner: Inner::new(config),
+        }
+    }
+
+    /// Set max number of idle connections per host.
+    ///
+    /// If limit is 0, connections are not pooled.
+    /// The default limit size is 8.
+    pub fn max_idle_per_host(mut self, limit: usize) -> Self {
+        self.config.max_idle_per_host = limit;
+        self.reconfigure()
+    }
+
+    /// Set idle timeout for pooled connection.
+    ///
+    /// Idle connection is closed if it is not used during this period.
+    /// Default idle timeout is 15 seconds.
+    pub fn idle_timeout(mut self, timeout: Seconds) -> Self {
+        self.config.idle_timeout = timeout.into();
+        self.reconfigure()
+    }
+
+    /// Set max lifetime period for connection.
+    ///
+    /// Connection lifetime is max lifetime of any opened connection
+    /// until it is closed regardless of idle timeout.
+    /// Default lifetime period is 75 seconds.
+    pub fn max_lifetime(mut self, lifetime: Seconds) -> Self {
+        self.config.max_lifetime = lifetime.into();
+        self.reconfigure()
+    }
+
+    /// Set memory pool.
+    ///
+    /// Use specified memory pool for memory allocations. By default P0
+    /// memory pool is used.
+    pub fn memory_pool(self, id: PoolId) -> Self {
+        Self {
+            connector: self.connector.memory_pool(id),
+            config: self.config,
+            inner: Inner::new(self.config),
+        }
+    }
+
+    fn reconfigure(self) -> Self {
+        Self {
+            connector: self.connector,
+            config: self.config,
+            inner: Inner::new(self.config),
+        }
+    }
+
+    /// Number of idle connections in the pool.
+    pub fn idle(&self) -> usize {
+        self.inner.borrow().idle()
+    }
+}
+
+impl<T: Address> PooledConnector<T> {
+    /// Get idle connection from the pool or connect to remote host
+    pub fn connect<U>(
+        &self,
+        message: U,
+    ) -> impl Future<Output = Result<PooledIo, ConnectError>>
+    where
+        Connect<T>: From<U>,
+    {
+        let req = Connect::from(message);
+        let key = Key {
+            host: req.host().to_string(),
+            port: req.port(),
+        };
+        let inner = self.inner.clone();
+
+        let result = inner.borrow_mut().acquire(&key);
+        if let Some((io, created)) = result {
+            trace!("Use pooled connection for {:?}:{}", key.host, key.port);
+            Either::Right(Ready::Ok(PooledIo::new(io, key, created, inner)))
+        } else {
+            let fut = self.connector.call(req);
+            Either::Left(async move {
+                let io = fut.await?;
+                Ok(PooledIo::new(io, key, now(), inner))
+            })
+        }
+    }
+}
+
+impl<T> Default for PooledConnector<T> {
+    fn default() -> Self {
+        PooledConnector::new(Connector::default())
+    }
+}
+
+impl<T> Clone for PooledConnector<T> {
+    fn clone(&self) -> Self {
+        PooledConnector {
+            connector: self.connector.clone(),
+            config: self.config,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for PooledConnector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledConnector")
+            .field("max_idle_per_host", &self.config.max_idle_per_host)
+            .field("idle_timeout", &self.config.idle_timeout)
+            .field("max_lifetime", &self.config.max_lifetime)
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+impl<T: Address, C> ServiceFactory<Connect<T>, C> for PooledConnector<T> {
+    type Response = PooledIo;
+    type Error = ConnectError;
+    type Service = PooledConnector<T>;
+    type InitError = ();
+    type Future = Ready<Self::Service, Self::InitError>;
+
+    #[inline]
+    fn new_service(&self, _: C) -> Self::Future {
+        Ready::Ok(self.clone())
+    }
+}
+
+impl<T: Address> Service<Connect<T>> for PooledConnector<T> {
+    type Response = PooledIo;
+    type Error = ConnectError;
+    type Future = Pin<Box<dyn Future<Output = Result<PooledIo, ConnectError>>>>;
+
+    #[inline]
+    fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn call(&self, req: Connect<T>) -> Self::Future {
+        Box::pin(self.connect(req))
+    }
+}
+
+/// Connection acquired from [`PooledConnector`](struct.PooledConnector.html).
+pub struct PooledIo {
+    io: Option<Io>,
+    key: Key,
+    created: Instant,
+    pool: Rc<RefCell<Inner>>,
+}
+
+impl PooledIo {
+    fn new(io: Io, key: Key, created: Instant, pool: Rc<RefCell<Inner>>) -> Self {
+        PooledIo {
+            key,
+            created,
+            pool,
+            io: Some(io),
+        }
+    }
+
+    /// Connection creation time.
+    pub fn created(&self) -> Instant {
+        self.created
+    }
+
+    /// Return connection to the pool.
+    ///
+    /// Connection must be in reusable state, any unread data
+    /// invalidates connection.
+    pub fn release(mut self) {
+        if let Some(io) = self.io.take() {
+            let key = self.key.clone();
+            if self.pool.borrow_mut().release(key, io, self.created) {
+                spawn(reaper(Rc::downgrade(&self.pool)));
+            }
+        }
+    }
+
+    /// Detach connection from the pool.
+    pub fn into_inner(mut self) -> Io {
+        self.io.take().unwrap()
+    }
+}
+
+impl ops::Deref for PooledIo {
+    type Target = Io;
+
+    fn deref(&self) -> &Io {
+        self.io.as_ref().unwrap()
+    }
+}
+
+impl fmt::Debug for PooledIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledIo")
+            .field("host", &self.key.host)
+            .field("port", &self.key.port)
+            .field("io", &self.io)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{time::sleep, util::lazy};
+
+    #[crate::rt_test]
+    async fn test_pooled_connect() {
+        let server = crate::server::test_server(|| {
+            crate::service::fn_service(|_| async { Ok::<_, ()>(()) })
+        });
+
+        let srv = PooledConnector::<String>::default()
+            .max_idle_per_host(1)
+            .idle_timeout(Seconds(10))
+            .max_lifetime(Seconds(10))
+            .memory_pool(PoolId::P5)
+            .clone();
+        assert!(format!("{:?}", srv).contains("PooledConnector"));
+        assert!(lazy(|cx| srv.poll_ready(cx)).await.is_ready());
+
+        let result = srv.connect(String::new()).await;
+        assert!(result.is_err());
+
+        let io = srv.connect(format!("{}", server.addr())).await.unwrap();
+        assert!(format!("{:?}", io).contains("PooledIo"));
+        let io2 = srv.call(format!("{}", server.addr()).into()).await.unwrap();
+        let hnd = io2.get_ref();
+        io.release();
+        io2.release();
+        assert_eq!(srv.idle(), 1);
+
+        // reuse pooled connection
+        let io = srv.connect(format!("{}", server.addr())).await.unwrap();
+        assert_eq!(srv.idle(), 0);
+        assert_eq!(io.get_ref(), hnd);
+
+        // detached connection is not returned to pool
+        let _ = io.into_inner();
+        assert_eq!(srv.idle(), 0);
+
+        let srv = srv.max_idle_per_host(0);
+        let io = srv.connect(format!("{}", server.addr())).await.unwrap();
+        io.release();
+        assert_eq!(srv.idle(), 0);
+    }
+
+    #[crate::rt_test]
+    async fn test_reaper() {
+        let server = crate::server::test_server(|| {
+            crate::service::fn_service(|_| async { Ok::<_, ()>(()) })
+        });
+
+        let srv = PooledConnector::<String>::default().idle_timeout(Seconds(1));
+        let io = srv.connect(format!("{}", server.addr())).await.unwrap();
+        io.release();
+        assert_eq!(srv.idle(), 1);
+        assert!(srv.inner.borrow().reaper);
+
+        // expired connection is closed without acquire
+        sleep(Millis(2_500)).await;
+        assert_eq!(srv.idle(), 0);
+        assert!(!srv.inner.borrow().reaper);
+    }
+
+    #[crate::rt_test]
+    async fn test_unshared_config() {
+        let server = crate::server::test_server(|| {
+            crate::service::fn_service(|_| async { Ok::<_, ()>(()) })
+        });
+
+        let srv = PooledConnector::<String>::default();
+        let io = srv.connect(format!("{}", server.addr())).await.unwrap();
+        io.release();
+        assert_eq!(srv.idle(), 1);
+
+        // reconfigured connector gets new pool
+        let srv2 = srv.clone().max_idle_per_host(0);
+        assert_eq!(srv.config.max_idle_per_host, 8);
+        assert_eq!(srv2.config.max_idle_per_host, 0);
+        assert_eq!(srv.idle(), 1);
+        assert_eq!(srv2.idle(), 0);
+
+        let io = srv2.connect(format!("{}", server.addr())).await.unwrap();
+        io.release();
+        assert_eq!(srv.idle(), 1);
+        assert_eq!(srv2.idle(), 0);
+    }
+}
@@ -1,22 +1,55 @@
 use std::{fmt, future::Future, io, marker, net, pin::Pin, task::Context, task::Poll};
 
-use super::{Address, Connect, ConnectError};
+use super::{Address, Connect, ConnectError, DnsCache};
 use crate::service::{Service, ServiceFactory};
 use crate::util::{Either, Ready};
 
 /// DNS Resolver Service
-pub struct Resolver<T>(marker::PhantomData<T>);
+pub struct Resolver<T> {
+    cache: Option<DnsCache>,
+    srv: bool,
+    _t: marker::PhantomData<T>,
+}
 
 impl<T> fmt::Debug for Resolver<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Resolver").finish()
+        f.debug_struct("Resolver")
+            .field("cache", &self.cache)
+            .field("srv", &self.srv)
+            .finish()
     }
 }
 
 impl<T> Resolver<T> {
     /// Create new resolver instance with custom configuration and options.
     pub fn new() -> Self {
-        Resolver(marker::PhantomData)
+        Resolver {
+            cache: None,
+            srv: false,
+            _t: marker::PhantomData,
+        }
+    }
+
+    /// Use dns cache for lookups.
+    ///
+    /// By default resolver does not cache records.
+    pub fn cache(mut self, cache: DnsCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    #[cfg(feature = "dns-srv")]
+    /// Enable SRV records resolution mode.
+    ///
+    /// Host names in form of `_service._proto.name` get resolved via SRV
+    /// records. Resolved addresses are ordered by records priority and weight
+    /// and use port from SRV record. Nameservers are taken from system
+    /// configuration.
+    ///
+    /// By default SRV resolution is disabled.
+    pub fn srv(mut self, enabled: bool) -> Self {
+        self.srv = enabled;
+        self
     }
 }
 
@@ -33,45 +66,40 @@ impl<T: Address> Resolver<T> {
             Either::Right(Ready::Ok(req))
         } else {
             trace!("DNS resolver: resolving host {:?}", req.host());
+            let cache = self.cache.clone();
+            let srv = self.srv && req.host().starts_with('_');
 
             Either::Left(async move {
-                let host = if req.host().contains(':') {
+                let host = if srv || req.host().contains(':') {
                     req.host().to_string()
                 } else {
                     format!("{}:{}", req.host(), req.port())
                 };
 
-                let fut = crate::rt::spawn_blocking(move || {
-                    net::ToSocketAddrs::to_socket_addrs(&host)
-                });
-
-                match fut.await {
-                    Ok(Ok(ips)) => {
-                        let port = req.port();
-                        let req = req.set_addrs(ips.map(|mut ip| {
-                            ip.set_port(port);
-                            ip
-                        }));
+                let result = match cache.as_ref().and_then(|c| c.get(&host)) {
+                    Some(result) => {
+                        trace!("DNS resolver: use cached records for {:?}", host);
+                        result
+                    }
+                    None => {
+                        let key = host.clone();
+                        let result = resolve(host, req.port(), srv).await;
+                        if let Some(ref cache) = cache {
+                            cache.set(key, &result);
+                        }
+                        result
+                    }
+                };
 
+                match result {
+                    Ok(ips) => {
+                        let req = req.set_addrs(ips);
                         trace!(
                             "DNS resolver: host {:?} resolved to {:?}",
                             req.host(),
                             req.addrs()
                         );
-
-                        if req.addr.is_none() {
-                            Err(ConnectError::NoRecords)
-                        } else {
-                            Ok(req)
-                        }
-                    }
-                    Ok(Err(e)) => {
-                        trace!(
-                            "DNS resolver: failed to resolve host {:?} err: {}",
-                            req.host(),
-                            e
-                        );
-                        Err(ConnectError::Resolver(e))
+                        Ok(req)
                     }
                     Err(e) => {
                         trace!(
@@ -79,10 +107,7 @@ impl<T: Address> Resolver<T> {
                             req.host(),
                             e
                         );
-                        Err(ConnectError::Resolver(io::Error::new(
-                            io::ErrorKind::Other,
-                            e,
-                        )))
+                        Err(e)
                     }
                 }
             })
@@ -90,6 +115,52 @@ impl<T: Address> Resolver<T> {
     }
 }
 
+async fn resolve(
+    host: String,
+    port: u16,
+    srv: bool,
+) -> Result<Vec<net::SocketAddr>, ConnectError> {
+    let fut = crate::rt::spawn_blocking(move || {
+        if srv {
+            srv_lookup(&host)
+        } else {
+            net::ToSocketAddrs::to_socket_addrs(&host).map(|ips| {
+                ips.map(|mut ip| {
+                    ip.set_port(port);
+                    ip
+                })
+                .collect()
+            })
+        }
+    });
+
+    match fut.await {
+        Ok(Ok(ips)) => {
+            if ips.is_empty() {
+                Err(ConnectError::NoRecords)
+            } else {
+                Ok(ips)
+            }
+        }
+        Ok(Err(e)) => Err(ConnectError::Resolver(e)),
+        Err(e) => Err(ConnectError::Resolver(io::Error::new(
+            io::ErrorKind::Other,
+            e,
+        ))),
+    }
+}
+
+#[cfg(feature = "dns-srv")]
+use super::srv::lookup as srv_lookup;
+
+#[cfg(not(feature = "dns-srv"))]
+fn srv_lookup(_: &str) -> io::Result<Vec<net::SocketAddr>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "SRV resolution requires `dns-srv` feature",
+    ))
+}
+
 impl<T> Default for Resolver<T> {
     fn default() -> Resolver<T> {
         Resolver::new()
@@ -98,7 +169,11 @@ impl<T> Default for Resolver<T> {
 
 impl<T> Clone for Resolver<T> {
     fn clone(&self) -> Self {
-        Resolver(marker::PhantomData)
+        Resolver {
+            cache: self.cache.clone(),
+            srv: self.srv,
+            _t: marker::PhantomData,
+        }
     }
 }
 
@@ -158,4 +233,24 @@ mod tests {
         assert_eq!(addrs.len(), 1);
         assert!(addrs.contains(&addr));
     }
+
+    #[crate::rt_test]
+    async fn resolver_cache() {
+        let cache = DnsCache::new();
+        let resolver = Resolver::new().cache(cache.clone());
+        assert!(format!("{:?}", resolver).contains("DnsCache"));
+
+        let res = resolver.lookup(Connect::new("localhost:8080")).await;
+        assert!(res.is_ok());
+        let res = resolver
+            .lookup(Connect::new("localhost:8080"))
+            .await
+            .unwrap();
+        assert!(res.addrs().all(|addr| addr.port() == 8080));
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.entries, 1);
+    }
 }
@@ -1,37 +1,44 @@
-use std::{convert::TryFrom, future::Future, io, pin::Pin, task::Context, task::Poll};
+use std::{convert::TryFrom, future::Future, io, pin::Pin, sync::Arc};
+use std::{task::Context, task::Poll, time::Instant, time::SystemTime};
 
 pub use ntex_tls::rustls::TlsFilter;
 pub use tls_rustls::{ClientConfig, ServerName};
 
-use ntex_tls::rustls::TlsConnector;
+use ntex_tls::rustls::{PeerCertChain, TlsConnector};
+use tls_rustls::client::{ServerCertVerified, ServerCertVerifier};
+use tls_rustls::{Certificate, Error as TlsError};
 
 use crate::io::{Base, Io};
 use crate::service::{Service, ServiceFactory};
+use crate::tls::types::PeerCertificates;
 use crate::util::{PoolId, Ready};
 
+use super::error::ConnectStage;
+use super::verify::Verification;
 use super::{Address, Connect, ConnectError, Connector as BaseConnector};
 
 /// Rustls connector factory
 pub struct Connector<T> {
     connector: BaseConnector<T>,
     inner: TlsConnector,
+    config: Arc<ClientConfig>,
+    verify: Verification,
 }
 
-impl<T> From<std::sync::Arc<ClientConfig>> for Connector<T> {
-    fn from(cfg: std::sync::Arc<ClientConfig>) -> Self {
+impl<T> From<Arc<ClientConfig>> for Connector<T> {
+    fn from(cfg: Arc<ClientConfig>) -> Self {
         Connector {
-            inner: TlsConnector::new(cfg),
+            inner: TlsConnector::new(cfg.clone()),
+            config: cfg,
             connector: BaseConnector::default(),
+            verify: Verification::default(),
         }
     }
 }
 
 impl<T> Connector<T> {
     pub fn new(config: ClientConfig) -> Self {
-        Connector {
-            inner: TlsConnector::new(std::sync::Arc::new(config)),
-            connector: BaseConnector::default(),
-        }
+        Arc::new(config).into()
     }
 
     /// Set memory pool.
@@ -41,7 +48,86 @@ impl<T> Connector<T> {
     pub fn memory_pool(self, id: PoolId) -> Self {
         Self {
             connector: self.connector.memory_pool(id),
-            inner: self.inner,
+            ..self
+        }
+    }
+
+    /// Pin server public key.
+    ///
+    /// `hash` is SHA-256 hash of DER encoded `SubjectPublicKeyInfo`,
+    /// see [`spki_sha256`](../fn.spki_sha256.html). Connection is established
+    /// if any certificate in server chain matches any pinned key, otherwise
+    /// connect fails with `ConnectError::PinMismatch`. Pins are checked in
+    /// addition to chain verification.
+    pub fn pin_public_key(mut self, hash: [u8; 32]) -> Self {
+        self.verify.pin(hash);
+        self
+    }
+
+    /// Set custom server certificates verifier.
+    ///
+    /// Verifier replaces default chain and host name verification, it is
+    /// called with requested host and server certificates chain, peer
+    /// certificate comes first. Handshake fails if verifier returns `false`.
+    pub fn verifier<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &PeerCertificates) -> bool + Send + Sync + 'static,
+    {
+        self.verify.set_verifier(Arc::new(f));
+        self.with_verifier()
+    }
+
+    /// Accept invalid server certificates.
+    ///
+    /// Disables chain and host name verification, use it for
+    /// test environments only. Pinned keys are still checked.
+    pub fn danger_accept_invalid_certs(mut self) -> Self {
+        self.verify.accept_invalid();
+        self.with_verifier()
+    }
+
+    /// Replace config's verifier, connector's config is not modified
+    fn with_verifier(mut self) -> Self {
+        let mut config = (*self.config).clone();
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(Verifier(self.verify.clone())));
+        self.inner = TlsConnector::new(Arc::new(config));
+        self
+    }
+}
+
+/// Custom server certificates verifier
+struct Verifier(Verification);
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        _: &mut dyn Iterator<Item = &[u8]>,
+        _: &[u8],
+        _: SystemTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        let host = if let ServerName::DnsName(name) = server_name {
+            name.as_ref()
+        } else {
+            ""
+        };
+        let certs = PeerCertificates(
+            Some(end_entity)
+                .into_iter()
+                .chain(intermediates)
+                .map(|cert| cert.0.clone())
+                .collect(),
+        );
+        if self.0.verify(host, &certs) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TlsError::InvalidCertificateData(
+                "Certificate is rejected by verifier".into(),
+            ))
         }
     }
 }
@@ -59,25 +145,73 @@ impl<T: Address + 'static> Connector<T> {
         let host = req.host().split(':').next().unwrap().to_owned();
         let conn = self.connector.call(req);
         let connector = self.inner.clone();
+        let verify = self.verify.clone();
 
         async move {
             let io = conn.await?;
-            trace!("SSL Handshake start for: {:?}", host);
+            handshake(connector, verify, io, host).await
+        }
+    }
+}
 
-            let host = ServerName::try_from(host.as_str())
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))?;
-            let connector = connector.server_name(host.clone());
+impl<T> Connector<T> {
+    /// Perform tls handshake over established connection
+    pub fn handshake(
+        &self,
+        io: Io,
+        host: &str,
+    ) -> impl Future<Output = Result<Io<TlsFilter<Base>>, ConnectError>> {
+        let host = host.split(':').next().unwrap().to_owned();
+        handshake(self.inner.clone(), self.verify.clone(), io, host)
+    }
+}
 
-            match io.add_filter(connector).await {
-                Ok(io) => {
-                    trace!("TLS Handshake success: {:?}", &host);
-                    Ok(io)
-                }
-                Err(e) => {
-                    trace!("TLS Handshake error: {:?}", e);
-                    Err(io::Error::new(io::ErrorKind::Other, format!("{}", e)).into())
+async fn handshake(
+    connector: TlsConnector,
+    verify: Verification,
+    io: Io,
+    host: String,
+) -> Result<Io<TlsFilter<Base>>, ConnectError> {
+    let started = Instant::now();
+    do_handshake(connector, verify, io, host.clone())
+        .await
+        .map_err(|err| {
+            err.with_context(&host, ConnectStage::Tls, Vec::new(), started.elapsed())
+        })
+}
+
+async fn do_handshake(
+    connector: TlsConnector,
+    verify: Verification,
+    io: Io,
+    host: String,
+) -> Result<Io<TlsFilter<Base>>, ConnectError> {
+    trace!("SSL Handshake start for: {:?}", host);
+
+    let host = ServerName::try_from(host.as_str())
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))?;
+    let connector = connector.server_name(host.clone());
+
+    match io.add_filter(connector).await {
+        Ok(io) => {
+            trace!("TLS Handshake success: {:?}", &host);
+            if verify.has_pins() {
+                let certs = PeerCertificates(
+                    io.query::<PeerCertChain>()
+                        .as_ref()
+                        .map(|chain| chain.0.iter().map(|cert| cert.0.clone()).collect())
+                        .unwrap_or_default(),
+                );
+                if !verify.check_pins(&certs) {
+                    trace!("TLS server public key is not pinned: {:?}", &host);
+                    return Err(ConnectError::PinMismatch);
                 }
             }
+            Ok(io)
+        }
+        Err(e) => {
+            trace!("TLS Handshake error: {:?}", e);
+            Err(io::Error::new(io::ErrorKind::Other, format!("{}", e)).into())
         }
     }
 }
@@ -86,7 +220,9 @@ impl<T> Clone for Connector<T> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            config: self.config.clone(),
             connector: self.connector.clone(),
+            verify: self.verify.clone(),
         }
     }
 }
@@ -1,16 +1,21 @@
 use std::task::{Context, Poll};
 use std::{collections::VecDeque, future::Future, io, net::SocketAddr, pin::Pin};
+use std::{mem, time::Instant};
 
 use crate::io::{types, Io};
 use crate::rt::tcp_connect_in;
 use crate::service::{Service, ServiceFactory};
+use crate::time::{Millis, Sleep};
 use crate::util::{Either, PoolId, PoolRef, Ready};
 
+use super::error::{ConnectAttempt, ConnectStage};
 use super::{Address, Connect, ConnectError, Resolver};
 
 pub struct Connector<T> {
     resolver: Resolver<T>,
     pool: PoolRef,
+    timeout: Millis,
+    addr_timeout: Millis,
 }
 
 impl<T> Connector<T> {
@@ -19,6 +24,8 @@ impl<T> Connector<T> {
         Connector {
             resolver: Resolver::new(),
             pool: PoolId::P0.pool_ref(),
+            timeout: Millis::ZERO,
+            addr_timeout: Millis::ZERO,
         }
     }
 
@@ -30,6 +37,35 @@ impl<T> Connector<T> {
         self.pool = id.pool_ref();
         self
     }
+
+    /// Use custom dns resolver.
+    pub fn resolver(mut self, resolver: Resolver<T>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Set connect timeout.
+    ///
+    /// Max time to connect to remote host including dns name resolution
+    /// and all connection attempts. To disable timeout set value to 0.
+    ///
+    /// By default connect timeout is disabled.
+    pub fn connect_timeout<U: Into<Millis>>(mut self, timeout: U) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Set per address connect timeout.
+    ///
+    /// Max time to wait for tcp handshake with one of resolved addresses.
+    /// If address does not respond within this time, connector tries
+    /// next resolved address. To disable timeout set value to 0.
+    ///
+    /// By default per address timeout is disabled.
+    pub fn per_address_timeout<U: Into<Millis>>(mut self, timeout: U) -> Self {
+        self.addr_timeout = timeout.into();
+        self
+    }
 }
 
 impl<T: Address> Connector<T> {
@@ -38,10 +74,7 @@ impl<T: Address> Connector<T> {
     where
         Connect<T>: From<U>,
     {
-        ConnectServiceResponse {
-            state: ConnectState::Resolve(self.resolver.call(message.into())),
-            pool: self.pool,
-        }
+        self.call(message.into())
     }
 }
 
@@ -56,6 +89,8 @@ impl<T> Clone for Connector<T> {
         Connector {
             resolver: self.resolver.clone(),
             pool: self.pool,
+            timeout: self.timeout,
+            addr_timeout: self.addr_timeout,
         }
     }
 }
@@ -85,7 +120,14 @@ impl<T: Address> Service<Connect<T>> for Connector<T> {
 
     #[inline]
     fn call(&self, req: Connect<T>) -> Self::Future {
-        ConnectServiceResponse::new(self.resolver.call(req))
+        ConnectServiceResponse {
+            host: req.host().to_string(),
+            started: Instant::now(),
+            state: ConnectState::Resolve(self.resolver.call(req)),
+            pool: self.pool,
+            timeout: self.timeout.map(Sleep::new),
+            addr_timeout: self.addr_timeout,
+        }
     }
 }
 
@@ -96,33 +138,67 @@ enum ConnectState<T: Address> {
 
 #[doc(hidden)]
 pub struct ConnectServiceResponse<T: Address> {
+    host: String,
+    started: Instant,
     state: ConnectState<T>,
     pool: PoolRef,
+    timeout: Option<Sleep>,
+    addr_timeout: Millis,
 }
 
 impl<T: Address> ConnectServiceResponse<T> {
-    pub(super) fn new(fut: <Resolver<T> as Service<Connect<T>>>::Future) -> Self {
+    pub(super) fn new(
+        host: String,
+        fut: <Resolver<T> as Service<Connect<T>>>::Future,
+    ) -> Self {
         Self {
+            host,
+            started: Instant::now(),
             state: ConnectState::Resolve(fut),
             pool: PoolId::P0.pool_ref(),
+            timeout: None,
+            addr_timeout: Millis::ZERO,
         }
     }
+
+    /// Add connect diagnostics to error
+    fn error(&mut self, err: ConnectError) -> ConnectError {
+        let (stage, attempts) = match self.state {
+            ConnectState::Resolve(_) => (ConnectStage::Resolve, Vec::new()),
+            ConnectState::Connect(ref mut fut) => {
+                (ConnectStage::Connect, mem::take(&mut fut.attempts))
+            }
+        };
+        err.with_context(&self.host, stage, attempts, self.started.elapsed())
+    }
 }
 
 impl<T: Address> Future for ConnectServiceResponse<T> {
     type Output = Result<Io, ConnectError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(ref timeout) = self.timeout {
+            if timeout.poll_elapsed(cx).is_ready() {
+                trace!("TCP connector - connect timeout");
+                return Poll::Ready(Err(self.error(ConnectError::Timeout)));
+            }
+        }
+
         match self.state {
-            ConnectState::Resolve(ref mut fut) => match Pin::new(fut).poll(cx)? {
+            ConnectState::Resolve(ref mut fut) => match Pin::new(fut).poll(cx) {
                 Poll::Pending => Poll::Pending,
-                Poll::Ready(address) => {
+                Poll::Ready(Err(err)) => Poll::Ready(Err(self.error(err))),
+                Poll::Ready(Ok(address)) => {
                     let port = address.port();
                     let Connect { req, addr, .. } = address;
 
                     if let Some(addr) = addr {
                         self.state = ConnectState::Connect(TcpConnectorResponse::new(
-                            req, port, addr, self.pool,
+                            req,
+                            port,
+                            addr,
+                            self.pool,
+                            self.addr_timeout,
                         ));
                         self.poll(cx)
                     } else if let Some(addr) = req.addr() {
@@ -131,15 +207,19 @@ impl<T: Address> Future for ConnectServiceResponse<T> {
                             addr.port(),
                             Either::Left(addr),
                             self.pool,
+                            self.addr_timeout,
                         ));
                         self.poll(cx)
                     } else {
                         error!("TCP connector: got unresolved address");
-                        Poll::Ready(Err(ConnectError::Unresolved))
+                        Poll::Ready(Err(self.error(ConnectError::Unresolved)))
                     }
                 }
             },
-            ConnectState::Connect(ref mut fut) => Pin::new(fut).poll(cx),
+            ConnectState::Connect(ref mut fut) => match Pin::new(fut).poll(cx) {
+                Poll::Ready(Err(err)) => Poll::Ready(Err(self.error(err))),
+                res => res,
+            },
         }
     }
 }
@@ -148,9 +228,13 @@ impl<T: Address> Future for ConnectServiceResponse<T> {
 struct TcpConnectorResponse<T> {
     req: Option<T>,
     port: u16,
+    addr: Option<(SocketAddr, Instant)>,
     addrs: Option<VecDeque<SocketAddr>>,
+    attempts: Vec<ConnectAttempt>,
     stream: Option<Pin<Box<dyn Future<Output = Result<Io, io::Error>>>>>,
     pool: PoolRef,
+    timeout: Millis,
+    delay: Option<Sleep>,
 }
 
 impl<T: Address> TcpConnectorResponse<T> {
@@ -159,6 +243,7 @@ impl<T: Address> TcpConnectorResponse<T> {
         port: u16,
         addr: Either<SocketAddr, VecDeque<SocketAddr>>,
         pool: PoolRef,
+        timeout: Millis,
     ) -> TcpConnectorResponse<T> {
         trace!(
             "TCP connector - connecting to {:?} port:{}",
@@ -169,28 +254,43 @@ impl<T: Address> TcpConnectorResponse<T> {
         match addr {
             Either::Left(addr) => TcpConnectorResponse {
                 req: Some(req),
+                addr: Some((addr, Instant::now())),
                 addrs: None,
+                attempts: Vec::new(),
                 stream: Some(Box::pin(tcp_connect_in(addr, pool))),
+                delay: timeout.map(Sleep::new),
                 pool,
                 port,
+                timeout,
             },
             Either::Right(addrs) => TcpConnectorResponse {
                 port,
                 pool,
+                timeout,
                 req: Some(req),
+                addr: None,
                 addrs: Some(addrs),
+                attempts: Vec::new(),
                 stream: None,
+                delay: None,
             },
         }
     }
 
-    fn can_continue(&self, err: &io::Error) -> bool {
+    fn can_continue(&mut self, err: &io::Error) -> bool {
         trace!(
             "TCP connector - failed to connect to {:?} port: {} err: {:?}",
             self.req.as_ref().unwrap().host(),
             self.port,
             err
         );
+        if let Some((addr, started)) = self.addr.take() {
+            self.attempts.push(ConnectAttempt {
+                addr,
+                error: io::Error::new(err.kind(), format!("{}", err)),
+                elapsed: started.elapsed(),
+            });
+        }
         !(self.addrs.is_none() || self.addrs.as_ref().unwrap().is_empty())
     }
 }
@@ -214,7 +314,21 @@ impl<T: Address> Future for TcpConnectorResponse<T> {
                         );
                         return Poll::Ready(Ok(sock));
                     }
-                    Poll::Pending => return Poll::Pending,
+                    Poll::Pending => {
+                        // check per address timeout
+                        match this.delay {
+                            Some(ref delay) if delay.poll_elapsed(cx).is_ready() => {
+                                let err = io::Error::new(
+                                    io::ErrorKind::TimedOut,
+                                    "Connect timeout",
+                                );
+                                if !this.can_continue(&err) {
+                                    return Poll::Ready(Err(ConnectError::Timeout));
+                                }
+                            }
+                            _ => return Poll::Pending,
+                        }
+                    }
                     Poll::Ready(Err(err)) => {
                         if !this.can_continue(&err) {
                             return Poll::Ready(Err(err.into()));
@@ -225,7 +339,14 @@ impl<T: Address> Future for TcpConnectorResponse<T> {
 
             // try to connect
             let addr = this.addrs.as_mut().unwrap().pop_front().unwrap();
+            this.addr = Some((addr, Instant::now()));
             this.stream = Some(Box::pin(tcp_connect_in(addr, this.pool)));
+            if this.timeout.non_zero() {
+                match this.delay {
+                    Some(ref delay) => delay.reset(this.timeout),
+                    None => this.delay = Some(Sleep::new(this.timeout)),
+                }
+            }
         }
     }
 }
@@ -263,4 +384,38 @@ mod tests {
         let result = crate::connect::connect(msg).await;
         assert!(result.is_ok());
     }
+
+    #[crate::rt_test]
+    async fn test_connect_error_context() {
+        let err = Connector::default().connect("").await.unwrap_err();
+        assert_eq!(err.context().unwrap().stage(), ConnectStage::Resolve);
+
+        let msg = Connect::new("localhost:1".to_string()).set_addrs(vec![
+            "127.0.0.1:1".parse().unwrap(),
+            "127.0.0.1:2".parse().unwrap(),
+        ]);
+        let err = Connector::default().call(msg).await.unwrap_err();
+        assert!(matches!(err, ConnectError::Io(_)));
+        let ctx = err.context().unwrap();
+        assert_eq!(ctx.host(), "localhost:1");
+        assert_eq!(ctx.stage(), ConnectStage::Connect);
+        assert_eq!(ctx.attempts().len(), 2);
+        assert_eq!(ctx.attempts()[1].addr, "127.0.0.1:2".parse().unwrap());
+    }
+
+    #[crate::rt_test]
+    async fn test_connect_timeout() {
+        let server = crate::server::test_server(|| {
+            crate::service::fn_service(|_| async { Ok::<_, ()>(()) })
+        });
+
+        // stalled address, connector must fail over to next address
+        let srv = Connector::default()
+            .connect_timeout(Millis(5_000))
+            .per_address_timeout(Millis(100));
+        let msg = Connect::new(format!("{}", server.addr()))
+            .set_addrs(vec!["10.255.255.1:80".parse().unwrap(), server.addr()]);
+        let result = srv.call(msg).await;
+        assert!(result.is_ok());
+    }
 }
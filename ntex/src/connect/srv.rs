@@ -0,0 +1,128 @@
+//! SRV records lookup
+use std::{cell::RefCell, io, net::SocketAddr};
+
+use nanorand::{Rng, WyRand};
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::Resolver;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SrvRecord {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+}
+
+thread_local! {
+    static RESOLVER: RefCell<Option<Resolver>> = RefCell::new(None);
+}
+
+/// Resolve `_service._proto.name` SRV record to ordered list of addresses.
+///
+/// Nameservers, retries and tcp fallback for truncated responses are
+/// handled by system configured resolver. This is blocking call.
+pub(super) fn lookup(name: &str) -> io::Result<Vec<SocketAddr>> {
+    RESOLVER.with(|cell| {
+        let mut resolver = cell.borrow_mut();
+        if resolver.is_none() {
+            *resolver = Some(Resolver::from_system_conf()?);
+        }
+        lookup_with(resolver.as_ref().unwrap(), name)
+    })
+}
+
+fn lookup_with(resolver: &Resolver, name: &str) -> io::Result<Vec<SocketAddr>> {
+    let records: Vec<_> = match resolver.srv_lookup(name) {
+        Ok(lookup) => lookup
+            .iter()
+            .map(|srv| SrvRecord {
+                priority: srv.priority(),
+                weight: srv.weight(),
+                port: srv.port(),
+                target: srv.target().to_utf8(),
+            })
+            .collect(),
+        Err(e) if no_records(&e) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut addrs = Vec::new();
+    for rec in order(records, &mut WyRand::new()) {
+        match resolver.lookup_ip(rec.target.as_str()) {
+            Ok(ips) => addrs.extend(ips.iter().map(|ip| SocketAddr::new(ip, rec.port))),
+            Err(e) => {
+                trace!(
+                    "SRV resolver: cannot resolve target {:?}: {}",
+                    rec.target,
+                    e
+                )
+            }
+        }
+    }
+    Ok(addrs)
+}
+
+fn no_records(err: &ResolveError) -> bool {
+    matches!(err.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+/// Order records by priority and weight as described in RFC 2782
+fn order(mut records: Vec<SrvRecord>, rng: &mut WyRand) -> Vec<SrvRecord> {
+    records.sort_by_key(|rec| rec.priority);
+
+    let mut result = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let count = records
+            .iter()
+            .take_while(|rec| rec.priority == priority)
+            .count();
+        let mut group: Vec<_> = records.drain(..count).collect();
+
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|rec| rec.weight as u32).sum();
+            let selected: u32 = rng.generate_range(0..=total);
+            let mut sum = 0;
+            let idx = group
+                .iter()
+                .position(|rec| {
+                    sum += rec.weight as u32;
+                    sum >= selected
+                })
+                .unwrap_or(0);
+            result.push(group.remove(idx));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(priority: u16, weight: u16, port: u16, target: &str) -> SrvRecord {
+        SrvRecord {
+            priority,
+            weight,
+            port,
+            target: target.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        let mut rng = WyRand::new();
+        let records = vec![
+            record(20, 0, 80, "c"),
+            record(10, 0, 80, "b"),
+            record(10, 100, 80, "a"),
+            record(30, 0, 80, "d"),
+        ];
+        let result = order(records, &mut rng);
+        let targets: Vec<_> = result.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(targets.len(), 4);
+        assert!(targets[..2].contains(&"a"));
+        assert!(targets[..2].contains(&"b"));
+        assert_eq!(&targets[2..], &["c", "d"]);
+    }
+}
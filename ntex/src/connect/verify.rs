@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use crate::tls::types::PeerCertificates;
+
+type VerifyFn = Arc<dyn Fn(&str, &PeerCertificates) -> bool + Send + Sync>;
+
+/// Server certificates verification settings of tls connector
+#[derive(Clone, Default)]
+pub(super) struct Verification {
+    pins: Vec<[u8; 32]>,
+    verifier: Option<VerifyFn>,
+    accept_invalid: bool,
+}
+
+impl Verification {
+    pub(super) fn pin(&mut self, hash: [u8; 32]) {
+        self.pins.push(hash);
+    }
+
+    pub(super) fn set_verifier(&mut self, f: VerifyFn) {
+        self.verifier = Some(f);
+    }
+
+    pub(super) fn accept_invalid(&mut self) {
+        self.accept_invalid = true;
+    }
+
+    pub(super) fn has_pins(&self) -> bool {
+        !self.pins.is_empty()
+    }
+
+    /// Default chain and host name verification is replaced
+    pub(super) fn is_custom(&self) -> bool {
+        self.verifier.is_some() || self.accept_invalid
+    }
+
+    /// Verify server certificates chain with custom verifier
+    pub(super) fn verify(&self, host: &str, certs: &PeerCertificates) -> bool {
+        if let Some(ref f) = self.verifier {
+            f(host, certs)
+        } else {
+            self.accept_invalid
+        }
+    }
+
+    /// Check if any certificate in chain matches any pinned public key
+    pub(super) fn check_pins(&self, certs: &PeerCertificates) -> bool {
+        self.pins.is_empty()
+            || certs
+                .0
+                .iter()
+                .filter_map(|cert| spki_sha256(cert))
+                .any(|hash| self.pins.contains(&hash))
+    }
+}
+
+/// SHA-256 hash of certificate's public key.
+///
+/// Hash is calculated over DER encoded `SubjectPublicKeyInfo`, the same
+/// value is used for HPKP pins. Returns `None` if certificate is malformed.
+pub fn spki_sha256(cert: &[u8]) -> Option<[u8; 32]> {
+    let spki = spki(cert)?;
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&Sha256::digest(spki));
+    Some(hash)
+}
+
+/// Extract DER encoded `SubjectPublicKeyInfo` from DER encoded certificate
+fn spki(cert: &[u8]) -> Option<&[u8]> {
+    // Certificate ::= SEQUENCE { tbsCertificate, signatureAlgorithm, signature }
+    let (hl, _) = der_header(cert)?;
+    let tbs = cert.get(hl..)?;
+    let (hl, len) = der_header(tbs)?;
+    let mut rest = tbs.get(hl..hl + len)?;
+
+    // skip version, serialNumber, signature, issuer, validity and subject
+    let skip = if rest.first() == Some(&0xa0) { 6 } else { 5 };
+    for _ in 0..skip {
+        let (hl, len) = der_header(rest)?;
+        rest = rest.get(hl + len..)?;
+    }
+    let (hl, len) = der_header(rest)?;
+    rest.get(..hl + len)
+}
+
+/// Parse DER element header, returns header and content length
+fn der_header(data: &[u8]) -> Option<(usize, usize)> {
+    let first = *data.get(1)? as usize;
+    if first < 0x80 {
+        Some((2, first))
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let mut len = 0;
+        for b in data.get(2..2 + n)? {
+            len = (len << 8) | *b as usize;
+        }
+        Some((2 + n, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tls_openssl::{sha::sha256, x509::X509};
+
+    use super::*;
+
+    #[test]
+    fn test_spki_sha256() {
+        let cert = X509::from_pem(include_bytes!("../../tests/cert.pem")).unwrap();
+        let der = cert.to_der().unwrap();
+        let hash = sha256(&cert.public_key().unwrap().public_key_to_der().unwrap());
+        assert_eq!(spki_sha256(&der), Some(hash));
+        assert_eq!(spki_sha256(&der[..20]), None);
+        assert_eq!(spki_sha256(b""), None);
+
+        let mut verify = Verification::default();
+        let certs = PeerCertificates(vec![der]);
+        assert!(verify.check_pins(&certs));
+        verify.pin([0; 32]);
+        assert!(!verify.check_pins(&certs));
+        verify.pin(hash);
+        assert!(verify.check_pins(&certs));
+        assert!(!verify.check_pins(&PeerCertificates(Vec::new())));
+    }
+}
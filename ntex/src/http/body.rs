@@ -1,8 +1,15 @@
+use std::io::{Read, Seek, SeekFrom};
 use std::{
-    error::Error, fmt, marker::PhantomData, mem, pin::Pin, task::Context, task::Poll,
+    cmp, error::Error, fmt, fs, future::Future, io, marker::PhantomData, mem, ops,
+    pin::Pin, task::Context, task::Poll,
 };
 
-use crate::util::{Bytes, BytesMut, Stream};
+use tok_io::io::{AsyncRead, ReadBuf};
+
+use crate::rt::{spawn_blocking, JoinHandle};
+use crate::util::{ready, BufMut, Bytes, BytesMut, Stream};
+
+const DEFAULT_CHUNK_SIZE: usize = 32_768;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 /// Body size hint
@@ -27,6 +34,13 @@ pub trait MessageBody: 'static {
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Bytes, Box<dyn Error>>>>;
+
+    #[doc(hidden)]
+    #[cfg(target_os = "linux")]
+    /// Get file and byte range if body is a file, used for `sendfile` support
+    fn as_file(&mut self) -> Option<(&fs::File, &mut ops::Range<u64>)> {
+        None
+    }
 }
 
 impl MessageBody for () {
@@ -53,6 +67,11 @@ impl<T: MessageBody> MessageBody for Box<T> {
     ) -> Poll<Option<Result<Bytes, Box<dyn Error>>>> {
         self.as_mut().poll_next_chunk(cx)
     }
+
+    #[cfg(target_os = "linux")]
+    fn as_file(&mut self) -> Option<(&fs::File, &mut ops::Range<u64>)> {
+        self.as_mut().as_file()
+    }
 }
 
 pub enum ResponseBody<B> {
@@ -112,6 +131,14 @@ impl<B: MessageBody> MessageBody for ResponseBody<B> {
             ResponseBody::Other(ref mut body) => body.poll_next_chunk(cx),
         }
     }
+
+    #[cfg(target_os = "linux")]
+    fn as_file(&mut self) -> Option<(&fs::File, &mut ops::Range<u64>)> {
+        match self {
+            ResponseBody::Body(ref mut body) => body.as_file(),
+            ResponseBody::Other(ref mut body) => body.as_file(),
+        }
+    }
 }
 
 impl<B: MessageBody + Unpin> Stream for ResponseBody<B> {
@@ -147,6 +174,27 @@ impl Body {
     pub fn from_message<B: MessageBody + 'static>(body: B) -> Body {
         Body::Message(Box::new(body))
     }
+
+    /// Create body from the whole file.
+    ///
+    /// Http/1 dispatcher sends file with `sendfile` syscall on supported
+    /// platforms if connection is not encrypted, otherwise file is read
+    /// chunk by chunk on blocking thread pool.
+    pub fn from_file(file: fs::File) -> io::Result<Body> {
+        let len = file.metadata()?.len();
+        Ok(Body::from_file_range(file, 0..len))
+    }
+
+    /// Create body from byte range of the file.
+    ///
+    /// See [`Body::from_file`] for details.
+    pub fn from_file_range(file: fs::File, range: ops::Range<u64>) -> Body {
+        Body::Message(Box::new(FileBody {
+            range,
+            file: Some(file),
+            fut: None,
+        }))
+    }
 }
 
 impl MessageBody for Body {
@@ -177,6 +225,88 @@ impl MessageBody for Body {
             Body::Message(ref mut body) => body.poll_next_chunk(cx),
         }
     }
+
+    #[cfg(target_os = "linux")]
+    fn as_file(&mut self) -> Option<(&fs::File, &mut ops::Range<u64>)> {
+        match self {
+            Body::Message(ref mut body) => body.as_file(),
+            _ => None,
+        }
+    }
+}
+
+/// Byte range of the file
+struct FileBody {
+    range: ops::Range<u64>,
+    // file is moved to blocking thread pool while chunk is being read
+    file: Option<fs::File>,
+    fut: Option<JoinHandle<(fs::File, io::Result<Bytes>)>>,
+}
+
+impl MessageBody for FileBody {
+    fn size(&self) -> BodySize {
+        BodySize::Sized(self.range.end.saturating_sub(self.range.start))
+    }
+
+    fn poll_next_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Box<dyn Error>>>> {
+        if let Some(ref mut fut) = self.fut {
+            let result = ready!(Pin::new(fut).poll(cx));
+            self.fut = None;
+
+            return Poll::Ready(Some(match result {
+                Ok((file, Ok(chunk))) => {
+                    self.file = Some(file);
+                    self.range.start += chunk.len() as u64;
+                    Ok(chunk)
+                }
+                Ok((_, Err(e))) => Err(Box::new(e)),
+                Err(_) => Err(Box::new(io::Error::new(io::ErrorKind::Other, "Canceled"))),
+            }));
+        }
+
+        if self.range.start >= self.range.end {
+            return Poll::Ready(None);
+        }
+        if let Some(mut file) = self.file.take() {
+            let range = self.range.clone();
+            self.fut = Some(spawn_blocking(move || {
+                let result = read_file_chunk(&mut file, range);
+                (file, result)
+            }));
+            self.poll_next_chunk(cx)
+        } else {
+            // previous read failed
+            Poll::Ready(None)
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn as_file(&mut self) -> Option<(&fs::File, &mut ops::Range<u64>)> {
+        match self.file {
+            Some(ref file) => Some((file, &mut self.range)),
+            None => None,
+        }
+    }
+}
+
+/// Read next chunk of the file range
+fn read_file_chunk(file: &mut fs::File, range: ops::Range<u64>) -> io::Result<Bytes> {
+    let size = cmp::min(range.end - range.start, DEFAULT_CHUNK_SIZE as u64) as usize;
+    let mut buf = vec![0; size];
+
+    file.seek(SeekFrom::Start(range.start))?;
+    let n = file.read(&mut buf)?;
+    if n == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "file is shorter than expected",
+        ));
+    }
+    buf.truncate(n);
+    Ok(Bytes::from(buf))
 }
 
 impl PartialEq for Body {
@@ -268,6 +398,15 @@ where
     }
 }
 
+impl<R> From<BodyFromRead<R>> for Body
+where
+    R: AsyncRead + Unpin + 'static,
+{
+    fn from(s: BodyFromRead<R>) -> Body {
+        Body::from_message(s)
+    }
+}
+
 impl MessageBody for Bytes {
     fn size(&self) -> BodySize {
         BodySize::Sized(self.len() as u64)
@@ -503,6 +642,115 @@ where
     }
 }
 
+/// Type represent streaming body from `AsyncRead` source.
+///
+/// Data is read from the source only when dispatcher is ready to send
+/// next chunk, so payload is not buffered. If length is known, response
+/// contains `content-length` header, otherwise appropriate transfer encoding
+/// is used.
+pub struct BodyFromRead<R> {
+    reader: R,
+    buf: BytesMut,
+    chunk_size: usize,
+    length: Option<u64>,
+    remaining: Option<u64>,
+}
+
+impl<R> BodyFromRead<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Create streaming body from `AsyncRead` source.
+    ///
+    /// By default chunk size is set to 32Kb.
+    pub fn new(reader: R) -> Self {
+        BodyFromRead {
+            reader,
+            buf: BytesMut::new(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            length: None,
+            remaining: None,
+        }
+    }
+
+    /// Set max size of the chunk read from the source.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = cmp::max(size, 1);
+        self
+    }
+
+    /// Set body length.
+    ///
+    /// Exactly `len` bytes are read from the source, if source
+    /// ends earlier body fails with `UnexpectedEof` error.
+    pub fn length(mut self, len: u64) -> Self {
+        self.length = Some(len);
+        self.remaining = Some(len);
+        self
+    }
+}
+
+impl<R> MessageBody for BodyFromRead<R>
+where
+    R: AsyncRead + Unpin + 'static,
+{
+    fn size(&self) -> BodySize {
+        match self.length {
+            Some(len) => BodySize::Sized(len),
+            None => BodySize::Stream,
+        }
+    }
+
+    fn poll_next_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Box<dyn Error>>>> {
+        let size = match self.remaining {
+            Some(0) => return Poll::Ready(None),
+            Some(remaining) => cmp::min(remaining, self.chunk_size as u64) as usize,
+            None => self.chunk_size,
+        };
+        self.buf.reserve(size);
+
+        let n = {
+            let dst = unsafe {
+                &mut *(self.buf.chunk_mut() as *mut _ as *mut [mem::MaybeUninit<u8>])
+            };
+            let mut buf = ReadBuf::uninit(&mut dst[..size]);
+            let ptr = buf.filled().as_ptr();
+            if let Err(e) = ready!(Pin::new(&mut self.reader).poll_read(cx, &mut buf)) {
+                return Poll::Ready(Some(Err(e.into())));
+            }
+
+            // Ensure the pointer does not change from under us
+            assert_eq!(ptr, buf.filled().as_ptr());
+            buf.filled().len()
+        };
+
+        if n == 0 {
+            return if self.remaining.is_some() {
+                Poll::Ready(Some(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Body source ended before expected length",
+                )
+                .into())))
+            } else {
+                Poll::Ready(None)
+            };
+        }
+
+        // Safety: This is guaranteed to be the number of initialized (and read)
+        // bytes due to the invariants provided by `ReadBuf::filled`.
+        unsafe {
+            self.buf.advance_mut(n);
+        }
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining -= n as u64;
+        }
+        Poll::Ready(Some(Ok(self.buf.split().freeze())))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use futures_util::stream;
@@ -710,6 +958,72 @@ mod tests {
         );
     }
 
+    #[crate::rt_test]
+    async fn test_body_file() {
+        let path = std::env::temp_dir().join(format!("ntex-body-{}", std::process::id()));
+        fs::write(&path, "0123456789").unwrap();
+
+        let mut body = Body::from_file_range(fs::File::open(&path).unwrap(), 2..6);
+        assert_eq!(body.size(), BodySize::Sized(4));
+        #[cfg(target_os = "linux")]
+        assert_eq!(body.as_file().map(|(_, range)| range.clone()), Some(2..6));
+        assert!(body != Body::Empty);
+        assert_eq!(
+            poll_fn(|cx| body.poll_next_chunk(cx)).await.unwrap().ok(),
+            Some(Bytes::from("2345")),
+        );
+        assert!(poll_fn(|cx| body.poll_next_chunk(cx)).await.is_none());
+
+        let body = Body::from_file(fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(body.size(), BodySize::Sized(10));
+
+        // file is shorter than range
+        let mut body = Body::from_file_range(fs::File::open(&path).unwrap(), 8..12);
+        assert_eq!(
+            poll_fn(|cx| body.poll_next_chunk(cx)).await.unwrap().ok(),
+            Some(Bytes::from("89")),
+        );
+        assert!(poll_fn(|cx| body.poll_next_chunk(cx))
+            .await
+            .unwrap()
+            .is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[crate::rt_test]
+    async fn body_from_read() {
+        let mut body = BodyFromRead::new(&b"12345"[..]).chunk_size(2);
+        assert_eq!(body.size(), BodySize::Stream);
+        for chunk in ["12", "34", "5"] {
+            assert_eq!(
+                poll_fn(|cx| body.poll_next_chunk(cx)).await.unwrap().ok(),
+                Some(Bytes::from(chunk)),
+            );
+        }
+        assert!(poll_fn(|cx| body.poll_next_chunk(cx)).await.is_none());
+
+        // read exactly `length` bytes
+        let mut body = BodyFromRead::new(&b"12345"[..]).length(3);
+        assert_eq!(body.size(), BodySize::Sized(3));
+        assert_eq!(
+            poll_fn(|cx| body.poll_next_chunk(cx)).await.unwrap().ok(),
+            Some(Bytes::from("123")),
+        );
+        assert!(poll_fn(|cx| body.poll_next_chunk(cx)).await.is_none());
+
+        // source is shorter than length
+        let mut body = BodyFromRead::new(&b"12"[..]).length(3);
+        assert_eq!(
+            poll_fn(|cx| body.poll_next_chunk(cx)).await.unwrap().ok(),
+            Some(Bytes::from("12")),
+        );
+        assert!(poll_fn(|cx| body.poll_next_chunk(cx))
+            .await
+            .unwrap()
+            .is_err());
+    }
+
     #[crate::rt_test]
     async fn sized_skips_empty_chunks() {
         let mut body = SizedStream::new(
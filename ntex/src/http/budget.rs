@@ -0,0 +1,201 @@
+//! Memory budget for buffered request data
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{cell::Cell, fmt, sync::Arc};
+
+/// Global memory budget.
+///
+/// Budget is shared between all workers and connections that are configured
+/// with it. Request payload buffers and websocket aggregation buffers register
+/// their allocations against the budget. If budget is exhausted, connections
+/// stop reading request payloads (backpressure) and new requests are rejected
+/// with `503 Service Unavailable` response.
+///
+/// Io read and write buffers are not accounted, their size is controlled
+/// by memory pools (`PoolId::set_pool_size()`).
+///
+/// ```rust
+/// use ntex::http::MemoryBudget;
+///
+/// // 256Mb for all workers
+/// let budget = MemoryBudget::new(256 * 1024 * 1024);
+/// ```
+#[derive(Clone)]
+pub struct MemoryBudget(Arc<Inner>);
+
+struct Inner {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Create new budget with size limit in bytes.
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget(Arc::new(Inner {
+            limit,
+            used: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Budget size limit.
+    pub fn limit(&self) -> usize {
+        self.0.limit
+    }
+
+    /// Number of bytes registered against budget.
+    pub fn used(&self) -> usize {
+        self.0.used.load(Ordering::Relaxed)
+    }
+
+    /// Number of available bytes.
+    pub fn available(&self) -> usize {
+        self.0.limit.saturating_sub(self.used())
+    }
+
+    /// Check if budget is exhausted.
+    pub fn is_exhausted(&self) -> bool {
+        self.used() >= self.0.limit
+    }
+
+    /// Register allocation, returns `false` if budget is exceeded.
+    ///
+    /// Allocation is registered even if budget is exceeded,
+    /// data is already in memory.
+    fn charge(&self, size: usize) -> bool {
+        self.0.used.fetch_add(size, Ordering::Relaxed) + size < self.0.limit
+    }
+
+    fn release(&self, size: usize) {
+        self.0.used.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("limit", &self.0.limit)
+            .field("used", &self.used())
+            .finish()
+    }
+}
+
+/// Allocations of one buffer, registered against local limit
+/// and optional global budget.
+///
+/// Registered allocations are released on drop.
+pub(crate) struct Reservation {
+    limit: usize,
+    used: Cell<usize>,
+    budget: Option<MemoryBudget>,
+}
+
+impl Reservation {
+    pub(crate) fn new(limit: usize, budget: Option<MemoryBudget>) -> Self {
+        Reservation {
+            limit,
+            budget,
+            used: Cell::new(0),
+        }
+    }
+
+    /// Number of registered bytes.
+    pub(crate) fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    /// Register allocation, returns `false` if local limit or
+    /// global budget is exceeded.
+    pub(crate) fn charge(&self, size: usize) -> bool {
+        let used = self.used.get() + size;
+        self.used.set(used);
+        let global = self.budget.as_ref().map(|b| b.charge(size)).unwrap_or(true);
+        global && used < self.limit
+    }
+
+    /// Release registered allocation, returns `true` if reservation
+    /// is within limits.
+    pub(crate) fn release(&self, size: usize) -> bool {
+        let size = std::cmp::min(size, self.used.get());
+        self.used.set(self.used.get() - size);
+        if let Some(ref budget) = self.budget {
+            budget.release(size);
+        }
+        self.has_capacity()
+    }
+
+    /// Release all registered allocations.
+    pub(crate) fn clear(&self) {
+        self.release(self.used.get());
+    }
+
+    /// Check if reservation is within limits.
+    pub(crate) fn has_capacity(&self) -> bool {
+        self.used.get() < self.limit
+            && self
+                .budget
+                .as_ref()
+                .map(|b| !b.is_exhausted())
+                .unwrap_or(true)
+    }
+}
+
+impl Clone for Reservation {
+    fn clone(&self) -> Self {
+        Reservation::new(self.limit, self.budget.clone())
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.clear()
+    }
+}
+
+impl fmt::Debug for Reservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reservation")
+            .field("limit", &self.limit)
+            .field("used", &self.used())
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let budget = MemoryBudget::new(100);
+        assert!(format!("{:?}", budget).contains("MemoryBudget"));
+
+        let r1 = Reservation::new(60, Some(budget.clone()));
+        let r2 = r1.clone();
+        assert!(r1.charge(50));
+        assert!(r1.has_capacity());
+        assert_eq!(budget.used(), 50);
+        assert_eq!(budget.available(), 50);
+
+        // local limit
+        assert!(!r1.charge(20));
+        assert!(!r1.has_capacity());
+        assert!(r1.release(20));
+
+        // global budget
+        assert!(r2.charge(40));
+        assert!(!budget.is_exhausted());
+        assert!(!r2.charge(10));
+        assert!(budget.is_exhausted());
+        assert!(!r1.has_capacity());
+        assert_eq!(budget.used(), 100);
+
+        drop(r2);
+        assert_eq!(budget.used(), 50);
+        assert!(r1.has_capacity());
+
+        r1.clear();
+        assert_eq!(r1.used(), 0);
+        assert_eq!(budget.used(), 0);
+        assert_eq!(budget.limit(), 100);
+    }
+}
@@ -1,13 +1,17 @@
-use std::{error::Error, fmt, marker::PhantomData};
+use std::{error::Error, fmt, marker::PhantomData, rc::Rc};
 
 use crate::http::body::MessageBody;
-use crate::http::config::{KeepAlive, OnRequest, ServiceConfig};
+use crate::http::config::{Data, DataFactory, KeepAlive, OnConnect, OnRequest};
+use crate::http::config::{ServiceConfig, Timeouts, DEFAULT_CONN_MEMORY_LIMIT};
 use crate::http::error::ResponseError;
-use crate::http::h1::{Codec, ExpectHandler, H1Service, UpgradeHandler};
+use crate::http::h1::{
+    Codec, ExpectHandler, H1Service, HeadLimits, HeaderCase, UpgradeHandler,
+};
 use crate::http::h2::H2Service;
 use crate::http::request::Request;
 use crate::http::response::Response;
 use crate::http::service::HttpService;
+use crate::http::MemoryBudget;
 use crate::io::{Filter, Io, IoRef};
 use crate::service::{boxed, IntoService, IntoServiceFactory, Service, ServiceFactory};
 use crate::time::{Millis, Seconds};
@@ -21,9 +25,18 @@ pub struct HttpServiceBuilder<F, S, X = ExpectHandler, U = UpgradeHandler<F>> {
     client_timeout: Millis,
     client_disconnect: Seconds,
     handshake_timeout: Millis,
+    header_case: HeaderCase,
+    head_limits: HeadLimits,
+    strict_parsing: bool,
+    catch_panics: bool,
+    timeouts: Timeouts,
+    memory_budget: Option<MemoryBudget>,
+    conn_memory_limit: usize,
+    message_pool: Option<usize>,
     expect: X,
     upgrade: Option<U>,
     on_request: Option<OnRequest>,
+    on_connect: Option<OnConnect>,
     _t: PhantomData<(F, S)>,
 }
 
@@ -35,9 +48,18 @@ impl<F, S> HttpServiceBuilder<F, S, ExpectHandler, UpgradeHandler<F>> {
             client_timeout: Millis::from_secs(3),
             client_disconnect: Seconds(3),
             handshake_timeout: Millis::from_secs(5),
+            header_case: HeaderCase::Lower,
+            head_limits: HeadLimits::default(),
+            strict_parsing: false,
+            catch_panics: true,
+            timeouts: Timeouts::default(),
+            memory_budget: None,
+            conn_memory_limit: DEFAULT_CONN_MEMORY_LIMIT,
+            message_pool: None,
             expect: ExpectHandler,
             upgrade: None,
             on_request: None,
+            on_connect: None,
             _t: PhantomData,
         }
     }
@@ -78,6 +100,55 @@ where
         self
     }
 
+    /// Set request head read timeout.
+    ///
+    /// Defines a timeout for reading request head after the first byte of
+    /// the request is received. If a client does not transmit the entire set
+    /// of headers within this time, the request is terminated with
+    /// `408 Request Timeout` response. Applies to every request on the connection.
+    ///
+    /// By default timeout is disabled.
+    pub fn request_head_timeout(mut self, timeout: Seconds) -> Self {
+        self.timeouts.request_head = timeout.into();
+        self
+    }
+
+    /// Set request body read timeout.
+    ///
+    /// Defines a timeout for reading request body. If a client does not transmit
+    /// the entire body within this time, request payload is terminated with error,
+    /// and connection is closed. If response is not sent yet, `408 Request Timeout`
+    /// response is sent.
+    ///
+    /// By default timeout is disabled.
+    pub fn request_body_timeout(mut self, timeout: Seconds) -> Self {
+        self.timeouts.request_body = timeout.into();
+        self
+    }
+
+    /// Set response write timeout.
+    ///
+    /// Defines how long response writing could be blocked by a client that
+    /// does not read data. Connection (or http/2 stream) is closed after timeout.
+    ///
+    /// By default timeout is disabled.
+    pub fn response_write_timeout(mut self, timeout: Seconds) -> Self {
+        self.timeouts.response_write = timeout.into();
+        self
+    }
+
+    /// Set idle connection timeout.
+    ///
+    /// Connection without in-flight requests is closed after timeout.
+    /// For http/1 connections it overrides keep-alive timeout.
+    ///
+    /// By default keep-alive timeout is used for http/1 connections,
+    /// http/2 connections are not closed.
+    pub fn idle_connection_timeout(mut self, timeout: Seconds) -> Self {
+        self.timeouts.idle_connection = timeout.into();
+        self
+    }
+
     /// Set server connection disconnect timeout in seconds.
     ///
     /// Defines a timeout for disconnect connection. If a disconnect procedure does not complete
@@ -102,6 +173,105 @@ where
         self
     }
 
+    /// Set header names casing mode for http/1 responses.
+    ///
+    /// By default header names are encoded in lowercase. Original casing
+    /// could be provided with `h1::HeaderCaseMap` stored in response extensions.
+    pub fn h1_header_case(mut self, case: HeaderCase) -> Self {
+        self.header_case = case;
+        self
+    }
+
+    /// Set max number of request headers.
+    ///
+    /// Requests with more headers are rejected with
+    /// `431 Request Header Fields Too Large` response.
+    /// By default max number of headers is 96.
+    pub fn max_header_count(mut self, num: usize) -> Self {
+        self.head_limits.max_headers = num;
+        self
+    }
+
+    /// Set max size of request head, request line and headers.
+    ///
+    /// Requests with larger head are rejected with
+    /// `431 Request Header Fields Too Large` response.
+    /// By default max size is 32Kb.
+    pub fn max_header_size(mut self, size: usize) -> Self {
+        self.head_limits.max_head_size = size;
+        self
+    }
+
+    /// Set max length of request uri.
+    ///
+    /// Requests with longer uri are rejected with `414 URI Too Long` response.
+    /// By default max length is 32Kb.
+    pub fn max_uri_length(mut self, len: usize) -> Self {
+        self.head_limits.max_uri_length = len;
+        self
+    }
+
+    /// Enable strict http/1 request parsing mode.
+    ///
+    /// In strict mode requests with both `Content-Length` and `Transfer-Encoding`
+    /// headers, multiple `Content-Length` headers, obs-fold header continuation
+    /// lines or line endings other than CRLF are rejected with
+    /// `400 Bad Request` response.
+    ///
+    /// By default strict mode is disabled.
+    pub fn strict_parsing(mut self, strict: bool) -> Self {
+        self.strict_parsing = strict;
+        self
+    }
+
+    /// Catch panics of http/1 service calls.
+    ///
+    /// Panic is converted to `500 Internal Server Error` response and
+    /// connection is closed after response is sent. If disabled, panic
+    /// tears down connection's task.
+    ///
+    /// By default panics are caught.
+    pub fn catch_panics(mut self, enabled: bool) -> Self {
+        self.catch_panics = enabled;
+        self
+    }
+
+    /// Set global memory budget.
+    ///
+    /// Buffered request payloads of all connections are registered against
+    /// the budget. Budget could be shared between workers. If budget is exhausted,
+    /// connections stop reading request payloads and new requests are rejected
+    /// with `503 Service Unavailable` response.
+    ///
+    /// By default budget is not set.
+    pub fn memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Set max size of buffered request data per connection.
+    ///
+    /// If connection buffers more data, reading from the peer is
+    /// paused until application consumes buffered data.
+    ///
+    /// By default limit is 32Kb.
+    pub fn connection_memory_limit(mut self, size: usize) -> Self {
+        self.conn_memory_limit = size;
+        self
+    }
+
+    /// Set capacity of request and response message pools.
+    ///
+    /// Pools are thread-local, capacity is applied to worker threads
+    /// of the server. Setting capacity to 0 disables pooling for the server,
+    /// released messages are deallocated.
+    ///
+    /// By default capacity is not changed, default pools capacity is 128.
+    pub fn message_pool_capacity(mut self, capacity: usize) -> Self {
+        self.message_pool = Some(capacity);
+        self
+    }
+
     /// Provide service for `EXPECT: 100-Continue` support.
     ///
     /// Service get called with request that contains `EXPECT` header.
@@ -118,9 +288,18 @@ where
             client_timeout: self.client_timeout,
             client_disconnect: self.client_disconnect,
             handshake_timeout: self.handshake_timeout,
+            header_case: self.header_case,
+            head_limits: self.head_limits,
+            strict_parsing: self.strict_parsing,
+            catch_panics: self.catch_panics,
+            timeouts: self.timeouts,
+            memory_budget: self.memory_budget,
+            conn_memory_limit: self.conn_memory_limit,
+            message_pool: self.message_pool,
             expect: expect.into_factory(),
             upgrade: self.upgrade,
             on_request: self.on_request,
+            on_connect: self.on_connect,
             _t: PhantomData,
         }
     }
@@ -141,9 +320,18 @@ where
             client_timeout: self.client_timeout,
             client_disconnect: self.client_disconnect,
             handshake_timeout: self.handshake_timeout,
+            header_case: self.header_case,
+            head_limits: self.head_limits,
+            strict_parsing: self.strict_parsing,
+            catch_panics: self.catch_panics,
+            timeouts: self.timeouts,
+            memory_budget: self.memory_budget,
+            conn_memory_limit: self.conn_memory_limit,
+            message_pool: self.message_pool,
             expect: self.expect,
             upgrade: Some(upgrade.into_factory()),
             on_request: self.on_request,
+            on_connect: self.on_connect,
             _t: PhantomData,
         }
     }
@@ -160,6 +348,22 @@ where
         self
     }
 
+    /// Set connection data callback.
+    ///
+    /// It get called once per connection, returned value is available
+    /// in request extensions for every request on this connection.
+    /// Use `PeerInfo::from_io` to capture socket level connection information.
+    pub fn on_connect<FC, T>(mut self, f: FC) -> Self
+    where
+        FC: Fn(&IoRef) -> T + 'static,
+        T: Clone + 'static,
+    {
+        self.on_connect = Some(Rc::new(move |io: &IoRef| {
+            Box::new(Data(f(io))) as Box<dyn DataFactory>
+        }));
+        self
+    }
+
     /// Finish service configuration and create *http service* for HTTP/1 protocol.
     pub fn h1<B, SF>(self, service: SF) -> H1Service<F, S, B, X, U>
     where
@@ -174,7 +378,15 @@ where
             self.client_timeout,
             self.client_disconnect,
             self.handshake_timeout,
-        );
+        )
+        .h1_header_case(self.header_case)
+        .head_limits(self.head_limits)
+        .strict_parsing(self.strict_parsing)
+        .catch_panics(self.catch_panics)
+        .timeouts(self.timeouts)
+        .memory_limits(self.memory_budget, self.conn_memory_limit)
+        .message_pool(self.message_pool)
+        .on_connect(self.on_connect);
         H1Service::with_config(cfg, service.into_factory())
             .expect(self.expect)
             .upgrade(self.upgrade)
@@ -196,7 +408,15 @@ where
             self.client_timeout,
             self.client_disconnect,
             self.handshake_timeout,
-        );
+        )
+        .h1_header_case(self.header_case)
+        .head_limits(self.head_limits)
+        .strict_parsing(self.strict_parsing)
+        .catch_panics(self.catch_panics)
+        .timeouts(self.timeouts)
+        .memory_limits(self.memory_budget, self.conn_memory_limit)
+        .message_pool(self.message_pool)
+        .on_connect(self.on_connect);
 
         H2Service::with_config(cfg, service.into_factory())
     }
@@ -217,7 +437,15 @@ where
             self.client_timeout,
             self.client_disconnect,
             self.handshake_timeout,
-        );
+        )
+        .h1_header_case(self.header_case)
+        .head_limits(self.head_limits)
+        .strict_parsing(self.strict_parsing)
+        .catch_panics(self.catch_panics)
+        .timeouts(self.timeouts)
+        .memory_limits(self.memory_budget, self.conn_memory_limit)
+        .message_pool(self.message_pool)
+        .on_connect(self.on_connect);
         HttpService::with_config(cfg, service.into_factory())
             .expect(self.expect)
             .upgrade(self.upgrade)
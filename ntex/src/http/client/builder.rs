@@ -1,12 +1,18 @@
 use std::{convert::TryFrom, fmt, rc::Rc};
 
 use crate::http::error::HttpError;
+use crate::http::h1::HeaderCase;
 use crate::http::header::{self, HeaderMap, HeaderName, HeaderValue};
-use crate::{service::Service, time::Millis};
+use crate::service::{boxed, Service, Transform};
+use crate::time::Millis;
 
 use super::connect::ConnectorWrapper;
-use super::error::ConnectError;
-use super::{Client, ClientConfig, Connect, Connection, Connector};
+use super::error::{ConnectError, SendRequestError};
+use super::middleware::{ClientCall, ClientService, Sender};
+use super::Proxy;
+use super::{Client, ClientConfig, ClientResponse, Connect, Connection, Connector};
+
+type Wrapper = Box<dyn Fn(ClientService) -> ClientService>;
 
 /// An HTTP Client builder
 ///
@@ -17,6 +23,7 @@ pub struct ClientBuilder {
     default_headers: bool,
     allow_redirects: bool,
     max_redirects: usize,
+    middlewares: Vec<Wrapper>,
 }
 
 impl Default for ClientBuilder {
@@ -31,10 +38,16 @@ impl ClientBuilder {
             default_headers: true,
             allow_redirects: true,
             max_redirects: 10,
+            middlewares: Vec::new(),
             config: ClientConfig {
                 headers: HeaderMap::new(),
                 timeout: Millis(5_000),
-                connector: Box::new(ConnectorWrapper(Connector::default().finish())),
+                header_case: HeaderCase::Lower,
+                max_redirects: 10,
+                connector: Rc::new(ConnectorWrapper(Rc::new(
+                    Connector::default().finish(),
+                ))),
+                middleware: None,
             },
         }
     }
@@ -44,10 +57,33 @@ impl ClientBuilder {
     where
         T: Service<Connect, Response = Connection, Error = ConnectError> + 'static,
     {
-        self.config.connector = Box::new(ConnectorWrapper(connector));
+        self.config.connector = Rc::new(ConnectorWrapper(Rc::new(connector)));
         self
     }
 
+    /// Use proxy for all requests.
+    ///
+    /// Client's connector is replaced with default connector that uses
+    /// the proxy, use `Connector::proxy()` to configure custom connector.
+    /// By default proxy is configured from environment variables,
+    /// see `Proxy::from_env()`.
+    ///
+    /// Panics if proxy url is not valid.
+    pub fn proxy(self, url: &str) -> Self {
+        let proxy = match Proxy::new(url) {
+            Ok(proxy) => proxy,
+            Err(e) => panic!("Invalid proxy url {:?}: {}", url, e),
+        };
+        self.connector(Connector::default().proxy(proxy).finish())
+    }
+
+    /// Do not use proxy, connect directly.
+    ///
+    /// Client's connector is replaced with default connector.
+    pub fn no_proxy(self) -> Self {
+        self.connector(Connector::default().no_proxy().finish())
+    }
+
     /// Set request timeout.
     ///
     /// Request timeout is the total time before a response must be received.
@@ -63,6 +99,15 @@ impl ClientBuilder {
         self
     }
 
+    /// Set header names casing mode for http/1 requests.
+    ///
+    /// By default header names are encoded in lowercase. Original casing
+    /// could be provided with `ClientRequest::header_case_map()` method.
+    pub fn h1_header_case(mut self, case: HeaderCase) -> Self {
+        self.config.header_case = case;
+        self
+    }
+
     /// Do not follow redirects.
     ///
     /// Redirects are allowed by default.
@@ -130,8 +175,50 @@ impl ClientBuilder {
         self.header(header::AUTHORIZATION, format!("Bearer {}", token))
     }
 
+    /// Register client middleware.
+    ///
+    /// Middleware is applied to every request sent by the client, each
+    /// redirected request passes through middlewares as well. Middleware
+    /// registered last is called first.
+    ///
+    /// ```rust
+    /// use ntex::http::client::{middleware::Retry, Client};
+    ///
+    /// #[ntex::main]
+    /// async fn main() {
+    ///     let client = Client::build().wrap(Retry::new().max_retries(2)).finish();
+    /// }
+    /// ```
+    pub fn wrap<M>(mut self, mw: M) -> Self
+    where
+        M: Transform<ClientService> + 'static,
+        M::Service: Service<ClientCall, Response = ClientResponse, Error = SendRequestError>
+            + 'static,
+        <M::Service as Service<ClientCall>>::Future: 'static,
+    {
+        self.middlewares
+            .push(Box::new(move |srv| boxed::service(mw.new_transform(srv))));
+        self
+    }
+
     /// Finish build process and create `Client` instance.
-    pub fn finish(self) -> Client {
+    pub fn finish(mut self) -> Client {
+        self.config.max_redirects = if self.allow_redirects {
+            self.max_redirects
+        } else {
+            0
+        };
+        if !self.middlewares.is_empty() {
+            let sender = boxed::service(Sender {
+                connector: self.config.connector.clone(),
+                header_case: self.config.header_case,
+            });
+            self.config.middleware = Some(
+                self.middlewares
+                    .iter()
+                    .fold(sender, |srv, wrapper| wrapper(srv)),
+            );
+        }
         Client(Rc::new(self.config))
     }
 }
@@ -1,14 +1,17 @@
-use std::{future::Future, net, pin::Pin};
+use std::{future::Future, net, pin::Pin, rc::Rc};
+
+use ntex_tls::types::HttpProtocol;
 
 use crate::http::body::Body;
-use crate::http::RequestHeadType;
+use crate::http::{h1::HeaderCase, RequestHeadType};
 use crate::service::Service;
 
 use super::error::{ConnectError, SendRequestError};
+use super::middleware::ClientCall;
 use super::response::ClientResponse;
-use super::{Connect as ClientConnect, Connection};
+use super::{h2proto, Connect as ClientConnect, Connection};
 
-pub(super) struct ConnectorWrapper<T>(pub(crate) T);
+pub(super) struct ConnectorWrapper<T>(pub(crate) Rc<T>);
 
 pub(super) trait Connect {
     fn send_request(
@@ -16,12 +19,13 @@ pub(super) trait Connect {
         head: RequestHeadType,
         body: Body,
         addr: Option<net::SocketAddr>,
+        header_case: HeaderCase,
     ) -> Pin<Box<dyn Future<Output = Result<ClientResponse, SendRequestError>>>>;
 }
 
 impl<T> Connect for ConnectorWrapper<T>
 where
-    T: Service<ClientConnect, Response = Connection, Error = ConnectError>,
+    T: Service<ClientConnect, Response = Connection, Error = ConnectError> + 'static,
     T::Future: 'static,
 {
     fn send_request(
@@ -29,21 +33,56 @@ where
         head: RequestHeadType,
         body: Body,
         addr: Option<net::SocketAddr>,
+        header_case: HeaderCase,
     ) -> Pin<Box<dyn Future<Output = Result<ClientResponse, SendRequestError>>>> {
         // connect to the host
         let fut = self.0.call(ClientConnect {
             uri: head.as_ref().uri.clone(),
             addr,
         });
+        let connector = self.0.clone();
 
         Box::pin(async move {
             let connection = fut.await?;
 
-            // send request
-            connection
-                .send_request(head, body)
-                .await
-                .map(|(head, payload)| ClientResponse::new(head, payload))
+            if connection.protocol() != HttpProtocol::Http2 {
+                // send request
+                return connection
+                    .send_request(head, body, header_case)
+                    .await
+                    .map(|(head, payload)| ClientResponse::new(head, payload));
+            }
+
+            // http/2 connection could be closed by the server at any time,
+            // keep copy of the request to resend it over new connection
+            let head = match head {
+                RequestHeadType::Owned(head) => RequestHeadType::Rc(Rc::new(head), None),
+                head => head,
+            };
+            let req = ClientCall::new(head, body, addr);
+            let retry = req.try_clone();
+            let (head, body, _) = req.into_parts();
+
+            let result = match connection.send_request(head, body, header_case).await {
+                Err(SendRequestError::H2(err)) if h2proto::is_refused(&err) => {
+                    if let Some(req) = retry {
+                        trace!("Http/2 request is refused, resend over new connection");
+                        let (head, body, addr) = req.into_parts();
+                        connector
+                            .call(ClientConnect {
+                                uri: head.as_ref().uri.clone(),
+                                addr,
+                            })
+                            .await?
+                            .send_request(head, body, header_case)
+                            .await
+                    } else {
+                        Err(SendRequestError::H2(err))
+                    }
+                }
+                result => result,
+            };
+            result.map(|(head, payload)| ClientResponse::new(head, payload))
         })
     }
 }
@@ -4,6 +4,7 @@ use h2::client::SendRequest;
 use ntex_tls::types::HttpProtocol;
 
 use crate::http::body::MessageBody;
+use crate::http::h1::HeaderCase;
 use crate::http::message::{RequestHeadType, ResponseHead};
 use crate::http::payload::Payload;
 use crate::io::IoBoxed;
@@ -33,17 +34,41 @@ pub(super) struct H2Sender(Rc<RefCell<H2SenderInner>>);
 struct H2SenderInner {
     io: SendRequest<Bytes>,
     closed: bool,
+    streams: usize,
+    max_streams: usize,
 }
 
 impl H2Sender {
     pub(super) fn new(io: SendRequest<Bytes>) -> Self {
-        Self(Rc::new(RefCell::new(H2SenderInner { io, closed: false })))
+        Self(Rc::new(RefCell::new(H2SenderInner {
+            io,
+            closed: false,
+            streams: 0,
+            max_streams: usize::MAX,
+        })))
     }
 
     pub(super) fn is_closed(&self) -> bool {
         self.0.borrow().closed
     }
 
+    /// Check if connection can open new stream
+    pub(super) fn is_available(&self) -> bool {
+        let inner = self.0.borrow();
+        !inner.closed && inner.streams < inner.max_streams
+    }
+
+    /// Set max number of concurrent streams allowed by the peer
+    pub(super) fn set_max_streams(&self, max: usize) {
+        self.0.borrow_mut().max_streams = max;
+    }
+
+    /// Reserve new stream
+    pub(super) fn stream(&self) -> H2Stream {
+        self.0.borrow_mut().streams += 1;
+        H2Stream(self.clone())
+    }
+
     pub(super) fn close(&self) {
         self.0.borrow_mut().closed = true;
     }
@@ -53,10 +78,28 @@ impl H2Sender {
     }
 }
 
+/// Active http/2 stream
+///
+/// Stream is released on drop.
+pub(super) struct H2Stream(H2Sender);
+
+impl H2Stream {
+    pub(super) fn connection(&self) -> &H2Sender {
+        &self.0
+    }
+}
+
+impl Drop for H2Stream {
+    fn drop(&mut self) {
+        (self.0).0.borrow_mut().streams -= 1;
+    }
+}
+
 #[doc(hidden)]
 /// HTTP client connection
 pub struct Connection {
     io: Option<ConnectionType>,
+    stream: Option<H2Stream>,
     created: time::Instant,
     pool: Option<Acquired>,
 }
@@ -77,8 +120,15 @@ impl Connection {
         created: time::Instant,
         pool: Option<Acquired>,
     ) -> Self {
+        // http/2 connection reserves stream until request is completed
+        let stream = if let ConnectionType::H2(ref h2) = io {
+            Some(h2.stream())
+        } else {
+            None
+        };
         Self {
             pool,
+            stream,
             created,
             io: Some(io),
         }
@@ -88,6 +138,7 @@ impl Connection {
         if let Some(mut pool) = self.pool {
             pool.release(Self {
                 io: self.io,
+                stream: None,
                 created: self.created,
                 pool: None,
             });
@@ -110,12 +161,23 @@ impl Connection {
         mut self,
         head: H,
         body: B,
+        header_case: HeaderCase,
     ) -> Result<(ResponseHead, Payload), SendRequestError> {
         match self.io.take().unwrap() {
             ConnectionType::H1(io) => {
-                h1proto::send_request(io, head.into(), body, self.created, self.pool).await
+                h1proto::send_request(
+                    io,
+                    head.into(),
+                    body,
+                    header_case,
+                    self.created,
+                    self.pool,
+                )
+                .await
+            }
+            ConnectionType::H2(_) => {
+                h2proto::send_request(self.stream.take().unwrap(), head.into(), body).await
             }
-            ConnectionType::H2(io) => h2proto::send_request(io, head.into(), body).await,
         }
     }
 }
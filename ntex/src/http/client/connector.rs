@@ -1,5 +1,7 @@
 use std::{rc::Rc, task::Context, task::Poll, time::Duration};
 
+use h2::client::Builder;
+
 use crate::connect::{Connect as TcpConnect, Connector as TcpConnector};
 use crate::http::Uri;
 use crate::io::IoBoxed;
@@ -11,6 +13,7 @@ use crate::util::{Either, Ready};
 use super::connection::Connection;
 use super::error::ConnectError;
 use super::pool::ConnectionPool;
+use super::proxy::{Proxy, ProxyConnector, TlsUpgrade};
 use super::Connect;
 
 #[cfg(feature = "openssl")]
@@ -40,8 +43,11 @@ pub struct Connector {
     conn_keep_alive: Duration,
     disconnect_timeout: Millis,
     limit: usize,
+    h2config: Builder,
     connector: BoxedConnector,
     ssl_connector: Option<BoxedConnector>,
+    tls: Option<TlsUpgrade>,
+    proxy: Option<Proxy>,
 }
 
 impl Default for Connector {
@@ -59,11 +65,14 @@ impl Connector {
                     .map_err(ConnectError::from),
             ),
             ssl_connector: None,
+            tls: None,
+            proxy: Proxy::from_env(),
             timeout: Millis(1_000),
             conn_lifetime: Duration::from_secs(75),
             conn_keep_alive: Duration::from_secs(15),
             disconnect_timeout: Millis(3_000),
             limit: 100,
+            h2config: Builder::new(),
         };
 
         #[cfg(feature = "openssl")]
@@ -117,18 +126,46 @@ impl Connector {
 
     #[cfg(feature = "openssl")]
     /// Use openssl connector for secured connections.
-    pub fn openssl(self, connector: SslConnector) -> Self {
+    pub fn openssl(mut self, connector: SslConnector) -> Self {
         use crate::connect::openssl::Connector;
 
-        self.secure_connector(Connector::new(connector))
+        let connector = Connector::new(connector);
+        let tls = connector.clone();
+        self.tls = Some(Rc::new(move |io, host| {
+            let fut = tls.handshake(io, host);
+            Box::pin(async move { fut.await.map(IoBoxed::from) })
+        }));
+        self.set_secure_connector(connector)
     }
 
     #[cfg(feature = "rustls")]
     /// Use rustls connector for secured connections.
-    pub fn rustls(self, connector: ClientConfig) -> Self {
+    pub fn rustls(mut self, connector: ClientConfig) -> Self {
         use crate::connect::rustls::Connector;
 
-        self.secure_connector(Connector::new(connector))
+        let connector = Connector::new(connector);
+        let tls = connector.clone();
+        self.tls = Some(Rc::new(move |io, host| {
+            let fut = tls.handshake(io, host);
+            Box::pin(async move { fut.await.map(IoBoxed::from) })
+        }));
+        self.set_secure_connector(connector)
+    }
+
+    /// Use proxy for connections.
+    ///
+    /// By default proxy is configured from environment variables,
+    /// see `Proxy::from_env()`. Proxy is not used for secure connections
+    /// if custom secure connector is set.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Connect directly, do not use proxy.
+    pub fn no_proxy(mut self) -> Self {
+        self.proxy = None;
+        self
     }
 
     /// Set total number of simultaneous connections per type of scheme.
@@ -140,6 +177,25 @@ impl Connector {
         self
     }
 
+    /// Set initial http/2 stream-level flow control window size.
+    ///
+    /// Window size determines how much data server can send for
+    /// each stream before waiting for window update.
+    /// Default window size is 65,535 bytes.
+    pub fn h2_initial_window_size(mut self, size: u32) -> Self {
+        self.h2config.initial_window_size(size);
+        self
+    }
+
+    /// Set initial http/2 connection-level flow control window size.
+    ///
+    /// Window is shared between all streams of the connection.
+    /// Default window size is 65,535 bytes.
+    pub fn h2_initial_connection_window_size(mut self, size: u32) -> Self {
+        self.h2config.initial_connection_window_size(size);
+        self
+    }
+
     /// Set keep-alive period for opened connection.
     ///
     /// Keep-alive period is the period between connection usage. If
@@ -187,6 +243,15 @@ impl Connector {
 
     /// Use custom connector to open secure connections.
     pub fn secure_connector<T>(mut self, connector: T) -> Self
+    where
+        T: Service<TcpConnect<Uri>, Error = crate::connect::ConnectError> + 'static,
+        IoBoxed: From<T::Response>,
+    {
+        self.tls = None;
+        self.set_secure_connector(connector)
+    }
+
+    fn set_secure_connector<T>(mut self, connector: T) -> Self
     where
         T: Service<TcpConnect<Uri>, Error = crate::connect::ConnectError> + 'static,
         IoBoxed: From<T::Response>,
@@ -203,9 +268,22 @@ impl Connector {
     pub fn finish(
         self,
     ) -> impl Service<Connect, Response = Connection, Error = ConnectError> + Clone {
-        let tcp_service = connector(self.connector, self.timeout, self.disconnect_timeout);
+        let proxy = self.proxy.map(Rc::new);
+        let tcp_connector = match proxy {
+            Some(ref proxy) => {
+                boxed::service(ProxyConnector::new(proxy.clone(), self.connector, None))
+            }
+            None => self.connector,
+        };
+        let tcp_service = connector(tcp_connector, self.timeout, self.disconnect_timeout);
 
         let ssl_pool = if let Some(ssl_connector) = self.ssl_connector {
+            let ssl_connector = match (proxy, self.tls) {
+                (Some(proxy), Some(tls)) => {
+                    boxed::service(ProxyConnector::new(proxy, ssl_connector, Some(tls)))
+                }
+                _ => ssl_connector,
+            };
             let srv = connector(ssl_connector, self.timeout, self.disconnect_timeout);
             Some(ConnectionPool::new(
                 srv,
@@ -213,6 +291,7 @@ impl Connector {
                 self.conn_keep_alive,
                 self.disconnect_timeout,
                 self.limit,
+                self.h2config.clone(),
             ))
         } else {
             None
@@ -225,6 +304,7 @@ impl Connector {
                 self.conn_keep_alive,
                 self.disconnect_timeout,
                 self.limit,
+                self.h2config,
             ),
             ssl_pool,
         })
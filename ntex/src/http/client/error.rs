@@ -59,11 +59,19 @@ pub enum ConnectError {
 
     /// Connector has been disconnected
     #[error("Connector has been disconnected")]
-    Disconnected(Option<io::Error>),
+    Disconnected(#[source] Option<io::Error>),
 
     /// Unresolved host name
     #[error("Connector received `Connect` method with unresolved host")]
     Unresolved,
+
+    /// Proxy tunnel could not be established
+    #[error("Proxy error: {0}")]
+    Proxy(String),
+
+    /// Server certificates do not match pinned public keys
+    #[error("Server certificate does not match pinned public keys")]
+    PinMismatch,
 }
 
 impl From<crate::connect::ConnectError> for ConnectError {
@@ -73,6 +81,8 @@ impl From<crate::connect::ConnectError> for ConnectError {
             crate::connect::ConnectError::NoRecords => ConnectError::NoRecords,
             crate::connect::ConnectError::InvalidInput => panic!(),
             crate::connect::ConnectError::Unresolved => ConnectError::Unresolved,
+            crate::connect::ConnectError::Timeout => ConnectError::Timeout,
+            crate::connect::ConnectError::PinMismatch => ConnectError::PinMismatch,
             crate::connect::ConnectError::Io(e) => ConnectError::Disconnected(Some(e)),
         }
     }
@@ -85,6 +95,17 @@ impl<T: std::fmt::Debug> From<HandshakeError<T>> for ConnectError {
     }
 }
 
+/// A set of errors that can occur during proxy url parsing
+#[derive(Error, Debug)]
+pub enum InvalidProxy {
+    #[error("Unsupported proxy scheme")]
+    UnknownScheme,
+    #[error("Missing proxy host name")]
+    MissingHost,
+    #[error("Proxy url parse error: {0}")]
+    Http(#[from] HttpError),
+}
+
 #[derive(Error, Debug)]
 pub enum InvalidUrl {
     #[error("Missing url scheme")]
@@ -17,6 +17,7 @@ pub(super) async fn send_request<B>(
     io: IoBoxed,
     mut head: RequestHeadType,
     body: B,
+    header_case: h1::HeaderCase,
     created: Instant,
     pool: Option<Acquired>,
 ) -> Result<(ResponseHead, Payload), SendRequestError>
@@ -57,7 +58,7 @@ where
     );
 
     // send request
-    let codec = h1::ClientCodec::default();
+    let codec = h1::ClientCodec::default().header_case(header_case);
     io.send((head, body.size()).into(), &codec).await?;
 
     log::trace!("http1 request has been sent");
@@ -1,23 +1,65 @@
-use std::convert::TryFrom;
+use std::{convert::TryFrom, pin::Pin, task::Context, task::Poll};
 
-use h2::SendStream;
+use h2::{Reason, SendStream};
 use http::header::{HeaderValue, CONNECTION, CONTENT_LENGTH, TRANSFER_ENCODING};
 use http::{request::Request, Method, Version};
 
 use crate::http::body::{BodySize, MessageBody};
+use crate::http::error::PayloadError;
 use crate::http::header::HeaderMap;
 use crate::http::message::{RequestHeadType, ResponseHead};
-use crate::http::payload::Payload;
-use crate::util::{poll_fn, Bytes};
+use crate::http::{h2::Payload as H2Payload, payload::Payload};
+use crate::util::{poll_fn, Bytes, Stream};
 
-use super::connection::H2Sender;
+use super::connection::H2Stream;
 use super::error::SendRequestError;
 
+/// Check if request is refused by the server and was not processed.
+///
+/// Such requests are safe to resend over new connection.
+pub(super) fn is_refused(err: &h2::Error) -> bool {
+    if err.is_go_away() && err.is_remote() {
+        // graceful shutdown, all streams above last stream id are not processed
+        err.reason() == Some(Reason::NO_ERROR)
+    } else {
+        err.reason() == Some(Reason::REFUSED_STREAM)
+    }
+}
+
 pub(super) async fn send_request<B>(
-    io: H2Sender,
+    stream: H2Stream,
     head: RequestHeadType,
     body: B,
 ) -> Result<(ResponseHead, Payload), SendRequestError>
+where
+    B: MessageBody,
+{
+    match send(&stream, head, body).await {
+        Ok((head, Some(payload))) => Ok((
+            head,
+            Payload::Stream(Box::pin(StreamPayload {
+                payload,
+                stream: Some(stream),
+            })),
+        )),
+        Ok((head, None)) => Ok((head, Payload::None)),
+        Err(err) => {
+            if let SendRequestError::H2(ref e) = err {
+                // connection is going away, do not use it for new requests
+                if e.is_go_away() {
+                    stream.connection().close();
+                }
+            }
+            Err(err)
+        }
+    }
+}
+
+async fn send<B>(
+    stream: &H2Stream,
+    head: RequestHeadType,
+    body: B,
+) -> Result<(ResponseHead, Option<H2Payload>), SendRequestError>
 where
     B: MessageBody,
 {
@@ -79,7 +121,7 @@ where
         req.headers_mut().append(key, value.clone());
     }
 
-    let mut sender = io.get_sender();
+    let mut sender = stream.connection().get_sender();
     let res = poll_fn(|cx| sender.poll_ready(cx)).await;
     if let Err(e) = res {
         log::trace!("SendRequest readiness failed: {:?}", e);
@@ -99,7 +141,11 @@ where
     };
 
     let (parts, body) = resp.into_parts();
-    let payload = if head_req { Payload::None } else { body.into() };
+    let payload = if head_req {
+        None
+    } else {
+        Some(H2Payload::new(body))
+    };
 
     let mut head = ResponseHead::new(parts.status);
     head.version = parts.version;
@@ -152,3 +198,24 @@ async fn send_body<B: MessageBody>(
         }
     }
 }
+
+/// Response payload, keeps stream reserved until payload is consumed
+struct StreamPayload {
+    payload: H2Payload,
+    stream: Option<H2Stream>,
+}
+
+impl Stream for StreamPayload {
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let result = Pin::new(&mut self.payload).poll_next(cx);
+        if let Poll::Ready(None) = result {
+            self.stream.take();
+        }
+        result
+    }
+}
@@ -0,0 +1,538 @@
+//! Client middlewares
+//!
+//! Client middleware is a `Transform` for the service that sends
+//! requests. Middlewares get registered with `ClientBuilder::wrap()` method
+//! and are applied to every request, including redirected ones.
+//!
+//! ```rust
+//! use ntex::http::client::{middleware::Retry, Client};
+//! use ntex::time::Millis;
+//!
+//! #[ntex::main]
+//! async fn main() {
+//!     let client = Client::build()
+//!         .wrap(Retry::new().max_retries(5).backoff(Millis(50), Millis(2_000)))
+//!         .finish();
+//! }
+//! ```
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use std::{cell::Cell, fmt, net, rc::Rc};
+
+use nanorand::{Rng, WyRand};
+
+use crate::http::body::Body;
+use crate::http::h1::HeaderCase;
+use crate::http::header::{self, HeaderMap};
+use crate::http::{Method, RequestHead, RequestHeadType, StatusCode, Uri};
+use crate::service::boxed::{BoxFuture, BoxService};
+use crate::service::{Service, Transform};
+use crate::time::{sleep, timeout_checked, Millis};
+
+use super::connect::Connect as HttpConnect;
+use super::error::SendRequestError;
+use super::response::ClientResponse;
+
+/// Boxed service that sends client requests
+pub type ClientService = BoxService<ClientCall, ClientResponse, SendRequestError>;
+
+/// Outgoing request passed through client middlewares
+pub struct ClientCall {
+    head: RequestHeadType,
+    body: Body,
+    addr: Option<net::SocketAddr>,
+}
+
+impl ClientCall {
+    pub(super) fn new(
+        head: RequestHeadType,
+        body: Body,
+        addr: Option<net::SocketAddr>,
+    ) -> Self {
+        ClientCall { head, body, addr }
+    }
+
+    /// Request's head.
+    ///
+    /// Extra headers of frozen request are not included,
+    /// use `head_mut()` to access all request headers.
+    pub fn head(&self) -> &RequestHead {
+        self.head.as_ref()
+    }
+
+    /// Mutable reference to request's head.
+    ///
+    /// Head of frozen request is copied, extra headers are merged into
+    /// copied head. Request extensions are not copied.
+    pub fn head_mut(&mut self) -> &mut RequestHead {
+        if let RequestHeadType::Rc(..) = self.head {
+            self.head = RequestHeadType::Owned(copy_head(&self.head));
+        }
+        match self.head {
+            RequestHeadType::Owned(ref mut head) => head,
+            RequestHeadType::Rc(..) => unreachable!(),
+        }
+    }
+
+    /// Request's uri
+    pub fn uri(&self) -> &Uri {
+        &self.head().uri
+    }
+
+    /// Request's method
+    pub fn method(&self) -> &Method {
+        &self.head().method
+    }
+
+    /// Request's body
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    /// Set request's body
+    pub fn set_body(&mut self, body: Body) {
+        self.body = body;
+    }
+
+    /// Socket address of the server, if it is set
+    pub fn address(&self) -> Option<net::SocketAddr> {
+        self.addr
+    }
+
+    /// Copy request, if request's body is buffered.
+    ///
+    /// Streaming body could not be copied.
+    pub fn try_clone(&self) -> Option<ClientCall> {
+        let body = match self.body {
+            Body::None => Body::None,
+            Body::Empty => Body::Empty,
+            Body::Bytes(ref b) => Body::Bytes(b.clone()),
+            Body::Message(_) => return None,
+        };
+        let head = match self.head {
+            RequestHeadType::Rc(ref head, ref extra) => {
+                RequestHeadType::Rc(head.clone(), extra.clone())
+            }
+            RequestHeadType::Owned(_) => RequestHeadType::Owned(copy_head(&self.head)),
+        };
+        Some(ClientCall {
+            head,
+            body,
+            addr: self.addr,
+        })
+    }
+
+    pub(super) fn into_parts(self) -> (RequestHeadType, Body, Option<net::SocketAddr>) {
+        (self.head, self.body, self.addr)
+    }
+}
+
+impl fmt::Debug for ClientCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCall")
+            .field("method", self.method())
+            .field("uri", self.uri())
+            .field("headers", &self.head().headers)
+            .field("extra_headers", &self.head.extra_headers())
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+/// Copy request head, extra headers are merged. Extensions are not copied.
+fn copy_head(src: &RequestHeadType) -> RequestHead {
+    let head = src.as_ref();
+    let mut headers: HeaderMap = head.headers.clone();
+    if let Some(extra) = src.extra_headers() {
+        for (key, value) in extra.iter() {
+            headers.insert(key.clone(), value.clone());
+        }
+    }
+    RequestHead {
+        uri: head.uri.clone(),
+        method: head.method.clone(),
+        version: head.version,
+        flags: head.flags,
+        headers,
+        ..Default::default()
+    }
+}
+
+/// Innermost client service, sends request with connector
+pub(super) struct Sender {
+    pub(super) connector: Rc<dyn HttpConnect>,
+    pub(super) header_case: HeaderCase,
+}
+
+impl Service<ClientCall> for Sender {
+    type Response = ClientResponse;
+    type Error = SendRequestError;
+    type Future = BoxFuture<ClientResponse, SendRequestError>;
+
+    #[inline]
+    fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&self, req: ClientCall) -> Self::Future {
+        let (head, body, addr) = req.into_parts();
+        self.connector
+            .send_request(head, body, addr, self.header_case)
+    }
+}
+
+/// Retry middleware.
+///
+/// Failed requests are sent again with exponential backoff and jitter.
+/// Requests are retried if connection could not be established. Idempotent
+/// requests are retried on i/o errors, per-try timeouts and on `502`, `503`
+/// and `504` responses as well, see `RequestHead::idempotent()`. Delay
+/// requested by `Retry-After` response header is honored, response is
+/// returned as is if requested delay is larger than max backoff delay.
+/// Requests with streaming body are not retried.
+///
+/// Number of retries is limited by retry budget, so failing upstream
+/// does not get overloaded by retries. Retries are limited by client's
+/// request timeout as well.
+#[derive(Clone, Debug)]
+pub struct Retry {
+    max_retries: usize,
+    base: Millis,
+    max: Millis,
+    try_timeout: Millis,
+    statuses: Vec<StatusCode>,
+    budget: RetryBudget,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_retries: 3,
+            base: Millis(100),
+            max: Millis(10_000),
+            try_timeout: Millis::ZERO,
+            statuses: vec![
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            budget: RetryBudget::default(),
+        }
+    }
+}
+
+impl Retry {
+    /// Create retry middleware with default settings
+    pub fn new() -> Self {
+        Retry::default()
+    }
+
+    /// Set max number of retries. By default it is 3.
+    pub fn max_retries(mut self, num: usize) -> Self {
+        self.max_retries = num;
+        self
+    }
+
+    /// Set base and max delay between retries.
+    ///
+    /// Delay doubles with every retry. By default base delay is 100 millis,
+    /// max delay is 10 seconds.
+    pub fn backoff<T: Into<Millis>>(mut self, base: T, max: T) -> Self {
+        self.base = base.into();
+        self.max = max.into();
+        self
+    }
+
+    /// Set timeout for each try.
+    ///
+    /// Timed out idempotent requests are retried. By default per-try
+    /// timeout is disabled.
+    pub fn try_timeout<T: Into<Millis>>(mut self, timeout: T) -> Self {
+        self.try_timeout = timeout.into();
+        self
+    }
+
+    /// Set response status codes that get retried.
+    ///
+    /// By default `502`, `503` and `504` responses are retried.
+    pub fn statuses(mut self, statuses: &[StatusCode]) -> Self {
+        self.statuses = statuses.to_vec();
+        self
+    }
+
+    /// Set retry budget.
+    ///
+    /// Budget is shared by all clients that use this middleware.
+    pub fn budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Delay before retry, half of the delay is random
+    fn delay(&self, attempt: usize) -> Millis {
+        let delay =
+            std::cmp::min(self.base.0.saturating_mul(1 << attempt.min(16)), self.max.0);
+        let half = delay / 2;
+        Millis(half + WyRand::new().generate_range(0..=delay - half))
+    }
+}
+
+/// Retry budget.
+///
+/// Every request deposits `ratio` of retry into the budget, every retry
+/// withdraws one. Unused retries are accumulated up to 100 requests worth
+/// of deposits. Besides deposits, `min_per_sec` retries per second
+/// are always allowed. By default 20% of requests could be retried,
+/// plus 10 retries per second.
+#[derive(Clone, Debug)]
+pub struct RetryBudget(Rc<BudgetInner>);
+
+#[derive(Debug)]
+struct BudgetInner {
+    ratio: f64,
+    min_per_sec: u32,
+    balance: Cell<f64>,
+    window: Cell<Instant>,
+    reserved: Cell<u32>,
+}
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        RetryBudget::new(0.2, 10)
+    }
+}
+
+impl RetryBudget {
+    /// Create retry budget.
+    ///
+    /// Panics if `ratio` is negative.
+    pub fn new(ratio: f64, min_per_sec: u32) -> Self {
+        assert!(ratio >= 0.0, "Retry ratio must not be negative");
+        RetryBudget(Rc::new(BudgetInner {
+            ratio,
+            min_per_sec,
+            balance: Cell::new(0.0),
+            window: Cell::new(Instant::now()),
+            reserved: Cell::new(0),
+        }))
+    }
+
+    fn deposit(&self) {
+        let b = &self.0;
+        b.balance
+            .set((b.balance.get() + b.ratio).min(b.ratio * 100.0));
+    }
+
+    fn withdraw(&self) -> bool {
+        let b = &self.0;
+        let now = Instant::now();
+        if now.duration_since(b.window.get()) >= Duration::from_secs(1) {
+            b.window.set(now);
+            b.reserved.set(0);
+        }
+
+        if b.reserved.get() < b.min_per_sec {
+            b.reserved.set(b.reserved.get() + 1);
+            true
+        } else if b.balance.get() >= 1.0 {
+            b.balance.set(b.balance.get() - 1.0);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<S> Transform<S> for Retry {
+    type Service = RetryService<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        RetryService {
+            service: Rc::new(service),
+            cfg: Rc::new(self.clone()),
+        }
+    }
+}
+
+pub struct RetryService<S> {
+    service: Rc<S>,
+    cfg: Rc<Retry>,
+}
+
+impl<S> Service<ClientCall> for RetryService<S>
+where
+    S: Service<ClientCall, Response = ClientResponse, Error = SendRequestError> + 'static,
+{
+    type Response = ClientResponse;
+    type Error = SendRequestError;
+    type Future = BoxFuture<ClientResponse, SendRequestError>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, mut req: ClientCall) -> Self::Future {
+        let srv = self.service.clone();
+        let cfg = self.cfg.clone();
+        let idempotent = req.head().idempotent();
+        cfg.budget.deposit();
+
+        Box::pin(async move {
+            let mut attempt = 0;
+            loop {
+                let next = if attempt < cfg.max_retries {
+                    req.try_clone()
+                } else {
+                    None
+                };
+
+                let result = timeout_checked(cfg.try_timeout, srv.call(req))
+                    .await
+                    .unwrap_or(Err(SendRequestError::Timeout));
+                let delay = match result {
+                    Ok(ref res) if idempotent && cfg.statuses.contains(&res.status()) => {
+                        match retry_after(res) {
+                            Some(delay) if delay > cfg.max => return result,
+                            Some(delay) => delay,
+                            None => cfg.delay(attempt),
+                        }
+                    }
+                    Err(SendRequestError::Connect(_)) => cfg.delay(attempt),
+                    Err(SendRequestError::Send(_))
+                    | Err(SendRequestError::H2(_))
+                    | Err(SendRequestError::Timeout)
+                        if idempotent =>
+                    {
+                        cfg.delay(attempt)
+                    }
+                    _ => return result,
+                };
+                req = match next {
+                    Some(next) if cfg.budget.withdraw() => next,
+                    _ => return result,
+                };
+
+                log::trace!("Retrying request {} in {:?}", req.uri(), delay);
+                drop(result);
+                sleep(delay).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+/// Delay requested by `Retry-After` header, seconds or http date
+fn retry_after(res: &ClientResponse) -> Option<Millis> {
+    let val = res
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = val.parse::<u32>() {
+        Some(Millis(secs.saturating_mul(1000)))
+    } else {
+        let time = httpdate::parse_http_date(val).ok()?;
+        let delay = time
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO);
+        Some(Millis::from(delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::TestResponse;
+    use crate::util::Bytes;
+
+    #[test]
+    fn test_delay() {
+        let retry = Retry::new().backoff(Millis(100), Millis(1_000));
+        for _ in 0..10 {
+            let d = retry.delay(0).0;
+            assert!((50..=100).contains(&d));
+            let d = retry.delay(2).0;
+            assert!((200..=400).contains(&d));
+            let d = retry.delay(64).0;
+            assert!((500..=1_000).contains(&d));
+        }
+    }
+
+    #[test]
+    fn test_budget() {
+        let budget = RetryBudget::new(0.5, 2);
+        assert!(budget.withdraw());
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+
+        budget.deposit();
+        assert!(!budget.withdraw());
+        budget.deposit();
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+
+        // deposits are capped
+        let budget = RetryBudget::new(0.1, 0);
+        for _ in 0..1000 {
+            budget.deposit();
+        }
+        assert!((0..10).all(|_| budget.withdraw()));
+        assert!(!budget.withdraw());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let res = TestResponse::with_header(header::RETRY_AFTER, "2").finish();
+        assert_eq!(retry_after(&res), Some(Millis(2_000)));
+
+        let res =
+            TestResponse::with_header(header::RETRY_AFTER, "Tue, 15 Nov 1994 08:12:31 GMT")
+                .finish();
+        assert_eq!(retry_after(&res), Some(Millis::ZERO));
+
+        let res = TestResponse::with_header(header::RETRY_AFTER, "soon").finish();
+        assert_eq!(retry_after(&res), None);
+        assert_eq!(retry_after(&TestResponse::default().finish()), None);
+    }
+
+    #[test]
+    fn test_call() {
+        let head = RequestHead {
+            uri: Uri::from_static("http://localhost/test"),
+            method: Method::POST,
+            ..Default::default()
+        };
+        let mut extra = HeaderMap::new();
+        extra.insert(header::ACCEPT, header::HeaderValue::from_static("*/*"));
+        let mut call = ClientCall::new(
+            RequestHeadType::Rc(Rc::new(head), Some(extra)),
+            Body::Bytes(Bytes::from_static(b"body")),
+            None,
+        );
+        assert_eq!(call.method(), Method::POST);
+        assert!(!call.head().headers.contains_key(header::ACCEPT));
+
+        let copy = call.try_clone().unwrap();
+        assert_eq!(copy.uri(), "http://localhost/test");
+        assert!(matches!(copy.body(), Body::Bytes(b) if b == "body"));
+
+        call.head_mut()
+            .headers
+            .insert(header::HOST, header::HeaderValue::from_static("localhost"));
+        assert!(call.head().headers.contains_key(header::ACCEPT));
+        assert!(call.head().headers.contains_key(header::HOST));
+        assert!(format!("{:?}", call).contains("ClientCall"));
+
+        call.set_body(Body::from_message(crate::http::body::BodyStream::new(
+            futures_util::stream::empty::<Result<Bytes, std::io::Error>>(),
+        )));
+        assert!(call.try_clone().is_none());
+    }
+}
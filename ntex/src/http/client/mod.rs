@@ -16,7 +16,7 @@
 //!     println!("Response: {:?}", response);
 //! }
 //! ```
-use std::{convert::TryFrom, rc::Rc};
+use std::{convert::TryFrom, net, rc::Rc};
 
 mod builder;
 mod connect;
@@ -26,26 +26,35 @@ pub mod error;
 mod frozen;
 mod h1proto;
 mod h2proto;
+pub mod middleware;
+pub mod multipart;
 mod pool;
+mod proxy;
+mod redirect;
 mod request;
 mod response;
 mod sender;
 mod test;
+pub mod ws;
 
 pub use self::builder::ClientBuilder;
 pub use self::connection::Connection;
 pub use self::connector::Connector;
 pub use self::frozen::{FrozenClientRequest, FrozenSendBuilder};
+pub use self::proxy::Proxy;
 pub use self::request::ClientRequest;
 pub use self::response::{ClientResponse, JsonBody, MessageBody};
 pub use self::sender::SendClientRequest;
 pub use self::test::TestResponse;
 
 use crate::http::error::HttpError;
-use crate::http::{HeaderMap, Method, RequestHead, Uri};
-use crate::time::Millis;
+use crate::http::{body::Body, h1::HeaderCase, HeaderMap, Method, RequestHead};
+use crate::http::{RequestHeadType, Uri};
+use crate::{service::boxed::BoxFuture, service::Service, time::Millis};
 
 use self::connect::{Connect as HttpConnect, ConnectorWrapper};
+use self::error::SendRequestError;
+use self::middleware::{ClientCall, ClientService};
 
 #[derive(Clone)]
 pub struct Connect {
@@ -74,17 +83,40 @@ pub struct Connect {
 pub struct Client(Rc<ClientConfig>);
 
 pub(self) struct ClientConfig {
-    pub(self) connector: Box<dyn HttpConnect>,
+    pub(self) connector: Rc<dyn HttpConnect>,
+    pub(self) middleware: Option<ClientService>,
     pub(self) headers: HeaderMap,
     pub(self) timeout: Millis,
+    pub(self) header_case: HeaderCase,
+    pub(self) max_redirects: usize,
+}
+
+impl ClientConfig {
+    /// Send request through client middlewares
+    pub(self) fn send_request(
+        &self,
+        head: RequestHeadType,
+        body: Body,
+        addr: Option<net::SocketAddr>,
+    ) -> BoxFuture<ClientResponse, SendRequestError> {
+        if let Some(ref srv) = self.middleware {
+            srv.call(ClientCall::new(head, body, addr))
+        } else {
+            self.connector
+                .send_request(head, body, addr, self.header_case)
+        }
+    }
 }
 
 impl Default for Client {
     fn default() -> Self {
         Client(Rc::new(ClientConfig {
-            connector: Box::new(ConnectorWrapper(Connector::default().finish())),
+            connector: Rc::new(ConnectorWrapper(Rc::new(Connector::default().finish()))),
+            middleware: None,
             headers: HeaderMap::new(),
             timeout: Millis(5_000),
+            header_case: HeaderCase::Lower,
+            max_redirects: 10,
         }))
     }
 }
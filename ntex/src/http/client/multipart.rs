@@ -0,0 +1,393 @@
+//! Multipart form builder for client requests
+//!
+//! ```rust
+//! use ntex::http::client::{Client, multipart::{Form, Part}};
+//! use ntex::util::Bytes;
+//!
+//! #[ntex::main]
+//! async fn main() {
+//!     let file = futures_util::stream::iter(vec![
+//!         Ok::<_, std::io::Error>(Bytes::from_static(b"chunk 1")),
+//!         Ok(Bytes::from_static(b"chunk 2")),
+//!     ]);
+//!
+//!     let form = Form::new()
+//!         .text("name", "ntex")
+//!         .part("file", Part::stream(file).file_name("data.txt"));
+//!
+//!     let response = Client::new()
+//!         .post("http://www.rust-lang.org")
+//!         .send_multipart(form)
+//!         .await;
+//!     println!("Response: {:?}", response);
+//! }
+//! ```
+use std::{collections::VecDeque, error::Error, fmt, io, task::Context, task::Poll};
+
+use nanorand::{Rng, WyRand};
+use tok_io::io::AsyncRead;
+
+use crate::http::body::{Body, BodyFromRead, BodySize, BodyStream, MessageBody};
+use crate::util::{ready, Bytes, Stream};
+
+/// `multipart/form-data` request body
+///
+/// Parts are streamed one by one, part's data is read from the source only
+/// when connection is ready to send next chunk. If size of all parts
+/// is known, request contains `content-length` header, otherwise chunked
+/// transfer encoding is used.
+pub struct Form {
+    boundary: String,
+    parts: VecDeque<(Bytes, Part)>,
+    current: Option<Part>,
+    trailer: Option<Bytes>,
+    size: Option<u64>,
+}
+
+impl Form {
+    /// Create new empty form with random boundary.
+    pub fn new() -> Self {
+        let boundary = format!("{:032x}", WyRand::new().generate::<u128>());
+        let trailer = Bytes::from(format!("--{}--\r\n", boundary));
+        Form {
+            size: Some(trailer.len() as u64),
+            trailer: Some(trailer),
+            parts: VecDeque::new(),
+            current: None,
+            boundary,
+        }
+    }
+
+    /// Form boundary.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Value of the `content-type` header for this form.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Add text field.
+    pub fn text<N, V>(self, name: N, value: V) -> Self
+    where
+        N: AsRef<str>,
+        V: Into<String>,
+    {
+        self.part(name, Part::text(value))
+    }
+
+    /// Add part to the form.
+    pub fn part<N: AsRef<str>>(mut self, name: N, part: Part) -> Self {
+        let mut head = String::new();
+        if !self.parts.is_empty() {
+            head.push_str("\r\n");
+        }
+        head.push_str("--");
+        head.push_str(&self.boundary);
+        head.push_str("\r\nContent-Disposition: form-data; name=\"");
+        head.push_str(&escape(name.as_ref()));
+        head.push('"');
+        if let Some(ref file_name) = part.file_name {
+            head.push_str("; filename=\"");
+            head.push_str(&escape(file_name));
+            head.push('"');
+        }
+        head.push_str("\r\n");
+        if let Some(ref mime) = part.mime {
+            head.push_str("Content-Type: ");
+            head.push_str(mime.as_ref());
+            head.push_str("\r\n");
+        } else if let Some(ref file_name) = part.file_name {
+            head.push_str("Content-Type: ");
+            head.push_str(
+                mime_guess::from_path(file_name)
+                    .first_or_octet_stream()
+                    .as_ref(),
+            );
+            head.push_str("\r\n");
+        }
+        head.push_str("\r\n");
+
+        // trailing CRLF of the last part is sent with the trailer
+        if self.parts.is_empty() {
+            let trailer = format!("\r\n--{}--\r\n", self.boundary);
+            self.size = self.size.map(|_| trailer.len() as u64);
+            self.trailer = Some(Bytes::from(trailer));
+        }
+        self.size = match (self.size, part.size()) {
+            (Some(size), Some(len)) => Some(size + head.len() as u64 + len),
+            _ => None,
+        };
+        self.parts.push_back((Bytes::from(head), part));
+        self
+    }
+}
+
+impl Default for Form {
+    fn default() -> Self {
+        Form::new()
+    }
+}
+
+impl fmt::Debug for Form {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Form")
+            .field("boundary", &self.boundary)
+            .field("parts", &self.parts.len())
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl From<Form> for Body {
+    fn from(form: Form) -> Body {
+        Body::from_message(form)
+    }
+}
+
+impl MessageBody for Form {
+    fn size(&self) -> BodySize {
+        match self.size {
+            Some(size) => BodySize::Sized(size),
+            None => BodySize::Stream,
+        }
+    }
+
+    fn poll_next_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Box<dyn Error>>>> {
+        loop {
+            if let Some(ref mut part) = self.current {
+                match ready!(part.body.poll_next_chunk(cx)) {
+                    Some(Ok(chunk)) => {
+                        if chunk.is_empty() {
+                            continue;
+                        }
+                        if let Some(ref mut remaining) = part.length {
+                            if chunk.len() as u64 > *remaining {
+                                return Poll::Ready(Some(Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "Part source is longer than expected length",
+                                )
+                                .into())));
+                            }
+                            *remaining -= chunk.len() as u64;
+                        }
+                        return Poll::Ready(Some(Ok(chunk)));
+                    }
+                    Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                    None => {
+                        if matches!(part.length, Some(n) if n != 0) {
+                            return Poll::Ready(Some(Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "Part source ended before expected length",
+                            )
+                            .into())));
+                        }
+                        self.current = None;
+                    }
+                }
+            }
+
+            return Poll::Ready(if let Some((head, part)) = self.parts.pop_front() {
+                self.current = Some(part);
+                Some(Ok(head))
+            } else {
+                self.trailer.take().map(Ok)
+            });
+        }
+    }
+}
+
+/// Single part of the multipart form
+pub struct Part {
+    body: Body,
+    length: Option<u64>,
+    file_name: Option<String>,
+    mime: Option<mime::Mime>,
+}
+
+impl Part {
+    fn new(body: Body) -> Self {
+        Part {
+            body,
+            length: None,
+            file_name: None,
+            mime: None,
+        }
+    }
+
+    /// Create text part.
+    pub fn text<V: Into<String>>(value: V) -> Self {
+        Part::new(Body::Bytes(Bytes::from(value.into())))
+    }
+
+    /// Create part from bytes.
+    pub fn bytes<V: Into<Bytes>>(value: V) -> Self {
+        Part::new(Body::Bytes(value.into()))
+    }
+
+    /// Create part from any body type.
+    ///
+    /// Part's size is taken from the body size hint.
+    pub fn body<B: Into<Body>>(body: B) -> Self {
+        Part::new(body.into())
+    }
+
+    /// Create streaming part from a stream of bytes.
+    pub fn stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin + 'static,
+        E: Error + 'static,
+    {
+        Part::new(Body::from_message(BodyStream::new(stream)))
+    }
+
+    /// Create streaming part from `AsyncRead` source.
+    pub fn reader<R>(reader: R) -> Self
+    where
+        R: AsyncRead + Unpin + 'static,
+    {
+        Part::new(Body::from_message(BodyFromRead::new(reader)))
+    }
+
+    /// Set part length.
+    ///
+    /// Source must produce exactly `len` bytes, otherwise form fails.
+    /// If length of all parts is known, form is sent with `content-length`
+    /// header instead of chunked transfer encoding.
+    pub fn length(mut self, len: u64) -> Self {
+        self.length = Some(len);
+        self
+    }
+
+    /// Set file name of the part.
+    ///
+    /// If content type is not set, it is guessed from file name.
+    pub fn file_name<T: Into<String>>(mut self, name: T) -> Self {
+        self.file_name = Some(name.into());
+        self
+    }
+
+    /// Set content type of the part.
+    pub fn content_type(mut self, mime: mime::Mime) -> Self {
+        self.mime = Some(mime);
+        self
+    }
+
+    fn size(&self) -> Option<u64> {
+        if let Some(len) = self.length {
+            Some(len)
+        } else {
+            match self.body.size() {
+                BodySize::None | BodySize::Empty => Some(0),
+                BodySize::Sized(len) => Some(len),
+                BodySize::Stream => None,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Part")
+            .field("size", &self.size())
+            .field("file_name", &self.file_name)
+            .field("content_type", &self.mime)
+            .finish()
+    }
+}
+
+/// Escape quotes and line breaks in field and file names
+fn escape(s: &str) -> String {
+    s.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{poll_fn, BytesMut};
+
+    async fn read(mut form: Form) -> Result<Bytes, Box<dyn Error>> {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = poll_fn(|cx| form.poll_next_chunk(cx)).await {
+            buf.extend_from_slice(&chunk?);
+        }
+        Ok(buf.freeze())
+    }
+
+    #[crate::rt_test]
+    async fn test_form() {
+        let form = Form::new();
+        let boundary = form.boundary().to_string();
+        assert_eq!(boundary.len(), 32);
+        assert_eq!(
+            form.content_type(),
+            format!("multipart/form-data; boundary={}", boundary)
+        );
+        assert_eq!(form.size(), BodySize::Sized(boundary.len() as u64 + 6));
+        assert_eq!(read(form).await.unwrap(), format!("--{}--\r\n", boundary));
+
+        let form = Form::new()
+            .text("name", "ntex")
+            .part(
+                "file",
+                Part::bytes(Bytes::from_static(b"data")).file_name("a\"b.txt"),
+            )
+            .part("bin", Part::text("{}").content_type(mime::APPLICATION_JSON));
+        let boundary = form.boundary().to_string();
+        let expected = format!(
+            "--{0}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nntex\r\n\
+             --{0}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a%22b.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\ndata\r\n\
+             --{0}\r\nContent-Disposition: form-data; name=\"bin\"\r\n\
+             Content-Type: application/json\r\n\r\n{{}}\r\n--{0}--\r\n",
+            boundary
+        );
+        assert_eq!(form.size(), BodySize::Sized(expected.len() as u64));
+        assert_eq!(read(form).await.unwrap(), expected);
+    }
+
+    #[crate::rt_test]
+    async fn test_streaming_part() {
+        let chunks = || {
+            futures_util::stream::iter(vec![
+                Ok::<_, io::Error>(Bytes::from_static(b"chunk1")),
+                Ok(Bytes::new()),
+                Ok(Bytes::from_static(b"chunk2")),
+            ])
+        };
+
+        let form = Form::new().part("file", Part::stream(chunks()));
+        assert_eq!(form.size(), BodySize::Stream);
+        let body = read(form).await.unwrap();
+        let s = std::str::from_utf8(&body).unwrap();
+        assert!(s.contains("\r\n\r\nchunk1chunk2\r\n--"));
+
+        let form = Form::new().part("file", Part::stream(chunks()).length(12));
+        let size = form.size();
+        let body = read(form).await.unwrap();
+        assert_eq!(size, BodySize::Sized(body.len() as u64));
+
+        let form = Form::new().part("file", Part::stream(chunks()).length(13));
+        assert!(read(form).await.is_err());
+        let form = Form::new().part("file", Part::stream(chunks()).length(11));
+        assert!(read(form).await.is_err());
+
+        let form =
+            Form::new().part("file", Part::reader(&b"reader"[..]).file_name("test.bin"));
+        assert_eq!(form.size(), BodySize::Stream);
+        let body = read(form).await.unwrap();
+        let s = std::str::from_utf8(&body).unwrap();
+        assert!(s.contains("Content-Type: application/octet-stream\r\n\r\nreader\r\n"));
+
+        let form = Form::new().part("empty", Part::body(Body::Empty));
+        let size = form.size();
+        let body = read(form).await.unwrap();
+        assert_eq!(size, BodySize::Sized(body.len() as u64));
+    }
+}
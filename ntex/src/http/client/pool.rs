@@ -8,7 +8,7 @@ use ntex_tls::types::HttpProtocol;
 
 use crate::io::{IoBoxed, TokioIoBoxed};
 use crate::time::{now, Millis};
-use crate::util::{ready, Bytes, HashMap, HashSet};
+use crate::util::{poll_fn, ready, Bytes, HashMap, HashSet};
 use crate::{channel::pool, rt::spawn, service::Service, task::LocalWaker};
 
 use super::connection::{Connection, ConnectionType, H2Sender};
@@ -59,6 +59,7 @@ where
         conn_keep_alive: Duration,
         disconnect_timeout: Millis,
         limit: usize,
+        h2config: Builder,
     ) -> Self {
         let connector = Rc::new(connector);
         let waiters = Rc::new(RefCell::new(Waiters {
@@ -70,6 +71,7 @@ where
             conn_keep_alive,
             disconnect_timeout,
             limit,
+            h2config,
             acquired: 0,
             available: HashMap::default(),
             connecting: HashSet::default(),
@@ -186,6 +188,7 @@ pub(super) struct Inner {
     conn_keep_alive: Duration,
     disconnect_timeout: Millis,
     limit: usize,
+    h2config: Builder,
     acquired: usize,
     available: HashMap<Key, VecDeque<AvailableConnection>>,
     connecting: HashSet<Key>,
@@ -248,6 +251,8 @@ impl Inner {
         // cleanup stale connections at the same time
         if let Some(ref mut connections) = self.available.get_mut(key) {
             let now = now();
+            let mut busy = Vec::new();
+            let mut result = None;
             while let Some(conn) = connections.pop_back() {
                 // check if it still usable
                 if (now - conn.used) > self.conn_keep_alive
@@ -261,6 +266,14 @@ impl Inner {
                     continue;
                 }
 
+                // all streams are in use, use other connection
+                if let ConnectionType::H2(ref s) = conn.io {
+                    if !s.is_closed() && !s.is_available() {
+                        busy.push(conn);
+                        continue;
+                    }
+                }
+
                 let io = conn.io;
 
                 match io {
@@ -292,7 +305,14 @@ impl Inner {
                         connections.push_front(conn);
                     }
                 }
-                return Acquire::Acquired(io, conn.created);
+                result = Some(Acquire::Acquired(io, conn.created));
+                break;
+            }
+            for conn in busy {
+                connections.push_front(conn);
+            }
+            if let Some(result) = result {
+                return result;
             }
         }
 
@@ -413,6 +433,7 @@ struct OpenConnection<F> {
     tx: Option<Waiter>,
     guard: Option<OpenGuard>,
     disconnect_timeout: Millis,
+    h2config: Builder,
     inner: Rc<RefCell<Inner>>,
 }
 
@@ -423,9 +444,11 @@ where
     fn spawn(key: Key, tx: Waiter, inner: Rc<RefCell<Inner>>, fut: F) {
         inner.borrow_mut().connecting.insert(key.clone());
         let disconnect_timeout = inner.borrow().disconnect_timeout;
+        let h2config = inner.borrow().h2config.clone();
 
         spawn(OpenConnection {
             fut,
+            h2config,
             disconnect_timeout,
             h2: None,
             tx: Some(tx),
@@ -451,7 +474,7 @@ where
         // handle http2 connection
         if let Some(ref mut h2) = this.h2 {
             return match ready!(Pin::new(h2).poll(cx)) {
-                Ok((snd, connection)) => {
+                Ok((snd, mut connection)) => {
                     // h2 connection is ready
                     let h2 = H2Sender::new(snd);
                     let conn = Connection::new(
@@ -471,7 +494,13 @@ where
 
                     let key = this.key.clone();
                     spawn(async move {
-                        let res = connection.await;
+                        let res = poll_fn(|cx| {
+                            let res = Pin::new(&mut connection).poll(cx);
+                            // peer could change max concurrent streams at any time
+                            h2.set_max_streams(connection.max_concurrent_send_streams());
+                            res
+                        })
+                        .await;
                         h2.close();
                         log::trace!(
                             "Http/2 connection is closed for {:?} with {:?}",
@@ -507,7 +536,7 @@ where
                     log::trace!("Connection is established, start http2 handshake");
                     // init http2 handshake
                     this.h2 =
-                        Some(Box::pin(Builder::new().handshake(TokioIoBoxed::from(io))));
+                        Some(Box::pin(this.h2config.handshake(TokioIoBoxed::from(io))));
                     self.poll(cx)
                 } else {
                     log::trace!("Connection is established, init http1 connection");
@@ -610,6 +639,7 @@ mod tests {
             Duration::from_secs(10),
             Millis::ZERO,
             1,
+            Builder::new(),
         )
         .clone();
 
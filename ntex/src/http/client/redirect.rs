@@ -0,0 +1,241 @@
+//! Redirects handling
+use std::{io, net, rc::Rc};
+
+use crate::http::body::Body;
+use crate::http::header::{self, HeaderMap};
+use crate::http::{uri, Method, RequestHead, RequestHeadType, StatusCode, Uri};
+
+use super::error::{ConnectError, SendRequestError};
+use super::response::ClientResponse;
+use super::ClientConfig;
+
+/// Send request and follow redirect responses.
+///
+/// `303` response changes request method to `GET` (except for `HEAD` requests),
+/// `301` and `302` responses change only `POST` to `GET`, request body is dropped
+/// if method is changed. `307` and `308` responses preserve method and body,
+/// streaming body cannot be sent again so redirect response is returned as is.
+/// Credentials are not sent to other origins. If max number of redirects
+/// is reached, last redirect response is returned.
+///
+/// Server could close connection right after redirect response, for example
+/// if request body is not consumed. If redirected request fails because
+/// reused connection is closed, request is sent once more.
+pub(super) async fn send(
+    mut head: RequestHeadType,
+    mut body: Body,
+    mut addr: Option<net::SocketAddr>,
+    config: Rc<ClientConfig>,
+) -> Result<ClientResponse, SendRequestError> {
+    let mut redirects = 0;
+
+    loop {
+        let prev = Snapshot::new(&head, &body);
+        let res = match config.send_request(head, body, addr).await {
+            Err(err) if redirects > 0 && is_disconnected(&err) => {
+                log::trace!("Connection is closed, retry redirect to {}", prev.uri);
+                let head = prev.head(prev.uri.clone(), prev.method.clone());
+                let body = prev.body.as_ref().map_or(Body::None, clone_body);
+                config
+                    .send_request(RequestHeadType::Owned(head), body, addr)
+                    .await?
+            }
+            res => res?,
+        };
+
+        if redirects >= config.max_redirects {
+            return Ok(res);
+        }
+        let status = res.status();
+        let keep_method = match status {
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => {
+                prev.method != Method::POST
+            }
+            StatusCode::SEE_OTHER => prev.method == Method::HEAD,
+            StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => true,
+            _ => return Ok(res),
+        };
+        let location = match res
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|loc| resolve(&prev.uri, loc))
+        {
+            Some(uri) => uri,
+            None => return Ok(res),
+        };
+
+        let (mut next, next_body) = if keep_method {
+            match prev.body {
+                Some(ref b) => (prev.head(location, prev.method.clone()), clone_body(b)),
+                // streaming body cannot be sent again
+                None => return Ok(res),
+            }
+        } else {
+            let method = if prev.method == Method::HEAD {
+                Method::HEAD
+            } else {
+                Method::GET
+            };
+            let mut next = prev.head(location, method);
+            next.headers.remove(header::CONTENT_TYPE);
+            next.headers.remove(header::CONTENT_LENGTH);
+            next.headers.remove(header::CONTENT_ENCODING);
+            (next, Body::None)
+        };
+
+        // do not leak credentials to other origins
+        if !same_origin(&prev.uri, &next.uri) {
+            next.headers.remove(header::AUTHORIZATION);
+            next.headers.remove(header::PROXY_AUTHORIZATION);
+            next.headers.remove(header::COOKIE);
+            next.headers.remove(header::HOST);
+            addr = None;
+        }
+        log::trace!("Following redirect {:?} to {}", status, next.uri);
+
+        redirects += 1;
+        head = RequestHeadType::Owned(next);
+        body = next_body;
+    }
+}
+
+/// Request data required for redirect
+struct Snapshot {
+    head: RequestHeadType,
+    uri: Uri,
+    method: Method,
+    body: Option<Body>,
+}
+
+impl Snapshot {
+    fn new(head: &RequestHeadType, body: &Body) -> Self {
+        let h = head.as_ref();
+        let head = match head {
+            RequestHeadType::Rc(head, extra) => {
+                RequestHeadType::Rc(head.clone(), extra.clone())
+            }
+            RequestHeadType::Owned(head) => RequestHeadType::Owned(RequestHead {
+                version: head.version,
+                flags: head.flags,
+                headers: head.headers.clone(),
+                ..Default::default()
+            }),
+        };
+        Snapshot {
+            head,
+            uri: h.uri.clone(),
+            method: h.method.clone(),
+            body: match body {
+                Body::None | Body::Empty | Body::Bytes(_) => Some(clone_body(body)),
+                Body::Message(_) => None,
+            },
+        }
+    }
+
+    /// Build request head for redirected request
+    fn head(&self, uri: Uri, method: Method) -> RequestHead {
+        let src = self.head.as_ref();
+        let mut head = RequestHead {
+            uri,
+            method,
+            version: src.version,
+            flags: src.flags,
+            headers: src.headers.clone(),
+            ..Default::default()
+        };
+        if let Some(extra) = self.head.extra_headers() {
+            merge(&mut head.headers, extra);
+        }
+        head
+    }
+}
+
+fn merge(headers: &mut HeaderMap, extra: &HeaderMap) {
+    for (key, value) in extra.iter() {
+        headers.insert(key.clone(), value.clone());
+    }
+}
+
+fn clone_body(body: &Body) -> Body {
+    match body {
+        Body::Empty => Body::Empty,
+        Body::Bytes(b) => Body::Bytes(b.clone()),
+        _ => Body::None,
+    }
+}
+
+/// Check if peer closed connection before response is received
+fn is_disconnected(err: &SendRequestError) -> bool {
+    match err {
+        SendRequestError::Connect(ConnectError::Disconnected(_)) => true,
+        SendRequestError::Send(err) => matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// Resolve `Location` header value against request uri
+fn resolve(base: &Uri, location: &str) -> Option<Uri> {
+    let location = location.trim().split('#').next()?;
+    let path = if location.starts_with("//") {
+        return format!("{}:{}", base.scheme_str()?, location).parse().ok();
+    } else if location.starts_with('/') {
+        location.to_string()
+    } else if location.contains("://") {
+        return location.parse().ok();
+    } else {
+        let path = base.path();
+        let dir = &path[..path.rfind('/').map_or(0, |idx| idx + 1)];
+        format!("{}{}", dir, location)
+    };
+
+    let mut parts = uri::Parts::default();
+    parts.scheme = base.scheme().cloned();
+    parts.authority = base.authority().cloned();
+    parts.path_and_query = Some(path.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+fn same_origin(a: &Uri, b: &Uri) -> bool {
+    a.scheme() == b.scheme() && a.host() == b.host() && port(a) == port(b)
+}
+
+fn port(uri: &Uri) -> Option<u16> {
+    uri.port_u16().or_else(|| match uri.scheme_str() {
+        Some("http") | Some("ws") => Some(80),
+        Some("https") | Some("wss") => Some(443),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let base: Uri = "http://localhost:8080/a/b?q=1".parse().unwrap();
+        let r = |loc| resolve(&base, loc).map(|u| u.to_string());
+
+        assert_eq!(r("/c").unwrap(), "http://localhost:8080/c");
+        assert_eq!(r("/c?x=1#frag").unwrap(), "http://localhost:8080/c?x=1");
+        assert_eq!(r("c").unwrap(), "http://localhost:8080/a/c");
+        assert_eq!(r("//example.com/d").unwrap(), "http://example.com/d");
+        assert_eq!(r("https://example.com/e").unwrap(), "https://example.com/e");
+        assert!(r("http://exa mple.com").is_none());
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let u = |s: &str| s.parse::<Uri>().unwrap();
+        assert!(same_origin(&u("http://a.com/x"), &u("http://a.com:80/y")));
+        assert!(!same_origin(&u("http://a.com/x"), &u("https://a.com/x")));
+        assert!(!same_origin(&u("http://a.com/x"), &u("http://b.com/x")));
+        assert!(!same_origin(&u("http://a.com/x"), &u("http://a.com:81/x")));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{convert::TryFrom, error::Error, fmt, net, rc::Rc};
+use std::{cmp, convert::TryFrom, error::Error, fmt, net, rc::Rc};
 
 #[cfg(feature = "cookie")]
 use coo_kie::{Cookie, CookieJar};
@@ -6,18 +6,22 @@ use serde::Serialize;
 
 use crate::http::body::Body;
 use crate::http::error::HttpError;
+use crate::http::h1::HeaderCaseMap;
 use crate::http::header::{self, HeaderMap, HeaderName, HeaderValue};
 use crate::http::{
     uri, ConnectionType, Method, RequestHead, RequestHeadType, Uri, Version,
 };
-use crate::{time::Millis, util::Bytes, util::Stream};
+use crate::{time::Millis, util::deadline::Deadline, util::Bytes, util::Stream};
 
 use super::error::{FreezeRequestError, InvalidUrl};
 use super::frozen::FrozenClientRequest;
+use super::multipart::Form;
 use super::sender::{PrepForSendingError, SendClientRequest};
 use super::ClientConfig;
 
-#[cfg(feature = "compress")]
+#[cfg(feature = "zstd")]
+const HTTPS_ENCODING: &str = "zstd, br, gzip, deflate";
+#[cfg(all(feature = "compress", not(feature = "zstd")))]
 const HTTPS_ENCODING: &str = "br, gzip, deflate";
 #[cfg(not(feature = "compress"))]
 const HTTPS_ENCODING: &str = "br";
@@ -303,6 +307,26 @@ impl ClientRequest {
         self
     }
 
+    /// Set original casing of request header names.
+    ///
+    /// Casing is used by http/1 connections if header case mode
+    /// is enabled with `ClientBuilder::h1_header_case()` method.
+    pub fn header_case_map(self, map: HeaderCaseMap) -> Self {
+        self.head.extensions_mut().insert(map);
+        self
+    }
+
+    /// Mark request as idempotent.
+    ///
+    /// Idempotent requests could be retried by client middlewares, for
+    /// example `POST` request with idempotency key. Requests with `GET`,
+    /// `HEAD`, `PUT`, `DELETE`, `OPTIONS` and `TRACE` methods are
+    /// idempotent by default.
+    pub fn idempotent(mut self) -> Self {
+        self.head.set_idempotent(true);
+        self
+    }
+
     /// Disable automatic decompress of response's body
     pub fn no_decompress(mut self) -> Self {
         self.response_decompress = false;
@@ -318,6 +342,15 @@ impl ClientRequest {
         self
     }
 
+    /// Set request timeout to remaining time budget of the deadline.
+    ///
+    /// Use it for propagating deadline of incoming request to outbound calls.
+    /// If deadline is already elapsed, minimal timeout is used.
+    pub fn deadline(self, deadline: Deadline) -> Self {
+        let millis = cmp::min(deadline.remaining().as_millis(), u32::MAX as u128) as u32;
+        self.timeout(Millis(cmp::max(millis, 1)))
+    }
+
     /// This method calls provided closure with builder reference if
     /// value is `true`.
     pub fn if_true<F>(self, value: bool, f: F) -> Self
@@ -398,7 +431,7 @@ impl ClientRequest {
             slf.addr,
             slf.response_decompress,
             slf.timeout,
-            slf.config.as_ref(),
+            &slf.config,
             body,
         )
     }
@@ -414,7 +447,7 @@ impl ClientRequest {
             slf.addr,
             slf.response_decompress,
             slf.timeout,
-            slf.config.as_ref(),
+            &slf.config,
             value,
         )
     }
@@ -432,11 +465,30 @@ impl ClientRequest {
             slf.addr,
             slf.response_decompress,
             slf.timeout,
-            slf.config.as_ref(),
+            &slf.config,
             value,
         )
     }
 
+    /// Set a `multipart/form-data` body and generate `ClientRequest`
+    ///
+    /// Form parts are streamed, content type header is set
+    /// unless it is already present.
+    pub fn send_multipart(self, form: Form) -> SendClientRequest {
+        let slf = match self.prep_for_sending() {
+            Ok(slf) => slf,
+            Err(e) => return e.into(),
+        };
+
+        RequestHeadType::Owned(slf.head).send_multipart(
+            slf.addr,
+            slf.response_decompress,
+            slf.timeout,
+            &slf.config,
+            form,
+        )
+    }
+
     /// Set an streaming body and generate `ClientRequest`.
     pub fn send_stream<S, E>(self, stream: S) -> SendClientRequest
     where
@@ -452,7 +504,7 @@ impl ClientRequest {
             slf.addr,
             slf.response_decompress,
             slf.timeout,
-            slf.config.as_ref(),
+            &slf.config,
             stream,
         )
     }
@@ -468,7 +520,7 @@ impl ClientRequest {
             slf.addr,
             slf.response_decompress,
             slf.timeout,
-            slf.config.as_ref(),
+            &slf.config,
         )
     }
 
@@ -602,6 +654,19 @@ mod tests {
         let _ = req.send_body("");
     }
 
+    #[crate::rt_test]
+    async fn test_deadline() {
+        let req = Client::new()
+            .get("/")
+            .deadline(Deadline::after(Millis(500)));
+        assert!(req.timeout <= Millis(500) && req.timeout > Millis(400));
+
+        let req = Client::new()
+            .get("/")
+            .deadline(Deadline::after(Millis::ZERO));
+        assert_eq!(req.timeout, Millis(1));
+    }
+
     #[crate::rt_test]
     async fn test_client_header() {
         let req = Client::build()
@@ -1,5 +1,5 @@
 use std::task::{Context, Poll};
-use std::{convert::TryFrom, error::Error, future::Future, net, pin::Pin};
+use std::{convert::TryFrom, error::Error, future::Future, net, pin::Pin, rc::Rc};
 
 use serde::Serialize;
 
@@ -16,6 +16,8 @@ use crate::http::encoding::Decoder;
 use crate::http::Payload;
 
 use super::error::{FreezeRequestError, InvalidUrl, SendRequestError};
+use super::multipart::Form;
+use super::redirect;
 use super::response::ClientResponse;
 use super::ClientConfig;
 
@@ -132,7 +134,7 @@ impl RequestHeadType {
         addr: Option<net::SocketAddr>,
         response_decompress: bool,
         mut timeout: Millis,
-        config: &ClientConfig,
+        config: &Rc<ClientConfig>,
         body: B,
     ) -> SendClientRequest
     where
@@ -142,11 +144,12 @@ impl RequestHeadType {
             timeout = config.timeout;
         }
 
-        SendClientRequest::new(
-            config.connector.send_request(self, body.into(), addr),
-            response_decompress,
-            timeout,
-        )
+        let fut = if config.max_redirects == 0 {
+            config.send_request(self, body.into(), addr)
+        } else {
+            Box::pin(redirect::send(self, body.into(), addr, config.clone()))
+        };
+        SendClientRequest::new(fut, response_decompress, timeout)
     }
 
     pub(super) fn send_json<T: Serialize>(
@@ -154,7 +157,7 @@ impl RequestHeadType {
         addr: Option<net::SocketAddr>,
         response_decompress: bool,
         timeout: Millis,
-        config: &ClientConfig,
+        config: &Rc<ClientConfig>,
         value: &T,
     ) -> SendClientRequest {
         let body = match serde_json::to_string(value) {
@@ -180,7 +183,7 @@ impl RequestHeadType {
         addr: Option<net::SocketAddr>,
         response_decompress: bool,
         timeout: Millis,
-        config: &ClientConfig,
+        config: &Rc<ClientConfig>,
         value: &T,
     ) -> SendClientRequest {
         let body = match serde_urlencoded::to_string(value) {
@@ -209,7 +212,7 @@ impl RequestHeadType {
         addr: Option<net::SocketAddr>,
         response_decompress: bool,
         timeout: Millis,
-        config: &ClientConfig,
+        config: &Rc<ClientConfig>,
         stream: S,
     ) -> SendClientRequest
     where
@@ -225,12 +228,34 @@ impl RequestHeadType {
         )
     }
 
+    pub(super) fn send_multipart(
+        mut self,
+        addr: Option<net::SocketAddr>,
+        response_decompress: bool,
+        timeout: Millis,
+        config: &Rc<ClientConfig>,
+        form: Form,
+    ) -> SendClientRequest {
+        // set content-type
+        if let Err(e) = self.set_header_if_none(header::CONTENT_TYPE, form.content_type()) {
+            return e.into();
+        }
+
+        self.send_body(
+            addr,
+            response_decompress,
+            timeout,
+            config,
+            Body::from_message(form),
+        )
+    }
+
     pub(super) fn send(
         self,
         addr: Option<net::SocketAddr>,
         response_decompress: bool,
         timeout: Millis,
-        config: &ClientConfig,
+        config: &Rc<ClientConfig>,
     ) -> SendClientRequest {
         self.send_body(addr, response_decompress, timeout, config, Body::None)
     }
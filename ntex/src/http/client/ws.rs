@@ -0,0 +1,38 @@
+//! Websockets client
+//!
+//! ```rust,no_run
+//! use ntex::http::client::ws;
+//!
+//! #[ntex::main]
+//! async fn main() -> Result<(), ws::WsClientError> {
+//!     let conn = ws::connect("wss://echo.websocket.org").await?;
+//!     let sink = conn.sink();
+//!     let rx = conn.receiver();
+//!
+//!     sink.send(ws::Message::Text("hello".into())).await?;
+//!     while let Some(frame) = rx.recv().await {
+//!         println!("Frame: {:?}", frame);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+use std::convert::TryFrom;
+
+pub use crate::ws::error::{WsClientBuilderError, WsClientError};
+pub use crate::ws::{Frame, Message, WsClient, WsClientBuilder, WsConnection, WsSink};
+
+use crate::http::{error::HttpError, Uri};
+use crate::io::Sealed;
+
+/// Connect to a websockets server.
+///
+/// Performs websockets handshake, tls connection is used for `wss` urls.
+/// Use `WsClient::build()` and `WsClientBuilder::connect()` for
+/// sub-protocols negotiation and request customization.
+pub async fn connect<U>(uri: U) -> Result<WsConnection<Sealed>, WsClientError>
+where
+    Uri: TryFrom<U>,
+    <Uri as TryFrom<U>>::Error: Into<HttpError>,
+{
+    WsClient::build(uri).connect().await
+}
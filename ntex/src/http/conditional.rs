@@ -0,0 +1,181 @@
+//! Conditional requests evaluation (RFC 7232)
+use httpdate::HttpDate;
+
+use super::header::{self, HeaderMap, HeaderName};
+use super::Method;
+
+/// Result of preconditions evaluation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Precondition {
+    /// Request could be processed
+    Passed,
+    /// Respond with `304 Not Modified`
+    NotModified,
+    /// Respond with `412 Precondition Failed`
+    Failed,
+}
+
+/// Evaluate request preconditions against selected representation
+pub(crate) fn evaluate(
+    headers: &HeaderMap,
+    method: &Method,
+    etag: Option<&str>,
+    last_modified: Option<HttpDate>,
+) -> Precondition {
+    let failed = if let Some(val) = header_str(headers, &header::IF_MATCH) {
+        !etag.map_or(false, |e| etag_matches(val, e, false))
+    } else if let (Some(lm), Some(since)) = (
+        last_modified,
+        header_date(headers, &header::IF_UNMODIFIED_SINCE),
+    ) {
+        lm > since
+    } else {
+        false
+    };
+    if failed {
+        return Precondition::Failed;
+    }
+
+    let not_modified = if let Some(val) = header_str(headers, &header::IF_NONE_MATCH) {
+        etag.map_or(false, |e| etag_matches(val, e, true))
+    } else if let (Some(lm), Some(since)) = (
+        last_modified,
+        header_date(headers, &header::IF_MODIFIED_SINCE),
+    ) {
+        lm <= since
+    } else {
+        false
+    };
+
+    if !not_modified {
+        Precondition::Passed
+    } else if method == Method::GET || method == Method::HEAD {
+        Precondition::NotModified
+    } else {
+        Precondition::Failed
+    }
+}
+
+/// `If-Range` is either strong entity tag or exact modification date
+pub(crate) fn if_range_matches(
+    headers: &HeaderMap,
+    etag: Option<&str>,
+    last_modified: Option<HttpDate>,
+) -> bool {
+    if let Some(val) = header_str(headers, &header::IF_RANGE) {
+        let val = val.trim();
+        if val.starts_with('"') || val.starts_with("W/") {
+            etag.map_or(false, |etag| etag_matches(val, etag, false))
+        } else {
+            last_modified.is_some() && val.parse().ok() == last_modified
+        }
+    } else {
+        true
+    }
+}
+
+pub(crate) fn header_str<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+pub(crate) fn header_date(headers: &HeaderMap, name: &HeaderName) -> Option<HttpDate> {
+    header_str(headers, name).and_then(|v| v.trim().parse().ok())
+}
+
+/// Check if list of entity tags matches etag
+pub(crate) fn etag_matches(header: &str, etag: &str, weak: bool) -> bool {
+    header.split(',').map(|s| s.trim()).any(|tag| {
+        if tag == "*" {
+            true
+        } else if weak {
+            tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        } else {
+            !tag.starts_with("W/") && !etag.starts_with("W/") && tag == etag
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header::HeaderValue;
+    use std::time::{Duration, SystemTime};
+
+    fn headers(items: &[(HeaderName, &str)]) -> HeaderMap {
+        let mut hdrs = HeaderMap::new();
+        for (name, val) in items {
+            hdrs.insert(name.clone(), HeaderValue::from_str(val).unwrap());
+        }
+        hdrs
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("\"a\"", "\"a\"", false));
+        assert!(etag_matches("\"b\", \"a\"", "\"a\"", false));
+        assert!(etag_matches("*", "\"a\"", false));
+        assert!(!etag_matches("W/\"a\"", "\"a\"", false));
+        assert!(!etag_matches("\"a\"", "W/\"a\"", false));
+        assert!(etag_matches("W/\"a\"", "\"a\"", true));
+        assert!(!etag_matches("\"b\"", "\"a\"", true));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let get = Method::GET;
+        let now = SystemTime::now();
+        let lm = HttpDate::from(now - Duration::from_secs(60));
+        let since = HttpDate::from(now).to_string();
+        let before = HttpDate::from(now - Duration::from_secs(120)).to_string();
+
+        let hdrs = headers(&[]);
+        assert_eq!(
+            evaluate(&hdrs, &get, Some("\"a\""), Some(lm)),
+            Precondition::Passed
+        );
+
+        let hdrs = headers(&[(header::IF_NONE_MATCH, "W/\"a\"")]);
+        assert_eq!(
+            evaluate(&hdrs, &get, Some("\"a\""), None),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate(&hdrs, &Method::HEAD, Some("\"a\""), None),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate(&hdrs, &Method::POST, Some("\"a\""), None),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(&hdrs, &get, Some("\"b\""), None),
+            Precondition::Passed
+        );
+
+        // if-none-match takes precedence over if-modified-since
+        let hdrs = headers(&[
+            (header::IF_NONE_MATCH, "\"b\""),
+            (header::IF_MODIFIED_SINCE, &since),
+        ]);
+        assert_eq!(
+            evaluate(&hdrs, &get, Some("\"a\""), Some(lm)),
+            Precondition::Passed
+        );
+
+        let hdrs = headers(&[(header::IF_MODIFIED_SINCE, &since)]);
+        assert_eq!(
+            evaluate(&hdrs, &get, None, Some(lm)),
+            Precondition::NotModified
+        );
+        let hdrs = headers(&[(header::IF_MODIFIED_SINCE, &before)]);
+        assert_eq!(evaluate(&hdrs, &get, None, Some(lm)), Precondition::Passed);
+
+        let hdrs = headers(&[(header::IF_MATCH, "\"b\"")]);
+        assert_eq!(
+            evaluate(&hdrs, &get, Some("\"a\""), None),
+            Precondition::Failed
+        );
+        let hdrs = headers(&[(header::IF_UNMODIFIED_SINCE, &before)]);
+        assert_eq!(evaluate(&hdrs, &get, None, Some(lm)), Precondition::Failed);
+    }
+}
@@ -1,8 +1,14 @@
-use std::{cell::Cell, ptr::copy_nonoverlapping, rc::Rc, time, time::Duration};
+use std::time::Duration;
+use std::{cell::Cell, cell::RefCell, ptr::copy_nonoverlapping, rc::Rc, time};
 
-use crate::http::{Request, Response};
+use crate::http::message::set_message_pool_capacity;
+use crate::http::{h1::HeadLimits, h1::HeaderCase, MemoryBudget, Request, Response};
 use crate::time::{now, sleep, Millis, Seconds, Sleep};
-use crate::{io::IoRef, service::boxed::BoxService, util::BytesMut};
+use crate::util::{BytesMut, Extensions};
+use crate::{io::IoRef, service::boxed::BoxService};
+
+/// Default max size of buffered request data per connection
+pub(super) const DEFAULT_CONN_MEMORY_LIMIT: usize = 32_768;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 /// Server keep-alive setting
@@ -37,6 +43,15 @@ impl From<Option<usize>> for KeepAlive {
     }
 }
 
+/// Request and connection timeouts, zero value disables timeout
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct Timeouts {
+    pub(super) request_head: Millis,
+    pub(super) request_body: Millis,
+    pub(super) response_write: Millis,
+    pub(super) idle_connection: Millis,
+}
+
 /// Http service configuration
 pub struct ServiceConfig(pub(super) Rc<Inner>);
 
@@ -47,6 +62,15 @@ pub(super) struct Inner {
     pub(super) ka_enabled: bool,
     pub(super) timer: DateService,
     pub(super) ssl_handshake_timeout: Millis,
+    pub(super) header_case: Cell<HeaderCase>,
+    pub(super) head_limits: Cell<HeadLimits>,
+    pub(super) strict_parsing: Cell<bool>,
+    pub(super) catch_panics: Cell<bool>,
+    pub(super) timeouts: Cell<Timeouts>,
+    pub(super) on_connect: RefCell<Option<OnConnect>>,
+    pub(super) memory_budget: RefCell<Option<MemoryBudget>>,
+    pub(super) conn_memory_limit: Cell<usize>,
+    pub(super) message_pool: Cell<Option<usize>>,
 }
 
 impl Clone for ServiceConfig {
@@ -87,13 +111,223 @@ impl ServiceConfig {
             client_timeout,
             client_disconnect,
             ssl_handshake_timeout,
-            timer: DateService::new(),
+            timer: DateService::default(),
+            header_case: Cell::new(HeaderCase::Lower),
+            head_limits: Cell::new(HeadLimits::default()),
+            strict_parsing: Cell::new(false),
+            catch_panics: Cell::new(true),
+            timeouts: Cell::new(Timeouts::default()),
+            on_connect: RefCell::new(None),
+            memory_budget: RefCell::new(None),
+            conn_memory_limit: Cell::new(DEFAULT_CONN_MEMORY_LIMIT),
+            message_pool: Cell::new(None),
         }))
     }
+
+    /// Set header names casing mode for h1 responses.
+    ///
+    /// By default header names are encoded in lowercase.
+    pub fn h1_header_case(self, case: HeaderCase) -> Self {
+        self.0.header_case.set(case);
+        self
+    }
+
+    /// Set max number of request headers.
+    ///
+    /// Requests with more headers are rejected with
+    /// `431 Request Header Fields Too Large` response.
+    /// By default max number of headers is 96.
+    pub fn max_header_count(self, num: usize) -> Self {
+        self.update_limits(|limits| limits.max_headers = num);
+        self
+    }
+
+    /// Set max size of request head, request line and headers.
+    ///
+    /// Requests with larger head are rejected with
+    /// `431 Request Header Fields Too Large` response.
+    /// By default max size is 32Kb.
+    pub fn max_header_size(self, size: usize) -> Self {
+        self.update_limits(|limits| limits.max_head_size = size);
+        self
+    }
+
+    /// Set max length of request uri.
+    ///
+    /// Requests with longer uri are rejected with `414 URI Too Long` response.
+    /// By default max length is 32Kb.
+    pub fn max_uri_length(self, len: usize) -> Self {
+        self.update_limits(|limits| limits.max_uri_length = len);
+        self
+    }
+
+    /// Enable strict http/1 request parsing mode.
+    ///
+    /// In strict mode requests with both `Content-Length` and `Transfer-Encoding`
+    /// headers, multiple `Content-Length` headers, obs-fold header continuation
+    /// lines or line endings other than CRLF are rejected with
+    /// `400 Bad Request` response.
+    ///
+    /// By default strict mode is disabled.
+    pub fn strict_parsing(self, strict: bool) -> Self {
+        self.0.strict_parsing.set(strict);
+        self
+    }
+
+    /// Catch panics of http/1 service calls.
+    ///
+    /// Panic is converted to `500 Internal Server Error` response and
+    /// connection is closed after response is sent. If disabled, panic
+    /// tears down connection's task.
+    ///
+    /// By default panics are caught.
+    pub fn catch_panics(self, enabled: bool) -> Self {
+        self.0.catch_panics.set(enabled);
+        self
+    }
+
+    /// Set request head read timeout.
+    ///
+    /// Defines a timeout for reading request head after the first byte of
+    /// the request is received. If a client does not transmit the entire set
+    /// of headers within this time, the request is terminated with
+    /// `408 Request Timeout` response. Applies to every request on the connection.
+    ///
+    /// By default timeout is disabled.
+    pub fn request_head_timeout(self, timeout: Seconds) -> Self {
+        self.update_timeouts(|t| t.request_head = timeout.into());
+        self
+    }
+
+    /// Set request body read timeout.
+    ///
+    /// Defines a timeout for reading request body. If a client does not transmit
+    /// the entire body within this time, request payload is terminated with error,
+    /// and connection is closed. If response is not sent yet, `408 Request Timeout`
+    /// response is sent.
+    ///
+    /// By default timeout is disabled.
+    pub fn request_body_timeout(self, timeout: Seconds) -> Self {
+        self.update_timeouts(|t| t.request_body = timeout.into());
+        self
+    }
+
+    /// Set response write timeout.
+    ///
+    /// Defines how long response writing could be blocked by a client that
+    /// does not read data. Connection (or http/2 stream) is closed after timeout.
+    ///
+    /// By default timeout is disabled.
+    pub fn response_write_timeout(self, timeout: Seconds) -> Self {
+        self.update_timeouts(|t| t.response_write = timeout.into());
+        self
+    }
+
+    /// Set idle connection timeout.
+    ///
+    /// Connection without in-flight requests is closed after timeout.
+    /// For http/1 connections it overrides keep-alive timeout.
+    ///
+    /// By default keep-alive timeout is used for http/1 connections,
+    /// http/2 connections are not closed.
+    pub fn idle_connection_timeout(self, timeout: Seconds) -> Self {
+        self.update_timeouts(|t| t.idle_connection = timeout.into());
+        self
+    }
+
+    /// Set global memory budget.
+    ///
+    /// Buffered request payloads and websocket aggregation buffers of all
+    /// connections are registered against the budget. Budget could be shared
+    /// between workers. If budget is exhausted, connections stop reading
+    /// request payloads and new requests are rejected with
+    /// `503 Service Unavailable` response.
+    ///
+    /// By default budget is not set.
+    pub fn memory_budget(self, budget: MemoryBudget) -> Self {
+        *self.0.memory_budget.borrow_mut() = Some(budget);
+        self
+    }
+
+    /// Set max size of buffered request data per connection.
+    ///
+    /// If connection buffers more data, reading from the peer is
+    /// paused until application consumes buffered data.
+    ///
+    /// By default limit is 32Kb.
+    pub fn connection_memory_limit(self, size: usize) -> Self {
+        self.0.conn_memory_limit.set(size);
+        self
+    }
+
+    /// Set capacity of request and response message pools.
+    ///
+    /// Pools are thread-local, capacity is applied to worker threads
+    /// of the server on service initialization. Setting capacity to 0
+    /// disables pooling, released messages are deallocated.
+    ///
+    /// By default capacity is not changed, default pools capacity is 128.
+    pub fn message_pool_capacity(self, capacity: usize) -> Self {
+        self.0.message_pool.set(Some(capacity));
+        self
+    }
+
+    pub(super) fn message_pool(self, capacity: Option<usize>) -> Self {
+        self.0.message_pool.set(capacity);
+        self
+    }
+
+    pub(super) fn memory_limits(self, budget: Option<MemoryBudget>, limit: usize) -> Self {
+        *self.0.memory_budget.borrow_mut() = budget;
+        self.0.conn_memory_limit.set(limit);
+        self
+    }
+
+    pub(super) fn timeouts(self, timeouts: Timeouts) -> Self {
+        self.0.timeouts.set(timeouts);
+        self
+    }
+
+    fn update_timeouts<F: FnOnce(&mut Timeouts)>(&self, f: F) {
+        let mut timeouts = self.0.timeouts.get();
+        f(&mut timeouts);
+        self.0.timeouts.set(timeouts);
+    }
+
+    pub(super) fn on_connect(self, f: Option<OnConnect>) -> Self {
+        *self.0.on_connect.borrow_mut() = f;
+        self
+    }
+
+    pub(super) fn head_limits(self, limits: HeadLimits) -> Self {
+        self.0.head_limits.set(limits);
+        self
+    }
+
+    fn update_limits<F: FnOnce(&mut HeadLimits)>(&self, f: F) {
+        let mut limits = self.0.head_limits.get();
+        f(&mut limits);
+        self.0.head_limits.set(limits);
+    }
 }
 
 pub(super) type OnRequest = BoxService<(Request, IoRef), Request, Response>;
 
+pub(super) type OnConnect = Rc<dyn Fn(&IoRef) -> Box<dyn DataFactory>>;
+
+/// Connection level data, set to extensions of every request
+pub(super) trait DataFactory {
+    fn set(&self, ext: &mut Extensions);
+}
+
+pub(super) struct Data<T>(pub(super) T);
+
+impl<T: Clone + 'static> DataFactory for Data<T> {
+    fn set(&self, ext: &mut Extensions) {
+        ext.insert(self.0.clone());
+    }
+}
+
 pub(super) struct DispatcherConfig<S, X, U> {
     pub(super) service: S,
     pub(super) expect: X,
@@ -104,6 +338,15 @@ pub(super) struct DispatcherConfig<S, X, U> {
     pub(super) ka_enabled: bool,
     pub(super) timer: DateService,
     pub(super) on_request: Option<OnRequest>,
+    pub(super) on_connect: Option<OnConnect>,
+    pub(super) header_case: HeaderCase,
+    pub(super) head_limits: HeadLimits,
+    pub(super) strict_parsing: bool,
+    pub(super) catch_panics: bool,
+    pub(super) timeouts: Timeouts,
+    pub(super) memory_budget: Option<MemoryBudget>,
+    pub(super) conn_memory_limit: usize,
+    pub(super) h2c: bool,
 }
 
 impl<S, X, U> DispatcherConfig<S, X, U> {
@@ -114,24 +357,55 @@ impl<S, X, U> DispatcherConfig<S, X, U> {
         upgrade: Option<U>,
         on_request: Option<OnRequest>,
     ) -> Self {
+        // service is initialized on worker thread
+        if let Some(capacity) = cfg.0.message_pool.get() {
+            set_message_pool_capacity(capacity);
+        }
+
         DispatcherConfig {
             service,
             expect,
             upgrade,
             on_request,
+            on_connect: cfg.0.on_connect.borrow().clone(),
             keep_alive: Duration::from(cfg.0.keep_alive),
             client_timeout: Duration::from(cfg.0.client_timeout),
             client_disconnect: cfg.0.client_disconnect,
             ka_enabled: cfg.0.ka_enabled,
             timer: cfg.0.timer.clone(),
+            header_case: cfg.0.header_case.get(),
+            head_limits: cfg.0.head_limits.get(),
+            strict_parsing: cfg.0.strict_parsing.get(),
+            catch_panics: cfg.0.catch_panics.get(),
+            timeouts: cfg.0.timeouts.get(),
+            memory_budget: cfg.0.memory_budget.borrow().clone(),
+            conn_memory_limit: cfg.0.conn_memory_limit.get(),
+            h2c: false,
         }
     }
 
+    /// Check if global memory budget is exhausted
+    pub(super) fn memory_exhausted(&self) -> bool {
+        self.memory_budget
+            .as_ref()
+            .map(|b| b.is_exhausted())
+            .unwrap_or(false)
+    }
+
     /// Return state of connection keep-alive functionality
     pub(super) fn keep_alive_enabled(&self) -> bool {
         self.ka_enabled
     }
 
+    /// Idle timeout for http/1 connections
+    pub(super) fn idle_timeout(&self) -> Duration {
+        if !self.timeouts.idle_connection.is_zero() {
+            self.timeouts.idle_connection.into()
+        } else {
+            self.keep_alive
+        }
+    }
+
     /// Return keep-alive timer Sleep is configured.
     pub(super) fn keep_alive_timer(&self) -> Option<Sleep> {
         if self.keep_alive != Duration::ZERO {
@@ -158,17 +432,26 @@ const DATE_VALUE_DEFAULT: [u8; DATE_VALUE_LENGTH_HDR] = [
     b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'\r', b'\n', b'\r', b'\n',
 ];
 
+thread_local! {
+    static DATE: Rc<DateServiceInner> = Rc::new(DateServiceInner::new());
+}
+
+/// Cached `Date` header value.
+///
+/// Value is shared by all services of the current thread and
+/// is refreshed by timer once per second while it is in use.
 #[derive(Clone)]
 pub struct DateService(Rc<DateServiceInner>);
 
 impl Default for DateService {
     fn default() -> Self {
-        DateService(Rc::new(DateServiceInner::new()))
+        DATE.with(|inner| DateService(inner.clone()))
     }
 }
 
 struct DateServiceInner {
     current: Cell<bool>,
+    used: Cell<bool>,
     current_time: Cell<time::Instant>,
     current_date: Cell<[u8; DATE_VALUE_LENGTH_HDR]>,
 }
@@ -177,6 +460,7 @@ impl DateServiceInner {
     fn new() -> Self {
         DateServiceInner {
             current: Cell::new(false),
+            used: Cell::new(false),
             current_time: Cell::new(time::Instant::now()),
             current_date: Cell::new(DATE_VALUE_DEFAULT),
         }
@@ -194,21 +478,31 @@ impl DateServiceInner {
 }
 
 impl DateService {
-    fn new() -> Self {
-        DateService(Rc::new(DateServiceInner::new()))
-    }
-
     fn check_date(&self) {
         if !self.0.current.get() {
             self.0.update();
 
-            // periodic date update
-            let s = self.clone();
+            // periodic date update, refresh stops if date is not used
+            let guard = RefreshGuard(self.0.clone());
             crate::rt::spawn(async move {
-                sleep(Millis(500)).await;
-                s.0.current.set(false);
+                let inner = &guard.0;
+                loop {
+                    // wake up at the beginning of the next second
+                    let subsec = time::SystemTime::now()
+                        .duration_since(time::UNIX_EPOCH)
+                        .map(|d| d.subsec_millis())
+                        .unwrap_or(0);
+                    sleep(Millis(1000 - subsec)).await;
+
+                    if inner.used.replace(false) {
+                        inner.update();
+                    } else {
+                        break;
+                    }
+                }
             });
         }
+        self.0.used.set(true);
     }
 
     pub(super) fn now(&self) -> time::Instant {
@@ -240,6 +534,15 @@ impl DateService {
     }
 }
 
+/// Resets cached date if refresh task stops or runtime is dropped
+struct RefreshGuard(Rc<DateServiceInner>);
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        self.0.current.set(false);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,6 +555,29 @@ mod tests {
         let mut buf2 = BytesMut::with_capacity(DATE_VALUE_LENGTH_HDR);
         date.set_date_header(&mut buf2);
         assert_eq!(buf1, buf2);
+
+        // shared per thread
+        assert!(Rc::ptr_eq(&date.0, &DateService::default().0));
+        let value = crate::http::helpers::date_value();
+        assert_eq!(value.as_bytes(), &buf1[6..35]);
+
+        // refreshed by timer
+        sleep(Millis(1200)).await;
+        let current = date.0.current_date.get();
+        assert_ne!(&current[..], &buf1[..]);
+    }
+
+    #[test]
+    fn test_message_pool_capacity() {
+        use crate::http::message::{pooled_messages, ResponseHead};
+
+        // pooling is disabled for worker thread
+        let cfg = ServiceConfig::default().message_pool_capacity(0);
+        let _ = DispatcherConfig::new(cfg, (), (), None::<()>, None);
+
+        drop(Response::Ok().finish());
+        assert_eq!(pooled_messages::<ResponseHead>(), 0);
+        set_message_pool_capacity(128);
     }
 
     #[test]
@@ -11,15 +11,28 @@ pub use self::encoder::Encoder;
 
 pub(self) struct Writer {
     buf: BytesMut,
+    size: usize,
+    limit: usize,
 }
 
+/// Writer's size limit is reached
+#[derive(Debug, thiserror::Error)]
+#[error("Writer size limit is reached")]
+struct Overflow;
+
 impl Writer {
     fn new() -> Writer {
         Writer {
             buf: BytesMut::with_capacity(8192),
+            size: 0,
+            limit: usize::MAX,
         }
     }
 
+    fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
     fn take(&mut self) -> Bytes {
         self.buf.split().freeze()
     }
@@ -27,6 +40,10 @@ impl Writer {
 
 impl io::Write for Writer {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.size += buf.len();
+        if self.size > self.limit {
+            return Err(io::Error::new(io::ErrorKind::Other, Overflow));
+        }
         self.buf.extend_from_slice(buf);
         Ok(buf.len())
     }
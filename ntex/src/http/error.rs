@@ -1,5 +1,5 @@
 //! Http related errors
-use std::{fmt, io, io::Write, str::Utf8Error, string::FromUtf8Error};
+use std::{any::Any, fmt, io, io::Write, str::Utf8Error, string::FromUtf8Error};
 
 use http::{header, uri::InvalidUri, StatusCode};
 
@@ -74,6 +74,9 @@ pub enum ParseError {
     /// A message head is too large to be reasonable.
     #[error("Message head is too large")]
     TooLarge,
+    /// A request uri is too long.
+    #[error("Request uri is too long")]
+    UriTooLong,
     /// A message reached EOF, but is not complete.
     #[error("Message is incomplete")]
     Incomplete,
@@ -178,6 +181,14 @@ pub enum DispatchError {
     #[error("The first request did not complete within the specified timeout")]
     SlowRequestTimeout,
 
+    /// The request payload did not complete within the specified timeout.
+    #[error("The request payload did not complete within the specified timeout")]
+    SlowPayloadTimeout,
+
+    /// Response write did not complete within the specified timeout.
+    #[error("Response write timeout")]
+    WriteTimeout,
+
     /// Disconnect timeout. Makes sense for ssl streams.
     #[error("Connection shutdown timeout")]
     DisconnectTimeout,
@@ -190,6 +201,10 @@ pub enum DispatchError {
     #[error("Malformed request")]
     MalformedRequest,
 
+    /// Global memory budget is exhausted
+    #[error("Memory budget is exhausted")]
+    MemoryBudgetExceeded,
+
     /// Response body processing error
     #[error("Response body processing error: {0}")]
     ResponsePayload(Box<dyn std::error::Error>),
@@ -235,6 +250,44 @@ pub enum BlockingError<E: fmt::Debug> {
     Canceled,
 }
 
+/// Service panicked during request handling
+///
+/// Error is rendered as `500 Internal Server Error` response,
+/// panic message is not sent to the client.
+#[derive(thiserror::Error, Debug)]
+#[error("Service panicked: {0}")]
+pub struct PanicError(String);
+
+impl PanicError {
+    /// Create error from panic payload, panic is logged and recorded in metrics
+    pub(crate) fn new(payload: &(dyn Any + Send)) -> Self {
+        let msg = if let Some(s) = payload.downcast_ref::<&'static str>() {
+            (*s).to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        error!("Service panicked during request handling: {}", msg);
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::service_panic();
+
+        PanicError(msg)
+    }
+
+    /// Panic message
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ResponseError for PanicError {
+    fn error_response(&self) -> Response {
+        Response::new(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
 impl From<crate::rt::JoinError> for PayloadError {
     fn from(_: crate::rt::JoinError) -> Self {
         PayloadError::Io(io::Error::new(
@@ -276,6 +329,20 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[test]
+    fn test_panic_error() {
+        let err = PanicError::new(&"boom");
+        assert_eq!(err.message(), "boom");
+        assert_eq!(err.to_string(), "Service panicked: boom");
+        let resp = err.error_response();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let err = PanicError::new(&"boom".to_string());
+        assert_eq!(err.message(), "boom");
+        let err = PanicError::new(&1usize);
+        assert_eq!(err.message(), "Box<dyn Any>");
+    }
+
     #[test]
     fn test_payload_error() {
         let err: PayloadError = io::Error::new(io::ErrorKind::Other, "ParseError").into();
@@ -0,0 +1,144 @@
+use crate::http::header::{HeaderName, InvalidHeaderName};
+use crate::util::{Bytes, HashMap};
+
+/// Header names casing mode for h1 encoder
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HeaderCase {
+    /// Encode header names in lowercase
+    Lower,
+    /// Use original casing from message's `HeaderCaseMap`,
+    /// other header names are encoded in lowercase
+    Preserve,
+    /// Use original casing from message's `HeaderCaseMap`,
+    /// other header names are title-cased, i.e. `Content-Length`
+    Title,
+}
+
+impl Default for HeaderCase {
+    fn default() -> Self {
+        HeaderCase::Lower
+    }
+}
+
+/// Original casing of header names.
+///
+/// Map must be stored in message extensions, h1 encoder uses it
+/// if `HeaderCase::Preserve` or `HeaderCase::Title` mode is enabled.
+///
+/// ```rust
+/// use ntex::http::{h1::HeaderCaseMap, Response};
+///
+/// let mut map = HeaderCaseMap::default();
+/// let name = map.insert("X-Legacy-Header").unwrap();
+///
+/// let mut res = Response::Ok().header(name, "value").finish();
+/// res.extensions_mut().insert(map);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HeaderCaseMap(HashMap<Bytes, Bytes>);
+
+impl HeaderCaseMap {
+    /// Store original casing of the header name.
+    ///
+    /// Returns normalized header name.
+    pub fn insert(&mut self, name: &str) -> Result<HeaderName, InvalidHeaderName> {
+        let hname = HeaderName::from_bytes(name.as_bytes())?;
+        self.0.insert(
+            Bytes::copy_from_slice(hname.as_str().as_bytes()),
+            Bytes::copy_from_slice(name.as_bytes()),
+        );
+        Ok(hname)
+    }
+
+    /// Get original casing of the header name
+    pub fn get(&self, name: &HeaderName) -> Option<&str> {
+        self.0
+            .get(name.as_str().as_bytes())
+            .map(|s| std::str::from_utf8(s).unwrap())
+    }
+
+    /// Number of stored header names
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if map is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Change casing of encoded header names in place
+pub(super) fn recase(buf: &mut [u8], case: HeaderCase, map: Option<&HeaderCaseMap>) {
+    let mut pos = 0;
+    while pos < buf.len() {
+        let end = buf[pos..]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .map(|idx| pos + idx)
+            .unwrap_or_else(|| buf.len());
+
+        let line = &mut buf[pos..end];
+        if let Some(idx) = line.iter().position(|b| *b == b':') {
+            let name = &mut line[..idx];
+            match map.and_then(|map| map.0.get(&name[..])) {
+                Some(orig) if orig.len() == name.len() => name.copy_from_slice(orig),
+                _ => {
+                    if case == HeaderCase::Title {
+                        title_case(name)
+                    }
+                }
+            }
+        }
+        pos = end + 2;
+    }
+}
+
+fn title_case(name: &mut [u8]) {
+    let mut upper = true;
+    for b in name.iter_mut() {
+        if upper {
+            b.make_ascii_uppercase();
+        }
+        upper = *b == b'-';
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recase() {
+        let mut map = HeaderCaseMap::default();
+        assert!(map.is_empty());
+        let name = map.insert("X-Legacy-HEADER").unwrap();
+        assert_eq!(name.as_str(), "x-legacy-header");
+        assert_eq!(map.get(&name), Some("X-Legacy-HEADER"));
+        assert_eq!(map.len(), 1);
+        assert!(map.insert("bad header").is_err());
+
+        let src = b"\r\nx-legacy-header: a:b\r\ncontent-length: 0\r\n\r\n";
+
+        let mut buf = src.to_vec();
+        recase(&mut buf, HeaderCase::Preserve, Some(&map));
+        assert_eq!(
+            &buf[..],
+            &b"\r\nX-Legacy-HEADER: a:b\r\ncontent-length: 0\r\n\r\n"[..]
+        );
+
+        let mut buf = src.to_vec();
+        recase(&mut buf, HeaderCase::Title, Some(&map));
+        assert_eq!(
+            &buf[..],
+            &b"\r\nX-Legacy-HEADER: a:b\r\nContent-Length: 0\r\n\r\n"[..]
+        );
+
+        let mut buf = src.to_vec();
+        recase(&mut buf, HeaderCase::Title, None);
+        assert_eq!(
+            &buf[..],
+            &b"\r\nX-Legacy-Header: a:b\r\nContent-Length: 0\r\n\r\n"[..]
+        );
+    }
+}
@@ -11,7 +11,7 @@ use crate::http::{Method, Version};
 use crate::util::{Bytes, BytesMut};
 
 use super::decoder::{PayloadDecoder, PayloadItem, PayloadType};
-use super::{decoder, encoder, reserve_readbuf, Message, MessageType};
+use super::{decoder, encoder, reserve_readbuf, HeaderCase, Message, MessageType};
 
 bitflags! {
     struct Flags: u8 {
@@ -72,6 +72,14 @@ impl ClientCodec {
         }
     }
 
+    /// Set header names casing mode for encoded requests.
+    ///
+    /// By default header names are encoded in lowercase.
+    pub fn header_case(self, case: HeaderCase) -> Self {
+        self.inner.encoder.case.set(case);
+        self
+    }
+
     /// Check if request is upgrade
     pub fn upgrade(&self) -> bool {
         self.inner.ctype.get() == ConnectionType::Upgrade
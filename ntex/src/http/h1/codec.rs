@@ -12,7 +12,7 @@ use crate::http::response::Response;
 use crate::http::{Method, Version};
 use crate::util::BytesMut;
 
-use super::{decoder, decoder::PayloadType, encoder, Message};
+use super::{decoder, decoder::PayloadType, encoder, HeadLimits, HeaderCase, Message};
 
 bitflags! {
     struct Flags: u8 {
@@ -80,6 +80,30 @@ impl Codec {
         }
     }
 
+    /// Set header names casing mode for encoded responses.
+    ///
+    /// By default header names are encoded in lowercase.
+    pub fn header_case(self, case: HeaderCase) -> Self {
+        self.encoder.case.set(case);
+        self
+    }
+
+    /// Set request head parsing limits
+    pub(super) fn head_limits(mut self, limits: HeadLimits) -> Self {
+        self.decoder.limits = limits;
+        self
+    }
+
+    /// Enable strict request parsing mode.
+    ///
+    /// Requests with both `Content-Length` and `Transfer-Encoding` headers,
+    /// multiple `Content-Length` headers, obs-fold header continuation lines
+    /// or line endings other than CRLF are rejected.
+    pub fn strict_parsing(mut self, strict: bool) -> Self {
+        self.decoder.strict = strict;
+        self
+    }
+
     #[inline]
     /// Check if request is upgrade
     pub fn upgrade(&self) -> bool {
@@ -108,6 +132,17 @@ impl Codec {
         self.timer.set_date_header(dst)
     }
 
+    /// Remaining size of the response body if payload could be
+    /// written to io stream directly
+    pub(super) fn payload_remaining(&self) -> Option<u64> {
+        self.encoder.remaining()
+    }
+
+    /// Account payload data written to io stream directly
+    pub(super) fn payload_written(&self, size: u64) {
+        self.encoder.consume(size)
+    }
+
     fn insert_flags(&self, f: Flags) {
         let mut flags = self.flags.get();
         flags.insert(f);
@@ -12,12 +12,38 @@ use crate::http::message::{ConnectionType, ResponseHead};
 use crate::http::request::Request;
 use crate::util::{Buf, Bytes, BytesMut};
 
+use super::scan::{find_line_byte, is_head_complete};
 use super::MAX_BUFFER_SIZE;
 
 const MAX_HEADERS: usize = 96;
 
+/// Message head parsing limits
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct HeadLimits {
+    /// Max number of headers
+    pub(crate) max_headers: usize,
+    /// Max size of the message head
+    pub(crate) max_head_size: usize,
+    /// Max length of the request uri
+    pub(crate) max_uri_length: usize,
+}
+
+impl Default for HeadLimits {
+    fn default() -> Self {
+        HeadLimits {
+            max_headers: MAX_HEADERS,
+            max_head_size: MAX_BUFFER_SIZE,
+            max_uri_length: MAX_BUFFER_SIZE,
+        }
+    }
+}
+
 /// Incoming messagd decoder
-pub(super) struct MessageDecoder<T: MessageType>(PhantomData<T>);
+pub(super) struct MessageDecoder<T: MessageType> {
+    pub(super) limits: HeadLimits,
+    pub(super) strict: bool,
+    _t: PhantomData<T>,
+}
 
 #[derive(Debug)]
 /// Incoming request type
@@ -29,13 +55,21 @@ pub enum PayloadType {
 
 impl<T: MessageType> Default for MessageDecoder<T> {
     fn default() -> Self {
-        MessageDecoder(PhantomData)
+        MessageDecoder {
+            limits: HeadLimits::default(),
+            strict: false,
+            _t: PhantomData,
+        }
     }
 }
 
 impl<T: MessageType> Clone for MessageDecoder<T> {
     fn clone(&self) -> Self {
-        MessageDecoder(PhantomData)
+        MessageDecoder {
+            limits: self.limits,
+            strict: self.strict,
+            _t: PhantomData,
+        }
     }
 }
 
@@ -44,7 +78,22 @@ impl<T: MessageType> Decoder for MessageDecoder<T> {
     type Error = ParseError;
 
     fn decode(&self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        T::decode(src)
+        if self.limits.max_headers > MAX_HEADERS {
+            let mut headers = vec![HeaderIndex::default(); self.limits.max_headers];
+            T::decode(src, &self.limits, self.strict, &mut headers)
+        } else {
+            // Unsafe: we read this data only after httparse parses headers into.
+            // performance bump for pipeline benchmarks.
+            #[allow(clippy::uninit_assumed_init)]
+            let mut headers: [HeaderIndex; MAX_HEADERS] =
+                unsafe { MaybeUninit::uninit().assume_init() };
+            T::decode(
+                src,
+                &self.limits,
+                self.strict,
+                &mut headers[..self.limits.max_headers],
+            )
+        }
     }
 }
 
@@ -61,18 +110,25 @@ pub(super) trait MessageType: Sized {
 
     fn headers_mut(&mut self) -> &mut HeaderMap;
 
-    fn decode(src: &mut BytesMut) -> Result<Option<(Self, PayloadType)>, ParseError>;
+    fn decode(
+        src: &mut BytesMut,
+        limits: &HeadLimits,
+        strict: bool,
+        headers: &mut [HeaderIndex],
+    ) -> Result<Option<(Self, PayloadType)>, ParseError>;
 
     fn set_headers(
         &mut self,
         slice: &Bytes,
         raw_headers: &[HeaderIndex],
+        strict: bool,
     ) -> Result<PayloadLength, ParseError> {
         let mut ka = None;
         let mut has_upgrade = false;
         let mut expect = false;
         let mut chunked = false;
         let mut seen_te = false;
+        let mut seen_cl = false;
         let mut content_length = None;
 
         {
@@ -92,12 +148,17 @@ pub(super) trait MessageType: Sized {
                         log::debug!("multiple Content-Length not allowed");
                         return Err(ParseError::Header);
                     }
+                    header::CONTENT_LENGTH if strict && (seen_cl || seen_te) => {
+                        log::debug!("Content-Length is not allowed in strict mode");
+                        return Err(ParseError::Header);
+                    }
                     header::CONTENT_LENGTH => match value.to_str() {
                         Ok(s) if s.trim_start().starts_with('+') => {
                             log::debug!("illegal Content-Length: {:?}", s);
                             return Err(ParseError::Header);
                         }
                         Ok(s) => {
+                            seen_cl = true;
                             if let Ok(len) = s.parse::<u64>() {
                                 if len != 0 {
                                     content_length = Some(len);
@@ -113,7 +174,7 @@ pub(super) trait MessageType: Sized {
                         }
                     },
                     // transfer-encoding
-                    header::TRANSFER_ENCODING if seen_te => {
+                    header::TRANSFER_ENCODING if seen_te || (strict && seen_cl) => {
                         log::debug!("Transfer-Encoding header usage is not allowed");
                         return Err(ParseError::Header);
                     }
@@ -210,46 +271,37 @@ impl MessageType for Request {
         &mut self.head_mut().headers
     }
 
-    #[allow(clippy::uninit_assumed_init)]
-    fn decode(src: &mut BytesMut) -> Result<Option<(Self, PayloadType)>, ParseError> {
-        // Unsafe: we read this data only after httparse parses headers into.
-        // performance bump for pipeline benchmarks.
-        let mut headers: [HeaderIndex; MAX_HEADERS] =
-            unsafe { MaybeUninit::uninit().assume_init() };
-
-        let (len, method, uri, ver, h_len) = {
+    fn decode(
+        src: &mut BytesMut,
+        limits: &HeadLimits,
+        strict: bool,
+        headers: &mut [HeaderIndex],
+    ) -> Result<Option<(Self, PayloadType)>, ParseError> {
+        let head = if limits.max_headers > MAX_HEADERS {
+            let mut parsed = vec![httparse::EMPTY_HEADER; limits.max_headers];
+            parse_request(src, limits, &mut parsed, headers)?
+        } else {
+            // Unsafe: we read this data only after httparse parses headers into.
+            // performance bump for pipeline benchmarks.
+            #[allow(clippy::uninit_assumed_init)]
             let mut parsed: [httparse::Header<'_>; MAX_HEADERS] =
                 unsafe { MaybeUninit::uninit().assume_init() };
-
-            let mut req = httparse::Request::new(&mut parsed);
-            match req.parse(src)? {
-                httparse::Status::Complete(len) => {
-                    let method = Method::from_bytes(req.method.unwrap().as_bytes())
-                        .map_err(|_| ParseError::Method)?;
-                    let uri = Uri::try_from(req.path.unwrap())?;
-                    let version = if req.version.unwrap() == 1 {
-                        Version::HTTP_11
-                    } else {
-                        Version::HTTP_10
-                    };
-                    HeaderIndex::record(src, req.headers, &mut headers);
-
-                    (len, method, uri, version, req.headers.len())
-                }
-                httparse::Status::Partial => {
-                    if src.len() >= MAX_BUFFER_SIZE {
-                        trace!("MAX_BUFFER_SIZE unprocessed data reached, closing");
-                        return Err(ParseError::TooLarge);
-                    }
-                    return Ok(None);
-                }
-            }
+            parse_request(src, limits, &mut parsed[..limits.max_headers], headers)?
+        };
+        let (len, method, uri, ver, h_len) = if let Some(head) = head {
+            head
+        } else {
+            return Ok(None);
         };
+        if strict {
+            check_line_endings(&src[..len])?;
+        }
 
         let mut msg = Request::new();
 
         // convert headers
-        let length = msg.set_headers(&src.split_to(len).freeze(), &headers[..h_len])?;
+        let length =
+            msg.set_headers(&src.split_to(len).freeze(), &headers[..h_len], strict)?;
 
         // payload decoder
         let decoder = match length {
@@ -291,47 +343,38 @@ impl MessageType for ResponseHead {
         &mut self.headers
     }
 
-    #[allow(clippy::uninit_assumed_init)]
-    fn decode(src: &mut BytesMut) -> Result<Option<(Self, PayloadType)>, ParseError> {
-        // Unsafe: we read this data only after httparse parses headers into.
-        // performance bump for pipeline benchmarks.
-        let mut headers: [HeaderIndex; MAX_HEADERS] =
-            unsafe { MaybeUninit::uninit().assume_init() };
-
-        let (len, ver, status, h_len) = {
+    fn decode(
+        src: &mut BytesMut,
+        limits: &HeadLimits,
+        strict: bool,
+        headers: &mut [HeaderIndex],
+    ) -> Result<Option<(Self, PayloadType)>, ParseError> {
+        let head = if limits.max_headers > MAX_HEADERS {
+            let mut parsed = vec![httparse::EMPTY_HEADER; limits.max_headers];
+            parse_response(src, limits, &mut parsed, headers)?
+        } else {
+            // Unsafe: we read this data only after httparse parses headers into.
+            // performance bump for pipeline benchmarks.
+            #[allow(clippy::uninit_assumed_init)]
             let mut parsed: [httparse::Header<'_>; MAX_HEADERS] =
                 unsafe { MaybeUninit::uninit().assume_init() };
-
-            let mut res = httparse::Response::new(&mut parsed);
-            match res.parse(src)? {
-                httparse::Status::Complete(len) => {
-                    let version = if res.version.unwrap() == 1 {
-                        Version::HTTP_11
-                    } else {
-                        Version::HTTP_10
-                    };
-                    let status = StatusCode::from_u16(res.code.unwrap())
-                        .map_err(|_| ParseError::Status)?;
-                    HeaderIndex::record(src, res.headers, &mut headers);
-
-                    (len, version, status, res.headers.len())
-                }
-                httparse::Status::Partial => {
-                    return if src.len() >= MAX_BUFFER_SIZE {
-                        log::error!("MAX_BUFFER_SIZE unprocessed data reached, closing");
-                        Err(ParseError::TooLarge)
-                    } else {
-                        Ok(None)
-                    };
-                }
-            }
+            parse_response(src, limits, &mut parsed[..limits.max_headers], headers)?
         };
+        let (len, ver, status, h_len) = if let Some(head) = head {
+            head
+        } else {
+            return Ok(None);
+        };
+        if strict {
+            check_line_endings(&src[..len])?;
+        }
 
         let mut msg = ResponseHead::new(status);
         msg.version = ver;
 
         // convert headers
-        let length = msg.set_headers(&src.split_to(len).freeze(), &headers[..h_len])?;
+        let length =
+            msg.set_headers(&src.split_to(len).freeze(), &headers[..h_len], strict)?;
 
         // message payload
         let decoder = if let PayloadLength::Payload(pl) = length {
@@ -353,7 +396,121 @@ impl MessageType for ResponseHead {
     }
 }
 
-#[derive(Clone, Copy)]
+/// Parse request line and headers
+fn parse_request<'a>(
+    src: &'a [u8],
+    limits: &HeadLimits,
+    parsed: &mut [httparse::Header<'a>],
+    headers: &mut [HeaderIndex],
+) -> Result<Option<(usize, Method, Uri, Version, usize)>, ParseError> {
+    let mut req = httparse::Request::new(parsed);
+    let status = req.parse(head_or_first_line(src))?;
+
+    if let Some(path) = req.path {
+        if path.len() > limits.max_uri_length {
+            trace!("Request uri is too long: {}", path.len());
+            return Err(ParseError::UriTooLong);
+        }
+    }
+
+    match status {
+        httparse::Status::Complete(len) => {
+            if len > limits.max_head_size {
+                trace!("Request head is too large: {}", len);
+                return Err(ParseError::TooLarge);
+            }
+            let method = Method::from_bytes(req.method.unwrap().as_bytes())
+                .map_err(|_| ParseError::Method)?;
+            let uri = Uri::try_from(req.path.unwrap())?;
+            let version = if req.version.unwrap() == 1 {
+                Version::HTTP_11
+            } else {
+                Version::HTTP_10
+            };
+            HeaderIndex::record(src, req.headers, headers);
+
+            Ok(Some((len, method, uri, version, req.headers.len())))
+        }
+        httparse::Status::Partial => {
+            if src.len() >= limits.max_head_size {
+                trace!("Max head size of unprocessed data reached, closing");
+                Err(ParseError::TooLarge)
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Parse status line and headers
+fn parse_response<'a>(
+    src: &'a [u8],
+    limits: &HeadLimits,
+    parsed: &mut [httparse::Header<'a>],
+    headers: &mut [HeaderIndex],
+) -> Result<Option<(usize, Version, StatusCode, usize)>, ParseError> {
+    let mut res = httparse::Response::new(parsed);
+    match res.parse(head_or_first_line(src))? {
+        httparse::Status::Complete(len) => {
+            let version = if res.version.unwrap() == 1 {
+                Version::HTTP_11
+            } else {
+                Version::HTTP_10
+            };
+            let status =
+                StatusCode::from_u16(res.code.unwrap()).map_err(|_| ParseError::Status)?;
+            HeaderIndex::record(src, res.headers, headers);
+
+            Ok(Some((len, version, status, res.headers.len())))
+        }
+        httparse::Status::Partial => {
+            if src.len() >= limits.max_head_size {
+                log::error!("Max head size of unprocessed data reached, closing");
+                Err(ParseError::TooLarge)
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Incomplete head gets re-parsed on every read, parse only first line
+/// until head is complete.
+fn head_or_first_line(src: &[u8]) -> &[u8] {
+    if is_head_complete(src) {
+        src
+    } else {
+        find_line_byte(src).map_or(src, |pos| &src[..=pos])
+    }
+}
+
+/// Check that every line of the message head ends with CRLF
+/// and there is no obs-fold header continuation lines
+fn check_line_endings(head: &[u8]) -> Result<(), ParseError> {
+    let mut idx = 0;
+    while let Some(pos) = find_line_byte(&head[idx..]) {
+        idx += pos;
+        match head[idx] {
+            b'\r' if head.get(idx + 1) != Some(&b'\n') => {
+                log::debug!("bare CR is not allowed in strict mode");
+                return Err(ParseError::Header);
+            }
+            b'\n' if idx == 0 || head[idx - 1] != b'\r' => {
+                log::debug!("bare LF is not allowed in strict mode");
+                return Err(ParseError::Header);
+            }
+            b'\n' if matches!(head.get(idx + 1), Some(b' ') | Some(b'\t')) => {
+                log::debug!("obs-fold header continuation is not allowed in strict mode");
+                return Err(ParseError::Header);
+            }
+            _ => (),
+        }
+        idx += 1;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Default)]
 pub(super) struct HeaderIndex {
     pub(super) name: (usize, usize),
     pub(super) value: (usize, usize),
@@ -1112,6 +1269,87 @@ mod tests {
         expect_parse_err!(&mut buf);
     }
 
+    #[test]
+    fn test_head_limits() {
+        let mut reader = MessageDecoder::<Request>::default();
+        reader.limits.max_headers = 2;
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\na: 1\r\nb: 2\r\n\r\n");
+        assert!(reader.decode(&mut buf).unwrap().is_some());
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\na: 1\r\nb: 2\r\nc: 3\r\n\r\n");
+        assert!(matches!(reader.decode(&mut buf), Err(ParseError::TooLarge)));
+
+        // more headers than default limit
+        reader.limits.max_headers = 200;
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\n");
+        for idx in 0..150 {
+            buf.extend_from_slice(format!("x-header-{}: {}\r\n", idx, idx).as_bytes());
+        }
+        buf.extend_from_slice(b"\r\n");
+        let (req, _) = reader.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(req.headers().len(), 150);
+
+        reader.limits.max_head_size = 32;
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\nx-header: 1234567890\r\n");
+        assert!(matches!(reader.decode(&mut buf), Err(ParseError::TooLarge)));
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\nx: 123456\r\n\r\n");
+        assert!(matches!(reader.decode(&mut buf), Err(ParseError::TooLarge)));
+
+        reader.limits.max_head_size = 1024;
+        reader.limits.max_uri_length = 8;
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\n\r\n");
+        assert!(reader.decode(&mut buf).unwrap().is_some());
+        let mut buf = BytesMut::from("GET /test/long HTTP/1.1\r\n");
+        assert!(matches!(
+            reader.decode(&mut buf),
+            Err(ParseError::UriTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        let requests: &[&[u8]] = &[
+            b"GET /test HTTP/1.1\r\ncontent-length: 0\r\ntransfer-encoding: chunked\r\n\r\n",
+            b"GET /test HTTP/1.1\r\ntransfer-encoding: identity\r\ncontent-length: 1\r\n\r\n",
+            b"GET /test HTTP/1.1\r\ncontent-length: 0\r\ncontent-length: 0\r\n\r\n",
+            b"GET /test HTTP/1.1\nx-header: 1\r\n\r\n",
+            b"GET /test HTTP/1.1\r\nx-header: 1\n\n",
+        ];
+
+        let mut reader = MessageDecoder::<Request>::default();
+        for req in requests {
+            let mut buf = BytesMut::from(*req);
+            assert!(reader.decode(&mut buf).unwrap().is_some());
+        }
+
+        reader.strict = true;
+        for req in requests {
+            let mut buf = BytesMut::from(*req);
+            assert!(matches!(reader.decode(&mut buf), Err(ParseError::Header)));
+        }
+
+        let mut buf = BytesMut::from(
+            "GET /test HTTP/1.1\r\ncontent-length: 4\r\nx-header: 1\r\n\r\ndata",
+        );
+        let (req, pl) = reader.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(req.headers().len(), 2);
+        assert!(matches!(pl, PayloadType::Payload(_)));
+
+        assert!(check_line_endings(b"x-header: 1\r\n \r\n").is_err());
+        assert!(check_line_endings(b"x-header: 1\r\n\tvalue\r\n").is_err());
+        assert!(check_line_endings(b"x-header: 1\rvalue\r\n").is_err());
+        assert!(check_line_endings(b"x-header: 1\r\n").is_ok());
+        assert!(check_line_endings(b"\nx-header: 1\r\n").is_err());
+        assert!(check_line_endings(b"x-header: 1\r").is_err());
+
+        // long heads are scanned in blocks
+        let head =
+            "GET /test HTTP/1.1\r\nx-long-header: 0123456789abcdef0123456789\r\n\r\n";
+        assert!(check_line_endings(head.as_bytes()).is_ok());
+        let head =
+            "GET /test HTTP/1.1\r\nx-long-header: 0123456789abcdef\n0123456789\r\n\r\n";
+        assert!(check_line_endings(head.as_bytes()).is_err());
+    }
+
     #[test]
     fn test_http_request_chunked_payload() {
         let mut buf = BytesMut::from(
@@ -1,22 +1,44 @@
 //! Framed transport dispatcher
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::task::{Context, Poll};
+#[cfg(target_os = "linux")]
+use std::{any, cmp, fs, ops};
 use std::{cell::RefCell, error::Error, future::Future, io, marker, pin::Pin, rc::Rc};
 
+#[cfg(target_os = "linux")]
+use crate::io::{types, Base};
 use crate::io::{Filter, Io, IoBoxed, RecvError};
+use crate::time::{sleep, Sleep};
+use crate::tls::types::EarlyData;
+#[cfg(target_os = "linux")]
+use crate::util::Buf;
 use crate::{service::Service, util::ready, util::Bytes};
 
 use crate::http;
 use crate::http::body::{BodySize, MessageBody, ResponseBody};
-use crate::http::config::DispatcherConfig;
-use crate::http::error::{DispatchError, ParseError, PayloadError, ResponseError};
+use crate::http::budget::Reservation;
+use crate::http::config::{DataFactory, DispatcherConfig};
+use crate::http::error::{
+    DispatchError, PanicError, ParseError, PayloadError, ResponseError,
+};
+use crate::http::h2::h2c;
 use crate::http::message::CurrentIo;
 use crate::http::request::Request;
 use crate::http::response::Response;
+use crate::http::StatusCode;
+#[cfg(feature = "metrics")]
+use crate::metrics::Protocol;
 
 use super::decoder::{PayloadDecoder, PayloadItem, PayloadType};
 use super::payload::{Payload, PayloadSender, PayloadStatus};
 use super::{codec::Codec, Message};
 
+/// Max size of the single `sendfile` call
+#[cfg(target_os = "linux")]
+const MAX_SENDFILE_SIZE: u64 = 0x7fff_f000;
+#[cfg(target_os = "linux")]
+const MIN_VECTORED_WRITE_SIZE: usize = 16 * 1024;
+
 bitflags::bitflags! {
     pub struct Flags: u16 {
         /// We parsed one complete request message
@@ -31,6 +53,12 @@ bitflags::bitflags! {
         const UPGRADE_HND          = 0b0001_0000;
         /// Stop after sending payload
         const SENDPAYLOAD_AND_STOP = 0b0010_0000;
+        /// Detect http/2 connection preface
+        const H2C_DETECT           = 0b0100_0000;
+        /// Request head timer is registered
+        const HEAD_TIMER           = 0b1000_0000;
+        /// Requests are received in tls early data
+        const EARLY_DATA           = 0b1_0000_0000;
     }
 }
 
@@ -80,6 +108,12 @@ struct DispatcherInner<F, S, B, X, U> {
     config: Rc<DispatcherConfig<S, X, U>>,
     error: Option<DispatchError>,
     payload: Option<(PayloadDecoder, PayloadSender)>,
+    h2c: Option<Option<Bytes>>,
+    data: Option<Box<dyn DataFactory>>,
+    body_timer: Option<Sleep>,
+    write_timer: Option<Sleep>,
+    #[cfg(feature = "metrics")]
+    started: Option<std::time::Instant>,
     _t: marker::PhantomData<(S, B)>,
 }
 
@@ -96,12 +130,27 @@ where
 {
     /// Construct new `Dispatcher` instance with outgoing messages stream.
     pub(in crate::http) fn new(io: Io<F>, config: Rc<DispatcherConfig<S, X, U>>) -> Self {
-        let codec = Codec::new(config.timer.clone(), config.keep_alive_enabled());
+        let codec = Codec::new(config.timer.clone(), config.keep_alive_enabled())
+            .header_case(config.header_case)
+            .head_limits(config.head_limits)
+            .strict_parsing(config.strict_parsing);
         io.set_disconnect_timeout(config.client_disconnect.into());
 
         // slow-request timer
         io.start_keepalive_timer(config.client_timeout);
 
+        // connection level data
+        let data = config.on_connect.as_ref().map(|f| f(&io));
+
+        let mut flags = if config.h2c {
+            Flags::KEEPALIVE_REG | Flags::H2C_DETECT
+        } else {
+            Flags::KEEPALIVE_REG
+        };
+        if io.query::<EarlyData>().get().is_some() {
+            flags.insert(Flags::EARLY_DATA);
+        }
+
         Dispatcher {
             call: CallState::None,
             st: State::ReadRequest,
@@ -109,13 +158,34 @@ where
                 io,
                 codec,
                 config,
-                flags: Flags::KEEPALIVE_REG,
+                flags,
                 error: None,
                 payload: None,
+                h2c: None,
+                data,
+                body_timer: None,
+                write_timer: None,
+                #[cfg(feature = "metrics")]
+                started: None,
                 _t: marker::PhantomData,
             },
         }
     }
+
+    /// Take io stream of http/2 cleartext connection.
+    ///
+    /// Returns `HEADERS` frame of accepted `Upgrade: h2c` request,
+    /// or `None` for prior knowledge connections.
+    #[allow(clippy::type_complexity)]
+    pub(in crate::http) fn take_h2c(
+        self: Pin<&mut Self>,
+    ) -> Option<(Io<F>, Option<Bytes>, Rc<DispatcherConfig<S, X, U>>)> {
+        let inner = self.project().inner;
+        inner
+            .h2c
+            .take()
+            .map(|frame| (inner.io.take(), frame, inner.config.clone()))
+    }
 }
 
 impl<F, S, B, X, U> Future for Dispatcher<F, S, B, X, U>
@@ -139,14 +209,18 @@ where
                 State::Call => {
                     let next = match this.call.project() {
                         CallStateProject::Service { fut } => {
-                            match fut.poll(cx) {
-                                Poll::Ready(result) => match result {
+                            match poll_service(fut, cx, this.inner.config.catch_panics) {
+                                Poll::Ready(Ok(result)) => match result {
                                     Ok(res) => {
                                         let (res, body) = res.into().into_parts();
                                         *this.st = this.inner.send_response(res, body);
                                     }
                                     Err(e) => *this.st = this.inner.handle_error(e, false),
                                 },
+                                // request state is unknown after panic, close connection
+                                Poll::Ready(Err(e)) => {
+                                    *this.st = this.inner.handle_error(e, true)
+                                }
                                 Poll::Pending => {
                                     // we might need to read more data into a request payload
                                     // (ie service future can wait for payload data)
@@ -154,8 +228,7 @@ where
                                         if let Err(e) =
                                             ready!(this.inner.poll_request_payload(cx))
                                         {
-                                            *this.st = State::Stop;
-                                            this.inner.error = Some(e);
+                                            *this.st = this.inner.payload_error(e);
                                         }
                                     } else {
                                         return Poll::Pending;
@@ -285,7 +358,35 @@ where
                     log::trace!("trying to read http message");
 
                     // decode incoming bytes stream
-                    match this.inner.io.poll_recv(&this.inner.codec, cx) {
+                    let result = if this.inner.flags.contains(Flags::H2C_DETECT) {
+                        // check for http/2 prior knowledge connection
+                        match this.inner.io.poll_recv(&h2c::Preface, cx) {
+                            Poll::Ready(Ok(true)) => {
+                                log::trace!("http/2 connection preface is received");
+                                this.inner.io.remove_keepalive_timer();
+                                this.inner.h2c = Some(None);
+                                return Poll::Ready(Ok(()));
+                            }
+                            Poll::Ready(Ok(false)) => {
+                                this.inner.flags.remove(Flags::H2C_DETECT);
+                                continue;
+                            }
+                            Poll::Ready(Err(err)) => Poll::Ready(Err(match err {
+                                RecvError::KeepAlive => RecvError::KeepAlive,
+                                RecvError::Stop => RecvError::Stop,
+                                RecvError::WriteBackpressure => {
+                                    RecvError::WriteBackpressure
+                                }
+                                RecvError::Decoder(err) => RecvError::Decoder(err),
+                                RecvError::PeerGone(err) => RecvError::PeerGone(err),
+                            })),
+                            Poll::Pending => Poll::Pending,
+                        }
+                    } else {
+                        this.inner.io.poll_recv(&this.inner.codec, cx)
+                    };
+
+                    match result {
                         Poll::Ready(Ok((mut req, pl))) => {
                             log::trace!(
                                 "http message is received: {:?} and payload {:?}",
@@ -293,18 +394,72 @@ where
                                 pl
                             );
 
+                            // reject requests while global memory budget is exhausted
+                            if this.inner.config.memory_exhausted() {
+                                log::trace!("memory budget is exhausted, reject request");
+                                let (res, body) =
+                                    Response::ServiceUnavailable().finish().into_parts();
+                                this.inner.error =
+                                    Some(DispatchError::MemoryBudgetExceeded);
+                                let state = this.inner.send_response(res, body.into_body());
+                                *this.st = if matches!(state, State::SendPayload { .. }) {
+                                    this.inner.flags.insert(Flags::SENDPAYLOAD_AND_STOP);
+                                    state
+                                } else {
+                                    State::Stop
+                                };
+                                continue;
+                            }
+
+                            // early data could be replayed, reject non-idempotent requests
+                            if this.inner.flags.contains(Flags::EARLY_DATA)
+                                && !req.head().method.is_idempotent()
+                            {
+                                log::trace!(
+                                    "reject {:?} request in early data",
+                                    req.method()
+                                );
+                                if !matches!(pl, PayloadType::None) {
+                                    this.inner.flags.insert(Flags::SENDPAYLOAD_AND_STOP);
+                                }
+                                let (res, body) =
+                                    Response::new(StatusCode::from_u16(425).unwrap())
+                                        .into_parts();
+                                *this.st = this.inner.send_response(res, body.into_body());
+                                continue;
+                            }
+
+                            // switch to http/2 for `Upgrade: h2c` requests without payload
+                            if this.inner.config.h2c
+                                && !matches!(pl, PayloadType::Payload(_))
+                            {
+                                if let Some(frame) = h2c::upgrade_frame(req.head()) {
+                                    log::trace!("switching to h2c for {:?}", req);
+                                    this.inner.io.remove_keepalive_timer();
+                                    if let Err(err) =
+                                        this.inner.io.write(h2c::UPGRADE_RESPONSE)
+                                    {
+                                        return Poll::Ready(Err(DispatchError::Encode(
+                                            err,
+                                        )));
+                                    }
+                                    this.inner.h2c = Some(Some(frame));
+                                    return Poll::Ready(Ok(()));
+                                }
+                            }
+
                             // configure request payload
                             let upgrade = match pl {
                                 PayloadType::None => false,
                                 PayloadType::Payload(decoder) => {
-                                    let (ps, pl) = Payload::create(false);
+                                    let (ps, pl) = this.inner.create_payload();
                                     req.replace_payload(http::Payload::H1(pl));
                                     this.inner.payload = Some((decoder, ps));
                                     false
                                 }
                                 PayloadType::Stream(decoder) => {
                                     if this.inner.config.upgrade.is_none() {
-                                        let (ps, pl) = Payload::create(false);
+                                        let (ps, pl) = this.inner.create_payload();
                                         req.replace_payload(http::Payload::H1(pl));
                                         this.inner.payload = Some((decoder, ps));
                                         false
@@ -315,11 +470,30 @@ where
                                 }
                             };
 
+                            // set connection level data
+                            if let Some(ref data) = this.inner.data {
+                                data.set(&mut req.extensions_mut());
+                            }
+
+                            #[cfg(feature = "metrics")]
+                            {
+                                this.inner.started =
+                                    Some(crate::metrics::http_request(Protocol::Http1));
+                            }
+
                             // slow-request first request
                             this.inner.flags.insert(Flags::STARTED);
-                            this.inner.flags.remove(Flags::KEEPALIVE_REG);
+                            this.inner
+                                .flags
+                                .remove(Flags::KEEPALIVE_REG | Flags::HEAD_TIMER);
                             this.inner.io.remove_keepalive_timer();
 
+                            // request payload timer
+                            let timeout = this.inner.config.timeouts.request_body;
+                            if this.inner.payload.is_some() && !timeout.is_zero() {
+                                this.inner.body_timer = Some(sleep(timeout));
+                            }
+
                             if upgrade {
                                 // Handle UPGRADE request
                                 log::trace!("prep io for upgrade handler");
@@ -374,9 +548,17 @@ where
                             }
                         }
                         Poll::Ready(Err(RecvError::Decoder(err))) => {
-                            // Malformed requests, respond with 400
+                            // Malformed requests, respond with 400,
+                            // 431 or 414 for requests over configured limits
                             log::trace!("malformed request: {:?}", err);
-                            let (res, body) = Response::BadRequest().finish().into_parts();
+                            let mut res = match err {
+                                ParseError::TooLarge => {
+                                    Response::RequestHeaderFieldsTooLarge()
+                                }
+                                ParseError::UriTooLong => Response::UriTooLong(),
+                                _ => Response::BadRequest(),
+                            };
+                            let (res, body) = res.finish().into_parts();
                             this.inner.error = Some(DispatchError::Parse(err));
                             *this.st = this.inner.send_response(res, body.into_body());
                         }
@@ -391,7 +573,9 @@ where
                         }
                         Poll::Ready(Err(RecvError::KeepAlive)) => {
                             // keep-alive timeout
-                            if !this.inner.flags.contains(Flags::STARTED) {
+                            if !this.inner.flags.contains(Flags::STARTED)
+                                || this.inner.flags.contains(Flags::HEAD_TIMER)
+                            {
                                 log::trace!("slow request timeout");
                                 let (req, body) =
                                     Response::RequestTimeout().finish().into_parts();
@@ -403,14 +587,27 @@ where
                             *this.st = State::Stop;
                         }
                         Poll::Pending => {
-                            // register keep-alive timer
-                            if this.inner.flags.contains(Flags::KEEPALIVE)
+                            // early data is consumed
+                            this.inner.flags.remove(Flags::EARLY_DATA);
+
+                            let head_timeout = this.inner.config.timeouts.request_head;
+                            if !head_timeout.is_zero()
+                                && !this.inner.flags.contains(Flags::HEAD_TIMER)
+                                && this.inner.io.with_read_buf(|buf| !buf.is_empty())
+                            {
+                                // register request head timer
+                                this.inner
+                                    .flags
+                                    .insert(Flags::HEAD_TIMER | Flags::KEEPALIVE_REG);
+                                this.inner.io.start_keepalive_timer(head_timeout.into());
+                            } else if this.inner.flags.contains(Flags::KEEPALIVE)
                                 && !this.inner.flags.contains(Flags::KEEPALIVE_REG)
                             {
+                                // register keep-alive timer
                                 this.inner.flags.insert(Flags::KEEPALIVE_REG);
-                                this.inner
-                                    .io
-                                    .start_keepalive_timer(this.inner.config.keep_alive);
+                                this.inner.io.start_keepalive_timer(
+                                    this.inner.config.idle_timeout(),
+                                );
                             }
                             return Poll::Pending;
                         }
@@ -435,8 +632,47 @@ where
                             this.inner.flags.insert(Flags::SENDPAYLOAD_AND_STOP);
                         }
                         loop {
-                            let _ = ready!(this.inner.io.poll_flush(cx, false));
+                            if this.inner.io.poll_flush(cx, false).is_pending() {
+                                if this.inner.poll_write_timeout(cx).is_ready() {
+                                    log::trace!("response write timeout, close connection");
+                                    this.inner.error = Some(DispatchError::WriteTimeout);
+                                    this.inner.io.force_close();
+                                    *this.st = State::Stop;
+                                    break;
+                                }
+                                return Poll::Pending;
+                            }
+                            this.inner.write_timer = None;
+
+                            #[cfg(target_os = "linux")]
+                            if let Some((file, range)) = body.as_file() {
+                                if let Some(st) =
+                                    ready!(this.inner.poll_send_file(cx, file, range))
+                                {
+                                    *this.st = st;
+                                    break;
+                                }
+                            }
+
                             let item = ready!(body.poll_next_chunk(cx));
+
+                            #[cfg(target_os = "linux")]
+                            let item = match item {
+                                Some(Ok(chunk)) => {
+                                    match this.inner.write_vectored(cx, chunk) {
+                                        Ok(Some(chunk)) => Some(Ok(chunk)),
+                                        Ok(None) => continue,
+                                        Err(err) => {
+                                            this.inner.error =
+                                                Some(DispatchError::PeerGone(Some(err)));
+                                            *this.st = State::Stop;
+                                            break;
+                                        }
+                                    }
+                                }
+                                item => item,
+                            };
+
                             if let Some(st) = this.inner.send_payload(item) {
                                 *this.st = st;
                                 break;
@@ -529,8 +765,22 @@ where
         }
     }
 
+    /// Create request payload, buffered data is registered against memory budget
+    fn create_payload(&self) -> (PayloadSender, Payload) {
+        Payload::with_reservation(
+            false,
+            Reservation::new(
+                self.config.conn_memory_limit,
+                self.config.memory_budget.clone(),
+            ),
+        )
+    }
+
     fn send_response(&mut self, msg: Response<()>, body: ResponseBody<B>) -> State<B> {
         trace!("sending response: {:?} body: {:?}", msg, body.size());
+        #[cfg(feature = "metrics")]
+        crate::metrics::http_response(Protocol::Http1, msg.status(), self.started.take());
+
         // we dont need to process responses if socket is disconnected
         // but we still want to handle requests with app service
         // so we skip response processing for droppped connection
@@ -568,6 +818,31 @@ where
         }
     }
 
+    /// Handle request payload error, response is not sent yet
+    fn payload_error(&mut self, err: DispatchError) -> State<B> {
+        let slow = matches!(err, DispatchError::SlowPayloadTimeout);
+        self.error = Some(err);
+
+        if slow {
+            let (res, body) = Response::RequestTimeout().finish().into_parts();
+            self.send_response(res, body.into_body())
+        } else {
+            State::Stop
+        }
+    }
+
+    /// Check response write timeout
+    fn poll_write_timeout(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let timeout = self.config.timeouts.response_write;
+        if timeout.is_zero() {
+            Poll::Pending
+        } else {
+            self.write_timer
+                .get_or_insert_with(|| sleep(timeout))
+                .poll_elapsed(cx)
+        }
+    }
+
     fn send_payload(
         &mut self,
         item: Option<Result<Bytes, Box<dyn Error>>>,
@@ -604,6 +879,150 @@ where
         }
     }
 
+    #[cfg(target_os = "linux")]
+    /// Send file range to socket with `sendfile` syscall
+    ///
+    /// Returns `None` if zero-copy transmission is not available for connection.
+    fn poll_send_file(
+        &mut self,
+        cx: &mut Context<'_>,
+        file: &fs::File,
+        range: &mut ops::Range<u64>,
+    ) -> Poll<Option<State<B>>>
+    where
+        T: Filter,
+    {
+        use std::os::unix::io::AsRawFd;
+
+        // io stream must not be transformed by filters, i.e. tls
+        if any::TypeId::of::<T>() != any::TypeId::of::<Base>() {
+            return Poll::Ready(None);
+        }
+        let remaining = if let Some(remaining) = self.codec.payload_remaining() {
+            remaining
+        } else {
+            return Poll::Ready(None);
+        };
+        let raw = if let Some(raw) = self.io.query::<types::RawWrite>().as_ref() {
+            raw.clone()
+        } else {
+            return Poll::Ready(None);
+        };
+
+        // response head must be written before file data
+        if let Err(err) = ready!(self.io.poll_flush(cx, true)) {
+            self.error = Some(DispatchError::PeerGone(Some(err)));
+            return Poll::Ready(Some(State::Stop));
+        }
+
+        let end = cmp::min(range.end, range.start.saturating_add(remaining));
+        while range.start < end {
+            let count = cmp::min(end - range.start, MAX_SENDFILE_SIZE) as usize;
+            let mut offset = range.start as libc::off_t;
+            let result = ready!(raw.0.poll_write_raw(cx, &mut |fd| {
+                let n = unsafe { libc::sendfile(fd, file.as_raw_fd(), &mut offset, count) };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            }));
+
+            match result {
+                Ok(0) => {
+                    trace!("file is shorter than response body size");
+                    self.error = Some(DispatchError::ResponsePayload(Box::new(
+                        io::Error::new(io::ErrorKind::UnexpectedEof, "file is truncated"),
+                    )));
+                    return Poll::Ready(Some(State::Stop));
+                }
+                Ok(n) => {
+                    trace!("sent {:?} bytes of file body", n);
+                    range.start += n as u64;
+                    self.codec.payload_written(n as u64);
+                }
+                Err(err) => {
+                    trace!("error during sending file body: {:?}", err);
+                    self.error = Some(DispatchError::PeerGone(Some(err)));
+                    return Poll::Ready(Some(State::Stop));
+                }
+            }
+        }
+        Poll::Ready(self.send_payload(None))
+    }
+
+    #[cfg(target_os = "linux")]
+    /// Write buffered data and response chunk to socket with `writev` syscall
+    ///
+    /// Large chunks get written without copying to write buffer, returns
+    /// part of the chunk that is not written to socket.
+    fn write_vectored(
+        &mut self,
+        cx: &mut Context<'_>,
+        chunk: Bytes,
+    ) -> io::Result<Option<Bytes>>
+    where
+        T: Filter,
+    {
+        // io stream must not be transformed by filters, i.e. tls
+        if chunk.len() < MIN_VECTORED_WRITE_SIZE
+            || any::TypeId::of::<T>() != any::TypeId::of::<Base>()
+        {
+            return Ok(Some(chunk));
+        }
+        let remaining = if let Some(remaining) = self.codec.payload_remaining() {
+            remaining
+        } else {
+            return Ok(Some(chunk));
+        };
+        let raw = if let Some(raw) = self.io.query::<types::RawWrite>().as_ref() {
+            raw.clone()
+        } else {
+            return Ok(Some(chunk));
+        };
+
+        let size = cmp::min(remaining, chunk.len() as u64) as usize;
+        let written = self.io.with_write_buf(|buf| {
+            let len = buf.len();
+            let result = raw.0.poll_write_raw(cx, &mut |fd| {
+                let iov = [
+                    libc::iovec {
+                        iov_base: buf.as_ptr() as *mut libc::c_void,
+                        iov_len: len,
+                    },
+                    libc::iovec {
+                        iov_base: chunk.as_ptr() as *mut libc::c_void,
+                        iov_len: size,
+                    },
+                ];
+                let n = unsafe { libc::writev(fd, iov.as_ptr(), iov.len() as libc::c_int) };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+
+            match result {
+                Poll::Ready(Ok(n)) => {
+                    // remove written data from write buffer
+                    buf.advance(cmp::min(n, len));
+                    Ok(n.saturating_sub(len))
+                }
+                Poll::Ready(Err(err)) => Err(err),
+                Poll::Pending => Ok(0),
+            }
+        })??;
+
+        trace!("sent {:?} bytes of response chunk with writev", written);
+        self.codec.payload_written(written as u64);
+        if written == chunk.len() {
+            Ok(None)
+        } else {
+            Ok(Some(chunk.slice(written..)))
+        }
+    }
+
     /// Process request's payload
     fn poll_request_payload(
         &mut self,
@@ -632,6 +1051,7 @@ where
                             updated = true;
                             payload.1.feed_eof();
                             self.payload = None;
+                            self.body_timer = None;
                             break;
                         }
                         Poll::Ready(Err(err)) => {
@@ -680,6 +1100,23 @@ where
                 }
                 if updated {
                     Poll::Ready(Ok(()))
+                } else if self
+                    .body_timer
+                    .as_ref()
+                    .map(|timer| timer.poll_elapsed(cx).is_ready())
+                    .unwrap_or(false)
+                {
+                    log::trace!("request payload timeout");
+                    if let Some(mut payload) = self.payload.take() {
+                        payload.1.set_error(PayloadError::Incomplete(Some(
+                            io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "Request payload timeout",
+                            ),
+                        )));
+                    }
+                    self.body_timer = None;
+                    Poll::Ready(Err(DispatchError::SlowPayloadTimeout))
                 } else {
                     Poll::Pending
                 }
@@ -696,6 +1133,23 @@ where
     }
 }
 
+/// Poll service future, panic is converted to error if catching is enabled
+fn poll_service<F: Future>(
+    fut: Pin<&mut F>,
+    cx: &mut Context<'_>,
+    catch_panics: bool,
+) -> Poll<Result<F::Output, PanicError>> {
+    if catch_panics {
+        match catch_unwind(AssertUnwindSafe(|| fut.poll(cx))) {
+            Ok(Poll::Ready(res)) => Poll::Ready(Ok(res)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(PanicError::new(&*payload))),
+        }
+    } else {
+        fut.poll(cx).map(Ok)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
@@ -838,6 +1292,130 @@ mod tests {
         assert!(h1.inner.io.is_closed());
     }
 
+    #[crate::rt_test]
+    async fn test_memory_budget_exhausted() {
+        let (client, server) = Io::create();
+        client.remote_buffer_cap(1024);
+        client.write("GET /test HTTP/1.1\r\n\r\n");
+
+        let budget = crate::http::MemoryBudget::new(4);
+        let reservation = Reservation::new(usize::MAX, Some(budget.clone()));
+        reservation.charge(4);
+
+        let config = ServiceConfig::new(
+            Seconds(5).into(),
+            Millis(1_000),
+            Seconds::ZERO,
+            Millis(5_000),
+        )
+        .memory_budget(budget);
+        let mut h1 = Dispatcher::<_, _, _, _, UpgradeHandler<Base>>::new(
+            nio::Io::new(server),
+            Rc::new(DispatcherConfig::new(
+                config,
+                fn_service(|_| {
+                    Box::pin(async { Ok::<_, io::Error>(Response::Ok().finish()) })
+                }),
+                ExpectHandler,
+                None,
+                None,
+            )),
+        );
+        sleep(Millis(50)).await;
+        let _ = lazy(|cx| Pin::new(&mut h1).poll(cx)).await;
+        sleep(Millis(50)).await;
+        assert!(lazy(|cx| Pin::new(&mut h1).poll(cx)).await.is_ready());
+        assert!(h1.inner.io.is_closed());
+
+        client.local_buffer(|buf| {
+            assert_eq!(&buf[..34], b"HTTP/1.1 503 Service Unavailable\r\n")
+        });
+        client.close().await;
+    }
+
+    #[crate::rt_test]
+    async fn test_early_data() {
+        let (client, server) = Io::create();
+        client.remote_buffer_cap(4096);
+        let mut decoder = ClientCodec::default();
+
+        // requests are received in tls early data
+        let mut h1 = h1(server, |_| async {
+            Ok::<_, io::Error>(Response::Ok().finish())
+        });
+        h1.inner.flags.insert(Flags::EARLY_DATA);
+        client.write("POST /test1 HTTP/1.1\r\n\r\nGET /test2 HTTP/1.1\r\n\r\n");
+        crate::rt::spawn(h1);
+
+        let mut buf = BytesMut::from(&client.read().await.unwrap()[..]);
+        assert_eq!(load(&mut decoder, &mut buf).status.as_u16(), 425);
+        assert!(load(&mut decoder, &mut buf).status.is_success());
+
+        // early data is consumed
+        client.write("POST /test3 HTTP/1.1\r\n\r\n");
+        let mut buf = BytesMut::from(&client.read().await.unwrap()[..]);
+        assert!(load(&mut decoder, &mut buf).status.is_success());
+
+        client.close().await;
+    }
+
+    #[crate::rt_test]
+    async fn test_service_panic() {
+        let (client, server) = Io::create();
+        client.remote_buffer_cap(4096);
+        let mut decoder = ClientCodec::default();
+        spawn_h1(server, |req: Request| async move {
+            if req.path() == "/panic" {
+                panic!("service panic");
+            }
+            Ok::<_, io::Error>(Response::Ok().finish())
+        });
+
+        client.write("GET /test1 HTTP/1.1\r\n\r\n");
+        let mut buf = BytesMut::from(&client.read().await.unwrap()[..]);
+        assert!(load(&mut decoder, &mut buf).status.is_success());
+
+        // panic is converted to response, connection is closed
+        client.write("GET /panic HTTP/1.1\r\n\r\n");
+        let mut buf = BytesMut::from(&client.read().await.unwrap()[..]);
+        assert_eq!(
+            load(&mut decoder, &mut buf).status,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        // following requests are not processed
+        client.write("GET /test2 HTTP/1.1\r\n\r\n");
+        sleep(Millis(50)).await;
+        assert!(client.read_any().is_empty());
+        client.close().await;
+        assert!(client.is_server_dropped());
+
+        // fail fast
+        let (client, server) = Io::create();
+        client.remote_buffer_cap(4096);
+        let config = ServiceConfig::default().catch_panics(false);
+        let h1 = Dispatcher::<_, _, _, _, UpgradeHandler<Base>>::new(
+            nio::Io::new(server),
+            Rc::new(DispatcherConfig::new(
+                config,
+                fn_service(|req: Request| async move {
+                    if req.path() == "/panic" {
+                        panic!("service panic");
+                    }
+                    Ok::<_, io::Error>(Response::Ok().finish())
+                }),
+                ExpectHandler,
+                None,
+                None,
+            )),
+        );
+        client.write("GET /panic HTTP/1.1\r\n\r\n");
+        sleep(Millis(50)).await;
+        let mut h1 = Box::pin(h1);
+        let res = lazy(|cx| catch_unwind(AssertUnwindSafe(|| h1.as_mut().poll(cx)))).await;
+        assert!(res.is_err());
+    }
+
     #[crate::rt_test]
     async fn test_pipeline() {
         let (client, server) = Io::create();
@@ -995,7 +1573,10 @@ mod tests {
         assert!(h1.inner.io.is_closed());
 
         let mut buf = BytesMut::from(&client.read().await.unwrap()[..]);
-        assert_eq!(load(&mut decoder, &mut buf).status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            load(&mut decoder, &mut buf).status,
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        );
     }
 
     #[crate::rt_test]
@@ -1,5 +1,6 @@
 use std::marker::PhantomData;
-use std::{cell::Cell, cmp, io, io::Write, mem, ptr, ptr::copy_nonoverlapping, slice};
+use std::ptr::copy_nonoverlapping;
+use std::{cell::Cell, cell::Ref, cmp, io, io::Write, mem, ptr, slice};
 
 use crate::http::body::BodySize;
 use crate::http::config::DateService;
@@ -8,7 +9,9 @@ use crate::http::helpers;
 use crate::http::message::{ConnectionType, RequestHeadType};
 use crate::http::response::Response;
 use crate::http::{HeaderMap, StatusCode, Version};
-use crate::util::{BufMut, BytesMut};
+use crate::util::{BufMut, BytesMut, Extensions};
+
+use super::case::{recase, HeaderCase, HeaderCaseMap};
 
 const AVERAGE_HEADER_SIZE: usize = 30;
 
@@ -16,6 +19,7 @@ const AVERAGE_HEADER_SIZE: usize = 30;
 pub(super) struct MessageEncoder<T: MessageType> {
     pub(super) length: BodySize,
     pub(super) te: Cell<TransferEncoding>,
+    pub(super) case: Cell<HeaderCase>,
     _t: PhantomData<T>,
 }
 
@@ -24,6 +28,7 @@ impl<T: MessageType> Default for MessageEncoder<T> {
         MessageEncoder {
             length: BodySize::None,
             te: Cell::new(TransferEncoding::empty()),
+            case: Cell::new(HeaderCase::Lower),
             _t: PhantomData,
         }
     }
@@ -34,6 +39,7 @@ impl<T: MessageType> Clone for MessageEncoder<T> {
         MessageEncoder {
             length: self.length,
             te: self.te.clone(),
+            case: self.case.clone(),
             _t: PhantomData,
         }
     }
@@ -46,6 +52,8 @@ pub(super) trait MessageType: Sized {
 
     fn extra_headers(&self) -> Option<&HeaderMap>;
 
+    fn extensions(&self) -> Ref<'_, Extensions>;
+
     fn chunked(&self) -> bool;
 
     fn encode_status(&self, dst: &mut BytesMut) -> io::Result<()>;
@@ -106,10 +114,9 @@ pub(super) trait MessageType: Sized {
         let extra_headers = self.extra_headers().unwrap_or(&empty_headers);
         let headers = self
             .headers()
-            .inner
-            .iter()
+            .entries()
             .filter(|(name, _)| !extra_headers.contains_key(*name))
-            .chain(extra_headers.inner.iter());
+            .chain(extra_headers.entries());
 
         // write headers
         let mut pos = 0;
@@ -216,6 +223,10 @@ impl MessageType for Response<()> {
         None
     }
 
+    fn extensions(&self) -> Ref<'_, Extensions> {
+        self.head().extensions()
+    }
+
     fn encode_status(&self, dst: &mut BytesMut) -> io::Result<()> {
         let head = self.head();
         let reason = head.reason().as_bytes();
@@ -245,6 +256,10 @@ impl MessageType for RequestHeadType {
         self.extra_headers()
     }
 
+    fn extensions(&self) -> Ref<'_, Extensions> {
+        self.as_ref().extensions()
+    }
+
     fn encode_status(&self, dst: &mut BytesMut) -> io::Result<()> {
         let head = self.as_ref();
         dst.reserve(256 + head.headers.len() * AVERAGE_HEADER_SIZE);
@@ -275,6 +290,18 @@ impl<T: MessageType> MessageEncoder<T> {
         result
     }
 
+    /// Remaining size of the sized body
+    pub(super) fn remaining(&self) -> Option<u64> {
+        self.te.get().remaining()
+    }
+
+    /// Account body data written directly to io stream
+    pub(super) fn consume(&self, size: u64) {
+        let mut te = self.te.get();
+        te.consume(size);
+        self.te.set(te);
+    }
+
     /// Encode eof
     pub(super) fn encode_eof(&self, buf: &mut BytesMut) -> io::Result<()> {
         let mut te = self.te.get();
@@ -313,7 +340,18 @@ impl<T: MessageType> MessageEncoder<T> {
         }
 
         message.encode_status(dst)?;
-        message.encode_headers(dst, version, length, ctype, timer)
+        let pos = dst.len();
+        message.encode_headers(dst, version, length, ctype, timer)?;
+
+        let case = self.case.get();
+        if case != HeaderCase::Lower {
+            recase(
+                &mut dst[pos..],
+                case,
+                message.extensions().get::<HeaderCaseMap>(),
+            );
+        }
+        Ok(())
     }
 }
 
@@ -366,6 +404,21 @@ impl TransferEncoding {
         }
     }
 
+    #[inline]
+    fn remaining(&self) -> Option<u64> {
+        match self.kind {
+            TransferEncodingKind::Length(remaining) if remaining > 0 => Some(remaining),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn consume(&mut self, size: u64) {
+        if let TransferEncodingKind::Length(ref mut remaining) = self.kind {
+            *remaining = remaining.saturating_sub(size);
+        }
+    }
+
     /// Encode message. Return `EOF` state of encoder
     #[inline]
     pub(super) fn encode(&mut self, msg: &[u8], buf: &mut BytesMut) -> io::Result<bool> {
@@ -437,6 +490,30 @@ impl TransferEncoding {
     }
 }
 
+/// Encode informational (1xx) response head
+pub(crate) fn encode_informational(
+    status: StatusCode,
+    headers: &HeaderMap,
+    dst: &mut BytesMut,
+) {
+    let reason = match status.as_u16() {
+        103 => "Early Hints",
+        _ => status.canonical_reason().unwrap_or(""),
+    };
+    dst.reserve(256 + headers.len() * AVERAGE_HEADER_SIZE + reason.len());
+
+    write_status_line(Version::HTTP_11, status.as_u16(), dst);
+    dst.extend_from_slice(reason.as_bytes());
+    dst.extend_from_slice(b"\r\n");
+    for (key, value) in headers.iter() {
+        dst.extend_from_slice(key.as_str().as_bytes());
+        dst.extend_from_slice(b": ");
+        dst.extend_from_slice(value.as_ref());
+        dst.extend_from_slice(b"\r\n");
+    }
+    dst.extend_from_slice(b"\r\n");
+}
+
 const DEC_DIGITS_LUT: &[u8] = b"0001020304050607080910111213141516171819\
       2021222324252627282930313233343536373839\
       4041424344454647484950515253545556575859\
@@ -600,6 +677,29 @@ mod tests {
         assert_eq!(bytes.split(), Bytes::from_static(b"4\r\ntest\r\n0\r\n\r\n"));
     }
 
+    #[test]
+    fn test_informational() {
+        let mut bytes = BytesMut::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            crate::http::header::LINK,
+            HeaderValue::from_static("</style.css>; rel=preload; as=style"),
+        );
+        encode_informational(StatusCode::from_u16(103).unwrap(), &headers, &mut bytes);
+        assert_eq!(
+            bytes.split(),
+            Bytes::from_static(
+                b"HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload; as=style\r\n\r\n"
+            )
+        );
+
+        encode_informational(StatusCode::PROCESSING, &HeaderMap::new(), &mut bytes);
+        assert_eq!(
+            bytes.split(),
+            Bytes::from_static(b"HTTP/1.1 102 Processing\r\n\r\n")
+        );
+    }
+
     #[test]
     fn test_extra_headers() {
         let mut bytes = BytesMut::with_capacity(2048);
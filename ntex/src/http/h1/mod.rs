@@ -1,6 +1,7 @@
 //! HTTP/1 implementation
 use crate::util::{Bytes, BytesMut};
 
+mod case;
 mod client;
 mod codec;
 mod decoder;
@@ -8,9 +9,11 @@ mod dispatcher;
 mod encoder;
 mod expect;
 mod payload;
+mod scan;
 mod service;
 mod upgrade;
 
+pub use self::case::{HeaderCase, HeaderCaseMap};
 pub use self::client::{ClientCodec, ClientPayloadCodec};
 pub use self::codec::Codec;
 pub use self::decoder::{PayloadDecoder, PayloadItem, PayloadType};
@@ -19,7 +22,9 @@ pub use self::payload::Payload;
 pub use self::service::{H1Service, H1ServiceHandler};
 pub use self::upgrade::UpgradeHandler;
 
+pub(super) use self::decoder::HeadLimits;
 pub(super) use self::dispatcher::Dispatcher;
+pub(super) use self::encoder::encode_informational;
 
 const MAX_BUFFER_SIZE: usize = 32_768;
 
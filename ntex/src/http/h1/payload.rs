@@ -3,7 +3,7 @@ use std::rc::{Rc, Weak};
 use std::task::{Context, Poll};
 use std::{cell::RefCell, collections::VecDeque, pin::Pin};
 
-use crate::http::error::PayloadError;
+use crate::http::{budget::Reservation, error::PayloadError};
 use crate::{task::LocalWaker, util::Bytes, util::Stream};
 
 /// max buffer size 32k
@@ -38,7 +38,15 @@ impl Payload {
     ///
     /// * `Payload` - *Receiver* side of the stream
     pub fn create(eof: bool) -> (PayloadSender, Payload) {
-        let shared = Rc::new(RefCell::new(Inner::new(eof)));
+        Payload::with_reservation(eof, Reservation::new(MAX_BUFFER_SIZE, None))
+    }
+
+    /// Create payload stream, buffered data is registered against reservation.
+    pub(crate) fn with_reservation(
+        eof: bool,
+        reservation: Reservation,
+    ) -> (PayloadSender, Payload) {
+        let shared = Rc::new(RefCell::new(Inner::new(eof, reservation)));
 
         (
             PayloadSender {
@@ -52,7 +60,10 @@ impl Payload {
     #[doc(hidden)]
     pub fn empty() -> Payload {
         Payload {
-            inner: Rc::new(RefCell::new(Inner::new(true))),
+            inner: Rc::new(RefCell::new(Inner::new(
+                true,
+                Reservation::new(MAX_BUFFER_SIZE, None),
+            ))),
         }
     }
 
@@ -139,12 +150,14 @@ struct Inner {
     items: VecDeque<Bytes>,
     task: LocalWaker,
     io_task: LocalWaker,
+    reservation: Reservation,
 }
 
 impl Inner {
-    fn new(eof: bool) -> Self {
+    fn new(eof: bool, reservation: Reservation) -> Self {
         Inner {
             eof,
+            reservation,
             len: 0,
             err: None,
             items: VecDeque::new(),
@@ -166,8 +179,8 @@ impl Inner {
 
     fn feed_data(&mut self, data: Bytes) {
         self.len += data.len();
+        self.need_read = self.reservation.charge(data.len());
         self.items.push_back(data);
-        self.need_read = self.len < MAX_BUFFER_SIZE;
         self.task.wake();
     }
 
@@ -177,7 +190,7 @@ impl Inner {
     ) -> Poll<Option<Result<Bytes, PayloadError>>> {
         if let Some(data) = self.items.pop_front() {
             self.len -= data.len();
-            self.need_read = self.len < MAX_BUFFER_SIZE;
+            self.need_read = self.reservation.release(data.len());
 
             if self.need_read && !self.eof {
                 self.task.register(cx.waker());
@@ -189,6 +202,8 @@ impl Inner {
         } else if self.eof {
             Poll::Ready(None)
         } else {
+            // buffer is empty, keep reading even if global budget
+            // is exhausted, otherwise payload could stall forever
             self.need_read = true;
             self.task.register(cx.waker());
             self.io_task.wake();
@@ -198,6 +213,7 @@ impl Inner {
 
     fn unread_data(&mut self, data: Bytes) {
         self.len += data.len();
+        self.reservation.charge(data.len());
         self.items.push_front(data);
     }
 }
@@ -205,7 +221,7 @@ impl Inner {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::util::poll_fn;
+    use crate::{http::MemoryBudget, util::poll_fn};
 
     #[crate::rt_test]
     async fn test_unread_data() {
@@ -219,4 +235,39 @@ mod tests {
             poll_fn(|cx| payload.readany(cx)).await.unwrap().unwrap()
         );
     }
+
+    #[crate::rt_test]
+    async fn test_memory_budget() {
+        let budget = MemoryBudget::new(16);
+        let (mut sender, mut payload) =
+            Payload::with_reservation(false, Reservation::new(10, Some(budget.clone())));
+
+        sender.feed_data(Bytes::from("data"));
+        assert_eq!(budget.used(), 4);
+        assert!(payload.inner.borrow().need_read);
+
+        // connection limit
+        sender.feed_data(Bytes::from("data2data2"));
+        assert_eq!(budget.used(), 14);
+        assert!(!payload.inner.borrow().need_read);
+
+        let _ = poll_fn(|cx| payload.readany(cx)).await;
+        assert_eq!(budget.used(), 10);
+        assert!(!payload.inner.borrow().need_read);
+        let _ = poll_fn(|cx| payload.readany(cx)).await;
+        assert_eq!(budget.used(), 0);
+        assert!(payload.inner.borrow().need_read);
+
+        // global budget
+        let (mut sender2, payload2) =
+            Payload::with_reservation(false, Reservation::new(100, Some(budget.clone())));
+        sender2.feed_data(Bytes::from("0123456789abcdef"));
+        sender.feed_data(Bytes::from("data"));
+        assert!(!payload.inner.borrow().need_read);
+
+        drop(payload);
+        assert_eq!(budget.used(), 16);
+        drop(payload2);
+        assert_eq!(budget.used(), 0);
+    }
 }
@@ -0,0 +1,159 @@
+//! Vectorized byte scanning for message head parsing
+//!
+//! Uses SSE2 on x86_64 and NEON on aarch64, both are part of
+//! the baseline target features, so no runtime detection is required.
+//! Other targets use word-at-a-time scanning.
+
+/// Find position of the first CR or LF byte.
+#[inline]
+pub(super) fn find_line_byte(buf: &[u8]) -> Option<usize> {
+    let idx = imp::skip_line_bytes(buf);
+    buf[idx..]
+        .iter()
+        .position(|b| *b == b'\r' || *b == b'\n')
+        .map(|pos| idx + pos)
+}
+
+/// Check if buffer contains complete message head.
+///
+/// Head is complete if it contains empty line, `LF` followed by `LF`
+/// or by `CRLF`. Leading empty lines could give false positive result.
+#[inline]
+pub(super) fn is_head_complete(buf: &[u8]) -> bool {
+    let mut idx = 0;
+    while let Some(pos) = find_line_byte(&buf[idx..]) {
+        idx += pos + 1;
+        if buf[idx - 1] == b'\n' {
+            match buf.get(idx) {
+                Some(b'\n') => return true,
+                Some(b'\r') if buf.get(idx + 1) == Some(&b'\n') => return true,
+                _ => (),
+            }
+        }
+    }
+    false
+}
+
+#[cfg(target_arch = "x86_64")]
+mod imp {
+    use std::arch::x86_64::*;
+
+    /// Skip 16 byte blocks without CR and LF bytes.
+    #[inline]
+    pub(super) fn skip_line_bytes(buf: &[u8]) -> usize {
+        let mut idx = 0;
+        // Safety: sse2 is always available on x86_64,
+        // loads are unaligned and within buffer bounds.
+        unsafe {
+            let cr = _mm_set1_epi8(b'\r' as i8);
+            let lf = _mm_set1_epi8(b'\n' as i8);
+            while idx + 16 <= buf.len() {
+                let chunk = _mm_loadu_si128(buf.as_ptr().add(idx) as *const __m128i);
+                let found =
+                    _mm_or_si128(_mm_cmpeq_epi8(chunk, cr), _mm_cmpeq_epi8(chunk, lf));
+                let mask = _mm_movemask_epi8(found) as u32;
+                if mask != 0 {
+                    return idx + mask.trailing_zeros() as usize;
+                }
+                idx += 16;
+            }
+        }
+        idx
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod imp {
+    use std::arch::aarch64::*;
+
+    /// Skip 16 byte blocks without CR and LF bytes.
+    #[inline]
+    pub(super) fn skip_line_bytes(buf: &[u8]) -> usize {
+        let mut idx = 0;
+        // Safety: neon is always available on aarch64,
+        // loads are within buffer bounds.
+        unsafe {
+            let cr = vdupq_n_u8(b'\r');
+            let lf = vdupq_n_u8(b'\n');
+            while idx + 16 <= buf.len() {
+                let chunk = vld1q_u8(buf.as_ptr().add(idx));
+                let found = vorrq_u8(vceqq_u8(chunk, cr), vceqq_u8(chunk, lf));
+                if vmaxvq_u8(found) != 0 {
+                    return idx;
+                }
+                idx += 16;
+            }
+        }
+        idx
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod imp {
+    use std::convert::TryInto;
+
+    const LO: u64 = 0x0101_0101_0101_0101;
+    const HI: u64 = 0x8080_8080_8080_8080;
+    const CR: u64 = LO * b'\r' as u64;
+    const LF: u64 = LO * b'\n' as u64;
+
+    #[inline]
+    fn has_zero(v: u64) -> bool {
+        v.wrapping_sub(LO) & !v & HI != 0
+    }
+
+    /// Skip 8 byte words without CR and LF bytes.
+    #[inline]
+    pub(super) fn skip_line_bytes(buf: &[u8]) -> usize {
+        let mut idx = 0;
+        while idx + 8 <= buf.len() {
+            let v = u64::from_ne_bytes(buf[idx..idx + 8].try_into().unwrap());
+            if has_zero(v ^ CR) || has_zero(v ^ LF) {
+                break;
+            }
+            idx += 8;
+        }
+        idx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_line_byte() {
+        assert_eq!(find_line_byte(b""), None);
+        assert_eq!(find_line_byte(b"\n"), Some(0));
+        assert_eq!(find_line_byte(b"GET / HTTP/1.1"), None);
+        assert_eq!(find_line_byte(b"GET / HTTP/1.1\r\n"), Some(14));
+
+        let mut buf = vec![b'a'; 100];
+        assert_eq!(find_line_byte(&buf), None);
+        for pos in 0..buf.len() {
+            buf[pos] = b'\r';
+            assert_eq!(find_line_byte(&buf), Some(pos));
+            buf[pos] = b'\n';
+            assert_eq!(find_line_byte(&buf), Some(pos));
+            buf[pos] = 0xff;
+            assert_eq!(find_line_byte(&buf), None);
+        }
+    }
+
+    #[test]
+    fn test_is_head_complete() {
+        assert!(!is_head_complete(b""));
+        assert!(!is_head_complete(b"GET / HTTP/1.1\r\n"));
+        assert!(!is_head_complete(b"GET / HTTP/1.1\r\nHost: a\r\n\r"));
+        assert!(!is_head_complete(b"GET / HTTP/1.1\r\r\n"));
+        assert!(is_head_complete(b"GET / HTTP/1.1\r\n\r\n"));
+        assert!(is_head_complete(b"GET / HTTP/1.1\n\n"));
+        assert!(is_head_complete(b"GET / HTTP/1.1\r\nHost: a\n\r\nbody"));
+
+        let mut buf = b"GET / HTTP/1.1\r\n".to_vec();
+        buf.extend_from_slice(&[b'a'; 100]);
+        assert!(!is_head_complete(&buf));
+        buf.extend_from_slice(b"\r\n\r\n");
+        assert!(is_head_complete(&buf));
+    }
+}
@@ -1,12 +1,13 @@
 use std::task::{Context, Poll};
-use std::{convert::TryFrom, future::Future, marker::PhantomData, pin::Pin, rc::Rc, time};
+use std::{cell::Cell, convert::TryFrom, future::Future, marker::PhantomData, pin::Pin};
+use std::{rc::Rc, time};
 
 use h2::server::{Connection, SendResponse};
 use h2::SendStream;
 use log::{error, trace};
 
 use crate::http::body::{BodySize, MessageBody, ResponseBody};
-use crate::http::config::{DateService, DispatcherConfig};
+use crate::http::config::{DataFactory, DateService, DispatcherConfig};
 use crate::http::error::{DispatchError, ResponseError};
 use crate::http::header::{
     HeaderValue, CONNECTION, CONTENT_LENGTH, DATE, TRANSFER_ENCODING,
@@ -14,8 +15,11 @@ use crate::http::header::{
 use crate::http::message::{CurrentIo, ResponseHead};
 use crate::http::{payload::Payload, request::Request, response::Response};
 use crate::io::{IoRef, TokioIoBoxed};
+#[cfg(feature = "metrics")]
+use crate::metrics::Protocol;
 use crate::service::Service;
-use crate::time::{now, Sleep};
+use crate::time::{now, sleep, Millis, Sleep};
+use crate::tls::types::EarlyData;
 use crate::util::{Bytes, BytesMut};
 
 const CHUNK_SIZE: usize = 16_384;
@@ -28,10 +32,45 @@ pin_project_lite::pin_project! {
         connection: Connection<TokioIoBoxed, Bytes>,
         ka_expire: time::Instant,
         ka_timer: Option<Sleep>,
+        idle_timer: Option<Sleep>,
+        streams: Rc<Streams>,
+        data: Option<Box<dyn DataFactory>>,
+        early_data: bool,
         _t: PhantomData<B>,
     }
 }
 
+/// Active streams tracker
+struct Streams {
+    active: Cell<usize>,
+    updated: Cell<time::Instant>,
+}
+
+/// Decrements active streams counter on drop
+struct StreamGuard {
+    streams: Rc<Streams>,
+    #[cfg(feature = "metrics")]
+    started: time::Instant,
+}
+
+impl StreamGuard {
+    fn new(streams: &Rc<Streams>) -> Self {
+        streams.active.set(streams.active.get() + 1);
+        StreamGuard {
+            streams: streams.clone(),
+            #[cfg(feature = "metrics")]
+            started: crate::metrics::http_request(Protocol::Http2),
+        }
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.streams.active.set(self.streams.active.get() - 1);
+        self.streams.updated.set(time::Instant::now());
+    }
+}
+
 impl<S, B, X, U> Dispatcher<S, B, X, U>
 where
     S: Service<Request> + 'static,
@@ -56,12 +95,32 @@ where
             (now(), None)
         };
 
+        // idle connection timer
+        let idle = config.timeouts.idle_connection;
+        let idle_timer = if idle.is_zero() {
+            None
+        } else {
+            Some(sleep(idle))
+        };
+        let streams = Rc::new(Streams {
+            active: Cell::new(0),
+            updated: Cell::new(time::Instant::now()),
+        });
+
+        // connection level data
+        let data = config.on_connect.as_ref().map(|f| f(&io));
+        let early_data = io.query::<EarlyData>().get().is_some();
+
         Dispatcher {
             io,
             config,
             connection,
             ka_expire,
             ka_timer,
+            idle_timer,
+            streams,
+            data,
+            early_data,
             _t: PhantomData,
         }
     }
@@ -84,9 +143,27 @@ where
             match Pin::new(&mut this.connection).poll_accept(cx) {
                 Poll::Ready(None) => return Poll::Ready(Ok(())),
                 Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err.into())),
-                Poll::Ready(Some(Ok((req, res)))) => {
+                Poll::Ready(Some(Ok((req, mut res)))) => {
                     trace!("h2 message is received: {:?}", req);
 
+                    // reject streams while global memory budget is exhausted
+                    if this.config.memory_exhausted() {
+                        trace!("memory budget is exhausted, reject stream");
+                        let mut h2_res = http::Response::new(());
+                        *h2_res.status_mut() = http::StatusCode::SERVICE_UNAVAILABLE;
+                        let _ = res.send_response(h2_res, true);
+                        continue;
+                    }
+
+                    // early data could be replayed, reject non-idempotent requests
+                    if this.early_data && !req.method().is_idempotent() {
+                        trace!("reject {:?} request in early data", req.method());
+                        let mut h2_res = http::Response::new(());
+                        *h2_res.status_mut() = http::StatusCode::from_u16(425).unwrap();
+                        let _ = res.send_response(h2_res, true);
+                        continue;
+                    }
+
                     // update keep-alive expire
                     if this.ka_timer.is_some() {
                         if let Some(expire) = this.config.keep_alive_expire() {
@@ -96,7 +173,10 @@ where
 
                     let (parts, body) = req.into_parts();
                     let mut req = Request::with_payload(Payload::H2(
-                        crate::http::h2::Payload::new(body),
+                        crate::http::h2::Payload::with_timeout(
+                            body,
+                            this.config.timeouts.request_body,
+                        ),
                     ));
 
                     let head = &mut req.head_mut();
@@ -106,6 +186,11 @@ where
                     head.headers = parts.headers.into();
                     head.io = CurrentIo::Ref(this.io.clone());
 
+                    // set connection level data
+                    if let Some(ref data) = this.data {
+                        data.set(&mut req.extensions_mut());
+                    }
+
                     crate::rt::spawn(ServiceResponse {
                         state: ServiceResponseState::ServiceCall {
                             call: this.config.service.call(req),
@@ -113,10 +198,39 @@ where
                         },
                         timer: this.config.timer.clone(),
                         buffer: None,
+                        write_timeout: this.config.timeouts.response_write,
+                        write_timer: None,
+                        _guard: StreamGuard::new(&this.streams),
                         _t: PhantomData,
                     });
                 }
-                Poll::Pending => return Poll::Pending,
+                Poll::Pending => {
+                    // early data is consumed
+                    this.early_data = false;
+
+                    // idle connection timeout
+                    if let Some(ref timer) = this.idle_timer {
+                        if timer.poll_elapsed(cx).is_ready() {
+                            let idle = this.config.timeouts.idle_connection;
+                            let expire =
+                                this.streams.updated.get() + time::Duration::from(idle);
+                            let now = time::Instant::now();
+
+                            if this.streams.active.get() == 0 && expire <= now {
+                                trace!("h2 connection is idle, shutting down");
+                                this.idle_timer = None;
+                                this.connection.graceful_shutdown();
+                                continue;
+                            } else if this.streams.active.get() == 0 {
+                                timer.reset(Millis::from(expire - now));
+                            } else {
+                                timer.reset(idle);
+                            }
+                            let _ = timer.poll_elapsed(cx);
+                        }
+                    }
+                    return Poll::Pending;
+                }
             }
         }
     }
@@ -128,6 +242,9 @@ pin_project_lite::pin_project! {
         state: ServiceResponseState<F, B>,
         timer: DateService,
         buffer: Option<Bytes>,
+        write_timeout: Millis,
+        write_timer: Option<Sleep>,
+        _guard: StreamGuard,
         _t: PhantomData<(I, E)>,
     }
 }
@@ -155,6 +272,13 @@ where
         let mut has_date = false;
         let mut skip_len = size != &BodySize::Stream;
 
+        #[cfg(feature = "metrics")]
+        crate::metrics::http_response(
+            Protocol::Http2,
+            head.status,
+            Some(self._guard.started),
+        );
+
         let mut res = http::Response::new(());
         *res.status_mut() = head.status;
         *res.version_mut() = http::Version::HTTP_2;
@@ -278,9 +402,23 @@ where
                 loop {
                     if let Some(buffer) = this.buffer {
                         match stream.poll_capacity(cx) {
-                            Poll::Pending => return Poll::Pending,
+                            Poll::Pending => {
+                                if !this.write_timeout.is_zero() {
+                                    let timeout = *this.write_timeout;
+                                    let timer = this
+                                        .write_timer
+                                        .get_or_insert_with(|| sleep(timeout));
+                                    if timer.poll_elapsed(cx).is_ready() {
+                                        trace!("h2 response write timeout, reset stream");
+                                        stream.send_reset(h2::Reason::CANCEL);
+                                        return Poll::Ready(());
+                                    }
+                                }
+                                return Poll::Pending;
+                            }
                             Poll::Ready(None) => return Poll::Ready(()),
                             Poll::Ready(Some(Ok(cap))) => {
+                                *this.write_timer = None;
                                 let len = buffer.len();
                                 let bytes = buffer.split_to(std::cmp::min(cap, len));
 
@@ -0,0 +1,248 @@
+//! HTTP/2 over cleartext tcp (h2c) support
+use crate::codec::Decoder;
+use crate::http::error::ParseError;
+use crate::http::header::{self, HeaderValue};
+use crate::http::{RequestHead, Version};
+use crate::util::{BufMut, Bytes, BytesMut};
+
+/// HTTP/2 client connection preface
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Response for accepted `Upgrade: h2c` request
+pub(in crate::http) const UPGRADE_RESPONSE: &[u8] =
+    b"HTTP/1.1 101 Switching Protocols\r\nconnection: upgrade\r\nupgrade: h2c\r\n\r\n";
+
+const HTTP2_SETTINGS: &str = "http2-settings";
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_SETTINGS: u8 = 0x4;
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const MAX_FRAME_SIZE: usize = 16_384;
+
+/// Prior knowledge detection.
+///
+/// Decodes `true` if connection starts with http/2 connection preface,
+/// `false` if it is not a http/2 connection. Does not consume any data.
+pub(in crate::http) struct Preface;
+
+impl Decoder for Preface {
+    type Item = bool;
+    type Error = ParseError;
+
+    fn decode(&self, src: &mut BytesMut) -> Result<Option<bool>, ParseError> {
+        if src.starts_with(PREFACE) {
+            Ok(Some(true))
+        } else if src.is_empty() || PREFACE.starts_with(src) {
+            Ok(None)
+        } else {
+            Ok(Some(false))
+        }
+    }
+}
+
+/// Connection preface after `Upgrade: h2c` request.
+///
+/// Decodes position right after client's initial `SETTINGS` frame.
+/// Does not consume any data.
+pub(in crate::http) struct UpgradePreface;
+
+impl Decoder for UpgradePreface {
+    type Item = usize;
+    type Error = ParseError;
+
+    fn decode(&self, src: &mut BytesMut) -> Result<Option<usize>, ParseError> {
+        if src.len() < PREFACE.len() + 9 {
+            if PREFACE.starts_with(&src[..std::cmp::min(src.len(), PREFACE.len())]) {
+                Ok(None)
+            } else {
+                Err(ParseError::InvalidInput(
+                    "Invalid http/2 connection preface",
+                ))
+            }
+        } else if !src.starts_with(PREFACE) || src[PREFACE.len() + 3] != FRAME_SETTINGS {
+            Err(ParseError::InvalidInput(
+                "Invalid http/2 connection preface",
+            ))
+        } else {
+            let hdr = &src[PREFACE.len()..];
+            let len = (hdr[0] as usize) << 16 | (hdr[1] as usize) << 8 | hdr[2] as usize;
+            let pos = PREFACE.len() + 9 + len;
+            if src.len() >= pos {
+                Ok(Some(pos))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Check if request is `Upgrade: h2c` request.
+///
+/// Returns `HEADERS` frame for stream 1 that represents upgrade request.
+pub(in crate::http) fn upgrade_frame(head: &RequestHead) -> Option<Bytes> {
+    if head.version != Version::HTTP_11
+        || head.headers.get_all(HTTP2_SETTINGS).count() != 1
+        || !head
+            .headers
+            .get(header::UPGRADE)
+            .and_then(|val| val.to_str().ok())
+            .map(|val| val.split(',').any(|v| v.trim().eq_ignore_ascii_case("h2c")))
+            .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let mut block = BytesMut::new();
+    let path = head.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    encode_header(&mut block, b":method", head.method.as_str().as_bytes());
+    encode_header(&mut block, b":scheme", b"http");
+    encode_header(&mut block, b":path", path.as_bytes());
+    if let Some(host) = head.headers.get(header::HOST) {
+        encode_header(&mut block, b":authority", host.as_bytes());
+    }
+    for (name, value) in head.headers.iter() {
+        if !is_connection_header(name, value) {
+            encode_header(&mut block, name.as_str().as_bytes(), value.as_bytes());
+        }
+    }
+    if block.len() > MAX_FRAME_SIZE {
+        log::trace!("h2c upgrade request headers are too large");
+        return None;
+    }
+
+    let mut frame = BytesMut::with_capacity(block.len() + 9);
+    frame.put_uint(block.len() as u64, 3);
+    frame.put_u8(FRAME_HEADERS);
+    frame.put_u8(FLAG_END_STREAM | FLAG_END_HEADERS);
+    frame.put_u32(1);
+    frame.extend_from_slice(&block);
+    Some(frame.freeze())
+}
+
+/// Connection specific headers are not allowed in http/2
+fn is_connection_header(name: &header::HeaderName, value: &HeaderValue) -> bool {
+    match *name {
+        header::CONNECTION
+        | header::UPGRADE
+        | header::HOST
+        | header::TRANSFER_ENCODING
+        | header::CONTENT_LENGTH => true,
+        header::TE => value != "trailers",
+        _ => name == HTTP2_SETTINGS || name == "keep-alive" || name == "proxy-connection",
+    }
+}
+
+/// Encode header as hpack literal without indexing
+fn encode_header(dst: &mut BytesMut, name: &[u8], value: &[u8]) {
+    dst.put_u8(0);
+    encode_int(dst, name.len());
+    dst.extend_from_slice(name);
+    encode_int(dst, value.len());
+    dst.extend_from_slice(value);
+}
+
+/// Encode hpack integer with 7-bit prefix
+fn encode_int(dst: &mut BytesMut, mut val: usize) {
+    if val < 0x7f {
+        dst.put_u8(val as u8);
+    } else {
+        dst.put_u8(0x7f);
+        val -= 0x7f;
+        while val >= 0x80 {
+            dst.put_u8((val & 0x7f) as u8 | 0x80);
+            val >>= 7;
+        }
+        dst.put_u8(val as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{header::HeaderName, Method};
+
+    #[test]
+    fn test_preface() {
+        let mut buf = BytesMut::new();
+        assert_eq!(Preface.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"PRI * HTTP");
+        assert_eq!(Preface.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"/2.0\r\n\r\nSM\r\n\r\n");
+        assert_eq!(Preface.decode(&mut buf).unwrap(), Some(true));
+        assert_eq!(buf.len(), PREFACE.len());
+
+        let mut buf = BytesMut::from("POST / HTTP/1.1\r\n");
+        assert_eq!(Preface.decode(&mut buf).unwrap(), Some(false));
+        let mut buf = BytesMut::from("PRI / HTTP/1.1\r\n");
+        assert_eq!(Preface.decode(&mut buf).unwrap(), Some(false));
+    }
+
+    #[test]
+    fn test_upgrade_preface() {
+        let mut buf = BytesMut::from(&PREFACE[..10]);
+        assert_eq!(UpgradePreface.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&PREFACE[10..]);
+        buf.extend_from_slice(&[0, 0, 6, FRAME_SETTINGS, 0, 0, 0, 0, 0]);
+        assert_eq!(UpgradePreface.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&[0, 3, 0, 0, 0, 100, 0, 0]);
+        assert_eq!(UpgradePreface.decode(&mut buf).unwrap(), Some(39));
+
+        let mut buf = BytesMut::from("GET / HTTP/1.1\r\n");
+        assert!(UpgradePreface.decode(&mut buf).is_err());
+        let mut buf = BytesMut::from(PREFACE);
+        buf.extend_from_slice(&[0, 0, 0, FRAME_HEADERS, 0, 0, 0, 0, 0]);
+        assert!(UpgradePreface.decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_upgrade_frame() {
+        let mut head = RequestHead {
+            method: Method::GET,
+            uri: "/test?q=1".parse().unwrap(),
+            ..Default::default()
+        };
+        head.headers
+            .insert(header::HOST, HeaderValue::from_static("localhost"));
+        head.headers
+            .insert(header::UPGRADE, HeaderValue::from_static("h2c"));
+        assert!(upgrade_frame(&head).is_none());
+
+        head.headers.insert(
+            HeaderName::from_static(HTTP2_SETTINGS),
+            HeaderValue::from_static(""),
+        );
+        head.headers.insert(
+            header::CONNECTION,
+            HeaderValue::from_static("Upgrade, HTTP2-Settings"),
+        );
+        head.headers.insert(
+            HeaderName::from_static("x-test"),
+            HeaderValue::from_static("value"),
+        );
+        let frame = upgrade_frame(&head).unwrap();
+        assert_eq!(&frame[3..9], &[FRAME_HEADERS, 0x5, 0, 0, 0, 1]);
+
+        let mut block = BytesMut::new();
+        encode_header(&mut block, b":method", b"GET");
+        encode_header(&mut block, b":scheme", b"http");
+        encode_header(&mut block, b":path", b"/test?q=1");
+        encode_header(&mut block, b":authority", b"localhost");
+        encode_header(&mut block, b"x-test", b"value");
+        assert_eq!(&frame[9..], &block[..]);
+        assert_eq!(frame[2] as usize, block.len());
+
+        head.version = Version::HTTP_10;
+        assert!(upgrade_frame(&head).is_none());
+    }
+
+    #[test]
+    fn test_encode_int() {
+        let mut buf = BytesMut::new();
+        encode_int(&mut buf, 10);
+        assert_eq!(&buf[..], &[10]);
+
+        let mut buf = BytesMut::new();
+        encode_int(&mut buf, 1337);
+        assert_eq!(&buf[..], &[0x7f, 0xba, 0x09]);
+    }
+}
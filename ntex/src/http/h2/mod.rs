@@ -1,25 +1,38 @@
 //! HTTP/2 implementation
-use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::{io, pin::Pin};
 
 use h2::RecvStream;
 
 mod dispatcher;
+pub(super) mod h2c;
 mod service;
 
 pub use self::dispatcher::Dispatcher;
 pub use self::service::H2Service;
+use crate::time::{sleep, Millis, Sleep};
 use crate::{http::error::PayloadError, util::Bytes, util::Stream};
 
 /// H2 receive stream
 #[derive(Debug)]
 pub struct Payload {
     pl: RecvStream,
+    timer: Option<Sleep>,
 }
 
 impl Payload {
     pub(crate) fn new(pl: RecvStream) -> Self {
-        Self { pl }
+        Self { pl, timer: None }
+    }
+
+    /// Receive stream must complete within specified timeout
+    pub(crate) fn with_timeout(pl: RecvStream, timeout: Millis) -> Self {
+        let timer = if timeout.is_zero() {
+            None
+        } else {
+            Some(sleep(timeout))
+        };
+        Self { pl, timer }
     }
 }
 
@@ -39,8 +52,24 @@ impl Stream for Payload {
                 }
             }
             Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err.into()))),
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => {
+                if let Some(ref timer) = this.timer {
+                    if timer.poll_elapsed(cx).is_ready() {
+                        this.timer = None;
+                        return Poll::Ready(Some(Err(PayloadError::Incomplete(Some(
+                            io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "Request payload timeout",
+                            ),
+                        )))));
+                    }
+                }
+                Poll::Pending
+            }
+            Poll::Ready(None) => {
+                this.timer = None;
+                Poll::Ready(None)
+            }
         }
     }
 }
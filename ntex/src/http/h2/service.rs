@@ -203,6 +203,7 @@ where
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 enum State<S: Service<Request>, B: MessageBody>
 where
     S: 'static,
@@ -1,18 +1,31 @@
 use std::collections::hash_map::{self, Entry};
-use std::convert::TryFrom;
+use std::convert::{TryFrom, TryInto};
+use std::{fmt, slice};
 
 use http::header::{HeaderName, HeaderValue};
 
 use crate::util::{Either, HashMap};
 
+/// Max number of header names stored inline
+const SMALL_SIZE: usize = 16;
+
 /// A set of HTTP headers
 ///
 /// `HeaderMap` is an multimap of [`HeaderName`] to values.
 ///
+/// Small maps store headers in a vector, lookups are linear scans.
+/// Map switches to hash map if it contains more than 16 header names.
+///
 /// [`HeaderName`]: struct.HeaderName.html
 #[derive(Debug, Clone)]
 pub struct HeaderMap {
-    pub(crate) inner: HashMap<HeaderName, Value>,
+    inner: Inner,
+}
+
+#[derive(Clone)]
+enum Inner {
+    Small(Vec<(HeaderName, Value)>),
+    Map(HashMap<HeaderName, Value>),
 }
 
 #[derive(Debug, Clone)]
@@ -63,7 +76,7 @@ impl HeaderMap {
     /// allocate.
     pub fn new() -> Self {
         HeaderMap {
-            inner: HashMap::default(),
+            inner: Inner::Small(Vec::new()),
         }
     }
 
@@ -76,9 +89,15 @@ impl HeaderMap {
     ///
     /// More capacity than requested may be allocated.
     pub fn with_capacity(capacity: usize) -> HeaderMap {
-        HeaderMap {
-            inner: HashMap::with_capacity_and_hasher(capacity, Default::default()),
-        }
+        let inner = if capacity <= SMALL_SIZE {
+            Inner::Small(Vec::with_capacity(capacity))
+        } else {
+            Inner::Map(HashMap::with_capacity_and_hasher(
+                capacity,
+                Default::default(),
+            ))
+        };
+        HeaderMap { inner }
     }
 
     /// Returns the number of keys stored in the map.
@@ -86,18 +105,24 @@ impl HeaderMap {
     /// This number could be be less than or equal to actual headers stored in
     /// the map.
     pub fn len(&self) -> usize {
-        self.inner.len()
+        match self.inner {
+            Inner::Small(ref items) => items.len(),
+            Inner::Map(ref map) => map.len(),
+        }
     }
 
     /// Returns true if the map contains no elements.
     pub fn is_empty(&self) -> bool {
-        self.inner.len() == 0
+        self.len() == 0
     }
 
     /// Clears the map, removing all key-value pairs. Keeps the allocated memory
     /// for reuse.
     pub fn clear(&mut self) {
-        self.inner.clear();
+        match self.inner {
+            Inner::Small(ref mut items) => items.clear(),
+            Inner::Map(ref mut map) => map.clear(),
+        }
     }
 
     /// Returns the number of headers the map can hold without reallocating.
@@ -105,7 +130,10 @@ impl HeaderMap {
     /// This number is an approximation as certain usage patterns could cause
     /// additional allocations before the returned capacity is filled.
     pub fn capacity(&self) -> usize {
-        self.inner.capacity()
+        match self.inner {
+            Inner::Small(ref items) => items.capacity(),
+            Inner::Map(ref map) => map.capacity(),
+        }
     }
 
     /// Reserves capacity for at least `additional` more headers to be inserted
@@ -117,7 +145,21 @@ impl HeaderMap {
     /// patterns could cause additional allocations before the number is
     /// reached.
     pub fn reserve(&mut self, additional: usize) {
-        self.inner.reserve(additional)
+        match self.inner {
+            Inner::Small(ref mut items) => {
+                if items.len() + additional > SMALL_SIZE {
+                    let mut map = HashMap::with_capacity_and_hasher(
+                        items.len() + additional,
+                        Default::default(),
+                    );
+                    map.extend(items.drain(..));
+                    self.inner = Inner::Map(map);
+                } else {
+                    items.reserve(additional)
+                }
+            }
+            Inner::Map(ref mut map) => map.reserve(additional),
+        }
     }
 
     /// Returns a reference to the value associated with the key.
@@ -130,15 +172,20 @@ impl HeaderMap {
     }
 
     fn get2<N: AsName>(&self, name: N) -> Option<&Value> {
-        match name.as_name() {
-            Either::Left(name) => self.inner.get(name),
-            Either::Right(s) => {
-                if let Ok(name) = HeaderName::try_from(s) {
-                    self.inner.get(&name)
-                } else {
-                    None
-                }
+        match self.inner {
+            Inner::Small(ref items) => {
+                position(items, name.as_name()).map(|idx| &items[idx].1)
             }
+            Inner::Map(ref map) => match name.as_name() {
+                Either::Left(name) => map.get(name),
+                Either::Right(s) => {
+                    if let Ok(name) = HeaderName::try_from(s) {
+                        map.get(&name)
+                    } else {
+                        None
+                    }
+                }
+            },
         }
     }
 
@@ -162,30 +209,33 @@ impl HeaderMap {
     /// is returned. Use `entry` to get all values associated with a given
     /// key. Returns `None` if there are no values associated with the key.
     pub fn get_mut<N: AsName>(&mut self, name: N) -> Option<&mut HeaderValue> {
-        match name.as_name() {
-            Either::Left(name) => self.inner.get_mut(name).map(|v| v.get_mut()),
-            Either::Right(s) => {
-                if let Ok(name) = HeaderName::try_from(s) {
-                    self.inner.get_mut(&name).map(|v| v.get_mut())
-                } else {
-                    None
+        match self.inner {
+            Inner::Small(ref mut items) => match name.as_name() {
+                Either::Left(name) => items
+                    .iter_mut()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.get_mut()),
+                Either::Right(s) => items
+                    .iter_mut()
+                    .find(|(n, _)| eq_lowercase(n.as_str().as_bytes(), s.as_bytes()))
+                    .map(|(_, v)| v.get_mut()),
+            },
+            Inner::Map(ref mut map) => match name.as_name() {
+                Either::Left(name) => map.get_mut(name).map(|v| v.get_mut()),
+                Either::Right(s) => {
+                    if let Ok(name) = HeaderName::try_from(s) {
+                        map.get_mut(&name).map(|v| v.get_mut())
+                    } else {
+                        None
+                    }
                 }
-            }
+            },
         }
     }
 
     /// Returns true if the map contains a value for the specified key.
     pub fn contains_key<N: AsName>(&self, key: N) -> bool {
-        match key.as_name() {
-            Either::Left(name) => self.inner.contains_key(name),
-            Either::Right(s) => {
-                if let Ok(name) = HeaderName::try_from(s) {
-                    self.inner.contains_key(&name)
-                } else {
-                    false
-                }
-            }
-        }
+        self.get2(key).is_some()
     }
 
     /// An iterator visiting all key-value pairs.
@@ -194,7 +244,7 @@ impl HeaderMap {
     /// the same crate version. Each key will be yielded once per associated
     /// value. So, if a key has 3 associated values, it will be yielded 3 times.
     pub fn iter(&self) -> Iter<'_> {
-        Iter::new(self.inner.iter())
+        Iter::new(self.entries())
     }
 
     /// An iterator visiting all keys.
@@ -203,7 +253,15 @@ impl HeaderMap {
     /// the same crate version. Each key will be yielded only once even if it
     /// has multiple associated values.
     pub fn keys(&self) -> Keys<'_> {
-        Keys(self.inner.keys())
+        Keys(self.entries())
+    }
+
+    /// An iterator visiting all keys with associated values.
+    pub(crate) fn entries(&self) -> Entries<'_> {
+        match self.inner {
+            Inner::Small(ref items) => Entries::Small(items.iter()),
+            Inner::Map(ref map) => Entries::Map(map.iter()),
+        }
     }
 
     /// Inserts a key-value pair into the map.
@@ -221,7 +279,19 @@ impl HeaderMap {
     /// The key is not updated, though; this matters for types that can be `==`
     /// without being identical.
     pub fn insert(&mut self, key: HeaderName, val: HeaderValue) {
-        let _ = self.inner.insert(key, Value::One(val));
+        match self.inner {
+            Inner::Small(ref mut items) => {
+                if let Some(idx) = items.iter().position(|(name, _)| *name == key) {
+                    items[idx].1 = Value::One(val);
+                } else {
+                    items.push((key, Value::One(val)));
+                    self.promote();
+                }
+            }
+            Inner::Map(ref mut map) => {
+                let _ = map.insert(key, Value::One(val));
+            }
+        }
     }
 
     /// Inserts a key-value pair into the map.
@@ -234,29 +304,121 @@ impl HeaderMap {
     /// updated, though; this matters for types that can be `==` without being
     /// identical.
     pub fn append(&mut self, key: HeaderName, value: HeaderValue) {
-        match self.inner.entry(key) {
-            Entry::Occupied(mut entry) => entry.get_mut().append(value),
-            Entry::Vacant(entry) => {
-                entry.insert(Value::One(value));
+        match self.inner {
+            Inner::Small(ref mut items) => {
+                if let Some(idx) = items.iter().position(|(name, _)| *name == key) {
+                    items[idx].1.append(value);
+                } else {
+                    items.push((key, Value::One(value)));
+                    self.promote();
+                }
             }
+            Inner::Map(ref mut map) => match map.entry(key) {
+                Entry::Occupied(mut entry) => entry.get_mut().append(value),
+                Entry::Vacant(entry) => {
+                    entry.insert(Value::One(value));
+                }
+            },
         }
     }
 
     /// Removes all headers for a particular header name from the map.
     pub fn remove<N: AsName>(&mut self, key: N) {
-        match key.as_name() {
-            Either::Left(name) => {
-                let _ = self.inner.remove(name);
+        match self.inner {
+            Inner::Small(ref mut items) => {
+                if let Some(idx) = position(items, key.as_name()) {
+                    items.remove(idx);
+                }
             }
-            Either::Right(s) => {
-                if let Ok(name) = HeaderName::try_from(s) {
-                    let _ = self.inner.remove(&name);
+            Inner::Map(ref mut map) => match key.as_name() {
+                Either::Left(name) => {
+                    let _ = map.remove(name);
                 }
+                Either::Right(s) => {
+                    if let Ok(name) = HeaderName::try_from(s) {
+                        let _ = map.remove(&name);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Switch to hash map if vector is too large
+    fn promote(&mut self) {
+        if let Inner::Small(ref mut items) = self.inner {
+            if items.len() > SMALL_SIZE {
+                let mut map =
+                    HashMap::with_capacity_and_hasher(items.len() * 2, Default::default());
+                map.extend(items.drain(..));
+                self.inner = Inner::Map(map);
             }
         }
     }
 }
 
+impl fmt::Debug for Inner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inner::Small(ref items) => f
+                .debug_map()
+                .entries(items.iter().map(|(k, v)| (k, v)))
+                .finish(),
+            Inner::Map(ref map) => f.debug_map().entries(map.iter()).finish(),
+        }
+    }
+}
+
+/// Find position of the header name in the vector
+fn position(
+    items: &[(HeaderName, Value)],
+    name: Either<&HeaderName, &str>,
+) -> Option<usize> {
+    match name {
+        Either::Left(name) => items.iter().position(|(n, _)| n == name),
+        Either::Right(s) => items
+            .iter()
+            .position(|(n, _)| eq_lowercase(n.as_str().as_bytes(), s.as_bytes())),
+    }
+}
+
+/// Compare lowercase header name with string, ignoring ascii case of the string.
+///
+/// Compares 8 bytes at a time, string does not need to be converted
+/// to `HeaderName`.
+fn eq_lowercase(name: &[u8], s: &[u8]) -> bool {
+    if name.len() != s.len() {
+        return false;
+    }
+
+    let mut a = name.chunks_exact(8);
+    let mut b = s.chunks_exact(8);
+    for (x, y) in (&mut a).zip(&mut b) {
+        let x = u64::from_ne_bytes(x.try_into().unwrap());
+        let y = u64::from_ne_bytes(y.try_into().unwrap());
+        if x != to_lowercase(y) {
+            return false;
+        }
+    }
+    a.remainder()
+        .iter()
+        .zip(b.remainder())
+        .all(|(x, y)| *x == y.to_ascii_lowercase())
+}
+
+/// Convert ascii upper case letters of the word to lower case
+fn to_lowercase(w: u64) -> u64 {
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const HIGH: u64 = ONES * 0x80;
+
+    let heptets = w & (ONES * 0x7f);
+    // high bit is set for bytes >= 'A'
+    let ge_a = heptets + ONES * (0x80 - b'A' as u64);
+    // high bit is set for bytes > 'Z'
+    let gt_z = heptets + ONES * (0x80 - b'Z' as u64 - 1);
+    let upper = (ge_a ^ gt_z) & !w & HIGH;
+    w | (upper >> 2)
+}
+
 #[doc(hidden)]
 pub trait AsName {
     fn as_name(&self) -> Either<&HeaderName, &str>;
@@ -325,14 +487,31 @@ impl<'a> Iterator for GetAll<'a> {
     }
 }
 
-pub struct Keys<'a>(hash_map::Keys<'a, HeaderName, Value>);
+pub(crate) enum Entries<'a> {
+    Small(slice::Iter<'a, (HeaderName, Value)>),
+    Map(hash_map::Iter<'a, HeaderName, Value>),
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = (&'a HeaderName, &'a Value);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a HeaderName, &'a Value)> {
+        match self {
+            Entries::Small(ref mut iter) => iter.next().map(|(k, v)| (k, v)),
+            Entries::Map(ref mut iter) => iter.next(),
+        }
+    }
+}
+
+pub struct Keys<'a>(Entries<'a>);
 
 impl<'a> Iterator for Keys<'a> {
     type Item = &'a HeaderName;
 
     #[inline]
     fn next(&mut self) -> Option<&'a HeaderName> {
-        self.0.next()
+        self.0.next().map(|(k, _)| k)
     }
 }
 
@@ -348,11 +527,11 @@ impl<'a> IntoIterator for &'a HeaderMap {
 pub struct Iter<'a> {
     idx: usize,
     current: Option<(&'a HeaderName, &'a Vec<HeaderValue>)>,
-    iter: hash_map::Iter<'a, HeaderName, Value>,
+    iter: Entries<'a>,
 }
 
 impl<'a> Iter<'a> {
-    fn new(iter: hash_map::Iter<'a, HeaderName, Value>) -> Self {
+    fn new(iter: Entries<'a>) -> Self {
         Self {
             iter,
             idx: 0,
@@ -424,4 +603,183 @@ mod tests {
         m.remove("content-type");
         assert!(m.is_empty());
     }
+
+    #[test]
+    fn test_small_and_large() {
+        let mut m = HeaderMap::new();
+        for i in 0..SMALL_SIZE {
+            m.append(
+                HeaderName::try_from(format!("x-header-{}", i)).unwrap(),
+                HeaderValue::from_static("v"),
+            );
+        }
+        assert!(matches!(m.inner, Inner::Small(_)));
+        m.append(CONTENT_TYPE, HeaderValue::from_static("text"));
+        m.append(CONTENT_TYPE, HeaderValue::from_static("html"));
+        assert!(matches!(m.inner, Inner::Map(_)));
+        assert_eq!(m.len(), SMALL_SIZE + 1);
+        assert_eq!(m.iter().count(), SMALL_SIZE + 2);
+        assert_eq!(m.get_all("Content-Type").count(), 2);
+        assert_eq!(m.get("X-Header-3").unwrap(), "v");
+
+        let mut m = HeaderMap::new();
+        m.insert(CONTENT_TYPE, HeaderValue::from_static("text"));
+        m.append(CONTENT_TYPE, HeaderValue::from_static("html"));
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.iter().count(), 2);
+        assert!(m.get("CONTENT-TYPE").is_some());
+        m.insert(CONTENT_TYPE, HeaderValue::from_static("json"));
+        assert_eq!(m.get_all(CONTENT_TYPE).count(), 1);
+        assert!(format!("{:?}", m).contains("content-type"));
+        m.remove("Content-type");
+        assert!(m.is_empty());
+    }
+
+    fn filled(n: usize) -> HeaderMap {
+        let mut m = HeaderMap::new();
+        for i in 0..n {
+            m.insert(
+                HeaderName::try_from(format!("x-header-{}", i)).unwrap(),
+                HeaderValue::from_static("v"),
+            );
+        }
+        m
+    }
+
+    #[test]
+    fn test_boundary_insert_append() {
+        let mut m = filled(SMALL_SIZE - 1);
+        m.insert(CONTENT_TYPE, HeaderValue::from_static("text"));
+        assert!(matches!(m.inner, Inner::Small(_)));
+        assert_eq!(m.len(), SMALL_SIZE);
+
+        // replacing existing name must not promote
+        m.insert(CONTENT_TYPE, HeaderValue::from_static("json"));
+        m.append(CONTENT_TYPE, HeaderValue::from_static("html"));
+        assert!(matches!(m.inner, Inner::Small(_)));
+        assert_eq!(m.len(), SMALL_SIZE);
+        let mut vals: Vec<_> = m.get_all(CONTENT_TYPE).collect();
+        vals.sort();
+        assert_eq!(vals, vec!["html", "json"]);
+
+        // 17th name promotes
+        m.append(
+            HeaderName::from_static("x-extra"),
+            HeaderValue::from_static("e"),
+        );
+        assert!(matches!(m.inner, Inner::Map(_)));
+        assert_eq!(m.len(), SMALL_SIZE + 1);
+        let mut vals: Vec<_> = m.get_all("content-type").collect();
+        vals.sort();
+        assert_eq!(vals, vec!["html", "json"]);
+
+        let mut m = filled(SMALL_SIZE);
+        m.insert(CONTENT_TYPE, HeaderValue::from_static("text"));
+        assert!(matches!(m.inner, Inner::Map(_)));
+        assert_eq!(m.len(), SMALL_SIZE + 1);
+        m.insert(CONTENT_TYPE, HeaderValue::from_static("json"));
+        assert_eq!(m.len(), SMALL_SIZE + 1);
+        assert_eq!(m.get_all(CONTENT_TYPE).count(), 1);
+    }
+
+    #[test]
+    fn test_boundary_remove() {
+        let mut m = filled(SMALL_SIZE);
+        m.remove("X-Header-0");
+        m.remove(HeaderName::from_static("x-header-1"));
+        m.remove("x-unknown");
+        assert!(matches!(m.inner, Inner::Small(_)));
+        assert_eq!(m.len(), SMALL_SIZE - 2);
+        assert!(!m.contains_key("x-header-0"));
+        assert!(!m.contains_key("x-header-1"));
+
+        let mut m = filled(SMALL_SIZE + 1);
+        assert!(matches!(m.inner, Inner::Map(_)));
+        m.remove("X-Header-0");
+        m.remove(HeaderName::from_static("x-header-1"));
+        m.remove("invalid name");
+        assert_eq!(m.len(), SMALL_SIZE - 1);
+        assert!(!m.contains_key("x-header-0"));
+        assert!(!m.contains_key(HeaderName::from_static("x-header-1")));
+        assert!(m.contains_key("x-header-2"));
+    }
+
+    #[test]
+    fn test_boundary_reserve() {
+        let mut m = filled(SMALL_SIZE);
+        m.reserve(0);
+        assert!(matches!(m.inner, Inner::Small(_)));
+        m.reserve(1);
+        assert!(matches!(m.inner, Inner::Map(_)));
+        assert_eq!(m.len(), SMALL_SIZE);
+        assert!(m.capacity() >= SMALL_SIZE + 1);
+        assert_eq!(m.get("X-Header-15").unwrap(), "v");
+
+        let mut m = filled(SMALL_SIZE + 1);
+        m.reserve(10);
+        assert!(m.capacity() >= SMALL_SIZE + 11);
+        assert_eq!(m.len(), SMALL_SIZE + 1);
+    }
+
+    #[test]
+    fn test_boundary_entries() {
+        for n in &[SMALL_SIZE, SMALL_SIZE + 1] {
+            let mut m = filled(*n);
+            m.append(
+                HeaderName::from_static("x-header-0"),
+                HeaderValue::from_static("w"),
+            );
+            assert_eq!(m.entries().count(), *n);
+            assert_eq!(m.keys().count(), *n);
+            assert_eq!(m.iter().count(), *n + 1);
+
+            let mut names: Vec<_> = m.keys().map(|k| k.as_str().to_string()).collect();
+            names.sort();
+            let mut expected: Vec<_> = (0..*n).map(|i| format!("x-header-{}", i)).collect();
+            expected.sort();
+            assert_eq!(names, expected);
+        }
+    }
+
+    #[test]
+    fn test_mixed_lookups_after_promotion() {
+        let mut m = filled(SMALL_SIZE);
+        m.insert(CONTENT_TYPE, HeaderValue::from_static("text"));
+        assert!(matches!(m.inner, Inner::Map(_)));
+
+        assert_eq!(m.get(CONTENT_TYPE).unwrap(), "text");
+        assert_eq!(m.get(&CONTENT_TYPE).unwrap(), "text");
+        assert_eq!(m.get("content-type").unwrap(), "text");
+        assert_eq!(m.get("Content-Type").unwrap(), "text");
+        assert_eq!(m.get("content-type".to_string()).unwrap(), "text");
+        assert!(m.get("content type").is_none());
+
+        *m.get_mut("CONTENT-TYPE").unwrap() = HeaderValue::from_static("json");
+        assert_eq!(m.get(CONTENT_TYPE).unwrap(), "json");
+        *m.get_mut(CONTENT_TYPE).unwrap() = HeaderValue::from_static("html");
+        assert_eq!(m.get("content-type").unwrap(), "html");
+
+        assert!(m.contains_key("X-HEADER-7"));
+        assert!(m.contains_key(HeaderName::from_static("x-header-7")));
+        assert_eq!(m.get_all("x-Header-7").count(), 1);
+    }
+
+    #[test]
+    fn test_eq_lowercase() {
+        assert!(eq_lowercase(b"x-forwarded-for", b"X-Forwarded-For"));
+        assert!(eq_lowercase(b"x-forwarded-for", b"x-forwarded-for"));
+        assert!(!eq_lowercase(b"x-forwarded-for", b"x-forwarded-fox"));
+        assert!(!eq_lowercase(b"x-forwarded-for", b"x-forwarded"));
+        assert!(!eq_lowercase(b"content-length@", b"CONTENT-LENGTH`"));
+        assert!(!eq_lowercase(b"@abcdefgh", b"`ABCDEFGH"));
+        assert!(!eq_lowercase(b"[", b"{"));
+        assert_eq!(
+            to_lowercase(u64::from_ne_bytes(*b"AZaz@[`{")),
+            u64::from_ne_bytes(*b"azaz@[`{")
+        );
+        assert_eq!(
+            to_lowercase(u64::from_ne_bytes([0xc1, 0xda, b'A', 0, 0, 0, 0, 0])),
+            u64::from_ne_bytes([0xc1, 0xda, b'a', 0, 0, 0, 0, 0])
+        );
+    }
 }
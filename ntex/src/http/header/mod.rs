@@ -1,6 +1,6 @@
 //! Various http headers
 
-pub use http::header::{HeaderName, HeaderValue, InvalidHeaderValue};
+pub use http::header::{HeaderName, HeaderValue, InvalidHeaderName, InvalidHeaderValue};
 
 pub(crate) mod map;
 
@@ -19,6 +19,8 @@ pub enum ContentEncoding {
     Deflate,
     /// Gzip algorithm
     Gzip,
+    /// A format using the Zstandard algorithm
+    Zstd,
     /// Indicates the identity function (i.e. no compression, nor modification)
     Identity,
 }
@@ -37,6 +39,7 @@ impl ContentEncoding {
             ContentEncoding::Br => "br",
             ContentEncoding::Gzip => "gzip",
             ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Zstd => "zstd",
             ContentEncoding::Identity | ContentEncoding::Auto => "identity",
         }
     }
@@ -45,6 +48,7 @@ impl ContentEncoding {
     /// default quality value
     pub fn quality(self) -> f64 {
         match self {
+            ContentEncoding::Zstd => 1.2,
             ContentEncoding::Br => 1.1,
             ContentEncoding::Gzip => 1.0,
             ContentEncoding::Deflate => 0.9,
@@ -63,12 +67,62 @@ impl<'a> From<&'a str> for ContentEncoding {
             ContentEncoding::Gzip
         } else if s.eq_ignore_ascii_case("deflate") {
             ContentEncoding::Deflate
+        } else if cfg!(feature = "zstd") && s.eq_ignore_ascii_case("zstd") {
+            ContentEncoding::Zstd
         } else {
             ContentEncoding::Identity
         }
     }
 }
 
+/// Header list item with quality value, i.e. `text/html;q=0.8`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct QualityItem<'a> {
+    /// Item value without parameters
+    pub item: &'a str,
+    /// Quality value, from 0.0 to 1.0
+    pub quality: f32,
+}
+
+impl<'a> QualityItem<'a> {
+    /// Parse comma separated header value with quality parameters.
+    ///
+    /// Items are returned in header order. Missing quality value defaults to 1.0,
+    /// malformed quality value is treated as 0.0. Parameters other than `q`
+    /// are dropped from the item value.
+    pub fn parse_list(value: &'a str) -> Vec<QualityItem<'a>> {
+        value
+            .split(',')
+            .filter_map(|part| {
+                let mut params = part.split(';');
+                let item = params.next().unwrap_or("").trim();
+                if item.is_empty() {
+                    return None;
+                }
+                let quality = params
+                    .filter_map(|param| {
+                        let (name, val) = param.split_once('=')?;
+                        if name.trim().eq_ignore_ascii_case("q") {
+                            Some(val.trim().parse::<f32>().unwrap_or(0.0))
+                        } else {
+                            None
+                        }
+                    })
+                    .next()
+                    .unwrap_or(1.0);
+                Some(QualityItem {
+                    item,
+                    quality: if quality.is_nan() {
+                        0.0
+                    } else {
+                        quality.clamp(0.0, 1.0)
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
 /// Convert http::HeaderMap to a HeaderMap
 impl From<http::HeaderMap> for HeaderMap {
     fn from(map: http::HeaderMap) -> HeaderMap {
@@ -111,4 +165,36 @@ mod tests {
         assert!(!ContentEncoding::Auto.is_compressed());
         assert_eq!(format!("{:?}", ContentEncoding::Identity), "Identity");
     }
+
+    #[test]
+    fn quality_items() {
+        let items =
+            QualityItem::parse_list("text/html, application/json;q=0.5 ,*/*; Q=0, ,x;q=x");
+        assert_eq!(
+            items,
+            vec![
+                QualityItem {
+                    item: "text/html",
+                    quality: 1.0
+                },
+                QualityItem {
+                    item: "application/json",
+                    quality: 0.5
+                },
+                QualityItem {
+                    item: "*/*",
+                    quality: 0.0
+                },
+                QualityItem {
+                    item: "x",
+                    quality: 0.0
+                },
+            ]
+        );
+
+        let items = QualityItem::parse_list("text/plain;charset=utf-8;q=2");
+        assert_eq!(items[0].item, "text/plain");
+        assert_eq!(items[0].quality, 1.0);
+        assert!(QualityItem::parse_list("").is_empty());
+    }
 }
@@ -1,9 +1,23 @@
+//! Http helpers
 use std::io;
 
 use percent_encoding::{AsciiSet, CONTROLS};
 
+use crate::http::{header::HeaderValue, DateService};
 use crate::util::BytesMut;
 
+/// Current `Date` header value.
+///
+/// Value is cached per thread and refreshed once per second, http/1 and
+/// http/2 encoders use the same value. Must be called within runtime context.
+pub fn date_value() -> HeaderValue {
+    let mut value = None;
+    DateService::default().set_date(|date| {
+        value = Some(HeaderValue::from_bytes(date).unwrap());
+    });
+    value.unwrap()
+}
+
 pub(crate) struct Writer<'a>(pub(crate) &'a mut BytesMut);
 
 impl<'a> io::Write for Writer<'a> {
@@ -1,4 +1,4 @@
-use std::{cell::Ref, cell::RefCell, cell::RefMut, net, rc::Rc};
+use std::{cell::Cell, cell::Ref, cell::RefCell, cell::RefMut, io, net, rc::Rc};
 
 use bitflags::bitflags;
 
@@ -25,6 +25,7 @@ bitflags! {
         const UPGRADE     = 0b0000_0100;
         const EXPECT      = 0b0000_1000;
         const NO_CHUNKING = 0b0001_0000;
+        const IDEMPOTENT  = 0b0010_0000;
     }
 }
 
@@ -170,6 +171,35 @@ impl RequestHead {
         }
     }
 
+    #[inline]
+    /// Request could be safely retried.
+    ///
+    /// Requests with idempotent method (`GET`, `HEAD`, `PUT`, `DELETE`,
+    /// `OPTIONS`, `TRACE`) and requests marked with `set_idempotent()`
+    /// are idempotent.
+    pub fn idempotent(&self) -> bool {
+        self.flags.contains(Flags::IDEMPOTENT)
+            || matches!(
+                self.method,
+                Method::GET
+                    | Method::HEAD
+                    | Method::PUT
+                    | Method::DELETE
+                    | Method::OPTIONS
+                    | Method::TRACE
+            )
+    }
+
+    #[inline]
+    /// Mark request as idempotent
+    pub fn set_idempotent(&mut self, val: bool) {
+        if val {
+            self.flags.insert(Flags::IDEMPOTENT);
+        } else {
+            self.flags.remove(Flags::IDEMPOTENT);
+        }
+    }
+
     #[inline]
     pub(crate) fn set_expect(&mut self) {
         self.flags.insert(Flags::EXPECT);
@@ -193,6 +223,47 @@ impl RequestHead {
         })
     }
 
+    /// Send informational (1xx) response, i.e. `103 Early Hints`.
+    ///
+    /// Informational response must be sent before final response.
+    /// Informational responses are not sent to HTTP/1.0 clients.
+    /// HTTP/2 protocol is not supported.
+    pub fn send_informational(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> io::Result<()> {
+        if !status.is_informational() || status == StatusCode::SWITCHING_PROTOCOLS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Status code is not informational",
+            ));
+        }
+        match self.version {
+            Version::HTTP_11 => (),
+            Version::HTTP_2 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Informational responses are not supported for HTTP/2",
+                ))
+            }
+            _ => return Ok(()),
+        }
+
+        if let Some(io) = self.io.as_ref() {
+            io.with_write_buf(|buf| {
+                buf.with_bytes_mut(|dst| {
+                    crate::http::h1::encode_informational(status, headers, dst)
+                })
+            })
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Request is not bound to connection",
+            ))
+        }
+    }
+
     /// Take io and codec for current request
     ///
     /// This objects are set only for upgrade requests
@@ -436,21 +507,54 @@ impl<T: Head> Drop for Message<T> {
     }
 }
 
+/// Default capacity of message pools
+const DEFAULT_POOL_CAPACITY: usize = 128;
+
+/// Set capacity of request and response message pools for current thread.
+///
+/// Released messages are kept in the thread-local pools for reuse.
+/// Setting capacity to 0 disables pooling, released messages are deallocated.
+/// Pools are thread-local, use `HttpServiceBuilder::message_pool_capacity()`
+/// to configure server workers. Default capacity is 128.
+pub fn set_message_pool_capacity(capacity: usize) {
+    REQUEST_POOL.with(|p| p.set_capacity(capacity));
+    RESPONSE_POOL.with(|p| p.set_capacity(capacity));
+}
+
+#[cfg(test)]
+/// Number of released messages kept in the pool of current thread
+pub(super) fn pooled_messages<T: Head>() -> usize {
+    T::with_pool(|p| p.pool.borrow().len())
+}
+
 /// Request's objects pool
-pub(crate) struct MessagePool<T: Head>(RefCell<Vec<Rc<T>>>);
+pub(crate) struct MessagePool<T: Head> {
+    pool: RefCell<Vec<Rc<T>>>,
+    cap: Cell<usize>,
+}
 
 thread_local!(static REQUEST_POOL: MessagePool<RequestHead> = MessagePool::<RequestHead>::new());
 thread_local!(static RESPONSE_POOL: MessagePool<ResponseHead> = MessagePool::<ResponseHead>::new());
 
 impl<T: Head> MessagePool<T> {
     fn new() -> MessagePool<T> {
-        MessagePool(RefCell::new(Vec::with_capacity(128)))
+        MessagePool {
+            pool: RefCell::new(Vec::with_capacity(DEFAULT_POOL_CAPACITY)),
+            cap: Cell::new(DEFAULT_POOL_CAPACITY),
+        }
+    }
+
+    fn set_capacity(&self, cap: usize) {
+        self.cap.set(cap);
+        let mut pool = self.pool.borrow_mut();
+        pool.truncate(cap);
+        pool.shrink_to_fit();
     }
 
     /// Get message from the pool
     #[inline]
     fn get_message(&self) -> Message<T> {
-        if let Some(mut msg) = self.0.borrow_mut().pop() {
+        if let Some(mut msg) = self.pool.borrow_mut().pop() {
             if let Some(r) = Rc::get_mut(&mut msg) {
                 r.clear();
             }
@@ -465,8 +569,8 @@ impl<T: Head> MessagePool<T> {
     #[inline]
     /// Release request instance
     fn release(&self, msg: Rc<T>) {
-        let v = &mut self.0.borrow_mut();
-        if v.len() < 128 {
+        let v = &mut self.pool.borrow_mut();
+        if v.len() < self.cap.get() {
             v.push(msg);
         }
     }
@@ -1,15 +1,18 @@
 //! Http protocol support.
 pub mod body;
+pub(crate) mod budget;
 mod builder;
 pub mod client;
+pub(crate) mod conditional;
 mod config;
 #[cfg(feature = "compress")]
 pub mod encoding;
-pub(crate) mod helpers;
+pub mod helpers;
 mod httpcodes;
 mod httpmessage;
 mod message;
 mod payload;
+mod peer;
 mod request;
 mod response;
 mod service;
@@ -22,14 +25,18 @@ pub mod test;
 
 pub(crate) use self::message::Message;
 
+pub use self::budget::MemoryBudget;
 pub use self::builder::HttpServiceBuilder;
 pub use self::client::Client;
 pub use self::config::{DateService, KeepAlive, ServiceConfig};
 pub use self::error::ResponseError;
 pub use self::header::HeaderMap;
 pub use self::httpmessage::HttpMessage;
-pub use self::message::{ConnectionType, RequestHead, RequestHeadType, ResponseHead};
+pub use self::message::{
+    set_message_pool_capacity, ConnectionType, RequestHead, RequestHeadType, ResponseHead,
+};
 pub use self::payload::{Payload, PayloadStream};
+pub use self::peer::PeerInfo;
 pub use self::request::Request;
 pub use self::response::{Response, ResponseBuilder};
 pub use self::service::HttpService;
@@ -0,0 +1,78 @@
+use std::{net::SocketAddr, rc::Rc};
+
+use crate::io::{types, IoRef};
+use crate::tls::types::{PeerCertificates, TlsInfo};
+
+/// Socket level information of the connection.
+///
+/// Information is captured once, at connection accept time.
+/// Use `HttpServiceBuilder::on_connect()` to make it available
+/// for every request via request extensions.
+///
+/// ```rust
+/// use ntex::http::{h1::H1Service, HttpService, PeerInfo, Request, Response};
+/// use ntex::io::Base;
+///
+/// let srv: H1Service<Base, _, _> = HttpService::build()
+///     .on_connect(PeerInfo::from_io)
+///     .h1(|req: Request| async move {
+///         let rtt = req
+///             .extensions()
+///             .get::<PeerInfo>()
+///             .and_then(|info| info.tcp_info())
+///             .map(|info| info.rtt);
+///         Ok::<_, std::io::Error>(Response::Ok().body(format!("{:?}", rtt)))
+///     });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PeerInfo {
+    addr: Option<SocketAddr>,
+    #[cfg(unix)]
+    cred: Option<types::PeerCred>,
+    tcp: Option<types::TcpInfo>,
+    tls: Option<TlsInfo>,
+    certs: Option<Rc<PeerCertificates>>,
+}
+
+impl PeerInfo {
+    /// Capture socket level information from io stream
+    pub fn from_io(io: &IoRef) -> Self {
+        PeerInfo {
+            addr: io.query::<types::PeerAddr>().get().map(|addr| addr.0),
+            #[cfg(unix)]
+            cred: io.query::<types::PeerCred>().get(),
+            tcp: io.query::<types::TcpInfo>().get(),
+            tls: io.query::<TlsInfo>().as_ref().cloned(),
+            certs: io
+                .query::<PeerCertificates>()
+                .as_ref()
+                .map(|certs| Rc::new(certs.clone())),
+        }
+    }
+
+    /// Peer socket address
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.addr
+    }
+
+    #[cfg(unix)]
+    /// Credentials of the peer process, unix domain sockets only
+    pub fn peer_cred(&self) -> Option<types::PeerCred> {
+        self.cred
+    }
+
+    /// Tcp connection statistics, linux only
+    pub fn tcp_info(&self) -> Option<types::TcpInfo> {
+        self.tcp
+    }
+
+    /// Negotiated tls protocol version and cipher suite
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls.as_ref()
+    }
+
+    /// Client certificates verified during tls handshake
+    pub fn peer_certificates(&self) -> Option<&PeerCertificates> {
+        self.certs.as_deref()
+    }
+}
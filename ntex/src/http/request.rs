@@ -1,9 +1,9 @@
-use std::{cell::Ref, cell::RefMut, fmt, mem, net};
+use std::{cell::Ref, cell::RefMut, fmt, io, mem, net};
 
 use crate::http::header::{self, HeaderMap};
 use crate::http::httpmessage::HttpMessage;
 use crate::http::message::{Message, RequestHead};
-use crate::http::{payload::Payload, Method, Uri, Version};
+use crate::http::{payload::Payload, Method, StatusCode, Uri, Version};
 use crate::io::{types, IoRef};
 use crate::util::Extensions;
 
@@ -131,6 +131,18 @@ impl Request {
         self.head().io.as_ref()
     }
 
+    /// Send informational (1xx) response, i.e. `103 Early Hints`.
+    ///
+    /// Informational response must be sent before final response.
+    /// HTTP/2 protocol is not supported.
+    pub fn send_informational(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> io::Result<()> {
+        self.head().send_informational(status, headers)
+    }
+
     /// Peer socket address
     ///
     /// Peer address is actual socket address, if proxy is used in front of
@@ -809,6 +809,22 @@ impl From<BytesMut> for Response {
     }
 }
 
+/// Convert `http::Response` to response.
+///
+/// Unlike `Response`, `http::Response` is `Send`, so it could be constructed
+/// on other threads, i.e. within `spawn_blocking` closure. Extensions are
+/// not converted.
+impl<B: Into<Body>> From<http::Response<B>> for Response {
+    fn from(res: http::Response<B>) -> Self {
+        let (parts, body) = res.into_parts();
+        let mut res = Response::with_body(parts.status, body.into());
+        let head = res.head_mut();
+        head.version = parts.version;
+        head.headers = parts.headers.into();
+        res
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1051,4 +1067,49 @@ mod tests {
             assert_eq!((cookie.name(), cookie.value()), ("cookie1", "val100"));
         }
     }
+
+    #[crate::rt_test]
+    async fn test_from_http_response() {
+        let res = crate::rt::spawn_blocking(|| {
+            http::Response::builder()
+                .status(StatusCode::CREATED)
+                .header(CONTENT_TYPE, "text/plain")
+                .body("test")
+                .unwrap()
+        })
+        .await
+        .unwrap();
+
+        let resp: Response = res.into();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(
+            resp.headers().get(CONTENT_TYPE).unwrap(),
+            HeaderValue::from_static("text/plain")
+        );
+        assert_eq!(resp.body().get_ref(), b"test");
+    }
+
+    #[test]
+    fn test_message_pool_capacity() {
+        use crate::http::message::pooled_messages;
+
+        // released head is kept in the pool and reused
+        let resp = Response::Ok().finish();
+        let head = resp.head() as *const ResponseHead;
+        let pooled = pooled_messages::<ResponseHead>();
+        drop(resp);
+        assert_eq!(pooled_messages::<ResponseHead>(), pooled + 1);
+        let resp = Response::Ok().finish();
+        assert_eq!(resp.head() as *const ResponseHead, head);
+        assert_eq!(pooled_messages::<ResponseHead>(), pooled);
+        drop(resp);
+
+        // pooling is disabled, released heads are deallocated
+        crate::http::set_message_pool_capacity(0);
+        assert_eq!(pooled_messages::<ResponseHead>(), 0);
+        let resp = Response::Ok().finish();
+        drop(resp);
+        assert_eq!(pooled_messages::<ResponseHead>(), 0);
+        crate::http::set_message_pool_capacity(128);
+    }
 }
@@ -1,13 +1,13 @@
 use std::task::{Context, Poll};
-use std::{cell, error, fmt, future, marker, pin::Pin, rc::Rc};
+use std::{any, cell, error, fmt, future, marker, pin::Pin, rc::Rc};
 
 use h2::server::{self, Handshake};
 use ntex_tls::types::HttpProtocol;
 
-use crate::io::{types, Filter, Io, IoRef, TokioIoBoxed};
+use crate::io::{types, Base, Filter, Io, IoRef, RecvError, TokioIoBoxed};
 use crate::service::{IntoServiceFactory, Service, ServiceFactory};
 use crate::time::{Millis, Seconds};
-use crate::util::Bytes;
+use crate::util::{ready, Bytes};
 
 use super::body::MessageBody;
 use super::builder::HttpServiceBuilder;
@@ -15,9 +15,12 @@ use super::config::{DispatcherConfig, KeepAlive, OnRequest, ServiceConfig};
 use super::error::{DispatchError, ResponseError};
 use super::request::Request;
 use super::response::Response;
-use super::{h1, h2::Dispatcher};
+use super::{h1, h2::h2c, h2::Dispatcher};
 
 /// `ServiceFactory` HTTP1.1/HTTP2 transport implementation
+///
+/// HTTP2 protocol is selected with ALPN for tls connections. For plain
+/// connections HTTP2 is used with prior knowledge or after `Upgrade: h2c` request.
 pub struct HttpService<F, S, B, X = h1::ExpectHandler, U = h1::UpgradeHandler<F>> {
     srv: S,
     cfg: ServiceConfig,
@@ -281,7 +284,9 @@ where
                 None
             };
 
-            let config = DispatcherConfig::new(cfg, service, expect, upgrade, on_request);
+            let mut config =
+                DispatcherConfig::new(cfg, service, expect, upgrade, on_request);
+            config.h2c = any::TypeId::of::<F>() == any::TypeId::of::<Base>();
 
             Ok(HttpServiceHandler {
                 config: Rc::new(config),
@@ -377,15 +382,8 @@ where
         );
 
         if io.query::<HttpProtocol>().get() == Some(HttpProtocol::Http2) {
-            io.set_disconnect_timeout(self.config.client_disconnect.into());
             HttpServiceHandlerResponse {
-                state: ResponseState::H2Handshake {
-                    data: Some((
-                        io.get_ref(),
-                        server::Builder::new().handshake(TokioIoBoxed::from(io)),
-                        self.config.clone(),
-                    )),
-                },
+                state: ResponseState::handshake(io, self.config.clone()),
             }
         } else {
             HttpServiceHandlerResponse {
@@ -445,6 +443,30 @@ pin_project_lite::pin_project! {
                 Rc<DispatcherConfig<S, X, U>>,
             )>,
         },
+        H2cUpgrade { data: Option<(Io<F>, Bytes, Rc<DispatcherConfig<S, X, U>>)> },
+    }
+}
+
+impl<F, S, B, X, U> ResponseState<F, S, B, X, U>
+where
+    F: Filter,
+    S: Service<Request> + 'static,
+    S::Error: ResponseError,
+    B: MessageBody,
+    X: Service<Request, Response = Request>,
+    X::Error: ResponseError + 'static,
+    U: Service<(Request, Io<F>, h1::Codec), Response = ()> + 'static,
+    U::Error: fmt::Display + error::Error,
+{
+    fn handshake(io: Io<F>, config: Rc<DispatcherConfig<S, X, U>>) -> Self {
+        io.set_disconnect_timeout(config.client_disconnect.into());
+        ResponseState::H2Handshake {
+            data: Some((
+                io.get_ref(),
+                server::Builder::new().handshake(TokioIoBoxed::from(io)),
+                config,
+            )),
+        }
     }
 }
 
@@ -466,7 +488,64 @@ where
         let this = self.as_mut().project();
 
         match this.state.project() {
-            StateProject::H1 { fut } => fut.poll(cx),
+            StateProject::H1 { mut fut } => {
+                let result = ready!(fut.as_mut().poll(cx));
+                match fut.take_h2c() {
+                    Some((io, Some(frame), cfg)) => {
+                        io.start_keepalive_timer(cfg.client_timeout);
+                        self.as_mut()
+                            .project()
+                            .state
+                            .set(ResponseState::H2cUpgrade {
+                                data: Some((io, frame, cfg)),
+                            });
+                        self.poll(cx)
+                    }
+                    Some((io, None, cfg)) => {
+                        self.as_mut()
+                            .project()
+                            .state
+                            .set(ResponseState::handshake(io, cfg));
+                        self.poll(cx)
+                    }
+                    None => Poll::Ready(result),
+                }
+            }
+            StateProject::H2cUpgrade { data } => {
+                // wait for client connection preface and insert
+                // upgrade request as a stream 1 right after initial settings frame
+                let pos = match ready!(data
+                    .as_ref()
+                    .unwrap()
+                    .0
+                    .poll_recv(&h2c::UpgradePreface, cx))
+                {
+                    Ok(pos) => pos,
+                    Err(RecvError::Decoder(err)) => return Poll::Ready(Err(err.into())),
+                    Err(RecvError::PeerGone(err)) => {
+                        return Poll::Ready(Err(DispatchError::PeerGone(err)))
+                    }
+                    Err(RecvError::KeepAlive) => {
+                        return Poll::Ready(Err(DispatchError::SlowRequestTimeout))
+                    }
+                    Err(RecvError::Stop) | Err(RecvError::WriteBackpressure) => {
+                        return Poll::Ready(Ok(()))
+                    }
+                };
+                let (io, frame, cfg) = data.take().unwrap();
+                io.remove_keepalive_timer();
+                io.with_read_buf(|buf| {
+                    let tail = Bytes::copy_from_slice(&buf[pos..]);
+                    buf.truncate(pos);
+                    buf.extend_from_slice(&frame);
+                    buf.extend_from_slice(&tail);
+                });
+                self.as_mut()
+                    .project()
+                    .state
+                    .set(ResponseState::handshake(io, cfg));
+                self.poll(cx)
+            }
             StateProject::H2 { ref mut fut } => Pin::new(fut).poll(cx),
             StateProject::H2Handshake { data } => {
                 let conn = if let Some(ref mut item) = data {
@@ -5,7 +5,10 @@
 //! * `openssl` - enables ssl support via `openssl` crate
 //! * `rustls` - enables ssl support via `rustls` crate
 //! * `compress` - enables compression support in http and web modules
+//! * `zstd` - enables zstd compression support, implies `compress`
 //! * `cookie` - enables cookie support in http and web modules
+//! * `session` - enables session middleware, implies `cookie`
+//! * `metrics` - enables prometheus metrics for server and http layers
 #![warn(
     rust_2018_idioms,
     unreachable_pub,
@@ -32,6 +35,8 @@ pub(crate) use ntex_macros::rt_test2 as rt_test;
 
 pub mod connect;
 pub mod http;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod server;
 pub mod web;
 pub mod ws;
@@ -47,6 +52,8 @@ pub use ntex_util::task;
 pub mod codec {
     //! Utilities for encoding and decoding frames.
     pub use ntex_codec::*;
+
+    pub use ntex_tokio::codec::{NtexCodec, TokioCodec};
 }
 
 pub mod router {
@@ -89,14 +96,14 @@ pub mod io {
     //! IO streaming utilities.
     pub use ntex_io::*;
 
-    pub use ntex_tokio::TokioIoBoxed;
+    pub use ntex_tokio::{AsyncIo, TokioIoBoxed};
 }
 
 pub mod testing {
     //! IO testing utilities.
     #[doc(hidden)]
     pub use ntex_io::testing::IoTest as Io;
-    pub use ntex_io::testing::IoTest;
+    pub use ntex_io::testing::{duplex, IoTest};
 }
 
 pub mod tls {
@@ -108,5 +115,6 @@ pub mod util {
     pub use ntex_bytes::{
         Buf, BufMut, ByteString, Bytes, BytesMut, BytesVec, Pool, PoolId, PoolRef,
     };
+    pub use ntex_util::channel::broadcast;
     pub use ntex_util::{future::*, ready, services::*, HashMap, HashSet};
 }
@@ -0,0 +1,450 @@
+//! Prometheus metrics for server and http layers.
+//!
+//! Metrics are collected for all servers and http services of the process.
+//! Collected values could be rendered in prometheus text format
+//! with `Metrics::gather()` or served by web application with `service()`.
+//!
+//! ```rust
+//! use ntex::{metrics, web};
+//!
+//! fn main() {
+//!     let app = web::App::new()
+//!         .service(metrics::service("/metrics"))
+//!         .service(web::resource("/").to(|| async { "ok" }));
+//! }
+//! ```
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::{collections::BTreeMap, fmt::Write, time};
+
+use once_cell::sync::Lazy;
+
+use crate::http::StatusCode;
+use crate::web::{self, ErrorRenderer, HttpResponse, WebServiceFactory};
+
+/// Latency histogram buckets, in seconds
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+static ACCEPT: AcceptMetrics = AcceptMetrics {
+    connections: AtomicU64::new(0),
+    errors: AtomicU64::new(0),
+    resets: AtomicU64::new(0),
+    backpressure: AtomicUsize::new(0),
+    pending: AtomicUsize::new(0),
+};
+
+static WORKERS: Lazy<Mutex<Vec<(usize, Weak<AtomicUsize>)>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+static H1: HttpMetrics = HttpMetrics::new();
+static H2: HttpMetrics = HttpMetrics::new();
+
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+/// Metrics registry
+///
+/// Registry is global, all servers and http services of the process
+/// record metrics to the same registry.
+#[derive(Copy, Clone, Debug)]
+pub struct Metrics;
+
+impl Metrics {
+    /// Render collected metrics in prometheus text format
+    pub fn gather() -> String {
+        let mut buf = String::with_capacity(4096);
+        let _ = ACCEPT.render(&mut buf);
+        let _ = render_workers(&mut buf);
+        let _ = HttpMetrics::render(&mut buf);
+        let _ = counter(
+            &mut buf,
+            "ntex_service_panics_total",
+            "Number of panics caught during request handling",
+            PANICS.load(Ordering::Relaxed),
+        );
+        buf
+    }
+
+    /// Number of accepted connections
+    pub fn accepted_connections() -> u64 {
+        ACCEPT.connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of opened connections, per worker
+    pub fn worker_connections() -> BTreeMap<usize, usize> {
+        let mut conns = BTreeMap::new();
+        if let Ok(workers) = WORKERS.lock() {
+            for (idx, counter) in workers.iter() {
+                if let Some(counter) = counter.upgrade() {
+                    *conns.entry(*idx).or_insert(0) += counter.load(Ordering::Relaxed);
+                }
+            }
+        }
+        conns
+    }
+
+    /// Number of received http requests
+    pub fn http_requests(protocol: Protocol) -> u64 {
+        protocol.metrics().requests.load(Ordering::Relaxed)
+    }
+
+    /// Number of sent http responses for status class, i.e. `2` for `2xx`
+    pub fn http_responses(protocol: Protocol, class: u16) -> u64 {
+        match class {
+            1..=5 => {
+                protocol.metrics().responses[class as usize - 1].load(Ordering::Relaxed)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Number of panics caught during request handling
+    pub fn service_panics() -> u64 {
+        PANICS.load(Ordering::Relaxed)
+    }
+}
+
+/// Web service that renders metrics in prometheus text format
+///
+/// Service handles `GET` requests for the path.
+pub fn service<Err: ErrorRenderer>(path: &str) -> impl WebServiceFactory<Err> {
+    web::resource(path).route(web::get().to(|| async {
+        HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4; charset=utf-8")
+            .body(Metrics::gather())
+    }))
+}
+
+/// Http protocol
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Http1,
+    Http2,
+}
+
+impl Protocol {
+    fn metrics(self) -> &'static HttpMetrics {
+        match self {
+            Protocol::Http1 => &H1,
+            Protocol::Http2 => &H2,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Http1 => "h1",
+            Protocol::Http2 => "h2",
+        }
+    }
+}
+
+struct AcceptMetrics {
+    connections: AtomicU64,
+    errors: AtomicU64,
+    resets: AtomicU64,
+    backpressure: AtomicUsize,
+    pending: AtomicUsize,
+}
+
+impl AcceptMetrics {
+    fn render(&self, buf: &mut String) -> std::fmt::Result {
+        counter(
+            buf,
+            "ntex_accept_connections_total",
+            "Number of accepted connections",
+            self.connections.load(Ordering::Relaxed),
+        )?;
+        counter(
+            buf,
+            "ntex_accept_errors_total",
+            "Number of socket accept errors",
+            self.errors.load(Ordering::Relaxed),
+        )?;
+        counter(
+            buf,
+            "ntex_accept_reset_connections_total",
+            "Number of connections reset because of full pending queue",
+            self.resets.load(Ordering::Relaxed),
+        )?;
+        gauge(
+            buf,
+            "ntex_accept_backpressure",
+            "Number of accept loops with enabled back-pressure",
+            self.backpressure.load(Ordering::Relaxed),
+        )?;
+        gauge(
+            buf,
+            "ntex_accept_pending_connections",
+            "Number of accepted connections waiting for available worker",
+            self.pending.load(Ordering::Relaxed),
+        )
+    }
+}
+
+struct HttpMetrics {
+    requests: AtomicU64,
+    responses: [AtomicU64; 5],
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl HttpMetrics {
+    const fn new() -> Self {
+        HttpMetrics {
+            requests: ZERO,
+            responses: [ZERO; 5],
+            buckets: [ZERO; BUCKETS.len()],
+            count: ZERO,
+            sum_us: ZERO,
+        }
+    }
+
+    fn observe(&self, latency: time::Duration) {
+        let secs = latency.as_secs_f64();
+        for (idx, le) in BUCKETS.iter().enumerate() {
+            if secs <= *le {
+                self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(buf: &mut String) -> std::fmt::Result {
+        let protocols = [Protocol::Http1, Protocol::Http2];
+
+        header(
+            buf,
+            "ntex_http_requests_total",
+            "Number of received http requests",
+            "counter",
+        )?;
+        for proto in protocols.iter() {
+            writeln!(
+                buf,
+                "ntex_http_requests_total{{protocol=\"{}\"}} {}",
+                proto.as_str(),
+                proto.metrics().requests.load(Ordering::Relaxed)
+            )?;
+        }
+
+        header(
+            buf,
+            "ntex_http_responses_total",
+            "Number of sent http responses by status class",
+            "counter",
+        )?;
+        for proto in protocols.iter() {
+            for (idx, val) in proto.metrics().responses.iter().enumerate() {
+                writeln!(
+                    buf,
+                    "ntex_http_responses_total{{protocol=\"{}\",status=\"{}xx\"}} {}",
+                    proto.as_str(),
+                    idx + 1,
+                    val.load(Ordering::Relaxed)
+                )?;
+            }
+        }
+
+        header(
+            buf,
+            "ntex_http_request_duration_seconds",
+            "Time from receiving request head to sending response head",
+            "histogram",
+        )?;
+        for proto in protocols.iter() {
+            let m = proto.metrics();
+            let count = m.count.load(Ordering::Relaxed);
+            for (le, val) in BUCKETS.iter().zip(m.buckets.iter()) {
+                writeln!(
+                    buf,
+                    "ntex_http_request_duration_seconds_bucket{{protocol=\"{}\",le=\"{}\"}} {}",
+                    proto.as_str(),
+                    le,
+                    val.load(Ordering::Relaxed)
+                )?;
+            }
+            writeln!(
+                buf,
+                "ntex_http_request_duration_seconds_bucket{{protocol=\"{}\",le=\"+Inf\"}} {}",
+                proto.as_str(),
+                count
+            )?;
+            writeln!(
+                buf,
+                "ntex_http_request_duration_seconds_sum{{protocol=\"{}\"}} {}",
+                proto.as_str(),
+                m.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0
+            )?;
+            writeln!(
+                buf,
+                "ntex_http_request_duration_seconds_count{{protocol=\"{}\"}} {}",
+                proto.as_str(),
+                count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn render_workers(buf: &mut String) -> std::fmt::Result {
+    header(
+        buf,
+        "ntex_worker_connections",
+        "Number of opened connections per worker",
+        "gauge",
+    )?;
+    for (idx, conns) in Metrics::worker_connections() {
+        writeln!(
+            buf,
+            "ntex_worker_connections{{worker=\"{}\"}} {}",
+            idx, conns
+        )?;
+    }
+    Ok(())
+}
+
+fn header(buf: &mut String, name: &str, help: &str, tp: &str) -> std::fmt::Result {
+    writeln!(buf, "# HELP {} {}", name, help)?;
+    writeln!(buf, "# TYPE {} {}", name, tp)
+}
+
+fn counter(buf: &mut String, name: &str, help: &str, val: u64) -> std::fmt::Result {
+    header(buf, name, help, "counter")?;
+    writeln!(buf, "{} {}", name, val)
+}
+
+fn gauge(buf: &mut String, name: &str, help: &str, val: usize) -> std::fmt::Result {
+    header(buf, name, help, "gauge")?;
+    writeln!(buf, "{} {}", name, val)
+}
+
+/// Connection is accepted by accept loop
+pub(crate) fn accept_connection() {
+    ACCEPT.connections.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Socket accept error
+pub(crate) fn accept_error() {
+    ACCEPT.errors.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Connection is reset, pending queue is full
+pub(crate) fn accept_reset() {
+    ACCEPT.resets.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Accept loop back-pressure state is changed
+pub(crate) fn accept_backpressure(on: bool) {
+    if on {
+        ACCEPT.backpressure.fetch_add(1, Ordering::Relaxed);
+    } else {
+        ACCEPT.backpressure.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Connection is added to or removed from pending queue
+pub(crate) fn accept_pending(added: bool) {
+    if added {
+        ACCEPT.pending.fetch_add(1, Ordering::Relaxed);
+    } else {
+        ACCEPT.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Register connections counter of started worker
+pub(crate) fn register_worker(idx: usize, counter: &Arc<AtomicUsize>) {
+    if let Ok(mut workers) = WORKERS.lock() {
+        // drop counters of stopped workers
+        workers.retain(|(_, counter)| counter.strong_count() > 0);
+        workers.push((idx, Arc::downgrade(counter)));
+    }
+}
+
+/// Http request head is received, returns request start time
+pub(crate) fn http_request(protocol: Protocol) -> time::Instant {
+    protocol.metrics().requests.fetch_add(1, Ordering::Relaxed);
+    time::Instant::now()
+}
+
+/// Http response head is sent
+pub(crate) fn http_response(
+    protocol: Protocol,
+    status: StatusCode,
+    started: Option<time::Instant>,
+) {
+    let m = protocol.metrics();
+    let class = (status.as_u16() / 100).clamp(1, 5);
+    m.responses[class as usize - 1].fetch_add(1, Ordering::Relaxed);
+    if let Some(started) = started {
+        m.observe(started.elapsed());
+    }
+}
+
+/// Panic is caught during request handling
+pub(crate) fn service_panic() {
+    PANICS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+
+    #[test]
+    fn test_http_metrics() {
+        let requests = Metrics::http_requests(Protocol::Http2);
+        let responses = Metrics::http_responses(Protocol::Http2, 4);
+
+        let started = http_request(Protocol::Http2);
+        http_response(Protocol::Http2, StatusCode::NOT_FOUND, Some(started));
+        assert!(Metrics::http_requests(Protocol::Http2) > requests);
+        assert!(Metrics::http_responses(Protocol::Http2, 4) > responses);
+        assert_eq!(Metrics::http_responses(Protocol::Http2, 7), 0);
+
+        let m = HttpMetrics::new();
+        m.observe(time::Duration::from_millis(30));
+        assert_eq!(m.buckets[1].load(Ordering::Relaxed), 0);
+        assert_eq!(m.buckets[3].load(Ordering::Relaxed), 1);
+        assert_eq!(m.buckets[10].load(Ordering::Relaxed), 1);
+        assert_eq!(m.sum_us.load(Ordering::Relaxed), 30_000);
+    }
+
+    #[test]
+    fn test_workers() {
+        let counter = Arc::new(AtomicUsize::new(3));
+        register_worker(1001, &counter);
+        assert_eq!(Metrics::worker_connections().get(&1001), Some(&3));
+        assert!(Metrics::gather().contains("ntex_worker_connections{worker=\"1001\"} 3"));
+
+        drop(counter);
+        assert!(!Metrics::worker_connections().contains_key(&1001));
+    }
+
+    #[crate::rt_test]
+    async fn test_service() {
+        let srv = init_service(web::App::new().service(service("/metrics"))).await;
+
+        let req = TestRequest::with_uri("/metrics").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; version=0.0.4; charset=utf-8"
+        );
+        let body = read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("# TYPE ntex_accept_connections_total counter"));
+        assert!(body.contains("ntex_http_requests_total{protocol=\"h1\"}"));
+        assert!(body.contains(
+            "ntex_http_request_duration_seconds_bucket{protocol=\"h2\",le=\"+Inf\"}"
+        ));
+    }
+}
@@ -1,25 +1,43 @@
-use std::{cell::Cell, io, sync::mpsc, sync::Arc, thread, time::Duration, time::Instant};
+use std::time::{Duration, Instant};
+use std::{cell::Cell, collections::VecDeque, io, sync::mpsc, sync::Arc, sync::Mutex};
 
+use nanorand::{Rng, WyRand};
 use polling::{Event, Poller};
+use socket2::SockRef;
 
 use crate::rt::System;
 use crate::time::{sleep, Millis};
 
-use super::socket::{Listener, SocketAddr};
+use super::filter::{AcceptFilter, FilterGuard};
+use super::socket::{Listener, SocketAddr, Stream};
 use super::worker::{Connection, WorkerClient};
-use super::{Server, ServerStatus, Token};
+use super::{BackpressurePolicy, OverflowAction, Server, ServerEvent, ServerStatus, Token};
 
 const ERR_TIMEOUT: Duration = Duration::from_millis(500);
 const ERR_SLEEP_TIMEOUT: Millis = Millis(525);
 
+type StatusHandler = Arc<Mutex<dyn FnMut(ServerStatus) + Send>>;
+
+#[derive(Clone)]
+struct AcceptConfig {
+    policy: BackpressurePolicy,
+    max_pending: usize,
+    overflow: OverflowAction,
+    status_handler: Option<StatusHandler>,
+    filter: Option<Arc<dyn AcceptFilter>>,
+}
+
 #[derive(Debug)]
 pub(super) enum Command {
     Stop,
     Pause,
     Resume,
     Worker(WorkerClient),
+    Add(Vec<(Token, Listener)>),
+    Remove(Vec<Token>),
     Timer,
     WorkerAvailable,
+    WorkerSaturated(usize),
 }
 
 struct ServerSocketInfo {
@@ -47,7 +65,7 @@ impl AcceptNotify {
 pub(super) struct AcceptLoop {
     notify: AcceptNotify,
     inner: Option<(mpsc::Receiver<Command>, Arc<Poller>, Server)>,
-    status_handler: Option<Box<dyn FnMut(ServerStatus) + Send>>,
+    config: AcceptConfig,
 }
 
 impl AcceptLoop {
@@ -65,7 +83,13 @@ impl AcceptLoop {
         AcceptLoop {
             notify,
             inner: Some((rx, poll, srv)),
-            status_handler: None,
+            config: AcceptConfig {
+                policy: BackpressurePolicy::RoundRobin,
+                max_pending: 0,
+                overflow: OverflowAction::Wait,
+                status_handler: None,
+                filter: None,
+            },
         }
     }
 
@@ -81,7 +105,26 @@ impl AcceptLoop {
     where
         F: FnMut(ServerStatus) + Send + 'static,
     {
-        self.status_handler = Some(Box::new(f));
+        let hnd: StatusHandler = Arc::new(Mutex::new(f));
+        self.config.status_handler = Some(hnd);
+    }
+
+    pub(super) fn set_filter<F: AcceptFilter>(&mut self, filter: F) {
+        self.config.filter = Some(Arc::new(filter));
+    }
+
+    pub(super) fn set_policy(&mut self, policy: BackpressurePolicy) {
+        self.config.policy = policy;
+    }
+
+    pub(super) fn set_pending(&mut self, limit: usize, action: OverflowAction) {
+        self.config.max_pending = limit;
+        self.config.overflow = action;
+    }
+
+    /// Use settings and status handler of other accept loop
+    pub(super) fn share_config(&mut self, other: &AcceptLoop) {
+        self.config = other.config.clone();
     }
 
     pub(super) fn start(
@@ -93,8 +136,6 @@ impl AcceptLoop {
             .inner
             .take()
             .expect("AcceptLoop cannot be used multiple times");
-        let status_handler = self.status_handler.take();
-
         Accept::start(
             rx,
             poll,
@@ -102,7 +143,7 @@ impl AcceptLoop {
             srv,
             workers,
             self.notify.clone(),
-            status_handler,
+            self.config.clone(),
         );
     }
 }
@@ -116,7 +157,9 @@ struct Accept {
     notify: AcceptNotify,
     next: usize,
     backpressure: bool,
-    status_handler: Option<Box<dyn FnMut(ServerStatus) + Send>>,
+    config: AcceptConfig,
+    pending: VecDeque<Connection>,
+    rng: WyRand,
 }
 
 impl Accept {
@@ -127,16 +170,16 @@ impl Accept {
         srv: Server,
         workers: Vec<WorkerClient>,
         notify: AcceptNotify,
-        status_handler: Option<Box<dyn FnMut(ServerStatus) + Send>>,
+        config: AcceptConfig,
     ) {
         let sys = System::current();
 
         // start accept thread
-        let _ = thread::Builder::new()
+        let _ = std::thread::Builder::new()
             .name("ntex-server accept loop".to_owned())
             .spawn(move || {
                 System::set_current(sys);
-                Accept::new(rx, poller, socks, workers, srv, notify, status_handler).poll()
+                Accept::new(rx, poller, socks, workers, srv, notify, config).poll()
             });
     }
 
@@ -147,7 +190,7 @@ impl Accept {
         workers: Vec<WorkerClient>,
         srv: Server,
         notify: AcceptNotify,
-        status_handler: Option<Box<dyn FnMut(ServerStatus) + Send>>,
+        config: AcceptConfig,
     ) -> Accept {
         let mut sockets = Vec::new();
         for (hnd_token, lst) in socks.into_iter() {
@@ -167,15 +210,19 @@ impl Accept {
             workers,
             notify,
             srv,
-            status_handler,
+            config,
             next: 0,
             backpressure: false,
+            pending: VecDeque::new(),
+            rng: WyRand::new(),
         }
     }
 
     fn update_status(&mut self, st: ServerStatus) {
-        if let Some(ref mut hnd) = self.status_handler {
-            (*hnd)(st)
+        if let Some(ref hnd) = self.config.status_handler {
+            if let Ok(mut hnd) = hnd.lock() {
+                (*hnd)(st)
+            }
         }
     }
 
@@ -201,9 +248,12 @@ impl Accept {
             }
 
             for event in events.iter() {
-                let readd = self.accept(event.key);
-                if readd {
-                    self.add_source(event.key);
+                // poller key is a socket token
+                let idx = self.sockets.iter().position(|s| s.token.0 == event.key);
+                if let Some(idx) = idx {
+                    if self.accept(idx) {
+                        self.add_source(idx);
+                    }
                 }
             }
 
@@ -226,9 +276,10 @@ impl Accept {
         loop {
             // try to register poller source
             let result = if info.registered.get() {
-                self.poller.modify(&info.sock, Event::readable(idx))
+                self.poller
+                    .modify(&info.sock, Event::readable(info.token.0))
             } else {
-                self.poller.add(&info.sock, Event::readable(idx))
+                self.poller.add(&info.sock, Event::readable(info.token.0))
             };
             if let Err(err) = result {
                 if err.kind() == io::ErrorKind::WouldBlock {
@@ -256,7 +307,7 @@ impl Accept {
         let info = &self.sockets[key];
 
         let result = if info.registered.get() {
-            self.poller.modify(&info.sock, Event::none(key))
+            self.poller.modify(&info.sock, Event::none(info.token.0))
         } else {
             return;
         };
@@ -301,6 +352,7 @@ impl Accept {
                             self.remove_source(key);
                         }
                         self.update_status(ServerStatus::NotReady);
+                        self.srv.emit(|| ServerEvent::AcceptPaused);
                     }
                     Command::Resume => {
                         log::trace!("Resuming accept loop");
@@ -309,11 +361,49 @@ impl Accept {
                             self.add_source(key);
                         }
                         self.update_status(ServerStatus::Ready);
+                        self.srv.emit(|| ServerEvent::AcceptResumed);
                     }
                     Command::Worker(worker) => {
                         log::trace!("Adding new worker to accept loop");
                         self.backpressure(false);
                         self.workers.push(worker);
+                        self.process_pending();
+                    }
+                    Command::Add(socks) => {
+                        for (token, lst) in socks {
+                            log::info!("Starting socket listener on {}", lst);
+                            self.sockets.push(ServerSocketInfo {
+                                token,
+                                addr: lst.local_addr(),
+                                sock: lst,
+                                registered: Cell::new(false),
+                                timeout: Cell::new(None),
+                            });
+                            if !self.backpressure {
+                                self.add_source(self.sockets.len() - 1);
+                            }
+                        }
+                    }
+                    Command::Remove(tokens) => {
+                        let (removed, sockets): (Vec<_>, Vec<_>) =
+                            std::mem::take(&mut self.sockets)
+                                .into_iter()
+                                .partition(|info| tokens.contains(&info.token));
+                        self.sockets = sockets;
+
+                        for info in removed {
+                            log::info!("Stopping socket listener on {}", info.addr);
+                            if info.registered.get() {
+                                if let Err(err) = self.poller.delete(&info.sock) {
+                                    log::error!(
+                                        "Cannot remove socket listener for {} err: {}",
+                                        info.addr,
+                                        err
+                                    );
+                                }
+                            }
+                            info.sock.remove_source();
+                        }
                     }
                     Command::Timer => {
                         self.process_timer();
@@ -321,6 +411,11 @@ impl Accept {
                     Command::WorkerAvailable => {
                         log::trace!("Worker is available");
                         self.backpressure(false);
+                        self.process_pending();
+                    }
+                    Command::WorkerSaturated(idx) => {
+                        log::trace!("Worker {} is saturated", idx);
+                        self.srv.emit(|| ServerEvent::WorkerSaturated { idx });
                     }
                 },
                 Err(err) => match err {
@@ -348,6 +443,9 @@ impl Accept {
         if self.backpressure {
             if !on {
                 self.backpressure = false;
+                #[cfg(feature = "metrics")]
+                crate::metrics::accept_backpressure(false);
+                self.srv.emit(|| ServerEvent::AcceptResumed);
                 for (key, info) in self.sockets.iter().enumerate() {
                     if info.timeout.get().is_none() {
                         // socket with timeout will re-register itself after timeout
@@ -361,6 +459,9 @@ impl Accept {
             }
         } else if on {
             self.backpressure = true;
+            #[cfg(feature = "metrics")]
+            crate::metrics::accept_backpressure(true);
+            self.srv.emit(|| ServerEvent::AcceptPaused);
             for key in 0..self.sockets.len() {
                 // disable err timeout
                 let info = &mut self.sockets[key];
@@ -380,6 +481,11 @@ impl Accept {
         );
 
         if self.backpressure {
+            if self.config.max_pending > 0 {
+                self.enqueue(msg);
+                return;
+            }
+
             while !self.workers.is_empty() {
                 match self.workers[self.next].send(msg) {
                     Ok(_) => (),
@@ -402,34 +508,26 @@ impl Accept {
                 break;
             }
         } else {
-            let mut idx = 0;
-            while idx < self.workers.len() {
-                idx += 1;
-                if self.workers[self.next].available() {
-                    match self.workers[self.next].send(msg) {
-                        Ok(_) => {
-                            log::trace!("Sent to worker {:?}", self.next);
-                            self.next = (self.next + 1) % self.workers.len();
-                            return;
-                        }
-                        Err(tmp) => {
-                            log::trace!("Worker failed while processing connection");
-                            self.update_status(ServerStatus::WorkerFailed);
-                            self.srv.worker_faulted(self.workers[self.next].idx);
-                            msg = tmp;
-                            self.workers.swap_remove(self.next);
-                            if self.workers.is_empty() {
-                                log::error!("No workers");
-                                self.backpressure(true);
-                                return;
-                            } else if self.workers.len() <= self.next {
-                                self.next = 0;
-                            }
-                            continue;
+            while let Some(idx) = self.select_worker() {
+                match self.workers[idx].send(msg) {
+                    Ok(_) => {
+                        log::trace!("Sent to worker {:?}", idx);
+                        return;
+                    }
+                    Err(tmp) => {
+                        log::trace!("Worker failed while processing connection");
+                        self.update_status(ServerStatus::WorkerFailed);
+                        self.srv.worker_faulted(self.workers[idx].idx);
+                        msg = tmp;
+                        self.workers.swap_remove(idx);
+                        if self.workers.is_empty() {
+                            log::error!("No workers");
+                            break;
+                        } else if self.workers.len() <= self.next {
+                            self.next = 0;
                         }
                     }
                 }
-                self.next = (self.next + 1) % self.workers.len();
             }
             // enable backpressure
             log::trace!("No available workers, enable back-pressure");
@@ -438,19 +536,132 @@ impl Accept {
         }
     }
 
-    fn accept(&mut self, token: usize) -> bool {
+    /// Select available worker according to backpressure policy
+    fn select_worker(&mut self) -> Option<usize> {
+        let workers = &self.workers;
+
+        match self.config.policy {
+            BackpressurePolicy::RoundRobin => {
+                for _ in 0..workers.len() {
+                    let idx = self.next;
+                    self.next = (self.next + 1) % workers.len();
+                    if workers[idx].available() {
+                        return Some(idx);
+                    }
+                }
+                None
+            }
+            BackpressurePolicy::LeastConnections => workers
+                .iter()
+                .enumerate()
+                .filter(|(_, worker)| worker.available())
+                .min_by_key(|(_, worker)| worker.connections())
+                .map(|(idx, _)| idx),
+            BackpressurePolicy::RandomTwoChoices => {
+                let available: Vec<_> = (0..workers.len())
+                    .filter(|idx| workers[*idx].available())
+                    .collect();
+
+                match available.len() {
+                    0 => None,
+                    1 => Some(available[0]),
+                    len => {
+                        let first = self.rng.generate_range(0..len);
+                        let mut second = self.rng.generate_range(0..len - 1);
+                        if second >= first {
+                            second += 1;
+                        }
+                        let (first, second) = (available[first], available[second]);
+                        if workers[second].connections() < workers[first].connections() {
+                            Some(second)
+                        } else {
+                            Some(first)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check connection with accept filter, rejected connection is reset
+    fn filter(&self, mut msg: Connection) -> Option<Connection> {
+        if let Some(ref filter) = self.config.filter {
+            if let Some(peer) = msg.io.peer_addr() {
+                if filter.accept(&peer) {
+                    msg.filter = Some(FilterGuard::new(filter.clone(), peer));
+                } else {
+                    log::trace!("Connection from {} is rejected by accept filter", peer);
+                    self.srv.emit(|| ServerEvent::ConnectionRejected {
+                        peer,
+                        token: msg.token.0,
+                    });
+                    reset(msg.io);
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::accept_reset();
+                    return None;
+                }
+            }
+        }
+        Some(msg)
+    }
+
+    /// Keep connection until one of the workers become available
+    fn enqueue(&mut self, msg: Connection) {
+        if self.pending.len() < self.config.max_pending {
+            self.pending.push_back(msg);
+            #[cfg(feature = "metrics")]
+            crate::metrics::accept_pending(true);
+        } else {
+            log::trace!("Pending connections queue is full, reset connection");
+            reset(msg.io);
+            #[cfg(feature = "metrics")]
+            crate::metrics::accept_reset();
+        }
+    }
+
+    /// Send pending connections to available workers
+    fn process_pending(&mut self) {
+        while !self.backpressure {
+            if let Some(msg) = self.pending.pop_front() {
+                #[cfg(feature = "metrics")]
+                crate::metrics::accept_pending(false);
+                self.accept_one(msg);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Pending connections queue is full and accept loop must wait
+    fn must_wait(&self) -> bool {
+        self.backpressure
+            && self.config.max_pending > 0
+            && self.config.overflow == OverflowAction::Wait
+            && self.pending.len() >= self.config.max_pending
+    }
+
+    fn accept(&mut self, idx: usize) -> bool {
         loop {
-            let msg = if let Some(info) = self.sockets.get_mut(token) {
+            // socket listener get resumed after back-pressure
+            if self.must_wait() {
+                log::trace!("Pending connections queue is full, stop accepting");
+                return false;
+            }
+
+            let msg = if let Some(info) = self.sockets.get_mut(idx) {
                 match info.sock.accept() {
                     Ok(Some(io)) => Connection {
                         io,
                         token: info.token,
+                        filter: None,
                     },
                     Ok(None) => return true,
                     Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
                     Err(ref e) if connection_error(e) => continue,
                     Err(e) => {
                         log::error!("Error accepting socket: {}", e);
+                        #[cfg(feature = "metrics")]
+                        crate::metrics::accept_error();
 
                         // sleep after error
                         info.timeout.set(Some(Instant::now() + ERR_TIMEOUT));
@@ -467,11 +678,46 @@ impl Accept {
                 return false;
             };
 
+            let msg = if let Some(msg) = self.filter(msg) {
+                msg
+            } else {
+                continue;
+            };
+
+            #[cfg(feature = "metrics")]
+            crate::metrics::accept_connection();
+            self.srv.emit(|| ServerEvent::ConnectionAccepted {
+                peer: msg.io.peer_addr(),
+                token: msg.token.0,
+            });
             self.accept_one(msg);
         }
     }
 }
 
+#[cfg(feature = "metrics")]
+impl Drop for Accept {
+    fn drop(&mut self) {
+        if self.backpressure {
+            crate::metrics::accept_backpressure(false);
+        }
+        for _ in 0..self.pending.len() {
+            crate::metrics::accept_pending(false);
+        }
+    }
+}
+
+/// Close connection with RST
+fn reset(io: Stream) {
+    match io {
+        Stream::Tcp(stream) => {
+            let _ = SockRef::from(&stream).set_linger(Some(Duration::from_secs(0)));
+        }
+        #[cfg(unix)]
+        Stream::Uds(_) => (),
+    }
+}
+
 /// This function defines errors that are per-connection. Which basically
 /// means that if we get this error from `accept()` system call it means
 /// next connection might be ready to be accepted.
@@ -484,3 +730,184 @@ fn connection_error(e: &io::Error) -> bool {
         || e.kind() == io::ErrorKind::ConnectionAborted
         || e.kind() == io::ErrorKind::ConnectionReset
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use async_channel::{unbounded, Receiver};
+
+    use super::*;
+    use crate::server::worker::{WorkerAvailability, WorkerCommand};
+
+    fn accept(
+        num: usize,
+        policy: BackpressurePolicy,
+        max_pending: usize,
+        overflow: OverflowAction,
+    ) -> (Accept, Vec<(Receiver<WorkerCommand>, WorkerAvailability)>) {
+        let poller = Arc::new(Poller::new().unwrap());
+        let (tx, rx) = mpsc::channel();
+        let notify = AcceptNotify::new(poller.clone(), tx);
+        let (srv_tx, _) = unbounded();
+
+        let mut workers = Vec::new();
+        let mut clients = Vec::new();
+        for idx in 0..num {
+            let (tx1, rx1) = unbounded();
+            let avail = WorkerAvailability::new(idx, notify.clone());
+            avail.set(true);
+            clients.push(WorkerClient::new(
+                idx,
+                tx1,
+                unbounded().0,
+                unbounded().0,
+                avail.clone(),
+            ));
+            workers.push((rx1, avail));
+        }
+
+        let config = AcceptConfig {
+            policy,
+            max_pending,
+            overflow,
+            status_handler: None,
+            filter: None,
+        };
+        let srv = Server::new(srv_tx);
+        let accept = Accept::new(rx, poller, Vec::new(), clients, srv, notify, config);
+        (accept, workers)
+    }
+
+    fn connection() -> Connection {
+        let lst = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let _ = std::net::TcpStream::connect(lst.local_addr().unwrap()).unwrap();
+        Connection {
+            io: Stream::Tcp(lst.accept().unwrap().0),
+            token: Token(0),
+            filter: None,
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let (mut accept, workers) =
+            accept(3, BackpressurePolicy::RoundRobin, 0, OverflowAction::Wait);
+        workers[1].1.set(false);
+
+        for _ in 0..4 {
+            accept.accept_one(connection());
+        }
+        assert_eq!(workers[0].0.len(), 2);
+        assert_eq!(workers[1].0.len(), 0);
+        assert_eq!(workers[2].0.len(), 2);
+    }
+
+    #[test]
+    fn test_least_connections() {
+        let (mut accept, workers) = accept(
+            3,
+            BackpressurePolicy::LeastConnections,
+            0,
+            OverflowAction::Wait,
+        );
+        workers[0].1.counter().store(5, Ordering::Relaxed);
+        workers[1].1.counter().store(1, Ordering::Relaxed);
+        workers[2].1.counter().store(3, Ordering::Relaxed);
+
+        accept.accept_one(connection());
+        assert_eq!(workers[1].0.len(), 1);
+
+        // unavailable worker is not used
+        workers[1].1.set(false);
+        accept.accept_one(connection());
+        assert_eq!(workers[1].0.len(), 1);
+        assert_eq!(workers[2].0.len(), 1);
+    }
+
+    #[test]
+    fn test_random_two_choices() {
+        let (mut accept, workers) = accept(
+            2,
+            BackpressurePolicy::RandomTwoChoices,
+            0,
+            OverflowAction::Wait,
+        );
+        workers[0].1.counter().store(5, Ordering::Relaxed);
+        workers[1].1.counter().store(1, Ordering::Relaxed);
+
+        for _ in 0..3 {
+            accept.accept_one(connection());
+        }
+        assert_eq!(workers[0].0.len(), 0);
+        assert_eq!(workers[1].0.len(), 3);
+    }
+
+    #[test]
+    fn test_pending_connections() {
+        let (mut accept, workers) =
+            accept(1, BackpressurePolicy::RoundRobin, 2, OverflowAction::Reset);
+        workers[0].1.set(false);
+
+        for _ in 0..3 {
+            accept.accept_one(connection());
+        }
+        assert!(accept.backpressure);
+        assert_eq!(accept.pending.len(), 2);
+        assert!(!accept.must_wait());
+        assert_eq!(workers[0].0.len(), 0);
+
+        accept.config.overflow = OverflowAction::Wait;
+        assert!(accept.must_wait());
+
+        // worker is available, send pending connections
+        workers[0].1.set(true);
+        accept.backpressure(false);
+        accept.process_pending();
+        assert!(accept.pending.is_empty());
+        assert_eq!(workers[0].0.len(), 2);
+    }
+
+    #[test]
+    fn test_events() {
+        let (mut accept, workers) =
+            accept(1, BackpressurePolicy::RoundRobin, 0, OverflowAction::Wait);
+        let events = accept.srv.events();
+
+        workers[0].1.saturated();
+        assert!(accept.process_cmd());
+        assert_eq!(
+            events.try_recv(),
+            Some(ServerEvent::WorkerSaturated { idx: 0 })
+        );
+
+        accept.accept_one(connection());
+        assert_eq!(events.try_recv(), Some(ServerEvent::AcceptPaused));
+
+        workers[0].1.set(true);
+        assert!(accept.process_cmd());
+        assert_eq!(events.try_recv(), Some(ServerEvent::AcceptResumed));
+        assert_eq!(events.try_recv(), None);
+    }
+
+    #[test]
+    fn test_filter() {
+        let (mut accept, _workers) =
+            accept(1, BackpressurePolicy::RoundRobin, 0, OverflowAction::Wait);
+        accept.config.filter =
+            Some(Arc::new(crate::server::IpLimiter::new().max_connections(1)));
+        let events = accept.srv.events();
+
+        let msg = accept.filter(connection()).unwrap();
+        assert!(msg.filter.is_some());
+        assert!(accept.filter(connection()).is_none());
+        assert!(matches!(
+            events.try_recv(),
+            Some(ServerEvent::ConnectionRejected { token: 0, .. })
+        ));
+
+        // connection is closed
+        drop(msg);
+        assert!(accept.filter(connection()).is_some());
+    }
+}
@@ -0,0 +1,847 @@
+//! Automatic certificates management with ACME protocol (RFC 8555)
+//!
+//! `acme` feature enables certificates issuance and renewal with ACME
+//! certificate authorities, i.e. Let's Encrypt. Domains ownership is verified
+//! with `http-01` challenge, so server must be reachable on port 80 for all
+//! domains. Certificates are managed by background arbiter, issued
+//! certificates are loaded to the acceptor via `AcceptorHandle`. Until
+//! certificate is issued, acceptor uses temporary self-signed certificate.
+//!
+//! ```rust,ignore
+//! use ntex::http::{HttpService, Response};
+//! use ntex::server::{self, acme, openssl::Acceptor};
+//! use ntex::service::pipeline_factory;
+//!
+//! #[ntex::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let acme = acme::Acme::new(
+//!         acme::AcmeConfig::new(["example.com", "www.example.com"])
+//!             .contact("mailto:admin@example.com")
+//!             .cache_dir("/var/lib/myapp/acme"),
+//!     )?;
+//!     let challenges = acme.challenges();
+//!     let handle = acme.acceptor_handle();
+//!     acme.start();
+//!
+//!     server::build()
+//!         .bind("acme", "0.0.0.0:80", move |_| {
+//!             HttpService::build().finish(challenges.service())
+//!         })?
+//!         .bind("https", "0.0.0.0:443", move |_| {
+//!             pipeline_factory(Acceptor::from(handle.clone()).map_err(|_| ())).and_then(
+//!                 HttpService::build()
+//!                     .finish(|_| async { Ok::<_, std::io::Error>(Response::Ok()) })
+//!                     .map_err(|_| ()),
+//!             )
+//!         })?
+//!         .run()
+//!         .await
+//! }
+//! ```
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::{cmp, fmt, fs, io, path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tls_openssl::bn::{BigNum, BigNumContext};
+use tls_openssl::ec::{EcGroup, EcKey, EcKeyRef};
+use tls_openssl::ecdsa::EcdsaSig;
+use tls_openssl::hash::MessageDigest;
+use tls_openssl::nid::Nid;
+use tls_openssl::pkey::{PKey, PKeyRef, Private};
+use tls_openssl::ssl::{self, AlpnError, SslAcceptor, SslMethod};
+use tls_openssl::x509::extension::SubjectAlternativeName;
+use tls_openssl::x509::{X509Builder, X509NameBuilder, X509ReqBuilder, X509};
+use tls_openssl::{asn1::Asn1Time, error::ErrorStack, sha::sha256, stack::Stack};
+
+use crate::http::client::Client;
+use crate::http::{Request, Response};
+use crate::rt::{spawn, Arbiter};
+use crate::service::{fn_service, ServiceFactory};
+use crate::time::{sleep, Seconds};
+use crate::util::{Bytes, Ready};
+
+use super::openssl::AcceptorHandle;
+
+/// Let's Encrypt production directory
+pub const LETS_ENCRYPT_PRODUCTION: &str = "https://acme-v02.api.letsencrypt.org/directory";
+/// Let's Encrypt staging directory
+pub const LETS_ENCRYPT_STAGING: &str =
+    "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+const CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const POLL_ATTEMPTS: usize = 30;
+const MAX_BODY: usize = 256 * 1024;
+
+/// ACME certificates configuration
+#[derive(Clone, Debug)]
+pub struct AcmeConfig {
+    directory: String,
+    domains: Vec<String>,
+    contact: Vec<String>,
+    renew_before: u32,
+    cache_dir: Option<PathBuf>,
+}
+
+impl AcmeConfig {
+    /// Create configuration for list of domains.
+    ///
+    /// Single certificate is issued for all domains.
+    pub fn new<I, S>(domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        AcmeConfig {
+            directory: LETS_ENCRYPT_PRODUCTION.to_string(),
+            domains: domains.into_iter().map(|d| d.into()).collect(),
+            contact: Vec::new(),
+            renew_before: 30,
+            cache_dir: None,
+        }
+    }
+
+    /// Set ACME directory url.
+    ///
+    /// By default Let's Encrypt production directory is used.
+    pub fn directory<S: Into<String>>(mut self, url: S) -> Self {
+        self.directory = url.into();
+        self
+    }
+
+    /// Use Let's Encrypt staging directory.
+    pub fn staging(self) -> Self {
+        self.directory(LETS_ENCRYPT_STAGING)
+    }
+
+    /// Add account contact, i.e. `mailto:admin@example.com`
+    pub fn contact<S: Into<String>>(mut self, contact: S) -> Self {
+        self.contact.push(contact.into());
+        self
+    }
+
+    /// Renew certificate number of days before expiration.
+    ///
+    /// By default certificate is renewed 30 days before expiration.
+    pub fn renew_before(mut self, days: u32) -> Self {
+        self.renew_before = days;
+        self
+    }
+
+    /// Store account key and issued certificate in directory.
+    ///
+    /// Cached certificate is used on server restart, without cache
+    /// new certificate is issued on every start.
+    pub fn cache_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cache_dir = Some(path.into());
+        self
+    }
+}
+
+/// Pending `http-01` challenges
+#[derive(Clone, Default)]
+pub struct Challenges(Arc<RwLock<HashMap<String, String>>>);
+
+impl Challenges {
+    /// Key authorization for challenge token
+    pub fn get(&self, token: &str) -> Option<String> {
+        self.0.read().unwrap().get(token).cloned()
+    }
+
+    /// Http service that responds to `http-01` challenges.
+    ///
+    /// Service responds with `404 Not Found` for all other requests.
+    pub fn service(
+        &self,
+    ) -> impl ServiceFactory<Request, Response = Response, Error = io::Error, InitError = ()>
+    {
+        let challenges = self.clone();
+        fn_service(move |req: Request| {
+            let res = req
+                .path()
+                .strip_prefix(CHALLENGE_PATH)
+                .and_then(|token| challenges.get(token))
+                .map(|key_auth| {
+                    Response::Ok()
+                        .content_type("application/octet-stream")
+                        .body(key_auth)
+                })
+                .unwrap_or_else(|| Response::NotFound().finish());
+            Ready::Ok(res)
+        })
+    }
+
+    fn insert(&self, token: String, key_auth: String) {
+        self.0.write().unwrap().insert(token, key_auth);
+    }
+
+    fn remove(&self, token: &str) {
+        self.0.write().unwrap().remove(token);
+    }
+}
+
+impl fmt::Debug for Challenges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Challenges")
+            .field("pending", &self.0.read().unwrap().len())
+            .finish()
+    }
+}
+
+/// ACME certificates manager
+#[derive(Clone)]
+pub struct Acme(Arc<Inner>);
+
+struct Inner {
+    cfg: AcmeConfig,
+    challenges: Challenges,
+    handle: AcceptorHandle,
+    cert: Mutex<Option<X509>>,
+}
+
+impl Acme {
+    /// Create certificates manager.
+    ///
+    /// Cached certificate is loaded if it is available.
+    pub fn new(cfg: AcmeConfig) -> io::Result<Self> {
+        if cfg.domains.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Domains list is empty",
+            ));
+        }
+
+        let (acceptor, cert) = match load_cert(&cfg) {
+            Some((key, chain)) => (acceptor(&key, &chain)?, Some(chain[0].clone())),
+            None => {
+                let (key, cert) = self_signed(&cfg.domains)?;
+                (acceptor(&key, &[cert])?, None)
+            }
+        };
+
+        Ok(Acme(Arc::new(Inner {
+            cfg,
+            cert: Mutex::new(cert),
+            challenges: Challenges::default(),
+            handle: AcceptorHandle::new(acceptor),
+        })))
+    }
+
+    /// Pending `http-01` challenges
+    pub fn challenges(&self) -> Challenges {
+        self.0.challenges.clone()
+    }
+
+    /// Acceptor handle, issued certificates are loaded to this handle
+    pub fn acceptor_handle(&self) -> AcceptorHandle {
+        self.0.handle.clone()
+    }
+
+    /// Start background arbiter for certificates management
+    pub fn start(&self) -> Arbiter {
+        let inner = self.0.clone();
+        let arb = Arbiter::with_name("ntex-acme".to_string());
+        arb.exec_fn(move || {
+            spawn(run(inner));
+        });
+        arb
+    }
+}
+
+impl fmt::Debug for Acme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Acme")
+            .field("config", &self.0.cfg)
+            .field("challenges", &self.0.challenges)
+            .finish()
+    }
+}
+
+async fn run(inner: Arc<Inner>) {
+    let client = Client::build().timeout(Seconds(30)).finish();
+    let domains = &inner.cfg.domains;
+
+    loop {
+        let renew_in = inner
+            .cert
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|cert| renew_in(cert, inner.cfg.renew_before));
+        if let Some(dur) = renew_in {
+            sleep(cmp::min(dur, CHECK_INTERVAL)).await;
+            continue;
+        }
+
+        match issue(&client, &inner).await {
+            Ok(()) => log::info!("Certificate for {:?} is issued", domains),
+            Err(err) => {
+                log::error!("Cannot issue certificate for {:?}: {}", domains, err);
+                sleep(RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn issue(client: &Client, inner: &Inner) -> io::Result<()> {
+    let cfg = &inner.cfg;
+    let account = Account::new(load_account_key(cfg)?)?;
+    let mut session = Session::new(client, account, &cfg.directory).await?;
+    session.register(&cfg.contact).await?;
+
+    let (order_url, order) = session.new_order(&cfg.domains).await?;
+    for url in &order.authorizations {
+        session.authorize(url, &inner.challenges).await?;
+    }
+
+    let key = PKey::from_ec_key(new_ec_key()?)?;
+    let csr = csr(&cfg.domains, &key)?;
+    let cert_url = session.finalize(&order_url, &order.finalize, &csr).await?;
+    let pem = session.post(&cert_url, None).await?.1;
+    let chain = X509::stack_from_pem(&pem)?;
+    if chain.is_empty() {
+        return Err(error("Certificate chain is empty"));
+    }
+
+    inner.handle.reload(acceptor(&key, &chain)?);
+    if let Some(ref dir) = cfg.cache_dir {
+        fs::write(dir.join("key.pem"), key.private_key_to_pem_pkcs8()?)?;
+        fs::write(dir.join("cert.pem"), &pem)?;
+    }
+    *inner.cert.lock().unwrap() = Some(chain[0].clone());
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    #[serde(default)]
+    token: String,
+}
+
+#[derive(Deserialize, Default)]
+struct Problem {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    detail: String,
+}
+
+/// Account key and its jwk representation
+struct Account {
+    key: EcKey<Private>,
+    x: String,
+    y: String,
+}
+
+impl Account {
+    fn new(key: EcKey<Private>) -> io::Result<Self> {
+        let mut ctx = BigNumContext::new()?;
+        let mut x = BigNum::new()?;
+        let mut y = BigNum::new()?;
+        key.public_key()
+            .affine_coordinates_gfp(key.group(), &mut x, &mut y, &mut ctx)?;
+
+        Ok(Account {
+            x: b64(&x.to_vec_padded(32)?),
+            y: b64(&y.to_vec_padded(32)?),
+            key,
+        })
+    }
+
+    fn jwk(&self) -> Value {
+        json!({"crv": "P-256", "kty": "EC", "x": self.x, "y": self.y})
+    }
+
+    /// Jwk thumbprint (RFC 7638), members are in lexicographic order
+    fn thumbprint(&self) -> String {
+        let jwk = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            self.x, self.y
+        );
+        b64(&sha256(jwk.as_bytes()))
+    }
+
+    /// Create JWS with ES256 signature
+    fn jws(
+        &self,
+        url: &str,
+        nonce: &str,
+        kid: Option<&str>,
+        payload: Option<&Value>,
+    ) -> io::Result<String> {
+        let mut protected = json!({"alg": "ES256", "nonce": nonce, "url": url});
+        if let Some(kid) = kid {
+            protected["kid"] = json!(kid);
+        } else {
+            protected["jwk"] = self.jwk();
+        }
+        let protected = b64(protected.to_string().as_bytes());
+        // empty payload is used for POST-as-GET requests
+        let payload = payload
+            .map(|p| b64(p.to_string().as_bytes()))
+            .unwrap_or_default();
+        let signature = sign(&self.key, format!("{}.{}", protected, payload).as_bytes())?;
+
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": b64(&signature),
+        })
+        .to_string())
+    }
+}
+
+struct Session<'a> {
+    client: &'a Client,
+    account: Account,
+    dir: Directory,
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+impl<'a> Session<'a> {
+    async fn new(
+        client: &'a Client,
+        account: Account,
+        directory: &str,
+    ) -> io::Result<Session<'a>> {
+        let mut res = client.get(directory).send().await.map_err(client_error)?;
+        let body = res.body().limit(MAX_BODY).await.map_err(client_error)?;
+        if !res.status().is_success() {
+            return Err(error(format!("Directory request failed: {}", res.status())));
+        }
+
+        Ok(Session {
+            client,
+            account,
+            dir: serde_json::from_slice(&body)?,
+            kid: None,
+            nonce: None,
+        })
+    }
+
+    async fn new_nonce(&self) -> io::Result<String> {
+        let res = self
+            .client
+            .head(&self.dir.new_nonce)
+            .send()
+            .await
+            .map_err(client_error)?;
+        res.header("replay-nonce")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .ok_or_else(|| error("Replay nonce is not available"))
+    }
+
+    /// Send signed request, returns location header and response body
+    async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> io::Result<(Option<String>, Bytes)> {
+        for _ in 0..3 {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.new_nonce().await?,
+            };
+            let body = self
+                .account
+                .jws(url, &nonce, self.kid.as_deref(), payload)?;
+
+            let mut res = self
+                .client
+                .post(url)
+                .content_type("application/jose+json")
+                .send_body(body)
+                .await
+                .map_err(client_error)?;
+            self.nonce = res
+                .header("replay-nonce")
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string());
+            let body = res.body().limit(MAX_BODY).await.map_err(client_error)?;
+
+            if res.status().is_success() {
+                let location = res
+                    .header("location")
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string());
+                return Ok((location, body));
+            }
+
+            let problem: Problem = serde_json::from_slice(&body).unwrap_or_default();
+            if problem.kind != "urn:ietf:params:acme:error:badNonce" {
+                return Err(error(format!(
+                    "Request failed {}: {} {}",
+                    res.status(),
+                    problem.kind,
+                    problem.detail
+                )));
+            }
+        }
+        Err(error("Too many bad nonce errors"))
+    }
+
+    async fn register(&mut self, contact: &[String]) -> io::Result<()> {
+        let url = self.dir.new_account.clone();
+        let payload = json!({"termsOfServiceAgreed": true, "contact": contact});
+        let (location, _) = self.post(&url, Some(&payload)).await?;
+        self.kid = Some(location.ok_or_else(|| error("Account url is not available"))?);
+        Ok(())
+    }
+
+    async fn new_order(&mut self, domains: &[String]) -> io::Result<(String, Order)> {
+        let url = self.dir.new_order.clone();
+        let identifiers: Vec<_> = domains
+            .iter()
+            .map(|d| json!({"type": "dns", "value": d}))
+            .collect();
+        let payload = json!({ "identifiers": identifiers });
+        let (location, body) = self.post(&url, Some(&payload)).await?;
+        let location = location.ok_or_else(|| error("Order url is not available"))?;
+        Ok((location, serde_json::from_slice(&body)?))
+    }
+
+    async fn authorize(&mut self, url: &str, challenges: &Challenges) -> io::Result<()> {
+        let auth: Authorization = serde_json::from_slice(&self.post(url, None).await?.1)?;
+        if auth.status == "valid" {
+            return Ok(());
+        }
+
+        let domain = auth.identifier.value;
+        let challenge = auth
+            .challenges
+            .into_iter()
+            .find(|ch| ch.kind == "http-01")
+            .ok_or_else(|| {
+                error(format!("http-01 challenge is not available: {}", domain))
+            })?;
+        let key_auth = format!("{}.{}", challenge.token, self.account.thumbprint());
+        challenges.insert(challenge.token.clone(), key_auth);
+
+        let res = self.validate(url, &challenge.url, &domain).await;
+        challenges.remove(&challenge.token);
+        res
+    }
+
+    async fn validate(
+        &mut self,
+        url: &str,
+        challenge: &str,
+        domain: &str,
+    ) -> io::Result<()> {
+        self.post(challenge, Some(&json!({}))).await?;
+
+        for _ in 0..POLL_ATTEMPTS {
+            let auth: Authorization =
+                serde_json::from_slice(&self.post(url, None).await?.1)?;
+            match auth.status.as_str() {
+                "valid" => return Ok(()),
+                "pending" => sleep(Seconds(2)).await,
+                status => {
+                    return Err(error(format!(
+                        "Authorization for {} failed: {}",
+                        domain, status
+                    )))
+                }
+            }
+        }
+        Err(error(format!("Authorization for {} timed out", domain)))
+    }
+
+    /// Finalize order, returns certificate url
+    async fn finalize(
+        &mut self,
+        url: &str,
+        finalize: &str,
+        csr: &[u8],
+    ) -> io::Result<String> {
+        let body = self
+            .post(finalize, Some(&json!({ "csr": b64(csr) })))
+            .await?
+            .1;
+        let mut order: Order = serde_json::from_slice(&body)?;
+
+        for _ in 0..POLL_ATTEMPTS {
+            match order.status.as_str() {
+                "valid" => {
+                    return order
+                        .certificate
+                        .ok_or_else(|| error("Certificate url is not available"))
+                }
+                "processing" => sleep(Seconds(2)).await,
+                status => return Err(error(format!("Order failed: {}", status))),
+            }
+            order = serde_json::from_slice(&self.post(url, None).await?.1)?;
+        }
+        Err(error("Order processing timed out"))
+    }
+}
+
+fn error<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+/// Http client errors are not `Send`, keep error message only
+fn client_error<E: fmt::Display>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+fn ssl_error(err: ErrorStack) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+fn b64(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+fn new_ec_key() -> io::Result<EcKey<Private>> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    Ok(EcKey::generate(&group)?)
+}
+
+/// ES256 signature, r and s values are concatenated
+fn sign(key: &EcKeyRef<Private>, data: &[u8]) -> io::Result<Vec<u8>> {
+    let sig = EcdsaSig::sign(&sha256(data), key)?;
+    let mut buf = sig.r().to_vec_padded(32)?;
+    buf.extend(sig.s().to_vec_padded(32)?);
+    Ok(buf)
+}
+
+fn san(domains: &[String]) -> SubjectAlternativeName {
+    let mut san = SubjectAlternativeName::new();
+    for domain in domains {
+        san.dns(domain);
+    }
+    san
+}
+
+/// Certificate signing request in DER format
+fn csr(domains: &[String], key: &PKeyRef<Private>) -> io::Result<Vec<u8>> {
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, &domains[0])?;
+
+    let mut req = X509ReqBuilder::new()?;
+    req.set_subject_name(&name.build())?;
+    req.set_pubkey(key)?;
+    let mut exts = Stack::new()?;
+    exts.push(san(domains).build(&req.x509v3_context(None))?)?;
+    req.add_extensions(&exts)?;
+    req.sign(key, MessageDigest::sha256())?;
+    Ok(req.build().to_der()?)
+}
+
+/// Temporary certificate, used until certificate is issued
+fn self_signed(domains: &[String]) -> io::Result<(PKey<Private>, X509)> {
+    let key = PKey::from_ec_key(new_ec_key()?)?;
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, &domains[0])?;
+    let name = name.build();
+
+    let mut cert = X509Builder::new()?;
+    cert.set_version(2)?;
+    cert.set_subject_name(&name)?;
+    cert.set_issuer_name(&name)?;
+    cert.set_pubkey(&key)?;
+    let not_before = Asn1Time::days_from_now(0).map_err(ssl_error)?;
+    let not_after = Asn1Time::days_from_now(1).map_err(ssl_error)?;
+    cert.set_not_before(&not_before)?;
+    cert.set_not_after(&not_after)?;
+    let san = san(domains).build(&cert.x509v3_context(None, None))?;
+    cert.append_extension(san)?;
+    cert.sign(&key, MessageDigest::sha256())?;
+    Ok((key, cert.build()))
+}
+
+fn acceptor(key: &PKeyRef<Private>, chain: &[X509]) -> io::Result<SslAcceptor> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_private_key(key)?;
+    builder.set_certificate(&chain[0])?;
+    for cert in &chain[1..] {
+        builder.add_extra_chain_cert(cert.clone())?;
+    }
+    builder.check_private_key()?;
+    builder.set_alpn_select_callback(|_, protos| {
+        ssl::select_next_proto(b"\x02h2\x08http/1.1", protos).ok_or(AlpnError::NOACK)
+    });
+    Ok(builder.build())
+}
+
+/// Time until certificate renewal, `None` if certificate must be renewed
+fn renew_in(cert: &X509, renew_before: u32) -> Option<Duration> {
+    let diff = Asn1Time::days_from_now(0)
+        .ok()?
+        .diff(cert.not_after())
+        .ok()?;
+    let secs = (diff.days as i64 - renew_before as i64) * 86400 + diff.secs as i64;
+    if secs > 0 {
+        Some(Duration::from_secs(secs as u64))
+    } else {
+        None
+    }
+}
+
+fn load_account_key(cfg: &AcmeConfig) -> io::Result<EcKey<Private>> {
+    if let Some(ref dir) = cfg.cache_dir {
+        let path = dir.join("account.pem");
+        if let Ok(pem) = fs::read(&path) {
+            return Ok(EcKey::private_key_from_pem(&pem)?);
+        }
+        let key = new_ec_key()?;
+        fs::create_dir_all(dir)?;
+        fs::write(&path, key.private_key_to_pem()?)?;
+        Ok(key)
+    } else {
+        new_ec_key()
+    }
+}
+
+/// Load cached certificate if it does not require renewal
+fn load_cert(cfg: &AcmeConfig) -> Option<(PKey<Private>, Vec<X509>)> {
+    let dir = cfg.cache_dir.as_ref()?;
+    let key = PKey::private_key_from_pem(&fs::read(dir.join("key.pem")).ok()?).ok()?;
+    let chain = X509::stack_from_pem(&fs::read(dir.join("cert.pem")).ok()?).ok()?;
+    let cert = chain.first()?;
+    renew_in(cert, cfg.renew_before)?;
+    Some((key, chain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::body::{Body, ResponseBody};
+    use crate::http::{test::TestRequest, Method, StatusCode};
+    use crate::service::Service;
+
+    #[test]
+    fn test_jws() {
+        let account = Account::new(new_ec_key().unwrap()).unwrap();
+        assert_eq!(account.thumbprint().len(), 43);
+
+        let jws = account
+            .jws(
+                "https://ca/new-order",
+                "nonce",
+                Some("kid"),
+                Some(&json!({})),
+            )
+            .unwrap();
+        let jws: Value = serde_json::from_str(&jws).unwrap();
+        let protected = base64::decode_config(
+            jws["protected"].as_str().unwrap(),
+            base64::URL_SAFE_NO_PAD,
+        )
+        .unwrap();
+        let protected: Value = serde_json::from_slice(&protected).unwrap();
+        assert_eq!(protected["alg"], "ES256");
+        assert_eq!(protected["kid"], "kid");
+        assert_eq!(protected["nonce"], "nonce");
+        assert!(protected.get("jwk").is_none());
+
+        // verify signature
+        let sig = base64::decode_config(
+            jws["signature"].as_str().unwrap(),
+            base64::URL_SAFE_NO_PAD,
+        )
+        .unwrap();
+        assert_eq!(sig.len(), 64);
+        let sig = EcdsaSig::from_private_components(
+            BigNum::from_slice(&sig[..32]).unwrap(),
+            BigNum::from_slice(&sig[32..]).unwrap(),
+        )
+        .unwrap();
+        let data = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap()
+        );
+        assert!(sig.verify(&sha256(data.as_bytes()), &account.key).unwrap());
+
+        // POST-as-GET
+        let jws = account
+            .jws("https://ca/order", "nonce", None, None)
+            .unwrap();
+        let jws: Value = serde_json::from_str(&jws).unwrap();
+        assert_eq!(jws["payload"], "");
+    }
+
+    #[test]
+    fn test_certificates() {
+        let domains = vec!["example.com".to_string(), "www.example.com".to_string()];
+        let (key, cert) = self_signed(&domains).unwrap();
+        assert!(renew_in(&cert, 0).is_some());
+        assert!(renew_in(&cert, 1).is_none());
+        let names: Vec<_> = cert
+            .subject_alt_names()
+            .unwrap()
+            .iter()
+            .map(|n| n.dnsname().unwrap().to_string())
+            .collect();
+        assert_eq!(names, domains);
+        assert!(acceptor(&key, &[cert]).is_ok());
+
+        let csr = csr(&domains, &key).unwrap();
+        let req = tls_openssl::x509::X509Req::from_der(&csr).unwrap();
+        assert!(req.verify(&key).unwrap());
+    }
+
+    #[crate::rt_test]
+    async fn test_challenges() {
+        let challenges = Challenges::default();
+        challenges.insert("token".to_string(), "token.key".to_string());
+        assert!(format!("{:?}", challenges).contains("pending: 1"));
+
+        let srv = challenges.service().new_service(()).await.unwrap();
+        let req = TestRequest::with_uri("/.well-known/acme-challenge/token")
+            .method(Method::GET)
+            .finish();
+        let res = srv.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        match res.body() {
+            ResponseBody::Body(Body::Bytes(body)) => assert_eq!(body, "token.key"),
+            _ => panic!(),
+        }
+
+        challenges.remove("token");
+        let req = TestRequest::with_uri("/.well-known/acme-challenge/token").finish();
+        let res = srv.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
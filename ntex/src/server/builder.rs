@@ -1,8 +1,11 @@
-use std::{fmt, future::Future, io, marker, mem, net, pin::Pin, task::Context, task::Poll};
+use std::{
+    cmp, collections::VecDeque, fmt, future::Future, io, marker, mem, net, pin::Pin,
+};
+use std::{task::Context, task::Poll, time::Duration, time::Instant};
 
 use async_channel::{unbounded, Receiver};
 use async_oneshot as oneshot;
-use log::{error, info};
+use log::{error, info, warn};
 use socket2::{Domain, SockAddr, Socket, Type};
 
 use crate::rt::{spawn, Signal, System};
@@ -15,24 +18,42 @@ use super::accept::{AcceptLoop, AcceptNotify, Command};
 use super::config::{
     Config, ConfigWrapper, ConfiguredService, ServiceConfig, ServiceRuntime,
 };
+use super::filter::AcceptFilter;
 use super::service::{Factory, InternalServiceFactory};
-use super::socket::Listener;
-use super::worker::{self, Worker, WorkerAvailability, WorkerClient};
-use super::{Server, ServerCommand, ServerStatus, Token};
+use super::socket::{Listener, SocketAddr};
+use super::worker::{self, Worker, WorkerAvailability, WorkerClient, WorkerLimits};
+use super::{BackpressurePolicy, BindFactory, OverflowAction};
+use super::{Server, ServerCommand, ServerEvent, ServerEvents, ServerStatus, Token};
 
 const STOP_DELAY: Millis = Millis(300);
 
+/// Platform supports `SO_REUSEPORT` socket option
+const REUSEPORT: bool = cfg!(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos"))
+));
+
 /// Server builder
 pub struct ServerBuilder {
     threads: usize,
+    worker_name: Option<String>,
+    affinity: Vec<usize>,
     token: Token,
     backlog: i32,
+    reuseport: bool,
+    maxconn: usize,
+    maxconnrate: Option<usize>,
     workers: Vec<(usize, WorkerClient)>,
     services: Vec<Box<dyn InternalServiceFactory>>,
     sockets: Vec<(Token, String, Listener)>,
+    listeners: Vec<(Token, String)>,
     accept: AcceptLoop,
+    loops: Vec<(usize, AcceptNotify)>,
     exit: bool,
+    stopping: bool,
     shutdown_timeout: Millis,
+    restart_policy: Option<(usize, Millis)>,
+    restarts: VecDeque<Instant>,
     no_signals: bool,
     cmd: Receiver<ServerCommand>,
     server: Server,
@@ -53,14 +74,24 @@ impl ServerBuilder {
 
         ServerBuilder {
             threads: num_cpus::get(),
+            worker_name: None,
+            affinity: Vec::new(),
             token: Token(0),
             workers: Vec::new(),
             services: Vec::new(),
             sockets: Vec::new(),
+            listeners: Vec::new(),
             accept: AcceptLoop::new(server.clone()),
+            loops: Vec::new(),
             backlog: 2048,
+            reuseport: false,
+            maxconn: worker::MAX_CONNS,
+            maxconnrate: None,
             exit: false,
+            stopping: false,
             shutdown_timeout: Millis::from_secs(30),
+            restart_policy: None,
+            restarts: VecDeque::new(),
             no_signals: false,
             cmd: rx,
             notify: Vec::new(),
@@ -77,6 +108,26 @@ impl ServerBuilder {
         self
     }
 
+    /// Set name prefix for worker threads.
+    ///
+    /// Worker thread name is set to `{prefix}:{idx}`.
+    /// By default arbiter's name is used, `ntex-rt:worker:{id}`.
+    pub fn worker_name<N: AsRef<str>>(mut self, prefix: N) -> Self {
+        self.worker_name = Some(prefix.as_ref().to_string());
+        self
+    }
+
+    /// Pin worker threads to cpu cores.
+    ///
+    /// Worker with index `idx` is pinned to `cores[idx % cores.len()]` core.
+    /// Cpu affinity is supported on linux only.
+    ///
+    /// By default workers are not pinned.
+    pub fn worker_affinity(mut self, cores: Vec<usize>) -> Self {
+        self.affinity = cores;
+        self
+    }
+
     /// Set the maximum number of pending connections.
     ///
     /// This refers to the number of clients that can be waiting to be served.
@@ -92,14 +143,72 @@ impl ServerBuilder {
         self
     }
 
+    /// Enable `SO_REUSEPORT` mode.
+    ///
+    /// In this mode each worker gets its own listening socket and its own
+    /// accept loop, kernel distributes incoming connections between sockets.
+    /// Server falls back to shared accept loop if platform does not support
+    /// `SO_REUSEPORT` option or server has unix domain listeners.
+    ///
+    /// By default `SO_REUSEPORT` mode is disabled.
+    ///
+    /// This method should be called before `bind()` method call.
+    pub fn reuseport(mut self, enabled: bool) -> Self {
+        self.reuseport = enabled;
+        self
+    }
+
     /// Sets the maximum per-worker number of concurrent connections.
     ///
     /// All socket listeners will stop accepting connections when this limit is
     /// reached for each worker.
     ///
     /// By default max connections is set to a 25k per worker.
-    pub fn maxconn(self, num: usize) -> Self {
-        worker::max_concurrent_connections(num);
+    pub fn maxconn(mut self, num: usize) -> Self {
+        self.maxconn = num;
+        self
+    }
+
+    /// Sets the maximum per-worker concurrent ssl connection establish process.
+    ///
+    /// All listeners will stop accepting connections when this limit is
+    /// reached. It can be used to limit the global SSL CPU usage.
+    ///
+    /// By default max connections is set to a 256.
+    pub fn maxconnrate(mut self, num: usize) -> Self {
+        self.maxconnrate = Some(num);
+        self
+    }
+
+    /// Set worker selection policy for accepted connections.
+    ///
+    /// By default round-robin policy is used.
+    pub fn backpressure_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.accept.set_policy(policy);
+        self
+    }
+
+    /// Set pending connections queue limit.
+    ///
+    /// If all workers are busy, accepted connections are kept in the pending
+    /// queue until one of the workers become available. If queue is full,
+    /// `action` defines what to do with new connections.
+    ///
+    /// By default pending queue is disabled and accepted connections are sent
+    /// to busy workers.
+    pub fn pending_connections(mut self, limit: usize, action: OverflowAction) -> Self {
+        self.accept.set_pending(limit, action);
+        self
+    }
+
+    /// Set accept filter for tcp connections.
+    ///
+    /// Filter is invoked by accept loop before connection is sent
+    /// to a worker, rejected connections get reset.
+    ///
+    /// By default all connections are accepted.
+    pub fn accept_filter<F: AcceptFilter>(mut self, filter: F) -> Self {
+        self.accept.set_filter(filter);
         self
     }
 
@@ -131,6 +240,23 @@ impl ServerBuilder {
         self
     }
 
+    /// Set restart policy for failed workers.
+    ///
+    /// Worker fails if its service panics. Failed worker gets re-created
+    /// with all registered services. If workers fail more than `max_restarts`
+    /// times within `period`, failed workers are not restarted anymore and
+    /// server stops if there are no running workers.
+    ///
+    /// By default failed workers are always restarted.
+    pub fn worker_restarts<T: Into<Millis>>(
+        mut self,
+        max_restarts: usize,
+        period: T,
+    ) -> Self {
+        self.restart_policy = Some((max_restarts, period.into()));
+        self
+    }
+
     /// Set server status handler.
     ///
     /// Server calls this handler on every inner status update.
@@ -142,6 +268,14 @@ impl ServerBuilder {
         self
     }
 
+    /// Subscribe to server accept events.
+    ///
+    /// Subscription receives all events emitted by the server,
+    /// including events emitted during server startup.
+    pub fn events(&self) -> ServerEvents {
+        self.server.events()
+    }
+
     /// Execute external configuration as part of the server building
     /// process.
     ///
@@ -151,7 +285,13 @@ impl ServerBuilder {
     where
         F: Fn(&mut ServiceConfig) -> io::Result<()>,
     {
-        let mut cfg = ServiceConfig::new(self.threads, self.backlog);
+        let mut cfg = ServiceConfig::new(
+            self.threads,
+            self.backlog,
+            self.reuseport,
+            self.maxconn,
+            self.maxconnrate,
+        );
 
         f(&mut cfg)?;
 
@@ -164,6 +304,8 @@ impl ServerBuilder {
         }
         self.services.push(Box::new(srv));
         self.threads = cfg.threads;
+        self.maxconn = cfg.maxconn;
+        self.maxconnrate = cfg.maxconnrate;
 
         Ok(self)
     }
@@ -198,7 +340,7 @@ impl ServerBuilder {
         F: Fn(Config) -> R + Send + Clone + 'static,
         R: ServiceFactory<Io>,
     {
-        let sockets = bind_addr(addr, self.backlog)?;
+        let sockets = bind_addr(addr, self.backlog, self.reuseport)?;
 
         for lst in sockets {
             let token = self.token.next();
@@ -296,25 +438,53 @@ impl ServerBuilder {
         } else {
             info!("Starting {} workers", self.threads);
 
-            // start workers
-            let mut workers = Vec::new();
-            for idx in 0..self.threads {
-                let worker = self.start_worker(idx, self.accept.notify());
-                workers.push(worker.clone());
-                self.workers.push((idx, worker));
-            }
+            if let Some(mut sockets) = self.reuseport_sockets() {
+                for sock in &self.sockets {
+                    info!("Starting \"{}\" service on {}", sock.1, sock.2);
+                    self.listeners.push((sock.0, sock.1.clone()));
+                    self.listener_bound(sock.0, &sock.1, &sock.2);
+                }
+                sockets.insert(
+                    0,
+                    mem::take(&mut self.sockets)
+                        .into_iter()
+                        .map(|t| (t.0, t.2))
+                        .collect(),
+                );
+
+                // start workers, each worker has its own accept thread
+                for (idx, socks) in sockets.into_iter().enumerate() {
+                    let mut accept = AcceptLoop::new(self.server.clone());
+                    accept.share_config(&self.accept);
+
+                    let worker = self.start_worker(idx, accept.notify());
+                    self.workers.push((idx, worker.clone()));
+                    self.loops.push((idx, accept.notify()));
+                    accept.start(socks, vec![worker]);
+                }
+            } else {
+                // start workers
+                let mut workers = Vec::new();
+                for idx in 0..self.threads {
+                    let worker = self.start_worker(idx, self.accept.notify());
+                    workers.push(worker.clone());
+                    self.workers.push((idx, worker));
+                }
 
-            // start accept thread
-            for sock in &self.sockets {
-                info!("Starting \"{}\" service on {}", sock.1, sock.2);
+                // start accept thread
+                for sock in &self.sockets {
+                    info!("Starting \"{}\" service on {}", sock.1, sock.2);
+                    self.listeners.push((sock.0, sock.1.clone()));
+                    self.listener_bound(sock.0, &sock.1, &sock.2);
+                }
+                self.accept.start(
+                    mem::take(&mut self.sockets)
+                        .into_iter()
+                        .map(|t| (t.0, t.2))
+                        .collect(),
+                    workers,
+                );
             }
-            self.accept.start(
-                mem::take(&mut self.sockets)
-                    .into_iter()
-                    .map(|t| (t.0, t.2))
-                    .collect(),
-                workers,
-            );
 
             // handle signals
             if !self.no_signals {
@@ -328,22 +498,216 @@ impl ServerBuilder {
         }
     }
 
+    fn listener_bound(&self, token: Token, name: &str, lst: &Listener) {
+        self.server.emit(|| ServerEvent::ListenerBound {
+            name: name.to_string(),
+            addr: lst.local_addr().tcp(),
+            token: token.0,
+        });
+    }
+
     fn start_worker(&self, idx: usize, notify: AcceptNotify) -> WorkerClient {
-        let avail = WorkerAvailability::new(notify);
+        let avail = WorkerAvailability::new(idx, notify);
+        #[cfg(feature = "metrics")]
+        crate::metrics::register_worker(idx, &avail.counter());
         let services: Vec<Box<dyn InternalServiceFactory>> =
             self.services.iter().map(|v| v.clone_factory()).collect();
 
-        Worker::start(idx, services, avail, self.shutdown_timeout)
+        let name = self
+            .worker_name
+            .as_ref()
+            .map(|prefix| format!("{}:{}", prefix, idx));
+        let core = if self.affinity.is_empty() {
+            None
+        } else {
+            Some(self.affinity[idx % self.affinity.len()])
+        };
+
+        let limits = WorkerLimits {
+            maxconn: Some(self.maxconn),
+            maxconnrate: self.maxconnrate,
+        };
+        let (worker, exit) = Worker::start(
+            idx,
+            name,
+            core,
+            services,
+            avail,
+            limits,
+            self.shutdown_timeout,
+        );
+
+        // worker future is dropped before completion
+        let srv = self.server.clone();
+        spawn(async move {
+            if exit.await.is_err() {
+                srv.worker_faulted(idx);
+            }
+        });
+        worker
+    }
+
+    /// Check restart policy and register worker restart
+    fn restart_allowed(&mut self) -> bool {
+        if let Some((max, period)) = self.restart_policy {
+            let now = Instant::now();
+            let period = Duration::from(period);
+            while let Some(inst) = self.restarts.front() {
+                if now.duration_since(*inst) > period {
+                    self.restarts.pop_front();
+                } else {
+                    break;
+                }
+            }
+            if self.restarts.len() >= max {
+                return false;
+            }
+            self.restarts.push_back(now);
+        }
+        true
+    }
+
+    /// Create listening sockets for workers in `SO_REUSEPORT` mode.
+    ///
+    /// First worker uses sockets created by `bind()` method.
+    fn reuseport_sockets(&self) -> Option<Vec<Vec<(Token, Listener)>>> {
+        if !self.reuseport {
+            return None;
+        } else if !REUSEPORT {
+            warn!("SO_REUSEPORT is not supported, use shared accept loop");
+            return None;
+        }
+
+        let mut sockets = Vec::new();
+        for _ in 1..self.threads {
+            let mut socks = Vec::new();
+            for (token, name, lst) in &self.sockets {
+                let addr = match lst.local_addr() {
+                    SocketAddr::Tcp(addr) => addr,
+                    #[cfg(unix)]
+                    SocketAddr::Uds(_) => {
+                        warn!(
+                            "SO_REUSEPORT is not supported by \"{}\" service, use shared accept loop",
+                            name
+                        );
+                        return None;
+                    }
+                };
+                match create_listener(addr, self.backlog, true) {
+                    Ok(lst) => socks.push((*token, Listener::from_tcp(lst))),
+                    Err(e) => {
+                        error!(
+                            "Cannot create SO_REUSEPORT listener on {}: {}, use shared accept loop",
+                            addr, e
+                        );
+                        return None;
+                    }
+                }
+            }
+            sockets.push(socks);
+        }
+        Some(sockets)
+    }
+
+    /// Send command to all accept loops
+    fn send_accept<F: Fn() -> Command>(&self, f: F) {
+        if self.loops.is_empty() {
+            self.accept.send(f());
+        } else {
+            self.loops.iter().for_each(|item| item.1.send(f()));
+        }
+    }
+
+    fn bind_dynamic(
+        &mut self,
+        name: String,
+        addrs: Vec<net::SocketAddr>,
+        factory: BindFactory,
+    ) -> io::Result<impl Future<Output = io::Result<()>>> {
+        let mut futs = Vec::new();
+        let mut tokens = Vec::new();
+
+        // sockets for each accept loop
+        let notify: Vec<_> = if self.loops.is_empty() {
+            vec![self.accept.notify()]
+        } else {
+            self.loops.iter().map(|item| item.1.clone()).collect()
+        };
+        let mut sockets: Vec<Vec<_>> = notify.iter().map(|_| Vec::new()).collect();
+
+        for lst in bind_addr(&addrs[..], self.backlog, self.reuseport)? {
+            let token = self.token.next();
+            let addr = lst.local_addr()?;
+            for socks in sockets.iter_mut().skip(1) {
+                let lst = create_listener(addr, self.backlog, true)?;
+                socks.push((token, Listener::from_tcp(lst)));
+            }
+            let srv = (factory.0)(token, addr);
+            let lst = Listener::from_tcp(lst);
+            for (_, worker) in &self.workers {
+                futs.push(worker.add_service(srv.clone_factory()));
+            }
+            info!("Starting \"{}\" service on {}", name, lst);
+            self.listener_bound(token, &name, &lst);
+
+            self.services.push(srv);
+            self.listeners.push((token, name.clone()));
+            tokens.push(token);
+            sockets[0].push((token, lst));
+        }
+
+        let workers: Vec<_> = self.workers.iter().map(|w| w.1.clone()).collect();
+        Ok(async move {
+            let res = join_all(futs).await;
+            if res.into_iter().all(|res| matches!(res, Ok(true))) {
+                for (notify, socks) in notify.into_iter().zip(sockets) {
+                    notify.send(Command::Add(socks));
+                }
+                Ok(())
+            } else {
+                for worker in workers {
+                    worker.remove_services(tokens.clone());
+                }
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Cannot start \"{}\" service", name),
+                ))
+            }
+        })
+    }
+
+    fn unbind(&mut self, name: &str) -> io::Result<()> {
+        let tokens: Vec<_> = self
+            .listeners
+            .iter()
+            .filter(|item| item.1 == name)
+            .map(|item| item.0)
+            .collect();
+
+        if tokens.is_empty() {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Service \"{}\" is not found", name),
+            ))
+        } else {
+            info!("Stopping \"{}\" service", name);
+            self.listeners.retain(|item| item.1 != name);
+            self.send_accept(|| Command::Remove(tokens.clone()));
+            for (_, worker) in &self.workers {
+                worker.remove_services(tokens.clone());
+            }
+            Ok(())
+        }
     }
 
     fn handle_cmd(&mut self, item: ServerCommand) {
         match item {
             ServerCommand::Pause(mut tx) => {
-                self.accept.send(Command::Pause);
+                self.send_accept(|| Command::Pause);
                 let _ = tx.send(());
             }
             ServerCommand::Resume(mut tx) => {
-                self.accept.send(Command::Resume);
+                self.send_accept(|| Command::Resume);
                 let _ = tx.send(());
             }
             ServerCommand::Signal(sig) => {
@@ -380,14 +744,58 @@ impl ServerBuilder {
             ServerCommand::Notify(tx) => {
                 self.notify.push(tx);
             }
+            ServerCommand::Bind {
+                name,
+                addrs,
+                factory,
+                mut completion,
+            } => match self.bind_dynamic(name, addrs, factory) {
+                Ok(fut) => {
+                    spawn(async move {
+                        let _ = completion.send(fut.await);
+                    });
+                }
+                Err(e) => {
+                    let _ = completion.send(Err(e));
+                }
+            },
+            ServerCommand::Unbind {
+                name,
+                mut completion,
+            } => {
+                let _ = completion.send(self.unbind(&name));
+            }
+            ServerCommand::Limits(limits, mut tx) => {
+                // restarted workers use new limits as well
+                if let Some(num) = limits.maxconn {
+                    self.maxconn = num;
+                }
+                if let Some(num) = limits.maxconnrate {
+                    self.maxconnrate = Some(num);
+                }
+                for (_, worker) in &self.workers {
+                    worker.set_limits(limits);
+                }
+                let _ = tx.send(());
+            }
+            ServerCommand::Stats(mut tx) => {
+                let futs: Vec<_> = self.workers.iter().map(|w| w.1.stats()).collect();
+                spawn(async move {
+                    let mut stats: Vec<_> =
+                        join_all(futs).await.into_iter().flatten().collect();
+                    stats.sort_by_key(|s| s.idx);
+                    let _ = tx.send(stats);
+                });
+            }
             ServerCommand::Stop {
                 graceful,
                 completion,
             } => {
                 let exit = self.exit;
+                self.stopping = true;
 
                 // stop accept thread
-                self.accept.send(Command::Stop);
+                self.send_accept(|| Command::Stop);
                 let notify = std::mem::take(&mut self.notify);
 
                 // stop workers
@@ -438,23 +846,48 @@ impl ServerBuilder {
                     }
                 }
 
-                if found {
-                    error!("Worker has died {:?}, restarting", idx);
+                if found && !self.stopping {
+                    self.server.emit(|| ServerEvent::WorkerDied { idx });
+
+                    if !self.restart_allowed() {
+                        error!("Worker has died {:?}, restart limit is reached", idx);
 
-                    let mut new_idx = self.workers.len();
-                    'found: loop {
-                        for i in 0..self.workers.len() {
-                            if self.workers[i].0 == new_idx {
-                                new_idx += 1;
-                                continue 'found;
-                            }
+                        // in SO_REUSEPORT mode accept loop of failed worker has no workers
+                        if let Some(pos) = self.loops.iter().position(|item| item.0 == idx)
+                        {
+                            self.loops.remove(pos).1.send(Command::Stop);
                         }
-                        break;
+                        if self.workers.is_empty() {
+                            error!("No running workers, stopping server");
+                            self.handle_cmd(ServerCommand::Stop {
+                                graceful: false,
+                                completion: None,
+                            });
+                        }
+                        return;
                     }
+                    error!("Worker has died {:?}, restarting", idx);
 
-                    let worker = self.start_worker(new_idx, self.accept.notify());
+                    // failed worker could be reported by accept loop as well,
+                    // index of failed worker must not be reused
+                    let new_idx =
+                        self.workers.iter().map(|item| item.0).fold(idx, cmp::max) + 1;
+
+                    // in SO_REUSEPORT mode new worker uses accept loop of failed worker
+                    let notify = if let Some(item) =
+                        self.loops.iter_mut().find(|item| item.0 == idx)
+                    {
+                        item.0 = new_idx;
+                        item.1.clone()
+                    } else {
+                        self.accept.notify()
+                    };
+
+                    let worker = self.start_worker(new_idx, notify.clone());
                     self.workers.push((new_idx, worker.clone()));
-                    self.accept.send(Command::Worker(worker));
+                    notify.send(Command::Worker(worker));
+                    self.server
+                        .emit(|| ServerEvent::WorkerRestarted { idx, new_idx });
                 }
             }
         }
@@ -493,12 +926,13 @@ async fn signals(srv: Server) {
 pub(super) fn bind_addr<S: net::ToSocketAddrs>(
     addr: S,
     backlog: i32,
+    reuseport: bool,
 ) -> io::Result<Vec<net::TcpListener>> {
     let mut err = None;
     let mut succ = false;
     let mut sockets = Vec::new();
     for addr in addr.to_socket_addrs()? {
-        match create_tcp_listener(addr, backlog) {
+        match create_listener(addr, backlog, reuseport) {
             Ok(lst) => {
                 succ = true;
                 sockets.push(lst);
@@ -524,6 +958,14 @@ pub(super) fn bind_addr<S: net::ToSocketAddrs>(
 pub(crate) fn create_tcp_listener(
     addr: net::SocketAddr,
     backlog: i32,
+) -> io::Result<net::TcpListener> {
+    create_listener(addr, backlog, false)
+}
+
+fn create_listener(
+    addr: net::SocketAddr,
+    backlog: i32,
+    reuseport: bool,
 ) -> io::Result<net::TcpListener> {
     let builder = match addr {
         net::SocketAddr::V4(_) => Socket::new(Domain::IPV4, Type::STREAM, None)?,
@@ -536,6 +978,11 @@ pub(crate) fn create_tcp_listener(
     #[cfg(not(windows))]
     builder.set_reuse_address(true)?;
 
+    if reuseport {
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        builder.set_reuse_port(true)?;
+    }
+
     builder.bind(&SockAddr::from(addr))?;
     builder.listen(backlog)?;
     Ok(net::TcpListener::from(builder))
@@ -548,6 +995,15 @@ mod tests {
     #[test]
     fn test_bind_addr() {
         let addrs: Vec<net::SocketAddr> = Vec::new();
-        assert!(bind_addr(&addrs[..], 10).is_err());
+        assert!(bind_addr(&addrs[..], 10, false).is_err());
+    }
+
+    #[test]
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    fn test_reuseport() {
+        let lst = bind_addr("127.0.0.1:0", 10, true).unwrap();
+        let addr = lst[0].local_addr().unwrap();
+        assert!(create_listener(addr, 10, true).is_ok());
+        assert!(create_listener(addr, 10, false).is_err());
     }
 }
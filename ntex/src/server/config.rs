@@ -44,14 +44,26 @@ pub struct ServiceConfig {
     pub(super) apply: Box<dyn ServiceRuntimeConfiguration + Send>,
     pub(super) threads: usize,
     pub(super) backlog: i32,
+    pub(super) reuseport: bool,
+    pub(super) maxconn: usize,
+    pub(super) maxconnrate: Option<usize>,
     applied: bool,
 }
 
 impl ServiceConfig {
-    pub(super) fn new(threads: usize, backlog: i32) -> Self {
+    pub(super) fn new(
+        threads: usize,
+        backlog: i32,
+        reuseport: bool,
+        maxconn: usize,
+        maxconnrate: Option<usize>,
+    ) -> Self {
         ServiceConfig {
             threads,
             backlog,
+            reuseport,
+            maxconn,
+            maxconnrate,
             services: Vec::new(),
             applied: false,
             apply: Box::new(ConfigWrapper {
@@ -64,12 +76,30 @@ impl ServiceConfig {
         }
     }
 
+    /// Sets the maximum per-worker number of concurrent connections.
+    ///
+    /// Limit could be changed for running server with
+    /// [`Server::set_maxconn()`](struct.Server.html#method.set_maxconn) method.
+    pub fn maxconn(&mut self, num: usize) -> &mut Self {
+        self.maxconn = num;
+        self
+    }
+
+    /// Sets the maximum per-worker concurrent ssl connection establish process.
+    ///
+    /// Limit could be changed for running server with
+    /// [`Server::set_maxconnrate()`](struct.Server.html#method.set_maxconnrate) method.
+    pub fn maxconnrate(&mut self, num: usize) -> &mut Self {
+        self.maxconnrate = Some(num);
+        self
+    }
+
     /// Add new service to the server.
     pub fn bind<U, N: AsRef<str>>(&mut self, name: N, addr: U) -> io::Result<&mut Self>
     where
         U: net::ToSocketAddrs,
     {
-        let sockets = bind_addr(addr, self.backlog)?;
+        let sockets = bind_addr(addr, self.backlog, self.reuseport)?;
 
         for lst in sockets {
             self.listen(name.as_ref(), lst);
@@ -1,4 +1,5 @@
-use std::{cell::Cell, rc::Rc, task};
+use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc};
+use std::{cell::Cell, cell::RefCell, rc::Rc, task};
 
 use crate::task::LocalWaker;
 
@@ -9,17 +10,19 @@ pub(super) struct Counter(Rc<CounterInner>);
 
 struct CounterInner {
     count: Cell<usize>,
-    capacity: usize,
+    capacity: Cell<usize>,
     task: LocalWaker,
+    shared: RefCell<Option<Arc<AtomicUsize>>>,
 }
 
 impl Counter {
     /// Create `Counter` instance and set max value.
     pub(super) fn new(capacity: usize) -> Self {
         Counter(Rc::new(CounterInner {
-            capacity,
+            capacity: Cell::new(capacity),
             count: Cell::new(0),
             task: LocalWaker::new(),
+            shared: RefCell::new(None),
         }))
     }
 
@@ -39,6 +42,26 @@ impl Counter {
         self.0.count.get()
     }
 
+    /// Get max value
+    pub(super) fn capacity(&self) -> usize {
+        self.0.capacity.get()
+    }
+
+    /// Change max value, notify task if counter is not at capacity anymore.
+    pub(super) fn set_capacity(&self, capacity: usize) {
+        let old = self.0.capacity.replace(capacity);
+        if capacity > old && self.0.count.get() >= old {
+            self.0.task.wake();
+        }
+    }
+
+    /// Mirror total number of acquired counts to atomic counter,
+    /// so it could be observed from other threads.
+    pub(super) fn set_shared(&self, shared: Arc<AtomicUsize>) {
+        shared.store(self.0.count.get(), Ordering::Relaxed);
+        *self.0.shared.borrow_mut() = Some(shared);
+    }
+
     pub(super) fn priv_clone(&self) -> Self {
         Counter(self.0.clone())
     }
@@ -64,18 +87,24 @@ impl Drop for CounterGuard {
 impl CounterInner {
     fn inc(&self) {
         self.count.set(self.count.get() + 1);
+        if let Some(ref shared) = *self.shared.borrow() {
+            shared.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
     fn dec(&self) {
         let num = self.count.get();
         self.count.set(num - 1);
-        if num == self.capacity {
+        if let Some(ref shared) = *self.shared.borrow() {
+            shared.fetch_sub(1, Ordering::Relaxed);
+        }
+        if num == self.capacity.get() {
             self.task.wake();
         }
     }
 
     fn available(&self, cx: &mut task::Context<'_>) -> bool {
-        if self.count.get() < self.capacity {
+        if self.count.get() < self.capacity.get() {
             true
         } else {
             self.task.register(cx.waker());
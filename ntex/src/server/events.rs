@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{fmt, net, pin::Pin, task::Context, task::Poll};
+
+use async_channel::{unbounded, Receiver, Sender};
+
+use crate::util::Stream;
+
+/// Server accept subsystem event
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerEvent {
+    /// Service is bound to the listening socket.
+    ///
+    /// `addr` is `None` for unix domain sockets.
+    ListenerBound {
+        name: String,
+        addr: Option<net::SocketAddr>,
+        token: usize,
+    },
+    /// Connection is accepted by the listener with `token`.
+    ///
+    /// `peer` is `None` for unix domain sockets.
+    ConnectionAccepted {
+        peer: Option<net::SocketAddr>,
+        token: usize,
+    },
+    /// Connection is rejected by accept filter
+    ConnectionRejected { peer: net::SocketAddr, token: usize },
+    /// Worker reached connections limit
+    WorkerSaturated { idx: usize },
+    /// Accept loop stopped accepting connections
+    AcceptPaused,
+    /// Accept loop resumed accepting connections
+    AcceptResumed,
+    /// Worker has died
+    WorkerDied { idx: usize },
+    /// Worker is restarted, `idx` is the index of the died worker
+    WorkerRestarted { idx: usize, new_idx: usize },
+}
+
+/// Subscription for server events.
+///
+/// Events are delivered via unbounded queue, dropping subscription
+/// unsubscribes it from the server.
+pub struct ServerEvents(Receiver<ServerEvent>);
+
+impl ServerEvents {
+    /// Receive next event, returns `None` if server is stopped.
+    pub async fn recv(&self) -> Option<ServerEvent> {
+        self.0.recv().await.ok()
+    }
+
+    /// Receive next event if it is available.
+    pub fn try_recv(&self) -> Option<ServerEvent> {
+        self.0.try_recv().ok()
+    }
+}
+
+impl Stream for ServerEvents {
+    type Item = ServerEvent;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+impl fmt::Debug for ServerEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerEvents")
+            .field("pending", &self.0.len())
+            .finish()
+    }
+}
+
+/// Server events subscribers
+#[derive(Clone, Default)]
+pub(super) struct Subscribers(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    active: AtomicBool,
+    subscribers: Mutex<Vec<Sender<ServerEvent>>>,
+}
+
+impl Subscribers {
+    pub(super) fn subscribe(&self) -> ServerEvents {
+        let (tx, rx) = unbounded();
+        self.0.subscribers.lock().unwrap().push(tx);
+        self.0.active.store(true, Ordering::Release);
+        ServerEvents(rx)
+    }
+
+    /// Check if there are any subscribers
+    pub(super) fn is_active(&self) -> bool {
+        self.0.active.load(Ordering::Acquire)
+    }
+
+    /// Send event to subscribers, event is constructed only
+    /// if there are active subscribers
+    pub(super) fn emit<F>(&self, f: F)
+    where
+        F: FnOnce() -> ServerEvent,
+    {
+        if self.is_active() {
+            let mut subscribers = self.0.subscribers.lock().unwrap();
+            let ev = f();
+            subscribers.retain(|tx| tx.try_send(ev.clone()).is_ok());
+            if subscribers.is_empty() {
+                self.0.active.store(false, Ordering::Release);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Subscribers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscribers")
+            .field("active", &self.is_active())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribers() {
+        let subs = Subscribers::default();
+        subs.emit(|| panic!("no subscribers"));
+        assert!(!subs.is_active());
+
+        let events = subs.subscribe();
+        assert!(subs.is_active());
+        assert!(format!("{:?}", events).contains("ServerEvents"));
+        subs.emit(|| ServerEvent::AcceptPaused);
+        assert_eq!(events.try_recv(), Some(ServerEvent::AcceptPaused));
+        assert_eq!(events.try_recv(), None);
+
+        drop(events);
+        subs.emit(|| ServerEvent::AcceptResumed);
+        assert!(!subs.is_active());
+    }
+}
@@ -0,0 +1,238 @@
+use std::{fmt, net, sync::Arc, sync::Mutex, time::Instant};
+
+use crate::util::HashMap;
+
+/// Accept loop connection filter.
+///
+/// Filter is invoked by accept loop with peer address of accepted tcp
+/// connection before connection is sent to a worker. Rejected connections
+/// get closed with RST, so no tls or http processing is done for them.
+/// Filter is not used for unix domain sockets.
+pub trait AcceptFilter: Send + Sync + 'static {
+    /// Check accepted connection, returns `false` if connection must be rejected.
+    fn accept(&self, peer: &net::SocketAddr) -> bool;
+
+    /// Connection accepted by the filter is closed.
+    ///
+    /// This method is called from worker thread.
+    fn closed(&self, _peer: &net::SocketAddr) {}
+}
+
+/// Notifies filter about connection close
+pub(super) struct FilterGuard {
+    peer: net::SocketAddr,
+    filter: Arc<dyn AcceptFilter>,
+}
+
+impl FilterGuard {
+    pub(super) fn new(filter: Arc<dyn AcceptFilter>, peer: net::SocketAddr) -> Self {
+        FilterGuard { peer, filter }
+    }
+}
+
+impl Drop for FilterGuard {
+    fn drop(&mut self) {
+        self.filter.closed(&self.peer)
+    }
+}
+
+impl fmt::Debug for FilterGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterGuard")
+            .field("peer", &self.peer)
+            .finish()
+    }
+}
+
+/// Number of accepted connections between idle entries cleanups
+const CLEANUP_INTERVAL: usize = 1024;
+
+/// Per source ip connection limits.
+///
+/// Limiter supports connections rate limiting with token bucket and
+/// max number of concurrent connections per ip address.
+///
+/// ```rust
+/// use ntex::server::{self, IpLimiter};
+///
+/// let builder = server::build().accept_filter(
+///     IpLimiter::new()
+///         // 10 new connections per second with bursts up to 20 connections
+///         .rate(20, 10)
+///         .max_connections(100),
+/// );
+/// ```
+pub struct IpLimiter {
+    rate: Option<(f64, f64)>,
+    max_conns: Option<usize>,
+    inner: Mutex<LimiterState>,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    accepted: usize,
+    items: HashMap<net::IpAddr, IpState>,
+}
+
+struct IpState {
+    tokens: f64,
+    updated: Instant,
+    conns: usize,
+}
+
+impl Default for IpLimiter {
+    fn default() -> Self {
+        IpLimiter {
+            rate: None,
+            max_conns: None,
+            inner: Mutex::new(LimiterState::default()),
+        }
+    }
+}
+
+impl IpLimiter {
+    /// Create limiter without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set connections rate limit.
+    ///
+    /// Every ip address gets bucket of size `burst`, bucket is refilled with
+    /// `per_second` tokens per second. Each new connection takes one token.
+    pub fn rate(mut self, burst: u32, per_second: u32) -> Self {
+        self.rate = Some((burst as f64, per_second as f64));
+        self
+    }
+
+    /// Set max number of concurrent connections per ip address.
+    pub fn max_connections(mut self, num: usize) -> Self {
+        self.max_conns = Some(num);
+        self
+    }
+
+    /// Number of tracked ip addresses.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().items.len()
+    }
+
+    /// Check if limiter does not track any ip addresses.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Refill bucket, returns `true` if ip state is idle
+    fn refill(&self, st: &mut IpState, now: Instant) -> bool {
+        if let Some((burst, per_second)) = self.rate {
+            let elapsed = now.duration_since(st.updated).as_secs_f64();
+            st.tokens = (st.tokens + elapsed * per_second).min(burst);
+            st.updated = now;
+            st.conns == 0 && st.tokens >= burst
+        } else {
+            st.conns == 0
+        }
+    }
+}
+
+impl AcceptFilter for IpLimiter {
+    fn accept(&self, peer: &net::SocketAddr) -> bool {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+
+        // remove idle entries
+        inner.accepted += 1;
+        if inner.accepted % CLEANUP_INTERVAL == 0 {
+            inner.items.retain(|_, st| !self.refill(st, now));
+        }
+
+        let burst = self.rate.map(|(burst, _)| burst).unwrap_or(0.0);
+        let st = inner.items.entry(peer.ip()).or_insert_with(|| IpState {
+            tokens: burst,
+            updated: now,
+            conns: 0,
+        });
+        self.refill(st, now);
+
+        if self.max_conns.map(|max| st.conns >= max).unwrap_or(false) {
+            log::trace!("Max connections limit is reached for {}", peer.ip());
+            return false;
+        }
+        if self.rate.is_some() {
+            if st.tokens < 1.0 {
+                log::trace!("Connections rate limit is reached for {}", peer.ip());
+                return false;
+            }
+            st.tokens -= 1.0;
+        }
+        st.conns += 1;
+        true
+    }
+
+    fn closed(&self, peer: &net::SocketAddr) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(st) = inner.items.get_mut(&peer.ip()) {
+            st.conns = st.conns.saturating_sub(1);
+            if self.refill(st, Instant::now()) {
+                inner.items.remove(&peer.ip());
+            }
+        }
+    }
+}
+
+impl fmt::Debug for IpLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IpLimiter")
+            .field("rate", &self.rate)
+            .field("max_connections", &self.max_conns)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_connections() {
+        let limiter = Arc::new(IpLimiter::new().max_connections(2));
+        let peer1: net::SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let peer2: net::SocketAddr = "127.0.0.2:1000".parse().unwrap();
+
+        assert!(limiter.accept(&peer1));
+        assert!(limiter.accept(&peer1));
+        assert!(!limiter.accept(&peer1));
+        assert!(limiter.accept(&peer2));
+        assert_eq!(limiter.len(), 2);
+
+        let guard = FilterGuard::new(limiter.clone(), peer1);
+        assert!(format!("{:?}", guard).contains("127.0.0.1"));
+        drop(guard);
+        assert!(limiter.accept(&peer1));
+
+        // idle entries are removed
+        limiter.closed(&peer2);
+        assert_eq!(limiter.len(), 1);
+        assert!(format!("{:?}", limiter).contains("IpLimiter"));
+    }
+
+    #[test]
+    fn test_rate() {
+        let limiter = IpLimiter::new().rate(2, 1);
+        let peer: net::SocketAddr = "127.0.0.1:1000".parse().unwrap();
+
+        assert!(limiter.accept(&peer));
+        assert!(limiter.accept(&peer));
+        assert!(!limiter.accept(&peer));
+
+        // entry is kept until bucket is full
+        limiter.closed(&peer);
+        limiter.closed(&peer);
+        assert!(!limiter.is_empty());
+
+        // bucket is refilled
+        let limiter = IpLimiter::new().rate(1, 1000);
+        assert!(limiter.accept(&peer));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(limiter.accept(&peer));
+    }
+}
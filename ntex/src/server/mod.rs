@@ -1,5 +1,5 @@
 //! General purpose tcp server
-use std::{future::Future, io, pin::Pin, task::Context, task::Poll};
+use std::{fmt, future::Future, io, net, pin::Pin, task::Context, task::Poll};
 
 use async_channel::Sender;
 use async_oneshot as oneshot;
@@ -8,6 +8,8 @@ mod accept;
 mod builder;
 mod config;
 mod counter;
+mod events;
+mod filter;
 mod service;
 mod socket;
 mod test;
@@ -16,6 +18,9 @@ mod worker;
 #[cfg(feature = "openssl")]
 pub use ntex_tls::openssl;
 
+#[cfg(feature = "acme")]
+pub mod acme;
+
 #[cfg(feature = "rustls")]
 pub use ntex_tls::rustls;
 
@@ -24,8 +29,18 @@ pub use ntex_tls::max_concurrent_ssl_accept;
 pub(crate) use self::builder::create_tcp_listener;
 pub use self::builder::ServerBuilder;
 pub use self::config::{Config, ServiceConfig, ServiceRuntime};
+pub use self::events::{ServerEvent, ServerEvents};
+pub use self::filter::{AcceptFilter, IpLimiter};
 pub use self::test::{build_test_server, test_server, TestServer};
 
+use self::events::Subscribers;
+use self::service::{Factory, InternalServiceFactory};
+#[cfg(test)]
+pub(crate) use self::worker::set_stopping;
+use self::worker::WorkerLimits;
+use crate::rt::{ShutdownHandle, ShutdownPhase};
+use crate::{io::Io, service::ServiceFactory};
+
 #[non_exhaustive]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 /// Server readiness status
@@ -35,6 +50,42 @@ pub enum ServerStatus {
     WorkerFailed,
 }
 
+/// Worker selection policy for accepted connections
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Connections are distributed between available workers in turn
+    RoundRobin,
+    /// Connection is sent to available worker with least number of connections
+    LeastConnections,
+    /// Connection is sent to one of two randomly selected available workers,
+    /// worker with less number of connections is used
+    RandomTwoChoices,
+}
+
+/// Action for accepted connection if pending connections queue is full
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowAction {
+    /// Stop accepting connections until one of the workers become available
+    Wait,
+    /// Reset connection
+    Reset,
+}
+
+/// Worker connection counters and limits
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// Worker index
+    pub idx: usize,
+    /// Number of opened connections
+    pub connections: usize,
+    /// Max number of concurrent connections
+    pub max_connections: usize,
+    /// Number of in-flight ssl handshakes
+    pub ssl_handshakes: usize,
+    /// Max number of concurrent ssl handshakes
+    pub max_ssl_handshakes: usize,
+}
+
 /// Socket id token
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub(self) struct Token(usize);
@@ -52,6 +103,14 @@ pub fn build() -> ServerBuilder {
     ServerBuilder::default()
 }
 
+/// Check if worker of the current thread is stopping.
+///
+/// Returns `true` while worker gracefully shuts down and waits
+/// for opened connections to complete.
+pub fn is_stopping() -> bool {
+    worker::is_stopping()
+}
+
 /// Ssl error combinded with service error.
 #[derive(Debug)]
 pub enum SslError<E> {
@@ -72,15 +131,46 @@ enum ServerCommand {
     },
     /// Notify of server stop
     Notify(oneshot::Sender<()>),
+    /// Start new service on running server
+    Bind {
+        name: String,
+        addrs: Vec<net::SocketAddr>,
+        factory: BindFactory,
+        completion: oneshot::Sender<io::Result<()>>,
+    },
+    /// Stop service on running server
+    Unbind {
+        name: String,
+        completion: oneshot::Sender<io::Result<()>>,
+    },
+    /// Update workers connection limits
+    Limits(WorkerLimits, oneshot::Sender<()>),
+    /// Get workers connection counters
+    Stats(oneshot::Sender<Vec<WorkerStats>>),
+}
+
+/// Creates service factory for a dynamically bound socket
+struct BindFactory(
+    Box<dyn Fn(Token, net::SocketAddr) -> Box<dyn InternalServiceFactory> + Send>,
+);
+
+impl fmt::Debug for BindFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BindFactory").finish()
+    }
 }
 
 /// Server controller
 #[derive(Debug)]
-pub struct Server(Sender<ServerCommand>, Option<oneshot::Receiver<()>>);
+pub struct Server(
+    Sender<ServerCommand>,
+    Option<oneshot::Receiver<()>>,
+    Subscribers,
+);
 
 impl Server {
     fn new(tx: Sender<ServerCommand>) -> Self {
-        Server(tx, None)
+        Server(tx, None, Subscribers::default())
     }
 
     /// Start server building process
@@ -130,11 +220,128 @@ impl Server {
             let _ = rx.await;
         }
     }
+
+    /// Add new service to the running server.
+    ///
+    /// Server binds to the address, starts service in all workers
+    /// and then starts accepting connections. Existing workers
+    /// are not restarted.
+    pub fn bind_dynamic<F, U, N, R>(
+        &self,
+        name: N,
+        addr: U,
+        factory: F,
+    ) -> impl Future<Output = io::Result<()>>
+    where
+        U: net::ToSocketAddrs,
+        N: AsRef<str>,
+        F: Fn(Config) -> R + Send + Clone + 'static,
+        R: ServiceFactory<Io>,
+    {
+        let (tx, rx) = oneshot::oneshot();
+        let name = name.as_ref().to_string();
+        let result = addr.to_socket_addrs().map(|addrs| {
+            let srv_name = name.clone();
+            let factory = BindFactory(Box::new(move |token, addr| {
+                Factory::create(srv_name.clone(), token, factory.clone(), addr)
+            }));
+            let _ = self.0.try_send(ServerCommand::Bind {
+                name,
+                factory,
+                addrs: addrs.collect(),
+                completion: tx,
+            });
+        });
+        async move {
+            result?;
+            rx.await.unwrap_or_else(|_| Err(stopped()))
+        }
+    }
+
+    /// Stop accepting connections for the service.
+    ///
+    /// All listeners registered with the service name get closed.
+    /// Opened connections remain active.
+    pub fn unbind<N: AsRef<str>>(&self, name: N) -> impl Future<Output = io::Result<()>> {
+        let (tx, rx) = oneshot::oneshot();
+        let _ = self.0.try_send(ServerCommand::Unbind {
+            name: name.as_ref().to_string(),
+            completion: tx,
+        });
+        async move { rx.await.unwrap_or_else(|_| Err(stopped())) }
+    }
+
+    /// Change maximum per-worker number of concurrent connections.
+    ///
+    /// New limit is applied by all running workers and by workers
+    /// started after failure.
+    pub fn set_maxconn(&self, num: usize) -> impl Future<Output = ()> {
+        self.set_limits(WorkerLimits {
+            maxconn: Some(num),
+            maxconnrate: None,
+        })
+    }
+
+    /// Change maximum per-worker number of concurrent ssl handshakes.
+    ///
+    /// New limit is applied by all running workers and by workers
+    /// started after failure.
+    pub fn set_maxconnrate(&self, num: usize) -> impl Future<Output = ()> {
+        self.set_limits(WorkerLimits {
+            maxconn: None,
+            maxconnrate: Some(num),
+        })
+    }
+
+    fn set_limits(&self, limits: WorkerLimits) -> impl Future<Output = ()> {
+        let (tx, rx) = oneshot::oneshot();
+        let _ = self.0.try_send(ServerCommand::Limits(limits, tx));
+        async move {
+            let _ = rx.await;
+        }
+    }
+
+    /// Get connection counters and limits of running workers.
+    pub fn worker_stats(&self) -> impl Future<Output = Vec<WorkerStats>> {
+        let (tx, rx) = oneshot::oneshot();
+        let _ = self.0.try_send(ServerCommand::Stats(tx));
+        async move { rx.await.unwrap_or_default() }
+    }
+
+    /// Subscribe to server accept events.
+    ///
+    /// Use `ServerBuilder::events()` for observing events emitted
+    /// during server startup.
+    pub fn events(&self) -> ServerEvents {
+        self.2.subscribe()
+    }
+
+    fn emit<F>(&self, f: F)
+    where
+        F: FnOnce() -> ServerEvent,
+    {
+        self.2.emit(f)
+    }
+
+    /// Register server in system shutdown handle.
+    ///
+    /// Server stops accepting connections at `StopAccepting` phase
+    /// and gracefully stops workers at `Drain` phase.
+    pub fn register_shutdown(&self, handle: &ShutdownHandle) {
+        let srv = self.clone();
+        handle.on_shutdown(ShutdownPhase::StopAccepting, move || srv.pause());
+        let srv = self.clone();
+        handle.on_shutdown(ShutdownPhase::Drain, move || srv.stop(true));
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "Server is stopped")
 }
 
 impl Clone for Server {
     fn clone(&self) -> Self {
-        Self(self.0.clone(), None)
+        Self(self.0.clone(), None, self.2.clone())
     }
 }
 
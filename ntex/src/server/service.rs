@@ -1,19 +1,19 @@
-use std::convert::TryInto;
-use std::{future::Future, net::SocketAddr, pin::Pin, task::Context, task::Poll};
+use std::{cell::Cell, cell::RefCell, convert::TryInto, future::Future, mem};
+use std::{net::SocketAddr, pin::Pin, rc::Rc, task::Context, task::Poll};
 
-use log::error;
+use log::{error, info};
 
-use crate::io::Io;
+use crate::io::{Io, IoRef};
 use crate::service::{Service, ServiceFactory};
-use crate::util::{Pool, PoolId, Ready};
+use crate::util::{HashMap, Pool, PoolId, Ready};
 use crate::{rt::spawn, time::Millis};
 
-use super::{counter::CounterGuard, socket::Stream, Config, Token};
+use super::{counter::CounterGuard, filter::FilterGuard, socket::Stream, Config, Token};
 
 /// Server message
 pub(super) enum ServerMessage {
     /// New stream
-    Connect(Stream),
+    Connect(Stream, Option<FilterGuard>),
     /// Gracefull shutdown in millis
     Shutdown(Millis),
     /// Force shutdown
@@ -48,6 +48,14 @@ pub(super) type BoxedServerService = Box<
 pub(super) struct StreamService<T> {
     service: T,
     pool: Pool,
+    conns: Rc<Connections>,
+}
+
+/// In-flight connections, used for force shutdown
+#[derive(Default)]
+struct Connections {
+    next: Cell<usize>,
+    items: RefCell<HashMap<usize, IoRef>>,
 }
 
 impl<T> StreamService<T> {
@@ -55,6 +63,7 @@ impl<T> StreamService<T> {
         StreamService {
             service,
             pool: pid.pool(),
+            conns: Rc::new(Connections::default()),
         }
     }
 }
@@ -87,7 +96,7 @@ where
 
     fn call(&self, (guard, req): (Option<CounterGuard>, ServerMessage)) -> Self::Future {
         match req {
-            ServerMessage::Connect(stream) => {
+            ServerMessage::Connect(stream, filter) => {
                 let stream = stream.try_into().map_err(|e| {
                     error!("Cannot convert to an async io stream: {}", e);
                 });
@@ -95,17 +104,35 @@ where
                 if let Ok(stream) = stream {
                     let stream: Io<_> = stream;
                     stream.set_memory_pool(self.pool.pool_ref());
+
+                    let id = self.conns.next.get();
+                    self.conns.next.set(id.wrapping_add(1));
+                    self.conns.items.borrow_mut().insert(id, stream.get_ref());
+
+                    let conns = self.conns.clone();
                     let f = self.service.call(stream);
                     spawn(async move {
                         let _ = f.await;
+                        conns.items.borrow_mut().remove(&id);
                         drop(guard);
+                        drop(filter);
                     });
                     Ready::Ok(())
                 } else {
                     Ready::Err(())
                 }
             }
-            _ => Ready::Ok(()),
+            ServerMessage::ForceShutdown => {
+                let items = mem::take(&mut *self.conns.items.borrow_mut());
+                if !items.is_empty() {
+                    info!("Force closing {} connections", items.len());
+                }
+                for io in items.values() {
+                    io.force_close();
+                }
+                Ready::Ok(())
+            }
+            ServerMessage::Shutdown(_) => Ready::Ok(()),
         }
     }
 }
@@ -36,6 +36,17 @@ pub(crate) enum SocketAddr {
     Uds(std::os::unix::net::SocketAddr),
 }
 
+impl SocketAddr {
+    /// Tcp socket address
+    pub(crate) fn tcp(&self) -> Option<net::SocketAddr> {
+        match self {
+            SocketAddr::Tcp(addr) => Some(*addr),
+            #[cfg(unix)]
+            SocketAddr::Uds(_) => None,
+        }
+    }
+}
+
 impl fmt::Display for SocketAddr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -140,6 +151,17 @@ pub enum Stream {
     Uds(std::os::unix::net::UnixStream),
 }
 
+impl Stream {
+    /// Peer address of tcp stream
+    pub(crate) fn peer_addr(&self) -> Option<net::SocketAddr> {
+        match self {
+            Stream::Tcp(stream) => stream.peer_addr().ok(),
+            #[cfg(unix)]
+            Stream::Uds(_) => None,
+        }
+    }
+}
+
 impl TryFrom<Stream> for Io {
     type Error = io::Error;
 
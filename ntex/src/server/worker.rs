@@ -1,16 +1,20 @@
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::{future::Future, pin::Pin, sync::Arc, task::Context, task::Poll};
+use std::{cell::Cell, collections::VecDeque, future::Future, pin::Pin, sync::Arc};
+use std::{fmt, io, task::Context, task::Poll};
 
 use async_channel::{unbounded, Receiver, Sender};
 use async_oneshot as oneshot;
 
 use crate::rt::{spawn, Arbiter};
-use crate::time::{sleep, Millis, Sleep};
-use crate::util::{join_all, ready, Stream as FutStream};
+use crate::service::Service;
+use crate::time::{interval, sleep, Millis, Sleep};
+use crate::util::{join_all, ready, PoolId, Ready, Stream as FutStream};
 
 use super::accept::{AcceptNotify, Command};
+use super::counter::{Counter, CounterGuard};
+use super::filter::FilterGuard;
 use super::service::{BoxedServerService, InternalServiceFactory, ServerMessage};
-use super::{counter::Counter, socket::Stream, Token};
+use super::{socket::Stream, Token, WorkerStats};
 
 #[derive(Debug)]
 pub(super) struct WorkerCommand(Connection);
@@ -23,32 +27,70 @@ pub(super) struct StopCommand {
     result: oneshot::Sender<bool>,
 }
 
+/// Add or remove worker services, update worker limits
+pub(super) enum ServicesCommand {
+    Add(Box<dyn InternalServiceFactory>, oneshot::Sender<bool>),
+    Remove(Vec<Token>),
+    Limits(WorkerLimits),
+    Stats(oneshot::Sender<WorkerStats>),
+}
+
+impl fmt::Debug for ServicesCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServicesCommand::Add(_, _) => f.debug_tuple("ServicesCommand::Add").finish(),
+            ServicesCommand::Remove(tokens) => f
+                .debug_tuple("ServicesCommand::Remove")
+                .field(tokens)
+                .finish(),
+            ServicesCommand::Limits(limits) => f
+                .debug_tuple("ServicesCommand::Limits")
+                .field(limits)
+                .finish(),
+            ServicesCommand::Stats(_) => f.debug_tuple("ServicesCommand::Stats").finish(),
+        }
+    }
+}
+
+type ServicesFuture =
+    Pin<Box<dyn Future<Output = Result<Vec<(Token, BoxedServerService)>, ()>>>>;
+
 #[derive(Debug)]
 pub(super) struct Connection {
     pub(super) io: Stream,
     pub(super) token: Token,
+    pub(super) filter: Option<FilterGuard>,
+}
+
+/// Per-worker connection limits, `None` keeps current value
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct WorkerLimits {
+    pub(super) maxconn: Option<usize>,
+    pub(super) maxconnrate: Option<usize>,
 }
 
 const STOP_TIMEOUT: Millis = Millis::ONE_SEC;
-static MAX_CONNS: AtomicUsize = AtomicUsize::new(25600);
+const CACHE_SHRINK_INTERVAL: Millis = Millis(10_000);
 
-/// Sets the maximum per-worker number of concurrent connections.
-///
-/// All socket listeners will stop accepting connections when this limit is
-/// reached for each worker.
-///
-/// By default max connections is set to a 25k per worker.
-pub(super) fn max_concurrent_connections(num: usize) {
-    MAX_CONNS.store(num, Ordering::Relaxed);
-}
+/// Default maximum per-worker number of concurrent connections
+pub(super) const MAX_CONNS: usize = 25600;
 
 pub(super) fn num_connections() -> usize {
     MAX_CONNS_COUNTER.with(|conns| conns.total())
 }
 
+/// Check if worker of the current thread is stopping
+pub(super) fn is_stopping() -> bool {
+    STOPPING.with(|st| st.get())
+}
+
+pub(crate) fn set_stopping(val: bool) {
+    STOPPING.with(|st| st.set(val))
+}
+
 thread_local! {
-    static MAX_CONNS_COUNTER: Counter =
-        Counter::new(MAX_CONNS.load(Ordering::Relaxed));
+    static MAX_CONNS_COUNTER: Counter = Counter::new(MAX_CONNS);
+    static STOPPING: Cell<bool> = Cell::new(false);
 }
 
 #[derive(Clone, Debug)]
@@ -56,6 +98,7 @@ pub(super) struct WorkerClient {
     pub(super) idx: usize,
     tx1: Sender<WorkerCommand>,
     tx2: Sender<StopCommand>,
+    tx3: Sender<ServicesCommand>,
     avail: WorkerAvailability,
 }
 
@@ -64,12 +107,14 @@ impl WorkerClient {
         idx: usize,
         tx1: Sender<WorkerCommand>,
         tx2: Sender<StopCommand>,
+        tx3: Sender<ServicesCommand>,
         avail: WorkerAvailability,
     ) -> Self {
         WorkerClient {
             idx,
             tx1,
             tx2,
+            tx3,
             avail,
         }
     }
@@ -84,24 +129,61 @@ impl WorkerClient {
         self.avail.available()
     }
 
+    /// Number of connections processed by worker
+    pub(super) fn connections(&self) -> usize {
+        self.avail.connections()
+    }
+
     pub(super) fn stop(&self, graceful: bool) -> oneshot::Receiver<bool> {
         let (result, rx) = oneshot::oneshot();
         let _ = self.tx2.try_send(StopCommand { graceful, result });
         rx
     }
+
+    /// Start new service, returns `true` if service is started
+    pub(super) fn add_service(
+        &self,
+        factory: Box<dyn InternalServiceFactory>,
+    ) -> oneshot::Receiver<bool> {
+        let (tx, rx) = oneshot::oneshot();
+        let _ = self.tx3.try_send(ServicesCommand::Add(factory, tx));
+        rx
+    }
+
+    /// Stop processing connections for services
+    pub(super) fn remove_services(&self, tokens: Vec<Token>) {
+        let _ = self.tx3.try_send(ServicesCommand::Remove(tokens));
+    }
+
+    /// Update worker connection limits
+    pub(super) fn set_limits(&self, limits: WorkerLimits) {
+        let _ = self.tx3.try_send(ServicesCommand::Limits(limits));
+    }
+
+    /// Get worker connection counters, returns `None` if worker is stopped
+    pub(super) fn stats(&self) -> impl Future<Output = Option<WorkerStats>> {
+        let (tx, rx) = oneshot::oneshot();
+        let _ = self.tx3.try_send(ServicesCommand::Stats(tx));
+        let idx = self.idx;
+        async move { rx.await.ok().map(|stats| WorkerStats { idx, ..stats }) }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub(super) struct WorkerAvailability {
+    idx: usize,
     notify: AcceptNotify,
     available: Arc<AtomicBool>,
+    connections: Arc<AtomicUsize>,
 }
 
 impl WorkerAvailability {
-    pub(super) fn new(notify: AcceptNotify) -> Self {
+    pub(super) fn new(idx: usize, notify: AcceptNotify) -> Self {
         WorkerAvailability {
+            idx,
             notify,
             available: Arc::new(AtomicBool::new(false)),
+            connections: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -109,12 +191,28 @@ impl WorkerAvailability {
         self.available.load(Ordering::Acquire)
     }
 
+    pub(super) fn connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Shared connections counter
+    pub(super) fn counter(&self) -> Arc<AtomicUsize> {
+        self.connections.clone()
+    }
+
     pub(super) fn set(&self, val: bool) {
         let old = self.available.swap(val, Ordering::Release);
         if !old && val {
             self.notify.send(Command::WorkerAvailable)
         }
     }
+
+    /// Worker is unavailable because services are not ready
+    pub(super) fn saturated(&self) {
+        if self.available.swap(false, Ordering::Release) {
+            self.notify.send(Command::WorkerSaturated(self.idx))
+        }
+    }
 }
 
 /// Service worker
@@ -124,7 +222,9 @@ impl WorkerAvailability {
 pub(super) struct Worker {
     rx: Receiver<WorkerCommand>,
     rx2: Receiver<StopCommand>,
+    rx3: Receiver<ServicesCommand>,
     services: Vec<WorkerService>,
+    pending: VecDeque<(usize, ServicesFuture, oneshot::Sender<bool>)>,
     availability: WorkerAvailability,
     conns: Counter,
     factories: Vec<Box<dyn InternalServiceFactory>>,
@@ -155,54 +255,126 @@ enum WorkerServiceStatus {
     Stopped,
 }
 
+/// Worker exit notification.
+///
+/// Resolves with error if worker future is dropped before completion,
+/// i.e. worker panicked.
+pub(super) type WorkerExit = oneshot::Receiver<()>;
+
+/// Notifies server about worker completion
+struct ExitGuard(Option<oneshot::Sender<()>>);
+
+impl ExitGuard {
+    fn completed(mut self) {
+        if let Some(mut tx) = self.0.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        if self.0.is_some() && std::thread::panicking() {
+            error!("Worker has panicked, stopping worker arbiter");
+            Arbiter::current().stop();
+        }
+    }
+}
+
 impl Worker {
     pub(super) fn start(
         idx: usize,
+        name: Option<String>,
+        core: Option<usize>,
         factories: Vec<Box<dyn InternalServiceFactory>>,
         availability: WorkerAvailability,
+        limits: WorkerLimits,
         shutdown_timeout: Millis,
-    ) -> WorkerClient {
+    ) -> (WorkerClient, WorkerExit) {
         let (tx1, rx1) = unbounded();
         let (tx2, rx2) = unbounded();
+        let (tx3, rx3) = unbounded();
+        let (exit_tx, exit) = oneshot::oneshot();
         let avail = availability.clone();
 
-        Arbiter::default().exec_fn(move || {
+        let arb = if let Some(name) = name {
+            Arbiter::with_name(name)
+        } else {
+            Arbiter::default()
+        };
+        arb.exec_fn(move || {
+            if let Some(core) = core {
+                if let Err(e) = set_affinity(core) {
+                    error!("Cannot pin worker {} to cpu core {}: {}", idx, core, e);
+                }
+            }
+
             let _ = spawn(async move {
-                match Worker::create(rx1, rx2, factories, availability, shutdown_timeout)
-                    .await
+                let guard = ExitGuard(Some(exit_tx));
+                match Worker::create(
+                    rx1,
+                    rx2,
+                    rx3,
+                    factories,
+                    availability,
+                    limits,
+                    shutdown_timeout,
+                )
+                .await
                 {
                     Ok(wrk) => {
-                        let _ = spawn(wrk);
+                        let _ = spawn(async move {
+                            wrk.await;
+                            guard.completed();
+                        });
+                        // release io buffers that stayed idle in cache
+                        let _ = spawn(async move {
+                            let interval = interval(CACHE_SHRINK_INTERVAL);
+                            loop {
+                                interval.tick().await;
+                                PoolId::shrink_cache_all();
+                            }
+                        });
                     }
                     Err(e) => {
                         error!("Cannot start worker: {:?}", e);
+                        guard.completed();
                         Arbiter::current().stop();
                     }
                 }
             });
         });
 
-        WorkerClient::new(idx, tx1, tx2, avail)
+        (WorkerClient::new(idx, tx1, tx2, tx3, avail), exit)
     }
 
     async fn create(
         rx: Receiver<WorkerCommand>,
         rx2: Receiver<StopCommand>,
+        rx3: Receiver<ServicesCommand>,
         factories: Vec<Box<dyn InternalServiceFactory>>,
         availability: WorkerAvailability,
+        limits: WorkerLimits,
         shutdown_timeout: Millis,
     ) -> Result<Worker, ()> {
         availability.set(false);
-        let mut wrk = MAX_CONNS_COUNTER.with(move |conns| Worker {
-            rx,
-            rx2,
-            availability,
-            factories,
-            shutdown_timeout,
-            services: Vec::new(),
-            conns: conns.priv_clone(),
-            state: WorkerState::Unavailable,
+        set_stopping(false);
+        let mut wrk = MAX_CONNS_COUNTER.with(move |conns| {
+            conns.set_shared(availability.counter());
+            Worker {
+                rx,
+                rx2,
+                rx3,
+                availability,
+                factories,
+                shutdown_timeout,
+                services: Vec::new(),
+                pending: VecDeque::new(),
+                conns: conns.priv_clone(),
+                state: WorkerState::Unavailable,
+            }
         });
+        wrk.set_limits(limits);
 
         let mut fut: Vec<Pin<Box<dyn Future<Output = _>>>> = Vec::new();
         for (idx, factory) in wrk.factories.iter().enumerate() {
@@ -223,12 +395,7 @@ impl Worker {
             Ok(services) => {
                 for item in services {
                     for (factory, token, service) in item {
-                        assert_eq!(token.0, wrk.services.len());
-                        wrk.services.push(WorkerService {
-                            factory,
-                            service,
-                            status: WorkerServiceStatus::Unavailable,
-                        });
+                        wrk.insert_service(factory, token, service);
                     }
                 }
                 Ok(wrk)
@@ -237,10 +404,110 @@ impl Worker {
         }
     }
 
+    fn insert_service(
+        &mut self,
+        factory: usize,
+        token: Token,
+        service: BoxedServerService,
+    ) {
+        // tokens of services that failed to start are not used
+        while self.services.len() < token.0 {
+            self.services.push(WorkerService {
+                factory,
+                service: Box::new(Unavailable),
+                status: WorkerServiceStatus::Stopped,
+            });
+        }
+        assert_eq!(token.0, self.services.len());
+        self.services.push(WorkerService {
+            factory,
+            service,
+            status: WorkerServiceStatus::Unavailable,
+        });
+    }
+
+    fn set_limits(&self, limits: WorkerLimits) {
+        if let Some(num) = limits.maxconn {
+            self.conns.set_capacity(num);
+        }
+        if let Some(num) = limits.maxconnrate {
+            ntex_tls::max_concurrent_ssl_accept(num);
+        }
+    }
+
+    fn update_services(&mut self, cx: &mut Context<'_>) {
+        while let Poll::Ready(Some(cmd)) = Pin::new(&mut self.rx3).poll_next(cx) {
+            match cmd {
+                ServicesCommand::Add(factory, tx) => {
+                    let fut = factory.create();
+                    self.pending.push_back((self.factories.len(), fut, tx));
+                    self.factories.push(factory);
+                }
+                ServicesCommand::Remove(tokens) => {
+                    for token in tokens {
+                        if let Some(srv) = self.services.get_mut(token.0) {
+                            if srv.status != WorkerServiceStatus::Stopped {
+                                trace!(
+                                    "Service {:?} is removed",
+                                    self.factories[srv.factory].name(token)
+                                );
+                                // stopping service is force closed on shutdown timeout
+                                srv.status = WorkerServiceStatus::Stopping;
+                            }
+                        }
+                    }
+                }
+                ServicesCommand::Limits(limits) => {
+                    trace!("Update worker limits {:?}", limits);
+                    self.set_limits(limits);
+                }
+                ServicesCommand::Stats(mut tx) => {
+                    let _ = tx.send(WorkerStats {
+                        idx: 0,
+                        connections: self.conns.total(),
+                        max_connections: self.conns.capacity(),
+                        ssl_handshakes: ntex_tls::ssl_accept_handshakes(),
+                        max_ssl_handshakes: ntex_tls::max_ssl_accept_handshakes(),
+                    });
+                }
+            }
+        }
+
+        // services must be inserted in order of tokens
+        while let Some(item) = self.pending.front_mut() {
+            let result = match Pin::new(&mut item.1).poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => break,
+            };
+            let (factory, _, mut tx) = self.pending.pop_front().unwrap();
+            match result {
+                Ok(items) => {
+                    for (token, service) in items {
+                        trace!(
+                            "Service {:?} has been started",
+                            self.factories[factory].name(token)
+                        );
+                        self.insert_service(factory, token, service);
+                    }
+                    let _ = tx.send(true);
+                }
+                Err(_) => {
+                    error!(
+                        "Cannot start {:?} service",
+                        self.factories[factory].name(Token(0))
+                    );
+                    let _ = tx.send(false);
+                }
+            }
+        }
+    }
+
     fn shutdown(&mut self, force: bool) {
         if force {
             self.services.iter_mut().for_each(|srv| {
-                if srv.status == WorkerServiceStatus::Available {
+                if srv.status == WorkerServiceStatus::Available
+                    || srv.status == WorkerServiceStatus::Stopping
+                {
                     srv.status = WorkerServiceStatus::Stopped;
                     let fut = srv.service.call((None, ServerMessage::ForceShutdown));
                     spawn(async move {
@@ -313,11 +580,7 @@ impl Worker {
 enum WorkerState {
     Available,
     Unavailable,
-    Restarting(
-        usize,
-        Token,
-        Pin<Box<dyn Future<Output = Result<Vec<(Token, BoxedServerService)>, ()>>>>,
-    ),
+    Restarting(usize, Token, ServicesFuture),
     Shutdown(Sleep, Sleep, Option<oneshot::Sender<bool>>),
 }
 
@@ -333,6 +596,7 @@ impl Future for Worker {
         })) = stop
         {
             self.availability.set(false);
+            set_stopping(true);
             let num = num_connections();
             if num == 0 {
                 info!("Shutting down worker, 0 connections");
@@ -360,6 +624,8 @@ impl Future for Worker {
             }
         }
 
+        self.update_services(cx);
+
         match self.state {
             WorkerState::Unavailable => {
                 match self.check_readiness(cx) {
@@ -422,6 +688,7 @@ impl Future for Worker {
                 match t2.poll_elapsed(cx) {
                     Poll::Pending => (),
                     Poll::Ready(_) => {
+                        info!("Graceful shutdown timeout, dropping {} connections", num);
                         let _ = tx.take().unwrap().send(false);
                         self.shutdown(true);
                         Arbiter::current().stop();
@@ -445,7 +712,7 @@ impl Future for Worker {
                         Ok(true) => (),
                         Ok(false) => {
                             trace!("Worker is unavailable");
-                            self.availability.set(false);
+                            self.availability.saturated();
                             self.state = WorkerState::Unavailable;
                             return self.poll(cx);
                         }
@@ -477,9 +744,10 @@ impl Future for Worker {
                                 self.factories[srv.factory].name(msg.token)
                             );
                         }
-                        let _ = srv
-                            .service
-                            .call((Some(guard), ServerMessage::Connect(msg.io)));
+                        let _ = srv.service.call((
+                            Some(guard),
+                            ServerMessage::Connect(msg.io, msg.filter),
+                        ));
                     } else {
                         return Poll::Ready(());
                     }
@@ -489,6 +757,54 @@ impl Future for Worker {
     }
 }
 
+/// Pin current thread to cpu core
+#[cfg(target_os = "linux")]
+fn set_affinity(core: usize) -> io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Cpu core index is out of range",
+        ));
+    }
+
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Cpu affinity is not supported",
+    ))
+}
+
+/// Placeholder for services that failed to start
+struct Unavailable;
+
+impl Service<(Option<CounterGuard>, ServerMessage)> for Unavailable {
+    type Response = ();
+    type Error = ();
+    type Future = Ready<(), ()>;
+
+    #[inline]
+    fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn call(&self, _: (Option<CounterGuard>, ServerMessage)) -> Self::Future {
+        Ready::Err(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{Arc, Mutex};
@@ -566,11 +882,12 @@ mod tests {
     async fn basics() {
         let (_tx1, rx1) = unbounded();
         let (tx2, rx2) = unbounded();
+        let (_tx3, rx3) = unbounded();
         let (sync_tx, _sync_rx) = std::sync::mpsc::channel();
         let poll = Arc::new(polling::Poller::new().unwrap());
         let waker = poll.clone();
         let avail =
-            WorkerAvailability::new(AcceptNotify::new(waker.clone(), sync_tx.clone()));
+            WorkerAvailability::new(0, AcceptNotify::new(waker.clone(), sync_tx.clone()));
 
         let st = Arc::new(Mutex::new(St::Pending));
         let counter = Arc::new(Mutex::new(0));
@@ -583,6 +900,7 @@ mod tests {
         let mut worker = Worker::create(
             rx1,
             rx2,
+            rx3,
             vec![Factory::create(
                 "test".to_string(),
                 Token(0),
@@ -590,6 +908,7 @@ mod tests {
                 "127.0.0.1:8080".parse().unwrap(),
             )],
             avail.clone(),
+            WorkerLimits::default(),
             Millis(5_000),
         )
         .await
@@ -639,6 +958,7 @@ mod tests {
 
         let _ = lazy(|cx| Pin::new(&mut worker).poll(cx)).await;
         assert!(!avail.available());
+        assert!(is_stopping());
         drop(g);
         assert!(lazy(|cx| Pin::new(&mut worker).poll(cx)).await.is_ready());
         let _ = rx.await;
@@ -646,7 +966,8 @@ mod tests {
         // force shutdown
         let (_tx1, rx1) = unbounded();
         let (tx2, rx2) = unbounded();
-        let avail = WorkerAvailability::new(AcceptNotify::new(waker, sync_tx.clone()));
+        let (_tx3, rx3) = unbounded();
+        let avail = WorkerAvailability::new(0, AcceptNotify::new(waker, sync_tx.clone()));
         let f = SrvFactory {
             st: st.clone(),
             counter: counter.clone(),
@@ -655,6 +976,7 @@ mod tests {
         let mut worker = Worker::create(
             rx1,
             rx2,
+            rx3,
             vec![Factory::create(
                 "test".to_string(),
                 Token(0),
@@ -662,6 +984,7 @@ mod tests {
                 "127.0.0.1:8080".parse().unwrap(),
             )],
             avail.clone(),
+            WorkerLimits::default(),
             Millis(5_000),
         )
         .await
@@ -673,6 +996,7 @@ mod tests {
         *st.lock().unwrap() = St::Ready;
         let _ = lazy(|cx| Pin::new(&mut worker).poll(cx)).await;
         assert!(avail.available());
+        assert!(!is_stopping());
 
         let (tx, rx) = oneshot::oneshot();
         tx2.try_send(StopCommand {
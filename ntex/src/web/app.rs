@@ -11,13 +11,17 @@ use crate::util::{Extensions, Ready};
 
 use super::app_service::{AppFactory, AppService};
 use super::config::{AppConfig, ServiceConfig};
+use super::error::RenderFn;
+use super::httprequest::HttpRequest;
 use super::request::WebRequest;
 use super::resource::Resource;
 use super::response::WebResponse;
-use super::route::Route;
+use super::rmap::NamedResource;
+use super::route::{Route, RouteTable};
 use super::service::{AppServiceFactory, ServiceFactoryWrapper, WebServiceFactory};
-use super::types::state::{State, StateFactory};
-use super::{DefaultError, ErrorRenderer};
+use super::types::scoped::RequestScope;
+use super::types::state::{State, StateFactory, WithState};
+use super::{DefaultError, ErrorRenderer, HttpResponse};
 
 type HttpNewService<Err: ErrorRenderer> =
     BoxServiceFactory<(), WebRequest<Err>, WebResponse, Err::Container, ()>;
@@ -34,9 +38,30 @@ pub struct App<M, F, Err: ErrorRenderer = DefaultError> {
     state: Vec<Box<dyn StateFactory>>,
     state_factories: Vec<FnStateFactory>,
     external: Vec<ResourceDef>,
+    named: Vec<(&'static str, &'static [&'static str])>,
+    route_hooks: Vec<Box<dyn Fn(&RouteTable)>>,
     extensions: Extensions,
     error_renderer: Err,
     case_insensitive: bool,
+    trailing_slash: TrailingSlash,
+    render_error: Option<RenderFn<Err>>,
+}
+
+/// Trailing slash handling policy.
+///
+/// Policy is applied during resource matching, so guards and
+/// `url_for()` use resource definitions as is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Trailing slash is significant, `/path` and `/path/` are different
+    /// resources. This is default policy.
+    Strict,
+    /// Resource matches path with or without trailing slash.
+    Rewrite,
+    /// Request is redirected with `308 Permanent Redirect` response to
+    /// the path with added or removed trailing slash, if only that path
+    /// matches resource.
+    Redirect,
 }
 
 impl App<Identity, Filter<DefaultError>, DefaultError> {
@@ -50,9 +75,13 @@ impl App<Identity, Filter<DefaultError>, DefaultError> {
             services: Vec::new(),
             default: None,
             external: Vec::new(),
+            named: Vec::new(),
+            route_hooks: Vec::new(),
             extensions: Extensions::new(),
             error_renderer: DefaultError,
             case_insensitive: false,
+            trailing_slash: TrailingSlash::Strict,
+            render_error: None,
         }
     }
 }
@@ -68,13 +97,49 @@ impl<Err: ErrorRenderer> App<Identity, Filter<Err>, Err> {
             services: Vec::new(),
             default: None,
             external: Vec::new(),
+            named: Vec::new(),
+            route_hooks: Vec::new(),
             extensions: Extensions::new(),
             error_renderer: err,
             case_insensitive: false,
+            trailing_slash: TrailingSlash::Strict,
+            render_error: None,
         }
     }
 }
 
+impl<S: 'static> App<Identity, Filter<WithState<S>>, WithState<S>> {
+    /// Create application builder with typed application state.
+    ///
+    /// Application state could be accessed by using `TypedState<S>` extractor,
+    /// state type is checked at compile time. State is also available
+    /// for `State<S>` extractor.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, types::TypedState, App, HttpResponse};
+    ///
+    /// async fn index(st: TypedState<String>) -> HttpResponse {
+    ///     HttpResponse::Ok().body(st.get_ref().clone())
+    /// }
+    ///
+    /// let app = App::with_state("state".to_string())
+    ///     .service(web::resource("/index.html").route(web::get().to(index)));
+    /// ```
+    pub fn with_state(state: S) -> Self {
+        App::with(WithState::new(DefaultError)).state(state)
+    }
+}
+
+impl<S: 'static, Err: ErrorRenderer>
+    App<Identity, Filter<WithState<S, Err>>, WithState<S, Err>>
+{
+    /// Create application builder with typed application state and
+    /// custom error renderer.
+    pub fn with_state_renderer(state: S, err: Err) -> Self {
+        App::with(WithState::new(err)).state(state)
+    }
+}
+
 impl<M, T, Err> App<M, T, Err>
 where
     T: ServiceFactory<
@@ -161,6 +226,16 @@ where
         self
     }
 
+    /// Register request scoped value factory.
+    ///
+    /// Scoped value is constructed lazily, the first time handler uses
+    /// `Scoped<T>` extractor, and is torn down after the response is written.
+    /// See `RequestScope` for details.
+    pub fn request_scope<U: 'static>(mut self, scope: RequestScope<U, Err>) -> Self {
+        self.extensions.insert(scope);
+        self
+    }
+
     /// Run external configuration as part of the application building
     /// process
     ///
@@ -325,6 +400,45 @@ where
         self
     }
 
+    /// Register typed named resource.
+    ///
+    /// Resource name and path parameters of the `NamedResource` type get
+    /// validated against registered resources during application startup.
+    /// Application initialization panics if resource is not registered or
+    /// its path parameters do not match.
+    pub fn named_resource<U: NamedResource>(mut self) -> Self {
+        self.named.push((U::NAME, U::PARAMS));
+        self
+    }
+
+    /// Register route table hook.
+    ///
+    /// Hook get called during application startup with the application
+    /// route table. Route table is also available as application state,
+    /// `HttpRequest::app_state::<RouteTable>()`.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, dev::RouteTable, App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     let app = App::new()
+    ///         .inspect_routes(|routes: &RouteTable| {
+    ///             for route in routes.iter() {
+    ///                 println!("{:?} {}", route.methods(), route.pattern());
+    ///             }
+    ///         })
+    ///         .service(web::resource("/index.html").route(
+    ///             web::get().to(|| async { HttpResponse::Ok() })));
+    /// }
+    /// ```
+    pub fn inspect_routes<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&RouteTable) + 'static,
+    {
+        self.route_hooks.push(Box::new(f));
+        self
+    }
+
     /// Register request filter.
     ///
     /// Filter runs during inbound processing in the request
@@ -380,9 +494,13 @@ where
             services: self.services,
             default: self.default,
             external: self.external,
+            named: self.named,
+            route_hooks: self.route_hooks,
             extensions: self.extensions,
             error_renderer: self.error_renderer,
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error,
         }
     }
 
@@ -421,10 +539,57 @@ where
             services: self.services,
             default: self.default,
             external: self.external,
+            named: self.named,
+            route_hooks: self.route_hooks,
             extensions: self.extensions,
             error_renderer: self.error_renderer,
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error,
+        }
+    }
+
+    /// Registers middleware with custom name.
+    ///
+    /// By default middleware name is a name of the middleware type.
+    pub fn wrap_named<U>(self, name: &'static str, mw: U) -> App<Stack<M, U>, T, Err> {
+        let mut app = self.wrap(mw);
+        app.middleware.set_name(name);
+        app
+    }
+
+    /// Names of registered middlewares in execution order.
+    ///
+    /// Middleware registered last is executed first.
+    ///
+    /// ```rust
+    /// use ntex::web::{middleware, App};
+    ///
+    /// let app = App::new()
+    ///     .wrap(middleware::DefaultHeaders::new())
+    ///     .wrap(middleware::Logger::default());
+    /// assert_eq!(app.middleware_order(), vec!["Logger", "DefaultHeaders"]);
+    /// ```
+    pub fn middleware_order(&self) -> Vec<&'static str>
+    where
+        M: Middlewares,
+    {
+        middleware_order(&self.middleware)
+    }
+
+    /// Check order of registered middlewares.
+    ///
+    /// Logs warning for each known order sensitive pair of middlewares
+    /// registered in problematic order, i.e. if `Compress` middleware
+    /// is executed before `Logger` middleware.
+    pub fn check_middleware_order(self) -> Self
+    where
+        M: Middlewares,
+    {
+        for warning in order_warnings(&self.middleware_order()) {
+            log::warn!("{}", warning);
         }
+        self
     }
 
     /// Use ascii case-insensitive routing.
@@ -434,6 +599,54 @@ where
         self.case_insensitive = true;
         self
     }
+
+    /// Set trailing slash handling policy.
+    ///
+    /// Policy applies to resources registered with the application,
+    /// scopes use their own policy. By default trailing slash is significant.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, App, HttpResponse, TrailingSlash};
+    ///
+    /// fn main() {
+    ///     // "/users/" request is redirected to "/users"
+    ///     let app = App::new()
+    ///         .trailing_slash(TrailingSlash::Redirect)
+    ///         .service(web::resource("/users").to(|| async { HttpResponse::Ok() }));
+    /// }
+    /// ```
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Set error rendering function.
+    ///
+    /// Function is used for errors that are rendered to a response while
+    /// application handles request, i.e. extractor or handler errors. If function
+    /// returns `None` error renders response itself. Scopes could register
+    /// their own function with `Scope::render_error()`, application function
+    /// is used if scope function returns `None`.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     let app = App::new()
+    ///         .render_error(|err: &web::Error, _| {
+    ///             let status = err.as_response_error().status_code();
+    ///             Some(HttpResponse::build(status).body(format!("<h1>{}</h1>", err)))
+    ///         })
+    ///         .service(web::resource("/index.html").to(|| async { "index" }));
+    /// }
+    /// ```
+    pub fn render_error<R>(mut self, f: R) -> Self
+    where
+        R: Fn(&Err::Container, &HttpRequest) -> Option<HttpResponse> + 'static,
+    {
+        self.render_error = Some(Rc::new(f));
+        self
+    }
 }
 
 impl<M, F, Err> App<M, F, Err>
@@ -512,9 +725,13 @@ where
             state_factories: Rc::new(self.state_factories),
             services: Rc::new(RefCell::new(self.services)),
             external: RefCell::new(self.external),
+            named: Rc::new(self.named),
+            route_hooks: Rc::new(self.route_hooks),
             default: self.default,
             extensions: RefCell::new(Some(self.extensions)),
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error,
         };
         map_config(app, move |_| cfg.clone())
     }
@@ -542,9 +759,13 @@ where
             state_factories: Rc::new(self.state_factories),
             services: Rc::new(RefCell::new(self.services)),
             external: RefCell::new(self.external),
+            named: Rc::new(self.named),
+            route_hooks: Rc::new(self.route_hooks),
             default: self.default,
             extensions: RefCell::new(Some(self.extensions)),
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error,
         }
     }
 }
@@ -570,9 +791,13 @@ where
             state_factories: Rc::new(self.state_factories),
             services: Rc::new(RefCell::new(self.services)),
             external: RefCell::new(self.external),
+            named: Rc::new(self.named),
+            route_hooks: Rc::new(self.route_hooks),
             default: self.default,
             extensions: RefCell::new(Some(self.extensions)),
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error,
         }
     }
 }
@@ -580,14 +805,93 @@ where
 pub struct Stack<Inner, Outer> {
     inner: Inner,
     outer: Outer,
+    name: &'static str,
 }
 
 impl<Inner, Outer> Stack<Inner, Outer> {
     pub(super) fn new(inner: Inner, outer: Outer) -> Self {
-        Stack { inner, outer }
+        Stack {
+            inner,
+            outer,
+            name: type_name::<Outer>(),
+        }
+    }
+
+    pub(super) fn set_name(&mut self, name: &'static str) {
+        self.name = name;
+    }
+}
+
+/// Registered middlewares
+pub trait Middlewares {
+    /// Add names of middlewares in registration order
+    fn names(&self, names: &mut Vec<&'static str>);
+}
+
+impl Middlewares for Identity {
+    fn names(&self, _: &mut Vec<&'static str>) {}
+}
+
+impl<Inner: Middlewares, Outer> Middlewares for Stack<Inner, Outer> {
+    fn names(&self, names: &mut Vec<&'static str>) {
+        self.inner.names(names);
+        names.push(self.name);
     }
 }
 
+/// Middlewares names in execution order
+pub(super) fn middleware_order<M: Middlewares>(middleware: &M) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    middleware.names(&mut names);
+    names.reverse();
+    names
+}
+
+/// Short type name, without module path and generic parameters
+fn type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Order sensitive middlewares, first middleware must be executed before second one
+const ORDER_RULES: &[(&str, &str, &str)] = &[
+    (
+        "Logger",
+        "Compress",
+        "logged response time and size do not include compression",
+    ),
+    (
+        "Compress",
+        "ETag",
+        "entity tag is not computed for compressed response",
+    ),
+    (
+        "Tracing",
+        "Logger",
+        "access log is not written within request span",
+    ),
+];
+
+/// Check order of known order sensitive middlewares
+fn order_warnings(order: &[&'static str]) -> Vec<String> {
+    ORDER_RULES
+        .iter()
+        .filter_map(|(first, second, reason)| {
+            let first_idx = order.iter().rposition(|name| name == first)?;
+            let second_idx = order.iter().position(|name| name == second)?;
+            if second_idx < first_idx {
+                Some(format!(
+                    "{} middleware is executed before {}, {}",
+                    second, first, reason
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
 impl<S, Inner, Outer> Transform<S> for Stack<Inner, Outer>
 where
     Inner: Transform<S>,
@@ -780,6 +1084,53 @@ mod tests {
         );
     }
 
+    #[crate::rt_test]
+    async fn test_middleware_order() {
+        use crate::web::middleware::{ETag, Logger, RateLimit};
+
+        let app = App::new()
+            .wrap(DefaultHeaders::new())
+            .wrap_named("Headers", DefaultHeaders::new())
+            .wrap(RateLimit::new(10, std::time::Duration::from_secs(1)))
+            .wrap(Logger::default());
+        assert_eq!(
+            app.middleware_order(),
+            vec!["Logger", "RateLimit", "Headers", "DefaultHeaders"]
+        );
+        assert!(App::new().middleware_order().is_empty());
+
+        let scope = web::scope::<_, DefaultError>("/scope")
+            .wrap(ETag::new())
+            .wrap_named("Headers", DefaultHeaders::new());
+        assert_eq!(scope.middleware_order(), vec!["Headers", "ETag"]);
+
+        let res = web::resource::<_, DefaultError>("/")
+            .wrap(Logger::default())
+            .wrap_named("Headers", DefaultHeaders::new());
+        assert_eq!(res.middleware_order(), vec!["Headers", "Logger"]);
+
+        // order check only logs warnings
+        let app = app.wrap(ETag::new()).check_middleware_order();
+        assert_eq!(app.middleware_order()[0], "ETag");
+    }
+
+    #[test]
+    fn test_order_warnings() {
+        assert!(order_warnings(&[]).is_empty());
+        assert!(order_warnings(&["Logger", "Compress", "ETag"]).is_empty());
+        assert!(order_warnings(&["Tracing", "Logger", "DefaultHeaders"]).is_empty());
+        assert!(order_warnings(&["Compress", "Other"]).is_empty());
+
+        assert_eq!(
+            order_warnings(&["Compress", "Logger"]),
+            vec!["Compress middleware is executed before Logger, logged response time and size do not include compression"]
+        );
+        let warnings = order_warnings(&["ETag", "Logger", "Compress", "Tracing"]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("ETag middleware is executed before Compress"));
+        assert!(warnings[1].starts_with("Logger middleware is executed before Tracing"));
+    }
+
     #[crate::rt_test]
     async fn test_router_wrap() {
         let srv = init_service(
@@ -817,6 +1168,71 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
+    #[crate::rt_test]
+    async fn test_trailing_slash_rewrite() {
+        let srv = init_service(
+            App::new()
+                .trailing_slash(TrailingSlash::Rewrite)
+                .route("/test", web::get().to(|| async { HttpResponse::Ok() }))
+                .route("/dir/", web::get().to(|| async { HttpResponse::Created() })),
+        )
+        .await;
+        let req = TestRequest::with_uri("/test/").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = TestRequest::with_uri("/dir").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let req = TestRequest::with_uri("/test/")
+            .method(Method::POST)
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[crate::rt_test]
+    async fn test_trailing_slash_redirect() {
+        let srv = init_service(
+            App::new()
+                .trailing_slash(TrailingSlash::Redirect)
+                .route("/test", web::get().to(|| async { HttpResponse::Ok() }))
+                .route("/dir/", web::get().to(|| async { HttpResponse::Ok() })),
+        )
+        .await;
+        let req = TestRequest::with_uri("/test").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = TestRequest::with_uri("/test/?q=1").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            HeaderValue::from_static("/test?q=1")
+        );
+
+        let req = TestRequest::with_uri("/dir").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            HeaderValue::from_static("/dir/")
+        );
+
+        // guards are checked
+        let req = TestRequest::with_uri("/dir")
+            .method(Method::POST)
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::with_uri("/unknown/").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
     #[cfg(feature = "url")]
     #[crate::rt_test]
     async fn test_external_resource() {
@@ -840,4 +1256,66 @@ mod tests {
         let body = read_body(resp).await;
         assert_eq!(body, Bytes::from_static(b"https://youtube.com/watch/12345"));
     }
+
+    #[crate::rt_test]
+    async fn test_route_table() {
+        struct Summary(&'static str);
+
+        let routes = Rc::new(RefCell::new(Vec::new()));
+        let routes2 = routes.clone();
+        let srv = init_service(
+            App::new()
+                .inspect_routes(move |table: &RouteTable| {
+                    *routes2.borrow_mut() = table.iter().cloned().collect();
+                })
+                .route(
+                    "/index.html",
+                    web::get().to(|| async { HttpResponse::Ok() }),
+                )
+                .service(
+                    web::scope("/users/").service(
+                        web::resource("/{id}")
+                            .name("user")
+                            .route(
+                                web::get()
+                                    .metadata(Summary("Get user"))
+                                    .to(|_: web::types::Path<u32>| async { "user" }),
+                            )
+                            .route(web::post().to(|_: Bytes| async { HttpResponse::Ok() })),
+                    ),
+                )
+                .route(
+                    "/test",
+                    web::get().to(|req: HttpRequest| async move {
+                        let table = req.app_state::<RouteTable>().unwrap();
+                        HttpResponse::Ok().body(format!("{}", table.len()))
+                    }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/test").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(read_body(resp).await, Bytes::from_static(b"4"));
+
+        let routes = routes.borrow();
+        assert_eq!(routes.len(), 4);
+        assert_eq!(routes[0].pattern(), "/index.html");
+        assert_eq!(routes[0].methods(), &[Method::GET]);
+        assert_eq!(routes[0].extractor(), Some("()"));
+        assert_eq!(routes[1].pattern(), "/users/{id}");
+        assert_eq!(routes[1].resource_name(), Some("user"));
+        assert_eq!(
+            routes[1].extractor(),
+            Some(std::any::type_name::<(web::types::Path<u32>,)>())
+        );
+        assert_eq!(routes[1].responder(), Some("&str"));
+        assert_eq!(
+            routes[1].metadata().get::<Summary>().map(|s| s.0),
+            Some("Get user")
+        );
+        assert_eq!(routes[2].pattern(), "/users/{id}");
+        assert_eq!(routes[2].methods(), &[Method::POST]);
+        assert!(routes[2].metadata().get::<Summary>().is_none());
+    }
 }
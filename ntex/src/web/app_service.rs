@@ -1,21 +1,24 @@
 use std::task::{Context, Poll};
 use std::{cell::RefCell, future::Future, marker::PhantomData, pin::Pin, rc::Rc};
 
+use crate::http::header::LOCATION;
 use crate::http::{Request, Response};
 use crate::router::{Path, ResourceDef, Router};
 use crate::service::boxed::{self, BoxService, BoxServiceFactory};
 use crate::service::{fn_service, PipelineFactory, Service, ServiceFactory, Transform};
 use crate::util::Extensions;
 
+use super::app::TrailingSlash;
 use super::config::AppConfig;
-use super::error::ErrorRenderer;
+use super::error::{self, ErrorRenderer, RenderFn};
 use super::guard::Guard;
 use super::httprequest::{HttpRequest, HttpRequestPool};
 use super::request::WebRequest;
 use super::response::WebResponse;
 use super::rmap::ResourceMap;
+use super::route::RouteTable;
 use super::service::{AppServiceFactory, WebServiceConfig};
-use super::types::state::StateFactory;
+use super::types::{scoped, state::StateFactory};
 
 type Guards = Vec<Box<dyn Guard>>;
 type HttpService<Err: ErrorRenderer> =
@@ -48,7 +51,11 @@ where
     pub(super) services: Rc<RefCell<Vec<Box<dyn AppServiceFactory<Err>>>>>,
     pub(super) default: Option<Rc<HttpNewService<Err>>>,
     pub(super) external: RefCell<Vec<ResourceDef>>,
+    pub(super) named: Rc<Vec<(&'static str, &'static [&'static str])>>,
+    pub(super) route_hooks: Rc<Vec<Box<dyn Fn(&RouteTable)>>>,
     pub(super) case_insensitive: bool,
+    pub(super) trailing_slash: TrailingSlash,
+    pub(super) render_error: Option<RenderFn<Err>>,
 }
 
 impl<T, F, Err> ServiceFactory<Request> for AppFactory<T, F, Err>
@@ -111,6 +118,7 @@ where
         std::mem::take(&mut *self.services.borrow_mut())
             .into_iter()
             .for_each(|mut srv| srv.register(&mut config));
+        let routes = RouteTable::new(config.take_routes());
         let (config, services) = config.into_services();
 
         // resource map
@@ -133,11 +141,23 @@ where
         if self.case_insensitive {
             router.case_insensitive();
         }
+        if self.trailing_slash == TrailingSlash::Rewrite {
+            router.ignore_trailing_slash();
+        }
+        let trailing_slash = self.trailing_slash;
+        let render_error = self.render_error.clone();
 
         // complete ResourceMap tree creation
         let rmap = Rc::new(rmap);
         rmap.finish(rmap.clone());
 
+        // validate typed named resources
+        for (name, params) in self.named.iter() {
+            if let Err(e) = rmap.check_named(name, params) {
+                panic!("{}", e);
+            }
+        }
+
         let filter_fut = self.filter.new_service(());
         let state = self.state.clone();
         let state_factories = self.state_factories.clone();
@@ -146,6 +166,12 @@ where
             .borrow_mut()
             .take()
             .unwrap_or_else(Extensions::new);
+
+        // route table
+        for f in self.route_hooks.iter() {
+            f(&routes);
+        }
+        extensions.insert(routes);
         let middleware = self.middleware.clone();
 
         Box::pin(async move {
@@ -158,6 +184,8 @@ where
             let routing = AppRouting {
                 router: router.finish(),
                 default: Some(default_fut.await?),
+                trailing_slash,
+                render_error,
             };
 
             // main service
@@ -211,7 +239,7 @@ where
 {
     type Response = WebResponse;
     type Error = T::Error;
-    type Future = T::Future;
+    type Future = AppFactoryServiceResponse<T::Future>;
 
     #[inline]
     fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -232,6 +260,7 @@ where
             inner.head = head;
             inner.payload = payload;
             inner.app_state = self.state.clone();
+            inner.resource_ids.clear();
             req
         } else {
             HttpRequest::new(
@@ -244,7 +273,9 @@ where
                 self.pool,
             )
         };
-        self.service.call(WebRequest::new(req))
+        AppFactoryServiceResponse {
+            fut: self.service.call(WebRequest::new(req)),
+        }
     }
 }
 
@@ -261,6 +292,8 @@ where
 struct AppRouting<Err: ErrorRenderer> {
     router: Router<HttpService<Err>, Guards>,
     default: Option<HttpService<Err>>,
+    trailing_slash: TrailingSlash,
+    render_error: Option<RenderFn<Err>>,
 }
 
 impl<Err: ErrorRenderer> Service<WebRequest<Err>> for AppRouting<Err> {
@@ -274,6 +307,10 @@ impl<Err: ErrorRenderer> Service<WebRequest<Err>> for AppRouting<Err> {
     }
 
     fn call(&self, mut req: WebRequest<Err>) -> Self::Future {
+        if let Some(ref f) = self.render_error {
+            error::push_renderer::<Err>(&mut req.extensions_mut(), f);
+        }
+
         let res = self.router.recognize_checked(&mut req, |req, guards| {
             if let Some(guards) = guards {
                 for f in guards {
@@ -285,8 +322,11 @@ impl<Err: ErrorRenderer> Service<WebRequest<Err>> for AppRouting<Err> {
             true
         });
 
-        if let Some((srv, _info)) = res {
+        if let Some((srv, info)) = res {
+            req.push_resource_id(info.id());
             srv.call(req)
+        } else if let Some(res) = self.redirect(&req) {
+            Box::pin(async move { Ok(req.into_response(res)) })
         } else if let Some(ref default) = self.default {
             default.call(req)
         } else {
@@ -296,6 +336,57 @@ impl<Err: ErrorRenderer> Service<WebRequest<Err>> for AppRouting<Err> {
     }
 }
 
+impl<Err: ErrorRenderer> AppRouting<Err> {
+    fn redirect(&self, req: &WebRequest<Err>) -> Option<Response> {
+        if self.trailing_slash == TrailingSlash::Redirect {
+            trailing_slash_redirect(&self.router, req)
+        } else {
+            None
+        }
+    }
+}
+
+/// Build redirect response if request path with added or removed
+/// trailing slash matches one of the router's resources.
+pub(super) fn trailing_slash_redirect<T, Err: ErrorRenderer>(
+    router: &Router<T, Guards>,
+    req: &WebRequest<Err>,
+) -> Option<Response> {
+    let toggle = |path: &str| {
+        if let Some(path) = path.strip_suffix('/') {
+            path.to_string()
+        } else {
+            format!("{}/", path)
+        }
+    };
+
+    let mut path = Path::new(toggle(req.match_info().path()));
+    router.recognize_checked(&mut path, |_, guards| {
+        if let Some(guards) = guards {
+            for f in guards {
+                if !f.check(req.head()) {
+                    return false;
+                }
+            }
+        }
+        true
+    })?;
+
+    let mut location = toggle(req.path());
+    if location.is_empty() {
+        return None;
+    }
+    if !req.query_string().is_empty() {
+        location.push('?');
+        location.push_str(req.query_string());
+    }
+    Some(
+        Response::PermanentRedirect()
+            .header(LOCATION, location)
+            .finish(),
+    )
+}
+
 /// Web app service
 pub struct AppService<F, Err: ErrorRenderer> {
     filter: F,
@@ -364,6 +455,28 @@ where
     }
 }
 
+pin_project_lite::pin_project! {
+    pub struct AppFactoryServiceResponse<F> {
+        #[pin]
+        fut: F,
+    }
+}
+
+impl<F, E> Future for AppFactoryServiceResponse<F>
+where
+    F: Future<Output = Result<WebResponse, E>>,
+{
+    type Output = Result<WebResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().fut.poll(cx) {
+            // schedule teardown of request scoped values
+            Poll::Ready(Ok(res)) => Poll::Ready(Ok(scoped::teardown(res))),
+            res => res,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::atomic::{AtomicBool, Ordering};
@@ -2,6 +2,7 @@ use std::{net::SocketAddr, rc::Rc};
 
 use crate::router::ResourceDef;
 
+use super::info::TrustedProxies;
 use super::resource::Resource;
 use super::route::Route;
 use super::service::{AppServiceFactory, ServiceFactoryWrapper, WebServiceFactory};
@@ -16,11 +17,24 @@ struct AppConfigInner {
     secure: bool,
     host: String,
     addr: SocketAddr,
+    trusted_proxies: Option<TrustedProxies>,
 }
 
 impl AppConfig {
     pub(crate) fn new(secure: bool, addr: SocketAddr, host: String) -> Self {
-        AppConfig(Rc::new(AppConfigInner { secure, host, addr }))
+        AppConfig(Rc::new(AppConfigInner {
+            secure,
+            host,
+            addr,
+            trusted_proxies: None,
+        }))
+    }
+
+    pub(crate) fn with_trusted_proxies(mut self, proxies: Option<TrustedProxies>) -> Self {
+        Rc::get_mut(&mut self.0)
+            .expect("Multiple copies exist")
+            .trusted_proxies = proxies;
+        self
     }
 
     /// Server host name.
@@ -43,6 +57,13 @@ impl AppConfig {
     pub fn local_addr(&self) -> SocketAddr {
         self.0.addr
     }
+
+    /// Trusted proxies configuration.
+    ///
+    /// If it is not set, forwarding headers are trusted unconditionally.
+    pub fn trusted_proxies(&self) -> Option<&TrustedProxies> {
+        self.0.trusted_proxies.as_ref()
+    }
 }
 
 impl Default for AppConfig {
@@ -1,10 +1,15 @@
 //! Web error
-use std::{cell::RefCell, fmt, io::Write, marker::PhantomData};
+use std::{cell::RefCell, fmt, io::Write, marker::PhantomData, rc::Rc};
 
 use thiserror::Error;
 
 pub use http::Error as HttpError;
 pub use serde_json::error::Error as JsonError;
+#[cfg(feature = "cbor")]
+/// Cbor serialization error
+pub type CborError = ciborium::ser::Error<std::io::Error>;
+#[cfg(feature = "msgpack")]
+pub use rmp_serde::encode::Error as MsgPackError;
 #[cfg(feature = "url")]
 pub use url_pkg::ParseError as UrlParseError;
 
@@ -12,9 +17,10 @@ use super::{HttpRequest, HttpResponse};
 use crate::http::body::Body;
 use crate::http::helpers::Writer;
 use crate::http::{error, header, StatusCode};
-use crate::util::{BytesMut, Either};
+use crate::util::{BytesMut, Either, Extensions};
 
 pub use super::error_default::{DefaultError, Error};
+pub use super::error_problem::{ProblemDetails, ProblemDetailsConfig};
 pub use crate::http::error::BlockingError;
 
 pub trait ErrorRenderer: Sized + 'static {
@@ -24,6 +30,16 @@ pub trait ErrorRenderer: Sized + 'static {
 pub trait ErrorContainer: error::ResponseError + Sized {
     /// Generate response for error container
     fn error_response(&self, req: &HttpRequest) -> HttpResponse;
+
+    /// Returns error as `std::error::Error`, used for error's source chain
+    fn as_error(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+
+    /// Backtrace captured on error creation
+    fn backtrace(&self) -> Option<&backtrace::Backtrace> {
+        None
+    }
 }
 
 /// Error that can be rendered to a `Response`
@@ -52,10 +68,59 @@ where
         );
         resp.set_body(Body::from(buf))
     }
+
+    /// Returns error as `std::error::Error`
+    ///
+    /// Error containers use it to expose error's source chain.
+    /// Chain is not available by default.
+    fn as_error(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
 }
 
 impl<Err: ErrorRenderer> WebResponseError<Err> for std::convert::Infallible {}
 
+/// Error rendering function registered with `App::render_error()`
+/// or `Scope::render_error()`
+pub(super) type RenderFn<Err> =
+    Rc<dyn Fn(&<Err as ErrorRenderer>::Container, &HttpRequest) -> Option<HttpResponse>>;
+
+/// Rendering functions of app and scopes that handle request, innermost last
+struct RenderChain<Err: ErrorRenderer>(Vec<RenderFn<Err>>);
+
+/// Add rendering function to request's render chain
+pub(super) fn push_renderer<Err: ErrorRenderer>(ext: &mut Extensions, f: &RenderFn<Err>) {
+    if let Some(chain) = ext.get_mut::<RenderChain<Err>>() {
+        chain.0.push(f.clone());
+    } else {
+        ext.insert(RenderChain::<Err>(vec![f.clone()]));
+    }
+}
+
+/// Generate response for error container.
+///
+/// Rendering functions are tried from innermost scope to the application,
+/// first produced response wins. Otherwise container renders response itself.
+pub(super) fn render<Err: ErrorRenderer>(
+    err: &Err::Container,
+    req: &HttpRequest,
+) -> HttpResponse {
+    super::middleware::capture_error(err, req);
+
+    let chain = req
+        .extensions()
+        .get::<RenderChain<Err>>()
+        .map(|chain| chain.0.clone());
+    if let Some(chain) = chain {
+        for f in chain.iter().rev() {
+            if let Some(res) = f(err, req) {
+                return res;
+            }
+        }
+    }
+    err.error_response(req)
+}
+
 impl<A, B, Err> WebResponseError<Err> for Either<A, B>
 where
     A: WebResponseError<Err>,
@@ -75,6 +140,13 @@ where
             Either::Right(ref b) => b.error_response(req),
         }
     }
+
+    fn as_error(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Either::Left(ref a) => a.as_error(),
+            Either::Right(ref b) => b.as_error(),
+        }
+    }
 }
 
 /// Errors which can occur when attempting to work with `Data` extractor
@@ -141,6 +213,104 @@ pub enum JsonPayloadError {
     Payload(#[from] error::PayloadError),
 }
 
+#[cfg(feature = "protobuf")]
+/// A set of errors that can occur during parsing protobuf payloads
+#[derive(Error, Debug)]
+pub enum ProtobufPayloadError {
+    /// Payload size is bigger than allowed. (default: 256kB)
+    #[error("Protobuf payload size is bigger than allowed")]
+    Overflow,
+    /// Content type error
+    #[error("Content type error")]
+    ContentType,
+    /// Deserialize error
+    #[error("Protobuf deserialize error: {0}")]
+    Deserialize(#[from] prost::DecodeError),
+    /// Payload error
+    #[error("Error that occur during reading payload: {0}")]
+    Payload(#[from] error::PayloadError),
+}
+
+#[cfg(feature = "msgpack")]
+/// A set of errors that can occur during parsing msgpack payloads
+#[derive(Error, Debug)]
+pub enum MsgPackPayloadError {
+    /// Payload size is bigger than allowed. (default: 256kB)
+    #[error("MsgPack payload size is bigger than allowed")]
+    Overflow,
+    /// Content type error
+    #[error("Content type error")]
+    ContentType,
+    /// Deserialize error
+    #[error("MsgPack deserialize error: {0}")]
+    Deserialize(#[from] rmp_serde::decode::Error),
+    /// Payload error
+    #[error("Error that occur during reading payload: {0}")]
+    Payload(#[from] error::PayloadError),
+}
+
+#[cfg(feature = "cbor")]
+/// A set of errors that can occur during parsing cbor payloads
+#[derive(Error, Debug)]
+pub enum CborPayloadError {
+    /// Payload size is bigger than allowed. (default: 256kB)
+    #[error("Cbor payload size is bigger than allowed")]
+    Overflow,
+    /// Content type error
+    #[error("Content type error")]
+    ContentType,
+    /// Deserialize error
+    #[error("Cbor deserialize error: {0}")]
+    Deserialize(#[from] ciborium::de::Error<std::io::Error>),
+    /// Payload error
+    #[error("Error that occur during reading payload: {0}")]
+    Payload(#[from] error::PayloadError),
+}
+
+/// A set of errors that can occur during parsing multipart payloads
+#[derive(Error, Debug)]
+pub enum MultipartError {
+    /// Content type error
+    #[error("Content type error")]
+    ContentType,
+    /// Multipart boundary is not found or invalid
+    #[error("Multipart boundary error")]
+    Boundary,
+    /// Multipart payload is incomplete
+    #[error("Multipart payload is incomplete")]
+    Incomplete,
+    /// Part's headers are invalid
+    #[error("Invalid part headers")]
+    Headers,
+    /// Number of parts is bigger than allowed
+    #[error("Number of parts is bigger than allowed ({0})")]
+    TooManyParts(usize),
+    /// Part size is bigger than allowed
+    #[error("Part size is bigger than allowed ({0} bytes)")]
+    PartOverflow(usize),
+    /// Form fields deserialize error
+    #[error("Multipart form deserialize error: {0}")]
+    Deserialize(#[from] serde::de::value::Error),
+    /// Io error
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Payload error
+    #[error("Error that occur during reading payload: {0}")]
+    Payload(#[from] error::PayloadError),
+}
+
+#[cfg(feature = "session")]
+/// A set of errors that can occur during session handling
+#[derive(Error, Debug)]
+pub enum SessionError {
+    /// Session value serialization error
+    #[error("Session value serialization error: {0}")]
+    Serialize(#[from] serde_json::Error),
+    /// Session store error
+    #[error("Session store error: {0}")]
+    Store(String),
+}
+
 /// A set of errors that can occur during parsing request paths
 #[derive(Error, Debug)]
 pub enum PathError {
@@ -155,6 +325,9 @@ pub enum QueryPayloadError {
     /// Deserialize error
     #[error("Query deserialize error: {0}")]
     Deserialize(#[from] serde::de::value::Error),
+    /// Nested keys depth exceeds limit
+    #[error("Query nesting depth exceeds limit: {0}")]
+    Depth(usize),
 }
 
 #[derive(Error, Debug)]
@@ -186,9 +359,18 @@ pub enum PayloadError {
 pub struct InternalError<T, Err = DefaultError> {
     cause: T,
     status: InternalErrorType,
+    source: Option<SourceFn<T>>,
     _t: PhantomData<Err>,
 }
 
+type SourceFn<T> = fn(&T) -> Option<&(dyn std::error::Error + 'static)>;
+
+fn error_source<T: std::error::Error>(
+    err: &T,
+) -> Option<&(dyn std::error::Error + 'static)> {
+    err.source()
+}
+
 enum InternalErrorType {
     Status(StatusCode),
     Response(RefCell<Option<HttpResponse>>),
@@ -200,6 +382,7 @@ impl<T> InternalError<T> {
         InternalError {
             cause,
             status: InternalErrorType::Status(status),
+            source: None,
             _t: PhantomData,
         }
     }
@@ -211,6 +394,7 @@ impl<T, Err> InternalError<T, Err> {
         InternalError {
             cause,
             status: InternalErrorType::Status(status),
+            source: None,
             _t: PhantomData,
         }
     }
@@ -220,6 +404,19 @@ impl<T, Err> InternalError<T, Err> {
         InternalError {
             cause,
             status: InternalErrorType::Response(RefCell::new(Some(response))),
+            source: None,
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T: std::error::Error + 'static, Err> InternalError<T, Err> {
+    /// Create `InternalError` instance that preserves cause's source chain
+    pub fn from_error(cause: T, status: StatusCode) -> Self {
+        InternalError {
+            cause,
+            status: InternalErrorType::Status(status),
+            source: Some(error_source::<T>),
             _t: PhantomData,
         }
     }
@@ -243,16 +440,35 @@ where
     }
 }
 
-impl<T: fmt::Display + fmt::Debug + 'static, E> std::error::Error for InternalError<T, E> {}
+impl<T: fmt::Display + fmt::Debug + 'static, E> std::error::Error for InternalError<T, E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.and_then(|f| f(&self.cause))
+    }
+}
 
 impl<T, E> WebResponseError<E> for InternalError<T, E>
 where
     T: fmt::Debug + fmt::Display + 'static,
     E: ErrorRenderer,
 {
+    fn status_code(&self) -> StatusCode {
+        match self.status {
+            InternalErrorType::Status(st) => st,
+            InternalErrorType::Response(ref resp) => resp
+                .borrow()
+                .as_ref()
+                .map(|resp| resp.status())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
     fn error_response(&self, _: &HttpRequest) -> HttpResponse {
         crate::http::error::ResponseError::error_response(self)
     }
+
+    fn as_error(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self)
+    }
 }
 
 impl<T, E> crate::http::error::ResponseError for InternalError<T, E>
@@ -701,6 +917,49 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_error_chain() {
+        use std::error::Error as StdError;
+
+        let req = TestRequest::default().to_http_request();
+
+        let err = Error::new(JsonPayloadError::Payload(error::PayloadError::Io(
+            io::Error::new(io::ErrorKind::Other, "reset"),
+        )));
+        let chain: Vec<_> = err.chain().map(|e| e.to_string()).collect();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[2], "reset");
+
+        let err = err.context("reading body").context("loading user");
+        assert_eq!(err.to_string(), "loading user");
+        assert_eq!(err.source().unwrap().to_string(), "reading body");
+        assert_eq!(err.chain().count(), 5);
+        assert_eq!(err.chain().last().unwrap().to_string(), "reset");
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            err.as_response_error().error_response(&req).status(),
+            StatusCode::BAD_REQUEST
+        );
+        let s = format!("{:?}", err);
+        assert!(s.contains("Caused by:"));
+        assert!(s.contains("    3: reset"));
+
+        let err: Error = InternalError::from_error(
+            UrlencodedError::Payload(error::PayloadError::Incomplete(None)),
+            StatusCode::BAD_REQUEST,
+        )
+        .into();
+        assert!(err.source().is_some());
+        let err: Error =
+            InternalError::new(UrlencodedError::UnknownLength, StatusCode::BAD_REQUEST)
+                .into();
+        assert!(err.source().is_none());
+        assert!(!format!("{:?}", err).contains("Caused by:"));
+    }
+
     #[test]
     fn test_other_errors() {
         let req = TestRequest::default().to_http_request();
@@ -712,6 +971,13 @@ mod tests {
         );
         assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
 
+        use crate::util::loadshed::LoadShedError;
+        let resp = WebResponseError::<DefaultError>::error_response(
+            &LoadShedError::<UrlencodedError>::Overloaded,
+            &req,
+        );
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
         let resp = WebResponseError::<DefaultError>::error_response(
             &SendRequestError::Connect(ConnectError::Timeout),
             &req,
@@ -883,6 +1149,8 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
         let resp = HandshakeError::BadWebsocketKey.error_response(&req);
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = HandshakeError::UnsupportedProtocol.error_response(&req);
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 
     #[test]
@@ -1,6 +1,8 @@
 //! Web error
-use std::{fmt, io, io::Write, str::Utf8Error};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{error::Error as StdError, fmt, io, io::Write, str::Utf8Error};
 
+use backtrace::Backtrace;
 use serde::de::value::Error as DeError;
 use serde_json::error::Error as JsonError;
 use serde_urlencoded::ser::Error as FormError;
@@ -8,10 +10,11 @@ use serde_urlencoded::ser::Error as FormError;
 use crate::http::body::Body;
 use crate::http::helpers::Writer;
 use crate::http::{self, header, StatusCode};
-use crate::util::{timeout::TimeoutError, BytesMut};
+use crate::util::{loadshed::LoadShedError, timeout::TimeoutError, BytesMut};
 use crate::ws::error::HandshakeError;
 
 use super::error::{self, ErrorContainer, ErrorRenderer, WebResponseError};
+use super::error_problem::extractor_error_response;
 use super::{HttpRequest, HttpResponse};
 
 /// Default error type
@@ -23,15 +26,34 @@ impl ErrorRenderer for DefaultError {
 }
 
 /// Generic error container for errors that supports `DefaultError` renderer.
-#[derive(thiserror::Error)]
+///
+/// Container is transparent, `Display` and `source()` are forwarded
+/// to underlying error. `Debug` output includes full source chain.
 pub struct Error {
     cause: Box<dyn WebResponseError<DefaultError>>,
+    backtrace: Option<Box<Backtrace>>,
+}
+
+/// Backtraces capturing is enabled by `DebugErrors` middleware
+static BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn enable_backtraces() {
+    BACKTRACES.store(true, Ordering::Relaxed);
+}
+
+fn capture_backtrace() -> Option<Box<Backtrace>> {
+    if BACKTRACES.load(Ordering::Relaxed) {
+        Some(Box::new(Backtrace::new()))
+    } else {
+        None
+    }
 }
 
 impl Error {
     pub fn new<T: WebResponseError<DefaultError> + 'static>(err: T) -> Error {
         Error {
             cause: Box::new(err),
+            backtrace: capture_backtrace(),
         }
     }
 
@@ -39,6 +61,49 @@ impl Error {
     pub fn as_response_error(&self) -> &dyn WebResponseError<DefaultError> {
         self.cause.as_ref()
     }
+
+    /// Wrap error with context message.
+    ///
+    /// Context message becomes error's `Display` output, wrapped error
+    /// is available as its source. Response is still generated
+    /// by wrapped error, context message is not sent to the client.
+    ///
+    /// ```rust
+    /// use ntex::web::Error;
+    ///
+    /// let err = Error::new(std::io::Error::new(std::io::ErrorKind::Other, "refused"))
+    ///     .context("loading user profile");
+    /// assert_eq!(err.to_string(), "loading user profile");
+    /// assert_eq!(err.chain().nth(1).unwrap().to_string(), "refused");
+    /// ```
+    pub fn context<C>(self, msg: C) -> Error
+    where
+        C: fmt::Display,
+    {
+        Error {
+            cause: Box::new(Context {
+                msg: msg.to_string(),
+                error: self,
+            }),
+            backtrace: None,
+        }
+    }
+
+    /// Backtrace captured on error creation.
+    ///
+    /// Backtraces are captured only if `DebugErrors` middleware is enabled.
+    /// For errors with context, backtrace of the wrapped error is returned.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.chain()
+            .filter_map(|err| err.downcast_ref::<Error>())
+            .find_map(|err| err.backtrace.as_deref())
+    }
+
+    /// Iterator over error and its sources, starting with error itself.
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
+        let err: &(dyn StdError + 'static) = self;
+        std::iter::successors(Some(err), |&err| err.source())
+    }
 }
 
 /// `Error` for any error which implements `WebResponseError<DefaultError>`
@@ -46,6 +111,7 @@ impl<T: WebResponseError<DefaultError>> From<T> for Error {
     fn from(err: T) -> Self {
         Error {
             cause: Box::new(err),
+            backtrace: capture_backtrace(),
         }
     }
 }
@@ -54,6 +120,14 @@ impl ErrorContainer for Error {
     fn error_response(&self, req: &HttpRequest) -> HttpResponse {
         self.cause.error_response(req)
     }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        Error::backtrace(self)
+    }
 }
 
 impl crate::http::error::ResponseError for Error {
@@ -77,7 +151,58 @@ impl fmt::Display for Error {
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "web::Error({:?})", &self.cause)
+        write!(f, "web::Error({:?})", &self.cause)?;
+        for (idx, err) in self.chain().skip(1).enumerate() {
+            if idx == 0 {
+                write!(f, "\n\nCaused by:")?;
+            }
+            write!(f, "\n    {}: {}", idx, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.cause.as_error().and_then(|err| err.source())
+    }
+}
+
+/// Error with context message, created by `Error::context()`
+struct Context {
+    msg: String,
+    error: Error,
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {:?}", self.msg, self.error.cause)
+    }
+}
+
+impl StdError for Context {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl WebResponseError<DefaultError> for Context {
+    fn status_code(&self) -> StatusCode {
+        self.error.cause.status_code()
+    }
+
+    fn error_response(&self, req: &HttpRequest) -> HttpResponse {
+        self.error.cause.error_response(req)
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
     }
 }
 
@@ -91,6 +216,18 @@ impl<E: WebResponseError<DefaultError>> WebResponseError<DefaultError> for Timeo
     }
 }
 
+/// Return `SERVICE_UNAVAILABLE` for `LoadShedError`
+impl<E: WebResponseError<DefaultError>> WebResponseError<DefaultError>
+    for LoadShedError<E>
+{
+    fn status_code(&self) -> StatusCode {
+        match self {
+            LoadShedError::Service(e) => e.status_code(),
+            LoadShedError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
 /// `InternalServerError` for `DataExtractorError`
 impl WebResponseError<DefaultError> for error::DataExtractorError {}
 
@@ -147,6 +284,10 @@ impl WebResponseError<DefaultError> for io::Error {
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
 }
 
 /// `InternalServerError` for `UrlGeneratorError`
@@ -161,6 +302,10 @@ impl WebResponseError<DefaultError> for error::UrlencodedError {
             _ => StatusCode::BAD_REQUEST,
         }
     }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
 }
 
 /// Return `BadRequest` for `JsonPayloadError`
@@ -171,13 +316,112 @@ impl WebResponseError<DefaultError> for error::JsonPayloadError {
             _ => StatusCode::BAD_REQUEST,
         }
     }
+
+    fn error_response(&self, req: &HttpRequest) -> HttpResponse {
+        extractor_error_response(self, req)
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
 }
 
+#[cfg(feature = "protobuf")]
+/// Return `BadRequest` for `ProtobufPayloadError`
+impl WebResponseError<DefaultError> for error::ProtobufPayloadError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            error::ProtobufPayloadError::Overflow => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+/// `InternalServerError` for `MsgPackError`
+impl WebResponseError<DefaultError> for error::MsgPackError {}
+
+#[cfg(feature = "msgpack")]
+/// Return `BadRequest` for `MsgPackPayloadError`
+impl WebResponseError<DefaultError> for error::MsgPackPayloadError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            error::MsgPackPayloadError::Overflow => StatusCode::PAYLOAD_TOO_LARGE,
+            error::MsgPackPayloadError::ContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self, req: &HttpRequest) -> HttpResponse {
+        extractor_error_response(self, req)
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
+}
+
+#[cfg(feature = "cbor")]
+/// `InternalServerError` for `CborError`
+impl WebResponseError<DefaultError> for error::CborError {}
+
+#[cfg(feature = "cbor")]
+/// Return `BadRequest` for `CborPayloadError`
+impl WebResponseError<DefaultError> for error::CborPayloadError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            error::CborPayloadError::Overflow => StatusCode::PAYLOAD_TOO_LARGE,
+            error::CborPayloadError::ContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self, req: &HttpRequest) -> HttpResponse {
+        extractor_error_response(self, req)
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
+}
+
+/// Response renderer for `MultipartError`
+impl WebResponseError<DefaultError> for error::MultipartError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            error::MultipartError::TooManyParts(_)
+            | error::MultipartError::PartOverflow(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            error::MultipartError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
+}
+
+#[cfg(feature = "session")]
+/// `InternalServerError` for `SessionError`
+impl WebResponseError<DefaultError> for error::SessionError {}
+
 /// Error renderer for `PathError`
 impl WebResponseError<DefaultError> for error::PathError {
     fn status_code(&self) -> StatusCode {
         StatusCode::NOT_FOUND
     }
+
+    fn error_response(&self, req: &HttpRequest) -> HttpResponse {
+        extractor_error_response(self, req)
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
 }
 
 /// Error renderer `QueryPayloadError`
@@ -185,11 +429,28 @@ impl WebResponseError<DefaultError> for error::QueryPayloadError {
     fn status_code(&self) -> StatusCode {
         StatusCode::BAD_REQUEST
     }
+
+    fn error_response(&self, req: &HttpRequest) -> HttpResponse {
+        extractor_error_response(self, req)
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
 }
 
 impl WebResponseError<DefaultError> for error::PayloadError {
     fn status_code(&self) -> StatusCode {
-        StatusCode::BAD_REQUEST
+        match *self {
+            error::PayloadError::Payload(ref err) => {
+                WebResponseError::<DefaultError>::status_code(err)
+            }
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
     }
 }
 
@@ -204,6 +465,10 @@ impl WebResponseError<DefaultError> for http::error::PayloadError {
             _ => StatusCode::BAD_REQUEST,
         }
     }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
 }
 
 #[cfg(feature = "cookie")]
@@ -232,6 +497,10 @@ impl WebResponseError<DefaultError> for http::client::error::SendRequestError {
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
 }
 
 /// Error renderer for ws::HandshakeError
@@ -256,6 +525,9 @@ impl WebResponseError<DefaultError> for HandshakeError {
             HandshakeError::BadWebsocketKey => HttpResponse::BadRequest()
                 .reason("Handshake error")
                 .finish(),
+            HandshakeError::UnsupportedProtocol => HttpResponse::BadRequest()
+                .reason("Unsupported sub-protocol")
+                .finish(),
         }
     }
 }
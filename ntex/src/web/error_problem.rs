@@ -0,0 +1,329 @@
+//! Problem details for http apis (RFC 7807)
+use std::{fmt, io::Write};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::http::body::Body;
+use crate::http::helpers::Writer;
+use crate::http::{header, StatusCode};
+use crate::util::BytesMut;
+
+use super::error::{ErrorRenderer, WebResponseError};
+use super::responder::{Ready, Responder};
+use super::{DefaultError, HttpRequest, HttpResponse};
+
+/// Problem details error, rendered as `application/problem+json` response.
+///
+/// `ProblemDetails` could be used as handler's error or as responder.
+/// Title defaults to canonical reason of the status code. Extension members
+/// are serialized next to standard members.
+///
+/// ```rust
+/// use ntex::http::StatusCode;
+/// use ntex::web::{self, error::ProblemDetails};
+///
+/// async fn index() -> Result<String, ProblemDetails> {
+///     Err(ProblemDetails::new(StatusCode::FORBIDDEN)
+///         .problem_type("https://example.com/probs/out-of-credit")
+///         .title("You do not have enough credit.")
+///         .detail("Your current balance is 30, but that costs 50.")
+///         .instance("/account/12345/msgs/abc")
+///         .extension("balance", 30))
+/// }
+///
+/// fn main() {
+///     let app = web::App::new().service(web::resource("/").to(index));
+/// }
+/// ```
+#[derive(Clone, Debug, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    problem_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
+}
+
+impl ProblemDetails {
+    /// Create problem details for status code
+    pub fn new(status: StatusCode) -> Self {
+        ProblemDetails {
+            problem_type: None,
+            title: status.canonical_reason().map(|s| s.to_string()),
+            status: status.as_u16(),
+            detail: None,
+            instance: None,
+            extensions: Map::new(),
+        }
+    }
+
+    /// Set problem type uri. By default type is `about:blank`
+    pub fn problem_type<T: Into<String>>(mut self, uri: T) -> Self {
+        self.problem_type = Some(uri.into());
+        self
+    }
+
+    /// Set short summary of the problem type
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set explanation specific to this occurrence of the problem
+    pub fn detail<T: Into<String>>(mut self, detail: T) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Set uri reference of this occurrence of the problem
+    pub fn instance<T: Into<String>>(mut self, instance: T) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Add extension member.
+    ///
+    /// Member is skipped if value could not be serialized to json.
+    pub fn extension<K: Into<String>, V: Serialize>(mut self, key: K, value: V) -> Self {
+        match serde_json::to_value(value) {
+            Ok(value) => {
+                self.extensions.insert(key.into(), value);
+            }
+            Err(e) => log::error!("Cannot serialize problem details extension: {}", e),
+        }
+        self
+    }
+
+    /// Problem status code
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Generate `application/problem+json` response
+    pub fn to_response(&self) -> HttpResponse {
+        match serde_json::to_vec(self) {
+            Ok(body) => HttpResponse::build(self.status())
+                .content_type("application/problem+json")
+                .body(body),
+            Err(e) => {
+                log::error!("Cannot serialize problem details: {}", e);
+                HttpResponse::InternalServerError().finish()
+            }
+        }
+    }
+}
+
+impl fmt::Display for ProblemDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.title, &self.detail) {
+            (Some(title), Some(detail)) => write!(f, "{}: {}", title, detail),
+            (Some(s), None) | (None, Some(s)) => f.write_str(s),
+            (None, None) => write!(f, "{}", self.status),
+        }
+    }
+}
+
+impl std::error::Error for ProblemDetails {}
+
+impl<Err: ErrorRenderer> WebResponseError<Err> for ProblemDetails {
+    fn status_code(&self) -> StatusCode {
+        self.status()
+    }
+
+    fn error_response(&self, _: &HttpRequest) -> HttpResponse {
+        self.to_response()
+    }
+}
+
+impl crate::http::error::ResponseError for ProblemDetails {
+    fn error_response(&self) -> HttpResponse {
+        self.to_response()
+    }
+}
+
+impl<Err: ErrorRenderer> Responder<Err> for ProblemDetails {
+    type Error = Err::Container;
+    type Future = Ready<HttpResponse>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        self.to_response().into()
+    }
+}
+
+/// Problem details configuration
+///
+/// ```rust
+/// use ntex::web::{self, App, error::ProblemDetailsConfig};
+///
+/// fn main() {
+///     // json, query and path extractor errors are rendered as problem+json
+///     let app = App::new()
+///         .app_state(ProblemDetailsConfig::default().extractor_errors(true));
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ProblemDetailsConfig {
+    extractor_errors: bool,
+}
+
+impl ProblemDetailsConfig {
+    /// Render `Json`, `Query` and `Path` extractor errors as problem details.
+    ///
+    /// By default extractor errors are rendered as plain text.
+    pub fn extractor_errors(mut self, enabled: bool) -> Self {
+        self.extractor_errors = enabled;
+        self
+    }
+}
+
+/// Render extractor error, as problem details if it is enabled
+pub(super) fn extractor_error_response<E>(err: &E, req: &HttpRequest) -> HttpResponse
+where
+    E: WebResponseError<DefaultError>,
+{
+    let status = err.status_code();
+    if req
+        .app_state::<ProblemDetailsConfig>()
+        .map_or(false, |cfg| cfg.extractor_errors)
+    {
+        ProblemDetails::new(status)
+            .detail(err.to_string())
+            .instance(req.path())
+            .to_response()
+    } else {
+        let mut resp = HttpResponse::new(status);
+        let mut buf = BytesMut::new();
+        let _ = write!(Writer(&mut buf), "{}", err);
+        resp.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        resp.set_body(Body::from(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::body::ResponseBody;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, types, App};
+
+    fn body(resp: &HttpResponse) -> Value {
+        match resp.body() {
+            ResponseBody::Body(Body::Bytes(ref b)) => serde_json::from_slice(b).unwrap(),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_problem_details() {
+        let problem = ProblemDetails::new(StatusCode::FORBIDDEN)
+            .problem_type("https://example.com/probs/out-of-credit")
+            .detail("Your current balance is 30")
+            .instance("/account/12345")
+            .extension("balance", 30);
+        assert_eq!(problem.status(), StatusCode::FORBIDDEN);
+        assert_eq!(problem.to_string(), "Forbidden: Your current balance is 30");
+
+        let req = TestRequest::default().to_http_request();
+        let resp = WebResponseError::<DefaultError>::error_response(&problem, &req);
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        assert_eq!(
+            body(&resp),
+            serde_json::json!({
+                "type": "https://example.com/probs/out-of-credit",
+                "title": "Forbidden",
+                "status": 403,
+                "detail": "Your current balance is 30",
+                "instance": "/account/12345",
+                "balance": 30,
+            })
+        );
+
+        let resp = crate::http::error::ResponseError::error_response(&ProblemDetails::new(
+            StatusCode::NOT_FOUND,
+        ));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body(&resp),
+            serde_json::json!({"title": "Not Found", "status": 404})
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_extractor_errors() {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            id: u32,
+        }
+
+        let srv = init_service(
+            App::new()
+                .app_state(ProblemDetailsConfig::default().extractor_errors(true))
+                .service(
+                    web::resource("/query")
+                        .to(|p: types::Query<Params>| async move { p.id.to_string() }),
+                )
+                .service(
+                    web::resource("/path/{id}")
+                        .to(|p: types::Path<Params>| async move { p.id.to_string() }),
+                )
+                .service(
+                    web::resource("/problem")
+                        .to(|| async { ProblemDetails::new(StatusCode::CONFLICT) }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/query?id=x").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body: Value = serde_json::from_slice(&read_body(resp).await).unwrap();
+        assert_eq!(body["status"], 400);
+        assert_eq!(body["instance"], "/query");
+
+        let req = TestRequest::with_uri("/path/x").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+
+        let req = TestRequest::with_uri("/problem").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        // disabled by default
+        let srv = init_service(
+            App::new().service(
+                web::resource("/query")
+                    .to(|p: types::Query<Params>| async move { p.id.to_string() }),
+            ),
+        )
+        .await;
+        let req = TestRequest::with_uri("/query?id=x").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+    }
+}
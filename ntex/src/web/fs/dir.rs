@@ -0,0 +1,115 @@
+use std::{fmt::Write, fs::DirEntry, io, path::PathBuf};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::http::{header, Response};
+use crate::web::HttpRequest;
+
+/// Characters that are encoded in directory listing links
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/');
+
+/// A directory; responds with the generated directory listing.
+#[derive(Debug)]
+pub struct Directory {
+    /// Base directory
+    pub base: PathBuf,
+    /// Path of subdirectory to generate listing for
+    pub path: PathBuf,
+}
+
+impl Directory {
+    /// Create a new directory
+    pub fn new(base: PathBuf, path: PathBuf) -> Directory {
+        Directory { base, path }
+    }
+
+    /// Is this entry visible from this directory?
+    pub fn is_visible(&self, entry: &io::Result<DirEntry>) -> bool {
+        if let Ok(ref entry) = *entry {
+            if let Some(name) = entry.file_name().to_str() {
+                if name.starts_with('.') {
+                    return false;
+                }
+            }
+            if let Ok(ref md) = entry.metadata() {
+                let ft = md.file_type();
+                return ft.is_dir() || ft.is_file() || ft.is_symlink();
+            }
+        }
+        false
+    }
+}
+
+/// Directory listing renderer
+pub(super) type DirectoryRenderer =
+    dyn Fn(&Directory, &HttpRequest) -> io::Result<Response>;
+
+/// Default directory listing renderer, generates html page
+pub(super) fn directory_listing(
+    dir: &Directory,
+    req: &HttpRequest,
+) -> io::Result<Response> {
+    let index_of = format!("Index of {}", req.path());
+    let base = req.path().trim_end_matches('/');
+    let mut entries = Vec::new();
+
+    for entry in dir.path.read_dir()? {
+        if dir.is_visible(&entry) {
+            let entry = entry?;
+            let is_dir = entry.metadata().map(|md| md.is_dir()).unwrap_or(false);
+            entries.push((entry.file_name().to_string_lossy().into_owned(), is_dir));
+        }
+    }
+    entries.sort();
+
+    let mut body = String::new();
+    let _ = write!(
+        body,
+        "<html>\n<head>\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+        escape_html(&index_of),
+        escape_html(&index_of)
+    );
+    for (name, is_dir) in entries {
+        let slash = if is_dir { "/" } else { "" };
+        let _ = writeln!(
+            body,
+            "<li><a href=\"{}/{}{}\">{}{}</a></li>",
+            escape_html(base),
+            utf8_percent_encode(&name, PATH_SEGMENT),
+            slash,
+            escape_html(&name),
+            slash
+        );
+    }
+    body.push_str("</ul>\n</body>\n</html>\n");
+
+    Ok(Response::Ok()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(body))
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
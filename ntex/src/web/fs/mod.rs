@@ -0,0 +1,516 @@
+//! Static files support
+use std::{fmt, io, path::Path, path::PathBuf, rc::Rc, task::Context, task::Poll};
+
+use percent_encoding::percent_decode_str;
+
+use crate::http::{header, Method, Response};
+use crate::router::ResourceDef;
+use crate::service::{Service, ServiceFactory};
+use crate::util::Ready;
+use crate::web::error::ErrorRenderer;
+use crate::web::service::{WebServiceConfig, WebServiceFactory};
+use crate::web::{HttpRequest, WebRequest, WebResponse};
+
+mod dir;
+mod named;
+mod range;
+
+pub use self::dir::Directory;
+pub use self::named::NamedFile;
+pub use self::range::{HttpRange, InvalidRange};
+
+use self::dir::{directory_listing, DirectoryRenderer};
+
+/// Static files handling service.
+///
+/// `Files` service must be registered with `App::service()` method.
+///
+/// ```rust
+/// use ntex::web::{self, fs, App};
+///
+/// let app = App::new()
+///     .service(fs::Files::new("/static", ".").index_file("index.html"));
+/// ```
+#[derive(Clone)]
+pub struct Files {
+    path: String,
+    directory: PathBuf,
+    index: Option<String>,
+    show_index: bool,
+    redirect_to_slash: bool,
+    hidden_files: bool,
+    use_etag: bool,
+    use_last_modified: bool,
+    renderer: Rc<DirectoryRenderer>,
+}
+
+impl Files {
+    /// Create new `Files` instance for specified base directory.
+    ///
+    /// `mount_path` is the url prefix, `serve_from` is the directory
+    /// files are served from.
+    pub fn new<T: Into<PathBuf>>(mount_path: &str, serve_from: T) -> Files {
+        let directory = serve_from.into();
+        let directory = directory.canonicalize().unwrap_or_else(|e| {
+            log::error!("Specified path is not a directory {:?}: {}", directory, e);
+            directory
+        });
+
+        Files {
+            path: mount_path.trim_end_matches('/').to_string(),
+            directory,
+            index: None,
+            show_index: false,
+            redirect_to_slash: false,
+            hidden_files: false,
+            use_etag: true,
+            use_last_modified: true,
+            renderer: Rc::new(directory_listing),
+        }
+    }
+
+    /// Show files listing for directories.
+    ///
+    /// By default show files listing is disabled.
+    pub fn show_files_listing(mut self) -> Self {
+        self.show_index = true;
+        self
+    }
+
+    /// Redirects to a slash-ended path when browsing a directory.
+    ///
+    /// By default never redirect.
+    pub fn redirect_to_slash_directory(mut self) -> Self {
+        self.redirect_to_slash = true;
+        self
+    }
+
+    /// Set custom directory renderer
+    pub fn files_listing_renderer<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Directory, &HttpRequest) -> io::Result<Response> + 'static,
+    {
+        self.renderer = Rc::new(f);
+        self
+    }
+
+    /// Set index file
+    ///
+    /// Shows specific index file for directory "/" instead of
+    /// showing files listing.
+    pub fn index_file<T: Into<String>>(mut self, index: T) -> Self {
+        self.index = Some(index.into());
+        self
+    }
+
+    /// Serve hidden files, file names that start with dot.
+    ///
+    /// By default hidden files are not served.
+    pub fn use_hidden_files(mut self) -> Self {
+        self.hidden_files = true;
+        self
+    }
+
+    /// Specifies whether to use `ETag` or not.
+    ///
+    /// Default is true.
+    pub fn use_etag(mut self, value: bool) -> Self {
+        self.use_etag = value;
+        self
+    }
+
+    /// Specifies whether to use `Last-Modified` or not.
+    ///
+    /// Default is true.
+    pub fn use_last_modified(mut self, value: bool) -> Self {
+        self.use_last_modified = value;
+        self
+    }
+
+    /// Resolve request path to the file system path
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let mut buf = self.directory.clone();
+        for segment in path.split('/') {
+            let segment = percent_decode_str(segment).decode_utf8().ok()?;
+            if segment.is_empty() || segment == "." {
+                continue;
+            } else if segment == ".."
+                || segment.contains('/')
+                || segment.contains('\\')
+                || segment.contains('\0')
+                || (segment.starts_with('.') && !self.hidden_files)
+            {
+                return None;
+            }
+            #[cfg(windows)]
+            {
+                if segment.contains(':') {
+                    return None;
+                }
+            }
+            buf.push(segment.as_ref());
+        }
+        Some(buf)
+    }
+
+    fn named_file(&self, path: &Path, req: &HttpRequest) -> Response {
+        match NamedFile::open(path) {
+            Ok(file) => file
+                .use_etag(self.use_etag)
+                .use_last_modified(self.use_last_modified)
+                .into_response(req),
+            Err(e) => io_error_response(e),
+        }
+    }
+
+    fn handle(&self, req: &HttpRequest) -> Response {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Response::MethodNotAllowed()
+                .header(header::ALLOW, "GET, HEAD")
+                .finish();
+        }
+
+        let path = match self.resolve(req.match_info().unprocessed()) {
+            Some(path) => path,
+            None => return Response::NotFound().finish(),
+        };
+
+        if path.is_dir() {
+            if self.redirect_to_slash && !req.path().ends_with('/') {
+                let mut location = format!("{}/", req.path());
+                if !req.query_string().is_empty() {
+                    location.push('?');
+                    location.push_str(req.query_string());
+                }
+                return Response::Found()
+                    .header(header::LOCATION, location)
+                    .finish();
+            }
+
+            if let Some(ref index) = self.index {
+                let index = path.join(index);
+                if index.is_file() {
+                    return self.named_file(&index, req);
+                }
+            }
+            if self.show_index {
+                let dir = Directory::new(self.directory.clone(), path);
+                (*self.renderer)(&dir, req).unwrap_or_else(io_error_response)
+            } else {
+                Response::NotFound().finish()
+            }
+        } else {
+            self.named_file(&path, req)
+        }
+    }
+}
+
+impl fmt::Debug for Files {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Files")
+            .field("path", &self.path)
+            .field("directory", &self.directory)
+            .field("index", &self.index)
+            .field("show_index", &self.show_index)
+            .finish()
+    }
+}
+
+impl<Err: ErrorRenderer> WebServiceFactory<Err> for Files {
+    fn register(self, config: &mut WebServiceConfig<Err>) {
+        let rdef = if self.path.is_empty() {
+            ResourceDef::prefix("/")
+        } else {
+            ResourceDef::root_prefix(self.path.as_str())
+        };
+        config.register_service(rdef, None, self, None)
+    }
+}
+
+impl<Err: ErrorRenderer> ServiceFactory<WebRequest<Err>> for Files {
+    type Response = WebResponse;
+    type Error = Err::Container;
+    type Service = FilesService;
+    type InitError = ();
+    type Future = Ready<Self::Service, Self::InitError>;
+
+    fn new_service(&self, _: ()) -> Self::Future {
+        Ready::Ok(FilesService(Rc::new(self.clone())))
+    }
+}
+
+/// Static files handling service
+pub struct FilesService(Rc<Files>);
+
+impl<Err: ErrorRenderer> Service<WebRequest<Err>> for FilesService {
+    type Response = WebResponse;
+    type Error = Err::Container;
+    type Future = Ready<WebResponse, Err::Container>;
+
+    #[inline]
+    fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&self, req: WebRequest<Err>) -> Self::Future {
+        let (req, _) = req.into_parts();
+        let res = self.0.handle(&req);
+        Ready::Ok(WebResponse::new(res, req))
+    }
+}
+
+fn io_error_response(e: io::Error) -> Response {
+    match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::InvalidInput => {
+            Response::NotFound().finish()
+        }
+        io::ErrorKind::PermissionDenied => Response::Forbidden().finish(),
+        _ => {
+            log::error!("Cannot serve file: {}", e);
+            Response::InternalServerError().finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::http::{header::HeaderValue, StatusCode};
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, App};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ntex-fs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("test.txt"), "0123456789").unwrap();
+        fs::write(dir.join(".hidden"), "hidden").unwrap();
+        fs::write(dir.join("sub").join("index.html"), "<html></html>").unwrap();
+        fs::write(dir.join("sub").join("a <b>.css"), "body {}").unwrap();
+        dir
+    }
+
+    #[crate::rt_test]
+    async fn test_files() {
+        let dir = test_dir("files");
+        let srv = init_service(
+            App::new().service(
+                Files::new("/static", &dir)
+                    .index_file("index.html")
+                    .redirect_to_slash_directory(),
+            ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/static/test.txt").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes");
+        assert!(resp.headers().contains_key(header::ETAG));
+        assert!(resp.headers().contains_key(header::LAST_MODIFIED));
+        assert_eq!(read_body(resp).await, "0123456789");
+
+        let req = TestRequest::with_uri("/static/sub/a%20%3Cb%3E.css").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/css; charset=utf-8"
+        );
+
+        // index file
+        let req = TestRequest::with_uri("/static/sub/").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(read_body(resp).await, "<html></html>");
+
+        let req = TestRequest::with_uri("/static/sub?q=1").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            "/static/sub/?q=1"
+        );
+
+        // not found, hidden and invalid paths
+        for path in &[
+            "/static/missing.txt",
+            "/static/.hidden",
+            "/static/../test.txt",
+            "/static/sub/%2e%2e/test.txt",
+            "/static/",
+        ] {
+            let req = TestRequest::with_uri(path).to_request();
+            let resp = call_service(&srv, req).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+
+        let req = TestRequest::with_uri("/static/test.txt")
+            .method(Method::POST)
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[crate::rt_test]
+    async fn test_files_listing() {
+        let dir = test_dir("listing");
+        let srv = init_service(
+            App::new().service(
+                web::scope("/app").service(
+                    Files::new("/", &dir)
+                        .show_files_listing()
+                        .use_hidden_files(),
+                ),
+            ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/app/").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        let body = read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("<a href=\"/app/sub/\">sub/</a>"));
+        assert!(body.contains("<a href=\"/app/test.txt\">test.txt</a>"));
+        assert!(!body.contains("hidden"));
+
+        let req = TestRequest::with_uri("/app/sub").to_request();
+        let body = read_body(call_service(&srv, req).await).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("<a href=\"/app/sub/a%20%3Cb%3E.css\">a &lt;b&gt;.css</a>"));
+
+        let req = TestRequest::with_uri("/app/.hidden").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(read_body(resp).await, "hidden");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[crate::rt_test]
+    async fn test_conditional_and_ranges() {
+        let dir = test_dir("ranges");
+        let srv = init_service(App::new().service(Files::new("/", &dir))).await;
+
+        let req = TestRequest::with_uri("/test.txt").to_request();
+        let resp = call_service(&srv, req).await;
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+        let last_modified = resp.headers().get(header::LAST_MODIFIED).unwrap().clone();
+
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert!(read_body(resp).await.is_empty());
+
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::IF_MODIFIED_SINCE, last_modified.clone())
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::IF_MATCH, "\"other\"")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::IF_UNMODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:37 GMT")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+        // ranges
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::RANGE, "bytes=2-5")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 2-5/10"
+        );
+        assert_eq!(read_body(resp).await, "2345");
+
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::RANGE, "bytes=-3")
+            .header(header::IF_RANGE, etag.clone())
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(read_body(resp).await, "789");
+
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::RANGE, "bytes=-3")
+            .header(header::IF_RANGE, last_modified)
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+
+        // if-range does not match, full content
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::RANGE, "bytes=-3")
+            .header(header::IF_RANGE, HeaderValue::from_static("\"other\""))
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(read_body(resp).await, "0123456789");
+
+        let req = TestRequest::with_uri("/test.txt")
+            .header(header::RANGE, "bytes=10-")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes */10"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_named_file() {
+        let dir = test_dir("named");
+        let file = NamedFile::open(dir.join("test.txt")).unwrap();
+        assert_eq!(file.path(), dir.join("test.txt"));
+        assert_eq!(file.content_type().essence_str(), "text/plain");
+        assert!(file.etag().is_some());
+        assert!(file.last_modified().is_some());
+        assert!(format!("{:?}", file).contains("NamedFile"));
+
+        let file = file.set_content_type(mime::APPLICATION_OCTET_STREAM);
+        assert_eq!(file.content_type(), &mime::APPLICATION_OCTET_STREAM);
+
+        let req = TestRequest::default().to_http_request();
+        let res = file
+            .use_etag(false)
+            .use_last_modified(false)
+            .set_content_disposition(HeaderValue::from_static("attachment"))
+            .into_response(&req);
+        assert!(!res.headers().contains_key(header::ETAG));
+        assert!(!res.headers().contains_key(header::LAST_MODIFIED));
+        assert_eq!(
+            res.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment"
+        );
+
+        assert!(NamedFile::open(dir.join("sub")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use std::{fs::File, fs::Metadata, io, path::Path, path::PathBuf, time::SystemTime};
+
+use mime::Mime;
+
+use crate::http::body::Body;
+use crate::http::conditional::{self, header_str, Precondition};
+use crate::http::header::{self, HeaderValue};
+use crate::http::{Response, StatusCode};
+use crate::web::error::ErrorRenderer;
+use crate::web::responder::{Ready, Responder};
+use crate::web::HttpRequest;
+
+use super::range::HttpRange;
+
+/// A file with an associated name.
+///
+/// `NamedFile` responds with file's content, supports conditional
+/// requests (`If-Match`, `If-None-Match`, `If-Modified-Since`,
+/// `If-Unmodified-Since`) and single byte range requests (`Range`, `If-Range`).
+///
+/// ```rust
+/// use ntex::web::{self, fs::NamedFile};
+///
+/// async fn index() -> std::io::Result<NamedFile> {
+///     NamedFile::open("static/index.html")
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct NamedFile {
+    path: PathBuf,
+    file: File,
+    md: Metadata,
+    modified: Option<SystemTime>,
+    content_type: Mime,
+    content_disposition: Option<HeaderValue>,
+    use_etag: bool,
+    use_last_modified: bool,
+}
+
+impl NamedFile {
+    /// Attempts to open a file in read-only mode.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<NamedFile> {
+        let file = File::open(&path)?;
+        Self::from_file(file, path)
+    }
+
+    /// Creates an instance from a previously opened file.
+    ///
+    /// `path` is used for content type guessing.
+    pub fn from_file<P: AsRef<Path>>(file: File, path: P) -> io::Result<NamedFile> {
+        let md = file.metadata()?;
+        if !md.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Path is not a file",
+            ));
+        }
+        let content_type = guess_mime_type(path.as_ref());
+
+        Ok(NamedFile {
+            path: path.as_ref().to_path_buf(),
+            modified: md.modified().ok(),
+            file,
+            md,
+            content_type,
+            content_disposition: None,
+            use_etag: true,
+            use_last_modified: true,
+        })
+    }
+
+    /// Returns reference to the underlying `File` object.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Retrieve the path of this file.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Content type of the file, it is guessed from file extension
+    pub fn content_type(&self) -> &Mime {
+        &self.content_type
+    }
+
+    /// Set the `Content-Type` for serving this file.
+    pub fn set_content_type(mut self, mime_type: Mime) -> Self {
+        self.content_type = mime_type;
+        self
+    }
+
+    /// Set the `Content-Disposition` for serving this file.
+    ///
+    /// By default `Content-Disposition` header is not set.
+    pub fn set_content_disposition(mut self, value: HeaderValue) -> Self {
+        self.content_disposition = Some(value);
+        self
+    }
+
+    /// Specifies whether to use `ETag` or not.
+    ///
+    /// Default is true.
+    pub fn use_etag(mut self, value: bool) -> Self {
+        self.use_etag = value;
+        self
+    }
+
+    /// Specifies whether to use `Last-Modified` or not.
+    ///
+    /// Default is true.
+    pub fn use_last_modified(mut self, value: bool) -> Self {
+        self.use_last_modified = value;
+        self
+    }
+
+    /// Entity tag of the file, it is based on file size and modification time
+    pub fn etag(&self) -> Option<String> {
+        self.modified.map(|modified| {
+            let dur = modified
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            #[cfg(unix)]
+            let ino = std::os::unix::fs::MetadataExt::ino(&self.md);
+            #[cfg(not(unix))]
+            let ino = 0;
+
+            format!(
+                "\"{:x}-{:x}-{:x}-{:x}\"",
+                ino,
+                self.md.len(),
+                dur.as_secs(),
+                dur.subsec_nanos()
+            )
+        })
+    }
+
+    /// Last modification time of the file
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Create response for the request
+    pub fn into_response(self, req: &HttpRequest) -> Response {
+        let etag = if self.use_etag { self.etag() } else { None };
+        let last_modified = if self.use_last_modified {
+            self.modified.map(httpdate::HttpDate::from)
+        } else {
+            None
+        };
+
+        let precondition = conditional::evaluate(
+            req.headers(),
+            req.method(),
+            etag.as_deref(),
+            last_modified,
+        );
+
+        let mut res = Response::build(StatusCode::OK);
+        if let Some(ref etag) = etag {
+            res.header(header::ETAG, etag.as_str());
+        }
+        if let Some(lm) = last_modified {
+            res.header(header::LAST_MODIFIED, lm.to_string());
+        }
+
+        match precondition {
+            Precondition::Passed => (),
+            Precondition::NotModified => {
+                return res.status(StatusCode::NOT_MODIFIED).body(Body::None)
+            }
+            Precondition::Failed => {
+                return res.status(StatusCode::PRECONDITION_FAILED).finish()
+            }
+        }
+
+        res.header(header::CONTENT_TYPE, self.content_type.to_string())
+            .header(header::ACCEPT_RANGES, "bytes");
+        if let Some(value) = self.content_disposition {
+            res.header(header::CONTENT_DISPOSITION, value);
+        }
+
+        // byte range
+        let size = self.md.len();
+        let mut range = 0..size;
+        if let Some(val) = header_str(req.headers(), &header::RANGE) {
+            if conditional::if_range_matches(req.headers(), etag.as_deref(), last_modified)
+            {
+                if let Ok(ranges) = HttpRange::parse(val, size) {
+                    // only first range is supported
+                    range = ranges[0].to_range();
+                    res.status(StatusCode::PARTIAL_CONTENT)
+                        .header(header::CONTENT_RANGE, ranges[0].content_range(size));
+                } else {
+                    return res
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                        .finish();
+                }
+            }
+        }
+
+        res.body(Body::from_file_range(self.file, range))
+    }
+}
+
+impl<Err: ErrorRenderer> Responder<Err> for NamedFile {
+    type Error = Err::Container;
+    type Future = Ready<Response>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        self.into_response(req).into()
+    }
+}
+
+/// Guess mime type from file extension, text types use utf-8 charset
+fn guess_mime_type(path: &Path) -> Mime {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    if mime.type_() == mime::TEXT && mime.get_param(mime::CHARSET).is_none() {
+        format!("{}; charset=utf-8", mime).parse().unwrap_or(mime)
+    } else {
+        mime
+    }
+}
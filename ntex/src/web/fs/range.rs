@@ -0,0 +1,127 @@
+/// Byte range of the `Range` request header
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HttpRange {
+    /// First byte of the range
+    pub start: u64,
+    /// Length of the range
+    pub length: u64,
+}
+
+impl HttpRange {
+    /// Parse `Range` header value.
+    ///
+    /// Returns list of satisfiable ranges for the content of `size` bytes.
+    /// Returns error if header is malformed or if none of the ranges are
+    /// satisfiable.
+    ///
+    /// ```rust
+    /// use ntex::web::fs::HttpRange;
+    ///
+    /// let ranges = HttpRange::parse("bytes=0-9, -5", 100).unwrap();
+    /// assert_eq!(ranges[0], HttpRange { start: 0, length: 10 });
+    /// assert_eq!(ranges[1], HttpRange { start: 95, length: 5 });
+    /// assert!(HttpRange::parse("bytes=100-", 100).is_err());
+    /// ```
+    pub fn parse(header: &str, size: u64) -> Result<Vec<HttpRange>, InvalidRange> {
+        let ranges = header.trim().strip_prefix("bytes=").ok_or(InvalidRange)?;
+
+        let mut result = Vec::new();
+        for range in ranges.split(',').map(|r| r.trim()) {
+            if range.is_empty() {
+                continue;
+            }
+            let (start, end) = range.split_once('-').ok_or(InvalidRange)?;
+            let (start, end) = (start.trim(), end.trim());
+
+            if start.is_empty() {
+                // suffix range, last N bytes
+                let length = end.parse::<u64>().map_err(|_| InvalidRange)?;
+                if length > 0 && size > 0 {
+                    let length = std::cmp::min(length, size);
+                    result.push(HttpRange {
+                        start: size - length,
+                        length,
+                    });
+                }
+            } else {
+                let start = start.parse::<u64>().map_err(|_| InvalidRange)?;
+                let end = if end.is_empty() {
+                    None
+                } else {
+                    Some(end.parse::<u64>().map_err(|_| InvalidRange)?)
+                };
+                if end.map(|end| end < start).unwrap_or(false) {
+                    return Err(InvalidRange);
+                }
+                if start < size {
+                    let end = end
+                        .map(|end| std::cmp::min(end, size - 1))
+                        .unwrap_or(size - 1);
+                    result.push(HttpRange {
+                        start,
+                        length: end - start + 1,
+                    });
+                }
+            }
+        }
+
+        if result.is_empty() {
+            Err(InvalidRange)
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Byte range of the content
+    pub fn to_range(&self) -> std::ops::Range<u64> {
+        self.start..self.start + self.length
+    }
+
+    /// `Content-Range` header value for the content of `size` bytes
+    pub fn content_range(&self, size: u64) -> String {
+        format!(
+            "bytes {}-{}/{}",
+            self.start,
+            self.start + self.length - 1,
+            size
+        )
+    }
+}
+
+/// `Range` header is malformed or not satisfiable
+#[derive(Copy, Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Range header is invalid or not satisfiable")]
+pub struct InvalidRange;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let r = |start, length| HttpRange { start, length };
+
+        assert_eq!(HttpRange::parse("bytes=0-0", 10).unwrap(), vec![r(0, 1)]);
+        assert_eq!(HttpRange::parse("bytes=2-", 10).unwrap(), vec![r(2, 8)]);
+        assert_eq!(HttpRange::parse("bytes=2-100", 10).unwrap(), vec![r(2, 8)]);
+        assert_eq!(HttpRange::parse("bytes=-3", 10).unwrap(), vec![r(7, 3)]);
+        assert_eq!(HttpRange::parse("bytes=-30", 10).unwrap(), vec![r(0, 10)]);
+        assert_eq!(
+            HttpRange::parse("bytes= 0-1, 20-30 ,4-5", 10).unwrap(),
+            vec![r(0, 2), r(4, 2)]
+        );
+
+        assert!(HttpRange::parse("", 10).is_err());
+        assert!(HttpRange::parse("bytes=", 10).is_err());
+        assert!(HttpRange::parse("bits=0-1", 10).is_err());
+        assert!(HttpRange::parse("bytes=5-2", 10).is_err());
+        assert!(HttpRange::parse("bytes=a-2", 10).is_err());
+        assert!(HttpRange::parse("bytes=10-", 10).is_err());
+        assert!(HttpRange::parse("bytes=-0", 10).is_err());
+        assert!(HttpRange::parse("bytes=0-", 0).is_err());
+
+        let range = HttpRange::parse("bytes=2-5,7-8", 10).unwrap()[0];
+        assert_eq!(range.to_range(), 2..6);
+        assert_eq!(range.content_range(10), "bytes 2-5/10");
+    }
+}
@@ -1,11 +1,15 @@
+use std::panic::{self, AssertUnwindSafe};
 use std::{future::Future, marker::PhantomData, pin::Pin, task::Context, task::Poll};
 
+use crate::http::error::PanicError;
+
 use super::error::ErrorRenderer;
 use super::extract::FromRequest;
 use super::httprequest::HttpRequest;
 use super::request::WebRequest;
 use super::responder::Responder;
 use super::response::WebResponse;
+use super::HttpResponse;
 
 /// Async fn handler
 pub trait Handler<T, Err>: Clone + 'static
@@ -40,6 +44,12 @@ pub(super) trait HandlerFn<Err: ErrorRenderer> {
     ) -> Pin<Box<dyn Future<Output = Result<WebResponse, Err::Container>>>>;
 
     fn clone_handler(&self) -> Box<dyn HandlerFn<Err>>;
+
+    /// Type name of the handler's extractor
+    fn extractor_type(&self) -> &'static str;
+
+    /// Type name of the handler's response
+    fn responder_type(&self) -> &'static str;
 }
 
 pub(super) struct HandlerWrapper<F, T, Err>
@@ -99,6 +109,14 @@ where
             _t: PhantomData,
         })
     }
+
+    fn extractor_type(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn responder_type(&self) -> &'static str {
+        std::any::type_name::<F::Output>()
+    }
 }
 
 pin_project_lite::pin_project! {
@@ -132,6 +150,39 @@ where
     type Output = Result<WebResponse, Err::Container>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let res = panic::catch_unwind(AssertUnwindSafe(|| self.as_mut().poll_handler(cx)));
+        let payload = match res {
+            Ok(res) => return res,
+            Err(payload) => payload,
+        };
+
+        let req = match self.as_mut().project().req.take() {
+            Some(req) => req,
+            None => panic::resume_unwind(payload),
+        };
+        if !req.app_state::<PanicConfig>().map_or(true, |cfg| cfg.catch) {
+            panic::resume_unwind(payload)
+        }
+        let _ = PanicError::new(&*payload);
+        Poll::Ready(Ok(WebResponse::new(
+            HttpResponse::InternalServerError().finish(),
+            req,
+        )))
+    }
+}
+
+impl<F, T, Err> HandlerWrapperResponse<F, T, Err>
+where
+    F: Handler<T, Err>,
+    T: FromRequest<Err>,
+    T::Error: Into<Err::Container>,
+    <F::Output as Responder<Err>>::Error: Into<Err::Container>,
+    Err: ErrorRenderer,
+{
+    fn poll_handler(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<WebResponse, Err::Container>> {
         let mut this = self.as_mut().project();
 
         if let Some(fut) = this.from_request.as_pin_mut() {
@@ -141,13 +192,14 @@ where
                     this = self.as_mut().project();
                     this.from_request.set(None);
                     this.handler.set(Some(fut));
-                    self.poll(cx)
+                    self.poll_handler(cx)
                 }
                 Poll::Pending => Poll::Pending,
-                Poll::Ready(Err(e)) => Poll::Ready(Ok(WebResponse::from_err::<Err, _>(
-                    e,
-                    this.req.take().unwrap(),
-                ))),
+                Poll::Ready(Err(e)) => {
+                    let req = this.req.take().unwrap();
+                    super::middleware::capture_extractor(&req, std::any::type_name::<T>());
+                    Poll::Ready(Ok(WebResponse::from_err::<Err, _>(e, req)))
+                }
             };
         }
 
@@ -158,7 +210,7 @@ where
                     this = self.as_mut().project();
                     this.handler.set(None);
                     this.responder.set(Some(fut));
-                    self.poll(cx)
+                    self.poll_handler(cx)
                 }
                 Poll::Pending => Poll::Pending,
             };
@@ -177,6 +229,43 @@ where
     }
 }
 
+/// Handler panics configuration
+///
+/// By default panic in a handler is caught, logged and converted
+/// to `500 Internal Server Error` response. If catching is disabled,
+/// panic is propagated to http dispatcher, which catches it according
+/// to `HttpServiceBuilder::catch_panics()` setting. Configuration
+/// is set as application state.
+///
+/// ```rust
+/// use ntex::web::{self, App, PanicConfig};
+///
+/// fn main() {
+///     // fail fast, panic is propagated to http layer
+///     let app = App::new()
+///         .app_state(PanicConfig::default().catch(false))
+///         .service(web::resource("/").to(|| async { "ok" }));
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct PanicConfig {
+    catch: bool,
+}
+
+impl Default for PanicConfig {
+    fn default() -> Self {
+        PanicConfig { catch: true }
+    }
+}
+
+impl PanicConfig {
+    /// Catch panics in handlers and extractors, enabled by default.
+    pub fn catch(mut self, enabled: bool) -> Self {
+        self.catch = enabled;
+        self
+    }
+}
+
 /// FromRequest trait impl for tuples
 macro_rules! factory_tuple ({ $(($n:tt, $T:ident)),+} => {
     impl<Func, $($T,)+ Res, Err> Handler<($($T,)+), Err> for Func
@@ -0,0 +1,315 @@
+//! Health-check and readiness endpoints
+use std::{fmt, future::Future, pin::Pin, rc::Rc};
+
+use serde_json::{json, Map, Value};
+
+use crate::http::StatusCode;
+use crate::time::{timeout_checked, Millis};
+use crate::util::join_all;
+
+use super::error::ErrorRenderer;
+use super::service::{WebServiceConfig, WebServiceFactory};
+use super::{resource, HttpResponse};
+
+type ProbeFn = Rc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), String>>>>>;
+
+/// Health-check and readiness endpoints.
+///
+/// `Health` serves liveness probes on `/healthz` and readiness probes on `/readyz`.
+/// Endpoint runs all registered probes and responds with `200 OK` if all probes
+/// succeed, otherwise with `503 Service Unavailable`. Response body is json
+/// object with result of each probe.
+///
+/// While server worker gracefully shuts down, readiness endpoint responds
+/// with `503 Service Unavailable` without running probes, so load balancers
+/// stop sending new requests before connections get closed.
+///
+/// ```rust
+/// use ntex::web::{self, health::Health, App};
+///
+/// async fn check_db() -> Result<(), String> {
+///     Ok(())
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .service(
+///             Health::new()
+///                 .liveness("app", || async { Ok::<_, String>(()) })
+///                 .readiness("db", check_db),
+///         )
+///         .service(web::resource("/").to(|| async { "ok" }));
+/// }
+/// ```
+pub struct Health {
+    live_path: String,
+    ready_path: String,
+    live: Vec<(String, ProbeFn)>,
+    ready: Vec<(String, ProbeFn)>,
+    timeout: Millis,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health {
+            live_path: "/healthz".to_string(),
+            ready_path: "/readyz".to_string(),
+            live: Vec::new(),
+            ready: Vec::new(),
+            timeout: Millis(5_000),
+        }
+    }
+}
+
+impl Health {
+    /// Create health endpoints without probes
+    pub fn new() -> Self {
+        Health::default()
+    }
+
+    /// Set path of liveness endpoint. By default it is `/healthz`
+    pub fn liveness_path<T: Into<String>>(mut self, path: T) -> Self {
+        self.live_path = path.into();
+        self
+    }
+
+    /// Set path of readiness endpoint. By default it is `/readyz`
+    pub fn readiness_path<T: Into<String>>(mut self, path: T) -> Self {
+        self.ready_path = path.into();
+        self
+    }
+
+    /// Set probe timeout. By default timeout is 5 seconds.
+    ///
+    /// Probe that does not complete in time is considered as failed.
+    /// Zero value disables timeout.
+    pub fn timeout<T: Into<Millis>>(mut self, timeout: T) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Register liveness probe
+    pub fn liveness<F, R, E>(mut self, name: &str, probe: F) -> Self
+    where
+        F: Fn() -> R + 'static,
+        R: Future<Output = Result<(), E>> + 'static,
+        E: fmt::Display,
+    {
+        self.live.push((name.to_string(), boxed(probe)));
+        self
+    }
+
+    /// Register readiness probe
+    pub fn readiness<F, R, E>(mut self, name: &str, probe: F) -> Self
+    where
+        F: Fn() -> R + 'static,
+        R: Future<Output = Result<(), E>> + 'static,
+        E: fmt::Display,
+    {
+        self.ready.push((name.to_string(), boxed(probe)));
+        self
+    }
+}
+
+fn boxed<F, R, E>(probe: F) -> ProbeFn
+where
+    F: Fn() -> R + 'static,
+    R: Future<Output = Result<(), E>> + 'static,
+    E: fmt::Display,
+{
+    Rc::new(move || {
+        let fut = probe();
+        Box::pin(async move { fut.await.map_err(|e| e.to_string()) })
+    })
+}
+
+impl fmt::Debug for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = |probes: &[(String, ProbeFn)]| {
+            probes
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>()
+        };
+        f.debug_struct("Health")
+            .field("liveness_path", &self.live_path)
+            .field("readiness_path", &self.ready_path)
+            .field("liveness", &names(&self.live))
+            .field("readiness", &names(&self.ready))
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<Err: ErrorRenderer> WebServiceFactory<Err> for Health {
+    fn register(self, config: &mut WebServiceConfig<Err>) {
+        let timeout = self.timeout;
+        let live = Rc::new(self.live);
+        let ready = Rc::new(self.ready);
+
+        let live = resource(self.live_path.as_str())
+            .to(move || check(live.clone(), timeout, false));
+        WebServiceFactory::register(live, config);
+
+        let ready = resource(self.ready_path.as_str())
+            .to(move || check(ready.clone(), timeout, true));
+        WebServiceFactory::register(ready, config);
+    }
+}
+
+/// Run probes and render response
+async fn check(
+    probes: Rc<Vec<(String, ProbeFn)>>,
+    timeout: Millis,
+    readiness: bool,
+) -> HttpResponse {
+    if readiness && crate::server::is_stopping() {
+        return response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({"status": "draining", "checks": {}}),
+        );
+    }
+
+    let results = join_all(
+        probes
+            .iter()
+            .map(|(_, probe)| timeout_checked(timeout, probe())),
+    )
+    .await;
+
+    let mut ok = true;
+    let mut checks = Map::new();
+    for ((name, _), result) in probes.iter().zip(results) {
+        let check = match result {
+            Ok(Ok(())) => json!({"status": "up"}),
+            Ok(Err(e)) => {
+                ok = false;
+                json!({"status": "down", "error": e})
+            }
+            Err(_) => {
+                ok = false;
+                json!({"status": "down", "error": "timeout"})
+            }
+        };
+        checks.insert(name.clone(), check);
+    }
+
+    if ok {
+        response(StatusCode::OK, json!({"status": "up", "checks": checks}))
+    } else {
+        response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({"status": "down", "checks": checks}),
+        )
+    }
+}
+
+fn response(status: StatusCode, body: Value) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{App, WebResponse};
+
+    async fn get<S, E>(srv: &S, path: &str) -> (StatusCode, Value)
+    where
+        S: crate::Service<crate::http::Request, Response = WebResponse, Error = E>,
+        E: fmt::Debug,
+    {
+        let resp = call_service(srv, TestRequest::with_uri(path).to_request()).await;
+        let status = resp.status();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        (
+            status,
+            serde_json::from_slice(&read_body(resp).await).unwrap(),
+        )
+    }
+
+    #[crate::rt_test]
+    async fn test_health() {
+        let srv = init_service(
+            App::new().service(
+                Health::new()
+                    .liveness("app", || async { Ok::<_, String>(()) })
+                    .readiness("db", || async { Ok::<_, String>(()) })
+                    .readiness("cache", || async { Err("connection refused") }),
+            ),
+        )
+        .await;
+
+        let (status, body) = get(&srv, "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({"status": "up", "checks": {"app": {"status": "up"}}})
+        );
+
+        let (status, body) = get(&srv, "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            body,
+            json!({"status": "down", "checks": {
+                "db": {"status": "up"},
+                "cache": {"status": "down", "error": "connection refused"},
+            }})
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_timeout_and_paths() {
+        let srv = init_service(
+            App::new().service(
+                Health::new()
+                    .liveness_path("/live")
+                    .readiness_path("/ready")
+                    .readiness("slow", || async {
+                        crate::time::sleep(Millis(500)).await;
+                        Ok::<_, String>(())
+                    })
+                    .timeout(Millis(10)),
+            ),
+        )
+        .await;
+
+        let (status, body) = get(&srv, "/live").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"status": "up", "checks": {}}));
+
+        let (status, body) = get(&srv, "/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["checks"]["slow"]["error"], "timeout");
+
+        let resp = call_service(&srv, TestRequest::with_uri("/healthz").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[crate::rt_test]
+    async fn test_draining() {
+        let srv = init_service(
+            App::new()
+                .service(Health::new().readiness("db", || async { Ok::<_, String>(()) })),
+        )
+        .await;
+
+        let (status, _) = get(&srv, "/readyz").await;
+        assert_eq!(status, StatusCode::OK);
+
+        crate::server::set_stopping(true);
+        let (status, body) = get(&srv, "/readyz").await;
+        let (live, _) = get(&srv, "/healthz").await;
+        crate::server::set_stopping(false);
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, json!({"status": "draining", "checks": {}}));
+        assert_eq!(live, StatusCode::OK);
+    }
+}
@@ -1,7 +1,7 @@
-use std::{cell::Ref, cell::RefCell, cell::RefMut, fmt, net, rc::Rc};
+use std::{cell::Ref, cell::RefCell, cell::RefMut, fmt, io, net, rc::Rc};
 
 use crate::http::{
-    HeaderMap, HttpMessage, Message, Method, Payload, RequestHead, Uri, Version,
+    HeaderMap, HttpMessage, Message, Method, Payload, RequestHead, StatusCode, Uri, Version,
 };
 use crate::io::{types, IoRef};
 use crate::router::Path;
@@ -22,6 +22,7 @@ pub(crate) struct HttpRequestInner {
     pub(crate) path: Path<Uri>,
     pub(crate) payload: Payload,
     pub(crate) app_state: Rc<Extensions>,
+    pub(crate) resource_ids: Vec<u16>,
     rmap: Rc<ResourceMap>,
     config: AppConfig,
     pool: &'static HttpRequestPool,
@@ -43,6 +44,7 @@ impl HttpRequest {
             path,
             payload,
             app_state,
+            resource_ids: Vec::new(),
             rmap,
             config,
             pool,
@@ -112,6 +114,18 @@ impl HttpRequest {
         self.head().io.as_ref()
     }
 
+    /// Send informational (1xx) response, i.e. `103 Early Hints`.
+    ///
+    /// Informational response must be sent before final response.
+    /// HTTP/2 protocol is not supported.
+    pub fn send_informational(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> io::Result<()> {
+        self.head().send_informational(status, headers)
+    }
+
     /// Peer socket address
     ///
     /// Peer address is actual socket address, if proxy is used in front of
@@ -182,6 +196,46 @@ impl HttpRequest {
         self.0.rmap.url_for(self, name, elements)
     }
 
+    #[cfg(feature = "url")]
+    /// Generate url for typed named resource
+    ///
+    /// This method is similar to `HttpRequest::url_for()` but resource
+    /// name and path parameters are provided by `NamedResource` type.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, App, HttpRequest, HttpResponse, NamedResource};
+    ///
+    /// struct UserUrl {
+    ///     id: u32,
+    /// }
+    ///
+    /// impl NamedResource for UserUrl {
+    ///     const NAME: &'static str = "user";
+    ///     const PARAMS: &'static [&'static str] = &["id"];
+    ///
+    ///     fn params(&self) -> Vec<String> {
+    ///         vec![self.id.to_string()]
+    ///     }
+    /// }
+    ///
+    /// async fn index(req: HttpRequest) -> HttpResponse {
+    ///     let url = req.url_for_typed(&UserUrl { id: 7 }); // <- generate url for "user" resource
+    ///     HttpResponse::Ok().into()
+    /// }
+    ///
+    /// fn main() {
+    ///     let app = App::new()
+    ///         .named_resource::<UserUrl>() // <- validate "user" resource on startup
+    ///         .service(web::resource("/user/{id}").name("user").to(index));
+    /// }
+    /// ```
+    pub fn url_for_typed<T: super::rmap::NamedResource>(
+        &self,
+        resource: &T,
+    ) -> Result<url_pkg::Url, super::error::UrlGenerationError> {
+        self.0.rmap.url_for_typed(self, resource)
+    }
+
     #[cfg(feature = "url")]
     /// Generate url for named resource
     ///
@@ -201,6 +255,29 @@ impl HttpRequest {
         &self.0.rmap
     }
 
+    /// Pattern of the matched resource, including prefixes of parent scopes.
+    ///
+    /// Returns `None` if request is not matched to a resource,
+    /// i.e. it is handled by default service.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, App, HttpRequest};
+    ///
+    /// async fn index(req: HttpRequest) -> String {
+    ///     // "/users/{id}"
+    ///     req.match_pattern().unwrap()
+    /// }
+    ///
+    /// fn main() {
+    ///     let app = App::new().service(
+    ///         web::scope("/users").service(web::resource("/{id}").to(index)),
+    ///     );
+    /// }
+    /// ```
+    pub fn match_pattern(&self) -> Option<String> {
+        self.0.rmap.match_pattern(&self.0.resource_ids)
+    }
+
     /// Get *ConnectionInfo* for the current request.
     ///
     /// This method panics if request's extensions container is already
@@ -498,6 +575,48 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 
+    #[crate::rt_test]
+    async fn test_match_pattern() {
+        let srv = init_service(
+            App::new()
+                .service(
+                    web::scope("/users")
+                        .service(web::resource("/{id}").to(|req: HttpRequest| async move {
+                            req.match_pattern().unwrap()
+                        }))
+                        .service(web::scope("/{id}/posts").service(
+                            web::resource("/{post}").to(|req: HttpRequest| async move {
+                                req.match_pattern().unwrap()
+                            }),
+                        )),
+                )
+                .service(
+                    web::resource("/")
+                        .to(|req: HttpRequest| async move { req.match_pattern().unwrap() }),
+                )
+                .default_service(web::to(|req: HttpRequest| async move {
+                    assert!(req.match_pattern().is_none());
+                    HttpResponse::NotFound()
+                })),
+        )
+        .await;
+
+        for (uri, pattern) in &[
+            ("/users/1", "/users/{id}"),
+            ("/users/1/posts/2", "/users/{id}/posts/{post}"),
+            ("/", "/"),
+        ] {
+            let req = TestRequest::with_uri(uri).to_request();
+            let resp = call_service(&srv, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(crate::web::test::read_body(resp).await, *pattern);
+        }
+
+        let req = TestRequest::with_uri("/unknown").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
     #[crate::rt_test]
     async fn test_extensions_dropped() {
         struct Tracker {
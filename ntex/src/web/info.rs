@@ -1,4 +1,4 @@
-use std::cell::Ref;
+use std::{cell::Ref, net::IpAddr, net::SocketAddr};
 
 use crate::http::header::{self, HeaderName};
 use crate::http::RequestHead;
@@ -7,6 +7,7 @@ use crate::web::config::AppConfig;
 const X_FORWARDED_FOR: &[u8] = b"x-forwarded-for";
 const X_FORWARDED_HOST: &[u8] = b"x-forwarded-host";
 const X_FORWARDED_PROTO: &[u8] = b"x-forwarded-proto";
+const X_REAL_IP: &[u8] = b"x-real-ip";
 
 /// `HttpRequest` connection information
 #[derive(Debug, Clone, Default)]
@@ -26,15 +27,36 @@ impl ConnectionInfo {
         Ref::map(req.extensions(), |e| e.get().unwrap())
     }
 
-    #[allow(clippy::cognitive_complexity)]
     fn new(req: &RequestHead, cfg: &AppConfig) -> ConnectionInfo {
+        ConnectionInfo::resolve(req, req.peer_addr(), cfg)
+    }
+
+    #[allow(clippy::cognitive_complexity)]
+    fn resolve(
+        req: &RequestHead,
+        peer_addr: Option<SocketAddr>,
+        cfg: &AppConfig,
+    ) -> ConnectionInfo {
         let mut host = None;
         let mut scheme = None;
         let mut remote = None;
         let mut peer = None;
 
+        // forwarding headers are ignored if peer is not trusted proxy
+        let trusted = cfg.trusted_proxies().map(|proxies| {
+            (
+                proxies,
+                peer_addr.map_or(false, |addr| proxies.is_trusted(addr.ip())),
+            )
+        });
+        let forwarded = trusted.map_or(true, |(_, trusted)| trusted);
+
         // load forwarded header
-        for hdr in req.headers.get_all(&header::FORWARDED) {
+        for hdr in req
+            .headers
+            .get_all(&header::FORWARDED)
+            .filter(|_| forwarded)
+        {
             if let Ok(val) = hdr.to_str() {
                 for pair in val.split(';') {
                     for el in pair.split(',') {
@@ -71,6 +93,7 @@ impl ConnectionInfo {
             if let Some(h) = req
                 .headers
                 .get(&HeaderName::from_lowercase(X_FORWARDED_PROTO).unwrap())
+                .filter(|_| forwarded)
             {
                 if let Ok(h) = h.to_str() {
                     scheme = h.split(',').next().map(|v| v.trim());
@@ -89,6 +112,7 @@ impl ConnectionInfo {
             if let Some(h) = req
                 .headers
                 .get(&HeaderName::from_lowercase(X_FORWARDED_HOST).unwrap())
+                .filter(|_| forwarded)
             {
                 if let Ok(h) = h.to_str() {
                     host = h.split(',').next().map(|v| v.trim());
@@ -108,26 +132,33 @@ impl ConnectionInfo {
         }
 
         // remote addr
-        if remote.is_none() {
-            if let Some(h) = req
-                .headers
-                .get(&HeaderName::from_lowercase(X_FORWARDED_FOR).unwrap())
-            {
-                if let Ok(h) = h.to_str() {
-                    remote = h.split(',').next().map(|v| v.trim());
+        let remote = match trusted {
+            Some((proxies, true)) => proxies.remote(req),
+            Some((_, false)) => None,
+            None => {
+                if remote.is_none() {
+                    if let Some(h) = req
+                        .headers
+                        .get(&HeaderName::from_lowercase(X_FORWARDED_FOR).unwrap())
+                    {
+                        if let Ok(h) = h.to_str() {
+                            remote = h.split(',').next().map(|v| v.trim());
+                        }
+                    }
                 }
+                remote.map(|s| s.to_owned())
             }
-            if remote.is_none() {
-                // get peeraddr from socketaddr
-                peer = req.peer_addr().map(|addr| format!("{}", addr));
-            }
+        };
+        if remote.is_none() {
+            // get peeraddr from socketaddr
+            peer = peer_addr.map(|addr| format!("{}", addr));
         }
 
         ConnectionInfo {
             peer,
+            remote,
             scheme: scheme.unwrap_or("http").to_owned(),
             host: host.unwrap_or("localhost").to_owned(),
-            remote: remote.map(|s| s.to_owned()),
         }
     }
 
@@ -164,10 +195,14 @@ impl ConnectionInfo {
     /// - X-Forwarded-For
     /// - peer name of opened socket
     ///
+    /// If [TrustedProxies](../struct.TrustedProxies.html) are configured, headers
+    /// are used only for requests received from trusted proxies, headers are
+    /// resolved in configured order.
+    ///
     /// # Security
     /// Do not use this function for security purposes, unless you can ensure the Forwarded and
-    /// X-Forwarded-For headers cannot be spoofed by the client. If you want the client's socket
-    /// address explicitly, use
+    /// X-Forwarded-For headers cannot be spoofed by the client, i.e. trusted proxies
+    /// are configured. If you want the client's socket address explicitly, use
     /// [`HttpRequest::peer_addr()`](../web/struct.HttpRequest.html#method.peer_addr) instead.
     #[inline]
     pub fn remote(&self) -> Option<&str> {
@@ -181,6 +216,204 @@ impl ConnectionInfo {
     }
 }
 
+/// Forwarding header
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ForwardedHeader {
+    /// `Forwarded` header, rfc7239
+    Forwarded,
+    /// `X-Forwarded-For` header
+    XForwardedFor,
+    /// `X-Real-IP` header
+    XRealIp,
+}
+
+/// Trusted proxies configuration.
+///
+/// Forwarding headers are used for connection information resolution only
+/// if request is received from one of trusted proxies. Remote address is
+/// resolved by walking list of forwarded addresses from the nearest proxy,
+/// first address that does not belong to trusted proxies is used as remote
+/// address. Number of walked addresses is limited by `depth`.
+///
+/// ```rust,no_run
+/// use ntex::web::{self, App, ForwardedHeader, HttpServer, TrustedProxies};
+///
+/// #[ntex::main]
+/// async fn main() -> std::io::Result<()> {
+///     HttpServer::new(|| App::new().service(web::resource("/").to(|| async { "ok" })))
+///         .trusted_proxies(
+///             TrustedProxies::new()
+///                 .proxy("10.0.0.0/8")
+///                 .proxy("fd00::/8")
+///                 .headers(&[ForwardedHeader::XForwardedFor])
+///                 .depth(2),
+///         )
+///         .bind("127.0.0.1:59090")?
+///         .run()
+///         .await
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TrustedProxies {
+    networks: Vec<(IpAddr, u8)>,
+    headers: Vec<ForwardedHeader>,
+    depth: usize,
+}
+
+impl Default for TrustedProxies {
+    fn default() -> Self {
+        TrustedProxies {
+            networks: Vec::new(),
+            headers: vec![ForwardedHeader::Forwarded, ForwardedHeader::XForwardedFor],
+            depth: 1,
+        }
+    }
+}
+
+impl TrustedProxies {
+    /// Create configuration without trusted proxies
+    pub fn new() -> Self {
+        TrustedProxies::default()
+    }
+
+    /// Add trusted proxy network, i.e. `10.0.0.0/8` or `192.168.1.1`.
+    ///
+    /// Panics if network is not valid ip address or cidr.
+    pub fn proxy(mut self, network: &str) -> Self {
+        let net = parse_network(network)
+            .unwrap_or_else(|| panic!("Invalid trusted proxy network: {:?}", network));
+        self.networks.push(net);
+        self
+    }
+
+    /// Set forwarding headers in order of preference.
+    ///
+    /// First header that contains forwarded addresses is used.
+    /// By default `Forwarded` and `X-Forwarded-For` headers are used.
+    pub fn headers(mut self, headers: &[ForwardedHeader]) -> Self {
+        self.headers = headers.to_vec();
+        self
+    }
+
+    /// Set max number of forwarded addresses to walk. By default depth is 1.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = std::cmp::max(depth, 1);
+        self
+    }
+
+    /// Check if address belongs to trusted proxies
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        let addr = canonical(addr);
+        self.networks.iter().any(|(net, prefix)| match (net, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                u32::from(*net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                u128::from(*net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        })
+    }
+
+    /// Resolve remote address of request received from trusted proxy
+    fn remote(&self, req: &RequestHead) -> Option<String> {
+        for hdr in &self.headers {
+            let addrs = forwarded_addrs(req, *hdr);
+            let mut remote = None;
+            for addr in addrs.into_iter().rev().take(self.depth) {
+                let trusted = parse_addr(addr).map_or(false, |addr| self.is_trusted(addr));
+                remote = Some(addr);
+                if !trusted {
+                    break;
+                }
+            }
+            if let Some(remote) = remote {
+                return Some(remote.to_owned());
+            }
+        }
+        None
+    }
+}
+
+/// List of forwarded addresses, from client to the nearest proxy
+fn forwarded_addrs(req: &RequestHead, hdr: ForwardedHeader) -> Vec<&str> {
+    let mut addrs = Vec::new();
+    match hdr {
+        ForwardedHeader::Forwarded => {
+            for val in req.headers.get_all(&header::FORWARDED) {
+                if let Ok(val) = val.to_str() {
+                    for el in val.split(',') {
+                        for pair in el.split(';') {
+                            let mut items = pair.trim().splitn(2, '=');
+                            if let (Some(name), Some(val)) = (items.next(), items.next()) {
+                                if name.trim().eq_ignore_ascii_case("for") {
+                                    addrs.push(val.trim());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ForwardedHeader::XForwardedFor | ForwardedHeader::XRealIp => {
+            let name = if hdr == ForwardedHeader::XRealIp {
+                X_REAL_IP
+            } else {
+                X_FORWARDED_FOR
+            };
+            let name = HeaderName::from_lowercase(name).unwrap();
+            for val in req.headers.get_all(&name) {
+                if let Ok(val) = val.to_str() {
+                    addrs
+                        .extend(val.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()));
+                }
+            }
+        }
+    }
+    addrs
+}
+
+/// Parse forwarded address, address could be quoted and could contain port
+fn parse_addr(addr: &str) -> Option<IpAddr> {
+    let addr = addr.trim_matches('"');
+    if let Ok(addr) = addr.parse::<IpAddr>() {
+        Some(addr)
+    } else if let Ok(addr) = addr.parse::<SocketAddr>() {
+        Some(addr.ip())
+    } else {
+        addr.strip_prefix('[')
+            .and_then(|s| s.split(']').next())
+            .and_then(|s| s.parse().ok())
+    }
+}
+
+fn parse_network(net: &str) -> Option<(IpAddr, u8)> {
+    let mut parts = net.trim().splitn(2, '/');
+    let addr = canonical(parts.next()?.parse().ok()?);
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match parts.next() {
+        Some(prefix) => prefix.parse().ok().filter(|p| *p <= max)?,
+        None => max,
+    };
+    Some((addr, prefix))
+}
+
+/// Ipv4-mapped ipv6 address is treated as ipv4 address
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, _, _] => {
+                let [.., a, b, c, d] = v6.octets();
+                IpAddr::from([a, b, c, d])
+            }
+            _ => addr,
+        },
+        addr => addr,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,4 +466,106 @@ mod tests {
         let info = req.connection_info();
         assert_eq!(info.scheme(), "https");
     }
+
+    #[test]
+    fn test_trusted_proxies() {
+        let proxies = TrustedProxies::new()
+            .proxy("10.0.0.0/8")
+            .proxy("192.168.1.1")
+            .proxy("fd00::/8");
+        assert!(proxies.is_trusted("10.1.2.3".parse().unwrap()));
+        assert!(proxies.is_trusted("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(proxies.is_trusted("192.168.1.1".parse().unwrap()));
+        assert!(!proxies.is_trusted("192.168.1.2".parse().unwrap()));
+        assert!(proxies.is_trusted("fd12::1".parse().unwrap()));
+        assert!(!proxies.is_trusted("fe80::1".parse().unwrap()));
+        assert!(TrustedProxies::new()
+            .proxy("0.0.0.0/0")
+            .is_trusted("1.2.3.4".parse().unwrap()));
+
+        assert_eq!(parse_network("10.0.0.0/33"), None);
+        assert_eq!(parse_network("10.0.0/8"), None);
+        assert_eq!(
+            parse_addr("\"[2001:db8::1]:4711\""),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(
+            parse_addr("192.0.2.60:8080"),
+            Some("192.0.2.60".parse().unwrap())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid trusted proxy network")]
+    fn test_invalid_proxy() {
+        let _ = TrustedProxies::new().proxy("10.0.0.0/40");
+    }
+
+    #[test]
+    fn test_trusted_remote() {
+        let cfg = AppConfig::default()
+            .with_trusted_proxies(Some(TrustedProxies::new().proxy("10.0.0.0/8").depth(2)));
+        let info = |req: crate::web::HttpRequest, peer| {
+            ConnectionInfo::resolve(req.head(), peer, &cfg)
+        };
+
+        // untrusted peer, headers are ignored
+        let peer = Some("192.0.2.1:1234".parse().unwrap());
+        let req = TestRequest::default()
+            .header(X_FORWARDED_FOR, "1.1.1.1")
+            .header(X_FORWARDED_PROTO, "https")
+            .header(X_FORWARDED_HOST, "example.com")
+            .to_http_request();
+        let info1 = info(req, peer);
+        assert_eq!(info1.remote(), Some("192.0.2.1:1234"));
+        assert_eq!(info1.scheme(), "http");
+        assert_eq!(info1.host(), "localhost:8080");
+
+        // client spoofs header, nearest untrusted address is used
+        let peer = Some("10.0.0.1:1234".parse().unwrap());
+        let req = TestRequest::default()
+            .header(X_FORWARDED_FOR, "1.1.1.1, 192.0.2.60, 10.0.0.2")
+            .header(X_FORWARDED_PROTO, "https")
+            .to_http_request();
+        let info1 = info(req, peer);
+        assert_eq!(info1.remote(), Some("192.0.2.60"));
+        assert_eq!(info1.scheme(), "https");
+
+        // depth limit
+        let peer = Some("10.0.0.1:1234".parse().unwrap());
+        let req = TestRequest::default()
+            .header(X_FORWARDED_FOR, "192.0.2.60, 10.0.0.3, 10.0.0.2")
+            .to_http_request();
+        assert_eq!(info(req, peer).remote(), Some("10.0.0.3"));
+
+        // forwarded header is preferred
+        let peer = Some("10.0.0.1:1234".parse().unwrap());
+        let req = TestRequest::default()
+            .header(X_FORWARDED_FOR, "192.0.2.60")
+            .header(header::FORWARDED, "for=\"[2001:db8::1]:4711\";proto=https")
+            .to_http_request();
+        assert_eq!(info(req, peer).remote(), Some("\"[2001:db8::1]:4711\""));
+
+        // header order
+        let cfg = AppConfig::default().with_trusted_proxies(Some(
+            TrustedProxies::new()
+                .proxy("10.0.0.0/8")
+                .headers(&[ForwardedHeader::XRealIp]),
+        ));
+        let peer = Some("10.0.0.1:1234".parse().unwrap());
+        let req = TestRequest::default()
+            .header(X_FORWARDED_FOR, "192.0.2.60")
+            .header(X_REAL_IP, "192.0.2.61")
+            .to_http_request();
+        let info2 = ConnectionInfo::resolve(req.head(), peer, &cfg);
+        assert_eq!(info2.remote(), Some("192.0.2.61"));
+
+        // no forwarded addresses
+        let peer = Some("10.0.0.1:1234".parse().unwrap());
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(
+            ConnectionInfo::resolve(req.head(), peer, &cfg).remote(),
+            Some("10.0.0.1:1234")
+        );
+    }
 }
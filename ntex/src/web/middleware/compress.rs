@@ -144,15 +144,20 @@ struct AcceptEncoding {
 impl Eq for AcceptEncoding {}
 
 impl Ord for AcceptEncoding {
-    #[allow(clippy::comparison_chain)]
+    /// Order by client's quality value, server preference is used
+    /// for encodings with equal quality values
     fn cmp(&self, other: &AcceptEncoding) -> cmp::Ordering {
-        if self.quality > other.quality {
-            cmp::Ordering::Less
-        } else if self.quality < other.quality {
-            cmp::Ordering::Greater
-        } else {
-            cmp::Ordering::Equal
-        }
+        other
+            .quality
+            .partial_cmp(&self.quality)
+            .unwrap_or(cmp::Ordering::Equal)
+            .then_with(|| {
+                other
+                    .encoding
+                    .quality()
+                    .partial_cmp(&self.encoding.quality())
+                    .unwrap_or(cmp::Ordering::Equal)
+            })
     }
 }
 
@@ -164,22 +169,30 @@ impl PartialOrd for AcceptEncoding {
 
 impl PartialEq for AcceptEncoding {
     fn eq(&self, other: &AcceptEncoding) -> bool {
-        self.quality == other.quality
+        self.cmp(other) == cmp::Ordering::Equal
     }
 }
 
 impl AcceptEncoding {
     fn new(tag: &str) -> Option<AcceptEncoding> {
-        let parts: Vec<&str> = tag.split(';').collect();
-        let encoding = match parts.len() {
-            0 => return None,
-            _ => ContentEncoding::from(parts[0]),
-        };
-        let quality = match parts.len() {
-            1 => encoding.quality(),
-            _ => f64::from_str(parts[1]).unwrap_or(0.0),
-        };
-        Some(AcceptEncoding { encoding, quality })
+        let mut parts = tag.split(';');
+        let name = parts.next()?;
+        let encoding = ContentEncoding::from(name);
+
+        // unsupported encoding
+        if encoding == ContentEncoding::Identity && !name.eq_ignore_ascii_case("identity") {
+            return None;
+        }
+
+        let quality = parts
+            .find_map(|p| p.strip_prefix("q=").or_else(|| p.strip_prefix("Q=")))
+            .map(|q| f64::from_str(q).unwrap_or(0.0))
+            .unwrap_or(1.0);
+        if quality > 0.0 {
+            Some(AcceptEncoding { encoding, quality })
+        } else {
+            None
+        }
     }
 
     /// Parse a raw Accept-Encoding header value into an ordered list.
@@ -201,3 +214,37 @@ impl AcceptEncoding {
         ContentEncoding::Identity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_encoding() {
+        let parse = |s| AcceptEncoding::parse(s, ContentEncoding::Auto);
+
+        assert_eq!(parse("gzip, deflate"), ContentEncoding::Gzip);
+        assert_eq!(parse("deflate, br, gzip"), ContentEncoding::Br);
+        assert_eq!(parse("gzip;q=0.5, deflate;q=0.8"), ContentEncoding::Deflate);
+        assert_eq!(parse("br;q=0, gzip;q=0.1"), ContentEncoding::Gzip);
+        assert_eq!(parse("unknown, deflate;q=0.5"), ContentEncoding::Deflate);
+        assert_eq!(parse("identity"), ContentEncoding::Identity);
+        assert_eq!(parse(""), ContentEncoding::Identity);
+        assert_eq!(
+            AcceptEncoding::parse("br, gzip;q=0.5", ContentEncoding::Gzip),
+            ContentEncoding::Gzip
+        );
+        assert_eq!(
+            AcceptEncoding::parse("br", ContentEncoding::Gzip),
+            ContentEncoding::Identity
+        );
+
+        #[cfg(feature = "zstd")]
+        {
+            assert_eq!(parse("gzip, br, zstd"), ContentEncoding::Zstd);
+            assert_eq!(parse("zstd;q=0.9, br"), ContentEncoding::Br);
+        }
+        #[cfg(not(feature = "zstd"))]
+        assert_eq!(parse("gzip, br, zstd"), ContentEncoding::Br);
+    }
+}
@@ -0,0 +1,242 @@
+//! Request deadline middleware
+use std::task::{Context, Poll};
+use std::{convert::Infallible, convert::TryFrom, future::Future, pin::Pin, rc::Rc};
+
+use crate::http::error::HttpError;
+use crate::http::{header::HeaderName, Response};
+use crate::service::{Service, Transform};
+use crate::time::Millis;
+use crate::util::deadline::{Deadline, HasDeadline};
+use crate::util::{select, timeout::TimeoutError, Either};
+use crate::web::{ErrorRenderer, HttpRequest, WebRequest, WebResponse};
+
+/// `Middleware` for attaching deadline to requests.
+///
+/// Deadline is calculated from default timeout and optional request header
+/// that carries remaining time budget of the caller in millis. The earliest
+/// deadline wins. Handlers could query remaining time with
+/// `HasDeadline::remaining()` and pass it to outbound calls with
+/// `ClientRequest::deadline()`.
+///
+/// If deadline elapses before response is ready, `GATEWAY_TIMEOUT`
+/// response is returned.
+///
+/// ```rust
+/// use ntex::web::{self, middleware, App, HttpRequest, HttpResponse};
+/// use ntex::util::deadline::HasDeadline;
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(middleware::RequestDeadline::new(ntex::time::Seconds(5))
+///               .header("x-request-timeout"))
+///         .service(web::resource("/test").to(|req: HttpRequest| async move {
+///             let remaining = req.remaining();
+///             HttpResponse::Ok()
+///         }));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RequestDeadline {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    timeout: Millis,
+    header: Option<HeaderName>,
+}
+
+impl RequestDeadline {
+    /// Construct `RequestDeadline` middleware with default timeout.
+    ///
+    /// Default timeout is disabled if it is set to 0.
+    pub fn new<T: Into<Millis>>(timeout: T) -> Self {
+        RequestDeadline {
+            inner: Rc::new(Inner {
+                timeout: timeout.into(),
+                header: None,
+            }),
+        }
+    }
+
+    /// Set request header that carries caller's time budget in millis.
+    pub fn header<K>(mut self, name: K) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<HttpError>,
+    {
+        match HeaderName::try_from(name) {
+            Ok(name) => {
+                Rc::get_mut(&mut self.inner)
+                    .expect("Multiple copies exist")
+                    .header = Some(name)
+            }
+            Err(_) => panic!("Cannot create header name"),
+        }
+        self
+    }
+}
+
+impl<S> Transform<S> for RequestDeadline {
+    type Service = RequestDeadlineMiddleware<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        RequestDeadlineMiddleware {
+            service,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub struct RequestDeadlineMiddleware<S> {
+    service: S,
+    inner: Rc<Inner>,
+}
+
+impl<S, Err> Service<WebRequest<Err>> for RequestDeadlineMiddleware<S>
+where
+    S: Service<WebRequest<Err>, Response = WebResponse, Error = Err::Container> + 'static,
+    S::Future: 'static,
+    Err: ErrorRenderer,
+    TimeoutError<Infallible>: Into<Err::Container>,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, mut req: WebRequest<Err>) -> Self::Future {
+        let budget = self.inner.header.as_ref().and_then(|name| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u32>().ok())
+                .map(|millis| Deadline::after(Millis(millis)))
+        });
+        let default = if self.inner.timeout.is_zero() {
+            None
+        } else {
+            Some(Deadline::after(self.inner.timeout))
+        };
+
+        let deadline = [req.deadline(), budget, default]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+        let deadline = if let Some(deadline) = deadline {
+            req.set_deadline(deadline);
+            deadline
+        } else {
+            return Box::pin(self.service.call(req));
+        };
+
+        if deadline.is_elapsed() {
+            return Box::pin(crate::util::Ready::Ok(
+                req.into_response(Response::GatewayTimeout().finish()),
+            ));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            match select(fut, deadline.sleep()).await {
+                Either::Left(res) => res,
+                Either::Right(_) => Err(TimeoutError::<Infallible>::Timeout.into()),
+            }
+        })
+    }
+}
+
+impl<Err> HasDeadline for WebRequest<Err> {
+    fn deadline(&self) -> Option<Deadline> {
+        self.extensions().deadline()
+    }
+
+    fn set_deadline(&mut self, deadline: Deadline) {
+        self.extensions_mut().set_deadline(deadline)
+    }
+}
+
+impl HasDeadline for HttpRequest {
+    fn deadline(&self) -> Option<Deadline> {
+        self.extensions().deadline()
+    }
+
+    fn set_deadline(&mut self, deadline: Deadline) {
+        self.extensions_mut().set_deadline(deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::service::IntoService;
+    use crate::time::sleep;
+    use crate::web::test::TestRequest;
+    use crate::web::{DefaultError, Error, HttpResponse};
+
+    fn ok_srv(
+    ) -> impl Fn(WebRequest<DefaultError>) -> crate::util::Ready<WebResponse, Error> {
+        |req| crate::util::Ready::Ok(req.into_response(HttpResponse::Ok().finish()))
+    }
+
+    #[crate::rt_test]
+    async fn test_deadline() {
+        let srv = |req: WebRequest<DefaultError>| async move {
+            let remaining = req.remaining().unwrap();
+            assert!(remaining <= std::time::Duration::from_millis(200));
+            Ok::<_, Error>(req.into_response(HttpResponse::Ok().finish()))
+        };
+        let mw = RequestDeadline::new(Millis(200))
+            .header("x-request-timeout")
+            .new_transform(srv.into_service());
+
+        let req = TestRequest::default().to_srv_request();
+        let resp = mw.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // caller budget
+        let req = TestRequest::default()
+            .header("x-request-timeout", "50")
+            .to_srv_request();
+        let srv = |req: WebRequest<DefaultError>| async move {
+            assert!(req.remaining().unwrap() <= std::time::Duration::from_millis(50));
+            sleep(Millis(300)).await;
+            Ok::<_, Error>(req.into_response(HttpResponse::Ok().finish()))
+        };
+        let mw = RequestDeadline::new(Millis(1000))
+            .header("x-request-timeout")
+            .new_transform(srv.into_service());
+        let resp = mw.call(req).await;
+        let err = resp.err().unwrap();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+
+        // elapsed
+        let req = TestRequest::default()
+            .header("x-request-timeout", "0")
+            .to_srv_request();
+        let mw = RequestDeadline::new(Millis::ZERO)
+            .header("x-request-timeout")
+            .new_transform(ok_srv().into_service());
+        let resp = mw.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        // no deadline
+        let req = TestRequest::default().to_srv_request();
+        let mw = RequestDeadline::new(Millis::ZERO).new_transform(ok_srv().into_service());
+        let resp = mw.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
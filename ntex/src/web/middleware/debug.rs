@@ -0,0 +1,341 @@
+//! Development error pages
+use std::task::{Context, Poll};
+use std::{fmt::Write, future::Future, pin::Pin};
+
+use serde_json::json;
+
+use crate::http::header;
+use crate::service::{Service, Transform};
+use crate::web::error::ErrorContainer;
+use crate::web::error_default::enable_backtraces;
+use crate::web::{HttpRequest, HttpResponse, WebRequest, WebResponse};
+
+/// `Middleware` for development error pages.
+///
+/// Server error responses generated from handler errors are replaced with
+/// a page that contains error's source chain, backtrace, matched route
+/// pattern and type of the extractor that failed. Page is rendered as json
+/// if request accepts `application/json`, otherwise as html.
+///
+/// Error pages expose application internals, so middleware is enabled
+/// only in debug builds. In release builds it is a no-op unless it is
+/// explicitly allowed with `DebugErrors::allow_release()`.
+///
+/// Backtraces are captured for `web::Error` containers on creation,
+/// capturing is enabled for the whole process once middleware
+/// is constructed for an application.
+///
+/// ```rust
+/// use ntex::web::{self, middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(middleware::DebugErrors::new())
+///         .service(web::resource("/").to(|| async { HttpResponse::Ok() }));
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct DebugErrors {
+    enabled: bool,
+}
+
+impl Default for DebugErrors {
+    fn default() -> Self {
+        DebugErrors {
+            enabled: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl DebugErrors {
+    /// Construct `DebugErrors` middleware, enabled in debug builds only.
+    pub fn new() -> Self {
+        DebugErrors::default()
+    }
+
+    /// Enable error pages in release builds.
+    ///
+    /// Error pages must not be exposed to untrusted clients.
+    pub fn allow_release(mut self) -> Self {
+        self.enabled = true;
+        self
+    }
+
+    /// Check if error pages are enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl<S> Transform<S> for DebugErrors {
+    type Service = DebugErrorsMiddleware<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        if self.enabled {
+            if !cfg!(debug_assertions) {
+                log::warn!("Debug error pages are enabled in release build");
+            }
+            enable_backtraces();
+        }
+        DebugErrorsMiddleware {
+            service,
+            enabled: self.enabled,
+        }
+    }
+}
+
+pub struct DebugErrorsMiddleware<S> {
+    service: S,
+    enabled: bool,
+}
+
+impl<S, E> Service<WebRequest<E>> for DebugErrorsMiddleware<S>
+where
+    S: Service<WebRequest<E>, Response = WebResponse>,
+    S::Future: 'static,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: WebRequest<E>) -> Self::Future {
+        let enabled = self.enabled;
+        if enabled {
+            req.extensions_mut().insert(DebugCapture);
+        }
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            if enabled && res.status().is_server_error() {
+                Ok(render(res))
+            } else {
+                Ok(res)
+            }
+        })
+    }
+}
+
+/// Marker in request extensions, enables errors capturing
+struct DebugCapture;
+
+/// Details of the error rendered for request
+struct ErrorDetails {
+    chain: Vec<String>,
+    debug: String,
+    backtrace: Option<String>,
+}
+
+/// Type of the handler's extractor that failed
+struct FailedExtractor(&'static str);
+
+/// Store details of the error if error pages are enabled for request
+pub(crate) fn capture_error<C: ErrorContainer>(err: &C, req: &HttpRequest) {
+    if !req.extensions().contains::<DebugCapture>() {
+        return;
+    }
+
+    let chain = if let Some(err) = err.as_error() {
+        std::iter::successors(Some(err), |err| err.source())
+            .map(|err| err.to_string())
+            .collect()
+    } else {
+        vec![err.to_string()]
+    };
+    let details = ErrorDetails {
+        chain,
+        debug: format!("{:?}", err),
+        backtrace: err.backtrace().map(|bt| format!("{:?}", bt)),
+    };
+    req.extensions_mut().insert(details);
+}
+
+/// Store type of the extractor that failed if error pages are enabled for request
+pub(crate) fn capture_extractor(req: &HttpRequest, extractor: &'static str) {
+    if req.extensions().contains::<DebugCapture>() {
+        req.extensions_mut().insert(FailedExtractor(extractor));
+    }
+}
+
+fn render(res: WebResponse) -> WebResponse {
+    let details = res.request().extensions_mut().remove::<ErrorDetails>();
+    let details = if let Some(details) = details {
+        details
+    } else {
+        // response is not generated from error
+        return res;
+    };
+    let extractor = res
+        .request()
+        .extensions_mut()
+        .remove::<FailedExtractor>()
+        .map(|ex| ex.0);
+
+    let status = res.status();
+    let req = res.request();
+    let pattern = req.match_pattern();
+    let accepts_json = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|val| val.to_str().ok())
+        .map_or(false, |val| val.contains("application/json"));
+
+    let response = if accepts_json {
+        let body = json!({
+            "status": status.as_u16(),
+            "method": req.method().as_str(),
+            "path": req.path(),
+            "pattern": pattern,
+            "extractor": extractor,
+            "errors": details.chain,
+            "debug": details.debug,
+            "backtrace": details.backtrace,
+        });
+        HttpResponse::build(status).json(&body)
+    } else {
+        let title = format!(
+            "{} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("")
+        );
+        let mut body = String::new();
+        let _ = writeln!(
+            body,
+            "<!DOCTYPE html>\n<html>\n<head>\n\
+             <meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n\
+             <body>\n<h1>{title}</h1>\n<p>{} {}</p>\n<dl>",
+            escape_html(req.method().as_str()),
+            escape_html(req.path()),
+            title = escape_html(title.trim()),
+        );
+        if let Some(ref pattern) = pattern {
+            let _ = writeln!(body, "<dt>Route</dt><dd>{}</dd>", escape_html(pattern));
+        }
+        if let Some(extractor) = extractor {
+            let _ = writeln!(
+                body,
+                "<dt>Extractor</dt><dd>{}</dd>",
+                escape_html(extractor)
+            );
+        }
+        body.push_str("</dl>\n<h2>Error</h2>\n<ol>\n");
+        for err in &details.chain {
+            let _ = writeln!(body, "<li>{}</li>", escape_html(err));
+        }
+        let _ = writeln!(body, "</ol>\n<pre>{}</pre>", escape_html(&details.debug));
+        if let Some(ref bt) = details.backtrace {
+            let _ = writeln!(body, "<h2>Backtrace</h2>\n<pre>{}</pre>", escape_html(bt));
+        }
+        body.push_str("</body>\n</html>\n");
+
+        HttpResponse::build(status)
+            .content_type("text/html; charset=utf-8")
+            .body(body)
+    };
+    res.into_response(response)
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, error, App};
+
+    #[crate::rt_test]
+    async fn test_debug_errors() {
+        let srv = init_service(
+            App::new()
+                .wrap(DebugErrors::new().allow_release())
+                .service(web::resource("/users/{id}").to(|| async {
+                    Err::<HttpResponse, _>(
+                        web::Error::new(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            "<db> is down",
+                        ))
+                        .context("loading user"),
+                    )
+                }))
+                .service(web::resource("/bad").to(|| async {
+                    Err::<HttpResponse, _>(error::ErrorBadRequest("bad"))
+                })),
+        )
+        .await;
+
+        let resp = call_service(&srv, TestRequest::with_uri("/users/1").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+        assert!(body.contains("<dd>/users/{id}</dd>"));
+        assert!(body.contains("<li>loading user</li>"));
+        assert!(body.contains("<li>&lt;db&gt; is down</li>"));
+        assert!(body.contains("<h2>Backtrace</h2>"));
+
+        let req = TestRequest::with_uri("/users/1")
+            .header(header::ACCEPT, "application/json")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(resp).await).unwrap();
+        assert_eq!(body["pattern"], "/users/{id}");
+        assert_eq!(body["errors"][0], "loading user");
+        assert_eq!(body["errors"][1], "<db> is down");
+        assert!(body["backtrace"].is_string());
+
+        // client errors are not replaced
+        let resp = call_service(&srv, TestRequest::with_uri("/bad").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(read_body(resp).await, "bad");
+    }
+
+    #[crate::rt_test]
+    async fn test_debug_errors_extractor() {
+        let srv = init_service(
+            App::new().wrap(DebugErrors::new().allow_release()).service(
+                web::resource("/")
+                    .to(|_: web::types::State<u32>| async { HttpResponse::Ok() }),
+            ),
+        )
+        .await;
+
+        let req = TestRequest::default()
+            .header(header::ACCEPT, "application/json")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(resp).await).unwrap();
+        assert!(body["extractor"].as_str().unwrap().contains("State<u32>"));
+    }
+
+    #[test]
+    fn test_debug_errors_release() {
+        assert_eq!(DebugErrors::new().is_enabled(), cfg!(debug_assertions));
+        assert!(DebugErrors::new().allow_release().is_enabled());
+    }
+}
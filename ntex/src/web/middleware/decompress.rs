@@ -0,0 +1,161 @@
+//! `Middleware` for decompressing request payload.
+use std::task::{Context, Poll};
+
+use crate::http::encoding::Decoder;
+use crate::http::header::{ContentEncoding, CONTENT_ENCODING, CONTENT_LENGTH};
+use crate::http::Payload;
+use crate::service::{Service, Transform};
+use crate::web::{WebRequest, WebResponse};
+
+#[derive(Debug, Clone)]
+/// `Middleware` for decompressing request payload.
+///
+/// Middleware decodes request payloads with `gzip`, `deflate` or `br`
+/// content encoding and removes `Content-Encoding` and `Content-Length`
+/// request headers. Decoded payload size is limited, payload stream
+/// returns `PayloadError::Overflow` error if decoded content exceeds the limit.
+/// Default limit is 8Mb.
+///
+/// ```rust
+/// use ntex::web::{self, middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(middleware::Decompress::new().max_size(1024 * 1024))
+///         .service(
+///             web::resource("/test")
+///                 .route(web::post().to(|body: String| async move { body }))
+///         );
+/// }
+/// ```
+pub struct Decompress {
+    max_size: usize,
+}
+
+impl Decompress {
+    /// Create new `Decompress` middleware.
+    pub fn new() -> Self {
+        Decompress {
+            max_size: 8 * 1024 * 1024,
+        }
+    }
+
+    /// Set max size of decoded payload.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl Default for Decompress {
+    fn default() -> Self {
+        Decompress::new()
+    }
+}
+
+impl<S> Transform<S> for Decompress {
+    type Service = DecompressMiddleware<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        DecompressMiddleware {
+            service,
+            max_size: self.max_size,
+        }
+    }
+}
+
+pub struct DecompressMiddleware<S> {
+    service: S,
+    max_size: usize,
+}
+
+impl<S, E> Service<WebRequest<E>> for DecompressMiddleware<S>
+where
+    S: Service<WebRequest<E>, Response = WebResponse>,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, mut req: WebRequest<E>) -> Self::Future {
+        let encoding = req
+            .headers()
+            .get(&CONTENT_ENCODING)
+            .and_then(|val| val.to_str().ok())
+            .map(ContentEncoding::from)
+            .unwrap_or(ContentEncoding::Identity);
+
+        if encoding.is_compressed() {
+            let payload = req.take_payload();
+            req.set_payload(Payload::from_stream(
+                Decoder::new(payload, encoding).max_size(self.max_size),
+            ));
+            req.headers_mut().remove(&CONTENT_ENCODING);
+            req.headers_mut().remove(&CONTENT_LENGTH);
+        }
+        self.service.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::util::Bytes;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, App};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(data).unwrap();
+        e.finish().unwrap()
+    }
+
+    #[crate::rt_test]
+    async fn test_decompress() {
+        let srv = init_service(App::new().wrap(Decompress::new().max_size(1024)).service(
+            web::resource("/").to(|req: web::HttpRequest, body: Bytes| async move {
+                assert!(req.headers().get(CONTENT_ENCODING).is_none());
+                body
+            }),
+        ))
+        .await;
+
+        let req = TestRequest::with_uri("/")
+            .header(CONTENT_ENCODING, "gzip")
+            .set_payload(gzip(b"hello world"))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(read_body(res).await, Bytes::from_static(b"hello world"));
+
+        // identity
+        let req = TestRequest::with_uri("/")
+            .set_payload(Bytes::from_static(b"hello world"))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(read_body(res).await, Bytes::from_static(b"hello world"));
+
+        // decoded payload overflow
+        let req = TestRequest::with_uri("/")
+            .header(CONTENT_ENCODING, "gzip")
+            .set_payload(gzip(&[0; 4096]))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,275 @@
+//! Middleware for `ETag` generation and conditional requests evaluation
+use std::task::{Context, Poll};
+use std::{future::Future, pin::Pin};
+
+use sha1::{Digest, Sha1};
+
+use crate::http::body::{Body, ResponseBody};
+use crate::http::conditional::{self, header_date, header_str, Precondition};
+use crate::http::header::{self, HeaderValue};
+use crate::http::{Method, StatusCode};
+use crate::service::{Service, Transform};
+use crate::web::{WebRequest, WebResponse};
+
+/// `Middleware` for `ETag` generation and conditional requests evaluation.
+///
+/// Middleware computes entity tag for buffered `200 OK` responses to `GET`
+/// and `HEAD` requests, streaming responses are not tagged. Entity tag set by
+/// handler is used as is. Request preconditions (`If-None-Match`,
+/// `If-Modified-Since`, `If-Match`, `If-Unmodified-Since`) are evaluated
+/// against response's `ETag` and `Last-Modified` headers, middleware responds
+/// with `304 Not Modified` without body or with `412 Precondition Failed`.
+///
+/// Responses with `Cache-Control: no-store` directive are not modified.
+/// Partial responses are not modified as well.
+///
+/// Middleware should be registered after compression middleware, so entity
+/// tag is computed for uncompressed response body.
+///
+/// ```rust
+/// use ntex::web::{self, middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(middleware::ETag::new())
+///         .service(web::resource("/").to(|| async { "content" }));
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ETag {
+    weak: bool,
+}
+
+impl ETag {
+    /// Construct `ETag` middleware, generated entity tags are strong.
+    pub fn new() -> Self {
+        ETag::default()
+    }
+
+    /// Generate weak entity tags.
+    ///
+    /// Weak entity tags should be used if response body could differ
+    /// in semantically insignificant way, for example if it is compressed
+    /// before middleware computes entity tag.
+    pub fn weak(mut self) -> Self {
+        self.weak = true;
+        self
+    }
+}
+
+impl<S> Transform<S> for ETag {
+    type Service = ETagMiddleware<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        ETagMiddleware {
+            service,
+            weak: self.weak,
+        }
+    }
+}
+
+pub struct ETagMiddleware<S> {
+    service: S,
+    weak: bool,
+}
+
+impl<S, E> Service<WebRequest<E>> for ETagMiddleware<S>
+where
+    S: Service<WebRequest<E>, Response = WebResponse>,
+    S::Future: 'static,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: WebRequest<E>) -> Self::Future {
+        let weak = self.weak;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            Ok(evaluate(res, weak))
+        })
+    }
+}
+
+fn evaluate(mut res: WebResponse, weak: bool) -> WebResponse {
+    let method = res.request().method();
+    if (method != Method::GET && method != Method::HEAD)
+        || res.status() != StatusCode::OK
+        || no_store(&res)
+    {
+        return res;
+    }
+
+    // generate entity tag for buffered body
+    if !res.headers().contains_key(header::ETAG) {
+        let etag = match res.response().body() {
+            ResponseBody::Body(Body::Bytes(b)) | ResponseBody::Other(Body::Bytes(b)) => {
+                Some(generate(b, weak))
+            }
+            _ => None,
+        };
+        if let Some(etag) = etag.and_then(|e| HeaderValue::from_str(&e).ok()) {
+            res.headers_mut().insert(header::ETAG, etag);
+        }
+    }
+
+    let etag = header_str(res.headers(), &header::ETAG);
+    let last_modified = header_date(res.headers(), &header::LAST_MODIFIED);
+    if etag.is_none() && last_modified.is_none() {
+        return res;
+    }
+
+    let req = res.request();
+    match conditional::evaluate(req.headers(), req.method(), etag, last_modified) {
+        Precondition::Passed => res,
+        Precondition::NotModified => {
+            *res.response_mut().status_mut() = StatusCode::NOT_MODIFIED;
+            drop(res.take_body());
+            let hdrs = res.headers_mut();
+            hdrs.remove(header::CONTENT_TYPE);
+            hdrs.remove(header::CONTENT_LENGTH);
+            res
+        }
+        Precondition::Failed => {
+            *res.response_mut().status_mut() = StatusCode::PRECONDITION_FAILED;
+            drop(res.take_body());
+            res
+        }
+    }
+}
+
+/// Check `Cache-Control` response header for `no-store` directive
+fn no_store(res: &WebResponse) -> bool {
+    res.headers()
+        .get_all(header::CACHE_CONTROL)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|d| d.trim().eq_ignore_ascii_case("no-store"))
+}
+
+/// Entity tag is base64 encoded sha1 digest of the body
+fn generate(body: &[u8], weak: bool) -> String {
+    let digest = Sha1::digest(body);
+    let tag = base64::encode_config(digest, base64::URL_SAFE_NO_PAD);
+    if weak {
+        format!("W/\"{}\"", tag)
+    } else {
+        format!("\"{}\"", tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Bytes;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, App, HttpResponse};
+
+    #[crate::rt_test]
+    async fn test_etag() {
+        let srv = init_service(
+            App::new()
+                .wrap(ETag::new())
+                .service(web::resource("/").to(|| async { "content" }))
+                .service(web::resource("/post").route(web::post().to(|| async { "post" }))),
+        )
+        .await;
+
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(etag, generate(b"content", false).as_str());
+        assert_eq!(read_body(resp).await, Bytes::from_static(b"content"));
+
+        let req = TestRequest::default()
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), &etag);
+        assert!(!resp.headers().contains_key(header::CONTENT_TYPE));
+        assert_eq!(read_body(resp).await, Bytes::new());
+
+        let req = TestRequest::default()
+            .method(Method::HEAD)
+            .header(header::IF_NONE_MATCH, "\"other\", W/\"x\"")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = TestRequest::default()
+            .header(header::IF_MATCH, "\"other\"")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+        // unsafe methods are not tagged
+        let req = TestRequest::with_uri("/post")
+            .method(Method::POST)
+            .header(header::IF_NONE_MATCH, "*")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!resp.headers().contains_key(header::ETAG));
+    }
+
+    #[crate::rt_test]
+    async fn test_precomputed() {
+        let srv = init_service(
+            App::new()
+                .wrap(ETag::new().weak())
+                .service(web::resource("/").to(|| async {
+                    HttpResponse::Ok()
+                        .header(header::ETAG, "\"v1\"")
+                        .header(header::LAST_MODIFIED, "Tue, 15 Nov 1994 08:12:31 GMT")
+                        .body("content")
+                }))
+                .service(web::resource("/weak").to(|| async { "content" }))
+                .service(web::resource("/no-store").to(|| async {
+                    HttpResponse::Ok()
+                        .header(header::CACHE_CONTROL, "private, no-store")
+                        .body("content")
+                })),
+        )
+        .await;
+
+        let req = TestRequest::default()
+            .header(header::IF_NONE_MATCH, "W/\"v1\"")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"v1\"");
+
+        let req = TestRequest::default()
+            .header(header::IF_MODIFIED_SINCE, "Tue, 15 Nov 1994 08:12:31 GMT")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let req = TestRequest::with_uri("/weak").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(
+            resp.headers().get(header::ETAG).unwrap(),
+            generate(b"content", true).as_str()
+        );
+
+        let req = TestRequest::with_uri("/no-store")
+            .header(header::IF_NONE_MATCH, "*")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!resp.headers().contains_key(header::ETAG));
+    }
+}
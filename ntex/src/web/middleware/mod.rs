@@ -4,9 +4,43 @@
 mod compress;
 #[cfg(feature = "compress")]
 pub use self::compress::Compress;
+#[cfg(feature = "compress")]
+mod decompress;
+#[cfg(feature = "compress")]
+pub use self::decompress::Decompress;
 
 mod logger;
 pub use self::logger::Logger;
 
+mod debug;
+pub use self::debug::DebugErrors;
+pub(crate) use self::debug::{capture_error, capture_extractor};
+
+mod deadline;
+pub use self::deadline::RequestDeadline;
+
 mod defaultheaders;
 pub use self::defaultheaders::DefaultHeaders;
+
+mod etag;
+pub use self::etag::ETag;
+
+mod security;
+pub use self::security::{
+    ContentSecurityPolicy, CspNonce, DefaultSecurityHeaders, FrameOptions,
+};
+
+mod ratelimit;
+pub use self::ratelimit::{
+    MemoryRateLimitStore, Quota, RateLimit, RateLimitDecision, RateLimitStore,
+};
+
+#[cfg(feature = "tracing")]
+mod tracing;
+#[cfg(feature = "tracing")]
+pub use self::tracing::{RequestSpan, TraceContext, Tracing};
+
+#[cfg(feature = "session")]
+pub mod session;
+#[cfg(feature = "session")]
+pub use self::session::{Session, SessionMiddleware};
@@ -0,0 +1,439 @@
+//! Rate limiting middleware
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{fmt, future::Future, pin::Pin, rc::Rc};
+
+use crate::http::header::{HeaderValue, RETRY_AFTER};
+use crate::http::{RequestHead, Response};
+use crate::service::{Service, Transform};
+use crate::web::{WebRequest, WebResponse};
+
+/// Token bucket quota
+///
+/// Bucket holds up to `burst` tokens, tokens are replenished at rate
+/// of `requests` per `period`. Each request consumes one token.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Quota {
+    burst: u32,
+    interval: Duration,
+}
+
+impl Quota {
+    /// Construct quota that allows `requests` per `period`.
+    ///
+    /// Burst size is equal to `requests`.
+    ///
+    /// Panics if `requests` is zero.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        assert!(requests > 0, "Number of requests must be greater than zero");
+        Quota {
+            burst: requests,
+            interval: period / requests,
+        }
+    }
+
+    /// Set maximum burst size.
+    ///
+    /// Panics if `burst` is zero.
+    pub fn burst(mut self, burst: u32) -> Self {
+        assert!(burst > 0, "Burst size must be greater than zero");
+        self.burst = burst;
+        self
+    }
+
+    /// Maximum number of tokens in the bucket
+    pub fn burst_size(&self) -> u32 {
+        self.burst
+    }
+
+    /// Time that is required to replenish one token
+    pub fn replenish_interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+/// Result of the token acquisition
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RateLimitDecision {
+    /// Request is allowed, number of remaining tokens
+    Allowed(u32),
+    /// Request is limited, time until next token is available
+    Limited(Duration),
+}
+
+/// Storage for token buckets
+///
+/// Store could be shared between workers, distributed stores (i.e. redis)
+/// allow to apply limits across multiple servers.
+pub trait RateLimitStore: 'static {
+    /// The future of the `acquire` operation
+    type Future: Future<Output = RateLimitDecision>;
+
+    /// Acquire one token from the bucket identified by `key`
+    fn acquire(&self, key: &str, quota: &Quota) -> Self::Future;
+}
+
+/// In-memory rate limit store.
+///
+/// Buckets are distributed between shards, each shard is protected by
+/// its own mutex. Store is shared between clones, so one store could be
+/// used by all workers.
+#[derive(Clone)]
+pub struct MemoryRateLimitStore {
+    shards: Arc<Vec<Mutex<Shard>>>,
+}
+
+struct Shard {
+    buckets: HashMap<String, Bucket>,
+    cleanup_at: usize,
+}
+
+#[derive(Copy, Clone)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+const CLEANUP_THRESHOLD: usize = 1024;
+
+impl Default for MemoryRateLimitStore {
+    fn default() -> Self {
+        Self::with_shards(16)
+    }
+}
+
+impl MemoryRateLimitStore {
+    /// Create in-memory store with 16 shards
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create in-memory store with specified number of shards
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "Number of shards must be greater than zero");
+        MemoryRateLimitStore {
+            shards: Arc::new(
+                (0..shards)
+                    .map(|_| {
+                        Mutex::new(Shard {
+                            buckets: HashMap::new(),
+                            cleanup_at: CLEANUP_THRESHOLD,
+                        })
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Number of tracked buckets
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| s.lock().unwrap().buckets.len())
+            .sum()
+    }
+
+    /// Check if store does not track any buckets
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shard(&self, key: &str) -> &Mutex<Shard> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[(hasher.finish() % self.shards.len() as u64) as usize]
+    }
+}
+
+impl Bucket {
+    /// Number of tokens at `now`
+    fn tokens(&self, now: Instant, quota: &Quota) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let tokens = self.tokens + elapsed / quota.interval.as_secs_f64();
+        tokens.min(quota.burst as f64)
+    }
+}
+
+impl Shard {
+    /// Remove full buckets, they are indistinguishable from new ones
+    fn cleanup(&mut self, now: Instant, quota: &Quota) {
+        let burst = quota.burst as f64;
+        self.buckets.retain(|_, b| b.tokens(now, quota) < burst);
+        self.cleanup_at = std::cmp::max(self.buckets.len() * 2, CLEANUP_THRESHOLD);
+    }
+}
+
+impl RateLimitStore for MemoryRateLimitStore {
+    type Future = std::future::Ready<RateLimitDecision>;
+
+    fn acquire(&self, key: &str, quota: &Quota) -> Self::Future {
+        let now = Instant::now();
+        let mut shard = self.shard(key).lock().unwrap();
+        if shard.buckets.len() >= shard.cleanup_at {
+            shard.cleanup(now, quota);
+        }
+
+        let bucket = shard
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket {
+                tokens: quota.burst as f64,
+                updated: now,
+            });
+        let tokens = bucket.tokens(now, quota);
+        bucket.updated = now;
+
+        let decision = if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            RateLimitDecision::Allowed(bucket.tokens as u32)
+        } else {
+            bucket.tokens = tokens;
+            RateLimitDecision::Limited(quota.interval.mul_f64(1.0 - tokens))
+        };
+        std::future::ready(decision)
+    }
+}
+
+impl fmt::Debug for MemoryRateLimitStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryRateLimitStore")
+            .field("shards", &self.shards.len())
+            .finish()
+    }
+}
+
+/// `Middleware` for limiting request rate.
+///
+/// Middleware implements token bucket algorithm, bucket is selected by
+/// request key. By default request key is peer ip address, requests
+/// without key are not limited. Limited requests get
+/// `429 Too Many Requests` response with `Retry-After` header.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use ntex::web::{self, middleware::RateLimit, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(
+///             // 10 requests per second, with bursts up to 20 requests
+///             RateLimit::new(10, Duration::from_secs(1))
+///                 .burst(20)
+///                 .key(|head| {
+///                     head.headers()
+///                         .get("x-api-key")
+///                         .and_then(|v| v.to_str().ok())
+///                         .map(|v| v.to_string())
+///                 }),
+///         )
+///         .service(web::resource("/").to(|| async { HttpResponse::Ok() }));
+/// }
+/// ```
+pub struct RateLimit<S = MemoryRateLimitStore> {
+    inner: Rc<Inner<S>>,
+}
+
+type KeyFn = dyn Fn(&RequestHead) -> Option<String>;
+
+struct Inner<S> {
+    store: S,
+    quota: Quota,
+    key: Box<KeyFn>,
+}
+
+impl RateLimit {
+    /// Construct rate limiter with in-memory store, it allows `requests`
+    /// per `period` for each key.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        RateLimit::with_store(MemoryRateLimitStore::default(), requests, period)
+    }
+}
+
+impl<S: RateLimitStore> RateLimit<S> {
+    /// Construct rate limiter with custom store
+    pub fn with_store(store: S, requests: u32, period: Duration) -> Self {
+        RateLimit {
+            inner: Rc::new(Inner {
+                store,
+                quota: Quota::new(requests, period),
+                key: Box::new(|head| head.peer_addr().map(|addr| addr.ip().to_string())),
+            }),
+        }
+    }
+
+    /// Set maximum burst size. By default it is equal to number of requests.
+    pub fn burst(mut self, burst: u32) -> Self {
+        let inner = Rc::get_mut(&mut self.inner).expect("Multiple copies exist");
+        inner.quota = inner.quota.burst(burst);
+        self
+    }
+
+    /// Set request key extractor.
+    ///
+    /// Requests without key are not limited.
+    pub fn key<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&RequestHead) -> Option<String> + 'static,
+    {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .key = Box::new(f);
+        self
+    }
+}
+
+impl<S, St> Transform<S> for RateLimit<St> {
+    type Service = RateLimitMiddleware<S, St>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        RateLimitMiddleware {
+            service: Rc::new(service),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub struct RateLimitMiddleware<S, St> {
+    service: Rc<S>,
+    inner: Rc<Inner<St>>,
+}
+
+impl<S, St, E> Service<WebRequest<E>> for RateLimitMiddleware<S, St>
+where
+    S: Service<WebRequest<E>, Response = WebResponse> + 'static,
+    St: RateLimitStore,
+    E: 'static,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: WebRequest<E>) -> Self::Future {
+        let inner = self.inner.clone();
+        let srv = self.service.clone();
+
+        Box::pin(async move {
+            if let Some(key) = (*inner.key)(req.head()) {
+                if let RateLimitDecision::Limited(retry_after) =
+                    inner.store.acquire(&key, &inner.quota).await
+                {
+                    // round up to whole seconds
+                    let secs = retry_after.as_secs()
+                        + if retry_after.subsec_nanos() > 0 { 1 } else { 0 };
+                    let mut res = Response::TooManyRequests();
+                    res.header(RETRY_AFTER, HeaderValue::from(secs));
+                    return Ok(req.into_response(res.finish()));
+                }
+            }
+            srv.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::web::test::{ok_service, TestRequest};
+
+    fn api_key(head: &RequestHead) -> Option<String> {
+        head.headers()
+            .get("x-api-key")
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[crate::rt_test]
+    async fn test_rate_limit() {
+        let mw = RateLimit::new(2, Duration::from_secs(10))
+            .key(api_key)
+            .new_transform(ok_service());
+
+        for _ in 0..2 {
+            let req = TestRequest::default()
+                .header("x-api-key", "key1")
+                .to_srv_request();
+            assert_eq!(mw.call(req).await.unwrap().status(), StatusCode::OK);
+        }
+        let req = TestRequest::default()
+            .header("x-api-key", "key1")
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = res
+            .headers()
+            .get(RETRY_AFTER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after > 0 && retry_after <= 5);
+
+        // other key
+        let req = TestRequest::default()
+            .header("x-api-key", "key2")
+            .to_srv_request();
+        assert_eq!(mw.call(req).await.unwrap().status(), StatusCode::OK);
+
+        // requests without key are not limited
+        for _ in 0..3 {
+            let req = TestRequest::default().to_srv_request();
+            assert_eq!(mw.call(req).await.unwrap().status(), StatusCode::OK);
+        }
+    }
+
+    #[crate::rt_test]
+    async fn test_memory_store() {
+        let store = MemoryRateLimitStore::with_shards(2);
+        let quota = Quota::new(1, Duration::from_millis(50)).burst(2);
+        assert_eq!(quota.burst_size(), 2);
+        assert_eq!(quota.replenish_interval(), Duration::from_millis(50));
+
+        assert_eq!(
+            store.acquire("a", &quota).await,
+            RateLimitDecision::Allowed(1)
+        );
+        assert_eq!(
+            store.acquire("a", &quota).await,
+            RateLimitDecision::Allowed(0)
+        );
+        match store.acquire("a", &quota).await {
+            RateLimitDecision::Limited(d) => assert!(d <= Duration::from_millis(50)),
+            _ => panic!(),
+        }
+        assert_eq!(store.len(), 1);
+
+        crate::time::sleep(crate::time::Millis(60)).await;
+        assert_eq!(
+            store.acquire("a", &quota).await,
+            RateLimitDecision::Allowed(0)
+        );
+
+        // full buckets are removed
+        let mut shard = store.shards[0].lock().unwrap();
+        shard.buckets.insert(
+            "b".to_string(),
+            Bucket {
+                tokens: 2.0,
+                updated: Instant::now(),
+            },
+        );
+        shard.cleanup(Instant::now(), &quota);
+        assert!(!shard.buckets.contains_key("b"));
+    }
+}
@@ -0,0 +1,522 @@
+//! Middleware for setting security related response headers
+use std::task::{Context, Poll};
+use std::{fmt, future::Future, ops, pin::Pin, rc::Rc, time::Duration};
+
+use nanorand::{Rng, WyRand};
+
+use crate::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use crate::http::Payload;
+use crate::service::{Service, Transform};
+use crate::util::Ready;
+use crate::web::error::{DataExtractorError, ErrorRenderer};
+use crate::web::{FromRequest, HttpRequest, WebRequest, WebResponse};
+
+/// `Middleware` for setting security related response headers.
+///
+/// By default middleware sets following headers:
+///
+/// * `Strict-Transport-Security: max-age=31536000; includeSubDomains`
+/// * `X-Content-Type-Options: nosniff`
+/// * `X-Frame-Options: DENY`
+/// * `Referrer-Policy: strict-origin-when-cross-origin`
+///
+/// `Content-Security-Policy` header is set only if policy is configured.
+/// This middleware does not set header if response headers already contains it.
+///
+/// ```rust
+/// use ntex::web::{self, middleware, App, HttpResponse};
+/// use ntex::web::middleware::{ContentSecurityPolicy, CspNonce, FrameOptions};
+///
+/// async fn index(nonce: CspNonce) -> HttpResponse {
+///     HttpResponse::Ok().content_type("text/html").body(format!(
+///         "<script nonce=\"{}\">console.log('hello')</script>", nonce
+///     ))
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(
+///             middleware::DefaultSecurityHeaders::new()
+///                 .frame_options(FrameOptions::SameOrigin)
+///                 .content_security_policy(
+///                     ContentSecurityPolicy::new()
+///                         .directive("default-src", &["'self'"])
+///                         .nonce("script-src"),
+///                 ),
+///         )
+///         .service(web::resource("/").to(index));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct DefaultSecurityHeaders {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    headers: HeaderMap,
+    csp: Option<ContentSecurityPolicy>,
+}
+
+/// `X-Frame-Options` header value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameOptions {
+    /// Page cannot be displayed in a frame
+    Deny,
+    /// Page can only be displayed in a frame on the same origin
+    SameOrigin,
+}
+
+impl Default for DefaultSecurityHeaders {
+    fn default() -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_static("max-age=31536000; includeSubDomains"),
+        );
+        headers.insert(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        );
+        headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        headers.insert(
+            header::REFERRER_POLICY,
+            HeaderValue::from_static("strict-origin-when-cross-origin"),
+        );
+
+        DefaultSecurityHeaders {
+            inner: Rc::new(Inner { headers, csp: None }),
+        }
+    }
+}
+
+impl DefaultSecurityHeaders {
+    /// Construct `DefaultSecurityHeaders` middleware with default headers.
+    pub fn new() -> DefaultSecurityHeaders {
+        DefaultSecurityHeaders::default()
+    }
+
+    /// Configure `Strict-Transport-Security` header.
+    ///
+    /// By default `max-age` is one year and `includeSubDomains` is set.
+    pub fn hsts(self, max_age: Duration, include_subdomains: bool, preload: bool) -> Self {
+        let mut val = format!("max-age={}", max_age.as_secs());
+        if include_subdomains {
+            val.push_str("; includeSubDomains");
+        }
+        if preload {
+            val.push_str("; preload");
+        }
+        self.set(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_str(&val).unwrap(),
+        )
+    }
+
+    /// Configure `X-Frame-Options` header. By default it is `DENY`.
+    pub fn frame_options(self, opts: FrameOptions) -> Self {
+        let val = match opts {
+            FrameOptions::Deny => "DENY",
+            FrameOptions::SameOrigin => "SAMEORIGIN",
+        };
+        self.set(header::X_FRAME_OPTIONS, HeaderValue::from_static(val))
+    }
+
+    /// Configure `Referrer-Policy` header.
+    ///
+    /// By default it is `strict-origin-when-cross-origin`.
+    ///
+    /// Panics if policy is not valid header value.
+    pub fn referrer_policy(self, policy: &str) -> Self {
+        let val = HeaderValue::from_str(policy).expect("Cannot create header value");
+        self.set(header::REFERRER_POLICY, val)
+    }
+
+    /// Set `Content-Security-Policy` header.
+    pub fn content_security_policy(mut self, csp: ContentSecurityPolicy) -> Self {
+        self.inner_mut().csp = Some(csp);
+        self
+    }
+
+    /// Do not set specified header.
+    ///
+    /// ```rust
+    /// use ntex::http::header;
+    /// use ntex::web::middleware::DefaultSecurityHeaders;
+    ///
+    /// // application is served over plain http
+    /// let mw = DefaultSecurityHeaders::new().without(header::STRICT_TRANSPORT_SECURITY);
+    /// ```
+    pub fn without(mut self, name: HeaderName) -> Self {
+        let inner = self.inner_mut();
+        if name == header::CONTENT_SECURITY_POLICY
+            || name == header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        {
+            inner.csp = None;
+        }
+        inner.headers.remove(name);
+        self
+    }
+
+    fn set(mut self, name: HeaderName, val: HeaderValue) -> Self {
+        self.inner_mut().headers.insert(name, val);
+        self
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Rc::get_mut(&mut self.inner).expect("Multiple copies exist")
+    }
+}
+
+/// `Content-Security-Policy` header builder.
+///
+/// Policy could contain per-request nonce for specified directives.
+/// Nonce is available for handlers via [`CspNonce`] extractor.
+#[derive(Clone, Debug, Default)]
+pub struct ContentSecurityPolicy {
+    directives: Vec<(String, Vec<String>)>,
+    nonce: Vec<String>,
+    report_only: bool,
+}
+
+impl ContentSecurityPolicy {
+    /// Create empty policy
+    pub fn new() -> Self {
+        ContentSecurityPolicy::default()
+    }
+
+    /// Add sources to the directive.
+    ///
+    /// ```rust
+    /// use ntex::web::middleware::ContentSecurityPolicy;
+    ///
+    /// let csp = ContentSecurityPolicy::new()
+    ///     .directive("default-src", &["'self'"])
+    ///     .directive("img-src", &["'self'", "https://images.example.com"])
+    ///     .directive("upgrade-insecure-requests", &[]);
+    /// ```
+    pub fn directive(mut self, name: &str, sources: &[&str]) -> Self {
+        let sources = sources.iter().map(|s| s.to_string());
+        if let Some(idx) = self.position(name) {
+            self.directives[idx].1.extend(sources);
+        } else {
+            self.directives.push((name.to_string(), sources.collect()));
+        }
+        self
+    }
+
+    /// Add per-request nonce source to the directive, i.e. `script-src`.
+    pub fn nonce(mut self, name: &str) -> Self {
+        if self.position(name).is_none() {
+            self.directives.push((name.to_string(), Vec::new()));
+        }
+        if !self.nonce.iter().any(|n| n == name) {
+            self.nonce.push(name.to_string());
+        }
+        self
+    }
+
+    /// Use `Content-Security-Policy-Report-Only` header instead
+    /// of `Content-Security-Policy`.
+    pub fn report_only(mut self, enabled: bool) -> Self {
+        self.report_only = enabled;
+        self
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.directives.iter().position(|(n, _)| n == name)
+    }
+
+    fn header_name(&self) -> HeaderName {
+        if self.report_only {
+            header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            header::CONTENT_SECURITY_POLICY
+        }
+    }
+
+    /// Render policy, with nonce source if nonce is provided
+    fn render(&self, nonce: Option<&str>) -> String {
+        let mut val = String::new();
+        for (name, sources) in &self.directives {
+            if !val.is_empty() {
+                val.push_str("; ");
+            }
+            val.push_str(name);
+            for src in sources {
+                val.push(' ');
+                val.push_str(src);
+            }
+            if let Some(nonce) = nonce {
+                if self.nonce.contains(name) {
+                    val.push_str(" 'nonce-");
+                    val.push_str(nonce);
+                    val.push('\'');
+                }
+            }
+        }
+        val
+    }
+}
+
+/// Per-request `Content-Security-Policy` nonce.
+///
+/// Nonce is generated by [`DefaultSecurityHeaders`] middleware if configured
+/// policy contains nonce sources.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CspNonce(Rc<str>);
+
+impl CspNonce {
+    fn generate() -> Self {
+        let nonce = WyRand::new().generate::<u128>();
+        CspNonce(base64::encode(nonce.to_be_bytes()).into())
+    }
+
+    /// Base64 encoded nonce
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ops::Deref for CspNonce {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<Err: ErrorRenderer> FromRequest<Err> for CspNonce {
+    type Error = DataExtractorError;
+    type Future = Ready<Self, Self::Error>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(nonce) = req.extensions().get::<CspNonce>() {
+            Ready::Ok(nonce.clone())
+        } else {
+            log::debug!(
+                "Failed to extract csp nonce, content security policy with nonce \
+                 is not configured. Request path: {:?}",
+                req.path()
+            );
+            Ready::Err(DataExtractorError::NotConfigured)
+        }
+    }
+}
+
+impl<S> Transform<S> for DefaultSecurityHeaders {
+    type Service = DefaultSecurityHeadersMiddleware<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        // policy without nonce is rendered only once
+        let csp = self.inner.csp.as_ref().and_then(|csp| {
+            if csp.nonce.is_empty() {
+                match HeaderValue::from_str(&csp.render(None)) {
+                    Ok(val) => Some((csp.header_name(), val)),
+                    Err(e) => {
+                        log::error!("Cannot create content security policy: {}", e);
+                        None
+                    }
+                }
+            } else {
+                None
+            }
+        });
+
+        DefaultSecurityHeadersMiddleware {
+            service,
+            csp,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub struct DefaultSecurityHeadersMiddleware<S> {
+    service: S,
+    inner: Rc<Inner>,
+    csp: Option<(HeaderName, HeaderValue)>,
+}
+
+impl<S, E> Service<WebRequest<E>> for DefaultSecurityHeadersMiddleware<S>
+where
+    S: Service<WebRequest<E>, Response = WebResponse>,
+    S::Future: 'static,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: WebRequest<E>) -> Self::Future {
+        let inner = self.inner.clone();
+        let mut csp = self.csp.clone();
+
+        // generate per-request nonce
+        if let Some(ref policy) = inner.csp {
+            if !policy.nonce.is_empty() {
+                let nonce = CspNonce::generate();
+                if let Ok(val) = HeaderValue::from_str(&policy.render(Some(&nonce))) {
+                    csp = Some((policy.header_name(), val));
+                }
+                req.extensions_mut().insert(nonce);
+            }
+        }
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+
+            // set response headers
+            for (key, value) in inner.headers.iter() {
+                if !res.headers().contains_key(key) {
+                    res.headers_mut().insert(key.clone(), value.clone());
+                }
+            }
+            if let Some((key, value)) = csp {
+                if !res.headers().contains_key(&key) {
+                    res.headers_mut().insert(key, value);
+                }
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, App, HttpResponse};
+
+    #[crate::rt_test]
+    async fn test_defaults() {
+        let srv = init_service(App::new().wrap(DefaultSecurityHeaders::new()).service(
+            web::resource("/").to(|| async {
+                HttpResponse::Ok()
+                    .header(header::X_FRAME_OPTIONS, "SAMEORIGIN")
+                    .finish()
+            }),
+        ))
+        .await;
+
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        let hdrs = resp.headers();
+        assert_eq!(
+            hdrs.get(header::STRICT_TRANSPORT_SECURITY).unwrap(),
+            "max-age=31536000; includeSubDomains"
+        );
+        assert_eq!(hdrs.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+        assert_eq!(hdrs.get(header::X_FRAME_OPTIONS).unwrap(), "SAMEORIGIN");
+        assert_eq!(
+            hdrs.get(header::REFERRER_POLICY).unwrap(),
+            "strict-origin-when-cross-origin"
+        );
+        assert!(!hdrs.contains_key(header::CONTENT_SECURITY_POLICY));
+    }
+
+    #[crate::rt_test]
+    async fn test_builder() {
+        let srv = init_service(
+            App::new()
+                .wrap(
+                    DefaultSecurityHeaders::new()
+                        .hsts(Duration::from_secs(600), false, true)
+                        .frame_options(FrameOptions::SameOrigin)
+                        .referrer_policy("no-referrer")
+                        .without(header::X_CONTENT_TYPE_OPTIONS)
+                        .content_security_policy(
+                            ContentSecurityPolicy::new()
+                                .directive("default-src", &["'self'"])
+                                .directive("img-src", &["'self'"])
+                                .directive("img-src", &["https://example.com"])
+                                .report_only(true),
+                        ),
+                )
+                .service(web::resource("/").to(|| async { HttpResponse::Ok() })),
+        )
+        .await;
+
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        let hdrs = resp.headers();
+        assert_eq!(
+            hdrs.get(header::STRICT_TRANSPORT_SECURITY).unwrap(),
+            "max-age=600; preload"
+        );
+        assert!(!hdrs.contains_key(header::X_CONTENT_TYPE_OPTIONS));
+        assert_eq!(hdrs.get(header::X_FRAME_OPTIONS).unwrap(), "SAMEORIGIN");
+        assert_eq!(hdrs.get(header::REFERRER_POLICY).unwrap(), "no-referrer");
+        assert!(!hdrs.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert_eq!(
+            hdrs.get(header::CONTENT_SECURITY_POLICY_REPORT_ONLY)
+                .unwrap(),
+            "default-src 'self'; img-src 'self' https://example.com"
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_nonce() {
+        let srv = init_service(
+            App::new()
+                .wrap(
+                    DefaultSecurityHeaders::new().content_security_policy(
+                        ContentSecurityPolicy::new()
+                            .directive("default-src", &["'self'"])
+                            .nonce("script-src"),
+                    ),
+                )
+                .service(
+                    web::resource("/")
+                        .to(|nonce: CspNonce| async move { nonce.to_string() }),
+                ),
+        )
+        .await;
+
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        let csp = resp
+            .headers()
+            .get(header::CONTENT_SECURITY_POLICY)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let nonce = read_body(resp).await;
+        assert_eq!(nonce.len(), 24);
+        assert_eq!(
+            csp,
+            format!(
+                "default-src 'self'; script-src 'nonce-{}'",
+                std::str::from_utf8(&nonce).unwrap()
+            )
+        );
+
+        // nonce is unique per request
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        assert_ne!(read_body(resp).await, nonce);
+    }
+
+    #[crate::rt_test]
+    async fn test_nonce_not_configured() {
+        let srv = init_service(App::new().wrap(DefaultSecurityHeaders::new()).service(
+            web::resource("/").to(|nonce: CspNonce| async move { nonce.to_string() }),
+        ))
+        .await;
+
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,942 @@
+//! Session middleware
+//!
+//! Session state is a map of json serialized values. State is persisted
+//! by a [`SessionStore`](trait.SessionStore.html), session key is kept in
+//! signed or encrypted cookie. [`CookieSessionStore`](struct.CookieSessionStore.html)
+//! keeps whole session state in the cookie.
+use std::cell::{Ref, RefCell};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use std::{collections::HashMap, fmt, future::Future, pin::Pin, rc::Rc};
+
+use coo_kie::{time::Duration as CookieDuration, Cookie, CookieJar};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub use crate::web::error::SessionError;
+pub use coo_kie::{Key, SameSite};
+
+use crate::http::header::{HeaderValue, SET_COOKIE};
+use crate::http::HttpMessage;
+use crate::service::{Service, Transform};
+use crate::util::Ready;
+use crate::web::error::ErrorRenderer;
+use crate::web::{FromRequest, HttpRequest, WebRequest, WebResponse};
+
+/// Session state, values are json serialized
+pub type SessionState = HashMap<String, String>;
+
+/// Server side session state storage
+///
+/// Store futures must not borrow the store, stores that do io are
+/// expected to clone connection handle into returned future.
+pub trait SessionStore: 'static {
+    /// The future of the `load` operation
+    type LoadFuture: Future<Output = Result<Option<SessionState>, SessionError>>;
+    /// The future of the `save`, `update` and `update_ttl` operations
+    type SaveFuture: Future<Output = Result<String, SessionError>>;
+    /// The future of the `delete` operation
+    type DeleteFuture: Future<Output = Result<(), SessionError>>;
+
+    /// Load session state by session key
+    ///
+    /// Returns `None` if session does not exist or is expired.
+    fn load(&self, key: &str) -> Self::LoadFuture;
+
+    /// Persist new session, returns session key
+    fn save(&self, state: SessionState, ttl: Duration) -> Self::SaveFuture;
+
+    /// Update existing session, returns session key
+    fn update(&self, key: &str, state: SessionState, ttl: Duration) -> Self::SaveFuture;
+
+    /// Extend session ttl, returns session key
+    fn update_ttl(&self, key: &str, ttl: Duration) -> Self::SaveFuture;
+
+    /// Delete session
+    fn delete(&self, key: &str) -> Self::DeleteFuture;
+}
+
+/// Session store that keeps whole session state in the cookie.
+///
+/// Session key is json serialized state with expiration time,
+/// cookie size is limited to 4Kb.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CookieSessionStore;
+
+#[derive(Serialize, Deserialize)]
+struct CookieState {
+    expires: u64,
+    state: SessionState,
+}
+
+impl CookieSessionStore {
+    fn serialize(state: SessionState, ttl: Duration) -> Result<String, SessionError> {
+        let key = serde_json::to_string(&CookieState {
+            expires: unix_time() + ttl.as_secs(),
+            state,
+        })?;
+        if key.len() > 4064 {
+            Err(SessionError::Store(
+                "Session state exceeds cookie size limit".to_string(),
+            ))
+        } else {
+            Ok(key)
+        }
+    }
+
+    fn deserialize(key: &str) -> Option<SessionState> {
+        serde_json::from_str::<CookieState>(key)
+            .ok()
+            .filter(|st| st.expires > unix_time())
+            .map(|st| st.state)
+    }
+}
+
+impl SessionStore for CookieSessionStore {
+    type LoadFuture = Ready<Option<SessionState>, SessionError>;
+    type SaveFuture = Ready<String, SessionError>;
+    type DeleteFuture = Ready<(), SessionError>;
+
+    fn load(&self, key: &str) -> Self::LoadFuture {
+        Ready::Ok(Self::deserialize(key))
+    }
+
+    fn save(&self, state: SessionState, ttl: Duration) -> Self::SaveFuture {
+        Ready::from(Self::serialize(state, ttl))
+    }
+
+    fn update(&self, _: &str, state: SessionState, ttl: Duration) -> Self::SaveFuture {
+        Ready::from(Self::serialize(state, ttl))
+    }
+
+    fn update_ttl(&self, key: &str, ttl: Duration) -> Self::SaveFuture {
+        let state = Self::deserialize(key).unwrap_or_default();
+        Ready::from(Self::serialize(state, ttl))
+    }
+
+    fn delete(&self, _: &str) -> Self::DeleteFuture {
+        Ready::Ok(())
+    }
+}
+
+/// Interval between expired sessions purges of `MemorySessionStore`
+const PURGE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// In-memory session store.
+///
+/// Store is shared between clones, it is suitable for tests and
+/// single process deployments. Sessions are lost on restart.
+/// Expired sessions are purged by any store operation, at most
+/// once per second.
+#[derive(Clone)]
+pub struct MemorySessionStore(Arc<Mutex<MemorySessions>>);
+
+struct MemorySessions {
+    sessions: HashMap<String, (SessionState, Instant)>,
+    purged: Instant,
+}
+
+impl MemorySessions {
+    /// Get sessions, expired sessions are purged if purge interval is elapsed
+    fn get(&mut self, now: Instant) -> &mut HashMap<String, (SessionState, Instant)> {
+        if now.duration_since(self.purged) >= PURGE_INTERVAL {
+            self.purged = now;
+            self.sessions.retain(|_, (_, expires)| *expires > now);
+        }
+        &mut self.sessions
+    }
+}
+
+impl Default for MemorySessionStore {
+    fn default() -> Self {
+        MemorySessionStore(Arc::new(Mutex::new(MemorySessions {
+            sessions: HashMap::new(),
+            purged: Instant::now(),
+        })))
+    }
+}
+
+impl MemorySessionStore {
+    /// Create new in-memory store
+    pub fn new() -> Self {
+        MemorySessionStore::default()
+    }
+
+    /// Number of stored sessions, including expired ones
+    /// that are not purged yet
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().sessions.len()
+    }
+
+    /// Check if store is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for MemorySessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySessionStore")
+            .field("sessions", &self.len())
+            .finish()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    type LoadFuture = Ready<Option<SessionState>, SessionError>;
+    type SaveFuture = Ready<String, SessionError>;
+    type DeleteFuture = Ready<(), SessionError>;
+
+    fn load(&self, key: &str) -> Self::LoadFuture {
+        let now = Instant::now();
+        let mut sessions = self.0.lock().unwrap();
+        let sessions = sessions.get(now);
+        match sessions.get(key) {
+            Some((state, expires)) if *expires > now => Ready::Ok(Some(state.clone())),
+            Some(_) => {
+                sessions.remove(key);
+                Ready::Ok(None)
+            }
+            None => Ready::Ok(None),
+        }
+    }
+
+    fn save(&self, state: SessionState, ttl: Duration) -> Self::SaveFuture {
+        let now = Instant::now();
+        let mut sessions = self.0.lock().unwrap();
+        let sessions = sessions.get(now);
+
+        let mut key = generate_key();
+        while sessions.contains_key(&key) {
+            key = generate_key();
+        }
+        sessions.insert(key.clone(), (state, now + ttl));
+        Ready::Ok(key)
+    }
+
+    fn update(&self, key: &str, state: SessionState, ttl: Duration) -> Self::SaveFuture {
+        let now = Instant::now();
+        self.0
+            .lock()
+            .unwrap()
+            .get(now)
+            .insert(key.to_string(), (state, now + ttl));
+        Ready::Ok(key.to_string())
+    }
+
+    fn update_ttl(&self, key: &str, ttl: Duration) -> Self::SaveFuture {
+        let now = Instant::now();
+        if let Some((_, expires)) = self.0.lock().unwrap().get(now).get_mut(key) {
+            *expires = now + ttl;
+        }
+        Ready::Ok(key.to_string())
+    }
+
+    fn delete(&self, key: &str) -> Self::DeleteFuture {
+        self.0.lock().unwrap().get(Instant::now()).remove(key);
+        Ready::Ok(())
+    }
+}
+
+/// Session status
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SessionStatus {
+    /// Session state has been changed
+    Changed,
+    /// Session has been purged, session cookie is removed
+    Purged,
+    /// Session key has been renewed
+    Renewed,
+    /// Session state is not changed
+    Unchanged,
+}
+
+/// Session extractor
+///
+/// Values are serialized to json. If session middleware is not registered,
+/// session changes are not persisted.
+///
+/// ```rust
+/// use ntex::web::{self, middleware::session::{Session, SessionError}};
+///
+/// async fn index(session: Session) -> Result<String, SessionError> {
+///     let counter = session.get::<u32>("counter")?.unwrap_or(0) + 1;
+///     session.insert("counter", counter)?;
+///     Ok(format!("Counter: {}", counter))
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone)]
+pub struct Session(Rc<RefCell<SessionInner>>);
+
+struct SessionInner {
+    state: SessionState,
+    status: SessionStatus,
+}
+
+impl Session {
+    fn new(state: SessionState) -> Self {
+        Session(Rc::new(RefCell::new(SessionInner {
+            state,
+            status: SessionStatus::Unchanged,
+        })))
+    }
+
+    /// Get a value from the session
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, SessionError> {
+        if let Some(val) = self.0.borrow().state.get(key) {
+            Ok(Some(serde_json::from_str(val)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Insert a value to the session
+    ///
+    /// Purged session can not be modified.
+    pub fn insert<K: Into<String>, T: Serialize>(
+        &self,
+        key: K,
+        value: T,
+    ) -> Result<(), SessionError> {
+        let mut inner = self.0.borrow_mut();
+        if inner.status != SessionStatus::Purged {
+            let value = serde_json::to_string(&value)?;
+            inner.state.insert(key.into(), value);
+            inner.changed();
+        }
+        Ok(())
+    }
+
+    /// Remove value from the session
+    ///
+    /// Returns json serialized value.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut inner = self.0.borrow_mut();
+        if inner.status != SessionStatus::Purged {
+            let val = inner.state.remove(key);
+            if val.is_some() {
+                inner.changed();
+            }
+            val
+        } else {
+            None
+        }
+    }
+
+    /// Remove all values from the session
+    pub fn clear(&self) {
+        let mut inner = self.0.borrow_mut();
+        if inner.status != SessionStatus::Purged && !inner.state.is_empty() {
+            inner.state.clear();
+            inner.changed();
+        }
+    }
+
+    /// Remove session from the store and remove session cookie
+    pub fn purge(&self) {
+        let mut inner = self.0.borrow_mut();
+        inner.state.clear();
+        inner.status = SessionStatus::Purged;
+    }
+
+    /// Generate new session key, session state is preserved
+    ///
+    /// Session key should be renewed on privilege level change,
+    /// i.e. after login, to prevent session fixation.
+    pub fn renew(&self) {
+        let mut inner = self.0.borrow_mut();
+        if inner.status != SessionStatus::Purged {
+            inner.status = SessionStatus::Renewed;
+        }
+    }
+
+    /// Session status
+    pub fn status(&self) -> SessionStatus {
+        self.0.borrow().status
+    }
+
+    /// Session entries
+    pub fn entries(&self) -> Ref<'_, SessionState> {
+        Ref::map(self.0.borrow(), |inner| &inner.state)
+    }
+
+    fn get_session(req: &HttpRequest) -> Session {
+        if let Some(session) = req.extensions().get::<Session>() {
+            return session.clone();
+        }
+        let session = Session::new(SessionState::new());
+        req.extensions_mut().insert(session.clone());
+        session
+    }
+
+    fn take(&self) -> (SessionState, SessionStatus) {
+        let mut inner = self.0.borrow_mut();
+        let status = std::mem::replace(&mut inner.status, SessionStatus::Unchanged);
+        (std::mem::take(&mut inner.state), status)
+    }
+}
+
+impl SessionInner {
+    fn changed(&mut self) {
+        if self.status != SessionStatus::Renewed {
+            self.status = SessionStatus::Changed;
+        }
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.0.borrow();
+        f.debug_struct("Session")
+            .field("state", &inner.state)
+            .field("status", &inner.status)
+            .finish()
+    }
+}
+
+impl<Err: ErrorRenderer> FromRequest<Err> for Session {
+    type Error = Err::Container;
+    type Future = Ready<Session, Err::Container>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut crate::http::Payload) -> Self::Future {
+        Ready::Ok(Session::get_session(req))
+    }
+}
+
+/// Session cookie content protection
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CookieContentSecurity {
+    /// Cookie value is encrypted, it provides confidentiality,
+    /// integrity, and authenticity
+    Private,
+    /// Cookie value is signed, it provides integrity and authenticity
+    Signed,
+}
+
+/// Session middleware
+///
+/// ```rust
+/// use ntex::web::{self, App, HttpResponse};
+/// use ntex::web::middleware::session::{CookieSessionStore, Key, SessionMiddleware};
+///
+/// let key = Key::generate();
+/// let app = App::new()
+///     .wrap(SessionMiddleware::new(CookieSessionStore, key).cookie_name("session"))
+///     .service(web::resource("/").to(|| async { HttpResponse::Ok() }));
+/// ```
+pub struct SessionMiddleware<S> {
+    inner: Rc<Inner<S>>,
+}
+
+struct Inner<S> {
+    store: S,
+    key: Key,
+    security: CookieContentSecurity,
+    name: String,
+    path: String,
+    domain: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: SameSite,
+    ttl: Duration,
+    rolling: bool,
+}
+
+impl<S: SessionStore> SessionMiddleware<S> {
+    /// Construct session middleware with session store and cookie key
+    pub fn new(store: S, key: Key) -> Self {
+        SessionMiddleware {
+            inner: Rc::new(Inner {
+                store,
+                key,
+                security: CookieContentSecurity::Private,
+                name: "id".to_string(),
+                path: "/".to_string(),
+                domain: None,
+                secure: true,
+                http_only: true,
+                same_site: SameSite::Lax,
+                ttl: Duration::from_secs(86400),
+                rolling: false,
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner<S> {
+        Rc::get_mut(&mut self.inner).expect("Multiple copies exist")
+    }
+
+    /// Set session cookie content protection. Default is `Private`
+    pub fn cookie_content_security(mut self, security: CookieContentSecurity) -> Self {
+        self.inner_mut().security = security;
+        self
+    }
+
+    /// Set session cookie name. Default is `id`
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.inner_mut().name = name.to_string();
+        self
+    }
+
+    /// Set session cookie path. Default is `/`
+    pub fn cookie_path(mut self, path: &str) -> Self {
+        self.inner_mut().path = path.to_string();
+        self
+    }
+
+    /// Set session cookie domain
+    pub fn cookie_domain(mut self, domain: &str) -> Self {
+        self.inner_mut().domain = Some(domain.to_string());
+        self
+    }
+
+    /// Set `Secure` attribute of the session cookie. Default is true
+    pub fn cookie_secure(mut self, value: bool) -> Self {
+        self.inner_mut().secure = value;
+        self
+    }
+
+    /// Set `HttpOnly` attribute of the session cookie. Default is true
+    pub fn cookie_http_only(mut self, value: bool) -> Self {
+        self.inner_mut().http_only = value;
+        self
+    }
+
+    /// Set `SameSite` attribute of the session cookie. Default is `Lax`
+    pub fn cookie_same_site(mut self, value: SameSite) -> Self {
+        self.inner_mut().same_site = value;
+        self
+    }
+
+    /// Set session ttl, it is also used as cookie `Max-Age`.
+    ///
+    /// Default is 1 day.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.inner_mut().ttl = ttl;
+        self
+    }
+
+    /// Extend session ttl on every request.
+    ///
+    /// By default ttl is extended only if session state is changed.
+    pub fn rolling(mut self, value: bool) -> Self {
+        self.inner_mut().rolling = value;
+        self
+    }
+}
+
+impl<S: SessionStore> Inner<S> {
+    /// Persist session state and set session cookie
+    async fn persist(
+        &self,
+        res: &mut WebResponse,
+        mut key: Option<String>,
+        session: Session,
+    ) -> Result<(), SessionError> {
+        let (state, status) = session.take();
+        let key = match status {
+            SessionStatus::Changed | SessionStatus::Renewed => {
+                if status == SessionStatus::Renewed {
+                    if let Some(ref k) = key.take() {
+                        self.store.delete(k).await?;
+                    }
+                }
+                if let Some(ref k) = key {
+                    self.store.update(k, state, self.ttl).await?
+                } else if !state.is_empty() {
+                    self.store.save(state, self.ttl).await?
+                } else {
+                    return Ok(());
+                }
+            }
+            SessionStatus::Purged => {
+                if let Some(ref k) = key {
+                    self.store.delete(k).await?;
+                    self.remove_cookie(res);
+                }
+                return Ok(());
+            }
+            SessionStatus::Unchanged => match key {
+                Some(ref k) if self.rolling => self.store.update_ttl(k, self.ttl).await?,
+                _ => return Ok(()),
+            },
+        };
+        self.set_cookie(res, key);
+        Ok(())
+    }
+}
+
+impl<S> Inner<S> {
+    /// Get session key from request cookie
+    fn session_key<E>(&self, req: &WebRequest<E>) -> Option<String> {
+        let cookie = req.cookie(&self.name)?;
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+
+        let cookie = match self.security {
+            CookieContentSecurity::Private => jar.private(&self.key).get(&self.name),
+            CookieContentSecurity::Signed => jar.signed(&self.key).get(&self.name),
+        };
+        cookie.map(|c| c.value().to_string())
+    }
+
+    fn set_cookie(&self, res: &mut WebResponse, key: String) {
+        let mut cookie = Cookie::new(self.name.clone(), key);
+        cookie.set_path(self.path.clone());
+        cookie.set_secure(self.secure);
+        cookie.set_http_only(self.http_only);
+        cookie.set_same_site(self.same_site);
+        cookie.set_max_age(CookieDuration::seconds(self.ttl.as_secs() as i64));
+        if let Some(ref domain) = self.domain {
+            cookie.set_domain(domain.clone());
+        }
+
+        let mut jar = CookieJar::new();
+        match self.security {
+            CookieContentSecurity::Private => jar.private_mut(&self.key).add(cookie),
+            CookieContentSecurity::Signed => jar.signed_mut(&self.key).add(cookie),
+        }
+        for cookie in jar.delta() {
+            self.add_cookie(res, cookie);
+        }
+    }
+
+    fn remove_cookie(&self, res: &mut WebResponse) {
+        let mut cookie = Cookie::new(self.name.clone(), "");
+        cookie.set_path(self.path.clone());
+        if let Some(ref domain) = self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        cookie.make_removal();
+        self.add_cookie(res, &cookie);
+    }
+
+    fn add_cookie(&self, res: &mut WebResponse, cookie: &Cookie<'_>) {
+        match HeaderValue::from_str(&cookie.encoded().to_string()) {
+            Ok(val) => res.headers_mut().append(SET_COOKIE, val),
+            Err(e) => log::error!("Cannot set session cookie: {}", e),
+        }
+    }
+}
+
+impl<S, St> Transform<S> for SessionMiddleware<St> {
+    type Service = SessionMiddlewareService<S, St>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        SessionMiddlewareService {
+            service: Rc::new(service),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub struct SessionMiddlewareService<S, St> {
+    service: Rc<S>,
+    inner: Rc<Inner<St>>,
+}
+
+impl<S, St, E> Service<WebRequest<E>> for SessionMiddlewareService<S, St>
+where
+    S: Service<WebRequest<E>, Response = WebResponse> + 'static,
+    St: SessionStore,
+    E: ErrorRenderer,
+    SessionError: Into<E::Container>,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: WebRequest<E>) -> Self::Future {
+        let inner = self.inner.clone();
+        let srv = self.service.clone();
+
+        Box::pin(async move {
+            let mut key = inner.session_key(&req);
+            let state = if let Some(ref k) = key {
+                match inner.store.load(k).await {
+                    Ok(Some(state)) => state,
+                    Ok(None) => {
+                        key = None;
+                        SessionState::new()
+                    }
+                    Err(e) => {
+                        let req = req.into_parts().0;
+                        return Ok(WebResponse::from_err::<E, _>(e, req));
+                    }
+                }
+            } else {
+                SessionState::new()
+            };
+
+            let session = Session::new(state);
+            req.extensions_mut().insert(session.clone());
+            let mut res = srv.call(req).await?;
+
+            if let Err(e) = inner.persist(&mut res, key, session).await {
+                let req = res.request().clone();
+                Ok(WebResponse::from_err::<E, _>(e, req))
+            } else {
+                Ok(res)
+            }
+        })
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Generate session key, 256 bits from os random generator
+fn generate_key() -> String {
+    let mut key = [0u8; 32];
+    getrandom::getrandom(&mut key).expect("Os random generator is not available");
+    base64::encode_config(key, base64::URL_SAFE_NO_PAD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, App, HttpResponse};
+
+    fn session_cookie(res: &WebResponse) -> Option<Cookie<'static>> {
+        res.headers()
+            .get(SET_COOKIE)
+            .and_then(|v| Cookie::parse_encoded(v.to_str().unwrap().to_string()).ok())
+    }
+
+    fn app_routes(cfg: &mut web::ServiceConfig) {
+        cfg.service(web::resource("/").to(|session: Session| async move {
+            let counter = session.get::<u32>("counter")?.unwrap_or(0) + 1;
+            session.insert("counter", counter)?;
+            Ok::<_, SessionError>(counter.to_string())
+        }))
+        .service(web::resource("/get").to(|session: Session| async move {
+            let counter = session.get::<u32>("counter")?.unwrap_or(0);
+            Ok::<_, SessionError>(counter.to_string())
+        }))
+        .service(web::resource("/renew").to(|session: Session| async move {
+            session.renew();
+            HttpResponse::Ok()
+        }))
+        .service(web::resource("/purge").to(|session: Session| async move {
+            session.purge();
+            HttpResponse::Ok()
+        }));
+    }
+
+    #[crate::rt_test]
+    async fn test_cookie_store() {
+        let key = Key::generate();
+        let srv = init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(CookieSessionStore, key.clone()))
+                .configure(app_routes),
+        )
+        .await;
+
+        let res = call_service(&srv, TestRequest::with_uri("/get").to_request()).await;
+        assert!(session_cookie(&res).is_none());
+
+        let res = call_service(&srv, TestRequest::with_uri("/").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+        assert_eq!(cookie.name(), "id");
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert!(!cookie.value().contains("counter"));
+
+        let req = TestRequest::with_uri("/").cookie(cookie).to_request();
+        let res = call_service(&srv, req).await;
+        let cookie = session_cookie(&res).unwrap();
+        assert_eq!(read_body(res).await, "2");
+
+        // tampered cookie
+        let mut tampered = cookie.clone();
+        tampered.set_value(format!("{}0", cookie.value()));
+        let req = TestRequest::with_uri("/get").cookie(tampered).to_request();
+        assert_eq!(read_body(call_service(&srv, req).await).await, "0");
+
+        // cookie signed with different key
+        let srv2 = init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(CookieSessionStore, Key::generate()))
+                .configure(app_routes),
+        )
+        .await;
+        let req = TestRequest::with_uri("/get")
+            .cookie(cookie.clone())
+            .to_request();
+        assert_eq!(read_body(call_service(&srv2, req).await).await, "0");
+
+        let req = TestRequest::with_uri("/purge").cookie(cookie).to_request();
+        let res = call_service(&srv, req).await;
+        let cookie = session_cookie(&res).unwrap();
+        assert_eq!(cookie.value(), "");
+        assert_eq!(cookie.max_age(), Some(CookieDuration::ZERO));
+    }
+
+    #[crate::rt_test]
+    async fn test_signed_cookie() {
+        let srv = init_service(
+            App::new()
+                .wrap(
+                    SessionMiddleware::new(CookieSessionStore, Key::generate())
+                        .cookie_content_security(CookieContentSecurity::Signed)
+                        .cookie_name("session")
+                        .cookie_secure(false),
+                )
+                .configure(app_routes),
+        )
+        .await;
+
+        let res = call_service(&srv, TestRequest::with_uri("/").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+        assert_eq!(cookie.name(), "session");
+        assert_eq!(cookie.secure(), None);
+        assert!(cookie.value().contains("counter"));
+
+        let req = TestRequest::with_uri("/get").cookie(cookie).to_request();
+        assert_eq!(read_body(call_service(&srv, req).await).await, "1");
+    }
+
+    #[crate::rt_test]
+    async fn test_memory_store() {
+        let store = MemorySessionStore::new();
+        let srv = init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(store.clone(), Key::generate()).rolling(true))
+                .configure(app_routes),
+        )
+        .await;
+
+        let res = call_service(&srv, TestRequest::with_uri("/").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+        assert_eq!(store.len(), 1);
+
+        // rolling session
+        let req = TestRequest::with_uri("/get")
+            .cookie(cookie.clone())
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert!(session_cookie(&res).is_some());
+        assert_eq!(read_body(res).await, "1");
+
+        // renew session key
+        let req = TestRequest::with_uri("/renew")
+            .cookie(cookie.clone())
+            .to_request();
+        let res = call_service(&srv, req).await;
+        let new_cookie = session_cookie(&res).unwrap();
+        assert_ne!(cookie.value(), new_cookie.value());
+        assert_eq!(store.len(), 1);
+
+        let req = TestRequest::with_uri("/get").cookie(cookie).to_request();
+        assert_eq!(read_body(call_service(&srv, req).await).await, "0");
+        let req = TestRequest::with_uri("/get")
+            .cookie(new_cookie.clone())
+            .to_request();
+        assert_eq!(read_body(call_service(&srv, req).await).await, "1");
+
+        let req = TestRequest::with_uri("/purge")
+            .cookie(new_cookie)
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(store.is_empty());
+    }
+
+    #[crate::rt_test]
+    async fn test_session_ttl() {
+        let store = MemorySessionStore::new();
+        let key = store
+            .save(SessionState::new(), Duration::from_secs(0))
+            .await
+            .unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), None);
+        assert!(store.is_empty());
+
+        let key = CookieSessionStore
+            .save(SessionState::new(), Duration::from_secs(0))
+            .await
+            .unwrap();
+        assert_eq!(CookieSessionStore.load(&key).await.unwrap(), None);
+        let key = CookieSessionStore
+            .save(SessionState::new(), Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(CookieSessionStore.load(&key).await.unwrap().is_some());
+    }
+
+    #[crate::rt_test]
+    async fn test_memory_store_purge() {
+        let store = MemorySessionStore::new();
+        for _ in 0..3 {
+            store
+                .save(SessionState::new(), Duration::from_secs(0))
+                .await
+                .unwrap();
+        }
+        let key = store
+            .save(SessionState::new(), Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(store.len(), 4);
+
+        // expired sessions are purged by reads after purge interval
+        store.0.lock().unwrap().purged -= PURGE_INTERVAL;
+        assert!(store.load("unknown").await.unwrap().is_none());
+        assert_eq!(store.len(), 1);
+        assert!(store.load(&key).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_generate_key() {
+        let key = generate_key();
+        assert_eq!(key.len(), 43);
+        assert!(key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_ne!(key, generate_key());
+    }
+
+    #[test]
+    fn test_session() {
+        let session = Session::new(SessionState::new());
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        session.insert("key", "value").unwrap();
+        assert_eq!(session.status(), SessionStatus::Changed);
+        assert_eq!(
+            session.get::<String>("key").unwrap(),
+            Some("value".to_string())
+        );
+        assert!(session.get::<u32>("key").is_err());
+        assert_eq!(session.remove("key"), Some("\"value\"".to_string()));
+        assert!(session.entries().is_empty());
+
+        session.renew();
+        session.insert("key", 1).unwrap();
+        assert_eq!(session.status(), SessionStatus::Renewed);
+
+        session.purge();
+        session.insert("key", 1).unwrap();
+        assert_eq!(session.status(), SessionStatus::Purged);
+        assert!(session.entries().is_empty());
+    }
+}
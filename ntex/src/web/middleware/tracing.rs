@@ -0,0 +1,458 @@
+//! Request tracing middleware
+use std::task::{Context, Poll};
+use std::{fmt, future::Future, ops, pin::Pin, rc::Rc, time};
+
+use nanorand::{Rng, WyRand};
+use tracing_pkg::{field, info_span, Instrument, Span};
+
+use crate::http::header::{HeaderName, HeaderValue};
+use crate::http::Payload;
+use crate::service::{Service, Transform};
+use crate::util::Ready;
+use crate::web::error::{DataExtractorError, ErrorRenderer};
+use crate::web::{FromRequest, HttpRequest, WebRequest, WebResponse};
+
+/// W3C trace context header
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+
+/// `Middleware` for request tracing.
+///
+/// Middleware creates `tracing` span per request. Span records request method,
+/// path, matched resource pattern, response status and latency. Trace context is
+/// extracted from W3C `traceparent` request header, new trace is started
+/// if request does not contain valid header. Response contains `traceparent`
+/// header with the request's span id.
+///
+/// Request span and trace context are available to handlers via
+/// `RequestSpan` extractor.
+///
+/// ```rust
+/// use ntex::web::{self, middleware, App};
+///
+/// async fn index(span: middleware::RequestSpan) -> String {
+///     span.in_scope(|| log::info!("handle request"));
+///
+///     // propagate trace context to upstream service
+///     span.context().traceparent()
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(middleware::Tracing::new())
+///         .service(web::resource("/index.html").to(index));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Tracing {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    inject: bool,
+}
+
+impl Default for Tracing {
+    fn default() -> Self {
+        Tracing {
+            inner: Rc::new(Inner { inject: true }),
+        }
+    }
+}
+
+impl Tracing {
+    /// Construct `Tracing` middleware.
+    pub fn new() -> Tracing {
+        Tracing::default()
+    }
+
+    /// Add `traceparent` header to responses. By default it is enabled.
+    pub fn inject_response(mut self, enabled: bool) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .inject = enabled;
+        self
+    }
+}
+
+impl<S> Transform<S> for Tracing {
+    type Service = TracingMiddleware<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        TracingMiddleware {
+            service,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Tracing middleware
+pub struct TracingMiddleware<S> {
+    service: S,
+    inner: Rc<Inner>,
+}
+
+impl<S, E> Service<WebRequest<E>> for TracingMiddleware<S>
+where
+    S: Service<WebRequest<E>, Response = WebResponse>,
+    S::Error: fmt::Display,
+    S::Future: 'static,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline]
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        self.service.poll_shutdown(cx, is_error)
+    }
+
+    fn call(&self, req: WebRequest<E>) -> Self::Future {
+        let start = time::Instant::now();
+        let context = req
+            .headers()
+            .get(&TRACEPARENT)
+            .and_then(|val| val.to_str().ok())
+            .and_then(TraceContext::parse)
+            .map(TraceContext::child)
+            .unwrap_or_else(TraceContext::new);
+
+        let span = info_span!(
+            "request",
+            http.method = %req.method(),
+            http.target = %req.path(),
+            http.route = field::Empty,
+            http.status_code = field::Empty,
+            latency_ms = field::Empty,
+            error = field::Empty,
+            trace_id = %format_args!("{:032x}", context.trace_id),
+            span_id = %format_args!("{:016x}", context.span_id),
+            parent_id = field::Empty,
+        );
+        if let Some(parent_id) = context.parent_id {
+            span.record(
+                "parent_id",
+                field::display(format_args!("{:016x}", parent_id)),
+            );
+        }
+        req.extensions_mut().insert(RequestSpan(Rc::new(SpanInner {
+            span: span.clone(),
+            context,
+        })));
+
+        let inject = self.inner.inject;
+        let fut = self.service.call(req).instrument(span.clone());
+
+        Box::pin(async move {
+            let res = fut.await;
+            span.record("latency_ms", start.elapsed().as_millis() as u64);
+
+            match res {
+                Ok(mut res) => {
+                    span.record("http.status_code", res.status().as_u16());
+                    if let Some(pattern) = res.request().match_pattern() {
+                        span.record("http.route", pattern.as_str());
+                    }
+                    if inject {
+                        if let Ok(val) = HeaderValue::from_str(&context.traceparent()) {
+                            res.headers_mut().insert(TRACEPARENT, val);
+                        }
+                    }
+                    Ok(res)
+                }
+                Err(e) => {
+                    span.record("error", field::display(&e));
+                    Err(e)
+                }
+            }
+        })
+    }
+}
+
+/// W3C trace context of the request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+    parent_id: Option<u64>,
+    flags: u8,
+}
+
+impl TraceContext {
+    /// Start new sampled trace
+    fn new() -> Self {
+        let mut rng = WyRand::new();
+        let trace_id = loop {
+            let id = (rng.generate::<u64>() as u128) << 64 | rng.generate::<u64>() as u128;
+            if id != 0 {
+                break id;
+            }
+        };
+        TraceContext {
+            trace_id,
+            span_id: span_id(&mut rng),
+            parent_id: None,
+            flags: 1,
+        }
+    }
+
+    /// Continue trace with new span
+    fn child(self) -> Self {
+        TraceContext {
+            trace_id: self.trace_id,
+            span_id: span_id(&mut WyRand::new()),
+            parent_id: Some(self.span_id),
+            flags: self.flags,
+        }
+    }
+
+    /// Parse `traceparent` header value
+    fn parse(val: &str) -> Option<Self> {
+        let mut parts = val.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+
+        let is_hex = |s: &str, len: usize| {
+            s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        if !is_hex(version, 2)
+            || version == "ff"
+            || (version == "00" && parts.next().is_some())
+            || !is_hex(trace_id, 32)
+            || !is_hex(span_id, 16)
+            || !is_hex(flags, 2)
+        {
+            return None;
+        }
+
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let span_id = u64::from_str_radix(span_id, 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            None
+        } else {
+            Some(TraceContext {
+                trace_id,
+                span_id,
+                parent_id: None,
+                flags: u8::from_str_radix(flags, 16).ok()?,
+            })
+        }
+    }
+
+    /// Trace id
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// Span id of the request
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// Span id of the caller, if request contains trace context
+    pub fn parent_id(&self) -> Option<u64> {
+        self.parent_id
+    }
+
+    /// Check if trace is sampled by the caller
+    pub fn is_sampled(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    /// `traceparent` header value for outgoing requests
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+fn span_id(rng: &mut WyRand) -> u64 {
+    loop {
+        let id = rng.generate::<u64>();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// Request span extractor.
+///
+/// Span is created by `Tracing` middleware. If middleware is not registered,
+/// using `RequestSpan` extractor would cause *Internal Server Error* response.
+#[derive(Clone)]
+pub struct RequestSpan(Rc<SpanInner>);
+
+struct SpanInner {
+    span: Span,
+    context: TraceContext,
+}
+
+impl RequestSpan {
+    /// Request span
+    pub fn span(&self) -> &Span {
+        &self.0.span
+    }
+
+    /// Trace context of the request
+    pub fn context(&self) -> &TraceContext {
+        &self.0.context
+    }
+}
+
+impl ops::Deref for RequestSpan {
+    type Target = Span;
+
+    fn deref(&self) -> &Span {
+        &self.0.span
+    }
+}
+
+impl fmt::Debug for RequestSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestSpan")
+            .field("span", &self.0.span)
+            .field("context", &self.0.context)
+            .finish()
+    }
+}
+
+impl<Err: ErrorRenderer> FromRequest<Err> for RequestSpan {
+    type Error = DataExtractorError;
+    type Future = Ready<Self, Self::Error>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(span) = req.extensions().get::<RequestSpan>() {
+            Ready::Ok(span.clone())
+        } else {
+            log::debug!(
+                "Failed to extract request span, tracing middleware is not configured. \
+                 Request path: {:?}",
+                req.path()
+            );
+            Ready::Err(DataExtractorError::NotConfigured)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::util::Bytes;
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, App};
+
+    #[test]
+    fn test_parse() {
+        let ctx =
+            TraceContext::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+                .unwrap();
+        assert_eq!(ctx.trace_id(), 0x0af7651916cd43dd8448eb211c80319c);
+        assert_eq!(ctx.span_id(), 0xb7ad6b7169203331);
+        assert!(ctx.is_sampled());
+        assert_eq!(
+            ctx.traceparent(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+        );
+
+        let child = ctx.child();
+        assert_eq!(child.trace_id(), ctx.trace_id());
+        assert_eq!(child.parent_id(), Some(ctx.span_id()));
+        assert_ne!(child.span_id(), ctx.span_id());
+
+        // future versions could have more fields
+        assert!(TraceContext::parse(
+            "01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00-xyz"
+        )
+        .is_some());
+
+        for val in &[
+            "",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-xyz",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c8031-b7ad6b7169203331-01",
+        ] {
+            assert!(TraceContext::parse(val).is_none(), "{}", val);
+        }
+    }
+
+    #[crate::rt_test]
+    async fn test_tracing() {
+        let srv = init_service(App::new().wrap(Tracing::new()).service(
+            web::scope("/users").service(web::resource("/{id}").to(
+                |span: RequestSpan, req: HttpRequest| async move {
+                    assert_eq!(req.match_pattern().unwrap(), "/users/{id}");
+                    let ctx = span.context();
+                    format!(
+                        "{:032x} {:?}",
+                        ctx.trace_id(),
+                        ctx.parent_id().map(|id| format!("{:016x}", id))
+                    )
+                },
+            )),
+        ))
+        .await;
+
+        let req = TestRequest::with_uri("/users/1")
+            .header(
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            )
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let traceparent = resp
+            .headers()
+            .get("traceparent")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(traceparent.starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+        assert!(!traceparent.contains("b7ad6b7169203331"));
+        assert_eq!(
+            read_body(resp).await,
+            Bytes::from_static(
+                b"0af7651916cd43dd8448eb211c80319c Some(\"b7ad6b7169203331\")"
+            )
+        );
+
+        // new trace
+        let req = TestRequest::with_uri("/users/1").to_request();
+        let resp = call_service(&srv, req).await;
+        let traceparent = TraceContext::parse(
+            resp.headers().get("traceparent").unwrap().to_str().unwrap(),
+        )
+        .unwrap();
+        assert!(traceparent.is_sampled());
+        let body = read_body(resp).await;
+        assert_eq!(
+            body,
+            Bytes::from(format!("{:032x} None", traceparent.trace_id()))
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_not_configured() {
+        let srv = init_service(
+            App::new().service(web::resource("/").to(|_: RequestSpan| async { "ok" })),
+        )
+        .await;
+
+        let req = TestRequest::default().to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
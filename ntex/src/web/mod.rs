@@ -49,7 +49,7 @@
 //! * Streaming and pipelining
 //! * Keep-alive and slow requests handling
 //! * *WebSockets* server/client
-//! * Transparent content compression/decompression (br, gzip, deflate)
+//! * Transparent content compression/decompression (br, gzip, deflate, zstd)
 //! * Configurable request routing
 //! * SSL support with OpenSSL or `rustls`
 //! * Middlewares
@@ -58,7 +58,9 @@
 //! ## Package feature
 //!
 //! * `cookie` - enables http cookie support
+//! * `session` - enables session middleware
 //! * `compress` - enables content encoding compression support
+//! * `zstd` - enables zstd content encoding support, implies `compress`
 //! * `openssl` - enables ssl support via `openssl` crate
 //! * `rustls` - enables ssl support via `rustls` crate
 
@@ -67,9 +69,12 @@ mod app_service;
 mod config;
 pub mod error;
 mod error_default;
+mod error_problem;
 mod extract;
+pub mod fs;
 pub mod guard;
 mod handler;
+pub mod health;
 mod httprequest;
 mod info;
 pub mod middleware;
@@ -101,18 +106,20 @@ pub use ntex_macros::web_trace as trace;
 pub use crate::http::Response as HttpResponse;
 pub use crate::http::ResponseBuilder as HttpResponseBuilder;
 
-pub use self::app::App;
+pub use self::app::{App, TrailingSlash};
 pub use self::config::ServiceConfig;
 pub use self::error::{
     DefaultError, Error, ErrorContainer, ErrorRenderer, WebResponseError,
 };
 pub use self::extract::FromRequest;
-pub use self::handler::Handler;
+pub use self::handler::{Handler, PanicConfig};
 pub use self::httprequest::HttpRequest;
+pub use self::info::{ForwardedHeader, TrustedProxies};
 pub use self::request::WebRequest;
 pub use self::resource::Resource;
 pub use self::responder::Responder;
 pub use self::response::WebResponse;
+pub use self::rmap::NamedResource;
 pub use self::route::Route;
 pub use self::scope::Scope;
 pub use self::server::HttpServer;
@@ -126,10 +133,11 @@ pub mod dev {
     //! traits by adding a glob import to the top of ntex::web heavy modules:
 
     use super::Handler;
+    pub use crate::web::app::Middlewares;
     pub use crate::web::config::AppConfig;
     pub use crate::web::info::ConnectionInfo;
     pub use crate::web::rmap::ResourceMap;
-    pub use crate::web::route::IntoRoutes;
+    pub use crate::web::route::{IntoRoutes, RouteInfo, RouteTable};
     pub use crate::web::service::{WebServiceAdapter, WebServiceConfig, WebServiceFactory};
 
     pub(crate) fn insert_slesh(mut patterns: Vec<String>) -> Vec<String> {
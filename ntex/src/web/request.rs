@@ -222,6 +222,14 @@ impl<Err> WebRequest<Err> {
         Rc::get_mut(&mut (self.req).0).unwrap().payload = payload;
     }
 
+    /// Add matched resource id
+    pub(super) fn push_resource_id(&mut self, id: u16) {
+        Rc::get_mut(&mut (self.req).0)
+            .unwrap()
+            .resource_ids
+            .push(id);
+    }
+
     #[doc(hidden)]
     /// Set new app state container
     pub fn set_state_container(&mut self, extensions: Rc<Extensions>) {
@@ -9,6 +9,7 @@ use crate::service::{pipeline_factory, PipelineFactory};
 use crate::service::{Identity, IntoServiceFactory, Service, ServiceFactory, Transform};
 use crate::util::{Either, Extensions, Ready};
 
+use super::app::{middleware_order, Filter, Middlewares, Stack};
 use super::dev::{insert_slesh, WebServiceConfig, WebServiceFactory};
 use super::error::ErrorRenderer;
 use super::extract::FromRequest;
@@ -17,7 +18,7 @@ use super::request::WebRequest;
 use super::responder::Responder;
 use super::response::WebResponse;
 use super::route::{IntoRoutes, Route, RouteService};
-use super::{app::Filter, app::Stack, guard::Guard, types::State};
+use super::{guard::Guard, types::State};
 
 type HttpService<Err: ErrorRenderer> =
     BoxService<WebRequest<Err>, WebResponse, Err::Container>;
@@ -296,6 +297,23 @@ where
         }
     }
 
+    /// Register a resource middleware with custom name.
+    ///
+    /// By default middleware name is a name of the middleware type.
+    pub fn wrap_named<U>(self, name: &'static str, mw: U) -> Resource<Err, Stack<M, U>, T> {
+        let mut resource = self.wrap(mw);
+        resource.middleware.set_name(name);
+        resource
+    }
+
+    /// Names of registered middlewares in execution order.
+    pub fn middleware_order(&self) -> Vec<&'static str>
+    where
+        M: Middlewares,
+    {
+        middleware_order(&self.middleware)
+    }
+
     /// Default service to be used if no matching route could be found.
     /// By default *405* response get returned. Resource does not use
     /// default handler from `App` or `Scope`.
@@ -347,6 +365,11 @@ where
             config.set_service_state(ext);
         }
 
+        // route table
+        for route in &self.routes {
+            config.register_route(route.info(rdef.pattern(), self.name.as_ref()));
+        }
+
         let router_factory = ResourceRouterFactory {
             routes: self.routes,
             state: self.state.map(Rc::new),
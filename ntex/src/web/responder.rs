@@ -6,9 +6,7 @@ use crate::http::header::{HeaderMap, HeaderName, HeaderValue};
 use crate::http::{Response, ResponseBuilder, StatusCode};
 use crate::util::{Bytes, BytesMut, Either};
 
-use super::error::{
-    DefaultError, ErrorContainer, ErrorRenderer, InternalError, WebResponseError,
-};
+use super::error::{self, DefaultError, ErrorRenderer, InternalError, WebResponseError};
 use super::httprequest::HttpRequest;
 
 pub struct Ready<T>(Option<T>);
@@ -142,7 +140,7 @@ where
     fn respond_to(self, req: &HttpRequest) -> Self::Future {
         match self {
             Ok(val) => Either::Left(val.respond_to(req)),
-            Err(e) => Either::Right(Ready(Some(e.into().error_response(req)))),
+            Err(e) => Either::Right(Ready(Some(error::render::<Err>(&e.into(), req)))),
         }
     }
 }
@@ -3,7 +3,7 @@ use std::fmt;
 use crate::http::body::{Body, MessageBody, ResponseBody};
 use crate::http::{HeaderMap, Response, ResponseHead, StatusCode};
 
-use super::error::{ErrorContainer, ErrorRenderer};
+use super::error::{self, ErrorRenderer};
 use super::httprequest::HttpRequest;
 
 /// An service http response
@@ -24,7 +24,7 @@ impl WebResponse {
         request: HttpRequest,
     ) -> Self {
         let err = err.into();
-        let res: Response = err.error_response(&request);
+        let res: Response = error::render::<Err>(&err, &request);
 
         if res.head().status == StatusCode::INTERNAL_SERVER_ERROR {
             log::error!("Internal Server Error: {:?}", err);
@@ -8,6 +8,23 @@ use crate::util::HashMap;
 #[cfg(feature = "url")]
 use crate::web::httprequest::HttpRequest;
 
+/// Typed named resource.
+///
+/// Type describes path parameters of the named resource and could be used
+/// for url generation with `HttpRequest::url_for_typed()` method.
+/// Resource name and parameter names get validated during
+/// application startup, check `App::named_resource()` method.
+pub trait NamedResource {
+    /// Name of the resource
+    const NAME: &'static str;
+
+    /// Names of the path parameters, in pattern order
+    const PARAMS: &'static [&'static str];
+
+    /// Values of the path parameters, in pattern order
+    fn params(&self) -> Vec<String>;
+}
+
 #[derive(Clone, Debug)]
 pub struct ResourceMap {
     #[allow(dead_code)]
@@ -44,6 +61,67 @@ impl ResourceMap {
             }
         }
     }
+
+    /// Build pattern of the resource matched with resource ids,
+    /// top level id goes first
+    pub(crate) fn match_pattern(&self, ids: &[u16]) -> Option<String> {
+        let mut rmap = self;
+        let mut pattern = String::new();
+        let mut ids = ids.iter();
+        while let Some(id) = ids.next() {
+            let (rdef, nested) = rmap.patterns.get(*id as usize)?;
+            let p = rdef.pattern();
+            if pattern.ends_with('/') && p.starts_with('/') {
+                pattern.push_str(&p[1..]);
+            } else {
+                pattern.push_str(p);
+            }
+            match nested {
+                Some(nested) => rmap = nested,
+                None if ids.next().is_none() => return Some(pattern),
+                None => return None,
+            }
+        }
+        // request is handled by scope's default service
+        None
+    }
+
+    /// Check that named resource exists and its path parameters
+    /// match the expected names, in pattern order.
+    pub(crate) fn check_named(&self, name: &str, params: &[&str]) -> Result<(), String> {
+        if let Some(names) = self.named_params(name, &[]) {
+            if names.iter().eq(params.iter()) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Named resource \"{}\" path parameters {:?} do not match {:?}",
+                    name, names, params
+                ))
+            }
+        } else {
+            Err(format!("Named resource \"{}\" is not registered", name))
+        }
+    }
+
+    fn named_params(&self, name: &str, parent: &[&str]) -> Option<Vec<String>> {
+        let mut root: Vec<&str> = parent.to_vec();
+        root.extend(self.root.var_names());
+
+        if let Some(pattern) = self.named.get(name) {
+            let mut names: Vec<String> = if pattern.pattern().starts_with('/') {
+                root.iter().map(|s| s.to_string()).collect()
+            } else {
+                Vec::new()
+            };
+            names.extend(pattern.var_names().map(|s| s.to_string()));
+            Some(names)
+        } else {
+            self.patterns
+                .iter()
+                .filter_map(|(_, rmap)| rmap.as_ref())
+                .find_map(|rmap| rmap.named_params(name, &root))
+        }
+    }
 }
 
 #[cfg(feature = "url")]
@@ -82,6 +160,18 @@ impl ResourceMap {
         }
     }
 
+    /// Generate url for typed named resource
+    ///
+    /// Check [`HttpRequest::url_for_typed()`](../struct.HttpRequest.html#method.
+    /// url_for_typed) for detailed information.
+    pub fn url_for_typed<T: NamedResource>(
+        &self,
+        req: &HttpRequest,
+        resource: &T,
+    ) -> Result<Url, super::error::UrlGenerationError> {
+        self.url_for(req, T::NAME, resource.params())
+    }
+
     // pub fn has_resource(&self, path: &str) -> bool {
     // let _path = if path.is_empty() { "/" } else { path };
 
@@ -1,6 +1,7 @@
-use std::{future::Future, mem, pin::Pin, rc::Rc, task::Context, task::Poll};
+use std::{fmt, future::Future, mem, pin::Pin, rc::Rc, task::Context, task::Poll};
 
-use crate::{http::Method, service::Service, service::ServiceFactory, util::Ready};
+use crate::util::{Extensions, Ready};
+use crate::{http::Method, service::Service, service::ServiceFactory};
 
 use super::error::ErrorRenderer;
 use super::error_default::DefaultError;
@@ -20,6 +21,7 @@ pub struct Route<Err: ErrorRenderer = DefaultError> {
     handler: Box<dyn HandlerFn<Err>>,
     methods: Vec<Method>,
     guards: Rc<Vec<Box<dyn Guard>>>,
+    metadata: Rc<Extensions>,
 }
 
 impl<Err: ErrorRenderer> Route<Err> {
@@ -29,6 +31,18 @@ impl<Err: ErrorRenderer> Route<Err> {
             handler: Box::new(HandlerWrapper::new(|| async { HttpResponse::NotFound() })),
             methods: Vec::new(),
             guards: Rc::new(Vec::new()),
+            metadata: Rc::new(Extensions::new()),
+        }
+    }
+
+    pub(super) fn info(&self, pattern: &str, name: Option<&String>) -> RouteInfo {
+        RouteInfo {
+            pattern: pattern.to_string(),
+            name: name.cloned(),
+            methods: self.methods.clone(),
+            extractor: Some(self.handler.extractor_type()),
+            responder: Some(self.handler.responder_type()),
+            metadata: self.metadata.clone(),
         }
     }
 
@@ -137,6 +151,38 @@ impl<Err: ErrorRenderer> Route<Err> {
         self
     }
 
+    /// Attach metadata to the route.
+    ///
+    /// Route metadata is not used by ntex itself, it is available in the
+    /// application route table and could be used by tools like openapi
+    /// generators.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, dev::RouteTable, App, HttpResponse};
+    ///
+    /// struct Summary(&'static str);
+    ///
+    /// fn main() {
+    ///     let app = App::new()
+    ///         .inspect_routes(|routes: &RouteTable| {
+    ///             for route in routes.iter() {
+    ///                 if let Some(summary) = route.metadata().get::<Summary>() {
+    ///                     println!("{} {:?}: {}", route.pattern(), route.methods(), summary.0);
+    ///                 }
+    ///             }
+    ///         })
+    ///         .service(web::resource("/index.html").route(
+    ///             web::get()
+    ///                 .metadata(Summary("Index page"))
+    ///                 .to(|| async { HttpResponse::Ok() })),
+    ///         );
+    /// }
+    /// ```
+    pub fn metadata<T: 'static>(mut self, data: T) -> Self {
+        Rc::get_mut(&mut self.metadata).unwrap().insert(data);
+        self
+    }
+
     /// Set handler function, use request extractors for parameters.
     ///
     /// ```rust
@@ -195,6 +241,132 @@ impl<Err: ErrorRenderer> Route<Err> {
     }
 }
 
+/// Route table entry
+///
+/// Describes registered route, its path pattern, resource name,
+/// http methods, type names of handler's extractor and response and
+/// route metadata.
+#[derive(Clone)]
+pub struct RouteInfo {
+    pattern: String,
+    name: Option<String>,
+    methods: Vec<Method>,
+    extractor: Option<&'static str>,
+    responder: Option<&'static str>,
+    metadata: Rc<Extensions>,
+}
+
+impl RouteInfo {
+    /// Create new route table entry for path pattern.
+    ///
+    /// Could be used by custom `WebServiceFactory` implementations
+    /// for route registration, check `WebServiceConfig::register_route()`.
+    pub fn new<T: Into<String>>(pattern: T) -> Self {
+        RouteInfo {
+            pattern: pattern.into(),
+            name: None,
+            methods: Vec::new(),
+            extractor: None,
+            responder: None,
+            metadata: Rc::new(Extensions::new()),
+        }
+    }
+
+    /// Set resource name.
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Add http method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Full path pattern of the route.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Name of the resource.
+    pub fn resource_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Http methods of the route, empty list matches any method.
+    pub fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+
+    /// Type name of the handler's extractor.
+    pub fn extractor(&self) -> Option<&'static str> {
+        self.extractor
+    }
+
+    /// Type name of the handler's response.
+    pub fn responder(&self) -> Option<&'static str> {
+        self.responder
+    }
+
+    /// Route metadata, check `Route::metadata()`.
+    pub fn metadata(&self) -> &Extensions {
+        &self.metadata
+    }
+
+    pub(super) fn with_prefix(mut self, prefix: &str) -> Self {
+        self.pattern = if self.pattern.is_empty() {
+            prefix.to_string()
+        } else if prefix.ends_with('/') && self.pattern.starts_with('/') {
+            format!("{}{}", prefix, &self.pattern[1..])
+        } else {
+            format!("{}{}", prefix, self.pattern)
+        };
+        self
+    }
+}
+
+impl fmt::Debug for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RouteInfo")
+            .field("pattern", &self.pattern)
+            .field("name", &self.name)
+            .field("methods", &self.methods)
+            .field("extractor", &self.extractor)
+            .field("responder", &self.responder)
+            .finish()
+    }
+}
+
+/// Application route table
+///
+/// Route table is built during application startup. It is available
+/// as application state, `HttpRequest::app_state::<RouteTable>()`,
+/// and in `App::inspect_routes()` hook.
+#[derive(Clone, Debug)]
+pub struct RouteTable(Rc<Vec<RouteInfo>>);
+
+impl RouteTable {
+    pub(super) fn new(routes: Vec<RouteInfo>) -> Self {
+        RouteTable(Rc::new(routes))
+    }
+
+    /// Iterate over registered routes.
+    pub fn iter(&self) -> impl Iterator<Item = &RouteInfo> {
+        self.0.iter()
+    }
+
+    /// Number of registered routes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if route table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 /// Convert object to a vec of routes
 pub trait IntoRoutes<Err: ErrorRenderer> {
     fn routes(self) -> Vec<Route<Err>>;
@@ -346,4 +518,40 @@ mod tests {
         let body = read_body(resp).await;
         assert_eq!(body, Bytes::from_static(b"{\"name\":\"test\"}"));
     }
+
+    #[crate::rt_test]
+    async fn test_handler_panic() {
+        use std::future::Future;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        use crate::service::Service;
+        use crate::util::lazy;
+        use crate::web::PanicConfig;
+
+        let srv = init_service(App::new().service(web::resource("/").to(|| async {
+            if true {
+                panic!("handler panic");
+            }
+            HttpResponse::Ok()
+        })))
+        .await;
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // fail fast
+        let srv = init_service(
+            App::new()
+                .app_state(PanicConfig::default().catch(false))
+                .service(web::resource("/").to(|| async {
+                    if true {
+                        panic!("handler panic");
+                    }
+                    HttpResponse::Ok()
+                })),
+        )
+        .await;
+        let mut fut = Box::pin(srv.call(TestRequest::default().to_request()));
+        let res = lazy(|cx| catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(cx)))).await;
+        assert!(res.is_err());
+    }
 }
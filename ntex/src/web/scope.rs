@@ -9,11 +9,13 @@ use crate::service::{pipeline_factory, PipelineFactory};
 use crate::service::{Identity, IntoServiceFactory, Service, ServiceFactory, Transform};
 use crate::util::{Either, Extensions, Ready};
 
-use super::app::{Filter, Stack};
+use super::app::{middleware_order, Filter, Middlewares, Stack, TrailingSlash};
+use super::app_service::trailing_slash_redirect;
 use super::config::ServiceConfig;
 use super::dev::{WebServiceConfig, WebServiceFactory};
-use super::error::ErrorRenderer;
+use super::error::{self, ErrorRenderer, RenderFn};
 use super::guard::Guard;
+use super::httprequest::HttpRequest;
 use super::request::WebRequest;
 use super::resource::Resource;
 use super::response::WebResponse;
@@ -21,6 +23,7 @@ use super::rmap::ResourceMap;
 use super::route::Route;
 use super::service::{AppServiceFactory, ServiceFactoryWrapper};
 use super::types::State;
+use super::HttpResponse;
 
 type Guards = Vec<Box<dyn Guard>>;
 type HttpService<Err: ErrorRenderer> =
@@ -69,6 +72,8 @@ pub struct Scope<Err: ErrorRenderer, M = Identity, T = Filter<Err>> {
     default: Rc<RefCell<Option<Rc<HttpNewService<Err>>>>>,
     external: Vec<ResourceDef>,
     case_insensitive: bool,
+    trailing_slash: TrailingSlash,
+    render_error: Option<RenderFn<Err>>,
 }
 
 impl<Err: ErrorRenderer> Scope<Err> {
@@ -84,6 +89,8 @@ impl<Err: ErrorRenderer> Scope<Err> {
             default: Rc::new(RefCell::new(None)),
             external: Vec::new(),
             case_insensitive: false,
+            trailing_slash: TrailingSlash::Strict,
+            render_error: None,
         }
     }
 }
@@ -172,6 +179,49 @@ where
         self
     }
 
+    /// Set trailing slash handling policy for scope's resources.
+    ///
+    /// By default trailing slash is significant.
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Set error rendering function for scope's resources.
+    ///
+    /// Function is used for errors that are rendered to a response while
+    /// scope handles request. If function returns `None`, rendering function
+    /// of parent scope or application is used, then error renders response itself.
+    ///
+    /// ```rust
+    /// use ntex::web::{self, App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     let app = App::new().service(
+    ///         web::scope("/api")
+    ///             .render_error(|err: &web::Error, _| {
+    ///                 let status = err.as_response_error().status_code();
+    ///                 Some(
+    ///                     HttpResponse::build(status)
+    ///                         .content_type("application/problem+json")
+    ///                         .json(&serde_json::json!({
+    ///                             "status": status.as_u16(),
+    ///                             "detail": err.to_string(),
+    ///                         })),
+    ///                 )
+    ///             })
+    ///             .service(web::resource("/users").to(|| async { "users" })),
+    ///     );
+    /// }
+    /// ```
+    pub fn render_error<R>(mut self, f: R) -> Self
+    where
+        R: Fn(&Err::Container, &HttpRequest) -> Option<HttpResponse> + 'static,
+    {
+        self.render_error = Some(Rc::new(f));
+        self
+    }
+
     /// Run external configuration as part of the scope building
     /// process
     ///
@@ -344,6 +394,8 @@ where
             default: self.default,
             external: self.external,
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error,
         }
     }
 
@@ -368,8 +420,27 @@ where
             default: self.default,
             external: self.external,
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error,
         }
     }
+
+    /// Registers middleware with custom name.
+    ///
+    /// By default middleware name is a name of the middleware type.
+    pub fn wrap_named<U>(self, name: &'static str, mw: U) -> Scope<Err, Stack<M, U>, T> {
+        let mut scope = self.wrap(mw);
+        scope.middleware.set_name(name);
+        scope
+    }
+
+    /// Names of registered middlewares in execution order.
+    pub fn middleware_order(&self) -> Vec<&'static str>
+    where
+        M: Middlewares,
+    {
+        middleware_order(&self.middleware)
+    }
 }
 
 impl<Err, M, T> WebServiceFactory<Err> for Scope<Err, M, T>
@@ -397,7 +468,14 @@ where
             .for_each(|mut srv| srv.register(&mut cfg));
 
         let slesh = self.rdef.iter().any(|s| s.ends_with('/'));
-        let mut rmap = ResourceMap::new(ResourceDef::root_prefix(self.rdef.clone()));
+        let root = ResourceDef::root_prefix(self.rdef.clone());
+
+        // nested routes
+        for info in cfg.take_routes() {
+            config.register_route(info.with_prefix(root.pattern()));
+        }
+
+        let mut rmap = ResourceMap::new(root);
 
         // external resources
         for mut rdef in std::mem::take(&mut self.external) {
@@ -414,6 +492,8 @@ where
             state: self.state.take().map(Rc::new),
             default: self.default.clone(),
             case_insensitive: self.case_insensitive,
+            trailing_slash: self.trailing_slash,
+            render_error: self.render_error.take(),
             services: Rc::new(
                 cfg.into_services()
                     .1
@@ -564,6 +644,8 @@ struct ScopeRouterFactory<Err: ErrorRenderer> {
     services: Rc<Vec<(ResourceDef, HttpNewService<Err>, RefCell<Option<Guards>>)>>,
     default: Rc<RefCell<Option<Rc<HttpNewService<Err>>>>>,
     case_insensitive: bool,
+    trailing_slash: TrailingSlash,
+    render_error: Option<RenderFn<Err>>,
 }
 
 impl<Err: ErrorRenderer> ServiceFactory<WebRequest<Err>> for ScopeRouterFactory<Err> {
@@ -576,6 +658,8 @@ impl<Err: ErrorRenderer> ServiceFactory<WebRequest<Err>> for ScopeRouterFactory<
     fn new_service(&self, _: ()) -> Self::Future {
         let services = self.services.clone();
         let case_insensitive = self.case_insensitive;
+        let trailing_slash = self.trailing_slash;
+        let render_error = self.render_error.clone();
         let state = self.state.clone();
         let default_fut = self
             .default
@@ -589,6 +673,9 @@ impl<Err: ErrorRenderer> ServiceFactory<WebRequest<Err>> for ScopeRouterFactory<
             if case_insensitive {
                 router.case_insensitive();
             }
+            if trailing_slash == TrailingSlash::Rewrite {
+                router.ignore_trailing_slash();
+            }
             for (path, factory, guards) in &mut services.iter() {
                 let service = factory.new_service(()).await?;
                 router.rdef(path.clone(), service).2 = guards.borrow_mut().take();
@@ -603,6 +690,8 @@ impl<Err: ErrorRenderer> ServiceFactory<WebRequest<Err>> for ScopeRouterFactory<
             Ok(ScopeRouter {
                 state,
                 default,
+                trailing_slash,
+                render_error,
                 router: router.finish(),
             })
         })
@@ -613,6 +702,18 @@ struct ScopeRouter<Err: ErrorRenderer> {
     state: Option<Rc<Extensions>>,
     router: Router<HttpService<Err>, Vec<Box<dyn Guard>>>,
     default: Option<HttpService<Err>>,
+    trailing_slash: TrailingSlash,
+    render_error: Option<RenderFn<Err>>,
+}
+
+impl<Err: ErrorRenderer> ScopeRouter<Err> {
+    fn redirect(&self, req: &WebRequest<Err>) -> Option<Response> {
+        if self.trailing_slash == TrailingSlash::Redirect {
+            trailing_slash_redirect(&self.router, req)
+        } else {
+            None
+        }
+    }
 }
 
 impl<Err: ErrorRenderer> Service<WebRequest<Err>> for ScopeRouter<Err> {
@@ -626,6 +727,10 @@ impl<Err: ErrorRenderer> Service<WebRequest<Err>> for ScopeRouter<Err> {
     }
 
     fn call(&self, mut req: WebRequest<Err>) -> Self::Future {
+        if let Some(ref f) = self.render_error {
+            error::push_renderer::<Err>(&mut req.extensions_mut(), f);
+        }
+
         let res = self.router.recognize_checked(&mut req, |req, guards| {
             if let Some(guards) = guards {
                 for f in guards {
@@ -637,11 +742,14 @@ impl<Err: ErrorRenderer> Service<WebRequest<Err>> for ScopeRouter<Err> {
             true
         });
 
-        if let Some((srv, _info)) = res {
+        if let Some((srv, info)) = res {
+            req.push_resource_id(info.id());
             if let Some(ref state) = self.state {
                 req.set_state_container(state.clone());
             }
             Either::Left(srv.call(req))
+        } else if let Some(res) = self.redirect(&req) {
+            Either::Right(Ready::Ok(req.into_response(res)))
         } else if let Some(ref default) = self.default {
             Either::Left(default.call(req))
         } else {
@@ -657,7 +765,7 @@ impl<Err: ErrorRenderer> Service<WebRequest<Err>> for ScopeRouter<Err> {
 #[cfg(test)]
 mod tests {
     use crate::http::body::{Body, ResponseBody};
-    use crate::http::header::{HeaderValue, CONTENT_TYPE};
+    use crate::http::header::{self, HeaderValue, CONTENT_TYPE};
     use crate::http::{Method, StatusCode};
     use crate::service::{fn_service, Service};
     use crate::util::{Bytes, Ready};
@@ -665,7 +773,7 @@ mod tests {
     use crate::web::request::WebRequest;
     use crate::web::test::{call_service, init_service, read_body, TestRequest};
     use crate::web::DefaultError;
-    use crate::web::{self, guard, App, HttpRequest, HttpResponse};
+    use crate::web::{self, guard, App, HttpRequest, HttpResponse, TrailingSlash};
 
     #[crate::rt_test]
     async fn test_scope() {
@@ -698,6 +806,129 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
+    #[crate::rt_test]
+    async fn test_scope_trailing_slash() {
+        let srv = init_service(
+            App::new()
+                .service(
+                    web::scope("/app")
+                        .trailing_slash(TrailingSlash::Rewrite)
+                        .service(
+                            web::resource("/path1").to(|| async { HttpResponse::Ok() }),
+                        ),
+                )
+                .service(
+                    web::scope("/app2")
+                        .trailing_slash(TrailingSlash::Redirect)
+                        .service(web::resource("").to(|| async { HttpResponse::Ok() }))
+                        .service(
+                            web::resource("/path1").to(|| async { HttpResponse::Ok() }),
+                        ),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/app/path1/").to_request();
+        let resp = srv.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = TestRequest::with_uri("/app2/path1/").to_request();
+        let resp = srv.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            HeaderValue::from_static("/app2/path1")
+        );
+
+        let req = TestRequest::with_uri("/app2/").to_request();
+        let resp = srv.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            HeaderValue::from_static("/app2")
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_scope_render_error() {
+        async fn fail() -> Result<HttpResponse, web::Error> {
+            Err(web::error::ErrorBadRequest("bad request").into())
+        }
+        async fn fail_internal() -> Result<HttpResponse, web::Error> {
+            Err(web::error::ErrorInternalServerError("internal").into())
+        }
+
+        let srv = init_service(
+            App::new()
+                .render_error(|err: &web::Error, _| {
+                    Some(
+                        HttpResponse::build(err.as_response_error().status_code())
+                            .content_type("text/html")
+                            .body(format!("<h1>{}</h1>", err)),
+                    )
+                })
+                .service(web::resource("/page").to(fail))
+                .service(
+                    web::scope("/api")
+                        .render_error(|err: &web::Error, _| {
+                            let status = err.as_response_error().status_code();
+                            if status.is_client_error() {
+                                Some(
+                                    HttpResponse::build(status)
+                                        .content_type("application/problem+json")
+                                        .body(format!("{{\"detail\":\"{}\"}}", err)),
+                                )
+                            } else {
+                                None
+                            }
+                        })
+                        .service(web::resource("/bad").to(fail))
+                        .service(web::resource("/internal").to(fail_internal))
+                        .service(
+                            web::scope("/nested").service(web::resource("/bad").to(fail)),
+                        ),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/page").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            read_body(resp).await,
+            Bytes::from_static(b"<h1>bad request</h1>")
+        );
+
+        let req = TestRequest::with_uri("/api/bad").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.headers().get(CONTENT_TYPE).unwrap(),
+            HeaderValue::from_static("application/problem+json")
+        );
+        assert_eq!(
+            read_body(resp).await,
+            Bytes::from_static(b"{\"detail\":\"bad request\"}")
+        );
+
+        // nested scope uses parent's function
+        let req = TestRequest::with_uri("/api/nested/bad").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(
+            resp.headers().get(CONTENT_TYPE).unwrap(),
+            HeaderValue::from_static("application/problem+json")
+        );
+
+        // scope function falls back to app function
+        let req = TestRequest::with_uri("/api/internal").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            read_body(resp).await,
+            Bytes::from_static(b"<h1>internal</h1>")
+        );
+    }
+
     #[crate::rt_test]
     async fn test_scope_root() {
         let srv = init_service(
@@ -1324,4 +1555,77 @@ mod tests {
             Bytes::from_static(b"http://localhost:8080/a/b/c/12345")
         );
     }
+
+    #[cfg(feature = "url")]
+    struct ItemUrl {
+        user: &'static str,
+        id: u32,
+    }
+
+    #[cfg(feature = "url")]
+    impl crate::web::NamedResource for ItemUrl {
+        const NAME: &'static str = "item";
+        const PARAMS: &'static [&'static str] = &["user", "id"];
+
+        fn params(&self) -> Vec<String> {
+            vec![self.user.to_string(), self.id.to_string()]
+        }
+    }
+
+    #[cfg(feature = "url")]
+    #[crate::rt_test]
+    async fn test_url_for_typed() {
+        let srv = init_service(
+            App::new().named_resource::<ItemUrl>().service(
+                web::scope("/{user}").service(
+                    web::resource("/items/{id}")
+                        .name("item")
+                        .route(web::get().to(|req: HttpRequest| async move {
+                            HttpResponse::Ok().body(format!(
+                                "{}",
+                                req.url_for_typed(&ItemUrl { user: "bob", id: 7 }).unwrap()
+                            ))
+                        })),
+                ),
+            ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/alice/items/1").to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = read_body(resp).await;
+        assert_eq!(
+            body,
+            Bytes::from_static(b"http://localhost:8080/bob/items/7")
+        );
+    }
+
+    #[cfg(feature = "url")]
+    #[crate::rt_test]
+    #[should_panic(expected = "do not match")]
+    async fn test_url_for_typed_mismatch() {
+        init_service(
+            App::new().named_resource::<ItemUrl>().service(
+                web::scope("/{user}").service(
+                    web::resource("/items/{item_id}")
+                        .name("item")
+                        .to(|| async { HttpResponse::Ok() }),
+                ),
+            ),
+        )
+        .await;
+    }
+
+    #[cfg(feature = "url")]
+    #[crate::rt_test]
+    #[should_panic(expected = "is not registered")]
+    async fn test_url_for_typed_not_registered() {
+        init_service(
+            App::new()
+                .named_resource::<ItemUrl>()
+                .service(web::resource("/items/{id}").to(|| async { HttpResponse::Ok() })),
+        )
+        .await;
+    }
 }
@@ -6,13 +6,14 @@ use tls_openssl::ssl::{AlpnError, SslAcceptor, SslAcceptorBuilder};
 use tls_rustls::ServerConfig as RustlsServerConfig;
 
 use crate::http::{
-    body::MessageBody, HttpService, KeepAlive, Request, Response, ResponseError,
+    body::MessageBody, HttpService, KeepAlive, PeerInfo, Request, Response, ResponseError,
 };
 use crate::server::{Server, ServerBuilder};
 use crate::service::{map_config, IntoServiceFactory, ServiceFactory};
 use crate::{time::Seconds, util::PoolId};
 
 use super::config::AppConfig;
+use super::info::TrustedProxies;
 
 struct Config {
     host: Option<String>,
@@ -21,6 +22,8 @@ struct Config {
     client_disconnect: Seconds,
     handshake_timeout: Seconds,
     pool: PoolId,
+    trusted_proxies: Option<TrustedProxies>,
+    peer_info: bool,
 }
 
 /// An HTTP Server.
@@ -78,6 +81,8 @@ where
                 client_disconnect: Seconds(5),
                 handshake_timeout: Seconds(5),
                 pool: PoolId::P0,
+                trusted_proxies: None,
+                peer_info: false,
             })),
             backlog: 1024,
             builder: ServerBuilder::default(),
@@ -127,8 +132,8 @@ where
     /// can be used to limit the global SSL CPU usage.
     ///
     /// By default max connections is set to a 256.
-    pub fn maxconnrate(self, num: usize) -> Self {
-        ntex_tls::max_concurrent_ssl_accept(num);
+    pub fn maxconnrate(mut self, num: usize) -> Self {
+        self.builder = self.builder.maxconnrate(num);
         self
     }
 
@@ -190,6 +195,31 @@ where
         self
     }
 
+    /// Set trusted proxies.
+    ///
+    /// Forwarding headers are used for resolving connection information
+    /// only if request is received from one of trusted proxies.
+    /// Check [TrustedProxies](./struct.TrustedProxies.html) documentation
+    /// for more information.
+    ///
+    /// By default forwarding headers are trusted unconditionally.
+    pub fn trusted_proxies(self, proxies: TrustedProxies) -> Self {
+        self.config.lock().unwrap().trusted_proxies = Some(proxies);
+        self
+    }
+
+    /// Capture connection information for every connection.
+    ///
+    /// [PeerInfo](../http/struct.PeerInfo.html) is available in request
+    /// extensions, i.e. peer address and client certificates verified
+    /// during tls handshake.
+    ///
+    /// By default connection information is not captured.
+    pub fn peer_info(self) -> Self {
+        self.config.lock().unwrap().peer_info = true;
+        self
+    }
+
     /// Stop ntex runtime when server get dropped.
     ///
     /// By default "stop runtime" is disabled.
@@ -243,14 +273,18 @@ where
                         false,
                         addr,
                         c.host.clone().unwrap_or_else(|| format!("{}", addr)),
-                    );
+                    )
+                    .with_trusted_proxies(c.trusted_proxies.clone());
                     r.memory_pool(c.pool);
 
-                    HttpService::build()
+                    let mut builder = HttpService::build()
                         .keep_alive(c.keep_alive)
                         .client_timeout(c.client_timeout)
-                        .disconnect_timeout(c.client_disconnect)
-                        .finish(map_config(factory(), move |_| cfg.clone()))
+                        .disconnect_timeout(c.client_disconnect);
+                    if c.peer_info {
+                        builder = builder.on_connect(PeerInfo::from_io);
+                    }
+                    builder.finish(map_config(factory(), move |_| cfg.clone()))
                 })?;
         Ok(self)
     }
@@ -285,14 +319,19 @@ where
                         true,
                         addr,
                         c.host.clone().unwrap_or_else(|| format!("{}", addr)),
-                    );
+                    )
+                    .with_trusted_proxies(c.trusted_proxies.clone());
                     r.memory_pool(c.pool);
 
-                    HttpService::build()
+                    let mut builder = HttpService::build()
                         .keep_alive(c.keep_alive)
                         .client_timeout(c.client_timeout)
                         .disconnect_timeout(c.client_disconnect)
-                        .ssl_handshake_timeout(c.handshake_timeout)
+                        .ssl_handshake_timeout(c.handshake_timeout);
+                    if c.peer_info {
+                        builder = builder.on_connect(PeerInfo::from_io);
+                    }
+                    builder
                         .finish(map_config(factory(), move |_| cfg.clone()))
                         .openssl(acceptor.clone())
                 })?;
@@ -330,14 +369,19 @@ where
                     true,
                     addr,
                     c.host.clone().unwrap_or_else(|| format!("{}", addr)),
-                );
+                )
+                .with_trusted_proxies(c.trusted_proxies.clone());
                 r.memory_pool(c.pool);
 
-                HttpService::build()
+                let mut builder = HttpService::build()
                     .keep_alive(c.keep_alive)
                     .client_timeout(c.client_timeout)
                     .disconnect_timeout(c.client_disconnect)
-                    .ssl_handshake_timeout(c.handshake_timeout)
+                    .ssl_handshake_timeout(c.handshake_timeout);
+                if c.peer_info {
+                    builder = builder.on_connect(PeerInfo::from_io);
+                }
+                builder
                     .finish(map_config(factory(), move |_| cfg.clone()))
                     .rustls(config.clone())
             },
@@ -442,13 +486,17 @@ where
                 false,
                 socket_addr,
                 c.host.clone().unwrap_or_else(|| format!("{}", socket_addr)),
-            );
+            )
+            .with_trusted_proxies(c.trusted_proxies.clone());
             r.memory_pool(c.pool);
 
-            HttpService::build()
+            let mut builder = HttpService::build()
                 .keep_alive(c.keep_alive)
-                .client_timeout(c.client_timeout)
-                .finish(map_config(factory(), move |_| config.clone()))
+                .client_timeout(c.client_timeout);
+            if c.peer_info {
+                builder = builder.on_connect(PeerInfo::from_io);
+            }
+            builder.finish(map_config(factory(), move |_| config.clone()))
         })?;
         Ok(self)
     }
@@ -475,13 +523,17 @@ where
                     false,
                     socket_addr,
                     c.host.clone().unwrap_or_else(|| format!("{}", socket_addr)),
-                );
+                )
+                .with_trusted_proxies(c.trusted_proxies.clone());
                 r.memory_pool(c.pool);
 
-                HttpService::build()
+                let mut builder = HttpService::build()
                     .keep_alive(c.keep_alive)
-                    .client_timeout(c.client_timeout)
-                    .finish(map_config(factory(), move |_| config.clone()))
+                    .client_timeout(c.client_timeout);
+                if c.peer_info {
+                    builder = builder.on_connect(PeerInfo::from_io);
+                }
+                builder.finish(map_config(factory(), move |_| config.clone()))
             },
         )?;
         Ok(self)
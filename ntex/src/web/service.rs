@@ -11,6 +11,7 @@ use super::guard::Guard;
 use super::request::WebRequest;
 use super::response::WebResponse;
 use super::rmap::ResourceMap;
+use super::route::RouteInfo;
 use super::types::state::StateFactory;
 
 pub trait WebServiceFactory<Err: ErrorRenderer> {
@@ -61,6 +62,7 @@ pub struct WebServiceConfig<Err: ErrorRenderer> {
         Option<Rc<ResourceMap>>,
     )>,
     service_state: Rc<Vec<Box<dyn StateFactory>>>,
+    routes: Vec<RouteInfo>,
 }
 
 impl<Err: ErrorRenderer> WebServiceConfig<Err> {
@@ -76,6 +78,7 @@ impl<Err: ErrorRenderer> WebServiceConfig<Err> {
             service_state,
             root: true,
             services: Vec::new(),
+            routes: Vec::new(),
         }
     }
 
@@ -98,6 +101,10 @@ impl<Err: ErrorRenderer> WebServiceConfig<Err> {
         (self.config, self.services)
     }
 
+    pub(crate) fn take_routes(&mut self) -> Vec<RouteInfo> {
+        std::mem::take(&mut self.routes)
+    }
+
     pub(crate) fn clone_config(&self) -> Self {
         WebServiceConfig {
             config: self.config.clone(),
@@ -105,6 +112,7 @@ impl<Err: ErrorRenderer> WebServiceConfig<Err> {
             services: Vec::new(),
             root: false,
             service_state: self.service_state.clone(),
+            routes: Vec::new(),
         }
     }
 
@@ -145,6 +153,13 @@ impl<Err: ErrorRenderer> WebServiceConfig<Err> {
         self.services
             .push((rdef, boxed::factory(factory.into_factory()), guards, nested));
     }
+
+    /// Register route table entry
+    ///
+    /// Route pattern is relative to the current scope.
+    pub fn register_route(&mut self, info: RouteInfo) {
+        self.routes.push(info);
+    }
 }
 
 /// Create service adapter for a specific path.
@@ -262,6 +277,11 @@ where
         if let Some(ref name) = self.name {
             *rdef.name_mut() = name.clone();
         }
+        let mut info = RouteInfo::new(rdef.pattern());
+        if let Some(name) = self.name {
+            info = info.name(name);
+        }
+        config.register_route(info);
         config.register_service(rdef, guards, self.srv, None)
     }
 }
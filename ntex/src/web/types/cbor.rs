@@ -0,0 +1,352 @@
+//! CBOR extractor/responder
+use std::{fmt, future::Future, ops, pin::Pin, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "compress")]
+use crate::http::encoding::Decoder;
+use crate::http::header::CONTENT_LENGTH;
+use crate::http::{HttpMessage, Payload, Response, StatusCode};
+use crate::util::{stream_recv, BytesMut};
+use crate::web::error::{CborError, CborPayloadError, ErrorRenderer, WebResponseError};
+use crate::web::responder::{Ready, Responder};
+use crate::web::{FromRequest, HttpRequest};
+
+/// CBOR helper
+///
+/// Cbor can be used for two different purpose. First is for cbor
+/// response generation and second is for extracting typed information
+/// from request's payload.
+///
+/// To extract typed information from request's body, the type `T` must
+/// implement the `Deserialize` trait from *serde*. Extractor accepts
+/// `application/cbor` content type.
+///
+/// [**CborConfig**](struct.CborConfig.html) allows to configure
+/// extraction process.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::web::{self, types::Cbor};
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Info {
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body, send it back
+/// async fn index(info: Cbor<Info>) -> Cbor<Info> {
+///     info
+/// }
+///
+/// fn main() {
+///     let app = web::App::new().service(
+///         web::resource("/index.html").route(web::post().to(index))
+///     );
+/// }
+/// ```
+pub struct Cbor<T>(pub T);
+
+impl<T> Cbor<T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> ops::Deref for Cbor<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ops::DerefMut for Cbor<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> fmt::Debug for Cbor<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Cbor").field(&self.0).finish()
+    }
+}
+
+impl<T: Serialize, Err: ErrorRenderer> Responder<Err> for Cbor<T>
+where
+    Err::Container: From<CborError>,
+{
+    type Error = CborError;
+    type Future = Ready<Response>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let mut body = Vec::new();
+        if let Err(e) = ciborium::ser::into_writer(&self.0, &mut body) {
+            return e.error_response(req).into();
+        }
+
+        Response::build(StatusCode::OK)
+            .content_type("application/cbor")
+            .body(body)
+            .into()
+    }
+}
+
+impl<T, Err: ErrorRenderer> FromRequest<Err> for Cbor<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = CborPayloadError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let (limit, ctype) = req
+            .app_state::<CborConfig>()
+            .map(|c| (c.limit, c.content_type.clone()))
+            .unwrap_or((262_144, None));
+
+        // check content-type
+        let valid = if let Ok(Some(mime)) = req.mime_type() {
+            (mime.type_() == mime::APPLICATION && mime.subtype() == "cbor")
+                || ctype.as_ref().map_or(false, |predicate| predicate(mime))
+        } else {
+            false
+        };
+        if !valid {
+            log::debug!(
+                "Unsupported Cbor content type. Request path: {}",
+                req.path()
+            );
+            return Box::pin(async { Err(CborPayloadError::ContentType) });
+        }
+
+        let len = req
+            .headers()
+            .get(&CONTENT_LENGTH)
+            .and_then(|l| l.to_str().ok())
+            .and_then(|s| s.parse::<usize>().ok());
+        if len.map_or(false, |len| len > limit) {
+            return Box::pin(async { Err(CborPayloadError::Overflow) });
+        }
+
+        #[cfg(feature = "compress")]
+        let mut payload = Decoder::from_headers(payload.take(), req.headers());
+        #[cfg(not(feature = "compress"))]
+        let mut payload = payload.take();
+
+        let req = req.clone();
+        Box::pin(async move {
+            let mut body = BytesMut::with_capacity(8192);
+
+            while let Some(item) = stream_recv(&mut payload).await {
+                let chunk = item?;
+                if (body.len() + chunk.len()) > limit {
+                    return Err(CborPayloadError::Overflow);
+                } else {
+                    body.extend_from_slice(&chunk);
+                }
+            }
+            ciborium::de::from_reader(&body[..]).map(Cbor).map_err(|e| {
+                log::debug!(
+                    "Failed to deserialize Cbor from payload. \
+                     Request path: {}",
+                    req.path()
+                );
+                e.into()
+            })
+        })
+    }
+}
+
+/// Cbor extractor configuration
+///
+/// ```rust
+/// use ntex::web::{self, App, types::{Cbor, CborConfig}};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Info {
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body, max payload size is 4kb
+/// async fn index(info: Cbor<Info>) -> String {
+///     format!("Welcome {}!", info.username)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/index.html")
+///             .app_state(
+///                 // change cbor extractor configuration
+///                 CborConfig::default()
+///                     .limit(4096)
+///                     .content_type(|mime| {
+///                         mime.type_() == mime::APPLICATION
+///                             && mime.subtype() == "octet-stream"
+///                     })
+///             )
+///             .route(web::post().to(index))
+///     );
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CborConfig {
+    limit: usize,
+    content_type: Option<Arc<dyn Fn(mime::Mime) -> bool + Send + Sync>>,
+}
+
+impl CborConfig {
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set predicate for additionally allowed content types
+    pub fn content_type<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(mime::Mime) -> bool + Send + Sync + 'static,
+    {
+        self.content_type = Some(Arc::new(predicate));
+        self
+    }
+}
+
+impl Default for CborConfig {
+    fn default() -> Self {
+        CborConfig {
+            limit: 262_144,
+            content_type: None,
+        }
+    }
+}
+
+impl fmt::Debug for CborConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CborConfig")
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::http::body::{Body, ResponseBody};
+    use crate::http::header;
+    use crate::util::Bytes;
+    use crate::web::test::{from_request, respond_to, TestRequest};
+    use crate::web::{DefaultError, WebResponseError};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct MyObject {
+        name: String,
+    }
+
+    fn payload() -> Bytes {
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(
+            &MyObject {
+                name: "test".to_string(),
+            },
+            &mut buf,
+        )
+        .unwrap();
+        Bytes::from(buf)
+    }
+
+    #[crate::rt_test]
+    async fn test_responder() {
+        let req = TestRequest::default().to_http_request();
+
+        let p = Cbor(MyObject {
+            name: "test".to_string(),
+        });
+        let resp = respond_to(p, &req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/cbor"
+        );
+        match resp.body() {
+            ResponseBody::Body(Body::Bytes(ref b)) => assert_eq!(b, &payload()),
+            _ => panic!(),
+        }
+    }
+
+    #[crate::rt_test]
+    async fn test_extract() {
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .header(header::CONTENT_LENGTH, payload().len().to_string())
+            .set_payload(payload())
+            .to_http_parts();
+        let p = from_request::<Cbor<MyObject>>(&req, &mut pl).await.unwrap();
+        assert_eq!(p.name, "test");
+        assert_eq!(
+            p.into_inner(),
+            MyObject {
+                name: "test".to_string()
+            }
+        );
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .set_payload(payload())
+            .state(CborConfig::default().limit(4))
+            .to_http_parts();
+        let res = from_request::<Cbor<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(CborPayloadError::Overflow)));
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .header(header::CONTENT_LENGTH, "10000")
+            .state(CborConfig::default().limit(100))
+            .to_http_parts();
+        let res = from_request::<Cbor<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(CborPayloadError::Overflow)));
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/json")
+            .set_payload(payload())
+            .to_http_parts();
+        let res = from_request::<Cbor<MyObject>>(&req, &mut pl).await;
+        let err = res.unwrap_err();
+        assert!(matches!(err, CborPayloadError::ContentType));
+        assert_eq!(
+            WebResponseError::<DefaultError>::status_code(&err),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .set_payload(payload())
+            .state(
+                CborConfig::default()
+                    .content_type(|mime| mime.subtype() == mime::OCTET_STREAM),
+            )
+            .to_http_parts();
+        let p = from_request::<Cbor<MyObject>>(&req, &mut pl).await.unwrap();
+        assert_eq!(p.name, "test");
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .set_payload(Bytes::from_static(b"\xa1\x64name"))
+            .to_http_parts();
+        let res = from_request::<Cbor<MyObject>>(&req, &mut pl).await;
+        let err = res.unwrap_err();
+        assert!(matches!(err, CborPayloadError::Deserialize(_)));
+        assert_eq!(
+            WebResponseError::<DefaultError>::status_code(&err),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
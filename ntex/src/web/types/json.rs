@@ -224,8 +224,8 @@ where
 /// ```
 #[derive(Clone)]
 pub struct JsonConfig {
-    limit: usize,
-    content_type: Option<Arc<dyn Fn(mime::Mime) -> bool + Send + Sync>>,
+    pub(super) limit: usize,
+    pub(super) content_type: Option<Arc<dyn Fn(mime::Mime) -> bool + Send + Sync>>,
 }
 
 impl JsonConfig {
@@ -0,0 +1,360 @@
+//! Newline delimited json extractor/responder
+use std::{error::Error, fmt, marker::PhantomData, pin::Pin, task::Context, task::Poll};
+
+use pin_project_lite::pin_project;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::http::body::{Body, BodySize, MessageBody};
+#[cfg(feature = "compress")]
+use crate::http::encoding::Decoder;
+use crate::http::{HttpMessage, Payload, Response, StatusCode};
+use crate::util::{ready, Bytes, BytesMut, Ready, Stream};
+use crate::web::error::{ErrorRenderer, JsonError, JsonPayloadError};
+use crate::web::responder::{self, Responder};
+use crate::web::{types::JsonConfig, FromRequest, HttpRequest};
+
+/// Newline delimited json helper (`application/x-ndjson`)
+///
+/// `JsonLines` is a stream of values, one json document per line.
+/// As an extractor it parses request's payload incrementally, so payload
+/// is never buffered entirely. As a responder it serializes stream of
+/// values, every value is sent as a separate chunk.
+///
+/// Extractor accepts `application/x-ndjson`, `application/jsonl` and
+/// `application/x-jsonlines` content types. Size of a single line is limited
+/// by [**JsonConfig**](struct.JsonConfig.html) limit, content type predicate
+/// of `JsonConfig` is applied as well. Empty lines are skipped.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::util::stream_recv;
+/// use ntex::web::{self, error::JsonPayloadError, types::JsonLines};
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Record {
+///     id: u64,
+/// }
+///
+/// /// bulk ingest, every line is parsed as it arrives
+/// async fn ingest(mut records: JsonLines<Record>) -> Result<String, JsonPayloadError> {
+///     let mut count = 0;
+///     while let Some(record) = stream_recv(&mut records).await {
+///         let _record = record?;
+///         count += 1;
+///     }
+///     Ok(format!("{} records", count))
+/// }
+///
+/// /// export, records are sent as they are produced
+/// async fn export() -> JsonLines<Record> {
+///     let (tx, rx) = ntex::channel::mpsc::channel();
+///     ntex::rt::spawn(async move {
+///         for id in 0..10 {
+///             let _ = tx.send(Record { id });
+///         }
+///     });
+///     JsonLines::new(rx)
+/// }
+///
+/// fn main() {
+///     let app = web::App::new()
+///         .service(web::resource("/ingest").route(web::post().to(ingest)))
+///         .service(web::resource("/export").route(web::get().to(export)));
+/// }
+/// ```
+pub struct JsonLines<T> {
+    stream: Pin<Box<dyn Stream<Item = Result<T, JsonPayloadError>>>>,
+}
+
+impl<T: 'static> JsonLines<T> {
+    /// Create `JsonLines` from stream of values
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Stream<Item = T> + 'static,
+    {
+        JsonLines {
+            stream: Box::pin(Items { stream }),
+        }
+    }
+}
+
+impl<T> Stream for JsonLines<T> {
+    type Item = Result<T, JsonPayloadError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.stream.as_mut().poll_next(cx)
+    }
+}
+
+impl<T> fmt::Debug for JsonLines<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLines").finish()
+    }
+}
+
+impl<T, Err: ErrorRenderer> FromRequest<Err> for JsonLines<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = JsonPayloadError;
+    type Future = Ready<Self, Self::Error>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let (limit, ctype) = req
+            .app_state::<JsonConfig>()
+            .map(|c| (c.limit, c.content_type.clone()))
+            .unwrap_or((32768, None));
+
+        let valid = if let Ok(Some(mime)) = req.mime_type() {
+            mime.type_() == mime::APPLICATION
+                && matches!(
+                    mime.subtype().as_str(),
+                    "x-ndjson" | "ndjson" | "jsonl" | "x-jsonlines"
+                )
+                || ctype.as_ref().map_or(false, |predicate| predicate(mime))
+        } else {
+            false
+        };
+        if !valid {
+            log::debug!(
+                "Unsupported JsonLines content type. Request path: {}",
+                req.path()
+            );
+            return Ready::Err(JsonPayloadError::ContentType);
+        }
+
+        #[cfg(feature = "compress")]
+        let payload = Decoder::from_headers(payload.take(), req.headers());
+        #[cfg(not(feature = "compress"))]
+        let payload = payload.take();
+
+        Ready::Ok(JsonLines {
+            stream: Box::pin(LinesDecoder {
+                payload,
+                limit,
+                buf: BytesMut::new(),
+                eof: false,
+                _t: PhantomData,
+            }),
+        })
+    }
+}
+
+impl<T: Serialize + 'static, Err: ErrorRenderer> Responder<Err> for JsonLines<T> {
+    type Error = JsonError;
+    type Future = responder::Ready<Response>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        Response::build(StatusCode::OK)
+            .content_type("application/x-ndjson")
+            .body(Body::from_message(LinesBody(self.stream)))
+            .into()
+    }
+}
+
+pin_project! {
+    /// Stream of infallible values
+    struct Items<S> {
+        #[pin]
+        stream: S,
+    }
+}
+
+impl<S: Stream> Stream for Items<S> {
+    type Item = Result<S::Item, JsonPayloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().stream.poll_next(cx).map(|item| item.map(Ok))
+    }
+}
+
+/// Incremental payload parser
+struct LinesDecoder<T> {
+    #[cfg(feature = "compress")]
+    payload: Decoder<Payload>,
+    #[cfg(not(feature = "compress"))]
+    payload: Payload,
+    limit: usize,
+    buf: BytesMut,
+    eof: bool,
+    _t: PhantomData<T>,
+}
+
+impl<T> Unpin for LinesDecoder<T> {}
+
+impl<T: DeserializeOwned> LinesDecoder<T> {
+    fn parse(line: &[u8]) -> Option<Result<T, JsonPayloadError>> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            None
+        } else {
+            Some(serde_json::from_slice(line).map_err(JsonPayloadError::Deserialize))
+        }
+    }
+
+    fn fail(&mut self, err: JsonPayloadError) -> Poll<Option<Result<T, JsonPayloadError>>> {
+        self.eof = true;
+        self.buf.clear();
+        Poll::Ready(Some(Err(err)))
+    }
+}
+
+impl<T: DeserializeOwned> Stream for LinesDecoder<T> {
+    type Item = Result<T, JsonPayloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(idx) = this.buf.iter().position(|b| *b == b'\n') {
+                if idx > this.limit {
+                    return this.fail(JsonPayloadError::Overflow);
+                }
+                let line = this.buf.split_to(idx + 1);
+                if let Some(item) = Self::parse(&line[..idx]) {
+                    return Poll::Ready(Some(item));
+                }
+                continue;
+            } else if this.buf.len() > this.limit {
+                return this.fail(JsonPayloadError::Overflow);
+            } else if this.eof {
+                return if this.buf.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    let line = this.buf.split();
+                    Poll::Ready(Self::parse(&line))
+                };
+            }
+
+            match ready!(Pin::new(&mut this.payload).poll_next(cx)) {
+                Some(Ok(chunk)) => this.buf.extend_from_slice(&chunk),
+                Some(Err(err)) => return this.fail(err.into()),
+                None => this.eof = true,
+            }
+        }
+    }
+}
+
+/// Response body, one chunk per value
+struct LinesBody<T>(Pin<Box<dyn Stream<Item = Result<T, JsonPayloadError>>>>);
+
+impl<T: Serialize + 'static> MessageBody for LinesBody<T> {
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Box<dyn Error>>>> {
+        match ready!(self.0.as_mut().poll_next(cx)) {
+            Some(Ok(item)) => {
+                let mut buf = match serde_json::to_vec(&item) {
+                    Ok(buf) => buf,
+                    Err(e) => return Poll::Ready(Some(Err(Box::new(e)))),
+                };
+                buf.push(b'\n');
+                Poll::Ready(Some(Ok(Bytes::from(buf))))
+            }
+            Some(Err(e)) => Poll::Ready(Some(Err(Box::new(e)))),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header;
+    use crate::util::{poll_fn, stream_recv};
+    use crate::web::test::{from_request, respond_to, TestRequest};
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Record {
+        id: u64,
+    }
+
+    async fn collect(
+        mut lines: JsonLines<Record>,
+    ) -> Vec<Result<Record, JsonPayloadError>> {
+        let mut items = Vec::new();
+        while let Some(item) = stream_recv(&mut lines).await {
+            items.push(item);
+        }
+        items
+    }
+
+    #[crate::rt_test]
+    async fn test_extract() {
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .set_payload(Bytes::from_static(
+                b"{\"id\":1}\r\n\n{\"id\":2}\n{\"id\":x}\n{\"id\":3}",
+            ))
+            .to_http_parts();
+        let lines = from_request::<JsonLines<Record>>(&req, &mut pl)
+            .await
+            .unwrap();
+        let items = collect(lines).await;
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0].as_ref().unwrap(), &Record { id: 1 });
+        assert_eq!(items[1].as_ref().unwrap(), &Record { id: 2 });
+        assert!(matches!(items[2], Err(JsonPayloadError::Deserialize(_))));
+        assert_eq!(items[3].as_ref().unwrap(), &Record { id: 3 });
+
+        // line size limit
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/jsonl")
+            .state(JsonConfig::default().limit(10))
+            .set_payload(Bytes::from_static(
+                b"{\"id\":1}\n{\"id\":     2}\n{\"id\":3}\n",
+            ))
+            .to_http_parts();
+        let lines = from_request::<JsonLines<Record>>(&req, &mut pl)
+            .await
+            .unwrap();
+        let items = collect(lines).await;
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[1], Err(JsonPayloadError::Overflow)));
+
+        // content type
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/json")
+            .to_http_parts();
+        let res = from_request::<JsonLines<Record>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(JsonPayloadError::ContentType)));
+    }
+
+    #[crate::rt_test]
+    async fn test_responder() {
+        let req = TestRequest::default().to_http_request();
+        let lines =
+            JsonLines::new(futures_util::stream::iter((1..4).map(|id| Record { id })));
+        let mut resp = respond_to(lines, &req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/x-ndjson"
+        );
+
+        // chunk per value
+        let mut body = resp.take_body();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = poll_fn(|cx| body.poll_next_chunk(cx)).await {
+            chunks.push(chunk.unwrap());
+        }
+        assert_eq!(
+            chunks,
+            vec![
+                Bytes::from_static(b"{\"id\":1}\n"),
+                Bytes::from_static(b"{\"id\":2}\n"),
+                Bytes::from_static(b"{\"id\":3}\n")
+            ]
+        );
+    }
+}
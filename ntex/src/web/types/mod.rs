@@ -1,18 +1,41 @@
 //! Extractor types
 
+#[cfg(feature = "cbor")]
+mod cbor;
 pub(in crate::web) mod form;
 pub(in crate::web) mod json;
+mod jsonlines;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+pub(in crate::web) mod multipart;
+mod negotiate;
 mod path;
 pub(in crate::web) mod payload;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod qs;
 mod query;
+mod ranged;
+pub(in crate::web) mod scoped;
 pub(in crate::web) mod state;
 
+#[cfg(feature = "cbor")]
+pub use self::cbor::{Cbor, CborConfig};
 pub use self::form::{Form, FormConfig};
 pub use self::json::{Json, JsonConfig};
+pub use self::jsonlines::JsonLines;
+#[cfg(feature = "msgpack")]
+pub use self::msgpack::{MsgPack, MsgPackConfig};
+pub use self::multipart::{Field, Multipart, MultipartConfig, MultipartForm, TempFile};
+pub use self::negotiate::{Negotiate, NegotiateConfig};
 pub use self::path::Path;
 pub use self::payload::{Payload, PayloadConfig};
-pub use self::query::Query;
-pub use self::state::State;
+#[cfg(feature = "protobuf")]
+pub use self::protobuf::{Protobuf, ProtobufConfig};
+pub use self::query::{DuplicateKeys, Query, QueryConfig};
+pub use self::ranged::Ranged;
+pub use self::scoped::{RequestScope, Scoped};
+pub use self::state::{State, TypedState, WithState};
 
 #[deprecated]
 #[doc(hidden)]
@@ -0,0 +1,360 @@
+//! MessagePack extractor/responder
+use std::{fmt, future::Future, ops, pin::Pin, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "compress")]
+use crate::http::encoding::Decoder;
+use crate::http::header::CONTENT_LENGTH;
+use crate::http::{HttpMessage, Payload, Response, StatusCode};
+use crate::util::{stream_recv, BytesMut};
+use crate::web::error::{
+    ErrorRenderer, MsgPackError, MsgPackPayloadError, WebResponseError,
+};
+use crate::web::responder::{Ready, Responder};
+use crate::web::{FromRequest, HttpRequest};
+
+/// MessagePack helper
+///
+/// MsgPack can be used for two different purpose. First is for msgpack
+/// response generation and second is for extracting typed information
+/// from request's payload.
+///
+/// To extract typed information from request's body, the type `T` must
+/// implement the `Deserialize` trait from *serde*. Extractor accepts
+/// `application/msgpack`, `application/x-msgpack` and
+/// `application/vnd.msgpack` content types.
+///
+/// [**MsgPackConfig**](struct.MsgPackConfig.html) allows to configure
+/// extraction process.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::web::{self, types::MsgPack};
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Info {
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body, send it back
+/// async fn index(info: MsgPack<Info>) -> MsgPack<Info> {
+///     info
+/// }
+///
+/// fn main() {
+///     let app = web::App::new().service(
+///         web::resource("/index.html").route(web::post().to(index))
+///     );
+/// }
+/// ```
+pub struct MsgPack<T>(pub T);
+
+impl<T> MsgPack<T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> ops::Deref for MsgPack<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ops::DerefMut for MsgPack<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> fmt::Debug for MsgPack<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MsgPack").field(&self.0).finish()
+    }
+}
+
+impl<T: Serialize, Err: ErrorRenderer> Responder<Err> for MsgPack<T>
+where
+    Err::Container: From<MsgPackError>,
+{
+    type Error = MsgPackError;
+    type Future = Ready<Response>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let body = match rmp_serde::to_vec_named(&self.0) {
+            Ok(body) => body,
+            Err(e) => return e.error_response(req).into(),
+        };
+
+        Response::build(StatusCode::OK)
+            .content_type("application/msgpack")
+            .body(body)
+            .into()
+    }
+}
+
+impl<T, Err: ErrorRenderer> FromRequest<Err> for MsgPack<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = MsgPackPayloadError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let (limit, ctype) = req
+            .app_state::<MsgPackConfig>()
+            .map(|c| (c.limit, c.content_type.clone()))
+            .unwrap_or((262_144, None));
+
+        // check content-type
+        let valid = if let Ok(Some(mime)) = req.mime_type() {
+            (mime.type_() == mime::APPLICATION
+                && matches!(
+                    mime.subtype().as_str(),
+                    "msgpack" | "x-msgpack" | "vnd.msgpack"
+                ))
+                || ctype.as_ref().map_or(false, |predicate| predicate(mime))
+        } else {
+            false
+        };
+        if !valid {
+            log::debug!(
+                "Unsupported MsgPack content type. Request path: {}",
+                req.path()
+            );
+            return Box::pin(async { Err(MsgPackPayloadError::ContentType) });
+        }
+
+        let len = req
+            .headers()
+            .get(&CONTENT_LENGTH)
+            .and_then(|l| l.to_str().ok())
+            .and_then(|s| s.parse::<usize>().ok());
+        if len.map_or(false, |len| len > limit) {
+            return Box::pin(async { Err(MsgPackPayloadError::Overflow) });
+        }
+
+        #[cfg(feature = "compress")]
+        let mut payload = Decoder::from_headers(payload.take(), req.headers());
+        #[cfg(not(feature = "compress"))]
+        let mut payload = payload.take();
+
+        let req = req.clone();
+        Box::pin(async move {
+            let mut body = BytesMut::with_capacity(8192);
+
+            while let Some(item) = stream_recv(&mut payload).await {
+                let chunk = item?;
+                if (body.len() + chunk.len()) > limit {
+                    return Err(MsgPackPayloadError::Overflow);
+                } else {
+                    body.extend_from_slice(&chunk);
+                }
+            }
+            rmp_serde::from_slice(&body).map(MsgPack).map_err(|e| {
+                log::debug!(
+                    "Failed to deserialize MsgPack from payload. \
+                     Request path: {}",
+                    req.path()
+                );
+                e.into()
+            })
+        })
+    }
+}
+
+/// MsgPack extractor configuration
+///
+/// ```rust
+/// use ntex::web::{self, App, types::{MsgPack, MsgPackConfig}};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Info {
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body, max payload size is 4kb
+/// async fn index(info: MsgPack<Info>) -> String {
+///     format!("Welcome {}!", info.username)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/index.html")
+///             .app_state(
+///                 // change msgpack extractor configuration
+///                 MsgPackConfig::default()
+///                     .limit(4096)
+///                     .content_type(|mime| {
+///                         mime.type_() == mime::APPLICATION
+///                             && mime.subtype() == "octet-stream"
+///                     })
+///             )
+///             .route(web::post().to(index))
+///     );
+/// }
+/// ```
+#[derive(Clone)]
+pub struct MsgPackConfig {
+    limit: usize,
+    content_type: Option<Arc<dyn Fn(mime::Mime) -> bool + Send + Sync>>,
+}
+
+impl MsgPackConfig {
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set predicate for additionally allowed content types
+    pub fn content_type<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(mime::Mime) -> bool + Send + Sync + 'static,
+    {
+        self.content_type = Some(Arc::new(predicate));
+        self
+    }
+}
+
+impl Default for MsgPackConfig {
+    fn default() -> Self {
+        MsgPackConfig {
+            limit: 262_144,
+            content_type: None,
+        }
+    }
+}
+
+impl fmt::Debug for MsgPackConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MsgPackConfig")
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::http::body::{Body, ResponseBody};
+    use crate::http::header;
+    use crate::util::Bytes;
+    use crate::web::test::{from_request, respond_to, TestRequest};
+    use crate::web::{DefaultError, WebResponseError};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct MyObject {
+        name: String,
+    }
+
+    fn payload() -> Bytes {
+        Bytes::from(
+            rmp_serde::to_vec_named(&MyObject {
+                name: "test".to_string(),
+            })
+            .unwrap(),
+        )
+    }
+
+    #[crate::rt_test]
+    async fn test_responder() {
+        let req = TestRequest::default().to_http_request();
+
+        let p = MsgPack(MyObject {
+            name: "test".to_string(),
+        });
+        let resp = respond_to(p, &req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/msgpack"
+        );
+        match resp.body() {
+            ResponseBody::Body(Body::Bytes(ref b)) => assert_eq!(b, &payload()),
+            _ => panic!(),
+        }
+    }
+
+    #[crate::rt_test]
+    async fn test_extract() {
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .header(header::CONTENT_LENGTH, payload().len().to_string())
+            .set_payload(payload())
+            .to_http_parts();
+        let p = from_request::<MsgPack<MyObject>>(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(p.name, "test");
+        assert_eq!(
+            p.into_inner(),
+            MyObject {
+                name: "test".to_string()
+            }
+        );
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/x-msgpack")
+            .set_payload(payload())
+            .state(MsgPackConfig::default().limit(4))
+            .to_http_parts();
+        let res = from_request::<MsgPack<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(MsgPackPayloadError::Overflow)));
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/vnd.msgpack")
+            .header(header::CONTENT_LENGTH, "10000")
+            .state(MsgPackConfig::default().limit(100))
+            .to_http_parts();
+        let res = from_request::<MsgPack<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(MsgPackPayloadError::Overflow)));
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/json")
+            .set_payload(payload())
+            .to_http_parts();
+        let res = from_request::<MsgPack<MyObject>>(&req, &mut pl).await;
+        let err = res.unwrap_err();
+        assert!(matches!(err, MsgPackPayloadError::ContentType));
+        assert_eq!(
+            WebResponseError::<DefaultError>::status_code(&err),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .set_payload(payload())
+            .state(
+                MsgPackConfig::default()
+                    .content_type(|mime| mime.subtype() == mime::OCTET_STREAM),
+            )
+            .to_http_parts();
+        let p = from_request::<MsgPack<MyObject>>(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(p.name, "test");
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .set_payload(Bytes::from_static(b"\x81\xa4name"))
+            .to_http_parts();
+        let res = from_request::<MsgPack<MyObject>>(&req, &mut pl).await;
+        let err = res.unwrap_err();
+        assert!(matches!(err, MsgPackPayloadError::Deserialize(_)));
+        assert_eq!(
+            WebResponseError::<DefaultError>::status_code(&err),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,985 @@
+//! Multipart extractor
+use std::{
+    cell::RefCell, fmt, fs, future::Future, io::Write, ops, path::Path, path::PathBuf,
+    pin::Pin, rc::Rc, task::Context, task::Poll,
+};
+
+use mime::Mime;
+use nanorand::{Rng, WyRand};
+use serde::de::{DeserializeOwned, Error as DeError};
+
+#[cfg(feature = "compress")]
+use crate::http::encoding::Decoder;
+use crate::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use crate::http::{HttpMessage, Payload};
+use crate::util::{ready, stream_recv, Buf, Bytes, BytesMut, Ready, Stream};
+use crate::web::error::{BlockingError, ErrorRenderer, MultipartError};
+use crate::web::{FromRequest, HttpRequest};
+
+const MAX_HEADERS: usize = 16;
+const MAX_HEADERS_SIZE: usize = 8192;
+
+/// Multipart payload extractor (`multipart/form-data`)
+///
+/// `Multipart` is a stream of parts, every part is a stream of `Bytes`.
+/// Parts must be consumed in order, unread data of the part is skipped
+/// when next part is requested.
+///
+/// [**MultipartConfig**](struct.MultipartConfig.html) allows to configure
+/// max number of parts and max part size.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::util::{stream_recv, BytesMut};
+/// use ntex::web::{self, error::MultipartError, types::Multipart};
+///
+/// async fn index(mut form: Multipart) -> Result<String, MultipartError> {
+///     let mut names = Vec::new();
+///     while let Some(field) = stream_recv(&mut form).await {
+///         let mut field = field?;
+///         let mut body = BytesMut::new();
+///         while let Some(chunk) = stream_recv(&mut field).await {
+///             body.extend_from_slice(&chunk?);
+///         }
+///         names.push(format!("{}: {} bytes", field.name(), body.len()));
+///     }
+///     Ok(names.join("\n"))
+/// }
+///
+/// fn main() {
+///     let app = web::App::new().service(
+///         web::resource("/upload").route(web::post().to(index))
+///     );
+/// }
+/// ```
+pub struct Multipart {
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl Multipart {
+    fn new(
+        req: &HttpRequest,
+        payload: &mut Payload,
+        cfg: &MultipartConfig,
+    ) -> Result<Self, MultipartError> {
+        let boundary = boundary(req)?;
+
+        #[cfg(feature = "compress")]
+        let payload = Decoder::from_headers(payload.take(), req.headers());
+        #[cfg(not(feature = "compress"))]
+        let payload = payload.take();
+
+        let mut delimiter = BytesMut::with_capacity(boundary.len() + 4);
+        delimiter.extend_from_slice(b"\r\n--");
+        delimiter.extend_from_slice(boundary.as_bytes());
+
+        Ok(Multipart {
+            inner: Rc::new(RefCell::new(Inner {
+                payload,
+                // first boundary is not prefixed with CRLF
+                buf: BytesMut::from(&b"\r\n"[..]),
+                delimiter: delimiter.freeze(),
+                state: State::Preamble,
+                eof: false,
+                part: 0,
+                part_size: 0,
+                max_parts: cfg.max_parts,
+                part_limit: cfg.part_limit,
+            })),
+        })
+    }
+}
+
+impl Stream for Multipart {
+    type Item = Result<Field, MultipartError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut inner = self.inner.borrow_mut();
+        inner.poll_part(cx).map(|res| {
+            res.map(|res| {
+                res.map(|part| Field {
+                    part,
+                    id: inner.part,
+                    inner: self.inner.clone(),
+                })
+            })
+        })
+    }
+}
+
+impl fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multipart").finish()
+    }
+}
+
+impl<Err: ErrorRenderer> FromRequest<Err> for Multipart {
+    type Error = MultipartError;
+    type Future = Ready<Multipart, MultipartError>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let tmp;
+        let cfg = if let Some(cfg) = req.app_state::<MultipartConfig>() {
+            cfg
+        } else {
+            tmp = MultipartConfig::default();
+            &tmp
+        };
+        Multipart::new(req, payload, cfg).into()
+    }
+}
+
+/// Single part of the multipart payload
+///
+/// `Field` is a stream of part's content.
+pub struct Field {
+    part: Part,
+    id: usize,
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl Field {
+    /// Field name from `Content-Disposition` header
+    pub fn name(&self) -> &str {
+        &self.part.name
+    }
+
+    /// File name from `Content-Disposition` header
+    pub fn filename(&self) -> Option<&str> {
+        self.part.filename.as_deref()
+    }
+
+    /// Part's content type
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.part.content_type.as_ref()
+    }
+
+    /// Part's headers
+    pub fn headers(&self) -> &HeaderMap {
+        &self.part.headers
+    }
+}
+
+impl Stream for Field {
+    type Item = Result<Bytes, MultipartError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.part == self.id {
+            inner.poll_chunk(cx)
+        } else {
+            Poll::Ready(None)
+        }
+    }
+}
+
+impl fmt::Debug for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Field")
+            .field("name", &self.part.name)
+            .field("filename", &self.part.filename)
+            .field("content_type", &self.part.content_type)
+            .field("headers", &self.part.headers)
+            .finish()
+    }
+}
+
+/// Multipart extractor configuration
+///
+/// ```rust
+/// use ntex::web::{self, App, types::{Multipart, MultipartConfig}};
+///
+/// async fn index(form: Multipart) -> String {
+///     "Uploaded".to_string()
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/upload")
+///             // allow 4 parts, 1Mb each
+///             .state(MultipartConfig::default().max_parts(4).part_limit(1_048_576))
+///             .route(web::post().to(index))
+///     );
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MultipartConfig {
+    max_parts: usize,
+    part_limit: usize,
+    temp_dir: Option<PathBuf>,
+}
+
+impl MultipartConfig {
+    /// Change max number of parts. By default max number is 64
+    pub fn max_parts(mut self, max: usize) -> Self {
+        self.max_parts = max;
+        self
+    }
+
+    /// Change max size of the part. By default max size is 4Mb
+    pub fn part_limit(mut self, limit: usize) -> Self {
+        self.part_limit = limit;
+        self
+    }
+
+    /// Set directory for `MultipartForm` temporary files.
+    ///
+    /// By default `std::env::temp_dir()` is used.
+    pub fn temp_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.temp_dir = Some(dir.into());
+        self
+    }
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        MultipartConfig {
+            max_parts: 64,
+            part_limit: 4_194_304,
+            temp_dir: None,
+        }
+    }
+}
+
+/// Multipart form helper (`multipart/form-data`)
+///
+/// Text fields are deserialized to type `T`, `T` must implement
+/// the `Deserialize` trait from *serde*. File fields, parts with file name,
+/// are written to temporary files. Temporary files are removed on drop,
+/// use `TempFile::persist()` to keep uploaded file.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::web::{self, error::MultipartError, types::MultipartForm};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Upload {
+///     title: String,
+/// }
+///
+/// async fn index(mut form: MultipartForm<Upload>) -> Result<String, MultipartError> {
+///     let mut size = 0;
+///     for file in form.files("file") {
+///         size += file.size();
+///     }
+///     Ok(format!("{}: {} bytes", form.title, size))
+/// }
+/// # fn main() {}
+/// ```
+pub struct MultipartForm<T> {
+    form: T,
+    files: Vec<(String, TempFile)>,
+}
+
+impl<T> MultipartForm<T> {
+    /// Deconstruct to an inner value, temporary files are removed
+    pub fn into_inner(self) -> T {
+        self.form
+    }
+
+    /// Uploaded files for the field
+    pub fn files<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a TempFile> + 'a {
+        self.files
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, f)| f)
+    }
+
+    /// Take all uploaded files with field names
+    pub fn take_files(&mut self) -> Vec<(String, TempFile)> {
+        std::mem::take(&mut self.files)
+    }
+}
+
+impl<T> ops::Deref for MultipartForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.form
+    }
+}
+
+impl<T> ops::DerefMut for MultipartForm<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.form
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MultipartForm<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultipartForm")
+            .field("form", &self.form)
+            .field("files", &self.files)
+            .finish()
+    }
+}
+
+impl<T, Err> FromRequest<Err> for MultipartForm<T>
+where
+    T: DeserializeOwned + 'static,
+    Err: ErrorRenderer,
+{
+    type Error = MultipartError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let cfg = req
+            .app_state::<MultipartConfig>()
+            .cloned()
+            .unwrap_or_default();
+        let multipart = Multipart::new(req, payload, &cfg);
+
+        Box::pin(async move {
+            let mut multipart = multipart?;
+            let dir = cfg.temp_dir.unwrap_or_else(std::env::temp_dir);
+            let mut fields = Vec::new();
+            let mut files = Vec::new();
+
+            while let Some(field) = stream_recv(&mut multipart).await {
+                let mut field = field?;
+                if field.filename().is_some() {
+                    let file = TempFile::write(&dir, &mut field).await?;
+                    files.push((field.part.name, file));
+                } else {
+                    let mut body = BytesMut::new();
+                    while let Some(chunk) = stream_recv(&mut field).await {
+                        body.extend_from_slice(&chunk?);
+                    }
+                    let value = String::from_utf8(body.to_vec()).map_err(|_| {
+                        MultipartError::Deserialize(DeError::custom(format!(
+                            "field `{}` is not valid utf-8",
+                            field.name()
+                        )))
+                    })?;
+                    fields.push((field.part.name, value));
+                }
+            }
+
+            let form = serde_urlencoded::to_string(&fields)
+                .map_err(|e| MultipartError::Deserialize(DeError::custom(e)))?;
+            Ok(MultipartForm {
+                form: serde_urlencoded::from_str(&form)?,
+                files,
+            })
+        })
+    }
+}
+
+/// Uploaded file stored in temporary directory
+///
+/// File is removed on drop, unless it is persisted.
+#[derive(Debug)]
+pub struct TempFile {
+    path: Option<PathBuf>,
+    filename: Option<String>,
+    content_type: Option<Mime>,
+    size: usize,
+}
+
+impl TempFile {
+    async fn write(dir: &Path, field: &mut Field) -> Result<Self, MultipartError> {
+        let path = dir.join(format!(
+            "ntex-upload-{}-{:016x}",
+            std::process::id(),
+            WyRand::new().generate::<u64>()
+        ));
+        let mut tmp = TempFile {
+            path: Some(path.clone()),
+            filename: field.filename().map(|s| s.to_string()),
+            content_type: field.content_type().cloned(),
+            size: 0,
+        };
+
+        let mut file = Some(
+            block(move || {
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(path)
+            })
+            .await?,
+        );
+        while let Some(chunk) = stream_recv(field).await {
+            let chunk = chunk?;
+            tmp.size += chunk.len();
+
+            let mut f = file.take().unwrap();
+            file = Some(
+                block(move || {
+                    f.write_all(&chunk)?;
+                    Ok(f)
+                })
+                .await?,
+            );
+        }
+        Ok(tmp)
+    }
+
+    /// Path of the temporary file
+    pub fn path(&self) -> &Path {
+        self.path.as_ref().unwrap()
+    }
+
+    /// File name from `Content-Disposition` header
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// File's content type
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.content_type.as_ref()
+    }
+
+    /// Size of the file
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Move temporary file to the new location
+    pub fn persist<P: AsRef<Path>>(mut self, path: P) -> std::io::Result<()> {
+        let tmp = self.path.take().unwrap();
+        if let Err(e) = fs::rename(&tmp, path) {
+            self.path = Some(tmp);
+            Err(e)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+async fn block<F, I>(f: F) -> Result<I, MultipartError>
+where
+    F: FnOnce() -> std::io::Result<I> + Send + 'static,
+    I: Send + 'static,
+{
+    match crate::web::block(f).await {
+        Ok(res) => Ok(res),
+        Err(BlockingError::Error(e)) => Err(e.into()),
+        Err(BlockingError::Canceled) => Err(MultipartError::Io(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "Thread pool is gone",
+        ))),
+    }
+}
+
+/// Get boundary from `Content-Type` header
+fn boundary(req: &HttpRequest) -> Result<String, MultipartError> {
+    let mt = req
+        .mime_type()
+        .map_err(|_| MultipartError::ContentType)?
+        .ok_or(MultipartError::ContentType)?;
+    if mt.type_() != mime::MULTIPART {
+        return Err(MultipartError::ContentType);
+    }
+
+    match mt.get_param(mime::BOUNDARY) {
+        Some(b) if !b.as_str().is_empty() && b.as_str().len() <= 70 => {
+            Ok(b.as_str().to_string())
+        }
+        _ => Err(MultipartError::Boundary),
+    }
+}
+
+#[derive(Debug)]
+struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<Mime>,
+    headers: HeaderMap,
+}
+
+impl Part {
+    fn new(headers: HeaderMap) -> Result<Self, MultipartError> {
+        let mut name = None;
+        let mut filename = None;
+        let mut filename_ext = None;
+
+        let cd = headers
+            .get(&header::CONTENT_DISPOSITION)
+            .and_then(|val| val.to_str().ok())
+            .ok_or(MultipartError::Headers)?;
+        let mut params = Params(cd);
+        match params.next() {
+            Some((kind, None)) if kind.eq_ignore_ascii_case("form-data") => (),
+            _ => return Err(MultipartError::Headers),
+        }
+        for (key, val) in params {
+            match (key.to_ascii_lowercase().as_str(), val) {
+                ("name", Some(val)) => name = Some(val),
+                ("filename", Some(val)) => filename = Some(val),
+                ("filename*", Some(val)) => filename_ext = ext_value(&val),
+                _ => (),
+            }
+        }
+
+        let content_type = if let Some(val) = headers.get(&header::CONTENT_TYPE) {
+            Some(
+                val.to_str()
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or(MultipartError::Headers)?,
+            )
+        } else {
+            None
+        };
+
+        Ok(Part {
+            name: name.ok_or(MultipartError::Headers)?,
+            filename: filename_ext.or(filename),
+            content_type,
+            headers,
+        })
+    }
+}
+
+/// `Content-Disposition` header parameters
+struct Params<'a>(&'a str);
+
+impl<'a> Iterator for Params<'a> {
+    type Item = (&'a str, Option<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let s = self
+            .0
+            .trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+        if s.is_empty() {
+            return None;
+        }
+
+        let end = s.find([';', '=']).unwrap_or(s.len());
+        let key = s[..end].trim();
+        if !s[end..].starts_with('=') {
+            self.0 = &s[end..];
+            return Some((key, None));
+        }
+
+        let s = s[end + 1..].trim_start();
+        if let Some(s) = s.strip_prefix('"') {
+            let mut val = String::new();
+            let mut chars = s.char_indices();
+            while let Some((idx, ch)) = chars.next() {
+                match ch {
+                    '\\' => {
+                        if let Some((_, ch)) = chars.next() {
+                            val.push(ch);
+                        }
+                    }
+                    '"' => {
+                        self.0 = &s[idx + 1..];
+                        return Some((key, Some(val)));
+                    }
+                    _ => val.push(ch),
+                }
+            }
+            // unterminated quoted string
+            self.0 = "";
+            Some((key, Some(val)))
+        } else {
+            let end = s.find(';').unwrap_or(s.len());
+            self.0 = &s[end..];
+            Some((key, Some(s[..end].trim().to_string())))
+        }
+    }
+}
+
+/// Decode extended parameter value, only utf-8 charset is supported
+fn ext_value(val: &str) -> Option<String> {
+    let mut parts = val.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _lang = parts.next()?;
+    let val = parts.next()?;
+    if charset.eq_ignore_ascii_case("utf-8") {
+        percent_encoding::percent_decode_str(val)
+            .decode_utf8()
+            .ok()
+            .map(|s| s.into_owned())
+    } else {
+        None
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Preamble,
+    Boundary,
+    Headers,
+    Body,
+    Eof,
+}
+
+struct Inner {
+    #[cfg(feature = "compress")]
+    payload: Decoder<Payload>,
+    #[cfg(not(feature = "compress"))]
+    payload: Payload,
+    buf: BytesMut,
+    delimiter: Bytes,
+    state: State,
+    eof: bool,
+    part: usize,
+    part_size: usize,
+    max_parts: usize,
+    part_limit: usize,
+}
+
+impl Inner {
+    /// Read more data from payload
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), MultipartError>> {
+        let res = if self.eof {
+            Err(MultipartError::Incomplete)
+        } else {
+            match Pin::new(&mut self.payload).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(chunk))) => {
+                    self.buf.extend_from_slice(&chunk);
+                    Ok(())
+                }
+                Poll::Ready(Some(Err(e))) => Err(e.into()),
+                Poll::Ready(None) => {
+                    self.eof = true;
+                    Err(MultipartError::Incomplete)
+                }
+            }
+        };
+        if res.is_err() {
+            self.state = State::Eof;
+        }
+        Poll::Ready(res)
+    }
+
+    fn find_delimiter(&self) -> Option<usize> {
+        self.buf
+            .windows(self.delimiter.len())
+            .position(|w| w == &self.delimiter[..])
+    }
+
+    /// Read next part's headers, skip unread data of the current part
+    fn poll_part(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Part, MultipartError>>> {
+        loop {
+            match self.state {
+                State::Preamble => {
+                    if let Some(idx) = self.find_delimiter() {
+                        self.buf.advance(idx + self.delimiter.len());
+                        self.state = State::Boundary;
+                        continue;
+                    } else if self.buf.len() >= self.delimiter.len() {
+                        self.buf.advance(self.buf.len() + 1 - self.delimiter.len());
+                    }
+                }
+                State::Body => {
+                    if let Some(Err(e)) = ready!(self.poll_chunk(cx)) {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    continue;
+                }
+                State::Boundary => {
+                    if self.buf.len() >= 2 {
+                        if &self.buf[..2] == b"--" {
+                            self.state = State::Eof;
+                            return Poll::Ready(None);
+                        } else if &self.buf[..2] == b"\r\n" {
+                            self.buf.advance(2);
+                            self.state = State::Headers;
+                            continue;
+                        } else {
+                            self.state = State::Eof;
+                            return Poll::Ready(Some(Err(MultipartError::Boundary)));
+                        }
+                    }
+                }
+                State::Headers => {
+                    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+                    match httparse::parse_headers(&self.buf, &mut headers) {
+                        Ok(httparse::Status::Complete((len, headers))) => {
+                            let part = parse_headers(headers).and_then(Part::new);
+                            self.buf.advance(len);
+                            self.part += 1;
+                            self.part_size = 0;
+                            self.state = State::Body;
+
+                            let res = if self.part > self.max_parts {
+                                Err(MultipartError::TooManyParts(self.max_parts))
+                            } else {
+                                part
+                            };
+                            if res.is_err() {
+                                self.state = State::Eof;
+                            }
+                            return Poll::Ready(Some(res));
+                        }
+                        Ok(httparse::Status::Partial)
+                            if self.buf.len() < MAX_HEADERS_SIZE => {}
+                        _ => {
+                            self.state = State::Eof;
+                            return Poll::Ready(Some(Err(MultipartError::Headers)));
+                        }
+                    }
+                }
+                State::Eof => return Poll::Ready(None),
+            }
+
+            if let Err(e) = ready!(self.poll_fill(cx)) {
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+    }
+
+    /// Read chunk of the current part
+    fn poll_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, MultipartError>>> {
+        while self.state == State::Body {
+            let chunk = if let Some(idx) = self.find_delimiter() {
+                let chunk = self.buf.split_to(idx).freeze();
+                self.buf.advance(self.delimiter.len());
+                self.state = State::Boundary;
+                chunk
+            } else if self.buf.len() >= self.delimiter.len() {
+                // delimiter could start in the tail of the buffer
+                self.buf
+                    .split_to(self.buf.len() + 1 - self.delimiter.len())
+                    .freeze()
+            } else {
+                Bytes::new()
+            };
+
+            if !chunk.is_empty() {
+                self.part_size += chunk.len();
+                if self.part_size > self.part_limit {
+                    self.state = State::Eof;
+                    return Poll::Ready(Some(Err(MultipartError::PartOverflow(
+                        self.part_limit,
+                    ))));
+                }
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+            if self.state == State::Body {
+                if let Err(e) = ready!(self.poll_fill(cx)) {
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+fn parse_headers(headers: &[httparse::Header<'_>]) -> Result<HeaderMap, MultipartError> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for h in headers {
+        let name = HeaderName::from_bytes(h.name.as_bytes())
+            .map_err(|_| MultipartError::Headers)?;
+        let value =
+            HeaderValue::from_bytes(h.value).map_err(|_| MultipartError::Headers)?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header::CONTENT_TYPE;
+    use crate::web::test::{from_request, TestRequest};
+    use crate::web::{DefaultError, WebResponseError};
+
+    const BODY: &[u8] = b"preamble\r\n\
+        --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        test\r\n\
+        --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"fn.txt\"\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\r\n\
+        data\r\n--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename*=UTF-8''%E2%82%AC.txt\r\n\r\n\
+        \r\n\
+        --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n";
+
+    fn request(body: &'static [u8]) -> TestRequest {
+        TestRequest::with_header(
+            CONTENT_TYPE,
+            "multipart/form-data; boundary=\"abbc761f78ff4d7cb7573b5a23f96ef0\"",
+        )
+        .set_payload(Bytes::from_static(body))
+    }
+
+    async fn read(field: &mut Field) -> Result<Bytes, MultipartError> {
+        let mut body = BytesMut::new();
+        while let Some(chunk) = stream_recv(field).await {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(body.freeze())
+    }
+
+    #[crate::rt_test]
+    async fn test_multipart() {
+        let (req, mut pl) = request(BODY).to_http_parts();
+        let mut mp = from_request::<Multipart>(&req, &mut pl).await.unwrap();
+
+        let mut field = stream_recv(&mut mp).await.unwrap().unwrap();
+        assert_eq!(field.name(), "title");
+        assert_eq!(field.filename(), None);
+        assert_eq!(field.content_type(), None);
+        assert_eq!(read(&mut field).await.unwrap(), Bytes::from_static(b"test"));
+        assert!(format!("{:?}", field).contains("title"));
+
+        let mut field = stream_recv(&mut mp).await.unwrap().unwrap();
+        assert_eq!(field.name(), "file");
+        assert_eq!(field.filename(), Some("fn.txt"));
+        assert_eq!(field.content_type().unwrap().essence_str(), "text/plain");
+        assert_eq!(field.headers().len(), 2);
+        assert_eq!(read(&mut field).await.unwrap(), Bytes::from_static(b"data"));
+
+        let mut field = stream_recv(&mut mp).await.unwrap().unwrap();
+        assert_eq!(field.filename(), Some("€.txt"));
+        assert_eq!(read(&mut field).await.unwrap(), Bytes::new());
+
+        assert!(stream_recv(&mut mp).await.is_none());
+        assert!(stream_recv(&mut field).await.is_none());
+    }
+
+    #[crate::rt_test]
+    async fn test_multipart_chunks() {
+        let (req, _) = request(b"").to_http_parts();
+        let mut pl =
+            Payload::from_stream(futures_util::stream::iter(BODY.iter().map(|b| {
+                Ok::<_, crate::http::error::PayloadError>(Bytes::copy_from_slice(&[*b]))
+            })));
+        let mut mp = from_request::<Multipart>(&req, &mut pl).await.unwrap();
+
+        // skip unread field
+        let field = stream_recv(&mut mp).await.unwrap().unwrap();
+        assert_eq!(field.name(), "title");
+
+        let mut field = stream_recv(&mut mp).await.unwrap().unwrap();
+        assert_eq!(field.name(), "file");
+        assert_eq!(read(&mut field).await.unwrap(), Bytes::from_static(b"data"));
+        assert!(stream_recv(&mut mp).await.unwrap().is_ok());
+        assert!(stream_recv(&mut mp).await.is_none());
+    }
+
+    #[crate::rt_test]
+    async fn test_multipart_errors() {
+        let (req, mut pl) = TestRequest::with_header(CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(BODY))
+            .to_http_parts();
+        let res = from_request::<Multipart>(&req, &mut pl).await;
+        assert!(matches!(res, Err(MultipartError::ContentType)));
+
+        let (req, mut pl) = TestRequest::with_header(CONTENT_TYPE, "multipart/form-data")
+            .set_payload(Bytes::from_static(BODY))
+            .to_http_parts();
+        let res = from_request::<Multipart>(&req, &mut pl).await;
+        assert!(matches!(res, Err(MultipartError::Boundary)));
+
+        let (req, mut pl) = request(&BODY[..BODY.len() - 10]).to_http_parts();
+        let mut mp = from_request::<Multipart>(&req, &mut pl).await.unwrap();
+        assert!(stream_recv(&mut mp).await.unwrap().is_ok());
+        assert!(stream_recv(&mut mp).await.unwrap().is_ok());
+        let mut field = stream_recv(&mut mp).await.unwrap().unwrap();
+        assert!(matches!(
+            read(&mut field).await,
+            Err(MultipartError::Incomplete)
+        ));
+        assert!(stream_recv(&mut mp).await.is_none());
+
+        let (req, mut pl) = request(BODY)
+            .state(MultipartConfig::default().max_parts(2))
+            .to_http_parts();
+        let mut mp = from_request::<Multipart>(&req, &mut pl).await.unwrap();
+        assert!(stream_recv(&mut mp).await.unwrap().is_ok());
+        assert!(stream_recv(&mut mp).await.unwrap().is_ok());
+        assert!(matches!(
+            stream_recv(&mut mp).await.unwrap(),
+            Err(MultipartError::TooManyParts(2))
+        ));
+        assert!(stream_recv(&mut mp).await.is_none());
+
+        let (req, mut pl) = request(BODY)
+            .state(MultipartConfig::default().part_limit(3))
+            .to_http_parts();
+        let mut mp = from_request::<Multipart>(&req, &mut pl).await.unwrap();
+        let mut field = stream_recv(&mut mp).await.unwrap().unwrap();
+        assert!(matches!(
+            read(&mut field).await,
+            Err(MultipartError::PartOverflow(3))
+        ));
+
+        let err = MultipartError::PartOverflow(3);
+        assert_eq!(
+            WebResponseError::<DefaultError>::status_code(&err),
+            crate::http::StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[derive(serde::Deserialize, Debug)]
+    struct Upload {
+        title: String,
+    }
+
+    #[crate::rt_test]
+    async fn test_multipart_form() {
+        let (req, mut pl) = request(BODY).to_http_parts();
+        let mut form = from_request::<MultipartForm<Upload>>(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(form.title, "test");
+        assert_eq!(form.files("file").count(), 2);
+        assert_eq!(form.files("title").count(), 0);
+
+        let file = form.files("file").next().unwrap();
+        assert_eq!(file.filename(), Some("fn.txt"));
+        assert_eq!(file.content_type().unwrap().essence_str(), "text/plain");
+        assert_eq!(file.size(), 4);
+        assert_eq!(fs::read(file.path()).unwrap(), b"data");
+
+        let mut files = form.take_files();
+        let (_, empty) = files.pop().unwrap();
+        let path = empty.path().to_owned();
+        assert_eq!(empty.size(), 0);
+        drop(empty);
+        assert!(!path.exists());
+
+        let (name, file) = files.pop().unwrap();
+        assert_eq!(name, "file");
+        let dst = std::env::temp_dir().join(format!("{}-persist", file.path().display()));
+        file.persist(&dst).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), b"data");
+        fs::remove_file(&dst).unwrap();
+
+        let (req, mut pl) = request(BODY).to_http_parts();
+        let res = from_request::<MultipartForm<(u32, u32)>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(MultipartError::Deserialize(_))));
+    }
+
+    #[test]
+    fn test_params() {
+        let params: Vec<_> =
+            Params("form-data; name=\"a;\\\"b\"; filename=test.txt;x").collect();
+        assert_eq!(
+            params,
+            vec![
+                ("form-data", None),
+                ("name", Some("a;\"b".to_string())),
+                ("filename", Some("test.txt".to_string())),
+                ("x", None),
+            ]
+        );
+        assert_eq!(ext_value("utf-8'en'%C2%A3"), Some("£".to_string()));
+        assert_eq!(ext_value("iso-8859-1''%A3"), None);
+    }
+}
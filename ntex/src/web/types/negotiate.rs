@@ -0,0 +1,412 @@
+//! Content negotiation responder
+use std::{error::Error, fmt, ops, sync::Arc};
+
+use serde::Serialize;
+
+use crate::http::header::{HeaderValue, QualityItem, ACCEPT, VARY};
+use crate::http::{Response, StatusCode};
+use crate::util::Bytes;
+use crate::web::error::{ErrorRenderer, JsonError, WebResponseError};
+use crate::web::responder::{Ready, Responder};
+use crate::web::HttpRequest;
+
+type Serializer =
+    Arc<dyn Fn(&serde_json::Value) -> Result<Bytes, Box<dyn Error>> + Send + Sync>;
+
+/// Content negotiation responder
+///
+/// `Negotiate` serializes value to the format requested by `Accept` header.
+/// Json (`application/json`) format is always available, additional formats
+/// could be registered with [**NegotiateConfig**](struct.NegotiateConfig.html).
+/// Most specific media range of `Accept` header defines quality of a format,
+/// format with highest quality wins, default format is preferred on ties.
+/// Default format is used if request does not have `Accept` header.
+///
+/// If none of registered formats is acceptable, *Not Acceptable* response
+/// is returned, unless it is disabled by `NegotiateConfig::not_acceptable()`.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::util::Bytes;
+/// use ntex::web::{self, App, types::{Negotiate, NegotiateConfig}};
+///
+/// #[derive(serde::Serialize)]
+/// struct MyObj {
+///     name: String,
+/// }
+///
+/// async fn index() -> Negotiate<MyObj> {
+///     Negotiate(MyObj { name: "name".to_string() })
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .app_state(
+///             NegotiateConfig::default()
+///                 .format(mime::TEXT_PLAIN, |val| Ok(Bytes::from(val.to_string())))
+///         )
+///         .service(web::resource("/index.html").route(web::get().to(index)));
+/// }
+/// ```
+pub struct Negotiate<T>(pub T);
+
+impl<T> Negotiate<T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> ops::Deref for Negotiate<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ops::DerefMut for Negotiate<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> fmt::Debug for Negotiate<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Negotiate").field(&self.0).finish()
+    }
+}
+
+impl<T: Serialize, Err: ErrorRenderer> Responder<Err> for Negotiate<T>
+where
+    Err::Container: From<JsonError>,
+{
+    type Error = JsonError;
+    type Future = Ready<Response>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let cfg = req.app_state::<NegotiateConfig>();
+        let default_cfg;
+        let cfg = if let Some(cfg) = cfg {
+            cfg
+        } else {
+            default_cfg = NegotiateConfig::default();
+            &default_cfg
+        };
+
+        let format = if let Some(format) = cfg.select(req) {
+            format
+        } else if cfg.not_acceptable {
+            log::debug!(
+                "None of response formats is acceptable. Request path: {}",
+                req.path()
+            );
+            let mut res = Response::NotAcceptable().finish();
+            res.headers_mut()
+                .insert(VARY, HeaderValue::from_static("accept"));
+            return res.into();
+        } else {
+            cfg.fallback()
+        };
+
+        let body = match format.encoder {
+            Encoder::Json => match serde_json::to_vec(&self.0) {
+                Ok(body) => Bytes::from(body),
+                Err(e) => return e.error_response(req).into(),
+            },
+            Encoder::Custom(ref f) => {
+                let res = serde_json::to_value(&self.0)
+                    .map_err(|e| Box::new(e) as Box<dyn Error>)
+                    .and_then(|val| f(&val));
+                match res {
+                    Ok(body) => body,
+                    Err(e) => {
+                        log::error!("Cannot serialize `{}` response: {}", format.mime, e);
+                        return Response::InternalServerError().finish().into();
+                    }
+                }
+            }
+        };
+
+        Response::build(StatusCode::OK)
+            .content_type(format.mime.as_ref())
+            .header(VARY, "accept")
+            .body(body)
+            .into()
+    }
+}
+
+/// Response formats registry of `Negotiate` responder
+///
+/// ```rust
+/// use ntex::util::Bytes;
+/// use ntex::web::{self, App, types::NegotiateConfig};
+///
+/// fn main() {
+///     let app = App::new().app_state(
+///         NegotiateConfig::default()
+///             // register custom format, serializer receives value as json tree
+///             .format("text/csv".parse().unwrap(), |val| {
+///                 Ok(Bytes::from(format!("{}\n", val)))
+///             })
+///             // prefer csv if client accepts any format
+///             .default_format("text/csv".parse().unwrap())
+///             // fall back to csv instead of 406 response
+///             .not_acceptable(false)
+///     );
+/// }
+/// ```
+#[derive(Clone)]
+pub struct NegotiateConfig {
+    formats: Vec<Format>,
+    default: Option<mime::Mime>,
+    not_acceptable: bool,
+}
+
+#[derive(Clone)]
+struct Format {
+    mime: mime::Mime,
+    encoder: Encoder,
+}
+
+#[derive(Clone)]
+enum Encoder {
+    Json,
+    Custom(Serializer),
+}
+
+impl NegotiateConfig {
+    /// Register response format.
+    ///
+    /// Serializer receives value converted to json tree. Format with the same
+    /// media type replaces previously registered one, including built-in json.
+    pub fn format<F>(mut self, mime: mime::Mime, f: F) -> Self
+    where
+        F: Fn(&serde_json::Value) -> Result<Bytes, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        let format = Format {
+            mime,
+            encoder: Encoder::Custom(Arc::new(f)),
+        };
+        if let Some(idx) = self.position(&format.mime) {
+            self.formats[idx] = format;
+        } else {
+            self.formats.push(format);
+        }
+        self
+    }
+
+    /// Set default response format. By default it is `application/json`.
+    ///
+    /// Media type must be registered with `format()` method, otherwise
+    /// json format is used.
+    pub fn default_format(mut self, mime: mime::Mime) -> Self {
+        self.default = Some(mime);
+        self
+    }
+
+    /// Respond with *Not Acceptable* if none of formats is acceptable.
+    /// Otherwise default format is used. By default it is enabled.
+    pub fn not_acceptable(mut self, enabled: bool) -> Self {
+        self.not_acceptable = enabled;
+        self
+    }
+
+    fn position(&self, mime: &mime::Mime) -> Option<usize> {
+        self.formats
+            .iter()
+            .position(|f| f.mime.essence_str() == mime.essence_str())
+    }
+
+    fn fallback(&self) -> &Format {
+        let idx = self
+            .default
+            .as_ref()
+            .and_then(|mime| self.position(mime))
+            .unwrap_or(0);
+        &self.formats[idx]
+    }
+
+    /// Select format with highest quality
+    fn select(&self, req: &HttpRequest) -> Option<&Format> {
+        let default = self.fallback();
+        let accept = if let Some(accept) =
+            req.headers().get(&ACCEPT).and_then(|val| val.to_str().ok())
+        {
+            QualityItem::parse_list(accept)
+        } else {
+            return Some(default);
+        };
+        if accept.is_empty() {
+            return Some(default);
+        }
+
+        let mut selected: Option<(&Format, f32)> = None;
+        for format in std::iter::once(default).chain(self.formats.iter()) {
+            let quality = quality(&accept, &format.mime);
+            if quality > 0.0 && selected.map_or(true, |(_, q)| quality > q) {
+                selected = Some((format, quality));
+            }
+        }
+        selected.map(|(format, _)| format)
+    }
+}
+
+impl Default for NegotiateConfig {
+    fn default() -> Self {
+        NegotiateConfig {
+            formats: vec![Format {
+                mime: mime::APPLICATION_JSON,
+                encoder: Encoder::Json,
+            }],
+            default: None,
+            not_acceptable: true,
+        }
+    }
+}
+
+/// Quality of the most specific media range that matches media type
+fn quality(accept: &[QualityItem<'_>], mime: &mime::Mime) -> f32 {
+    let mut matched = None;
+    for item in accept {
+        let (type_, subtype) = item.item.split_once('/').unwrap_or((item.item, ""));
+        let (type_, subtype) = (type_.trim(), subtype.trim());
+
+        let specificity = if type_ == "*" && subtype == "*" {
+            0
+        } else if !type_.eq_ignore_ascii_case(mime.type_().as_str()) {
+            continue;
+        } else if subtype == "*" {
+            1
+        } else if subtype.eq_ignore_ascii_case(mime.subtype().as_str()) {
+            2
+        } else {
+            continue;
+        };
+        if matched.map_or(true, |(s, _)| specificity > s) {
+            matched = Some((specificity, item.quality));
+        }
+    }
+    matched.map_or(0.0, |(_, q)| q)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::body::{Body, ResponseBody};
+    use crate::http::header;
+    use crate::web::test::{respond_to, TestRequest};
+
+    #[derive(serde::Serialize)]
+    struct MyObject {
+        name: String,
+    }
+
+    fn obj() -> Negotiate<MyObject> {
+        Negotiate(MyObject {
+            name: "test".to_string(),
+        })
+    }
+
+    fn body(resp: &Response) -> Bytes {
+        match resp.body() {
+            ResponseBody::Body(Body::Bytes(ref b)) => b.clone(),
+            _ => panic!(),
+        }
+    }
+
+    fn config() -> NegotiateConfig {
+        NegotiateConfig::default().format(mime::TEXT_PLAIN, |val| {
+            Ok(Bytes::from(val["name"].as_str().unwrap().to_string()))
+        })
+    }
+
+    #[crate::rt_test]
+    async fn test_negotiate() {
+        // default format
+        let req = TestRequest::default().to_http_request();
+        let resp = respond_to(obj(), &req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "accept");
+        assert_eq!(body(&resp), Bytes::from_static(b"{\"name\":\"test\"}"));
+
+        let req = TestRequest::default()
+            .header(header::ACCEPT, "text/html, text/*;q=0.5, */*;q=0.1")
+            .state(config())
+            .to_http_request();
+        let resp = respond_to(obj(), &req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+        assert_eq!(body(&resp), Bytes::from_static(b"test"));
+
+        // ties prefer default format
+        let req = TestRequest::default()
+            .header(header::ACCEPT, "*/*")
+            .state(config().default_format(mime::TEXT_PLAIN))
+            .to_http_request();
+        let resp = respond_to(obj(), &req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+
+        // most specific range wins
+        let req = TestRequest::default()
+            .header(header::ACCEPT, "*/*, text/plain;q=0")
+            .state(config())
+            .to_http_request();
+        let resp = respond_to(obj(), &req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_not_acceptable() {
+        let req = TestRequest::default()
+            .header(header::ACCEPT, "text/html")
+            .to_http_request();
+        let resp = respond_to(obj(), &req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "accept");
+
+        let req = TestRequest::default()
+            .header(header::ACCEPT, "text/html")
+            .state(
+                config()
+                    .default_format(mime::TEXT_PLAIN)
+                    .not_acceptable(false),
+            )
+            .to_http_request();
+        let resp = respond_to(obj(), &req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_serializer_error() {
+        let req = TestRequest::default()
+            .header(header::ACCEPT, "text/plain")
+            .state(
+                NegotiateConfig::default()
+                    .format(mime::TEXT_PLAIN, |_| Err("unsupported".into())),
+            )
+            .to_http_request();
+        let resp = respond_to(obj(), &req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
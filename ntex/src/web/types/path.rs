@@ -234,6 +234,26 @@ mod tests {
         from_request::<()>(&req, &mut pl).await.unwrap();
     }
 
+    #[crate::rt_test]
+    async fn test_constraints_extract() {
+        let mut router = Router::<usize>::build();
+        router.path("/{id:\\d+}/{tail:[a-z/]+}*", 10).0.set_id(0);
+        let router = router.finish();
+
+        let mut req = TestRequest::with_uri("/12/a/b/c").to_srv_request();
+        assert!(router.recognize(req.match_info_mut()).is_some());
+
+        let (req, mut pl) = req.into_parts();
+        let res = from_request::<Path<(u32, Vec<String>)>>(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(res.0, 12);
+        assert_eq!(res.1, vec!["a", "b", "c"]);
+
+        let mut req = TestRequest::with_uri("/12/a/1").to_srv_request();
+        assert!(router.recognize(req.match_info_mut()).is_none());
+    }
+
     #[crate::rt_test]
     async fn test_request_extract() {
         let mut router = Router::<usize>::build();
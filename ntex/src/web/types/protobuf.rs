@@ -0,0 +1,293 @@
+//! Protobuf extractor/responder
+use std::{fmt, future::Future, ops, pin::Pin};
+
+use prost::Message;
+
+#[cfg(feature = "compress")]
+use crate::http::encoding::Decoder;
+use crate::http::header::CONTENT_LENGTH;
+use crate::http::{HttpMessage, Payload, Response, StatusCode};
+use crate::util::{stream_recv, BytesMut};
+use crate::web::error::{ErrorRenderer, ProtobufPayloadError};
+use crate::web::responder::{Ready, Responder};
+use crate::web::{FromRequest, HttpRequest};
+
+/// Protobuf helper
+///
+/// Protobuf can be used for two different purpose. First is for protobuf
+/// response generation and second is for extracting typed information
+/// from request's payload.
+///
+/// To extract typed information from request's body, the type `T` must
+/// implement the `Message` trait from *prost*. Extractor accepts
+/// `application/x-protobuf` and `application/protobuf` content types.
+///
+/// [**ProtobufConfig**](struct.ProtobufConfig.html) allows to configure
+/// extraction process.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::web::{self, types::Protobuf};
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct Info {
+///     #[prost(string, tag = "1")]
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body, send it back
+/// async fn index(info: Protobuf<Info>) -> Protobuf<Info> {
+///     info
+/// }
+///
+/// fn main() {
+///     let app = web::App::new().service(
+///         web::resource("/index.html").route(web::post().to(index))
+///     );
+/// }
+/// ```
+pub struct Protobuf<T>(pub T);
+
+impl<T> Protobuf<T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> ops::Deref for Protobuf<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ops::DerefMut for Protobuf<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> fmt::Debug for Protobuf<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Protobuf").field(&self.0).finish()
+    }
+}
+
+impl<T: Message, Err: ErrorRenderer> Responder<Err> for Protobuf<T> {
+    type Error = Err::Container;
+    type Future = Ready<Response>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        Response::build(StatusCode::OK)
+            .content_type("application/x-protobuf")
+            .body(self.0.encode_to_vec())
+            .into()
+    }
+}
+
+impl<T, Err: ErrorRenderer> FromRequest<Err> for Protobuf<T>
+where
+    T: Message + Default + 'static,
+{
+    type Error = ProtobufPayloadError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let limit = req
+            .app_state::<ProtobufConfig>()
+            .map(|c| c.limit)
+            .unwrap_or(262_144);
+
+        // check content-type
+        let valid = if let Ok(Some(mime)) = req.mime_type() {
+            mime.type_() == mime::APPLICATION
+                && matches!(mime.subtype().as_str(), "x-protobuf" | "protobuf")
+        } else {
+            false
+        };
+        if !valid {
+            log::debug!(
+                "Unsupported Protobuf content type. Request path: {}",
+                req.path()
+            );
+            return Box::pin(async { Err(ProtobufPayloadError::ContentType) });
+        }
+
+        let len = req
+            .headers()
+            .get(&CONTENT_LENGTH)
+            .and_then(|l| l.to_str().ok())
+            .and_then(|s| s.parse::<usize>().ok());
+        if len.map_or(false, |len| len > limit) {
+            return Box::pin(async { Err(ProtobufPayloadError::Overflow) });
+        }
+
+        #[cfg(feature = "compress")]
+        let mut payload = Decoder::from_headers(payload.take(), req.headers());
+        #[cfg(not(feature = "compress"))]
+        let mut payload = payload.take();
+
+        let req = req.clone();
+        Box::pin(async move {
+            let mut body = BytesMut::with_capacity(8192);
+
+            while let Some(item) = stream_recv(&mut payload).await {
+                let chunk = item?;
+                if (body.len() + chunk.len()) > limit {
+                    return Err(ProtobufPayloadError::Overflow);
+                } else {
+                    body.extend_from_slice(&chunk);
+                }
+            }
+            T::decode(&body[..]).map(Protobuf).map_err(|e| {
+                log::debug!(
+                    "Failed to deserialize Protobuf from payload. \
+                     Request path: {}",
+                    req.path()
+                );
+                e.into()
+            })
+        })
+    }
+}
+
+/// Protobuf extractor configuration
+///
+/// ```rust
+/// use ntex::web::{self, App, types::{Protobuf, ProtobufConfig}};
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct Info {
+///     #[prost(string, tag = "1")]
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body, max payload size is 4kb
+/// async fn index(info: Protobuf<Info>) -> String {
+///     format!("Welcome {}!", info.username)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/index.html")
+///             .app_state(ProtobufConfig::default().limit(4096))
+///             .route(web::post().to(index))
+///     );
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ProtobufConfig {
+    limit: usize,
+}
+
+impl ProtobufConfig {
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl Default for ProtobufConfig {
+    fn default() -> Self {
+        ProtobufConfig { limit: 262_144 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::body::{Body, ResponseBody};
+    use crate::http::header;
+    use crate::util::Bytes;
+    use crate::web::test::{from_request, respond_to, TestRequest};
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MyObject {
+        #[prost(string, tag = "1")]
+        name: String,
+    }
+
+    fn payload() -> Bytes {
+        Bytes::from(
+            MyObject {
+                name: "test".to_string(),
+            }
+            .encode_to_vec(),
+        )
+    }
+
+    #[crate::rt_test]
+    async fn test_responder() {
+        let req = TestRequest::default().to_http_request();
+
+        let p = Protobuf(MyObject {
+            name: "test".to_string(),
+        });
+        let resp = respond_to(p, &req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/x-protobuf"
+        );
+        match resp.body() {
+            ResponseBody::Body(Body::Bytes(ref b)) => assert_eq!(b, &payload()),
+            _ => panic!(),
+        }
+    }
+
+    #[crate::rt_test]
+    async fn test_extract() {
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/x-protobuf")
+            .header(header::CONTENT_LENGTH, payload().len().to_string())
+            .set_payload(payload())
+            .to_http_parts();
+        let p = from_request::<Protobuf<MyObject>>(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(p.name, "test");
+        assert_eq!(
+            p.into_inner(),
+            MyObject {
+                name: "test".to_string()
+            }
+        );
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/protobuf")
+            .set_payload(payload())
+            .state(ProtobufConfig::default().limit(4))
+            .to_http_parts();
+        let res = from_request::<Protobuf<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(ProtobufPayloadError::Overflow)));
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/x-protobuf")
+            .header(header::CONTENT_LENGTH, "10000")
+            .state(ProtobufConfig::default().limit(100))
+            .to_http_parts();
+        let res = from_request::<Protobuf<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(ProtobufPayloadError::Overflow)));
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/json")
+            .set_payload(payload())
+            .to_http_parts();
+        let res = from_request::<Protobuf<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(ProtobufPayloadError::ContentType)));
+
+        let (req, mut pl) = TestRequest::default()
+            .header(header::CONTENT_TYPE, "application/x-protobuf")
+            .set_payload(Bytes::from_static(b"\x0a\x10abc"))
+            .to_http_parts();
+        let res = from_request::<Protobuf<MyObject>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(ProtobufPayloadError::Deserialize(_))));
+    }
+}
@@ -0,0 +1,529 @@
+//! Nested query string deserializer
+use std::borrow::Cow;
+
+use percent_encoding::percent_decode_str;
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, Error as DeError, IntoDeserializer};
+use serde::de::{Unexpected, Visitor};
+use serde::forward_to_deserialize_any;
+
+use super::query::DuplicateKeys;
+use crate::web::error::QueryPayloadError;
+
+/// Parsed query parameters
+enum Node {
+    /// Values of the key, in order of appearance
+    Values(Vec<String>),
+    /// Nested keys, in order of appearance
+    Map(Vec<(String, Node)>),
+}
+
+/// Deserialize query string with nested keys and repeated parameters.
+pub(super) fn from_str<T: DeserializeOwned>(
+    query: &str,
+    depth: usize,
+    duplicates: DuplicateKeys,
+) -> Result<T, QueryPayloadError> {
+    let root = parse(query, depth)?;
+    Ok(T::deserialize(NodeDeserializer {
+        key: "",
+        node: &root,
+        duplicates,
+    })?)
+}
+
+fn parse(query: &str, depth: usize) -> Result<Node, QueryPayloadError> {
+    let mut root = Vec::new();
+    for pair in query.split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = if let Some(idx) = pair.find('=') {
+            (&pair[..idx], &pair[idx + 1..])
+        } else {
+            (pair, "")
+        };
+        let key = decode(key);
+
+        // `key[]` is the same as repeated `key`
+        let mut keys = split_key(&key);
+        if keys.len() > 1 && keys[keys.len() - 1].is_empty() {
+            keys.pop();
+        }
+        if keys.len() - 1 > depth {
+            return Err(QueryPayloadError::Depth(depth));
+        }
+        if keys[..keys.len() - 1].iter().any(|k| k.is_empty()) {
+            return Err(Error::custom(format!("unsupported query key: {}", key)).into());
+        }
+        insert(&mut root, &keys, decode(value).into_owned())?;
+    }
+    Ok(Node::Map(root))
+}
+
+fn decode(s: &str) -> Cow<'_, str> {
+    if s.contains('+') {
+        let s = s.replace('+', " ");
+        Cow::Owned(percent_decode_str(&s).decode_utf8_lossy().into_owned())
+    } else {
+        percent_decode_str(s).decode_utf8_lossy()
+    }
+}
+
+/// Split `a[b][c]` key to `a`, `b` and `c` keys, malformed keys are not split
+fn split_key(key: &str) -> Vec<&str> {
+    if let Some(start) = key.find('[') {
+        if start > 0 && key.ends_with(']') {
+            let mut keys = vec![&key[..start]];
+            for k in key[start + 1..key.len() - 1].split("][") {
+                if k.contains(['[', ']']) {
+                    return vec![key];
+                }
+                keys.push(k);
+            }
+            return keys;
+        }
+    }
+    vec![key]
+}
+
+fn insert(
+    mut items: &mut Vec<(String, Node)>,
+    keys: &[&str],
+    value: String,
+) -> Result<(), Error> {
+    let (last, path) = keys.split_last().unwrap();
+    for key in path {
+        let pos = if let Some(pos) = items.iter().position(|(k, _)| k == key) {
+            pos
+        } else {
+            items.push((key.to_string(), Node::Map(Vec::new())));
+            items.len() - 1
+        };
+        items = match items[pos].1 {
+            Node::Map(ref mut items) => items,
+            Node::Values(_) => return Err(conflict(key)),
+        };
+    }
+    match items.iter_mut().find(|(k, _)| k == last) {
+        Some((_, Node::Values(ref mut values))) => values.push(value),
+        Some((_, Node::Map(_))) => return Err(conflict(last)),
+        None => items.push((last.to_string(), Node::Values(vec![value]))),
+    }
+    Ok(())
+}
+
+fn conflict(key: &str) -> Error {
+    Error::custom(format!(
+        "query parameter is used both as value and as nested keys: {}",
+        key
+    ))
+}
+
+macro_rules! forward_to_value {
+    ($($trait_fn:ident)*) => {
+        $(
+            fn $trait_fn<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.value()?.$trait_fn(visitor)
+            }
+        )*
+    };
+}
+
+struct NodeDeserializer<'de> {
+    key: &'de str,
+    node: &'de Node,
+    duplicates: DuplicateKeys,
+}
+
+impl<'de> NodeDeserializer<'de> {
+    /// Single value of the key
+    fn value(&self) -> Result<ValueDeserializer<'de>, Error> {
+        match self.node {
+            Node::Values(ref values) => {
+                let value = match self.duplicates {
+                    DuplicateKeys::First => &values[0],
+                    DuplicateKeys::Last => &values[values.len() - 1],
+                    DuplicateKeys::Reject => {
+                        if values.len() > 1 {
+                            return Err(Error::custom(format!(
+                                "duplicate query parameter: {}",
+                                self.key
+                            )));
+                        }
+                        &values[0]
+                    }
+                };
+                Ok(ValueDeserializer(value))
+            }
+            Node::Map(_) => Err(Error::invalid_type(Unexpected::Map, &"value")),
+        }
+    }
+
+    fn child(&self, key: &'de str, node: &'de Node) -> Self {
+        NodeDeserializer {
+            key,
+            node,
+            duplicates: self.duplicates,
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for NodeDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for NodeDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            Node::Values(_) => self.value()?.deserialize_any(visitor),
+            Node::Map(_) => self.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            Node::Values(ref values) => {
+                let mut seq =
+                    SeqDeserializer::new(values.iter().map(|v| ValueDeserializer(v)));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Node::Map(ref items) => {
+                // indexed keys, `key[0]=a&key[1]=b`
+                let mut indexed = Vec::with_capacity(items.len());
+                for (k, node) in items {
+                    let idx: usize = k
+                        .parse()
+                        .map_err(|_| Error::invalid_type(Unexpected::Map, &"sequence"))?;
+                    indexed.push((idx, k, node));
+                }
+                indexed.sort_by_key(|item| item.0);
+
+                let mut seq = SeqDeserializer::new(
+                    indexed
+                        .into_iter()
+                        .map(|(_, k, node)| self.child(k.as_str(), node)),
+                );
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            Node::Map(ref items) => {
+                let mut map = MapDeserializer::new(
+                    items
+                        .iter()
+                        .map(|(k, node)| (k.as_str(), self.child(k.as_str(), node))),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Node::Values(ref values) => {
+                Err(Error::invalid_type(Unexpected::Str(&values[0]), &"map"))
+            }
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.value()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_unit
+        deserialize_identifier
+    }
+}
+
+macro_rules! parse_value {
+    ($trait_fn:ident, $visit_fn:ident) => {
+        fn $trait_fn<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            let v = self
+                .0
+                .parse()
+                .map_err(|_| Error::invalid_value(Unexpected::Str(self.0), &visitor))?;
+            visitor.$visit_fn(v)
+        }
+    };
+}
+
+/// Single query parameter value
+struct ValueDeserializer<'de>(&'de str);
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(self.0))
+    }
+
+    parse_value!(deserialize_bool, visit_bool);
+    parse_value!(deserialize_i8, visit_i8);
+    parse_value!(deserialize_i16, visit_i16);
+    parse_value!(deserialize_i32, visit_i32);
+    parse_value!(deserialize_i64, visit_i64);
+    parse_value!(deserialize_u8, visit_u8);
+    parse_value!(deserialize_u16, visit_u16);
+    parse_value!(deserialize_u32, visit_u32);
+    parse_value!(deserialize_u64, visit_u64);
+    parse_value!(deserialize_f32, visit_f32);
+    parse_value!(deserialize_f64, visit_f64);
+    parse_value!(deserialize_char, visit_char);
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Filter {
+        tag: Vec<String>,
+        page: Option<u32>,
+        range: Option<Range>,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Range {
+        min: i32,
+        max: i32,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Item {
+        name: String,
+        qty: u32,
+    }
+
+    #[test]
+    fn test_split_key() {
+        assert_eq!(split_key("a"), vec!["a"]);
+        assert_eq!(split_key("a[]"), vec!["a", ""]);
+        assert_eq!(split_key("a[b][c]"), vec!["a", "b", "c"]);
+        assert_eq!(split_key("[a]"), vec!["[a]"]);
+        assert_eq!(split_key("a[b"), vec!["a[b"]);
+        assert_eq!(split_key("a[b]c]"), vec!["a[b]c]"]);
+    }
+
+    #[test]
+    fn test_repeated_and_nested() {
+        let f: Filter = from_str(
+            "tag=a&tag[]=b&range[min]=-1&range%5Bmax%5D=10",
+            5,
+            DuplicateKeys::Reject,
+        )
+        .unwrap();
+        assert_eq!(
+            f,
+            Filter {
+                tag: vec!["a".to_string(), "b".to_string()],
+                page: None,
+                range: Some(Range { min: -1, max: 10 }),
+            }
+        );
+
+        let items: HashMap<String, Vec<Item>> = from_str(
+            "items[1][name]=b+c&items[1][qty]=2&items[0][name]=a&items[0][qty]=1",
+            5,
+            DuplicateKeys::Reject,
+        )
+        .unwrap();
+        assert_eq!(
+            items["items"],
+            vec![
+                Item {
+                    name: "a".to_string(),
+                    qty: 1
+                },
+                Item {
+                    name: "b c".to_string(),
+                    qty: 2
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let q = "tag=a&page=1&page=2";
+        assert!(from_str::<Filter>(q, 5, DuplicateKeys::Reject).is_err());
+        let f: Filter = from_str(q, 5, DuplicateKeys::First).unwrap();
+        assert_eq!(f.page, Some(1));
+        let f: Filter = from_str(q, 5, DuplicateKeys::Last).unwrap();
+        assert_eq!(f.page, Some(2));
+    }
+
+    #[test]
+    fn test_errors() {
+        match from_str::<Filter>("range[min][v]=1", 1, DuplicateKeys::Reject) {
+            Err(QueryPayloadError::Depth(1)) => (),
+            _ => panic!(),
+        }
+        assert!(
+            from_str::<Filter>("tag=a&range=1&range[min]=1", 5, DuplicateKeys::Last)
+                .is_err()
+        );
+        assert!(from_str::<Filter>("tag[][a]=1", 5, DuplicateKeys::Last).is_err());
+        assert!(from_str::<Filter>("tag=a&page=x", 5, DuplicateKeys::Last).is_err());
+        assert!(from_str::<Vec<u32>>("a=1", 5, DuplicateKeys::Last).is_err());
+    }
+}
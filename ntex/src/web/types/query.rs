@@ -3,6 +3,7 @@ use std::{fmt, ops};
 
 use serde::de;
 
+use super::qs;
 use crate::web::error::{ErrorRenderer, QueryPayloadError};
 use crate::web::{FromRequest, HttpRequest};
 use crate::{http::Payload, util::Ready};
@@ -132,11 +133,15 @@ where
 
     #[inline]
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        serde_urlencoded::from_str::<T>(req.query_string())
-            .map(|val| Ready::Ok(Query(val)))
-            .unwrap_or_else(move |e| {
-                let e = QueryPayloadError::Deserialize(e);
+        let res = if let Some(cfg) = req.app_state::<QueryConfig>() {
+            qs::from_str::<T>(req.query_string(), cfg.depth, cfg.duplicates)
+        } else {
+            serde_urlencoded::from_str::<T>(req.query_string())
+                .map_err(QueryPayloadError::Deserialize)
+        };
 
+        res.map(|val| Ready::Ok(Query(val)))
+            .unwrap_or_else(move |e| {
                 log::debug!(
                     "Failed during Query extractor deserialization. \
                      Request path: {:?}",
@@ -147,6 +152,90 @@ where
     }
 }
 
+/// Query extractor configuration
+///
+/// By default `Query<T>` extractor uses plain `application/x-www-form-urlencoded`
+/// parser. If `QueryConfig` is registered, extractor uses parser that supports
+/// repeated parameters, `tag=a&tag=b` or `tag[]=a&tag[]=b`, and nested keys,
+/// `range[min]=1&range[max]=10` or `items[0][name]=a`.
+///
+/// ```rust
+/// use ntex::web::{self, types::DuplicateKeys, types::QueryConfig, App};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Range {
+///     min: u32,
+///     max: u32,
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct Filter {
+///     tag: Vec<String>,
+///     range: Option<Range>,
+/// }
+///
+/// // `/items?tag=a&tag=b&range[min]=1&range[max]=10`
+/// async fn index(filter: web::types::Query<Filter>) -> String {
+///     format!("tags: {:?}", filter.tag)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/items")
+///             .app_state(
+///                 QueryConfig::default()
+///                     .depth(2)
+///                     .duplicate_keys(DuplicateKeys::Last),
+///             )
+///             .route(web::get().to(index)),
+///     );
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct QueryConfig {
+    depth: usize,
+    duplicates: DuplicateKeys,
+}
+
+/// Strategy for repeated parameters deserialized into a single value
+///
+/// Sequences always collect all values of the parameter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Use first value
+    First,
+    /// Use last value
+    Last,
+    /// Fail with deserialize error
+    Reject,
+}
+
+impl QueryConfig {
+    /// Set max depth of nested keys, `a[b][c]` has depth 2. By default depth is 5.
+    ///
+    /// Query with deeper keys fails with `QueryPayloadError::Depth` error.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set strategy for repeated parameters. By default repeated
+    /// parameters are rejected, unless deserialized into a sequence.
+    pub fn duplicate_keys(mut self, strategy: DuplicateKeys) -> Self {
+        self.duplicates = strategy;
+        self
+    }
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        QueryConfig {
+            depth: 5,
+            duplicates: DuplicateKeys::Reject,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,4 +281,33 @@ mod tests {
         let s = s.into_inner();
         assert_eq!(s.id, "test1");
     }
+
+    #[crate::rt_test]
+    async fn test_request_extract_nested() {
+        #[derive(serde::Deserialize)]
+        struct Filter {
+            tag: Vec<String>,
+            page: u32,
+        }
+
+        // plain parser
+        let req = TestRequest::with_uri("/?tag=a&tag=b&page=1").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(from_request::<Query<Filter>>(&req, &mut pl).await.is_err());
+
+        let req = TestRequest::with_uri("/?tag=a&tag[]=b&page=1&page=2")
+            .state(QueryConfig::default().duplicate_keys(DuplicateKeys::First))
+            .to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = from_request::<Query<Filter>>(&req, &mut pl).await.unwrap();
+        assert_eq!(s.tag, vec!["a", "b"]);
+        assert_eq!(s.page, 1);
+
+        let req = TestRequest::with_uri("/?tag[a][b]=1&page=1")
+            .state(QueryConfig::default().depth(1))
+            .to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let res = from_request::<Query<Filter>>(&req, &mut pl).await;
+        assert!(matches!(res, Err(QueryPayloadError::Depth(1))));
+    }
 }
@@ -0,0 +1,377 @@
+//! Byte range responder for streaming bodies
+use std::task::{Context, Poll};
+use std::{collections::VecDeque, error::Error, ops, pin::Pin, rc::Rc, time::SystemTime};
+
+use httpdate::HttpDate;
+use nanorand::{Rng, WyRand};
+use pin_project_lite::pin_project;
+
+use crate::http::body::{Body, SizedStream};
+use crate::http::conditional::{self, header_str};
+use crate::http::{header, Response, StatusCode};
+use crate::util::{Bytes, Stream};
+use crate::web::error::ErrorRenderer;
+use crate::web::fs::HttpRange;
+use crate::web::responder::{Ready, Responder};
+use crate::web::HttpRequest;
+
+type BoxedStream = Pin<Box<dyn Stream<Item = Result<Bytes, Box<dyn Error>>>>>;
+type RangeFn = Rc<dyn Fn(ops::Range<u64>) -> BoxedStream>;
+
+/// Byte range responder for content of known size.
+///
+/// Handler provides total size of the content and a function that produces
+/// stream of bytes for requested byte range. `Ranged` responds with
+/// `206 Partial Content` for satisfiable `Range` requests, multiple ranges
+/// are sent as `multipart/byteranges` body. Not satisfiable ranges get
+/// `416 Range Not Satisfiable` response. Full content is sent if request
+/// does not contain `Range` header or if `If-Range` precondition does not match.
+///
+/// ## Example
+///
+/// ```rust
+/// use ntex::util::Bytes;
+/// use ntex::web::{self, types::Ranged};
+///
+/// const MEDIA: &[u8] = b"media content";
+///
+/// async fn media() -> Ranged {
+///     Ranged::new(MEDIA.len() as u64, |range| {
+///         let chunk = &MEDIA[range.start as usize..range.end as usize];
+///         futures_util::stream::iter(vec![Ok::<_, std::io::Error>(Bytes::from_static(chunk))])
+///     })
+///     .content_type("video/mp4")
+///     .etag("\"v1\"")
+/// }
+///
+/// fn main() {
+///     let app = web::App::new().service(web::resource("/media").to(media));
+/// }
+/// ```
+pub struct Ranged {
+    size: u64,
+    content_type: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<HttpDate>,
+    f: RangeFn,
+}
+
+impl Ranged {
+    /// Create responder for content of `size` bytes
+    pub fn new<F, S, E>(size: u64, f: F) -> Self
+    where
+        F: Fn(ops::Range<u64>) -> S + 'static,
+        S: Stream<Item = Result<Bytes, E>> + 'static,
+        E: Error + 'static,
+    {
+        Ranged {
+            size,
+            content_type: None,
+            etag: None,
+            last_modified: None,
+            f: Rc::new(move |range| Box::pin(MapErr { stream: f(range) })),
+        }
+    }
+
+    /// Set content type of the content
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Set entity tag of the content, it is used for `If-Range` evaluation
+    pub fn etag<T: Into<String>>(mut self, etag: T) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+
+    /// Set last modification time of the content, it is used
+    /// for `If-Range` evaluation
+    pub fn last_modified(mut self, time: SystemTime) -> Self {
+        self.last_modified = Some(HttpDate::from(time));
+        self
+    }
+
+    /// Create response for the request
+    pub fn into_response(self, req: &HttpRequest) -> Response {
+        let size = self.size;
+
+        let mut res = Response::build(StatusCode::OK);
+        res.header(header::ACCEPT_RANGES, "bytes");
+        if let Some(ref etag) = self.etag {
+            res.header(header::ETAG, etag.as_str());
+        }
+        if let Some(lm) = self.last_modified {
+            res.header(header::LAST_MODIFIED, lm.to_string());
+        }
+
+        let range = header_str(req.headers(), &header::RANGE).filter(|_| {
+            conditional::if_range_matches(
+                req.headers(),
+                self.etag.as_deref(),
+                self.last_modified,
+            )
+        });
+        let ranges = match range.map(|val| HttpRange::parse(val, size)) {
+            Some(Ok(ranges)) => ranges,
+            Some(Err(_)) => {
+                return res
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                    .finish()
+            }
+            None => {
+                if let Some(ref ct) = self.content_type {
+                    res.header(header::CONTENT_TYPE, ct.as_str());
+                }
+                let stream = (self.f)(0..size);
+                return res.body(Body::from_message(SizedStream::new(size, stream)));
+            }
+        };
+
+        res.status(StatusCode::PARTIAL_CONTENT);
+        if ranges.len() == 1 {
+            let range = ranges[0];
+            if let Some(ref ct) = self.content_type {
+                res.header(header::CONTENT_TYPE, ct.as_str());
+            }
+            let stream = (self.f)(range.to_range());
+            res.header(header::CONTENT_RANGE, range.content_range(size))
+                .body(Body::from_message(SizedStream::new(range.length, stream)))
+        } else {
+            let boundary = format!("{:016x}", WyRand::new().generate::<u64>());
+            let body = ByteRanges::new(
+                &boundary,
+                self.content_type.as_deref(),
+                &ranges,
+                size,
+                self.f,
+            );
+            res.header(
+                header::CONTENT_TYPE,
+                format!("multipart/byteranges; boundary={}", boundary),
+            )
+            .body(Body::from_message(SizedStream::new(
+                body.size,
+                Box::pin(body),
+            )))
+        }
+    }
+}
+
+impl<Err: ErrorRenderer> Responder<Err> for Ranged {
+    type Error = Err::Container;
+    type Future = Ready<Response>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        self.into_response(req).into()
+    }
+}
+
+pin_project! {
+    struct MapErr<S> {
+        #[pin]
+        stream: S,
+    }
+}
+
+impl<S, E> Stream for MapErr<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Error + 'static,
+{
+    type Item = Result<Bytes, Box<dyn Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project()
+            .stream
+            .poll_next(cx)
+            .map(|item| item.map(|res| res.map_err(|e| Box::new(e) as Box<dyn Error>)))
+    }
+}
+
+/// `multipart/byteranges` body
+struct ByteRanges {
+    f: RangeFn,
+    size: u64,
+    parts: VecDeque<(Bytes, ops::Range<u64>)>,
+    current: Option<BoxedStream>,
+    trailer: Option<Bytes>,
+}
+
+impl ByteRanges {
+    fn new(
+        boundary: &str,
+        content_type: Option<&str>,
+        ranges: &[HttpRange],
+        total: u64,
+        f: RangeFn,
+    ) -> Self {
+        let mut size = 0;
+        let mut parts = VecDeque::with_capacity(ranges.len());
+        for (idx, range) in ranges.iter().enumerate() {
+            let mut head = String::new();
+            if idx != 0 {
+                head.push_str("\r\n");
+            }
+            head.push_str("--");
+            head.push_str(boundary);
+            head.push_str("\r\n");
+            if let Some(ct) = content_type {
+                head.push_str("Content-Type: ");
+                head.push_str(ct);
+                head.push_str("\r\n");
+            }
+            head.push_str("Content-Range: ");
+            head.push_str(&range.content_range(total));
+            head.push_str("\r\n\r\n");
+
+            size += head.len() as u64 + range.length;
+            parts.push_back((Bytes::from(head), range.to_range()));
+        }
+        let trailer = Bytes::from(format!("\r\n--{}--\r\n", boundary));
+        size += trailer.len() as u64;
+
+        ByteRanges {
+            f,
+            size,
+            parts,
+            current: None,
+            trailer: Some(trailer),
+        }
+    }
+}
+
+impl Stream for ByteRanges {
+    type Item = Result<Bytes, Box<dyn Error>>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(ref mut stream) = this.current {
+            match stream.as_mut().poll_next(cx) {
+                Poll::Ready(None) => this.current = None,
+                result => return result,
+            }
+        }
+
+        if let Some((head, range)) = this.parts.pop_front() {
+            this.current = Some((this.f)(range));
+            Poll::Ready(Some(Ok(head)))
+        } else {
+            Poll::Ready(this.trailer.take().map(Ok))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::body::{BodySize, MessageBody};
+    use crate::web::test::{call_service, init_service, read_body, TestRequest};
+    use crate::web::{self, App};
+
+    const CONTENT: &[u8] = b"0123456789abcdef";
+
+    fn ranged() -> Ranged {
+        Ranged::new(CONTENT.len() as u64, |range| {
+            // emit every byte as separate chunk
+            futures_util::stream::iter(
+                CONTENT[range.start as usize..range.end as usize]
+                    .iter()
+                    .map(|b| Ok::<_, std::io::Error>(Bytes::copy_from_slice(&[*b])))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .content_type("text/plain")
+        .etag("\"v1\"")
+    }
+
+    #[crate::rt_test]
+    async fn test_ranged() {
+        let srv =
+            init_service(App::new().service(web::resource("/").to(|| async { ranged() })))
+                .await;
+
+        let resp = call_service(&srv, TestRequest::default().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes");
+        assert_eq!(read_body(resp).await, Bytes::from_static(CONTENT));
+
+        let req = TestRequest::default()
+            .header(header::RANGE, "bytes=2-5")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 2-5/16"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+        assert_eq!(read_body(resp).await, Bytes::from_static(b"2345"));
+
+        let req = TestRequest::default()
+            .header(header::RANGE, "bytes=16-")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes */16"
+        );
+
+        // if-range does not match, full content
+        let req = TestRequest::default()
+            .header(header::RANGE, "bytes=2-5")
+            .header(header::IF_RANGE, "\"v2\"")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(read_body(resp).await, Bytes::from_static(CONTENT));
+    }
+
+    #[crate::rt_test]
+    async fn test_multiple_ranges() {
+        let srv =
+            init_service(App::new().service(web::resource("/").to(|| async { ranged() })))
+                .await;
+
+        let req = TestRequest::default()
+            .header(header::RANGE, "bytes=0-1, -3")
+            .to_request();
+        let resp = call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+
+        let ct = resp.headers().get(header::CONTENT_TYPE).unwrap();
+        let boundary = ct
+            .to_str()
+            .unwrap()
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap()
+            .to_string();
+        let body = read_body(resp).await;
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            format!(
+                "--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/16\r\n\r\n01\
+                 \r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 13-15/16\r\n\r\ndef\
+                 \r\n--{b}--\r\n",
+                b = boundary
+            )
+        );
+
+        // size of the body is known
+        let req = TestRequest::default()
+            .header(header::RANGE, "bytes=0-1, -3")
+            .to_http_request();
+        assert_eq!(
+            ranged().into_response(&req).body().size(),
+            BodySize::Sized(body.len() as u64)
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! Request scoped values
+use std::task::{Context, Poll};
+use std::{cell::RefCell, error::Error, future::Future, ops::Deref, pin::Pin, rc::Rc};
+
+use crate::http::body::{Body, BodySize, MessageBody, ResponseBody};
+use crate::http::{Payload, StatusCode};
+use crate::util::Bytes;
+use crate::web::error::{DataExtractorError, DefaultError, ErrorRenderer};
+use crate::web::extract::FromRequest;
+use crate::web::httprequest::HttpRequest;
+use crate::web::response::WebResponse;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+type Teardown = Box<dyn FnOnce(&HttpRequest, StatusCode) -> Option<BoxFuture<()>>>;
+
+/// Per-request value factory.
+///
+/// Request scope constructs value lazily, the first time handler
+/// extracts it with `Scoped<T>` extractor. Value is constructed at most
+/// once per request, all extractors of the same request share it.
+/// Optional teardown function runs after the response is written,
+/// it receives the value and the response status.
+///
+/// Teardown does not run if request processing failed with an error,
+/// value is dropped instead.
+///
+/// ```rust
+/// use ntex::web::{self, App, HttpResponse};
+/// use ntex::web::types::{RequestScope, Scoped};
+///
+/// struct Transaction {
+///     id: usize,
+/// }
+///
+/// impl Transaction {
+///     async fn commit(self) {}
+///     async fn rollback(self) {}
+/// }
+///
+/// async fn index(tx: Scoped<Transaction>) -> HttpResponse {
+///     HttpResponse::Ok().body(format!("transaction: {}", tx.id))
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .request_scope(
+///             RequestScope::new(|_| async { Ok::<_, web::Error>(Transaction { id: 1 }) })
+///                 .teardown(|tx, status| async move {
+///                     if status.is_success() {
+///                         tx.commit().await
+///                     } else {
+///                         tx.rollback().await
+///                     }
+///                 }),
+///         )
+///         .service(web::resource("/index.html").route(web::get().to(index)));
+/// }
+/// ```
+pub struct RequestScope<T, Err: ErrorRenderer = DefaultError> {
+    factory: Box<dyn Fn(&HttpRequest) -> BoxFuture<Result<T, Err::Container>>>,
+    teardown: Option<Rc<dyn Fn(T, StatusCode) -> BoxFuture<()>>>,
+}
+
+impl<T: 'static, Err: ErrorRenderer> RequestScope<T, Err> {
+    /// Create request scope with value factory.
+    pub fn new<F, R, E>(factory: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> R + 'static,
+        R: Future<Output = Result<T, E>> + 'static,
+        E: Into<Err::Container>,
+    {
+        RequestScope {
+            factory: Box::new(move |req| {
+                let fut = factory(req);
+                Box::pin(async move { fut.await.map_err(Into::into) })
+            }),
+            teardown: None,
+        }
+    }
+
+    /// Set teardown function.
+    ///
+    /// Teardown function get called after response is written.
+    /// Teardown is skipped if value is still referenced at that time.
+    pub fn teardown<F, R>(mut self, f: F) -> Self
+    where
+        F: Fn(T, StatusCode) -> R + 'static,
+        R: Future<Output = ()> + 'static,
+    {
+        self.teardown = Some(Rc::new(move |val, status| Box::pin(f(val, status))));
+        self
+    }
+}
+
+/// Request scoped value extractor.
+///
+/// Value is constructed by `RequestScope<T>` factory registered
+/// with `App::request_scope()` method. If request scope is not registered,
+/// using `Scoped<T>` extractor would cause *Internal Server Error* response.
+pub struct Scoped<T>(Rc<T>);
+
+impl<T> Scoped<T> {
+    /// Get reference to inner value.
+    pub fn get_ref(&self) -> &T {
+        self.0.as_ref()
+    }
+}
+
+impl<T> Deref for Scoped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0.as_ref()
+    }
+}
+
+impl<T> Clone for Scoped<T> {
+    fn clone(&self) -> Scoped<T> {
+        Scoped(self.0.clone())
+    }
+}
+
+impl<T: 'static, Err: ErrorRenderer> FromRequest<Err> for Scoped<T>
+where
+    DataExtractorError: Into<Err::Container>,
+{
+    type Error = Err::Container;
+    type Future = BoxFuture<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let slot = req.extensions().get::<Rc<Slot<T, Err>>>().cloned();
+        let slot = if let Some(slot) = slot {
+            slot
+        } else if let Some(scope) = req.app_state::<RequestScope<T, Err>>() {
+            let slot =
+                Rc::new(Slot(RefCell::new(SlotState::Pending((scope.factory)(req)))));
+            let mut ext = req.extensions_mut();
+            ext.insert(slot.clone());
+            if let Some(teardown) = scope.teardown.clone() {
+                if !ext.contains::<ScopeTeardown>() {
+                    ext.insert(ScopeTeardown(Vec::new()));
+                }
+                ext.get_mut::<ScopeTeardown>()
+                    .unwrap()
+                    .0
+                    .push(Box::new(move |req, status| {
+                        let slot = req.extensions_mut().remove::<Rc<Slot<T, Err>>>()?;
+                        let st = slot.0.replace(SlotState::Failed);
+                        if let SlotState::Ready(val) = st {
+                            match Rc::try_unwrap(val) {
+                                Ok(val) => Some(teardown(val, status)),
+                                Err(_) => {
+                                    log::warn!(
+                                        "Request scoped value is still in use, skip teardown"
+                                    );
+                                    None
+                                }
+                            }
+                        } else {
+                            None
+                        }
+                    }));
+            }
+            slot
+        } else {
+            log::debug!(
+                "Failed to construct request scoped value, request scope is not configured. \
+                 Request path: {:?}",
+                req.path()
+            );
+            return Box::pin(async { Err(DataExtractorError::NotConfigured.into()) });
+        };
+
+        Box::pin(crate::util::poll_fn(move |cx| slot.poll(cx)))
+    }
+}
+
+struct Slot<T, Err: ErrorRenderer>(RefCell<SlotState<T, Err>>);
+
+enum SlotState<T, Err: ErrorRenderer> {
+    Pending(BoxFuture<Result<T, Err::Container>>),
+    Ready(Rc<T>),
+    Failed,
+}
+
+impl<T, Err: ErrorRenderer> Slot<T, Err>
+where
+    DataExtractorError: Into<Err::Container>,
+{
+    fn poll(&self, cx: &mut Context<'_>) -> Poll<Result<Scoped<T>, Err::Container>> {
+        let mut st = self.0.borrow_mut();
+        let res = match *st {
+            SlotState::Ready(ref val) => return Poll::Ready(Ok(Scoped(val.clone()))),
+            SlotState::Failed => {
+                return Poll::Ready(Err(DataExtractorError::NotConfigured.into()))
+            }
+            SlotState::Pending(ref mut fut) => match fut.as_mut().poll(cx) {
+                Poll::Ready(res) => res,
+                Poll::Pending => return Poll::Pending,
+            },
+        };
+        match res {
+            Ok(val) => {
+                let val = Rc::new(val);
+                *st = SlotState::Ready(val.clone());
+                Poll::Ready(Ok(Scoped(val)))
+            }
+            Err(e) => {
+                *st = SlotState::Failed;
+                Poll::Ready(Err(e))
+            }
+        }
+    }
+}
+
+/// Teardown functions of request scoped values
+struct ScopeTeardown(Vec<Teardown>);
+
+/// Schedule teardown of request scoped values.
+///
+/// Teardown runs once response body is written and dropped.
+pub(in crate::web) fn teardown(res: WebResponse) -> WebResponse {
+    let teardown = res.request().extensions_mut().remove::<ScopeTeardown>();
+    if let Some(teardown) = teardown {
+        let status = res.status();
+        let futs: Vec<_> = teardown
+            .0
+            .into_iter()
+            .filter_map(|f| f(res.request(), status))
+            .collect();
+        if !futs.is_empty() {
+            return res.map_body(|_, body| {
+                ResponseBody::Other(Body::from_message(TeardownBody { body, futs }))
+            });
+        }
+    }
+    res
+}
+
+struct TeardownBody {
+    body: ResponseBody<Body>,
+    futs: Vec<BoxFuture<()>>,
+}
+
+impl MessageBody for TeardownBody {
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Box<dyn Error>>>> {
+        self.body.poll_next_chunk(cx)
+    }
+}
+
+impl Drop for TeardownBody {
+    fn drop(&mut self) {
+        let futs = std::mem::take(&mut self.futs);
+        crate::rt::spawn(async move {
+            for fut in futs {
+                fut.await
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::time::{sleep, Millis};
+    use crate::web::test::{init_service, read_response, TestRequest};
+    use crate::web::{self, App, HttpResponse};
+
+    #[crate::rt_test]
+    async fn test_request_scope() {
+        let created = Rc::new(Cell::new(0));
+        let teardown = Rc::new(RefCell::new(Vec::new()));
+
+        let c = created.clone();
+        let t = teardown.clone();
+        let srv = init_service(
+            App::new()
+                .request_scope(
+                    RequestScope::new(move |req| {
+                        c.set(c.get() + 1);
+                        let path = req.path().to_string();
+                        async move { Ok::<_, web::Error>(path) }
+                    })
+                    .teardown(move |path, status| {
+                        t.borrow_mut().push((path, status));
+                        async {}
+                    }),
+                )
+                .service(web::resource("/test").to(
+                    |v1: Scoped<String>, v2: Scoped<String>| async move {
+                        assert!(Rc::ptr_eq(&v1.0, &v2.0));
+                        HttpResponse::Ok().body(v1.get_ref().clone())
+                    },
+                ))
+                .service(web::resource("/none").to(|| async { HttpResponse::Ok() }))
+                .service(
+                    web::resource("/error")
+                        .to(|_: Scoped<String>| async { HttpResponse::BadRequest() }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/test").to_request();
+        let body = read_response(&srv, req).await;
+        assert_eq!(body, Bytes::from_static(b"/test"));
+        sleep(Millis(50)).await;
+        assert_eq!(created.get(), 1);
+        assert_eq!(
+            &*teardown.borrow(),
+            &[("/test".to_string(), StatusCode::OK)]
+        );
+
+        // value is not used
+        let req = TestRequest::with_uri("/none").to_request();
+        let _ = read_response(&srv, req).await;
+        assert_eq!(created.get(), 1);
+
+        let req = TestRequest::with_uri("/error").to_request();
+        let _ = read_response(&srv, req).await;
+        sleep(Millis(50)).await;
+        assert_eq!(created.get(), 2);
+        assert_eq!(
+            teardown.borrow()[1],
+            ("/error".to_string(), StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[crate::rt_test]
+    async fn test_request_scope_not_configured() {
+        let srv = init_service(App::new().service(
+            web::resource("/").to(|_: Scoped<String>| async { HttpResponse::Ok() }),
+        ))
+        .await;
+
+        let req = TestRequest::default().to_request();
+        let res = crate::web::test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -1,8 +1,8 @@
-use std::{ops::Deref, sync::Arc};
+use std::{marker::PhantomData, ops::Deref, sync::Arc};
 
 use crate::http::Payload;
 use crate::util::{Extensions, Ready};
-use crate::web::error::{DataExtractorError, ErrorRenderer};
+use crate::web::error::{DataExtractorError, DefaultError, ErrorRenderer};
 use crate::web::extract::FromRequest;
 use crate::web::httprequest::HttpRequest;
 
@@ -116,6 +116,114 @@ impl<T: 'static, E: ErrorRenderer> FromRequest<E> for State<T> {
     }
 }
 
+/// Error renderer of the application with typed state.
+///
+/// It wraps actual error renderer and carries type of the application
+/// state. Application with typed state is created with `App::with_state()`.
+pub struct WithState<S, Err = DefaultError> {
+    err: Err,
+    _t: PhantomData<fn() -> S>,
+}
+
+impl<S, Err> WithState<S, Err> {
+    pub(crate) fn new(err: Err) -> Self {
+        WithState {
+            err,
+            _t: PhantomData,
+        }
+    }
+
+    /// Get reference to inner error renderer
+    pub fn renderer(&self) -> &Err {
+        &self.err
+    }
+}
+
+impl<S: 'static, Err: ErrorRenderer> ErrorRenderer for WithState<S, Err> {
+    type Container = Err::Container;
+}
+
+/// Typed application state.
+///
+/// `TypedState<S>` extractor is available only for applications created
+/// with `App::with_state()` method, state type is checked at compile time.
+/// Handlers that use typed state could not be registered in application
+/// with different state type.
+///
+/// ```rust
+/// use std::cell::Cell;
+/// use ntex::web::{self, types::TypedState, App, HttpResponse};
+///
+/// struct MyState {
+///     counter: Cell<usize>,
+/// }
+///
+/// async fn index(st: TypedState<MyState>) -> HttpResponse {
+///     st.counter.set(st.counter.get() + 1);
+///     HttpResponse::Ok().into()
+/// }
+///
+/// let app = App::with_state(MyState { counter: Cell::new(0) })
+///     .service(web::resource("/index.html").route(web::get().to(index)));
+/// ```
+///
+/// Application without state or with different state type does not compile.
+///
+/// ```rust,compile_fail
+/// use ntex::web::{self, types::TypedState, App, HttpResponse};
+///
+/// async fn index(st: TypedState<String>) -> HttpResponse {
+///     HttpResponse::Ok().into()
+/// }
+///
+/// let app = App::with_state(10usize)
+///     .service(web::resource("/index.html").route(web::get().to(index)));
+/// ```
+#[derive(Debug)]
+pub struct TypedState<S>(State<S>);
+
+impl<S> TypedState<S> {
+    /// Get reference to inner app state.
+    pub fn get_ref(&self) -> &S {
+        self.0.get_ref()
+    }
+
+    /// Convert to the `State<S>`
+    pub fn into_inner(self) -> State<S> {
+        self.0
+    }
+}
+
+impl<S> Deref for TypedState<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        self.0.get_ref()
+    }
+}
+
+impl<S> Clone for TypedState<S> {
+    fn clone(&self) -> TypedState<S> {
+        TypedState(self.0.clone())
+    }
+}
+
+impl<S: 'static, Err: ErrorRenderer> FromRequest<WithState<S, Err>> for TypedState<S> {
+    type Error = DataExtractorError;
+    type Future = Ready<Self, Self::Error>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // state is registered by `App::with_state()`, lookup could fail
+        // only for requests that are constructed manually
+        if let Some(st) = req.app_state::<State<S>>() {
+            Ready::Ok(TypedState(st.clone()))
+        } else {
+            Ready::Err(DataExtractorError::NotConfigured)
+        }
+    }
+}
+
 impl<T: 'static> StateFactory for State<T> {
     fn create(&self, extensions: &mut Extensions) -> bool {
         if !extensions.contains::<State<T>>() {
@@ -163,6 +271,36 @@ mod tests {
         assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[crate::rt_test]
+    async fn test_typed_state_extractor() {
+        let srv = init_service(
+            App::with_state("TEST".to_string())
+                .state(10usize)
+                .service(web::resource("/").to(
+                    |st: web::types::TypedState<String>, num: State<usize>| async move {
+                        assert_eq!(st.to_lowercase(), "test");
+                        assert_eq!(**num, 10);
+                        HttpResponse::Ok()
+                    },
+                ))
+                .service(web::scope("/scope").service(web::resource("/").to(
+                    |st: web::types::State<String>| async move {
+                        assert_eq!(st.as_str(), "TEST");
+                        HttpResponse::Ok()
+                    },
+                ))),
+        )
+        .await;
+
+        let req = TestRequest::default().to_request();
+        let resp = srv.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = TestRequest::with_uri("/scope/").to_request();
+        let resp = srv.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
     #[crate::rt_test]
     async fn test_app_data_extractor() {
         let srv = init_service(
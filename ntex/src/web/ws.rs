@@ -1,15 +1,109 @@
 //! WebSockets protocol support
-use std::fmt;
+use std::time::{Duration, Instant};
+use std::{cell::Cell, fmt, future::Future, pin::Pin, rc::Rc};
 
 pub use crate::ws::{CloseCode, CloseReason, Frame, Message, WsSink};
 
-use crate::http::{body::BodySize, h1, StatusCode};
+use crate::http::{body::BodySize, h1, header, StatusCode};
 use crate::service::{
-    apply_fn, fn_factory_with_config, IntoServiceFactory, Service, ServiceFactory,
+    apply_fn, fn_factory_with_config, fn_service, IntoServiceFactory, Service,
+    ServiceFactory,
 };
+use crate::time::{now, sleep, Seconds};
 use crate::web::{HttpRequest, HttpResponse};
 use crate::ws::{error::HandshakeError, error::WsError, handshake};
-use crate::{io::DispatchItem, rt, util::Either, util::Ready, ws};
+use crate::{
+    io::DispatchItem, rt, util::select, util::Bytes, util::Either, util::Ready, ws,
+};
+
+/// Websocket connection handler.
+///
+/// Handler is used with [`start_handler`], returned messages are sent to the peer.
+/// Handler sends heartbeat pings and closes connection if peer does not
+/// send any frames within client timeout.
+///
+/// ```rust
+/// use ntex::web::{self, ws, HttpRequest, HttpResponse};
+///
+/// struct Echo;
+///
+/// impl ws::WsHandler for Echo {
+///     type Error = web::Error;
+///
+///     async fn on_message(
+///         &self,
+///         frame: ws::Frame,
+///         _: &ws::WsSink,
+///     ) -> Result<Option<ws::Message>, web::Error> {
+///         Ok(match frame {
+///             ws::Frame::Text(text) => Some(ws::Message::Text(
+///                 String::from_utf8_lossy(&text).as_ref().into(),
+///             )),
+///             ws::Frame::Binary(bin) => Some(ws::Message::Binary(bin)),
+///             _ => None,
+///         })
+///     }
+/// }
+///
+/// async fn index(req: HttpRequest) -> Result<HttpResponse, web::Error> {
+///     ws::start_handler(req, Echo).await
+/// }
+/// ```
+pub trait WsHandler: 'static {
+    /// Handler error
+    type Error: fmt::Debug;
+
+    /// Heartbeat ping interval, zero value disables pings.
+    ///
+    /// By default interval is set to 5 seconds.
+    fn heartbeat_interval(&self) -> Seconds {
+        Seconds(5)
+    }
+
+    /// Max time between frames received from the peer, zero value
+    /// disables timeout.
+    ///
+    /// By default timeout is set to 10 seconds.
+    fn client_timeout(&self) -> Seconds {
+        Seconds(10)
+    }
+
+    /// Handle text, binary and continuation frames
+    fn on_message(
+        &self,
+        frame: Frame,
+        sink: &WsSink,
+    ) -> impl Future<Output = Result<Option<Message>, Self::Error>>;
+
+    /// Handle ping frame, by default responds with pong
+    fn on_ping(
+        &self,
+        payload: Bytes,
+        _: &WsSink,
+    ) -> impl Future<Output = Result<Option<Message>, Self::Error>> {
+        async move { Ok(Some(Message::Pong(payload))) }
+    }
+
+    /// Handle pong frame
+    fn on_pong(
+        &self,
+        _: Bytes,
+        _: &WsSink,
+    ) -> impl Future<Output = Result<Option<Message>, Self::Error>> {
+        async move { Ok(None) }
+    }
+
+    /// Handle close frame, by default responds with the same close reason.
+    ///
+    /// Connection get closed after response is sent.
+    fn on_close(
+        &self,
+        reason: Option<CloseReason>,
+        _: &WsSink,
+    ) -> impl Future<Output = Result<Option<Message>, Self::Error>> {
+        async move { Ok(Some(Message::Close(reason))) }
+    }
+}
 
 /// Do websocket handshake and start websockets service.
 pub async fn start<T, F, Err>(req: HttpRequest, factory: F) -> Result<HttpResponse, Err>
@@ -60,11 +154,268 @@ where
     start_with(req, factory).await
 }
 
+/// Do websocket handshake and start websockets handler.
+pub async fn start_handler<H, Err>(
+    req: HttpRequest,
+    handler: H,
+) -> Result<HttpResponse, Err>
+where
+    H: WsHandler,
+    Err: From<HandshakeError>,
+{
+    Ok(start_handler_with(req, Rc::new(handler), None).await?)
+}
+
+/// Start websockets handler, negotiated sub-protocol is sent to the peer
+async fn start_handler_with<H: WsHandler>(
+    req: HttpRequest,
+    handler: Rc<H>,
+    protocol: Option<Rc<str>>,
+) -> Result<HttpResponse, HandshakeError> {
+    let factory = fn_factory_with_config(move |sink: WsSink| {
+        let handler = handler.clone();
+        let activity = Rc::new(Cell::new(now()));
+
+        // start heartbeat task
+        let interval = handler.heartbeat_interval();
+        let timeout = handler.client_timeout();
+        if !interval.is_zero() || !timeout.is_zero() {
+            rt::spawn(heartbeat(sink.clone(), activity.clone(), interval, timeout));
+        }
+
+        Ready::<_, HandshakeError>::Ok(fn_service(move |req| {
+            let handler = handler.clone();
+            let sink = sink.clone();
+            activity.set(now());
+
+            async move {
+                let item = match req {
+                    DispatchItem::Item(item) => item,
+                    DispatchItem::WBackPressureEnabled
+                    | DispatchItem::WBackPressureDisabled => return Ok(None),
+                    DispatchItem::KeepAliveTimeout => return Err(WsError::KeepAlive),
+                    DispatchItem::DecoderError(e) | DispatchItem::EncoderError(e) => {
+                        return Err(WsError::Protocol(e))
+                    }
+                    DispatchItem::Disconnect(e) => return Err(WsError::Disconnected(e)),
+                };
+
+                let result = match item {
+                    Frame::Ping(payload) => handler.on_ping(payload, &sink).await,
+                    Frame::Pong(payload) => handler.on_pong(payload, &sink).await,
+                    Frame::Close(reason) => {
+                        let result = handler.on_close(reason, &sink).await;
+                        rt::spawn(async move { sink.io().close() });
+                        result
+                    }
+                    frame => handler.on_message(frame, &sink).await,
+                };
+                result.map_err(WsError::Service)
+            }
+        }))
+    });
+
+    start_with_protocol(req, factory, protocol.as_deref()).await
+}
+
+type StartFn = Box<
+    dyn Fn(HttpRequest, Option<Rc<str>>) -> BoxFuture<Result<HttpResponse, HandshakeError>>,
+>;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+/// Websocket sub-protocols router.
+///
+/// Router negotiates sub-protocol with `Sec-WebSocket-Protocol` header during
+/// handshake and starts handler registered for selected sub-protocol.
+/// Sub-protocols are selected in order of client's preference. If none of
+/// requested sub-protocols is registered, default handler is started without
+/// sub-protocol, or handshake fails with `HandshakeError::UnsupportedProtocol`
+/// error if default handler is not set.
+///
+/// ```rust
+/// use ntex::web::{self, ws, App};
+///
+/// struct GraphQl;
+/// struct Chat;
+///
+/// impl ws::WsHandler for GraphQl {
+///     type Error = web::Error;
+///
+///     async fn on_message(
+///         &self,
+///         _: ws::Frame,
+///         _: &ws::WsSink,
+///     ) -> Result<Option<ws::Message>, web::Error> {
+///         Ok(None)
+///     }
+/// }
+///
+/// impl ws::WsHandler for Chat {
+///     type Error = web::Error;
+///
+///     async fn on_message(
+///         &self,
+///         frame: ws::Frame,
+///         _: &ws::WsSink,
+///     ) -> Result<Option<ws::Message>, web::Error> {
+///         Ok(match frame {
+///             ws::Frame::Text(text) => Some(ws::Message::Text(
+///                 String::from_utf8_lossy(&text).as_ref().into(),
+///             )),
+///             _ => None,
+///         })
+///     }
+/// }
+///
+/// fn main() {
+///     let router = ws::WsRouter::new()
+///         .protocol("graphql-transport-ws", GraphQl)
+///         .protocol("chat.v1", Chat);
+///
+///     let app = App::new().service(web::resource("/ws").to(move |req: web::HttpRequest| {
+///         router.start::<web::Error>(req)
+///     }));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct WsRouter {
+    protocols: Rc<Vec<(Rc<str>, StartFn)>>,
+    default: Option<Rc<StartFn>>,
+}
+
+impl Default for WsRouter {
+    fn default() -> Self {
+        WsRouter {
+            protocols: Rc::new(Vec::new()),
+            default: None,
+        }
+    }
+}
+
+impl WsRouter {
+    /// Create empty router
+    pub fn new() -> Self {
+        WsRouter::default()
+    }
+
+    /// Register handler for sub-protocol.
+    ///
+    /// Sub-protocol names are case-sensitive.
+    pub fn protocol<H: WsHandler>(mut self, name: &str, handler: H) -> Self {
+        let handler = Rc::new(handler);
+        Rc::get_mut(&mut self.protocols)
+            .expect("Router is already in use")
+            .push((name.into(), start_fn(handler)));
+        self
+    }
+
+    /// Set handler for connections without supported sub-protocol
+    pub fn default_handler<H: WsHandler>(mut self, handler: H) -> Self {
+        self.default = Some(Rc::new(start_fn(Rc::new(handler))));
+        self
+    }
+
+    /// Select sub-protocol for request
+    pub fn negotiate(&self, req: &HttpRequest) -> Option<&str> {
+        self.select(req).map(|idx| self.protocols[idx].0.as_ref())
+    }
+
+    fn select(&self, req: &HttpRequest) -> Option<usize> {
+        req.headers()
+            .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+            .filter_map(|val| val.to_str().ok())
+            .flat_map(|val| val.split(','))
+            .map(|name| name.trim())
+            .find_map(|name| {
+                self.protocols
+                    .iter()
+                    .position(|(proto, _)| proto.as_ref() == name)
+            })
+    }
+
+    /// Do websocket handshake and start handler of negotiated sub-protocol
+    pub fn start<Err>(
+        &self,
+        req: HttpRequest,
+    ) -> impl Future<Output = Result<HttpResponse, Err>>
+    where
+        Err: From<HandshakeError>,
+    {
+        let fut = if let Some(idx) = self.select(&req) {
+            let (ref name, ref f) = self.protocols[idx];
+            log::trace!("Ws sub-protocol {:?} is selected", name);
+            Either::Left(f(req, Some(name.clone())))
+        } else if let Some(ref f) = self.default {
+            Either::Left(f(req, None))
+        } else {
+            Either::Right(Ready::Err(HandshakeError::UnsupportedProtocol))
+        };
+        async move { Ok(fut.await?) }
+    }
+}
+
+fn start_fn<H: WsHandler>(handler: Rc<H>) -> StartFn {
+    Box::new(move |req, protocol| {
+        Box::pin(start_handler_with(req, handler.clone(), protocol))
+    })
+}
+
+/// Send heartbeat pings and check peer activity
+async fn heartbeat(
+    sink: WsSink,
+    activity: Rc<Cell<Instant>>,
+    interval: Seconds,
+    timeout: Seconds,
+) {
+    let period = if interval.is_zero() {
+        timeout
+    } else if timeout.is_zero() {
+        interval
+    } else {
+        std::cmp::min(interval, timeout)
+    };
+
+    let mut last_ping = now();
+    loop {
+        if let Either::Right(_) = select(sleep(period), sink.on_disconnect()).await {
+            break;
+        }
+
+        if !timeout.is_zero() && now() - activity.get() >= Duration::from(timeout) {
+            log::trace!("Ws client timeout, closing connection");
+            sink.io().close();
+            break;
+        }
+        if !interval.is_zero() && now() - last_ping >= Duration::from(interval) {
+            last_ping = now();
+            if sink.send(Message::Ping(Bytes::new())).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
 /// Do websocket handshake and start websockets service.
 pub async fn start_with<T, F, Err>(
     req: HttpRequest,
     factory: F,
 ) -> Result<HttpResponse, Err>
+where
+    T: ServiceFactory<DispatchItem<ws::Codec>, WsSink, Response = Option<Message>>
+        + 'static,
+    T::Error: fmt::Debug,
+    F: IntoServiceFactory<T, DispatchItem<ws::Codec>, WsSink>,
+    Err: From<T::InitError> + From<HandshakeError>,
+{
+    start_with_protocol(req, factory, None).await
+}
+
+async fn start_with_protocol<T, F, Err>(
+    req: HttpRequest,
+    factory: F,
+    protocol: Option<&str>,
+) -> Result<HttpResponse, Err>
 where
     T: ServiceFactory<DispatchItem<ws::Codec>, WsSink, Response = Option<Message>>
         + 'static,
@@ -75,7 +426,11 @@ where
     log::trace!("Start ws handshake verification for {:?}", req.path());
 
     // ws handshake
-    let res = handshake(req.head())?.finish().into_parts().0;
+    let mut res = handshake(req.head())?;
+    if let Some(protocol) = protocol {
+        res.header(header::SEC_WEBSOCKET_PROTOCOL, protocol);
+    }
+    let res = res.finish().into_parts().0;
 
     // extract io
     let item = req
@@ -131,6 +131,7 @@ where
     /// Complete request construction and connect to a websockets server.
     pub fn connect(&self) -> impl Future<Output = Result<WsConnection<F>, WsClientError>> {
         let head = self.head.clone();
+        let protocols = head.headers.get(&header::SEC_WEBSOCKET_PROTOCOL).cloned();
         let max_size = self.max_size;
         let server_mode = self.server_mode;
         let to = self.timeout;
@@ -240,6 +241,23 @@ where
                 log::trace!("Missing SEC-WEBSOCKET-ACCEPT header");
                 return Err(WsClientError::MissingWebSocketAcceptHeader);
             };
+
+            // Check selected sub-protocol
+            if let Some(proto) = response.headers.get(&header::SEC_WEBSOCKET_PROTOCOL) {
+                let requested = protocols
+                    .as_ref()
+                    .and_then(|protos| protos.to_str().ok())
+                    .map(|protos| {
+                        protos
+                            .split(',')
+                            .any(|p| p.trim().as_bytes() == proto.as_bytes())
+                    })
+                    .unwrap_or(false);
+                if !requested {
+                    log::trace!("Server selected sub-protocol was not requested");
+                    return Err(WsClientError::InvalidProtocol(proto.clone()));
+                }
+            }
             log::trace!("Ws handshake response verification is completed");
 
             // response and ws io
@@ -304,6 +322,64 @@ impl WsClientBuilder<Base, ()> {
     }
 }
 
+impl WsClientBuilder<Base, Connector<Uri>> {
+    /// Complete client construction and connect to a websockets server.
+    ///
+    /// Tls connector is used for `wss` urls, openssl or rustls
+    /// depending on enabled features.
+    pub async fn connect(&mut self) -> Result<WsConnection<Sealed>, WsClientError> {
+        let secure = parts(&mut self.inner, &self.err)
+            .map(|parts| matches!(parts.head.uri.scheme_str(), Some("wss" | "https")))
+            .unwrap_or(false);
+
+        if secure {
+            #[cfg(feature = "openssl")]
+            {
+                let mut ssl = openssl::SslConnector::builder(openssl::SslMethod::tls())
+                    .map_err(|e| {
+                        ConnectError::Io(std::io::Error::new(std::io::ErrorKind::Other, e))
+                    })?;
+                let _ = ssl.set_alpn_protos(b"\x08http/1.1");
+                let client = self.openssl(ssl.build()).finish()?;
+                Ok(client.connect().await?.seal())
+            }
+            #[cfg(all(not(feature = "openssl"), feature = "rustls"))]
+            {
+                use tls_rustls::{OwnedTrustAnchor, RootCertStore};
+
+                let mut cert_store = RootCertStore::empty();
+                cert_store.add_server_trust_anchors(
+                    webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+                        OwnedTrustAnchor::from_subject_spki_name_constraints(
+                            ta.subject,
+                            ta.spki,
+                            ta.name_constraints,
+                        )
+                    }),
+                );
+                let mut config = rustls::ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(cert_store)
+                    .with_no_client_auth();
+                config.alpn_protocols = vec![b"http/1.1".to_vec()];
+                let client = self.rustls(std::sync::Arc::new(config)).finish()?;
+                Ok(client.connect().await?.seal())
+            }
+            #[cfg(not(any(feature = "openssl", feature = "rustls")))]
+            {
+                Err(ConnectError::Io(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Tls connector is not available",
+                ))
+                .into())
+            }
+        } else {
+            let client = self.finish()?;
+            Ok(client.connect().await?.seal())
+        }
+    }
+}
+
 impl<F, T> WsClientBuilder<F, T>
 where
     T: Service<Connect<Uri>, Response = Io<F>, Error = ConnectError>,
@@ -677,6 +753,14 @@ impl<F> WsConnection<F> {
     pub fn response(&self) -> &ClientResponse {
         &self.res
     }
+
+    /// Sub-protocol selected by the server
+    pub fn protocol(&self) -> Option<&str> {
+        self.res
+            .headers()
+            .get(&header::SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|proto| proto.to_str().ok())
+    }
 }
 
 impl<F> WsConnection<F> {
@@ -1,6 +1,7 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 
 use crate::codec::{Decoder, Encoder};
+use crate::http::{budget::Reservation, MemoryBudget};
 use crate::util::{ByteString, Bytes, BytesMut};
 
 use super::error::ProtocolError;
@@ -55,6 +56,9 @@ pub enum Item {
 pub struct Codec {
     flags: Cell<Flags>,
     max_size: usize,
+    max_message_size: usize,
+    message: RefCell<BytesMut>,
+    reservation: Option<Reservation>,
 }
 
 bitflags::bitflags! {
@@ -63,6 +67,8 @@ bitflags::bitflags! {
         const R_CONTINUATION = 0b0000_0010;
         const W_CONTINUATION = 0b0000_0100;
         const CLOSED         = 0b0000_1000;
+        const AGGREGATE      = 0b0001_0000;
+        const R_TEXT         = 0b0010_0000;
     }
 }
 
@@ -71,7 +77,10 @@ impl Codec {
     pub fn new() -> Codec {
         Codec {
             max_size: 65_536,
+            max_message_size: 0,
+            message: RefCell::new(BytesMut::new()),
             flags: Cell::new(Flags::SERVER),
+            reservation: None,
         }
     }
 
@@ -83,6 +92,28 @@ impl Codec {
         self
     }
 
+    /// Enable continuation frames aggregation.
+    ///
+    /// Fragmented text and binary messages are combined into complete
+    /// `Frame::Text` and `Frame::Binary` frames. Decoder fails with
+    /// `ProtocolError::Overflow` error if message size exceeds `max_size`.
+    ///
+    /// By default aggregation is disabled.
+    pub fn aggregate_continuations(mut self, max_size: usize) -> Self {
+        self.max_message_size = max_size;
+        self.insert_flags(Flags::AGGREGATE);
+        self
+    }
+
+    /// Register aggregation buffer against global memory budget.
+    ///
+    /// Decoder fails with `ProtocolError::Overflow` error if budget
+    /// is exceeded.
+    pub fn memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.reservation = Some(Reservation::new(usize::MAX, Some(budget)));
+        self
+    }
+
     /// Set decoder to client mode.
     ///
     /// By default decoder works in server mode.
@@ -107,6 +138,55 @@ impl Codec {
         flags.remove(f);
         self.flags.set(flags);
     }
+
+    fn release(&self) {
+        if let Some(ref reservation) = self.reservation {
+            reservation.clear();
+        }
+    }
+
+    /// Add continuation frame to the message
+    fn aggregate(&self, item: Item) -> Result<Option<Frame>, ProtocolError> {
+        let mut message = self.message.borrow_mut();
+        let (data, last) = match item {
+            Item::FirstText(data) => {
+                self.insert_flags(Flags::R_TEXT);
+                (data, false)
+            }
+            Item::FirstBinary(data) => {
+                self.remove_flags(Flags::R_TEXT);
+                (data, false)
+            }
+            Item::Continue(data) => (data, false),
+            Item::Last(data) => (data, true),
+        };
+
+        if message.len() + data.len() > self.max_message_size {
+            message.clear();
+            self.release();
+            return Err(ProtocolError::Overflow);
+        }
+        if let Some(ref reservation) = self.reservation {
+            if !reservation.charge(data.len()) {
+                message.clear();
+                reservation.clear();
+                return Err(ProtocolError::Overflow);
+            }
+        }
+        message.extend_from_slice(&data);
+
+        if last {
+            self.release();
+            let data = message.split().freeze();
+            if self.flags.get().contains(Flags::R_TEXT) {
+                Ok(Some(Frame::Text(data)))
+            } else {
+                Ok(Some(Frame::Binary(data)))
+            }
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 impl Default for Codec {
@@ -220,6 +300,25 @@ impl Decoder for Codec {
     type Error = ProtocolError;
 
     fn decode(&self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if !self.flags.get().contains(Flags::AGGREGATE) {
+            return self.decode_frame(src);
+        }
+
+        loop {
+            match self.decode_frame(src)? {
+                Some(Frame::Continuation(item)) => {
+                    if let Some(frame) = self.aggregate(item)? {
+                        return Ok(Some(frame));
+                    }
+                }
+                item => return Ok(item),
+            }
+        }
+    }
+}
+
+impl Codec {
+    fn decode_frame(&self, src: &mut BytesMut) -> Result<Option<Frame>, ProtocolError> {
         match Parser::parse(src, self.flags.get().contains(Flags::SERVER), self.max_size) {
             Ok(Some((finished, opcode, payload))) => {
                 // handle continuation
@@ -307,3 +406,129 @@ impl Decoder for Codec {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(items: Vec<Message>) -> BytesMut {
+        let codec = Codec::new().client_mode();
+        let mut buf = BytesMut::new();
+        for item in items {
+            codec.encode(item, &mut buf).unwrap();
+        }
+        buf
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut buf = encode(vec![
+            Message::Continuation(Item::FirstText(Bytes::from_static(b"Hello"))),
+            Message::Ping(Bytes::from_static(b"ping")),
+            Message::Continuation(Item::Continue(Bytes::from_static(b", "))),
+            Message::Continuation(Item::Last(Bytes::from_static(b"World"))),
+            Message::Continuation(Item::FirstBinary(Bytes::from_static(b"1"))),
+            Message::Continuation(Item::Last(Bytes::from_static(b"2"))),
+            Message::Binary(Bytes::from_static(b"3")),
+        ]);
+
+        let codec = Codec::new().aggregate_continuations(64);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Frame::Ping(Bytes::from_static(b"ping")))
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Frame::Text(Bytes::from_static(b"Hello, World")))
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Frame::Binary(Bytes::from_static(b"12")))
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Frame::Binary(Bytes::from_static(b"3")))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn test_aggregate_partial() {
+        let buf = encode(vec![
+            Message::Continuation(Item::FirstBinary(Bytes::from_static(b"1"))),
+            Message::Continuation(Item::Last(Bytes::from_static(b"2"))),
+        ]);
+
+        let codec = Codec::new().aggregate_continuations(64);
+        let mut src = BytesMut::new();
+        for b in &buf[..buf.len() - 1] {
+            src.extend_from_slice(&[*b]);
+            assert_eq!(codec.decode(&mut src).unwrap(), None);
+        }
+        src.extend_from_slice(&buf[buf.len() - 1..]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Frame::Binary(Bytes::from_static(b"12")))
+        );
+    }
+
+    #[test]
+    fn test_aggregate_overflow() {
+        let mut buf = encode(vec![
+            Message::Continuation(Item::FirstText(Bytes::from_static(b"Hello"))),
+            Message::Continuation(Item::Last(Bytes::from_static(b", World"))),
+        ]);
+
+        let codec = Codec::new().aggregate_continuations(10);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(ProtocolError::Overflow)
+        ));
+
+        // aggregation is disabled
+        let mut buf = encode(vec![Message::Continuation(Item::FirstText(
+            Bytes::from_static(b"Hello"),
+        ))]);
+        assert_eq!(
+            Codec::new().decode(&mut buf).unwrap(),
+            Some(Frame::Continuation(Item::FirstText(Bytes::from_static(
+                b"Hello"
+            ))))
+        );
+    }
+
+    #[test]
+    fn test_aggregate_memory_budget() {
+        let budget = MemoryBudget::new(8);
+        let codec = Codec::new()
+            .aggregate_continuations(64)
+            .memory_budget(budget.clone());
+
+        let first_len = encode(vec![Message::Continuation(Item::FirstBinary(
+            Bytes::from_static(b"123"),
+        ))])
+        .len();
+        let mut buf = encode(vec![
+            Message::Continuation(Item::FirstBinary(Bytes::from_static(b"123"))),
+            Message::Continuation(Item::Last(Bytes::from_static(b"45"))),
+        ]);
+        let mut src = buf.split_to(first_len);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(budget.used(), 3);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Frame::Binary(Bytes::from_static(b"12345")))
+        );
+        assert_eq!(budget.used(), 0);
+
+        let mut buf = encode(vec![
+            Message::Continuation(Item::FirstText(Bytes::from_static(b"Hello"))),
+            Message::Continuation(Item::Last(Bytes::from_static(b", World"))),
+        ]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(ProtocolError::Overflow)
+        ));
+        assert_eq!(budget.used(), 0);
+    }
+}
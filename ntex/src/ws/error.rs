@@ -94,6 +94,12 @@ pub enum WsClientError {
     /// Invalid challenge response
     #[error("Invalid challenge response")]
     InvalidChallengeResponse(String, HeaderValue),
+    /// Server selected sub-protocol that was not requested
+    #[error("Invalid sub-protocol: {0:?}")]
+    InvalidProtocol(HeaderValue),
+    /// Client builder error
+    #[error("{0}")]
+    Builder(#[from] WsClientBuilderError),
     /// Protocol error
     #[error("{0}")]
     Protocol(#[from] ProtocolError),
@@ -144,6 +150,9 @@ pub enum HandshakeError {
     /// Websocket key is not set or wrong
     #[error("Unknown websocket key")]
     BadWebsocketKey,
+    /// None of requested sub-protocols is supported
+    #[error("Unsupported websocket sub-protocol")]
+    UnsupportedProtocol,
 }
 
 impl ResponseError for HandshakeError {
@@ -167,6 +176,9 @@ impl ResponseError for HandshakeError {
             HandshakeError::BadWebsocketKey => {
                 Response::BadRequest().reason("Handshake error").finish()
             }
+            HandshakeError::UnsupportedProtocol => Response::BadRequest()
+                .reason("Unsupported sub-protocol")
+                .finish(),
         }
     }
 }
@@ -114,6 +114,52 @@ async fn test_openssl_string() {
     assert_eq!(item, Bytes::from_static(b"test"));
 }
 
+#[cfg(feature = "openssl")]
+#[ntex::test]
+async fn test_openssl_alpn() {
+    use ntex::server::openssl;
+    use ntex_tls::types::{AlpnProtocol, HttpProtocol};
+    use tls_openssl::ssl::{self, SslConnector, SslMethod, SslVerifyMode};
+
+    let srv = test_server(|| {
+        let mut builder = ssl::SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        builder
+            .set_private_key_file("./tests/key.pem", ssl::SslFiletype::PEM)
+            .unwrap();
+        builder
+            .set_certificate_chain_file("./tests/cert.pem")
+            .unwrap();
+        builder.set_alpn_select_callback(|_, protos| {
+            ssl::select_next_proto(b"\x02h2", protos).ok_or(ssl::AlpnError::NOACK)
+        });
+
+        pipeline_factory(openssl::Acceptor::new(builder.build())).and_then(fn_service(
+            |io: Io<_>| async move {
+                assert_eq!(
+                    io.query::<AlpnProtocol>().as_ref().unwrap().as_bytes(),
+                    b"h2"
+                );
+                Ok::<_, Box<dyn std::error::Error>>(())
+            },
+        ))
+    });
+
+    let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+    builder.set_verify(SslVerifyMode::NONE);
+    builder.set_alpn_protos(b"\x02h2\x08http/1.1").unwrap();
+
+    let conn = ntex::connect::openssl::Connector::new(builder.build());
+    let addr = format!("127.0.0.1:{}", srv.addr().port());
+    let io = conn.call(addr.into()).await.unwrap();
+    let proto = io.query::<AlpnProtocol>().as_ref().unwrap().clone();
+    assert_eq!(proto, AlpnProtocol(b"h2".to_vec()));
+    assert_eq!(proto.http_protocol(), HttpProtocol::Http2);
+    assert_eq!(
+        io.query::<HttpProtocol>().get().unwrap(),
+        HttpProtocol::Http2
+    );
+}
+
 #[cfg(feature = "openssl")]
 #[ntex::test]
 async fn test_openssl_read_before_error() {
@@ -151,11 +197,197 @@ async fn test_openssl_read_before_error() {
     assert!(io.recv(&BytesCodec).await.unwrap().is_none());
 }
 
+#[cfg(feature = "openssl")]
+#[ntex::test]
+async fn test_openssl_sni_reload() {
+    use ntex::server::openssl;
+    use std::sync::Mutex;
+    use tls_openssl::ssl::{self, SslConnector, SslMethod, SslVerifyMode};
+
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let names2 = names.clone();
+    let sni = ssl_acceptor();
+    let mut builder = ssl::SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    openssl::set_sni_resolver(&mut builder, move |name| {
+        names2.lock().unwrap().push(name.to_string());
+        Some(sni.context().to_owned())
+    });
+    let handle = openssl::AcceptorHandle::new(builder.build());
+
+    let h = handle.clone();
+    let srv = test_server(move || {
+        pipeline_factory(openssl::Acceptor::from(h.clone())).and_then(fn_service(
+            |io: Io<_>| async move {
+                io.send(Bytes::from_static(b"test"), &BytesCodec)
+                    .await
+                    .unwrap();
+                Ok::<_, Box<dyn std::error::Error>>(())
+            },
+        ))
+    });
+
+    let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+    builder.set_verify(SslVerifyMode::NONE);
+    let conn = ntex::connect::openssl::Connector::new(builder.build());
+    let req = || Connect::new("localhost".to_string()).set_addr(Some(srv.addr()));
+
+    let io = conn.call(req()).await.unwrap();
+    let item = io.recv(&BytesCodec).await.unwrap().unwrap();
+    assert_eq!(item, Bytes::from_static(b"test"));
+    assert_eq!(names.lock().unwrap().as_slice(), ["localhost"]);
+
+    // acceptor without certificates
+    handle.reload(
+        ssl::SslAcceptor::mozilla_intermediate(SslMethod::tls())
+            .unwrap()
+            .build(),
+    );
+    assert!(conn.call(req()).await.is_err());
+
+    handle.reload(ssl_acceptor());
+    let io = conn.call(req()).await.unwrap();
+    let item = io.recv(&BytesCodec).await.unwrap().unwrap();
+    assert_eq!(item, Bytes::from_static(b"test"));
+}
+
+#[cfg(feature = "openssl")]
+#[ntex::test]
+async fn test_openssl_client_auth() {
+    use ntex::server::openssl;
+    use ntex_tls::types::PeerCertificates;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tls_openssl::ssl::{self, SslConnector, SslMethod, SslVerifyMode};
+    use tls_openssl::x509::X509;
+
+    let cert = X509::from_pem(include_bytes!("cert.pem")).unwrap();
+    let der = cert.to_der().unwrap();
+    let checked = Arc::new(AtomicBool::new(false));
+    let checked2 = checked.clone();
+
+    let srv = test_server(move || {
+        let mut builder = ssl::SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        builder
+            .set_private_key_file("./tests/key.pem", ssl::SslFiletype::PEM)
+            .unwrap();
+        builder
+            .set_certificate_chain_file("./tests/cert.pem")
+            .unwrap();
+        let checked = checked2.clone();
+        openssl::ClientAuth::new()
+            .ca_pem(include_bytes!("cert.pem"))
+            .unwrap()
+            .revocation_check(move |certs| {
+                checked.store(true, Ordering::Relaxed);
+                !certs.is_empty()
+            })
+            .configure(&mut builder)
+            .unwrap();
+
+        let der = der.clone();
+        pipeline_factory(openssl::Acceptor::new(builder.build())).and_then(fn_service(
+            move |io: Io<_>| {
+                let der = der.clone();
+                async move {
+                    let certs = io.query::<PeerCertificates>().as_ref().cloned().unwrap();
+                    assert_eq!(certs.peer_certificate(), Some(der.as_slice()));
+                    io.send(Bytes::from_static(b"test"), &BytesCodec)
+                        .await
+                        .unwrap();
+                    Ok::<_, Box<dyn std::error::Error>>(())
+                }
+            },
+        ))
+    });
+    let addr = format!("127.0.0.1:{}", srv.addr().port());
+
+    // client certificate
+    let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+    builder.set_verify(SslVerifyMode::NONE);
+    builder
+        .set_private_key_file("./tests/key.pem", ssl::SslFiletype::PEM)
+        .unwrap();
+    builder
+        .set_certificate_chain_file("./tests/cert.pem")
+        .unwrap();
+    let conn = ntex::connect::openssl::Connector::new(builder.build());
+    let io = conn.call(addr.clone().into()).await.unwrap();
+    let item = io.recv(&BytesCodec).await.unwrap().unwrap();
+    assert_eq!(item, Bytes::from_static(b"test"));
+    assert!(checked.load(Ordering::Relaxed));
+
+    // no client certificate
+    let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+    builder.set_verify(SslVerifyMode::NONE);
+    let conn = ntex::connect::openssl::Connector::new(builder.build());
+    if let Ok(io) = conn.call(addr.into()).await {
+        assert!(!matches!(io.recv(&BytesCodec).await, Ok(Some(_))));
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[ntex::test]
+async fn test_openssl_pinning() {
+    use ntex::connect::{openssl::Connector, spki_sha256, ConnectError, ConnectStage};
+    use ntex::server::openssl;
+    use tls_openssl::{
+        ssl::{SslConnector, SslMethod},
+        x509::X509,
+    };
+
+    let srv = test_server(|| {
+        pipeline_factory(fn_service(|io: Io<_>| async move { Ok(io) }))
+            .and_then(openssl::Acceptor::new(ssl_acceptor()))
+            .and_then(fn_service(|io: Io<_>| async move {
+                io.send(Bytes::from_static(b"test"), &BytesCodec)
+                    .await
+                    .unwrap();
+                Ok::<_, Box<dyn std::error::Error>>(())
+            }))
+    });
+    let cert = X509::from_pem(include_bytes!("cert.pem"))
+        .unwrap()
+        .to_der()
+        .unwrap();
+    let pin = spki_sha256(&cert).unwrap();
+    let addr = format!("127.0.0.1:{}", srv.addr().port());
+    let connector =
+        || Connector::new(SslConnector::builder(SslMethod::tls()).unwrap().build());
+
+    // self-signed certificate
+    let res = connector().call(addr.clone().into()).await;
+    assert!(matches!(res, Err(ConnectError::Io(_))));
+    let err = res.unwrap_err();
+    assert_eq!(err.context().unwrap().stage(), ConnectStage::Tls);
+
+    let conn = connector()
+        .danger_accept_invalid_certs()
+        .pin_public_key(pin);
+    let io = conn.call(addr.clone().into()).await.unwrap();
+    let item = io.recv(&BytesCodec).await.unwrap().unwrap();
+    assert_eq!(item, Bytes::from_static(b"test"));
+
+    let conn = connector()
+        .danger_accept_invalid_certs()
+        .pin_public_key([0; 32]);
+    let res = conn.call(addr.clone().into()).await;
+    assert!(matches!(res, Err(ConnectError::PinMismatch)));
+
+    // custom verifier
+    let conn = connector().verifier(move |host, certs| {
+        host == "127.0.0.1" && certs.peer_certificate() == Some(&cert[..])
+    });
+    assert!(conn.call(addr.clone().into()).await.is_ok());
+
+    let conn = connector().verifier(|_, _| false);
+    assert!(conn.call(addr.into()).await.is_err());
+}
+
 #[cfg(feature = "rustls")]
 #[ntex::test]
 async fn test_rustls_string() {
     use ntex::server::rustls;
-    use ntex_tls::{rustls::PeerCert, rustls::PeerCertChain, types::HttpProtocol};
+    use ntex_tls::types::{AlpnProtocol, HttpProtocol};
+    use ntex_tls::{rustls::PeerCert, rustls::PeerCertChain};
     use rustls_pemfile::certs;
     use std::fs::File;
     use std::io::BufReader;
@@ -192,6 +424,7 @@ async fn test_rustls_string() {
         io.query::<HttpProtocol>().get().unwrap(),
         HttpProtocol::Http1
     );
+    assert!(io.query::<AlpnProtocol>().as_ref().is_none());
     let cert_file = &mut BufReader::new(File::open("tests/cert.pem").unwrap());
     let cert_chain: Vec<Certificate> = certs(cert_file)
         .unwrap()
@@ -212,6 +445,63 @@ async fn test_rustls_string() {
     assert!(io.recv(&BytesCodec).await.unwrap().is_none());
 }
 
+#[cfg(feature = "rustls")]
+#[ntex::test]
+async fn test_rustls_pinning() {
+    use ntex::connect::{rustls::Connector, spki_sha256, ConnectError};
+    use ntex::server::rustls;
+    use tls_rustls::{ClientConfig, RootCertStore};
+
+    let srv = test_server(|| {
+        pipeline_factory(fn_service(|io: Io<_>| async move { Ok(io) }))
+            .and_then(rustls::Acceptor::new(tls_acceptor()))
+            .and_then(fn_service(|io: Io<_>| async move {
+                io.send(Bytes::from_static(b"test"), &BytesCodec)
+                    .await
+                    .unwrap();
+                Ok::<_, std::io::Error>(())
+            }))
+    });
+    let cert = rustls_pemfile::certs(&mut &include_bytes!("cert.pem")[..])
+        .unwrap()
+        .remove(0);
+    let pin = spki_sha256(&cert).unwrap();
+    let addr = format!("localhost:{}", srv.addr().port());
+    let connector = || {
+        Connector::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(RootCertStore::empty())
+                .with_no_client_auth(),
+        )
+    };
+
+    // self-signed certificate
+    assert!(connector().call(addr.clone().into()).await.is_err());
+
+    let conn = connector()
+        .danger_accept_invalid_certs()
+        .pin_public_key(pin);
+    let io = conn.call(addr.clone().into()).await.unwrap();
+    let item = io.recv(&BytesCodec).await.unwrap().unwrap();
+    assert_eq!(item, Bytes::from_static(b"test"));
+
+    let conn = connector()
+        .danger_accept_invalid_certs()
+        .pin_public_key([0; 32]);
+    let res = conn.call(addr.clone().into()).await;
+    assert!(matches!(res, Err(ConnectError::PinMismatch)));
+
+    // custom verifier
+    let conn = connector().verifier(move |host, certs| {
+        host == "localhost" && certs.peer_certificate() == Some(&cert[..])
+    });
+    assert!(conn.call(addr.clone().into()).await.is_ok());
+
+    let conn = connector().verifier(|_, _| false);
+    assert!(conn.call(addr.into()).await.is_err());
+}
+
 #[ntex::test]
 async fn test_static_str() {
     let srv = test_server(|| {
@@ -235,6 +525,27 @@ async fn test_static_str() {
     assert!(io.is_err());
 }
 
+#[cfg(feature = "tokio")]
+#[ntex::test]
+async fn test_async_io() {
+    let srv = test_server(|| {
+        fn_service(|io: Io| async move {
+            let item = io.recv(&BytesCodec).await.unwrap().unwrap();
+            io.send(item.freeze(), &BytesCodec).await.unwrap();
+            time::sleep(time::Millis(100)).await;
+            Ok::<_, io::Error>(())
+        })
+    });
+
+    let stream = tok_io::net::TcpStream::connect(srv.addr()).await.unwrap();
+    let io = Io::new(ntex::io::AsyncIo(stream));
+    io.send(Bytes::from_static(b"test"), &BytesCodec)
+        .await
+        .unwrap();
+    let item = io.recv(&BytesCodec).await.unwrap().unwrap();
+    assert_eq!(item, Bytes::from_static(b"test"));
+}
+
 #[ntex::test]
 async fn test_new_service() {
     let srv = test_server(|| {
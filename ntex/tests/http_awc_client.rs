@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
 
 use brotli2::write::BrotliEncoder;
 use coo_kie::Cookie;
@@ -9,15 +10,19 @@ use flate2::{read::GzDecoder, write::GzEncoder, write::ZlibEncoder, Compression}
 use futures_util::stream::once;
 use rand::Rng;
 
-use ntex::http::client::error::{JsonPayloadError, SendRequestError};
-use ntex::http::client::{Client, Connector};
+use ntex::http::body::{BodySize, MessageBody};
+use ntex::http::client::error::{ConnectError, JsonPayloadError, SendRequestError};
+use ntex::http::client::middleware::{ClientCall, Retry, RetryBudget};
+use ntex::http::client::multipart::{Form, Part};
+use ntex::http::client::{Client, ClientResponse, Connector};
 use ntex::http::test::server as test_server;
-use ntex::http::{header, HttpMessage, HttpService};
-use ntex::service::{map_config, pipeline_factory};
+use ntex::http::{header, HttpMessage, HttpService, StatusCode};
+use ntex::service::{map_config, pipeline_factory, Service, Transform};
+use ntex::util::{stream_recv, Bytes, BytesMut, Ready};
 use ntex::web::dev::AppConfig;
 use ntex::web::middleware::Compress;
 use ntex::web::{self, test, App, BodyEncoding, Error, HttpRequest, HttpResponse};
-use ntex::{time::sleep, time::Millis, time::Seconds, util::Bytes, util::Ready};
+use ntex::{time::sleep, time::Millis, time::Seconds};
 
 const STR: &str = "Hello World Hello World Hello World Hello World Hello World \
                    Hello World Hello World Hello World Hello World Hello World \
@@ -158,6 +163,410 @@ async fn test_form() {
     assert!(response.status().is_success());
 }
 
+#[ntex::test]
+async fn test_multipart() {
+    let srv = test::server(|| {
+        App::new().service(web::resource("/").route(web::to(
+            |req: HttpRequest, mut form: web::types::Multipart| async move {
+                let mut result = match req.headers().get(header::CONTENT_LENGTH) {
+                    Some(len) => format!("length={};", len.to_str().unwrap()),
+                    None => "chunked;".to_string(),
+                };
+                while let Some(field) = stream_recv(&mut form).await {
+                    let mut field = field?;
+                    let mut body = BytesMut::new();
+                    while let Some(chunk) = stream_recv(&mut field).await {
+                        body.extend_from_slice(&chunk?);
+                    }
+                    result.push_str(&format!(
+                        "{}:{}:{}:{};",
+                        field.name(),
+                        field.filename().unwrap_or(""),
+                        field
+                            .content_type()
+                            .map(|m| m.to_string())
+                            .unwrap_or_default(),
+                        String::from_utf8_lossy(&body)
+                    ));
+                }
+                Ok::<_, Error>(result)
+            },
+        )))
+    });
+
+    // streaming part, chunked transfer encoding
+    let stream = futures_util::stream::iter(vec![
+        Ok::<_, std::io::Error>(Bytes::from_static(b"chunk1")),
+        Ok(Bytes::from_static(b"chunk2")),
+    ]);
+    let form = Form::new()
+        .text("name", "ntex")
+        .part("file", Part::stream(stream).file_name("data.txt"));
+    let mut response = srv.post("/").send_multipart(form).await.unwrap();
+    assert!(response.status().is_success());
+    let bytes = response.body().await.unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(b"chunked;name:::ntex;file:data.txt:text/plain:chunk1chunk2;")
+    );
+
+    // all sizes are known, content-length is used
+    let form = Form::new().text("name", "ntex").part(
+        "file",
+        Part::reader(&b"reader data"[..])
+            .length(11)
+            .file_name("data.bin"),
+    );
+    let len = match form.size() {
+        BodySize::Sized(len) => len,
+        _ => panic!(),
+    };
+    let request = srv.post("/").freeze().unwrap();
+    let mut response = request.send_multipart(form).await.unwrap();
+    assert!(response.status().is_success());
+    let bytes = response.body().await.unwrap();
+    assert_eq!(
+        bytes,
+        format!(
+            "length={};name:::ntex;file:data.bin:application/octet-stream:reader data;",
+            len
+        )
+    );
+}
+
+#[ntex::test]
+async fn test_redirects() {
+    let srv = test::server(|| {
+        App::new()
+            .service(web::resource("/found").to(|| async {
+                HttpResponse::Found()
+                    .header(header::LOCATION, "/target")
+                    .finish()
+            }))
+            .service(web::resource("/temporary").to(|| async {
+                HttpResponse::TemporaryRedirect()
+                    .header(header::LOCATION, "target")
+                    .finish()
+            }))
+            .service(web::resource("/loop").to(|| async {
+                HttpResponse::Found()
+                    .header(header::LOCATION, "/loop")
+                    .finish()
+            }))
+            .service(web::resource("/target").to(
+                |req: HttpRequest, body: Bytes| async move {
+                    HttpResponse::Ok().body(format!(
+                        "{} {} {}",
+                        req.method(),
+                        req.headers().contains_key(header::CONTENT_TYPE),
+                        String::from_utf8_lossy(&body)
+                    ))
+                },
+            ))
+    });
+
+    // 302 changes POST to GET and drops body
+    let mut response = srv.post("/found").send_json(&"data").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.body().await.unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"GET false "));
+
+    // 307 preserves method and body
+    let mut response = srv.put("/temporary").send_json(&"data").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.body().await.unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"PUT true \"data\""));
+
+    // streaming body cannot be sent again
+    let response = srv
+        .put("/temporary")
+        .send_stream(once(Ready::Ok::<_, JsonPayloadError>(Bytes::from_static(
+            b"data",
+        ))))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+
+    // max redirects
+    let client = Client::build().max_redirects(3).finish();
+    let response = client.get(srv.url("/loop")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FOUND);
+
+    let client = Client::build().disable_redirects().finish();
+    let response = client.get(srv.url("/found")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FOUND);
+}
+
+struct Signature;
+
+impl<S> Transform<S> for Signature {
+    type Service = SignatureService<S>;
+
+    fn new_transform(&self, service: S) -> Self::Service {
+        SignatureService(service)
+    }
+}
+
+struct SignatureService<S>(S);
+
+impl<S> Service<ClientCall> for SignatureService<S>
+where
+    S: Service<ClientCall>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&self, mut req: ClientCall) -> Self::Future {
+        let sig = format!("{} {}", req.method(), req.uri().path());
+        req.head_mut()
+            .headers
+            .insert(header::AUTHORIZATION, sig.parse().unwrap());
+        self.0.call(req)
+    }
+}
+
+#[ntex::test]
+async fn test_middleware() {
+    let num = Arc::new(AtomicUsize::new(0));
+    let num2 = num.clone();
+
+    let srv = test::server(move || {
+        let num = num2.clone();
+        App::new()
+            .service(web::resource("/found").to(|| async {
+                HttpResponse::Found()
+                    .header(header::LOCATION, "/signed")
+                    .finish()
+            }))
+            .service(web::resource("/signed").to(|req: HttpRequest| async move {
+                HttpResponse::Ok().body(format!(
+                    "{:?}",
+                    req.headers().get(header::AUTHORIZATION).unwrap()
+                ))
+            }))
+            .service(web::resource("/unavailable").to(move || {
+                let n = num.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if n < 2 {
+                        HttpResponse::ServiceUnavailable().finish()
+                    } else {
+                        HttpResponse::Ok().finish()
+                    }
+                }
+            }))
+    });
+
+    // every redirected request is passed through middlewares
+    let client = Client::build().wrap(Signature).finish();
+    let mut response: ClientResponse = client.get(srv.url("/found")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.body().await.unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"\"GET /signed\""));
+
+    let client = Client::build()
+        .wrap(Retry::new().backoff(Millis(10), Millis(50)))
+        .finish();
+    let response = client.get(srv.url("/unavailable")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(num.load(Ordering::Relaxed), 3);
+
+    // non idempotent requests are not retried
+    num.store(0, Ordering::Relaxed);
+    let response = client.post(srv.url("/unavailable")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(num.load(Ordering::Relaxed), 1);
+
+    num.store(0, Ordering::Relaxed);
+    let client = Client::build().wrap(Retry::new().max_retries(1)).finish();
+    let response = client.get(srv.url("/unavailable")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(num.load(Ordering::Relaxed), 2);
+}
+
+#[ntex::test]
+async fn test_retry_policy() {
+    let num = Arc::new(AtomicUsize::new(0));
+    let num2 = num.clone();
+
+    let srv = test::server(move || {
+        let num = num2.clone();
+        let num3 = num2.clone();
+        App::new()
+            .service(web::resource("/retry-after/{secs}").to(
+                move |path: web::types::Path<String>| {
+                    let n = num.fetch_add(1, Ordering::Relaxed);
+                    async move {
+                        if n == 0 {
+                            HttpResponse::ServiceUnavailable()
+                                .header(header::RETRY_AFTER, path.into_inner())
+                                .finish()
+                        } else {
+                            HttpResponse::Ok().finish()
+                        }
+                    }
+                },
+            ))
+            .service(web::resource("/slow").to(move || {
+                let n = num3.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if n == 0 {
+                        sleep(Millis(500)).await;
+                    }
+                    HttpResponse::Ok().finish()
+                }
+            }))
+    });
+
+    let client = Client::build()
+        .wrap(
+            Retry::new()
+                .backoff(Millis(10), Millis(1_500))
+                .try_timeout(Millis(200)),
+        )
+        .finish();
+
+    // marked request is retried
+    let response = client
+        .post(srv.url("/retry-after/0"))
+        .idempotent()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(num.load(Ordering::Relaxed), 2);
+
+    // retry-after is larger than max delay
+    num.store(0, Ordering::Relaxed);
+    let response = client.get(srv.url("/retry-after/5")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(num.load(Ordering::Relaxed), 1);
+
+    // per-try timeout
+    num.store(0, Ordering::Relaxed);
+    let response = client.get(srv.url("/slow")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(num.load(Ordering::Relaxed), 2);
+
+    // retry budget is exhausted
+    num.store(0, Ordering::Relaxed);
+    let client = Client::build()
+        .wrap(
+            Retry::new()
+                .backoff(Millis(10), Millis(50))
+                .budget(RetryBudget::new(0.0, 0)),
+        )
+        .finish();
+    let response = client.get(srv.url("/retry-after/0")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(num.load(Ordering::Relaxed), 1);
+}
+
+/// Tunneling proxy, connects every tunnel to `target`
+fn start_proxy(target: std::net::SocketAddr, socks: bool) -> std::net::SocketAddr {
+    use std::net::{TcpListener, TcpStream};
+
+    fn pipe(mut src: TcpStream, mut dst: TcpStream) {
+        std::thread::spawn(move || {
+            let _ = std::io::copy(&mut src, &mut dst);
+            let _ = dst.shutdown(std::net::Shutdown::Both);
+        });
+    }
+
+    fn read_exact(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        stream.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    let lst = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = lst.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in lst.incoming() {
+            let mut stream = stream.unwrap();
+            if socks {
+                assert_eq!(read_exact(&mut stream, 4), [5, 2, 0, 2]);
+                stream.write_all(&[5, 2]).unwrap();
+                let len = read_exact(&mut stream, 2)[1] as usize;
+                let user = read_exact(&mut stream, len);
+                let len = read_exact(&mut stream, 1)[0] as usize;
+                let pass = read_exact(&mut stream, len);
+                if (&user[..], &pass[..]) != (b"user", b"pass") {
+                    stream.write_all(&[1, 1]).unwrap();
+                    continue;
+                }
+                stream.write_all(&[1, 0]).unwrap();
+
+                let head = read_exact(&mut stream, 5);
+                assert_eq!(&head[..4], &[5, 1, 0, 3]);
+                let host = read_exact(&mut stream, head[4] as usize + 2);
+                assert_eq!(&host[..host.len() - 2], b"example.test");
+                stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+            } else {
+                let mut head = Vec::new();
+                while !head.ends_with(b"\r\n\r\n") {
+                    head.extend(read_exact(&mut stream, 1));
+                }
+                let head = String::from_utf8(head).unwrap();
+                assert!(head.starts_with("CONNECT example.test:"));
+                if !head.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n") {
+                    stream
+                        .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                        .unwrap();
+                    continue;
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                    .unwrap();
+            }
+
+            let conn = TcpStream::connect(target).unwrap();
+            pipe(stream.try_clone().unwrap(), conn.try_clone().unwrap());
+            pipe(conn, stream);
+        }
+    });
+    addr
+}
+
+#[ntex::test]
+async fn test_proxy() {
+    let srv = test::server(|| {
+        App::new().service(web::resource("/").to(|req: HttpRequest| async move {
+            HttpResponse::Ok().body(format!("{:?}", req.headers().get(header::HOST)))
+        }))
+    });
+    let url = format!("http://example.test:{}/", srv.addr().port());
+
+    for socks in [false, true] {
+        let proxy = start_proxy(srv.addr(), socks);
+        let scheme = if socks { "socks5h" } else { "http" };
+
+        let client = Client::build()
+            .proxy(&format!("{}://user:pass@{}", scheme, proxy))
+            .finish();
+        let mut response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.body().await.unwrap();
+        assert_eq!(
+            bytes,
+            format!("Some(\"example.test:{}\")", srv.addr().port())
+        );
+
+        // invalid credentials
+        let client = Client::build()
+            .proxy(&format!("{}://user:wrong@{}", scheme, proxy))
+            .finish();
+        match client.get(&url).send().await {
+            Err(SendRequestError::Connect(ConnectError::Proxy(_))) => (),
+            res => panic!("proxy error expected: {:?}", res.map(|r| r.status())),
+        }
+    }
+}
+
 #[ntex::test]
 async fn test_timeout() {
     let srv = test::server(|| {
@@ -883,3 +1292,42 @@ async fn client_bearer_auth() {
     let response = request.send().await.unwrap();
     assert!(response.status().is_success());
 }
+
+#[ntex::test]
+async fn client_h1_header_case() {
+    let lst = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = lst.local_addr().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let (mut stream, _) = lst.accept().unwrap();
+        let mut data = Vec::new();
+        let mut buf = [0; 1024];
+        while !data.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            data.extend_from_slice(&buf[..n]);
+        }
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+        let _ = tx.send(String::from_utf8(data).unwrap());
+    });
+
+    let mut map = ntex::http::h1::HeaderCaseMap::default();
+    let name = map.insert("X-LEGACY-header").unwrap();
+
+    let client = Client::build()
+        .h1_header_case(ntex::http::h1::HeaderCase::Preserve)
+        .finish();
+    let response = client
+        .get(format!("http://{}/", addr))
+        .header(name, "value")
+        .header(header::ACCEPT, "*/*")
+        .header_case_map(map)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let data = rx.recv().unwrap();
+    assert!(data.contains("\r\nX-LEGACY-header: value\r\n"));
+    assert!(data.contains("\r\naccept: */*\r\n"));
+}
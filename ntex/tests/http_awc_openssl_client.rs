@@ -9,9 +9,11 @@ use tls_openssl::ssl::{
 use ntex::http::client::{Client, Connector};
 use ntex::http::test::server as test_server;
 use ntex::http::{HttpService, Version};
-use ntex::service::{map_config, pipeline_factory, ServiceFactory};
+use ntex::io::{Io, IoBoxed, TokioIoBoxed};
+use ntex::service::{fn_service, map_config, pipeline_factory, ServiceFactory};
 use ntex::web::{self, dev::AppConfig, App, HttpResponse};
-use ntex::{time::Seconds, util::Ready};
+use ntex::{server::TestServer, time::sleep, time::Millis, time::Seconds};
+use ntex::{tls::openssl::Acceptor, util::join_all, util::Bytes, util::Ready};
 
 fn ssl_acceptor() -> SslAcceptor {
     // load ssl keys
@@ -88,3 +90,119 @@ async fn test_connection_reuse_h2() {
     // one connection
     assert_eq!(num.load(Ordering::Relaxed), 1);
 }
+
+fn client() -> Client {
+    // disable ssl verification
+    let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+    builder.set_verify(SslVerifyMode::NONE);
+    let _ = builder
+        .set_alpn_protos(b"\x02h2\x08http/1.1")
+        .map_err(|e| log::error!("Cannot set alpn protocol: {:?}", e));
+
+    Client::build()
+        .connector(
+            Connector::default()
+                .timeout(Seconds(30))
+                .h2_initial_window_size(1024 * 1024)
+                .h2_initial_connection_window_size(4 * 1024 * 1024)
+                .openssl(builder.build())
+                .finish(),
+        )
+        .finish()
+}
+
+/// Plain http/2 server, every response is delayed for 200 millis
+fn h2_server(max_streams: u32, goaway: bool, num: Arc<AtomicUsize>) -> TestServer {
+    ntex::server::test_server(move || {
+        let num = num.clone();
+        pipeline_factory(Acceptor::new(ssl_acceptor()).map_err(|_| ())).and_then(
+            fn_service(move |io: Io<_>| {
+                num.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    let mut conn = h2::server::Builder::new()
+                        .max_concurrent_streams(max_streams)
+                        .handshake::<_, Bytes>(TokioIoBoxed::from(IoBoxed::from(io)))
+                        .await
+                        .map_err(|_| ())?;
+
+                    let mut shutdown = goaway;
+                    while let Some(Ok((_, mut tx))) = conn.accept().await {
+                        if shutdown {
+                            // server is going away, but completes first request
+                            shutdown = false;
+                            conn.graceful_shutdown();
+                        }
+                        ntex::rt::spawn(async move {
+                            sleep(Millis(200)).await;
+                            let _ = tx.send_response(http::Response::new(()), true);
+                        });
+                    }
+                    Ok::<_, ()>(())
+                }
+            }),
+        )
+    })
+}
+
+#[ntex::test]
+async fn test_h2_multiplexing() {
+    let num = Arc::new(AtomicUsize::new(0));
+    let srv = h2_server(100, false, num.clone());
+    let client = client();
+
+    let responses =
+        join_all((0..5).map(|_| client.get(format!("https://{}/", srv.addr())).send()))
+            .await;
+    for response in responses {
+        let response = response.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.version(), Version::HTTP_2);
+    }
+
+    // all requests are sent over one connection
+    assert_eq!(num.load(Ordering::Relaxed), 1);
+}
+
+#[ntex::test]
+async fn test_h2_max_concurrent_streams() {
+    let num = Arc::new(AtomicUsize::new(0));
+    let srv = h2_server(1, false, num.clone());
+    let client = client();
+    let url = format!("https://{}/", srv.addr());
+
+    // client receives server settings
+    let response = client.get(&url).send().await.unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(num.load(Ordering::Relaxed), 1);
+    // response payload keeps stream reserved
+    drop(response);
+
+    // first connection allows one stream only, second request opens
+    // new connection
+    let responses = join_all((0..2).map(|_| client.get(&url).send())).await;
+    for response in responses {
+        assert!(response.unwrap().status().is_success());
+    }
+    assert_eq!(num.load(Ordering::Relaxed), 2);
+}
+
+#[ntex::test]
+async fn test_h2_goaway() {
+    let num = Arc::new(AtomicUsize::new(0));
+    let srv = h2_server(100, true, num.clone());
+    let client = client();
+    let url = format!("https://{}/", srv.addr());
+
+    let req1 = client.get(&url).send();
+    let req2 = async {
+        // server sends GOAWAY, but first connection is still open
+        sleep(Millis(100)).await;
+        client.get(&url).send().await
+    };
+    let (res1, res2) = futures_util::future::join(req1, req2).await;
+    assert!(res1.unwrap().status().is_success());
+    assert!(res2.unwrap().status().is_success());
+
+    // second request is sent over new connection
+    assert_eq!(num.load(Ordering::Relaxed), 2);
+}
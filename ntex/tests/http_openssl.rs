@@ -8,7 +8,9 @@ use ntex::codec::BytesCodec;
 use ntex::http::error::PayloadError;
 use ntex::http::header::{self, HeaderName, HeaderValue};
 use ntex::http::test::server as test_server;
-use ntex::http::{body, h1, HttpService, Method, Request, Response, StatusCode, Version};
+use ntex::http::{
+    body, h1, HttpService, Method, PeerInfo, Request, Response, StatusCode, Version,
+};
 use ntex::service::{fn_service, ServiceFactory};
 use ntex::util::{Bytes, BytesMut, Ready};
 use ntex::{io::Io, time::Seconds, web::error::InternalError, ws, ws::handshake_response};
@@ -109,6 +111,28 @@ async fn test_h2_1() -> io::Result<()> {
     Ok(())
 }
 
+#[ntex::test]
+async fn test_h2_peer_info() -> io::Result<()> {
+    let srv = test_server(move || {
+        HttpService::build()
+            .on_connect(PeerInfo::from_io)
+            .h2(|req: Request| {
+                let info = req.extensions().get::<PeerInfo>().cloned().unwrap();
+                assert!(info.peer_addr().is_some());
+                let tls = info.tls_info().unwrap();
+                assert!(tls.version.starts_with("TLSv1"));
+                assert!(!tls.cipher.is_empty());
+                Ready::Ok::<_, io::Error>(Response::Ok().finish())
+            })
+            .openssl(ssl_acceptor())
+            .map_err(|_| ())
+    });
+
+    let response = srv.srequest(Method::GET, "/").send().await.unwrap();
+    assert!(response.status().is_success());
+    Ok(())
+}
+
 #[ntex::test]
 async fn test_h2_body() -> io::Result<()> {
     let data = "HELLOWORLD".to_owned().repeat(64 * 1024);
@@ -7,10 +7,12 @@ use regex::Regex;
 use ntex::http::header::{HeaderName, HeaderValue};
 use ntex::http::test::server as test_server;
 use ntex::http::{
-    body, header, HttpService, KeepAlive, Method, Request, Response, StatusCode,
+    body, h1, header, HttpService, KeepAlive, Method, PeerInfo, Request, Response,
+    StatusCode,
 };
 use ntex::time::{sleep, Millis, Seconds};
-use ntex::{service::fn_service, util::Bytes, util::Ready, web::error};
+use ntex::{codec::BytesCodec, io::TokioIoBoxed, service::fn_service};
+use ntex::{util::Bytes, util::BytesMut, util::Ready, web::error};
 
 #[ntex::test]
 async fn test_h1() {
@@ -51,6 +53,236 @@ async fn test_h1_2() {
     assert!(!hdr.to_str().unwrap().starts_with("000"));
 }
 
+#[ntex::test]
+async fn test_early_hints() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .keep_alive(KeepAlive::Disabled)
+            .h1(fn_service(|req: Request| async move {
+                let mut headers = header::HeaderMap::new();
+                headers.insert(
+                    header::LINK,
+                    HeaderValue::from_static("</style.css>; rel=preload"),
+                );
+                let hints = StatusCode::from_u16(103).unwrap();
+                req.send_informational(hints, &headers).unwrap();
+                assert!(req.send_informational(StatusCode::OK, &headers).is_err());
+                sleep(Millis(20)).await;
+                Ok::<_, io::Error>(Response::Ok().finish())
+            }))
+    });
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with(
+        "HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload\r\n\r\nHTTP/1.1 200 OK\r\n"
+    ));
+
+    // informational responses are not sent to HTTP/1.0 clients
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.0\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.0 200 OK\r\n"));
+}
+
+#[ntex::test]
+async fn test_h1_header_case() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .keep_alive(KeepAlive::Disabled)
+            .h1_header_case(h1::HeaderCase::Title)
+            .h1(fn_service(|_| async move {
+                let mut map = h1::HeaderCaseMap::default();
+                let name = map.insert("X-LEGACY-header").unwrap();
+                let mut res = Response::Ok().header(name, "value").body("test");
+                res.extensions_mut().insert(map);
+                Ok::<_, io::Error>(res)
+            }))
+    });
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(data.contains("\r\nContent-Length: 4\r\n"));
+    assert!(data.contains("\r\nConnection: close\r\n"));
+    assert!(data.contains("\r\nDate: "));
+    assert!(data.contains("\r\nX-LEGACY-header: value\r\n"));
+}
+
+#[ntex::test]
+async fn test_http1_timeouts() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .keep_alive(KeepAlive::Timeout(Seconds(30)))
+            .request_head_timeout(Seconds(1))
+            .request_body_timeout(Seconds(1))
+            .h1(|mut req: Request| async move {
+                let mut pl = req.take_payload();
+                while let Some(item) = pl.next().await {
+                    if item.is_err() {
+                        return Ok::<_, io::Error>(Response::BadRequest().finish());
+                    }
+                }
+                Ok::<_, io::Error>(Response::Ok().finish())
+            })
+    });
+
+    // slow request head on keep-alive connection
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\n\r\nGET /test HTTP/1.1\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(data.contains("HTTP/1.1 408 Request Timeout\r\n"));
+
+    // slow request payload
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"POST /test HTTP/1.1\r\ncontent-length: 10\r\n\r\n12345");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+}
+
+#[ntex::test]
+async fn test_http1_idle_connection_timeout() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .keep_alive(KeepAlive::Timeout(Seconds(30)))
+            .idle_connection_timeout(Seconds(1))
+            .h1(|_| Ready::Ok::<_, io::Error>(Response::Ok().finish()))
+    });
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\n\r\n");
+    let start = std::time::Instant::now();
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[ntex::test]
+async fn test_http1_on_connect() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .on_connect(|io| (PeerInfo::from_io(io), "connection"))
+            .h1(|req: Request| {
+                let (info, data) = req
+                    .extensions()
+                    .get::<(PeerInfo, &'static str)>()
+                    .cloned()
+                    .unwrap();
+                assert_eq!(data, "connection");
+                assert_eq!(info.peer_addr(), req.peer_addr());
+                assert!(info.tls_info().is_none());
+                #[cfg(target_os = "linux")]
+                assert!(info.tcp_info().is_some());
+                Ready::Ok::<_, io::Error>(Response::Ok().finish())
+            })
+    });
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(
+        b"GET /test HTTP/1.1\r\n\r\nGET /test HTTP/1.1\r\nconnection: close\r\n\r\n",
+    );
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert_eq!(data.matches("HTTP/1.1 200 OK\r\n").count(), 2);
+}
+
+#[ntex::test]
+async fn test_h2c_prior_knowledge() {
+    let mut srv = test_server(|| {
+        HttpService::build().finish(|req: Request| async move {
+            let body = format!("{:?} {}", req.version(), req.path());
+            Ok::<_, io::Error>(Response::Ok().body(body))
+        })
+    });
+
+    let io = ntex::connect::connect(srv.addr()).await.unwrap();
+    let (mut client, conn) = h2::client::handshake(TokioIoBoxed::from(io)).await.unwrap();
+    ntex::rt::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let req = http::Request::get("http://localhost/test")
+        .body(())
+        .unwrap();
+    let (res, _) = client.send_request(req, true).unwrap();
+    let res = res.await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let data = res.into_body().data().await.unwrap().unwrap();
+    assert_eq!(&data[..], b"HTTP/2.0 /test");
+
+    // http/1 requests
+    let res = srv.request(Method::GET, "/test").send().await.unwrap();
+    assert!(res.status().is_success());
+    let body = srv.load_body(res).await.unwrap();
+    assert_eq!(&body[..], b"HTTP/1.1 /test");
+}
+
+#[ntex::test]
+async fn test_h2c_upgrade() {
+    let srv = test_server(|| {
+        HttpService::build().finish(|req: Request| async move {
+            let body = format!(
+                "{:?} {} {:?}",
+                req.version(),
+                req.path(),
+                req.headers().get("x-test")
+            );
+            Ok::<_, io::Error>(Response::Ok().body(body))
+        })
+    });
+
+    let io = ntex::connect::connect(srv.addr()).await.unwrap();
+    io.write(
+        b"GET /test HTTP/1.1\r\nhost: localhost\r\nconnection: Upgrade, HTTP2-Settings\r\n\
+          upgrade: h2c\r\nhttp2-settings: AAMAAABkAAQAAP__\r\nx-test: value\r\n\r\n",
+    )
+    .unwrap();
+    let item = io.recv(&h1::ClientCodec::default()).await.unwrap().unwrap();
+    assert_eq!(item.status, StatusCode::SWITCHING_PROTOCOLS);
+    assert_eq!(item.headers.get("upgrade").unwrap(), "h2c");
+
+    // client connection preface and empty settings frame
+    io.write(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0")
+        .unwrap();
+
+    // read server frames until response for stream 1 is complete
+    let mut buf = BytesMut::new();
+    let mut headers = false;
+    let mut data = BytesMut::new();
+    loop {
+        if buf.len() >= 9 {
+            let len = (buf[0] as usize) << 16 | (buf[1] as usize) << 8 | buf[2] as usize;
+            if buf.len() >= len + 9 {
+                let frame = buf.split_to(len + 9);
+                let (tp, flags, stream) = (frame[3], frame[4], frame[8]);
+                if stream == 1 {
+                    match tp {
+                        0x1 => headers = true,
+                        0x0 => data.extend_from_slice(&frame[9..]),
+                        _ => (),
+                    }
+                    if flags & 0x1 != 0 {
+                        break;
+                    }
+                }
+                continue;
+            }
+        }
+        buf.extend_from_slice(&io.recv(&BytesCodec).await.unwrap().unwrap());
+    }
+    assert!(headers);
+    assert_eq!(&data[..], b"HTTP/2.0 /test Some(\"value\")");
+}
+
 #[ntex::test]
 async fn test_expect_continue() {
     let srv = test_server(|| {
@@ -172,6 +404,70 @@ async fn test_http1_malformed_request() {
     assert!(data.starts_with("HTTP/1.1 400 Bad Request"));
 }
 
+#[ntex::test]
+async fn test_http1_head_limits() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .max_header_count(2)
+            .max_uri_length(16)
+            .h1(|_| Ready::Ok::<_, io::Error>(Response::Ok().finish()))
+    });
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\na: 1\r\nb: 2\r\nc: 3\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test/tests/test/long HTTP/1.1\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 414 URI Too Long"));
+
+    let srv = test_server(|| {
+        HttpService::build()
+            .max_header_size(64)
+            .h1(|_| Ready::Ok::<_, io::Error>(Response::Ok().finish()))
+    });
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\nx-header: ");
+    let _ = stream.write_all(&[b'a'; 128]);
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+}
+
+#[ntex::test]
+async fn test_http1_strict_parsing() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .strict_parsing(true)
+            .h1(|_| Ready::Ok::<_, io::Error>(Response::Ok().finish()))
+    });
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(
+        b"POST /test HTTP/1.1\r\ncontent-length: 5\r\ntransfer-encoding: chunked\r\n\r\n0\r\n\r\n",
+    );
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 400 Bad Request"));
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\nx-header: 1\r\n value\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 400 Bad Request"));
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\nconnection: close\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 200 OK"));
+}
+
 #[ntex::test]
 async fn test_http1_keepalive() {
     let srv = test_server(|| {
@@ -513,6 +809,121 @@ async fn test_h1_body_length() {
     assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
 }
 
+#[ntex::test]
+async fn test_h1_body_from_read() {
+    let mut srv = test_server(|| {
+        HttpService::build().h1(|req: Request| {
+            let body = body::BodyFromRead::new(STR.as_bytes()).chunk_size(64);
+            let body = if req.path() == "/sized" {
+                body.length(STR.len() as u64)
+            } else {
+                body
+            };
+            Ready::Ok::<_, io::Error>(Response::Ok().body(body))
+        })
+    });
+
+    let response = srv.request(Method::GET, "/").send().await.unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers().get(header::CONTENT_LENGTH).is_none());
+    let bytes = srv.load_body(response).await.unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+
+    let response = srv.request(Method::GET, "/sized").send().await.unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(header::CONTENT_LENGTH).unwrap(),
+        &format!("{}", STR.len())
+    );
+    let bytes = srv.load_body(response).await.unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+}
+
+#[ntex::test]
+async fn test_h1_body_file() {
+    let data = STR.repeat(1000);
+    let path = std::env::temp_dir().join(format!("ntex-body-file-{}", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+
+    let p = path.clone();
+    let mut srv = test_server(move || {
+        let p = p.clone();
+        HttpService::build().h1(move |req: Request| {
+            let file = std::fs::File::open(&p).unwrap();
+            let body = if req.path() == "/range" {
+                body::Body::from_file_range(file, 10..5010)
+            } else {
+                body::Body::from_file(file).unwrap()
+            };
+            Ready::Ok::<_, io::Error>(Response::Ok().body(body))
+        })
+    });
+
+    let response = srv.request(Method::GET, "/").send().await.unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(header::CONTENT_LENGTH).unwrap(),
+        &format!("{}", data.len())
+    );
+    let bytes = srv.load_body(response).await.unwrap();
+    assert_eq!(bytes, Bytes::from(data.clone()));
+
+    // same connection is used
+    let response = srv.request(Method::GET, "/range").send().await.unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.load_body(response).await.unwrap();
+    assert_eq!(bytes, Bytes::copy_from_slice(&data.as_bytes()[10..5010]));
+
+    let response = srv.request(Method::HEAD, "/").send().await.unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.load_body(response).await.unwrap();
+    assert!(bytes.is_empty());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[ntex::test]
+async fn test_h1_body_vectored() {
+    let data = STR.repeat(100);
+
+    let d = data.clone();
+    let mut srv = test_server(move || {
+        let d = d.clone();
+        HttpService::build().h1(move |req: Request| {
+            let body = Bytes::from(d.clone());
+            let res = if req.path() == "/stream" {
+                let chunks = vec![
+                    Ok::<_, Box<dyn std::error::Error>>(body.slice(..100)),
+                    Ok(body.slice(100..40_000)),
+                    Ok(body.slice(40_000..)),
+                ];
+                Response::Ok().body(body::SizedStream::new(
+                    body.len() as u64,
+                    futures_util::stream::iter(chunks),
+                ))
+            } else {
+                Response::Ok().body(body)
+            };
+            Ready::Ok::<_, io::Error>(res)
+        })
+    });
+
+    let response = srv.request(Method::GET, "/").send().await.unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(header::CONTENT_LENGTH).unwrap(),
+        &format!("{}", data.len())
+    );
+    let bytes = srv.load_body(response).await.unwrap();
+    assert_eq!(bytes, Bytes::from(data.clone()));
+
+    // same connection is used
+    let response = srv.request(Method::GET, "/stream").send().await.unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.load_body(response).await.unwrap();
+    assert_eq!(bytes, Bytes::from(data));
+}
+
 #[ntex::test]
 async fn test_h1_body_chunked_explicit() {
     let mut srv = test_server(|| {
@@ -610,3 +1021,30 @@ async fn test_h1_service_error() {
     let bytes = srv.load_body(response).await.unwrap();
     assert_eq!(bytes, Bytes::from_static(b"error"));
 }
+
+#[cfg(feature = "metrics")]
+#[ntex::test]
+async fn test_h1_metrics() {
+    use ntex::metrics::{Metrics, Protocol};
+
+    let accepted = Metrics::accepted_connections();
+    let requests = Metrics::http_requests(Protocol::Http1);
+    let responses = Metrics::http_responses(Protocol::Http1, 4);
+
+    let srv = test_server(|| {
+        HttpService::build()
+            .h1(|_| Ready::Ok::<_, io::Error>(Response::NotFound().finish()))
+    });
+
+    let response = srv.request(Method::GET, "/").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    assert!(Metrics::accepted_connections() > accepted);
+    assert!(Metrics::http_requests(Protocol::Http1) > requests);
+    assert!(Metrics::http_responses(Protocol::Http1, 4) > responses);
+    assert!(!Metrics::worker_connections().is_empty());
+
+    let body = Metrics::gather();
+    assert!(body.contains("# TYPE ntex_http_request_duration_seconds histogram"));
+    assert!(body.contains("ntex_worker_connections{worker=\"0\"}"));
+}
@@ -2,7 +2,7 @@ use std::io;
 
 use ntex::codec::BytesCodec;
 use ntex::http::test::server as test_server;
-use ntex::http::{body::BodySize, h1, HttpService, Request, Response};
+use ntex::http::{body::BodySize, client, h1, header, HttpService, Request, Response};
 use ntex::io::{DispatchItem, Dispatcher, Io};
 use ntex::ws::handshake_response;
 use ntex::{util::ByteString, util::Bytes, util::Ready, ws};
@@ -103,3 +103,65 @@ async fn test_transport() {
     let item = io.recv(&BytesCodec).await.unwrap().unwrap();
     assert_eq!(item, Bytes::from_static(b"text"));
 }
+
+#[ntex::test]
+async fn test_connect() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .upgrade(|(req, io, codec): (Request, Io, h1::Codec)| {
+                async move {
+                    let mut res = handshake_response(req.head());
+                    if let Some(proto) = req.headers().get(header::SEC_WEBSOCKET_PROTOCOL) {
+                        let proto = proto.to_str().unwrap().split(',').next_back().unwrap();
+                        if proto.trim() == "bad" {
+                            res.header(header::SEC_WEBSOCKET_PROTOCOL, "unknown");
+                        } else {
+                            res.header(header::SEC_WEBSOCKET_PROTOCOL, proto.trim());
+                        }
+                    }
+
+                    // send handshake respone
+                    io.encode(
+                        h1::Message::Item((res.finish().drop_body(), BodySize::None)),
+                        &codec,
+                    )
+                    .unwrap();
+
+                    // start websocket service
+                    Dispatcher::new(io.seal(), ws::Codec::default(), ws_service).await
+                }
+            })
+            .finish(|_| Ready::Ok::<_, io::Error>(Response::NotFound()))
+    });
+
+    let conn = client::ws::connect(format!("ws://{}/", srv.addr()))
+        .await
+        .unwrap();
+    assert!(conn.protocol().is_none());
+
+    let sink = conn.sink();
+    let rx = conn.receiver();
+    sink.send(ws::Message::Text(ByteString::from_static("text")))
+        .await
+        .unwrap();
+    let item = rx.recv().await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Text(Bytes::from_static(b"text")));
+
+    // sub-protocol negotiation
+    let conn = client::ws::WsClient::build(format!("ws://{}/", srv.addr()))
+        .protocols(["v1", "v2"])
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(conn.protocol(), Some("v2"));
+
+    // server selected not requested sub-protocol
+    let conn = client::ws::WsClient::build(format!("ws://{}/", srv.addr()))
+        .protocols(["v1", "bad"])
+        .connect()
+        .await;
+    assert!(matches!(
+        conn.err().unwrap(),
+        client::ws::WsClientError::InvalidProtocol(_)
+    ));
+}
@@ -3,7 +3,7 @@ use std::{io, io::Read, net, sync::mpsc, sync::Arc, thread, time};
 
 use ntex::codec::BytesCodec;
 use ntex::io::Io;
-use ntex::server::{Server, TestServer};
+use ntex::server::{Server, ServerEvent, TestServer};
 use ntex::service::fn_service;
 use ntex::util::{Bytes, Ready};
 
@@ -225,9 +225,8 @@ fn test_panic_in_worker() {
     thread::sleep(time::Duration::from_millis(100));
     assert_eq!(counter.load(Relaxed), 1);
 
-    // first connect get dropped, because there is no workers
-    assert!(net::TcpStream::connect(addr).is_ok());
-    thread::sleep(time::Duration::from_millis(300));
+    // failed worker is restarted
+    thread::sleep(time::Duration::from_millis(400));
     assert!(net::TcpStream::connect(addr).is_ok());
     thread::sleep(time::Duration::from_millis(500));
     assert_eq!(counter.load(Relaxed), 2);
@@ -235,3 +234,418 @@ fn test_panic_in_worker() {
     sys.stop();
     let _ = h.join();
 }
+
+#[test]
+fn test_shutdown_timeout() {
+    let addr = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let srv = Server::build()
+                .workers(1)
+                .disable_signals()
+                .shutdown_timeout(time::Duration::from_millis(500))
+                .bind("test", addr, move |_| {
+                    fn_service(|io: Io| async move {
+                        // keep connection open until peer or server closes it
+                        while let Ok(Some(_)) = io.recv(&BytesCodec).await {}
+                        Ok::<_, ()>(())
+                    })
+                })
+                .unwrap()
+                .run();
+            let _ = tx.send((srv, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (srv, sys) = rx.recv().unwrap();
+
+    thread::sleep(time::Duration::from_millis(300));
+    let mut conn = net::TcpStream::connect(addr).unwrap();
+    conn.set_read_timeout(Some(time::Duration::from_secs(5)))
+        .unwrap();
+    thread::sleep(time::Duration::from_millis(100));
+
+    // graceful stop, connection is still alive
+    let start = time::Instant::now();
+    drop(srv.stop(true));
+    thread::sleep(time::Duration::from_millis(100));
+    assert!(net::TcpStream::connect(addr).is_err());
+
+    // connection get closed after shutdown timeout
+    let mut buf = [0u8; 4];
+    assert_eq!(conn.read(&mut buf).unwrap_or(0), 0);
+    assert!(start.elapsed() >= time::Duration::from_millis(500));
+
+    sys.stop();
+    let _ = h.join();
+}
+
+#[test]
+fn test_bind_dynamic() {
+    let addr1 = TestServer::unused_addr();
+    let addr2 = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let srv = Server::build()
+                .workers(2)
+                .disable_signals()
+                .bind("test", addr1, move |_| {
+                    fn_service(|_| Ready::Ok::<_, ()>(()))
+                })
+                .unwrap()
+                .run();
+            let _ = tx.send((srv, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (srv, sys) = rx.recv().unwrap();
+    thread::sleep(time::Duration::from_millis(300));
+    assert!(net::TcpStream::connect(addr2).is_err());
+
+    // add new listener
+    let res = ntex::rt::System::new("client").block_on(srv.bind_dynamic(
+        "dynamic",
+        addr2,
+        |_| {
+            fn_service(|io: Io| async move {
+                io.send(Bytes::from_static(b"test"), &BytesCodec)
+                    .await
+                    .unwrap();
+                Ok::<_, ()>(())
+            })
+        },
+    ));
+    assert!(res.is_ok());
+    thread::sleep(time::Duration::from_millis(100));
+
+    let mut buf = [0u8; 4];
+    let mut conn = net::TcpStream::connect(addr2).unwrap();
+    let _ = conn.read_exact(&mut buf);
+    assert_eq!(buf, b"test"[..]);
+
+    // remove listeners
+    let res = ntex::rt::System::new("client").block_on(srv.unbind("dynamic"));
+    assert!(res.is_ok());
+    let res = ntex::rt::System::new("client").block_on(srv.unbind("unknown"));
+    assert_eq!(res.err().unwrap().kind(), io::ErrorKind::NotFound);
+    thread::sleep(time::Duration::from_millis(100));
+    assert!(net::TcpStream::connect(addr2).is_err());
+    assert!(net::TcpStream::connect(addr1).is_ok());
+
+    sys.stop();
+    let _ = h.join();
+}
+
+#[test]
+fn test_server_events() {
+    let addr = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let builder = Server::build()
+                .workers(1)
+                .disable_signals()
+                .bind("test", addr, move |_| {
+                    fn_service(|_| Ready::Ok::<_, ()>(()))
+                })
+                .unwrap();
+            let events = builder.events();
+            let srv = builder.run();
+            let _ = tx.send((srv, events, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (srv, events, sys) = rx.recv().unwrap();
+    assert_eq!(
+        events.try_recv(),
+        Some(ServerEvent::ListenerBound {
+            name: "test".to_string(),
+            addr: Some(addr),
+            token: 0
+        })
+    );
+    thread::sleep(time::Duration::from_millis(300));
+
+    let conn = net::TcpStream::connect(addr).unwrap();
+    thread::sleep(time::Duration::from_millis(100));
+    assert_eq!(
+        events.try_recv(),
+        Some(ServerEvent::ConnectionAccepted {
+            peer: Some(conn.local_addr().unwrap()),
+            token: 0
+        })
+    );
+
+    ntex::rt::System::new("client").block_on(srv.pause());
+    thread::sleep(time::Duration::from_millis(100));
+    assert_eq!(events.try_recv(), Some(ServerEvent::AcceptPaused));
+
+    sys.stop();
+    let _ = h.join();
+}
+
+#[test]
+fn test_worker_restart() {
+    let addr = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+    let num = Arc::new(AtomicUsize::new(0));
+    let num2 = num.clone();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let builder = Server::build()
+                .workers(1)
+                .disable_signals()
+                .worker_restarts(1, ntex::time::Seconds(60))
+                .bind("test", addr, move |_| {
+                    let num = num2.clone();
+                    fn_service(move |io: Io| {
+                        // first and third connections panic worker
+                        if num.fetch_add(1, Relaxed) != 1 {
+                            panic!("worker failure");
+                        }
+                        async move {
+                            io.send(Bytes::from_static(b"test"), &BytesCodec)
+                                .await
+                                .unwrap();
+                            Ok::<_, ()>(())
+                        }
+                    })
+                })
+                .unwrap();
+            let events = builder.events();
+            let srv = builder.run();
+            let _ = tx.send((srv, events, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (_srv, events, sys) = rx.recv().unwrap();
+    thread::sleep(time::Duration::from_millis(300));
+    while events.try_recv().is_some() {}
+
+    // worker is restarted
+    let _ = net::TcpStream::connect(addr).unwrap();
+    thread::sleep(time::Duration::from_millis(300));
+    let events: Vec<_> = std::iter::from_fn(|| events.try_recv()).collect();
+    assert!(events.contains(&ServerEvent::WorkerDied { idx: 0 }));
+    assert!(events.contains(&ServerEvent::WorkerRestarted { idx: 0, new_idx: 1 }));
+
+    let mut buf = [0u8; 4];
+    let mut conn = net::TcpStream::connect(addr).unwrap();
+    let _ = conn.read_exact(&mut buf);
+    assert_eq!(buf, b"test"[..]);
+    assert_eq!(num.load(Relaxed), 2);
+
+    // restart limit is reached, server stops
+    let _ = net::TcpStream::connect(addr).unwrap();
+    thread::sleep(time::Duration::from_millis(300));
+    assert!(net::TcpStream::connect(addr).is_err());
+
+    sys.stop();
+    let _ = h.join();
+}
+
+#[test]
+fn test_worker_limits() {
+    let addr = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let srv = Server::build()
+                .workers(1)
+                .maxconn(10)
+                .maxconnrate(5)
+                .disable_signals()
+                .bind("test", addr, move |_| {
+                    fn_service(|io: Io| async move {
+                        io.send(Bytes::from_static(b"test"), &BytesCodec)
+                            .await
+                            .unwrap();
+                        let _ = io.recv(&BytesCodec).await;
+                        Ok::<_, ()>(())
+                    })
+                })
+                .unwrap()
+                .run();
+            let _ = tx.send((srv, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (srv, sys) = rx.recv().unwrap();
+    thread::sleep(time::Duration::from_millis(300));
+
+    let mut buf = [0u8; 4];
+    let mut conn = net::TcpStream::connect(addr).unwrap();
+    let _ = conn.read_exact(&mut buf);
+    assert_eq!(buf, b"test"[..]);
+
+    let stats = ntex::rt::System::new("client").block_on(srv.worker_stats());
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].connections, 1);
+    assert_eq!(stats[0].max_connections, 10);
+    assert_eq!(stats[0].ssl_handshakes, 0);
+    assert_eq!(stats[0].max_ssl_handshakes, 5);
+
+    // worker is at capacity
+    ntex::rt::System::new("client").block_on(srv.set_maxconn(1));
+    thread::sleep(time::Duration::from_millis(100));
+    let mut conn2 = net::TcpStream::connect(addr).unwrap();
+    conn2
+        .set_read_timeout(Some(time::Duration::from_millis(300)))
+        .unwrap();
+    assert!(conn2.read_exact(&mut buf).is_err());
+
+    ntex::rt::System::new("client").block_on(srv.set_maxconn(2));
+    ntex::rt::System::new("client").block_on(srv.set_maxconnrate(1));
+    conn2
+        .set_read_timeout(Some(time::Duration::from_millis(1000)))
+        .unwrap();
+    let mut buf = [0u8; 4];
+    let _ = conn2.read_exact(&mut buf);
+    assert_eq!(buf, b"test"[..]);
+
+    let stats = ntex::rt::System::new("client").block_on(srv.worker_stats());
+    assert_eq!(stats[0].connections, 2);
+    assert_eq!(stats[0].max_connections, 2);
+    assert_eq!(stats[0].max_ssl_handshakes, 1);
+
+    sys.stop();
+    let _ = h.join();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_reuseport() {
+    let addr = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let srv = Server::build()
+                .workers(2)
+                .reuseport(true)
+                .disable_signals()
+                .bind("test", addr, move |_| {
+                    fn_service(|io: Io| async move {
+                        io.send(Bytes::from_static(b"test"), &BytesCodec)
+                            .await
+                            .unwrap();
+                        Ok::<_, ()>(())
+                    })
+                })
+                .unwrap()
+                .run();
+            let _ = tx.send((srv, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (srv, sys) = rx.recv().unwrap();
+    thread::sleep(time::Duration::from_millis(300));
+
+    for _ in 0..10 {
+        let mut buf = [0u8; 4];
+        let mut conn = net::TcpStream::connect(addr).unwrap();
+        let _ = conn.read_exact(&mut buf);
+        assert_eq!(buf, b"test"[..]);
+    }
+
+    // all accept loops get stopped
+    drop(srv.stop(false));
+    thread::sleep(time::Duration::from_millis(100));
+    assert!(net::TcpStream::connect(addr).is_err());
+
+    sys.stop();
+    let _ = h.join();
+}
+
+#[test]
+fn test_worker_name() {
+    let addr = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let srv = Server::build()
+                .workers(1)
+                .worker_name("test-worker")
+                .worker_affinity(vec![0])
+                .disable_signals()
+                .bind("test", addr, move |_| {
+                    fn_service(|io: Io| async move {
+                        let name = thread::current().name().unwrap().to_string();
+                        io.send(Bytes::from(name), &BytesCodec).await.unwrap();
+                        Ok::<_, ()>(())
+                    })
+                })
+                .unwrap()
+                .run();
+            let _ = tx.send((srv, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (_, sys) = rx.recv().unwrap();
+    thread::sleep(time::Duration::from_millis(300));
+
+    let mut buf = [0u8; 13];
+    let mut conn = net::TcpStream::connect(addr).unwrap();
+    let _ = conn.read_exact(&mut buf);
+    assert_eq!(buf, b"test-worker:0"[..]);
+
+    sys.stop();
+    let _ = h.join();
+}
+
+#[test]
+fn test_shutdown_handle() {
+    let addr = TestServer::unused_addr();
+    let (tx, rx) = mpsc::channel();
+    let finalized = Arc::new(AtomicUsize::new(0));
+    let finalized2 = finalized.clone();
+
+    let h = thread::spawn(move || {
+        let sys = ntex::rt::System::new("test");
+        sys.run(move || {
+            let handle = ntex::rt::System::current().shutdown_handle();
+            let srv = Server::build()
+                .workers(1)
+                .disable_signals()
+                .bind("test", addr, move |_| {
+                    fn_service(|_| Ready::Ok::<_, ()>(()))
+                })
+                .unwrap()
+                .run();
+            srv.register_shutdown(&handle);
+            handle.on_shutdown(ntex::rt::ShutdownPhase::Finalize, move || async move {
+                finalized2.fetch_add(1, Relaxed);
+            });
+            let _ = tx.send((handle, ntex::rt::System::current()));
+            Ok(())
+        })
+    });
+    let (handle, sys) = rx.recv().unwrap();
+
+    thread::sleep(time::Duration::from_millis(300));
+    assert!(net::TcpStream::connect(addr).is_ok());
+
+    sys.arbiter().exec_fn(move || {
+        ntex::rt::spawn(async move {
+            assert!(handle.shutdown().await);
+        });
+    });
+    let _ = h.join();
+    assert_eq!(finalized.load(Relaxed), 1);
+    assert!(net::TcpStream::connect(addr).is_err());
+}
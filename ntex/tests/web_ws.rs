@@ -1,10 +1,11 @@
 use std::io;
 
-use ntex::http::StatusCode;
+use ntex::http::{header, StatusCode};
 use ntex::service::{fn_factory_with_config, fn_service};
+use ntex::time::Seconds;
 use ntex::util::{ByteString, Bytes};
 use ntex::web::{self, test, ws, App, HttpRequest, HttpResponse};
-use ntex::ws::error::WsClientError;
+use ntex::ws::{error::WsClientError, WsClient};
 
 async fn service(msg: ws::Frame) -> Result<Option<ws::Message>, io::Error> {
     let msg = match msg {
@@ -66,6 +67,208 @@ async fn web_ws() {
     assert_eq!(item, ws::Frame::Close(Some(ws::CloseCode::Away.into())));
 }
 
+struct Echo;
+
+impl ws::WsHandler for Echo {
+    type Error = io::Error;
+
+    fn heartbeat_interval(&self) -> Seconds {
+        Seconds(1)
+    }
+
+    async fn on_message(
+        &self,
+        frame: ws::Frame,
+        _: &ws::WsSink,
+    ) -> Result<Option<ws::Message>, io::Error> {
+        Ok(match frame {
+            ws::Frame::Text(text) => Some(ws::Message::Text(
+                String::from_utf8_lossy(&text).as_ref().into(),
+            )),
+            ws::Frame::Binary(bin) => Some(ws::Message::Binary(bin)),
+            _ => None,
+        })
+    }
+}
+
+#[ntex::test]
+async fn web_ws_handler() {
+    let srv = test::server(|| {
+        App::new().service(web::resource("/").route(web::to(
+            |req: HttpRequest| async move {
+                ws::start_handler::<_, web::Error>(req, Echo).await
+            },
+        )))
+    });
+
+    let (io, codec, _) = srv.ws().await.unwrap().into_inner();
+    io.send(ws::Message::Text(ByteString::from_static("text")), &codec)
+        .await
+        .unwrap();
+    let item = io.recv(&codec).await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Text(Bytes::from_static(b"text")));
+
+    io.send(ws::Message::Ping("text".into()), &codec)
+        .await
+        .unwrap();
+    let item = io.recv(&codec).await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Pong("text".to_string().into()));
+
+    // heartbeat
+    let item = io.recv(&codec).await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Ping(Bytes::new()));
+
+    io.send(
+        ws::Message::Close(Some(ws::CloseCode::Normal.into())),
+        &codec,
+    )
+    .await
+    .unwrap();
+    let item = io.recv(&codec).await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Close(Some(ws::CloseCode::Normal.into())));
+}
+
+#[ntex::test]
+async fn web_ws_handler_timeout() {
+    struct Handler;
+
+    impl ws::WsHandler for Handler {
+        type Error = io::Error;
+
+        fn heartbeat_interval(&self) -> Seconds {
+            Seconds::ZERO
+        }
+
+        fn client_timeout(&self) -> Seconds {
+            Seconds(1)
+        }
+
+        async fn on_message(
+            &self,
+            _: ws::Frame,
+            _: &ws::WsSink,
+        ) -> Result<Option<ws::Message>, io::Error> {
+            Ok(None)
+        }
+    }
+
+    let srv = test::server(|| {
+        App::new().service(web::resource("/").route(web::to(
+            |req: HttpRequest| async move {
+                ws::start_handler::<_, web::Error>(req, Handler).await
+            },
+        )))
+    });
+
+    // peer is inactive, connection get closed
+    let (io, codec, _) = srv.ws().await.unwrap().into_inner();
+    assert!(matches!(io.recv(&codec).await, Ok(None) | Err(_)));
+}
+
+struct Upper;
+
+impl ws::WsHandler for Upper {
+    type Error = io::Error;
+
+    async fn on_message(
+        &self,
+        frame: ws::Frame,
+        _: &ws::WsSink,
+    ) -> Result<Option<ws::Message>, io::Error> {
+        Ok(match frame {
+            ws::Frame::Text(text) => Some(ws::Message::Text(
+                String::from_utf8_lossy(&text).to_uppercase().into(),
+            )),
+            _ => None,
+        })
+    }
+}
+
+#[ntex::test]
+async fn web_ws_router() {
+    let srv = test::server(|| {
+        let router = ws::WsRouter::new()
+            .protocol("echo.v1", Echo)
+            .protocol("upper.v1", Upper);
+        let router2 = router.clone().default_handler(Echo);
+
+        App::new()
+            .service(
+                web::resource("/")
+                    .to(move |req: HttpRequest| router.start::<web::Error>(req)),
+            )
+            .service(
+                web::resource("/default")
+                    .to(move |req: HttpRequest| router2.start::<web::Error>(req)),
+            )
+    });
+
+    let connect = |path: &'static str, protos: &'static [&'static str]| {
+        let url = srv.url(path);
+        let addr = srv.addr();
+        async move {
+            let mut builder = WsClient::build(url);
+            builder.address(addr).timeout(Seconds(30));
+            if !protos.is_empty() {
+                builder.protocols(protos);
+            }
+            builder.finish().unwrap().connect().await
+        }
+    };
+
+    // client preference order
+    let conn = connect("/", &["unknown", "upper.v1", "echo.v1"])
+        .await
+        .unwrap();
+    assert_eq!(
+        conn.response()
+            .headers()
+            .get(header::SEC_WEBSOCKET_PROTOCOL)
+            .unwrap(),
+        "upper.v1"
+    );
+    let (io, codec, _) = conn.into_inner();
+    io.send(ws::Message::Text(ByteString::from_static("text")), &codec)
+        .await
+        .unwrap();
+    let item = io.recv(&codec).await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Text(Bytes::from_static(b"TEXT")));
+
+    let conn = connect("/", &["echo.v1"]).await.unwrap();
+    let (io, codec, _) = conn.into_inner();
+    io.send(ws::Message::Text(ByteString::from_static("text")), &codec)
+        .await
+        .unwrap();
+    let item = io.recv(&codec).await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Text(Bytes::from_static(b"text")));
+
+    // unsupported sub-protocol
+    assert!(matches!(
+        connect("/", &["unknown"]).await.err().unwrap(),
+        WsClientError::InvalidResponseStatus(StatusCode::BAD_REQUEST)
+    ));
+    assert!(matches!(
+        connect("/", &[]).await.err().unwrap(),
+        WsClientError::InvalidResponseStatus(StatusCode::BAD_REQUEST)
+    ));
+
+    // default handler
+    let conn = connect("/default", &["unknown"]).await.unwrap();
+    assert!(conn
+        .response()
+        .headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .is_none());
+    let conn = connect("/default", &["upper.v1"]).await.unwrap();
+    assert_eq!(
+        conn.response()
+            .headers()
+            .get(header::SEC_WEBSOCKET_PROTOCOL)
+            .unwrap(),
+        "upper.v1"
+    );
+}
+
 #[ntex::test]
 async fn web_no_ws() {
     let srv = test::server(|| {